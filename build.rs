@@ -0,0 +1,18 @@
+//! Embed the application manifest into Windows executables.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=app.manifest");
+    let os = std::env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let env = std::env::var("CARGO_CFG_TARGET_ENV").unwrap_or_default();
+    // opt in to paths over MAX_PATH, where long paths are enabled in the system.
+    // only the MSVC linker merges manifests, GNU builds run without it
+    if os == "windows" && env == "msvc" {
+        let manifest = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("app.manifest");
+        println!("cargo:rustc-link-arg-bins=/MANIFEST:EMBED");
+        println!(
+            "cargo:rustc-link-arg-bins=/MANIFESTINPUT:{}",
+            manifest.display()
+        );
+    }
+}
@@ -1,11 +1,24 @@
 use std::io;
 
-use byteorder::{LE, ReadBytesExt, WriteBytesExt};
-
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use log::warn;
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    endian::Endian,
+    hirc::{HircLayout, HircObject},
+    layout::{self, ByteLen, DataOffset},
+    stmg::Stmg,
+};
+
 type Result<T> = std::result::Result<T, BnkError>;
 
+/// BKHD length covering version, bank ID, language ID, alt values and project ID.
+const BKHD_KNOWN_LENGTH: u32 = 20;
+/// BKHD length also covering bank type and hash, from version 145.
+const BKHD_HASH_LENGTH: u32 = 40;
+const BKHD_HASH_VERSION: u32 = 145;
+
 #[derive(Debug, thiserror::Error)]
 pub enum BnkError {
     #[error("IO error: {0}")]
@@ -13,11 +26,32 @@ pub enum BnkError {
 
     #[error("Accessing DATA section before DIDX section.")]
     MissingDidx,
+    #[error("DATA entry {0} overlaps with previous entry.")]
+    OverlappingData(u32),
+    #[error("Bank does not start with BKHD section.")]
+    MissingBkhd,
+    #[error("Bank name '{0}' in STID is longer than 255 bytes.")]
+    BankNameTooLong(String),
+    #[error("{section} section at offset {offset} has invalid length {length}.")]
+    InvalidSectionLength {
+        section: String,
+        offset: u64,
+        length: u32,
+    },
+    #[error("HIRC object at offset {offset} has invalid length {length}.")]
+    InvalidHircLength { offset: u64, length: u32 },
+    #[error("DATA entry {id} at offset {offset} runs past the end of file.")]
+    DataOutOfBounds { id: u32, offset: u64 },
+    #[error("DATA section does not fit in 4 GiB.")]
+    DataTooLarge,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Bnk {
     pub sections: Vec<Section>,
+    /// Byte order of the bank, written back in the same order.
+    #[serde(default, skip_serializing_if = "Endian::is_little")]
+    pub endian: Endian,
 }
 
 impl Bnk {
@@ -25,16 +59,17 @@ impl Bnk {
     where
         R: io::Read + io::Seek,
     {
+        let endian = detect_endian(reader)?;
         let mut sections = Vec::new();
         loop {
             let mut magic = [0u8; 4];
-            if let Err(e) = reader.read_exact(&mut magic) {
-                if e.kind() == io::ErrorKind::UnexpectedEof {
-                    break;
-                }
+            if let Err(e) = reader.read_exact(&mut magic)
+                && e.kind() == io::ErrorKind::UnexpectedEof
+            {
+                break;
             };
             let section = if &magic == b"DATA" {
-                let total_length = reader.read_u32::<LE>()?;
+                let total_length = endian.read_u32(reader)?;
                 let didx_entries = sections
                     .iter()
                     .find_map(|sec: &Section| {
@@ -46,10 +81,18 @@ impl Bnk {
                     })
                     .ok_or(BnkError::MissingDidx)?;
                 let data_start_pos = reader.stream_position()?;
+                let stream_length = stream_length(reader)?;
                 let mut data_list = Vec::with_capacity(didx_entries.len());
                 for entry in didx_entries {
-                    let mut data = vec![0; entry.length as usize];
-                    reader.seek(io::SeekFrom::Start(data_start_pos + entry.offset as u64))?;
+                    let position = entry.offset.file_position(data_start_pos);
+                    if position + entry.length.get() as u64 > stream_length {
+                        return Err(BnkError::DataOutOfBounds {
+                            id: entry.id,
+                            offset: position,
+                        });
+                    }
+                    let mut data = vec![0; entry.length.as_usize()];
+                    reader.seek(io::SeekFrom::Start(position))?;
                     reader.read_exact(&mut data)?;
                     data_list.push(data);
                 }
@@ -60,61 +103,296 @@ impl Bnk {
                     payload: SectionPayload::Data { data_list },
                 }
             } else {
-                Section::from_reader(reader, magic)?
+                Section::from_reader(reader, magic, endian)?
             };
             sections.push(section);
         }
-        Ok(Bnk { sections })
+        Ok(Bnk { sections, endian }.with_hirc_layout())
+    }
+
+    /// Read a possibly damaged bank, skipping what cannot be read with a warning.
+    ///
+    /// A section which fails to parse is skipped to its declared end, reading
+    /// stops at a section running past the end of file. DATA entries outside
+    /// the file are dropped with their DIDX entries, so both stay in step.
+    pub fn from_reader_lenient<R>(reader: &mut R) -> Result<Self>
+    where
+        R: io::Read + io::Seek,
+    {
+        let file_length = reader.seek(io::SeekFrom::End(0))?;
+        reader.seek(io::SeekFrom::Start(0))?;
+        let endian = detect_endian(reader)?;
+        let mut sections: Vec<Section> = Vec::new();
+        loop {
+            let start = reader.stream_position()?;
+            let mut magic = [0u8; 4];
+            if reader.read_exact(&mut magic).is_err() {
+                break;
+            }
+            let Ok(section_length) = endian.read_u32(reader) else {
+                warn!("Section at {} is truncated, skipped.", start);
+                break;
+            };
+            let name = String::from_utf8_lossy(&magic).to_string();
+            let end = start + 8 + section_length as u64;
+            if &magic == b"DATA" {
+                let Some(didx) = sections.iter_mut().find_map(|sec| match &mut sec.payload {
+                    SectionPayload::Didx { entries } => Some(entries),
+                    _ => None,
+                }) else {
+                    warn!("DATA section before DIDX section, skipped.");
+                    reader.seek(io::SeekFrom::Start(end))?;
+                    continue;
+                };
+                let data_start = start + 8;
+                let data_end = end.min(file_length);
+                let mut data_list = Vec::with_capacity(didx.len());
+                let mut kept = Vec::with_capacity(didx.len());
+                for (idx, entry) in didx.iter().enumerate() {
+                    let position = entry.offset.file_position(data_start);
+                    if position + entry.length.get() as u64 > data_end {
+                        warn!(
+                            "DATA entry [{}] {} is out of bounds, skipped.",
+                            idx, entry.id
+                        );
+                        continue;
+                    }
+                    let mut data = vec![0; entry.length.as_usize()];
+                    reader.seek(io::SeekFrom::Start(position))?;
+                    reader.read_exact(&mut data)?;
+                    data_list.push(data);
+                    kept.push(entry.clone());
+                }
+                if kept.len() < didx.len() {
+                    *didx = kept;
+                    let didx_section = sections
+                        .iter_mut()
+                        .find(|sec| &sec.magic == b"DIDX")
+                        .unwrap();
+                    didx_section.section_length = (data_list.len() * size_of::<DidxEntry>()) as u32;
+                }
+                sections.push(Section {
+                    magic,
+                    section_length,
+                    payload: SectionPayload::Data { data_list },
+                });
+                if end > file_length {
+                    warn!("DATA section runs past the end of file.");
+                    break;
+                }
+                reader.seek(io::SeekFrom::Start(end))?;
+                continue;
+            }
+            if end > file_length {
+                warn!(
+                    "{} section at {} runs past the end of file, skipped.",
+                    name, start
+                );
+                break;
+            }
+            reader.seek(io::SeekFrom::Start(start + 4))?;
+            match Section::from_reader(reader, magic, endian) {
+                Ok(section) => sections.push(section),
+                Err(e) => warn!("{} section at {} is damaged, skipped: {}", name, start, e),
+            }
+            reader.seek(io::SeekFrom::Start(end))?;
+        }
+        Ok(Bnk { sections, endian }.with_hirc_layout())
+    }
+
+    /// Version of the bank in BKHD.
+    pub fn version(&self) -> Option<u32> {
+        self.sections
+            .iter()
+            .find_map(|section| match &section.payload {
+                SectionPayload::Bkhd { version, .. } => Some(*version),
+                _ => None,
+            })
+    }
+
+    /// Set the layout of HIRC objects by the bank version.
+    fn with_hirc_layout(mut self) -> Self {
+        let layout = self
+            .version()
+            .map_or(HircLayout::default(), HircLayout::for_version);
+        for section in &mut self.sections {
+            if let SectionPayload::Hirc { entries } = &mut section.payload {
+                for entry in entries {
+                    entry.layout = layout;
+                }
+            }
+        }
+        self
+    }
+
+    /// Read only the BKHD section at the start of a bank.
+    pub fn read_header<R>(reader: &mut R) -> Result<Section>
+    where
+        R: io::Read + io::Seek,
+    {
+        let endian = detect_endian(reader)?;
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != b"BKHD" {
+            return Err(BnkError::MissingBkhd);
+        }
+        Section::from_reader(reader, magic, endian)
+    }
+
+    /// Names of banks in the STID section, the bank itself and banks it references.
+    pub fn bank_names(&self) -> &[StidEntry] {
+        self.sections
+            .iter()
+            .find_map(|section| match &section.payload {
+                SectionPayload::Stid { entries, .. } => Some(entries.as_slice()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// Read only the STID section of a bank, empty if it has none.
+    pub fn read_bank_names<R>(reader: &mut R) -> Result<Vec<StidEntry>>
+    where
+        R: io::Read + io::Seek,
+    {
+        let endian = detect_endian(reader)?;
+        let mut magic = [0u8; 4];
+        loop {
+            if let Err(e) = reader.read_exact(&mut magic) {
+                if e.kind() == io::ErrorKind::UnexpectedEof {
+                    return Ok(vec![]);
+                }
+                return Err(e.into());
+            }
+            if &magic == b"STID" {
+                let section = Section::from_reader(reader, magic, endian)?;
+                return Ok(match section.payload {
+                    SectionPayload::Stid { entries, .. } => entries,
+                    _ => vec![],
+                });
+            }
+            let section_length = endian.read_u32(reader)?;
+            reader.seek(io::SeekFrom::Current(section_length as i64))?;
+        }
+    }
+
+    /// Position of DATA section payload in a bank, `None` if the bank
+    /// has no DATA section, e.g. a bank of streamed or prefetched media only.
+    pub fn data_position<R>(reader: &mut R) -> Result<Option<u64>>
+    where
+        R: io::Read + io::Seek,
+    {
+        let endian = detect_endian(reader)?;
+        let length = stream_length(reader)?;
+        let mut magic = [0u8; 4];
+        while reader.stream_position()? + 8 <= length {
+            reader.read_exact(&mut magic)?;
+            let section_length = endian.read_u32(reader)?;
+            if &magic == b"DATA" {
+                return Ok(Some(reader.stream_position()?));
+            }
+            reader.seek(io::SeekFrom::Current(section_length as i64))?;
+        }
+        Ok(None)
     }
 
     pub fn write_to<W>(&self, writer: &mut W) -> Result<()>
     where
         W: io::Write + io::Seek,
     {
+        let endian = self.endian;
         let mut didx_entries: Option<&[DidxEntry]> = None;
 
         for section in &self.sections {
             writer.write_all(&section.magic)?;
-            writer.write_u32::<LE>(section.section_length)?;
+            // HIRC, STID and STMG lengths follow their entries, which may be edited in bank.json
+            let section_length = match &section.payload {
+                SectionPayload::Hirc { entries } => {
+                    4 + entries.iter().map(|e| 5 + e.length).sum::<u32>()
+                }
+                SectionPayload::Stid { entries, .. } => {
+                    8 + entries.iter().map(|e| 5 + e.name.len() as u32).sum::<u32>()
+                }
+                SectionPayload::Stmg(stmg) => stmg.byte_len(),
+                _ => section.section_length,
+            };
+            endian.write_u32(writer, section_length)?;
 
             match &section.payload {
                 SectionPayload::Bkhd {
                     version,
                     id,
+                    language_id,
+                    alt_values,
+                    project_id,
+                    bank_type,
+                    bank_hash,
                     unknown,
                 } => {
-                    writer.write_u32::<LE>(*version)?;
-                    writer.write_u32::<LE>(*id)?;
+                    endian.write_u32(writer, *version)?;
+                    endian.write_u32(writer, *id)?;
+                    for value in [language_id, alt_values, project_id, bank_type]
+                        .into_iter()
+                        .flatten()
+                    {
+                        endian.write_u32(writer, *value)?;
+                    }
+                    if let Some(bank_hash) = bank_hash {
+                        writer.write_all(bank_hash)?;
+                    }
                     writer.write_all(unknown)?;
                 }
                 SectionPayload::Didx { entries } => {
                     didx_entries.replace(entries);
                     for entry in entries {
-                        let entry_bytes: [u8; 12] = unsafe { std::mem::transmute(entry.clone()) };
-                        writer.write_all(&entry_bytes)?;
+                        entry.write_to(writer, endian)?;
                     }
                 }
                 SectionPayload::Hirc { entries } => {
-                    writer.write_u32::<LE>(entries.len() as u32)?;
+                    endian.write_u32(writer, entries.len() as u32)?;
                     for entry in entries {
-                        entry.write_to(writer)?;
+                        entry.write_to(writer, endian)?;
                     }
                 }
+                SectionPayload::Stid {
+                    string_type,
+                    entries,
+                } => {
+                    endian.write_u32(writer, *string_type)?;
+                    endian.write_u32(writer, entries.len() as u32)?;
+                    for entry in entries {
+                        let name_length = u8::try_from(entry.name.len())
+                            .map_err(|_| BnkError::BankNameTooLong(entry.name.clone()))?;
+                        endian.write_u32(writer, entry.id)?;
+                        writer.write_u8(name_length)?;
+                        writer.write_all(entry.name.as_bytes())?;
+                    }
+                }
+                SectionPayload::Stmg(stmg) => {
+                    stmg.write_to(writer)?;
+                }
                 SectionPayload::Data { data_list } => {
                     let Some(didx_entries) = didx_entries else {
                         return Err(BnkError::MissingDidx);
                     };
-                    let data_start_pos = writer.stream_position()?;
-                    for (i, data) in data_list.iter().enumerate() {
-                        let entry = &didx_entries[i];
-                        writer.seek(io::SeekFrom::Start(data_start_pos + entry.offset as u64))?;
+                    // padding is written explicitly, so the section is complete
+                    // even if it is the last one
+                    let mut written = DataOffset::ZERO;
+                    for (entry, data) in didx_entries.iter().zip(data_list) {
+                        let padding = entry
+                            .offset
+                            .distance_from(written)
+                            .ok_or(BnkError::OverlappingData(entry.id))?;
+                        layout::write_padding(writer, padding)?;
                         writer.write_all(data)?;
-                        // 16字节对齐 padding
+                        written = entry
+                            .offset
+                            .checked_add(ByteLen::of(data))
+                            .ok_or(BnkError::DataTooLarge)?;
                     }
-                    // 移动到padding末尾
-                    writer.seek(io::SeekFrom::Start(
-                        data_start_pos + section.section_length as u64,
-                    ))?;
+                    let section_end = DataOffset(section.section_length);
+                    let padding = section_end.distance_from(written).unwrap_or_default();
+                    layout::write_padding(writer, padding)?;
                 }
                 SectionPayload::Unk { data } => {
                     writer.write_all(data)?;
@@ -134,68 +412,100 @@ pub struct Section {
 }
 
 impl Section {
-    pub fn new(payload: SectionPayload) -> Self {
-        match &payload {
-            SectionPayload::Didx { entries } => Section {
-                magic: *b"DIDX",
-                section_length: entries.len() as u32 * size_of::<DidxEntry>() as u32,
-                payload,
-            },
-            SectionPayload::Data { data_list } => {
-                let mut total_length = 0;
-                for data in data_list {
-                    total_length += data.len();
-                }
-                Section {
-                    magic: *b"DATA",
-                    section_length: total_length as u32,
-                    payload,
-                }
-            }
-            _ => unimplemented!("Section::new for payload: {:#?}", payload),
-        }
-    }
-
-    fn from_reader<R>(reader: &mut R, magic: [u8; 4]) -> Result<Self>
+    fn from_reader<R>(reader: &mut R, magic: [u8; 4], endian: Endian) -> Result<Self>
     where
         R: io::Read + io::Seek,
     {
-        let section_length = reader.read_u32::<LE>()?;
+        let section_length = endian.read_u32(reader)?;
+        let start = reader.stream_position()?;
+        let end = start + section_length as u64;
+        let invalid_length = || BnkError::InvalidSectionLength {
+            section: String::from_utf8_lossy(&magic).to_string(),
+            offset: start - 8,
+            length: section_length,
+        };
+        if end > stream_length(reader)? {
+            return Err(invalid_length());
+        }
         let payload = match &magic {
-            b"BKHD" => SectionPayload::Bkhd {
-                version: reader.read_u32::<LE>()?,
-                id: reader.read_u32::<LE>()?,
-                unknown: {
-                    let mut unknown = vec![0; section_length as usize - 8];
-                    reader.read_exact(&mut unknown)?;
-                    unknown
-                },
-            },
+            b"BKHD" => {
+                // version and bank ID
+                if section_length < 8 {
+                    return Err(invalid_length());
+                }
+                let version = endian.read_u32(reader)?;
+                let id = endian.read_u32(reader)?;
+                // too short in very old banks
+                let mut known = [None; 3];
+                if section_length >= BKHD_KNOWN_LENGTH {
+                    for value in &mut known {
+                        *value = Some(endian.read_u32(reader)?);
+                    }
+                }
+                let [language_id, alt_values, project_id] = known;
+                let mut known_length = 8 + 4 * known.iter().flatten().count();
+                // fields after project ID differ between versions
+                let (mut bank_type, mut bank_hash) = (None, None);
+                if version >= BKHD_HASH_VERSION && section_length >= BKHD_HASH_LENGTH {
+                    bank_type = Some(endian.read_u32(reader)?);
+                    let mut hash = [0; 16];
+                    reader.read_exact(&mut hash)?;
+                    bank_hash = Some(hash);
+                    known_length = BKHD_HASH_LENGTH as usize;
+                }
+                let mut unknown = vec![0; section_length as usize - known_length];
+                reader.read_exact(&mut unknown)?;
+                SectionPayload::Bkhd {
+                    version,
+                    id,
+                    language_id,
+                    alt_values,
+                    project_id,
+                    bank_type,
+                    bank_hash,
+                    unknown,
+                }
+            }
             b"DIDX" => {
                 let entry_count = (section_length as usize) / size_of::<DidxEntry>();
                 let mut entries = Vec::with_capacity(entry_count);
                 for _ in 0..entry_count {
-                    let mut buf = [0; size_of::<DidxEntry>()];
-                    reader.read_exact(&mut buf)?;
-                    entries.push(unsafe { std::mem::transmute::<[u8; 12], DidxEntry>(buf) });
+                    entries.push(DidxEntry::from_reader(reader, endian)?);
                 }
                 SectionPayload::Didx { entries }
             }
-            b"HIRC" => {
-                let count = reader.read_u32::<LE>()?;
-                let mut entries = Vec::with_capacity(count as usize);
+            // typed objects are little endian, kept raw in big endian banks
+            b"HIRC" if endian.is_little() => {
+                let count = endian.read_u32(reader)?;
+                // smallest object is type, length and ID
+                let mut entries =
+                    Vec::with_capacity((count as usize).min(section_length as usize / 9));
                 for _ in 0..count {
                     let entry_type = reader.read_u8()?;
                     // let entry_type = HircEntryType::from_repr(entry_type).ok_or(
                     //     Error::UnknownHircEntryType(reader.stream_position()?, entry_type),
                     // )?;
-                    entries.push(HircEntry::from_reader(reader, entry_type)?);
+                    entries.push(HircEntry::from_reader(reader, entry_type, end, endian)?);
                 }
                 SectionPayload::Hirc { entries }
             }
             b"DATA" => {
                 unreachable!("DATA section should be handled separately.");
             }
+            b"STID" => {
+                let mut data = vec![0; section_length as usize];
+                reader.read_exact(&mut data)?;
+                // kept as is if it does not parse exactly, to write it back unchanged
+                parse_stid(&data, endian).unwrap_or(SectionPayload::Unk { data })
+            }
+            b"STMG" if endian.is_little() => {
+                let mut data = vec![0; section_length as usize];
+                reader.read_exact(&mut data)?;
+                match Stmg::parse(&data) {
+                    Some(stmg) => SectionPayload::Stmg(stmg),
+                    None => SectionPayload::Unk { data },
+                }
+            }
             _ => {
                 let mut data = vec![0; section_length as usize];
                 reader.read_exact(&mut data)?;
@@ -217,6 +527,24 @@ pub enum SectionPayload {
     Bkhd {
         version: u32,
         id: u32,
+        /// Short ID of bank language, hash of `sfx` for language independent banks.
+        /// Missing in old `bank.json`, where it is kept in `unknown`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        language_id: Option<u32>,
+        /// Alignment of media in the low 16 bits, device allocated flag in the high 16 bits.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        alt_values: Option<u32>,
+        /// ID of the Wwise project the bank is generated from.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        project_id: Option<u32>,
+        /// 0 for user banks, 1 for the init bank. From version 145, missing
+        /// in old `bank.json` like the IDs above.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bank_type: Option<u32>,
+        /// Hash of bank contents computed by Wwise, not checked by the game.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bank_hash: Option<[u8; 16]>,
+        /// Padding and fields of other versions.
         unknown: Vec<u8>,
     },
     Didx {
@@ -228,26 +556,143 @@ pub enum SectionPayload {
     Data {
         data_list: Vec<Vec<u8>>,
     },
+    /// Bank names by ID.
+    Stid {
+        /// Always 1, for UTF-8 names.
+        string_type: u32,
+        entries: Vec<StidEntry>,
+    },
+    /// State groups, switch groups and game parameters of init and music banks.
+    Stmg(Stmg),
     Unk {
         data: Vec<u8>,
     },
 }
 
+/// Name of a bank in the STID section.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StidEntry {
+    pub id: u32,
+    /// Written as `bank_name`, apart from `name` annotations, which are not
+    /// edits, see [`crate::names::BANK_NAME_KEY`].
+    #[serde(rename = "bank_name", alias = "name")]
+    pub name: String,
+}
+
+/// Parse a STID section payload, `None` unless it is consumed exactly.
+fn parse_stid(data: &[u8], endian: Endian) -> Option<SectionPayload> {
+    let mut reader = io::Cursor::new(data);
+    let string_type = endian.read_u32(&mut reader).ok()?;
+    let count = endian.read_u32(&mut reader).ok()?;
+    let mut entries = vec![];
+    for _ in 0..count {
+        let id = endian.read_u32(&mut reader).ok()?;
+        let name_length = reader.read_u8().ok()?;
+        let mut name = vec![0; name_length as usize];
+        io::Read::read_exact(&mut reader, &mut name).ok()?;
+        entries.push(StidEntry {
+            id,
+            name: String::from_utf8(name).ok()?,
+        });
+    }
+    (reader.position() == data.len() as u64).then_some(SectionPayload::Stid {
+        string_type,
+        entries,
+    })
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "HircEntryRepr", try_from = "HircEntryRepr")]
 pub struct HircEntry {
     pub type_id: u8,
     pub length: u32,
     pub id: u32,
     pub data: Vec<u8>,
+    /// Layout of object data, by the bank version.
+    pub layout: HircLayout,
+}
+
+/// HIRC entry in `bank.json`, typed object if supported, raw data otherwise.
+///
+/// Entry length is derived from data, so edited objects can change size.
+/// It is still written for readers of the format, and ignored when read.
+#[derive(Serialize, Deserialize)]
+struct HircEntryRepr {
+    type_id: u8,
+    #[serde(default)]
+    length: u32,
+    id: u32,
+    #[serde(default, skip_serializing_if = "HircLayout::is_latest")]
+    layout: HircLayout,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    object: Option<HircObject>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<Vec<u8>>,
+}
+
+impl From<HircEntry> for HircEntryRepr {
+    fn from(entry: HircEntry) -> Self {
+        match HircObject::parse(entry.type_id, &entry.data, entry.layout) {
+            Some(object) => Self {
+                type_id: entry.type_id,
+                length: entry.length,
+                id: entry.id,
+                layout: entry.layout,
+                object: Some(object),
+                data: None,
+            },
+            None => Self {
+                type_id: entry.type_id,
+                length: entry.length,
+                id: entry.id,
+                layout: entry.layout,
+                object: None,
+                data: Some(entry.data),
+            },
+        }
+    }
+}
+
+impl TryFrom<HircEntryRepr> for HircEntry {
+    type Error = String;
+
+    fn try_from(repr: HircEntryRepr) -> std::result::Result<Self, Self::Error> {
+        let data = match (repr.object, repr.data) {
+            (Some(object), _) if object.type_id() != repr.type_id => {
+                return Err(format!(
+                    "HIRC object {} of type_id {} has object of type_id {}",
+                    repr.id,
+                    repr.type_id,
+                    object.type_id()
+                ));
+            }
+            (Some(object), _) => object.to_bytes(repr.layout),
+            (None, Some(data)) => data,
+            (None, None) => return Err(format!("HIRC object {} has no object or data", repr.id)),
+        };
+        Ok(Self {
+            type_id: repr.type_id,
+            length: data.len() as u32 + 4,
+            id: repr.id,
+            data,
+            layout: repr.layout,
+        })
+    }
 }
 
 impl HircEntry {
-    fn from_reader<R>(reader: &mut R, type_id: u8) -> Result<Self>
+    /// Read an object after its type, within a HIRC section ending at `section_end`.
+    fn from_reader<R>(reader: &mut R, type_id: u8, section_end: u64, endian: Endian) -> Result<Self>
     where
         R: io::Read + io::Seek,
     {
-        let length = reader.read_u32::<LE>()?;
-        let id = reader.read_u32::<LE>()?;
+        let offset = reader.stream_position()? - 1;
+        let length = endian.read_u32(reader)?;
+        // length counts the ID and data after it
+        if length < 4 || offset + 5 + length as u64 > section_end {
+            return Err(BnkError::InvalidHircLength { offset, length });
+        }
+        let id = endian.read_u32(reader)?;
         let mut data = vec![0; length as usize - 4];
         reader.read_exact(&mut data)?;
         Ok(HircEntry {
@@ -255,27 +700,164 @@ impl HircEntry {
             length,
             id,
             data,
+            // set from BKHD once the bank is read
+            layout: HircLayout::default(),
         })
     }
 
-    fn write_to<W>(&self, writer: &mut W) -> Result<()>
+    fn write_to<W>(&self, writer: &mut W, endian: Endian) -> Result<()>
     where
         W: io::Write,
     {
         writer.write_u8(self.type_id)?;
-        writer.write_u32::<LE>(self.length)?;
-        writer.write_u32::<LE>(self.id)?;
+        endian.write_u32(writer, self.length)?;
+        endian.write_u32(writer, self.id)?;
         writer.write_all(&self.data)?;
         Ok(())
     }
 }
 
-#[repr(C)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DidxEntry {
     pub id: u32,
-    pub offset: u32,
-    pub length: u32,
+    pub offset: DataOffset,
+    pub length: ByteLen,
+}
+
+impl DidxEntry {
+    fn from_reader<R: io::Read>(reader: &mut R, endian: Endian) -> io::Result<Self> {
+        Ok(Self {
+            id: endian.read_u32(reader)?,
+            offset: DataOffset(endian.read_u32(reader)?),
+            length: ByteLen(endian.read_u32(reader)?),
+        })
+    }
+
+    fn write_to<W: io::Write>(&self, writer: &mut W, endian: Endian) -> io::Result<()> {
+        endian.write_u32(writer, self.id)?;
+        endian.write_u32(writer, self.offset.get())?;
+        endian.write_u32(writer, self.length.get())
+    }
+}
+
+/// Byte order of a bank by the version in its BKHD section, keeping the
+/// stream position.
+fn detect_endian<R>(reader: &mut R) -> io::Result<Endian>
+where
+    R: io::Read + io::Seek,
+{
+    Endian::detect_at(reader, 8)
+}
+
+/// Length of a stream, keeping its position.
+fn stream_length<S: io::Seek>(stream: &mut S) -> io::Result<u64> {
+    let position = stream.stream_position()?;
+    let length = stream.seek(io::SeekFrom::End(0))?;
+    stream.seek(io::SeekFrom::Start(position))?;
+    Ok(length)
+}
+
+/// Alignment policy of wem data in DATA section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataAlignment {
+    /// Alignment of each entry offset, 1 for no padding.
+    pub entry: u32,
+    /// Pad the end of section to entry alignment as well.
+    pub pad_end: bool,
+}
+
+impl DataAlignment {
+    /// Layout written by Wwise, entries aligned to 16 bytes without padding after the last one.
+    pub const WWISE: Self = Self {
+        entry: 16,
+        pad_end: false,
+    };
+
+    /// Alignment reproducing the layout of existing DIDX entries and DATA
+    /// section length, `None` if no power of two up to 4096 does.
+    ///
+    /// Wwise layout is preferred when several alignments match, e.g. for
+    /// entries whose lengths are all multiples of 16.
+    pub fn detect(entries: &[DidxEntry], data_length: ByteLen) -> Option<Self> {
+        let mut candidates = std::iter::once(Self::WWISE.entry)
+            .chain((0..=12).map(|shift| 1 << shift))
+            .flat_map(|entry| [false, true].map(|pad_end| Self { entry, pad_end }));
+        candidates.find(|&alignment| {
+            BnkLayout::plan(entries.iter().map(|e| (e.id, e.length)), alignment).is_ok_and(
+                |layout| {
+                    layout.data_length == data_length
+                        && layout
+                            .entries
+                            .iter()
+                            .zip(entries)
+                            .all(|(planned, original)| planned.offset == original.offset)
+                },
+            )
+        })
+    }
+}
+
+impl Default for DataAlignment {
+    fn default() -> Self {
+        Self::WWISE
+    }
+}
+
+/// Planned DIDX entries and DATA section length.
+///
+/// All layout computation goes through this, so entry offsets and
+/// section lengths always agree with each other.
+#[derive(Debug, Clone)]
+pub struct BnkLayout {
+    pub entries: Vec<DidxEntry>,
+    pub data_length: ByteLen,
+}
+
+impl BnkLayout {
+    /// Plan the layout of `(id, length)` items in order.
+    pub fn plan(
+        items: impl IntoIterator<Item = (u32, ByteLen)>,
+        alignment: DataAlignment,
+    ) -> Result<Self> {
+        let (ids, lengths): (Vec<u32>, Vec<ByteLen>) = items.into_iter().unzip();
+        let (offsets, mut end) = layout::place_blocks(
+            DataOffset::ZERO,
+            lengths.iter().map(|&length| (length, alignment.entry)),
+        )
+        .ok_or(BnkError::DataTooLarge)?;
+        if alignment.pad_end {
+            end = end
+                .align_up(alignment.entry)
+                .ok_or(BnkError::DataTooLarge)?;
+        }
+        let entries = ids
+            .into_iter()
+            .zip(offsets)
+            .zip(lengths)
+            .map(|((id, offset), length)| DidxEntry { id, offset, length })
+            .collect();
+        Ok(Self {
+            entries,
+            data_length: ByteLen(end.get()),
+        })
+    }
+
+    /// Build DIDX and DATA sections of the wem data with this layout.
+    pub fn into_sections(self, data_list: Vec<Vec<u8>>) -> (Section, Section) {
+        let didx = Section {
+            magic: *b"DIDX",
+            section_length: (self.entries.len() * size_of::<DidxEntry>()) as u32,
+            payload: SectionPayload::Didx {
+                entries: self.entries,
+            },
+        };
+        let data = Section {
+            magic: *b"DATA",
+            section_length: self.data_length.get(),
+            payload: SectionPayload::Data { data_list },
+        };
+        (didx, data)
+    }
 }
 
 #[cfg(test)]
@@ -283,11 +865,318 @@ mod tests {
     use std::{fs, io};
 
     use super::*;
+    use crate::utils;
 
     const INPUT_HIRC: &str = "test_files/Wp00_Cmn.sbnk.1.X64";
     const INPUT_HIRC_2: &str = "test_files/Wp00_Cmn_Effect.sbnk.1.X64";
     const INPUT_DIDX_DATA: &str = "test_files/Wp00_Cmn_m.sbnk.1.X64";
 
+    #[test]
+    fn test_layout_matches_original() {
+        let input = fs::read(INPUT_DIDX_DATA).unwrap();
+        let bank = Bnk::from_reader(&mut io::Cursor::new(&input)).unwrap();
+        let (didx_section, data_section) = {
+            let mut sections = bank.sections.iter();
+            let didx = sections.find(|sec| &sec.magic == b"DIDX").unwrap();
+            let data = sections.find(|sec| &sec.magic == b"DATA").unwrap();
+            (didx, data)
+        };
+        let SectionPayload::Didx { entries } = &didx_section.payload else {
+            unreachable!()
+        };
+
+        let layout = BnkLayout::plan(
+            entries.iter().map(|e| (e.id, e.length)),
+            DataAlignment::WWISE,
+        )
+        .unwrap();
+        assert_eq!(layout.data_length.get(), data_section.section_length);
+        for (planned, original) in layout.entries.iter().zip(entries) {
+            assert_eq!(planned.offset, original.offset);
+        }
+
+        let padded = BnkLayout::plan(
+            [(1, ByteLen(10)), (2, ByteLen(10))],
+            DataAlignment {
+                entry: 16,
+                pad_end: true,
+            },
+        )
+        .unwrap();
+        assert_eq!(padded.entries[1].offset, DataOffset(16));
+        assert_eq!(padded.data_length, ByteLen(32));
+
+        // alignment of the source is detected from its layout
+        assert_eq!(
+            DataAlignment::detect(entries, ByteLen(data_section.section_length)),
+            Some(DataAlignment::WWISE)
+        );
+        assert_eq!(
+            DataAlignment::detect(&padded.entries, padded.data_length),
+            Some(DataAlignment {
+                entry: 16,
+                pad_end: true,
+            })
+        );
+        let packed = BnkLayout::plan(
+            [(1, ByteLen(10)), (2, ByteLen(5)), (3, ByteLen(3))],
+            DataAlignment {
+                entry: 2,
+                pad_end: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            DataAlignment::detect(&packed.entries, packed.data_length),
+            Some(DataAlignment {
+                entry: 2,
+                pad_end: false,
+            })
+        );
+        let mut irregular = packed.entries.clone();
+        irregular[1].offset = DataOffset(11);
+        assert_eq!(DataAlignment::detect(&irregular, packed.data_length), None);
+
+        // rebuilt sections are written byte identical
+        let SectionPayload::Data { data_list } = &data_section.payload else {
+            unreachable!()
+        };
+        let (didx, data) = layout.into_sections(data_list.clone());
+        let mut rebuilt = bank.clone();
+        rebuilt
+            .sections
+            .retain(|sec| &sec.magic != b"DIDX" && &sec.magic != b"DATA");
+        rebuilt.sections.insert(1, didx);
+        rebuilt.sections.insert(2, data);
+        let mut output = io::Cursor::new(vec![]);
+        rebuilt.write_to(&mut output).unwrap();
+        assert_eq!(output.into_inner(), input);
+    }
+
+    #[test]
+    fn test_lenient() {
+        let input = fs::read(INPUT_DIDX_DATA).unwrap();
+        let bank = Bnk::from_reader(&mut io::Cursor::new(&input)).unwrap();
+        let entries = |bank: &Bnk| {
+            bank.sections
+                .iter()
+                .find_map(|sec| match &sec.payload {
+                    SectionPayload::Didx { entries } => Some(entries.clone()),
+                    _ => None,
+                })
+                .unwrap()
+        };
+        let count = entries(&bank).len();
+        assert_eq!(
+            Bnk::from_reader_lenient(&mut io::Cursor::new(&input))
+                .unwrap()
+                .sections
+                .len(),
+            bank.sections.len()
+        );
+
+        // cut in the last entry of DATA
+        let truncated = &input[..input.len() - 100];
+        assert!(Bnk::from_reader(&mut io::Cursor::new(truncated)).is_err());
+        let salvaged = Bnk::from_reader_lenient(&mut io::Cursor::new(truncated)).unwrap();
+        let kept = entries(&salvaged);
+        assert_eq!(kept.len(), count - 1);
+        let SectionPayload::Data { data_list } = &salvaged
+            .sections
+            .iter()
+            .find(|sec| &sec.magic == b"DATA")
+            .unwrap()
+            .payload
+        else {
+            unreachable!()
+        };
+        assert_eq!(data_list.len(), kept.len());
+    }
+
+    #[test]
+    fn test_malformed() {
+        let section = |magic: &[u8; 4], payload: &[u8]| {
+            [&magic[..], &(payload.len() as u32).to_le_bytes(), payload].concat()
+        };
+        let bkhd = section(b"BKHD", &[0; 8]);
+        let read = |input: Vec<u8>| Bnk::from_reader(&mut io::Cursor::new(input)).unwrap_err();
+
+        // object length not covering its ID
+        let hirc = section(b"HIRC", &[&1u32.to_le_bytes()[..], &[2], &[0; 8]].concat());
+        let err = read([bkhd.clone(), hirc].concat());
+        assert!(matches!(
+            err,
+            BnkError::InvalidHircLength {
+                offset: 28,
+                length: 0
+            }
+        ));
+        assert!(err.to_string().contains("offset 28"));
+
+        // object running past its section
+        let hirc = section(
+            b"HIRC",
+            &[
+                &1u32.to_le_bytes()[..],
+                &[2],
+                &100u32.to_le_bytes(),
+                &[0; 4],
+            ]
+            .concat(),
+        );
+        assert!(matches!(
+            read([bkhd.clone(), hirc].concat()),
+            BnkError::InvalidHircLength { length: 100, .. }
+        ));
+
+        // header too short for its fields, section past the end of file
+        assert!(matches!(
+            read(section(b"BKHD", &[0; 4])),
+            BnkError::InvalidSectionLength { length: 4, .. }
+        ));
+        let mut input = [bkhd.clone(), section(b"STMG", &[0; 16])].concat();
+        input.truncate(input.len() - 1);
+        assert!(matches!(
+            read(input),
+            BnkError::InvalidSectionLength {
+                offset: 16,
+                length: 16,
+                ..
+            }
+        ));
+
+        // wem data past the end of file
+        let didx = section(
+            b"DIDX",
+            &[1u32, 0, u32::MAX]
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect::<Vec<u8>>(),
+        );
+        let data = section(b"DATA", &[0; 16]);
+        assert!(matches!(
+            read([bkhd, didx, data].concat()),
+            BnkError::DataOutOfBounds { id: 1, offset: 44 }
+        ));
+    }
+
+    #[test]
+    fn test_bkhd_ids() {
+        let input = fs::read(INPUT_DIDX_DATA).unwrap();
+        let header = Bnk::read_header(&mut io::Cursor::new(&input)).unwrap();
+        let SectionPayload::Bkhd {
+            language_id,
+            project_id,
+            bank_type,
+            bank_hash: Some(bank_hash),
+            unknown,
+            ..
+        } = &header.payload
+        else {
+            unreachable!()
+        };
+        assert_eq!(*language_id, Some(utils::fnv1_32("sfx")));
+        assert_eq!(*project_id, Some(7159));
+        assert_eq!(*bank_type, Some(0));
+        assert_eq!(bank_hash.as_slice(), &input[32..48]);
+        assert_eq!(
+            unknown.len(),
+            header.section_length as usize - BKHD_HASH_LENGTH as usize
+        );
+
+        // bank.json of old versions keeps the IDs in unknown
+        let mut json = serde_json::to_value(&header).unwrap();
+        let content = json["content"].as_object_mut().unwrap();
+        for field in [
+            "language_id",
+            "alt_values",
+            "project_id",
+            "bank_type",
+            "bank_hash",
+        ] {
+            content.remove(field);
+        }
+        let ids = input[16..48].iter().map(|b| serde_json::json!(b));
+        let old_unknown: Vec<_> = ids
+            .chain(unknown.iter().map(|b| serde_json::json!(b)))
+            .collect();
+        content.insert("unknown".to_string(), old_unknown.into());
+        let old: Section = serde_json::from_value(json).unwrap();
+        let bank = Bnk {
+            sections: vec![old],
+            endian: Endian::Little,
+        };
+        let mut output = io::Cursor::new(vec![]);
+        bank.write_to(&mut output).unwrap();
+        assert_eq!(
+            output.into_inner(),
+            input[..8 + header.section_length as usize]
+        );
+    }
+
+    #[test]
+    fn test_stid() {
+        let section = |magic: &[u8; 4], body: &[u8]| {
+            [magic, &(body.len() as u32).to_le_bytes()[..], body].concat()
+        };
+        let stid = [
+            &[1, 0, 0, 0, 2, 0, 0, 0][..],
+            &[0xE9, 0x03, 0, 0, 4],
+            b"Test",
+            &[0xEA, 0x03, 0, 0, 3],
+            b"Cmn",
+        ]
+        .concat();
+        let input = [
+            section(b"BKHD", &[0; 8]),
+            section(b"STID", &stid),
+            section(b"ENVS", &[7; 3]),
+        ]
+        .concat();
+        let bank = Bnk::from_reader(&mut io::Cursor::new(&input)).unwrap();
+        let names = [
+            StidEntry {
+                id: 1001,
+                name: "Test".to_string(),
+            },
+            StidEntry {
+                id: 1002,
+                name: "Cmn".to_string(),
+            },
+        ];
+        assert_eq!(bank.bank_names(), names);
+        assert_eq!(
+            Bnk::read_bank_names(&mut io::Cursor::new(&input)).unwrap(),
+            names
+        );
+
+        // renamed in bank.json
+        let mut json = serde_json::to_value(&bank).unwrap();
+        json["sections"][1]["content"]["entries"][1]["bank_name"] = "Common".into();
+        let renamed: Bnk = serde_json::from_value(json.clone()).unwrap();
+        // and in bank.json of earlier versions
+        let entry = &mut json["sections"][1]["content"]["entries"][1];
+        *entry = serde_json::json!({ "id": 1002, "name": "Common" });
+        let earlier: Bnk = serde_json::from_value(json).unwrap();
+        assert_eq!(earlier.bank_names(), renamed.bank_names());
+        let mut output = io::Cursor::new(vec![]);
+        renamed.write_to(&mut output).unwrap();
+        let output = Bnk::from_reader(&mut io::Cursor::new(output.into_inner())).unwrap();
+        assert_eq!(output.bank_names()[1].name, "Common");
+        assert_eq!(output.sections[1].section_length, stid.len() as u32 + 3);
+
+        // kept as is if it does not parse
+        let input = [section(b"BKHD", &[0; 8]), section(b"STID", &stid[..10])].concat();
+        let bank = Bnk::from_reader(&mut io::Cursor::new(&input)).unwrap();
+        assert!(matches!(
+            bank.sections[1].payload,
+            SectionPayload::Unk { .. }
+        ));
+        let mut output = io::Cursor::new(vec![]);
+        bank.write_to(&mut output).unwrap();
+        assert_eq!(output.into_inner(), input);
+    }
+
     #[test]
     fn test_hirc() {
         let input = fs::read(INPUT_HIRC).unwrap();
@@ -297,6 +1186,47 @@ mod tests {
         eprintln!("{:?}", sbnk.sections[0]);
     }
 
+    #[test]
+    fn test_hirc_json_roundtrip() {
+        let input = fs::read(INPUT_HIRC).unwrap();
+        let bank = Bnk::from_reader(&mut io::Cursor::new(&input)).unwrap();
+        let json = serde_json::to_value(&bank).unwrap();
+        let entries = json["sections"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|section| section["type"] == "Hirc")
+            .unwrap()["content"]["entries"]
+            .as_array()
+            .unwrap();
+        assert!(
+            entries
+                .iter()
+                .any(|entry| entry["object"]["type"] == "sound")
+        );
+        // length is written as before, typed objects included
+        assert!(entries.iter().all(|entry| entry["length"].is_u64()));
+
+        let reread: Bnk = serde_json::from_value(json).unwrap();
+        let mut output = io::Cursor::new(vec![]);
+        reread.write_to(&mut output).unwrap();
+        assert_eq!(output.into_inner(), input);
+
+        // raw data of old bank.json
+        let entry: HircEntry = serde_json::from_str(
+            r#"{"type_id": 4, "length": 9, "id": 1, "data": [1, 2, 0, 0, 0]}"#,
+        )
+        .unwrap();
+        assert_eq!(entry.length, 9);
+        assert_eq!(entry.data, [1, 2, 0, 0, 0]);
+        // a stale length of edited data is not trusted
+        let entry: HircEntry = serde_json::from_str(
+            r#"{"type_id": 4, "length": 5, "id": 1, "data": [1, 2, 0, 0, 0]}"#,
+        )
+        .unwrap();
+        assert_eq!(entry.length, 9);
+    }
+
     #[test]
     fn test_hirc_2() {
         let input = fs::read(INPUT_HIRC_2).unwrap();
@@ -1,20 +1,71 @@
-use std::sync::LazyLock;
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::LazyLock,
+    time::Duration,
+};
 
-use eyre::Context;
 use log::{error, warn};
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
-use crate::{ffmpeg::FFmpegCli, wwise::WwiseConsole};
+use crate::{
+    ffmpeg::FFmpegCli,
+    vgmstream::VgmstreamCli,
+    wwise::{self, ConsoleLimits, ProcessPriority, WwiseConsole},
+};
 
 const CONFIG_PATH: &str = "config.toml";
+/// Folder name of the tool in the user data folder.
+const APP_DIR_NAME: &str = "mhws-sound-tool";
+const CONFIG_BACKUP_SUFFIX: &str = ".bak";
 static GLOBAL_CONFIG: LazyLock<Mutex<Config>> = LazyLock::new(|| Mutex::new(Config::init_load()));
 
+type Result<T> = std::result::Result<T, ConfigError>;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to access {}: {source}", path.display())]
+    File { path: PathBuf, source: io::Error },
+    #[error("Failed to parse config: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("Failed to serialize config: {0}")]
+    Serialize(#[from] toml::ser::Error),
+    #[error("No version field in config")]
+    MissingVersion,
+    #[error("Version field is not an integer")]
+    InvalidVersion,
+    #[error("Unsupported config version: {0}")]
+    UnsupportedVersion(i64),
+}
+
+impl ConfigError {
+    /// Attach the path to an IO error.
+    fn file(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| Self::File { path, source }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub version: i32,
     #[serde(default)]
     pub bin: Vec<BinConfig>,
+    #[serde(default)]
+    pub transcode: TranscodeConfig,
+    #[serde(default)]
+    pub paths: PathsConfig,
+    #[serde(default)]
+    pub update: UpdateConfig,
+    #[serde(default)]
+    pub wwise: WwiseConfig,
+    #[serde(default)]
+    pub gui: GuiConfig,
+    #[serde(default)]
+    pub history: HistoryConfig,
 }
 
 impl Config {
@@ -26,19 +77,31 @@ impl Config {
         config
     }
 
-    pub fn initialize(&mut self) -> eyre::Result<()> {
-        if self.get_bin_config("ffmpeg").is_none() {
-            if let Ok(ffmpeg) = FFmpegCli::new() {
-                self.set_bin_config("ffmpeg", ffmpeg.program_path().to_string_lossy().as_ref());
-            }
+    pub fn initialize(&mut self) -> Result<()> {
+        if self.get_bin_config("ffmpeg").is_none()
+            && let Ok(ffmpeg) = FFmpegCli::new()
+        {
+            self.set_bin_config("ffmpeg", ffmpeg.program_path().to_string_lossy().as_ref());
         }
-        if self.get_bin_config("WwiseConsole").is_none() {
-            if let Ok(wwise_console) = WwiseConsole::new() {
-                self.set_bin_config(
-                    "WwiseConsole",
-                    wwise_console.program_path().to_string_lossy().as_ref(),
-                );
-            }
+        if self.get_bin_config("vgmstream-cli").is_none()
+            && let Ok(vgmstream) = VgmstreamCli::new()
+        {
+            self.set_bin_config(
+                "vgmstream-cli",
+                vgmstream.program_path().to_string_lossy().as_ref(),
+            );
+        }
+        let wwise_console = match self.wwise.wine() {
+            Some(wine) => WwiseConsole::new_with_wine(wine),
+            None => WwiseConsole::new(),
+        };
+        if self.get_bin_config("WwiseConsole").is_none()
+            && let Ok(wwise_console) = wwise_console
+        {
+            self.set_bin_config(
+                "WwiseConsole",
+                wwise_console.program_path().to_string_lossy().as_ref(),
+            );
         }
         Ok(())
     }
@@ -67,10 +130,8 @@ impl Config {
         }
     }
 
-    pub fn try_save(&self) -> eyre::Result<()> {
-        let config_string = toml::to_string_pretty(self).context("Failed to serialize config")?;
-        std::fs::write(CONFIG_PATH, config_string).context("Failed to write config file")?;
-        Ok(())
+    pub fn try_save(&self) -> Result<()> {
+        save_config(self, CONFIG_PATH)
     }
 
     pub fn save(&self) {
@@ -80,6 +141,26 @@ impl Config {
     }
 }
 
+/// Folder of user data shared by all working folders, e.g. installed data
+/// packs: `%APPDATA%\mhws-sound-tool` on Windows, `~/Library/Application
+/// Support/mhws-sound-tool` on macOS, `$XDG_DATA_HOME/mhws-sound-tool` or
+/// `~/.local/share/mhws-sound-tool` elsewhere.
+pub fn user_data_dir() -> Option<PathBuf> {
+    let env_dir = |name| {
+        std::env::var_os(name)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    };
+    let base = if cfg!(windows) {
+        env_dir("APPDATA")?
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME")?.join("Library").join("Application Support")
+    } else {
+        env_dir("XDG_DATA_HOME").or_else(|| Some(env_dir("HOME")?.join(".local").join("share")))?
+    };
+    Some(base.join(APP_DIR_NAME))
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BinConfig {
     pub name: String,
@@ -88,27 +169,272 @@ pub struct BinConfig {
     pub params: Vec<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscodeConfig {
+    /// Apply a limiter to wav files that peak above `limiter_threshold_db`
+    /// before converting to wem.
+    #[serde(default)]
+    pub limiter: bool,
+    /// Limiter ceiling in dBFS.
+    #[serde(default = "default_limiter_threshold_db")]
+    pub limiter_threshold_db: f32,
+    /// Wwise target platform of conversion, e.g. `Windows`.
+    #[serde(default = "default_platform")]
+    pub platform: String,
+    /// Trim leading and trailing silence of wav files before converting to wem,
+    /// removing padding of DAW exports.
+    #[serde(default)]
+    pub trim_silence: bool,
+    /// Level in dBFS below which samples are regarded as silence.
+    #[serde(default = "default_trim_threshold_db")]
+    pub trim_threshold_db: f32,
+    /// Maximum number of files transcoded concurrently, 0 for one per CPU core.
+    #[serde(default)]
+    pub jobs: usize,
+}
+
+impl Default for TranscodeConfig {
+    fn default() -> Self {
+        Self {
+            limiter: false,
+            limiter_threshold_db: default_limiter_threshold_db(),
+            platform: default_platform(),
+            trim_silence: false,
+            trim_threshold_db: default_trim_threshold_db(),
+            jobs: 0,
+        }
+    }
+}
+
+fn default_limiter_threshold_db() -> f32 {
+    -1.0
+}
+
+fn default_trim_threshold_db() -> f32 {
+    -60.0
+}
+
+fn default_platform() -> String {
+    wwise::DEFAULT_PLATFORM.to_string()
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PathsConfig {
+    /// Default output root of drag-and-drop mode, instead of next to the inputs.
+    ///
+    /// Outputs are sorted into subfolders by type, e.g. `projects`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_root: Option<String>,
+    /// Game folder, or a mod folder of a mod manager, packaged bundles are
+    /// deployed to with `--deploy`, at their `natives/...` path.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deploy_root: Option<String>,
+}
+
+impl PathsConfig {
+    pub const PROJECTS_DIR: &str = "projects";
+    pub const BUNDLES_DIR: &str = "bundles";
+    pub const WEM_DIR: &str = "wem";
+
+    /// Output folder of the type under `output_root`, if configured.
+    pub fn output_dir(&self, kind: &str) -> Option<PathBuf> {
+        self.output_root
+            .as_ref()
+            .filter(|root| !root.trim().is_empty())
+            .map(|root| PathBuf::from(root).join(kind))
+    }
+
+    /// Configured deploy folder.
+    pub fn deploy_root(&self) -> Option<PathBuf> {
+        self.deploy_root
+            .as_ref()
+            .filter(|root| !root.trim().is_empty())
+            .map(PathBuf::from)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateConfig {
+    /// Check GitHub releases for a newer version on every start,
+    /// same as `--version-check`.
+    #[serde(default)]
+    pub check_on_start: bool,
+    /// Executable replaced by `self-update`, removed on next start.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replaced_exe: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistoryConfig {
+    /// Number of packaged outputs kept in history for each project,
+    /// 0 to keep none, the default.
+    #[serde(default)]
+    pub keep: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GuiConfig {
+    /// Project opened last in the GUI, offered to resume on start.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_project: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WwiseConfig {
+    /// Folder of temp Wwise projects used for conversion, next to the exe by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temp_project_root: Option<String>,
+    /// Name of temp Wwise projects, suffixed by platform and number.
+    #[serde(default = "default_temp_project_name")]
+    pub temp_project_name: String,
+    /// Maximum number of temp projects of a platform used at the same time,
+    /// by parallel conversions.
+    #[serde(default = "default_max_temp_projects")]
+    pub max_temp_projects: usize,
+    /// Maximum number of WwiseConsole processes converting at the same time,
+    /// of all platforms. 0 for no limit besides `max_temp_projects`.
+    #[serde(default)]
+    pub max_processes: usize,
+    /// Seconds to wait in queue for a free temp project or process,
+    /// 0 to fail right away.
+    #[serde(default = "default_queue_timeout")]
+    pub queue_timeout: u64,
+    /// Priority of WwiseConsole processes.
+    #[serde(default)]
+    pub priority: ProcessPriority,
+    /// CPU cores WwiseConsole processes may run on, e.g. `[0, 1, 2, 3]`,
+    /// all if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub affinity: Vec<usize>,
+    /// Wine program running WwiseConsole.exe off Windows, `wine` by default
+    /// there. Set to empty to run the console directly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wine: Option<String>,
+}
+
+impl Default for WwiseConfig {
+    fn default() -> Self {
+        Self {
+            temp_project_root: None,
+            temp_project_name: default_temp_project_name(),
+            max_temp_projects: default_max_temp_projects(),
+            max_processes: 0,
+            queue_timeout: default_queue_timeout(),
+            priority: ProcessPriority::default(),
+            affinity: vec![],
+            wine: None,
+        }
+    }
+}
+
+impl WwiseConfig {
+    /// Folder of temp projects, configured or next to the exe.
+    pub fn temp_project_root(&self) -> std::io::Result<PathBuf> {
+        match self
+            .temp_project_root
+            .as_ref()
+            .filter(|root| !root.trim().is_empty())
+        {
+            Some(root) => Ok(PathBuf::from(root)),
+            None => Ok(std::env::current_exe()?.parent().unwrap().to_path_buf()),
+        }
+    }
+
+    /// Limits of WwiseConsole processes.
+    pub fn console_limits(&self) -> ConsoleLimits {
+        ConsoleLimits {
+            max_processes: self.max_processes,
+            queue_timeout: Duration::from_secs(self.queue_timeout),
+            priority: self.priority,
+            affinity: self.affinity.clone(),
+        }
+    }
+
+    /// Wine program to run WwiseConsole with, `None` to run it directly.
+    pub fn wine(&self) -> Option<PathBuf> {
+        match self.wine.as_deref().map(str::trim) {
+            Some("") => None,
+            Some(wine) => Some(PathBuf::from(wine)),
+            None if cfg!(windows) => None,
+            None => Some(PathBuf::from("wine")),
+        }
+    }
+}
+
+fn default_temp_project_name() -> String {
+    wwise::TEMP_PROJECT_NAME.to_string()
+}
+
+fn default_max_temp_projects() -> usize {
+    wwise::DEFAULT_MAX_TEMP_PROJECTS
+}
+
+fn default_queue_timeout() -> u64 {
+    wwise::DEFAULT_QUEUE_TIMEOUT.as_secs()
+}
+
 /// Load the config from a file, or use the default config if it doesn't exist.
+///
+/// A config that fails to load is recovered from its backup if possible.
+/// It is left in place, and not backed up on next save.
 fn load_config(path: &str) -> Config {
-    if let Ok(config) = load_config_from_file(path) {
-        config
-    } else {
-        default_config()
+    if !Path::new(path).exists() {
+        return default_config();
+    }
+    let e = match load_config_from_file(path) {
+        Ok(config) => return config,
+        Err(e) => e,
+    };
+    let backup_path = with_suffix(path, CONFIG_BACKUP_SUFFIX);
+    match load_config_from_file(&backup_path) {
+        Ok(config) => {
+            warn!(
+                "Failed to load {}: {:#}. Recovered from backup {}.",
+                path, e, backup_path
+            );
+            config
+        }
+        Err(_) => {
+            warn!(
+                "Failed to load {}: {:#}. Using default config, no valid backup found.",
+                path, e
+            );
+            default_config()
+        }
+    }
+}
+
+/// Write the config to a temp file and move it into place, so a crash never
+/// leaves a partial config. A valid previous config is kept as backup.
+fn save_config(config: &Config, path: &str) -> Result<()> {
+    let config_string = toml::to_string_pretty(config)?;
+    let temp_path = with_suffix(path, ".tmp");
+    {
+        let mut file = fs::File::create(&temp_path).map_err(ConfigError::file(&temp_path))?;
+        file.write_all(config_string.as_bytes())
+            .and_then(|_| file.sync_all())
+            .map_err(ConfigError::file(&temp_path))?;
+    }
+    if load_config_from_file(path).is_ok() {
+        let backup_path = with_suffix(path, CONFIG_BACKUP_SUFFIX);
+        fs::copy(path, &backup_path).map_err(ConfigError::file(&backup_path))?;
     }
+    fs::rename(&temp_path, path).map_err(ConfigError::file(path))?;
+    Ok(())
 }
 
-fn load_config_from_file(path: &str) -> eyre::Result<Config> {
-    let config_string = std::fs::read_to_string(path).context("Failed to read config file")?;
+fn with_suffix(path: &str, suffix: &str) -> String {
+    format!("{}{}", path, suffix)
+}
+
+fn load_config_from_file(path: &str) -> Result<Config> {
+    let config_string = std::fs::read_to_string(path).map_err(ConfigError::file(path))?;
     // dynamically deserialize, version check
     let config: serde_json::Value = toml::from_str(&config_string)?;
-    let version = config
-        .get("version")
-        .ok_or(eyre::eyre!("No version field in config"))?;
-    let version = version
-        .as_i64()
-        .ok_or(eyre::eyre!("Version field is not an integer"))?;
+    let version = config.get("version").ok_or(ConfigError::MissingVersion)?;
+    let version = version.as_i64().ok_or(ConfigError::InvalidVersion)?;
     if version != 1 {
-        return Err(eyre::eyre!("Unsupported config version: {}", version));
+        return Err(ConfigError::UnsupportedVersion(version));
     }
     // deserialize the config
     let config: Config = toml::from_str(&config_string)?;
@@ -119,5 +445,47 @@ fn default_config() -> Config {
     Config {
         version: 1,
         bin: vec![],
+        transcode: TranscodeConfig::default(),
+        paths: PathsConfig::default(),
+        update: UpdateConfig::default(),
+        wwise: WwiseConfig::default(),
+        gui: GuiConfig::default(),
+        history: HistoryConfig::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_backup_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml").to_string_lossy().to_string();
+        let backup_path = with_suffix(&path, CONFIG_BACKUP_SUFFIX);
+
+        let mut config = default_config();
+        config.set_bin_config("ffmpeg", "/opt/ffmpeg");
+        save_config(&config, &path).unwrap();
+        assert!(!Path::new(&backup_path).exists());
+        config.set_bin_config("vgmstream-cli", "/opt/vgmstream-cli");
+        save_config(&config, &path).unwrap();
+        assert!(Path::new(&backup_path).is_file());
+
+        // truncated by a crash, recovered from the backup of previous save
+        fs::write(&path, "version = 1\n[[bin]]\nname = ").unwrap();
+        let recovered = load_config(&path);
+        assert_eq!(
+            recovered.get_bin_config("ffmpeg").unwrap().path,
+            "/opt/ffmpeg"
+        );
+        // the corrupted file does not replace the valid backup
+        save_config(&recovered, &path).unwrap();
+        assert!(load_config_from_file(&backup_path).is_ok());
+        assert!(
+            load_config(&dir.path().join("missing.toml").to_string_lossy())
+                .bin
+                .is_empty()
+        );
     }
 }
@@ -0,0 +1,327 @@
+//! Create a new PCK from a folder of wem and bnk files.
+//!
+//! Files are named by ID, or by a name mapped to an ID in `pck.toml`. Files
+//! in a subfolder named after a language belong to that language, others
+//! have language ID 0. `pck.toml` is optional:
+//!
+//! ```toml
+//! version = 1
+//! # alignment of entry data
+//! block_size = 1
+//!
+//! [languages]
+//! sfx = 0
+//! english = 1
+//!
+//! [ids]
+//! my_theme = 900001
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    layout::{self, ByteLen, DataOffset},
+    pck::{PckFileEntry, PckHeader, PckString},
+};
+
+type Result<T> = std::result::Result<T, CreateError>;
+
+pub const PCK_MANIFEST_FILE_NAME: &str = "pck.toml";
+
+#[derive(Debug, thiserror::Error)]
+pub enum CreateError {
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("Failed to access {}: {source}", path.display())]
+    File { path: PathBuf, source: io::Error },
+
+    #[error("Bad {PCK_MANIFEST_FILE_NAME}: {0}")]
+    Manifest(#[from] toml::de::Error),
+    #[error("File {} is not named by ID or a name in [ids].", .0.display())]
+    BadFileName(PathBuf),
+    #[error("Language folder '{0}' is not listed in [languages].")]
+    UnknownLanguage(String),
+    #[error("Entry {id} of language {language_id} exists more than once.")]
+    DuplicateEntry { id: u32, language_id: u32 },
+    #[error("No wem or bnk files found in {}", .0.display())]
+    Empty(PathBuf),
+    #[error("Bad {PCK_MANIFEST_FILE_NAME}: block_size must be at least 1.")]
+    InvalidBlockSize,
+    #[error("File {} is larger than 4 GiB.", .0.display())]
+    FileTooLarge(PathBuf),
+    #[error("Entry data of the package does not fit in 4 GiB.")]
+    PackageTooLarge,
+}
+
+impl CreateError {
+    /// Attach the path to an IO error.
+    fn file(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| CreateError::File { path, source }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PckManifest {
+    /// AKPK version, 1 for current Wwise.
+    pub version: u32,
+    /// Alignment of entry data, also the unit of entry offsets.
+    pub block_size: u32,
+    /// Language IDs by name.
+    pub languages: BTreeMap<String, u32>,
+    /// IDs of files not named by ID, by file stem.
+    pub ids: BTreeMap<String, u32>,
+}
+
+impl Default for PckManifest {
+    fn default() -> Self {
+        Self {
+            version: 1,
+            block_size: 1,
+            languages: BTreeMap::from([("sfx".to_string(), 0)]),
+            ids: BTreeMap::new(),
+        }
+    }
+}
+
+impl PckManifest {
+    /// Load `pck.toml` of a folder, returns default if not present.
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let path = dir.as_ref().join(PCK_MANIFEST_FILE_NAME);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let manifest: Self =
+            toml::from_str(&fs::read_to_string(&path).map_err(CreateError::file(&path))?)?;
+        if manifest.block_size == 0 {
+            return Err(CreateError::InvalidBlockSize);
+        }
+        Ok(manifest)
+    }
+
+    fn language_id(&self, name: &str) -> Result<u32> {
+        self.languages
+            .iter()
+            .find(|(language, _)| language.eq_ignore_ascii_case(name))
+            .map(|(_, id)| *id)
+            .ok_or_else(|| CreateError::UnknownLanguage(name.to_string()))
+    }
+
+    fn file_id(&self, path: &Path) -> Result<u32> {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let stem = stem.trim();
+        stem.parse()
+            .ok()
+            .or_else(|| self.ids.get(stem).copied())
+            .ok_or_else(|| CreateError::BadFileName(path.to_path_buf()))
+    }
+}
+
+/// Summary of a created PCK.
+#[derive(Debug, Clone, Default)]
+pub struct CreatedPck {
+    pub bnk_count: usize,
+    pub wem_count: usize,
+    pub size: u64,
+}
+
+/// Entry file found in input folder.
+struct EntryFile {
+    id: u32,
+    language_id: u32,
+    path: PathBuf,
+    length: ByteLen,
+}
+
+/// Create a PCK at `output_path` from wem and bnk files in `input_dir`.
+///
+/// Entries are sorted by ID, as the game looks them up by binary search.
+pub fn create_pck(
+    input_dir: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+) -> Result<CreatedPck> {
+    let input_dir = input_dir.as_ref();
+    let manifest = PckManifest::load(input_dir)?;
+
+    let mut bnk_files = vec![];
+    let mut wem_files = vec![];
+    let mut dirs = vec![(input_dir.to_path_buf(), 0)];
+    for entry in fs::read_dir(input_dir).map_err(CreateError::file(input_dir))? {
+        let path = entry.map_err(CreateError::file(input_dir))?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir() && !name.starts_with('.') {
+            let language_id = manifest.language_id(&name)?;
+            dirs.push((path, language_id));
+        }
+    }
+    for (dir, language_id) in dirs {
+        for entry in fs::read_dir(&dir).map_err(CreateError::file(&dir))? {
+            let path = entry.map_err(CreateError::file(&dir))?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let ext = path
+                .extension()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_lowercase();
+            let files = match ext.as_str() {
+                "wem" => &mut wem_files,
+                "bnk" => &mut bnk_files,
+                _ => continue,
+            };
+            let length =
+                u32::try_from(fs::metadata(&path).map_err(CreateError::file(&path))?.len())
+                    .map_err(|_| CreateError::FileTooLarge(path.clone()))?;
+            files.push(EntryFile {
+                id: manifest.file_id(&path)?,
+                language_id,
+                length: ByteLen(length),
+                path,
+            });
+        }
+    }
+    if bnk_files.is_empty() && wem_files.is_empty() {
+        return Err(CreateError::Empty(input_dir.to_path_buf()));
+    }
+    for files in [&mut bnk_files, &mut wem_files] {
+        files.sort_by_key(|file| (file.id, file.language_id));
+        if let Some(pair) = files
+            .windows(2)
+            .find(|pair| (pair[0].id, pair[0].language_id) == (pair[1].id, pair[1].language_id))
+        {
+            return Err(CreateError::DuplicateEntry {
+                id: pair[0].id,
+                language_id: pair[0].language_id,
+            });
+        }
+    }
+
+    let mut string_table: Vec<PckString> = manifest
+        .languages
+        .iter()
+        .map(|(value, index)| PckString {
+            index: *index,
+            value: value.clone(),
+        })
+        .collect();
+    string_table.sort_by_key(|string| string.index);
+    let to_entry = |file: &EntryFile| PckFileEntry {
+        id: file.id,
+        padding_block_size: manifest.block_size,
+        length: ByteLen(0),
+        offset: DataOffset::ZERO,
+        language_id: file.language_id,
+    };
+    let mut header = PckHeader::new(
+        manifest.version,
+        string_table,
+        bnk_files.iter().map(to_entry).collect(),
+        wem_files.iter().map(to_entry).collect(),
+    );
+
+    // BNK entries first, like packages written by Wwise
+    let files: Vec<&EntryFile> = bnk_files.iter().chain(&wem_files).collect();
+    let (offsets, _) = layout::place_blocks(
+        header.get_data_offset_start(),
+        files.iter().map(|file| (file.length, manifest.block_size)),
+    )
+    .ok_or(CreateError::PackageTooLarge)?;
+    let entries = header
+        .bnk_entries
+        .iter_mut()
+        .chain(header.wem_entries.iter_mut());
+    for ((entry, offset), file) in entries.zip(&offsets).zip(&files) {
+        entry.set_data(*offset, file.length);
+    }
+
+    let output_path = output_path.as_ref();
+    let mut writer =
+        io::BufWriter::new(File::create(output_path).map_err(CreateError::file(output_path))?);
+    header.write_to(&mut writer)?;
+    let mut position = header.get_data_offset_start();
+    for (offset, file) in offsets.iter().zip(&files) {
+        layout::write_padding(
+            &mut writer,
+            offset.distance_from(position).unwrap_or_default(),
+        )
+        .map_err(CreateError::file(output_path))?;
+        let mut input = File::open(&file.path).map_err(CreateError::file(&file.path))?;
+        let copied = io::copy(&mut input, &mut writer).map_err(CreateError::file(&file.path))?;
+        position = *offset + ByteLen(copied as u32);
+    }
+    writer.flush().map_err(CreateError::file(output_path))?;
+    drop(writer);
+
+    Ok(CreatedPck {
+        bnk_count: bnk_files.len(),
+        wem_count: wem_files.len(),
+        size: fs::metadata(output_path)
+            .map_err(CreateError::file(output_path))?
+            .len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_pck() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input");
+        fs::create_dir_all(input.join("English")).unwrap();
+        fs::write(
+            input.join(PCK_MANIFEST_FILE_NAME),
+            "block_size = 16\n[languages]\nsfx = 0\nenglish = 1\n[ids]\ntheme = 900001\n",
+        )
+        .unwrap();
+        fs::write(input.join("900002.wem"), b"RIFF-second").unwrap();
+        fs::write(input.join("theme.wem"), b"RIFF-first").unwrap();
+        fs::write(input.join("English").join("900002.wem"), b"RIFF-voice").unwrap();
+        fs::write(input.join("notes.txt"), b"ignored").unwrap();
+
+        let output = dir.path().join("New.spck.1.X64");
+        let created = create_pck(&input, &output).unwrap();
+        assert_eq!((created.bnk_count, created.wem_count), (0, 3));
+
+        let mut reader = io::BufReader::new(File::open(&output).unwrap());
+        let header = PckHeader::from_reader(&mut reader).unwrap();
+        let ids: Vec<(u32, u32)> = header
+            .wem_entries
+            .iter()
+            .map(|entry| (entry.id, entry.language_id))
+            .collect();
+        assert_eq!(ids, [(900001, 0), (900002, 0), (900002, 1)]);
+        for (i, expected) in [&b"RIFF-first"[..], b"RIFF-second", b"RIFF-voice"]
+            .iter()
+            .enumerate()
+        {
+            let mut data = vec![];
+            io::Read::read_to_end(&mut header.wem_reader(&mut reader, i).unwrap(), &mut data)
+                .unwrap();
+            assert_eq!(data, *expected);
+        }
+
+        fs::write(input.join(PCK_MANIFEST_FILE_NAME), "block_size = 0\n").unwrap();
+        assert!(matches!(
+            create_pck(&input, &output),
+            Err(CreateError::InvalidBlockSize)
+        ));
+        fs::remove_file(input.join(PCK_MANIFEST_FILE_NAME)).unwrap();
+
+        fs::create_dir_all(input.join("klingon")).unwrap();
+        assert!(matches!(
+            create_pck(&input, &output),
+            Err(CreateError::UnknownLanguage(_))
+        ));
+    }
+}
@@ -0,0 +1,385 @@
+//! Community maintained data packs of known names and bundle descriptions.
+//!
+//! A data pack is a single JSON file, installed to `datapacks/` in the user
+//! data folder, see [`config::user_data_dir`]:
+//!
+//! ```json
+//! {
+//!   "name": "mhws-events",
+//!   "version": "2025.10",
+//!   "game": "mhws",
+//!   "description": "Event names of weapon sounds",
+//!   "bundles": { "Wp00_Cmn.sbnk.1.X64": "Great Sword common sounds" },
+//!   "names": ["Play_wp00_swing"],
+//!   "ids": { "123456": "wp00_hit" }
+//! }
+//! ```
+//!
+//! `names` are hashed to IDs like lines of a name list, `ids` are names of
+//! IDs not derived from names, e.g. media IDs.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{config, names::NameList, utils};
+
+type Result<T> = std::result::Result<T, DataPackError>;
+
+pub const DATAPACKS_DIR: &str = "datapacks";
+
+#[derive(Debug, thiserror::Error)]
+pub enum DataPackError {
+    #[error("Failed to access {}: {source}", path.display())]
+    File { path: PathBuf, source: io::Error },
+
+    #[error("Bad data pack {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error("Invalid data pack name: '{0}'")]
+    InvalidName(String),
+    #[error("Data pack not installed: {0}")]
+    NotInstalled(String),
+    #[error("Data pack checksum mismatch, expected SHA-256 {expected}, got {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+}
+
+impl DataPackError {
+    /// Attach the path to an IO error.
+    fn file(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| DataPackError::File { path, source }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DataPack {
+    /// Unique name, also the installed file name.
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Game the pack is made for, e.g. `mhws`.
+    #[serde(default)]
+    pub game: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Descriptions of bundles by file name.
+    #[serde(default)]
+    pub bundles: BTreeMap<String, String>,
+    /// Names hashed to IDs.
+    #[serde(default)]
+    pub names: Vec<String>,
+    /// Names of IDs not derived from names.
+    #[serde(default)]
+    pub ids: BTreeMap<u32, String>,
+}
+
+impl DataPack {
+    pub fn from_slice(path: impl AsRef<Path>, data: &[u8]) -> Result<Self> {
+        let pack: Self = serde_json::from_slice(data).map_err(|source| DataPackError::Parse {
+            path: path.as_ref().to_path_buf(),
+            source,
+        })?;
+        // used as file name
+        let valid_name = !pack.name.is_empty()
+            && pack
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+            && !pack.name.starts_with('.');
+        if !valid_name {
+            return Err(DataPackError::InvalidName(pack.name));
+        }
+        Ok(pack)
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        Self::from_slice(path, &fs::read(path).map_err(DataPackError::file(path))?)
+    }
+
+    /// Check downloaded pack content against its published SHA-256 in hex.
+    pub fn verify_sha256(data: &[u8], expected: &str) -> Result<()> {
+        let actual = utils::to_hex(&Sha256::digest(data));
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            return Err(DataPackError::ChecksumMismatch {
+                expected: expected.trim().to_string(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Count of known IDs.
+    pub fn len(&self) -> usize {
+        self.names.len() + self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.ids.is_empty()
+    }
+
+    pub fn add_to(&self, list: &mut NameList) {
+        for name in &self.names {
+            list.insert(utils::fnv1_32(name), name);
+        }
+        for (id, name) in &self.ids {
+            list.insert(*id, name);
+        }
+    }
+}
+
+/// A name or bundle matched by [`DataPacks::search`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum SearchMatch {
+    Name {
+        id: u32,
+        name: String,
+        pack: String,
+    },
+    Bundle {
+        file_name: String,
+        description: String,
+        pack: String,
+    },
+}
+
+/// Data packs installed in a directory.
+#[derive(Debug, Clone, Default)]
+pub struct DataPacks {
+    dir: PathBuf,
+    packs: Vec<DataPack>,
+    /// File of each pack in `packs`.
+    paths: Vec<PathBuf>,
+}
+
+impl DataPacks {
+    /// Load packs installed in `dir`, empty if it does not exist.
+    /// Bad packs are skipped with a warning.
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let mut packs = Self {
+            dir: dir.as_ref().to_path_buf(),
+            ..Default::default()
+        };
+        packs.load_from(dir.as_ref())?;
+        Ok(packs)
+    }
+
+    fn load_from(&mut self, dir: &Path) -> Result<()> {
+        if !dir.is_dir() {
+            return Ok(());
+        }
+        let mut paths = vec![];
+        for entry in fs::read_dir(dir).map_err(DataPackError::file(dir))? {
+            let path = entry.map_err(DataPackError::file(dir))?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        for path in paths {
+            match DataPack::from_path(&path) {
+                Ok(pack) => {
+                    self.packs.push(pack);
+                    self.paths.push(path);
+                }
+                Err(e) => log::warn!("Skipped data pack: {}", e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Packs installed in the user data folder, next to `config.toml` if
+    /// it is unknown.
+    ///
+    /// Packs in `datapacks/` next to `config.toml`, where older versions
+    /// installed them, are still loaded before them.
+    pub fn installed() -> Result<Self> {
+        let legacy_dir = Path::new(DATAPACKS_DIR);
+        let dir = config::user_data_dir()
+            .map(|data_dir| data_dir.join(DATAPACKS_DIR))
+            .unwrap_or_else(|| legacy_dir.to_path_buf());
+        let mut packs = Self {
+            dir: dir.clone(),
+            ..Default::default()
+        };
+        if legacy_dir.is_dir() && fs::canonicalize(legacy_dir).ok() != fs::canonicalize(&dir).ok() {
+            packs.load_from(legacy_dir)?;
+            if !packs.is_empty() {
+                log::warn!(
+                    "Data packs in {} are from an older version, install them again to move them to {}.",
+                    legacy_dir.display(),
+                    dir.display()
+                );
+            }
+        }
+        packs.load_from(&dir)?;
+        Ok(packs)
+    }
+
+    pub fn packs(&self) -> &[DataPack] {
+        &self.packs
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packs.is_empty()
+    }
+
+    /// Install a pack from its file content, replacing an installed pack
+    /// of the same name.
+    pub fn install(&mut self, data: &[u8]) -> Result<&DataPack> {
+        let pack = DataPack::from_slice("<input>", data)?;
+        fs::create_dir_all(&self.dir).map_err(DataPackError::file(&self.dir))?;
+        let path = self.dir.join(format!("{}.json", pack.name));
+        fs::write(&path, data).map_err(DataPackError::file(&path))?;
+        // a pack of the same name elsewhere is replaced as well
+        while let Some(pos) = self.position(&pack.name) {
+            if self.paths[pos] != path {
+                fs::remove_file(&self.paths[pos]).map_err(DataPackError::file(&self.paths[pos]))?;
+            }
+            self.packs.remove(pos);
+            self.paths.remove(pos);
+        }
+        self.packs.push(pack);
+        self.paths.push(path);
+        Ok(self.packs.last().unwrap())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<()> {
+        let Some(pos) = self.position(name) else {
+            return Err(DataPackError::NotInstalled(name.to_string()));
+        };
+        fs::remove_file(&self.paths[pos]).map_err(DataPackError::file(&self.paths[pos]))?;
+        self.packs.remove(pos);
+        self.paths.remove(pos);
+        Ok(())
+    }
+
+    /// Position of the last loaded pack of a name.
+    fn position(&self, name: &str) -> Option<usize> {
+        self.packs.iter().rposition(|pack| pack.name == name)
+    }
+
+    /// Known names of all packs, later installed packs take precedence.
+    pub fn name_list(&self) -> NameList {
+        let mut list = NameList::default();
+        for pack in &self.packs {
+            pack.add_to(&mut list);
+        }
+        list
+    }
+
+    /// Description of a bundle by its file name, case insensitive.
+    pub fn bundle_description(&self, file_name: &str) -> Option<&str> {
+        self.packs.iter().rev().find_map(|pack| {
+            pack.bundles
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(file_name))
+                .map(|(_, description)| description.as_str())
+        })
+    }
+
+    /// Names, IDs and bundles containing `query`, case insensitive.
+    pub fn search(&self, query: &str) -> Vec<SearchMatch> {
+        let query = query.to_lowercase();
+        let query_id: Option<u32> = query.parse().ok();
+        let mut matches = vec![];
+        for pack in &self.packs {
+            let hashed = pack.names.iter().map(|name| (utils::fnv1_32(name), name));
+            let explicit = pack.ids.iter().map(|(id, name)| (*id, name));
+            for (id, name) in hashed.chain(explicit) {
+                if query_id == Some(id) || name.to_lowercase().contains(&query) {
+                    matches.push(SearchMatch::Name {
+                        id,
+                        name: name.clone(),
+                        pack: pack.name.clone(),
+                    });
+                }
+            }
+            for (file_name, description) in &pack.bundles {
+                if file_name.to_lowercase().contains(&query)
+                    || description.to_lowercase().contains(&query)
+                {
+                    matches.push(SearchMatch::Bundle {
+                        file_name: file_name.clone(),
+                        description: description.clone(),
+                        pack: pack.name.clone(),
+                    });
+                }
+            }
+        }
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACK: &str = r#"{
+        "name": "weapons",
+        "version": "1",
+        "bundles": { "Wp00_Cmn.sbnk.1.X64": "Great Sword common sounds" },
+        "names": ["Play_wp00_swing"],
+        "ids": { "123456": "wp00_hit" }
+    }"#;
+
+    #[test]
+    fn test_datapacks() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut packs = DataPacks::load(dir.path()).unwrap();
+        assert!(packs.is_empty());
+        assert_eq!(packs.install(PACK.as_bytes()).unwrap().len(), 2);
+        assert!(packs.install(br#"{"name": "../evil"}"#).is_err());
+
+        let packs = DataPacks::load(dir.path()).unwrap();
+        assert_eq!(packs.packs().len(), 1);
+        let names = packs.name_list();
+        assert_eq!(
+            names.get(utils::fnv1_32("Play_wp00_swing")),
+            Some("Play_wp00_swing")
+        );
+        assert_eq!(names.get(123456), Some("wp00_hit"));
+        assert_eq!(
+            packs.bundle_description("wp00_cmn.sbnk.1.x64"),
+            Some("Great Sword common sounds")
+        );
+
+        let matches = packs.search("WP00");
+        assert_eq!(matches.len(), 3);
+        assert_eq!(
+            packs.search("123456"),
+            [SearchMatch::Name {
+                id: 123456,
+                name: "wp00_hit".to_string(),
+                pack: "weapons".to_string(),
+            }]
+        );
+
+        let mut packs = packs;
+        packs.install(PACK.as_bytes()).unwrap();
+        assert_eq!(packs.packs().len(), 1);
+        packs.remove("weapons").unwrap();
+        assert!(DataPacks::load(dir.path()).unwrap().is_empty());
+        assert!(packs.remove("weapons").is_err());
+    }
+
+    #[test]
+    fn test_verify_sha256() {
+        let hash = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert!(DataPack::verify_sha256(b"hello", hash).is_ok());
+        assert!(DataPack::verify_sha256(b"hello", &hash.to_uppercase()).is_ok());
+        assert!(matches!(
+            DataPack::verify_sha256(b"hello!", hash),
+            Err(DataPackError::ChecksumMismatch { .. })
+        ));
+    }
+}
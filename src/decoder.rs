@@ -0,0 +1,124 @@
+use std::{fs::File, io, path::Path};
+
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{CODEC_TYPE_NULL, DecoderOptions},
+    errors::Error as SymphoniaError,
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::MetadataOptions,
+    probe::Hint,
+};
+
+use crate::detect::InputFileType;
+
+type Result<T> = std::result::Result<T, DecodeError>;
+
+/// Formats decoded in-process, others go through ffmpeg.
+const SUPPORTED_FORMATS: &[&str] = &["mp3", "flac", "ogg", "aac"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    #[error("Decoder IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("Failed to decode audio: {0}")]
+    Decode(#[from] SymphoniaError),
+    #[error("No audio track found.")]
+    NoAudioTrack,
+    #[error("Failed to write wav: {0}")]
+    Wav(#[from] hound::Error),
+}
+
+/// Whether the file can be decoded without ffmpeg.
+pub fn is_supported(path: impl AsRef<Path>) -> bool {
+    matches!(
+        InputFileType::from_path(path),
+        Some(InputFileType::GeneralAudio(format)) if SUPPORTED_FORMATS.contains(&format)
+    )
+}
+
+/// Decode an audio file to 16-bit PCM wav file data.
+pub fn decode_to_wav(input: impl AsRef<Path>) -> Result<Vec<u8>> {
+    let input = input.as_ref();
+    let stream = MediaSourceStream::new(Box::new(File::open(input)?), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = input.extension() {
+        hint.with_extension(&ext.to_string_lossy());
+    }
+    let probed = symphonia::default::get_probe().format(
+        &hint,
+        stream,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+    let mut format = probed.format;
+    let track = format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or(DecodeError::NoAudioTrack)?;
+    let track_id = track.id;
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut wav_spec = None;
+    let mut samples: Vec<i16> = vec![];
+    let mut sample_buf: Option<SampleBuffer<i16>> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // corrupted frame, skip it
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let spec = *decoded.spec();
+        wav_spec.get_or_insert(hound::WavSpec {
+            channels: spec.channels.count() as u16,
+            sample_rate: spec.rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        });
+        let buf = match &mut sample_buf {
+            Some(buf) if buf.capacity() >= decoded.capacity() * spec.channels.count() => buf,
+            _ => sample_buf.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+        };
+        buf.copy_interleaved_ref(decoded);
+        samples.extend_from_slice(buf.samples());
+    }
+
+    let wav_spec = wav_spec.ok_or(DecodeError::NoAudioTrack)?;
+    let mut output = io::Cursor::new(vec![]);
+    let mut writer = hound::WavWriter::new(&mut output, wav_spec)?;
+    for sample in samples {
+        writer.write_sample(sample)?;
+    }
+    writer.finalize()?;
+
+    Ok(output.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_mp3() {
+        let input = "test_files/test_sound.mp3";
+        assert!(is_supported(input));
+        assert!(!is_supported("test_files/test_sound.wav"));
+
+        let wav = decode_to_wav(input).unwrap();
+        let reader = hound::WavReader::new(io::Cursor::new(wav)).unwrap();
+        assert_eq!(reader.spec().bits_per_sample, 16);
+        assert!(reader.duration() > 0);
+    }
+}
@@ -0,0 +1,429 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::{debug, warn};
+use sha2::{Digest, Sha256};
+
+use crate::{modinfo::ModInfo, utils};
+
+type Result<T> = std::result::Result<T, DeployError>;
+
+/// Extension of staged files, named by a hash of their full target path.
+const STAGING_EXT: &str = "deploy-staging";
+const BACKUP_SUFFIX: &str = ".deploy-backup";
+
+/// Folder of the tool in a game install, holding backups and the record
+/// of written files.
+pub const GAME_DATA_DIR: &str = ".mhws-sound-tool";
+/// Folder of backups of original game files, in [`GAME_DATA_DIR`].
+pub const VANILLA_BACKUP_DIR: &str = "vanilla-backups";
+/// Record of files written into a game install, in [`GAME_DATA_DIR`].
+const WRITTEN_RECORD_FILE: &str = "written.json";
+/// Files in the root folder of a game install.
+const GAME_ROOT_MARKERS: [&str; 2] = ["MonsterHunterWilds.exe", "re_chunk_000.pak"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum DeployError {
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("Failed to stage {path}: {source}")]
+    Stage { path: PathBuf, source: io::Error },
+    #[error("Hash mismatch after staging: {0}")]
+    HashMismatch(PathBuf),
+    #[error("Failed to place {path}, deployment rolled back: {source}")]
+    Commit { path: PathBuf, source: io::Error },
+    #[error("More than one file would be deployed to {0}.")]
+    DuplicateTarget(PathBuf),
+}
+
+/// Deploy multiple files as a whole.
+///
+/// All files are staged next to their targets and verified first,
+/// then moved into place. If any step fails, files already placed
+/// are restored, so the targets are never left half deployed.
+#[derive(Debug, Default)]
+pub struct Deployment {
+    items: Vec<DeployItem>,
+}
+
+#[derive(Debug)]
+struct DeployItem {
+    source: PathBuf,
+    target: PathBuf,
+}
+
+impl Deployment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, source: impl AsRef<Path>, target: impl AsRef<Path>) {
+        self.items.push(DeployItem {
+            source: source.as_ref().to_path_buf(),
+            target: target.as_ref().to_path_buf(),
+        });
+    }
+
+    /// Run the deployment, returns deployed target paths.
+    pub fn commit(self) -> Result<Vec<PathBuf>> {
+        for (i, item) in self.items.iter().enumerate() {
+            if self.items[..i]
+                .iter()
+                .any(|other| other.target == item.target)
+            {
+                return Err(DeployError::DuplicateTarget(item.target.clone()));
+            }
+        }
+
+        // stage all files and verify hashes
+        let mut staged = vec![];
+        for item in &self.items {
+            match Self::stage(item) {
+                Ok(path) => staged.push(path),
+                Err(e) => {
+                    for path in &staged {
+                        let _ = fs::remove_file(path);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        // move staged files into place
+        let mut placed: Vec<(&DeployItem, Option<PathBuf>)> = vec![];
+        for (item, staging_path) in self.items.iter().zip(&staged) {
+            match Self::place(item, staging_path) {
+                Ok(backup) => placed.push((item, backup)),
+                Err(source) => {
+                    Self::rollback(&placed);
+                    for path in &staged {
+                        let _ = fs::remove_file(path);
+                    }
+                    return Err(DeployError::Commit {
+                        path: item.target.clone(),
+                        source,
+                    });
+                }
+            }
+        }
+
+        // all done, drop backups
+        for (_, backup) in &placed {
+            if let Some(backup) = backup {
+                let _ = fs::remove_file(backup);
+            }
+        }
+
+        Ok(self.items.into_iter().map(|item| item.target).collect())
+    }
+
+    fn stage(item: &DeployItem) -> Result<PathBuf> {
+        let staging_path = staging_path(&item.target);
+        let stage_err = |source| DeployError::Stage {
+            path: item.target.clone(),
+            source,
+        };
+        if let Some(parent) = item.target.parent() {
+            fs::create_dir_all(parent).map_err(stage_err)?;
+        }
+        fs::copy(&item.source, &staging_path).map_err(stage_err)?;
+        debug!("Staged: {}", staging_path.display());
+
+        let source_hash = file_sha256(&item.source).map_err(stage_err)?;
+        let staged_hash = file_sha256(&staging_path).map_err(stage_err)?;
+        if source_hash != staged_hash {
+            let _ = fs::remove_file(&staging_path);
+            return Err(DeployError::HashMismatch(item.target.clone()));
+        }
+        Ok(staging_path)
+    }
+
+    /// Move the staged file to target, returns the backup path of replaced file.
+    fn place(item: &DeployItem, staging_path: &Path) -> io::Result<Option<PathBuf>> {
+        let backup = if item.target.exists() {
+            let backup = with_suffix(&item.target, BACKUP_SUFFIX);
+            fs::rename(&item.target, &backup)?;
+            Some(backup)
+        } else {
+            None
+        };
+        if let Err(e) = fs::rename(staging_path, &item.target) {
+            if let Some(backup) = &backup {
+                let _ = fs::rename(backup, &item.target);
+            }
+            return Err(e);
+        }
+        Ok(backup)
+    }
+
+    fn rollback(placed: &[(&DeployItem, Option<PathBuf>)]) {
+        for (item, backup) in placed.iter().rev() {
+            let result = match backup {
+                Some(backup) => fs::rename(backup, &item.target),
+                None => fs::remove_file(&item.target),
+            };
+            if let Err(e) = result {
+                warn!("Failed to roll back {}: {}", item.target.display(), e);
+            }
+        }
+    }
+}
+
+/// Path of a game file relative to the game or mod folder, starting from its
+/// `natives` folder, e.g. `natives/STM/Sound/Wwise/Wp00_Cmn.sbnk.1.X64`.
+pub fn natives_relative_path(path: impl AsRef<Path>) -> Option<PathBuf> {
+    let components: Vec<_> = path.as_ref().components().collect();
+    let pos = components
+        .iter()
+        .rposition(|c| c.as_os_str().eq_ignore_ascii_case("natives"))?;
+    (pos + 1 < components.len()).then(|| components[pos..].iter().collect())
+}
+
+/// Root folder of the game install a path is in, `None` if it is not in one.
+pub fn game_root(path: impl AsRef<Path>) -> Option<PathBuf> {
+    let path = std::path::absolute(path).ok()?;
+    path.ancestors()
+        .skip(1)
+        .find(|dir| {
+            GAME_ROOT_MARKERS
+                .iter()
+                .any(|marker| dir.join(marker).is_file())
+        })
+        .map(Path::to_path_buf)
+}
+
+/// Folder of backups of original files of a game install.
+pub fn vanilla_backup_dir(game_root: impl AsRef<Path>) -> PathBuf {
+    game_root
+        .as_ref()
+        .join(GAME_DATA_DIR)
+        .join(VANILLA_BACKUP_DIR)
+}
+
+/// SHA-256 of files written by the tool into a game install, by path
+/// relative to the game root.
+fn load_written_record(game_root: &Path) -> io::Result<BTreeMap<String, String>> {
+    let path = game_root.join(GAME_DATA_DIR).join(WRITTEN_RECORD_FILE);
+    if !path.is_file() {
+        return Ok(BTreeMap::new());
+    }
+    serde_json::from_str(&fs::read_to_string(&path)?).map_err(io::Error::other)
+}
+
+fn record_key(path: &Path, game_root: &Path) -> io::Result<String> {
+    let path = std::path::absolute(path)?;
+    let relative_path = path
+        .strip_prefix(game_root)
+        .map_err(|_| io::Error::other(format!("{} is not in game folder", path.display())))?;
+    Ok(relative_path.to_string_lossy().replace('\\', "/"))
+}
+
+/// Record a file written by the tool into a game install, so it is not taken
+/// for an original game file when replaced later.
+pub fn record_written(path: impl AsRef<Path>, game_root: impl AsRef<Path>) -> io::Result<()> {
+    let (path, game_root) = (path.as_ref(), game_root.as_ref());
+    let mut record = load_written_record(game_root)?;
+    record.insert(
+        record_key(path, game_root)?,
+        utils::to_hex(&file_sha256(path)?),
+    );
+    let data_dir = game_root.join(GAME_DATA_DIR);
+    fs::create_dir_all(&data_dir)?;
+    let content = serde_json::to_string_pretty(&record).map_err(io::Error::other)?;
+    fs::write(data_dir.join(WRITTEN_RECORD_FILE), content)
+}
+
+/// Whether a file of a game install was written by the tool: it matches
+/// the recorded hash, or has a mod info sidecar next to it.
+pub fn is_tool_written(path: impl AsRef<Path>, game_root: impl AsRef<Path>) -> io::Result<bool> {
+    let (path, game_root) = (path.as_ref(), game_root.as_ref());
+    if ModInfo::sidecar_path(path).is_file() {
+        return Ok(true);
+    }
+    let record = load_written_record(game_root)?;
+    match record.get(&record_key(path, game_root)?) {
+        Some(hash) => Ok(*hash == utils::to_hex(&file_sha256(path)?)),
+        None => Ok(false),
+    }
+}
+
+/// Copy a file of a game install to `backup_dir`, at its path under the game
+/// root, returns the backup path. An existing backup is never replaced, so it
+/// stays the original file.
+pub fn backup_vanilla(
+    path: impl AsRef<Path>,
+    game_root: impl AsRef<Path>,
+    backup_dir: impl AsRef<Path>,
+) -> io::Result<PathBuf> {
+    let path = std::path::absolute(path)?;
+    let relative_path = path
+        .strip_prefix(game_root.as_ref())
+        .map_err(|_| io::Error::other(format!("{} is not in game folder", path.display())))?;
+    let backup = backup_dir.as_ref().join(relative_path);
+    if !backup.exists() {
+        if let Some(parent) = backup.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(&path, &backup)?;
+    }
+    Ok(backup)
+}
+
+/// Calculate SHA-256 hash of a file.
+pub fn file_sha256(path: impl AsRef<Path>) -> io::Result<[u8; 32]> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+/// Staging path next to `target`, unique for each full target path.
+fn staging_path(target: &Path) -> PathBuf {
+    target.with_file_name(utils::staging_name(target, STAGING_EXT))
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deploy_rollback() {
+        let src_dir = tempfile::tempdir().unwrap();
+        let dst_dir = tempfile::tempdir().unwrap();
+        fs::write(src_dir.path().join("a.bnk"), b"new a").unwrap();
+        fs::write(src_dir.path().join("b.bnk"), b"new b").unwrap();
+        fs::write(dst_dir.path().join("a.bnk"), b"old a").unwrap();
+
+        // successful deployment
+        let mut deployment = Deployment::new();
+        deployment.add(src_dir.path().join("a.bnk"), dst_dir.path().join("a.bnk"));
+        deployment.add(src_dir.path().join("b.bnk"), dst_dir.path().join("b.bnk"));
+        deployment.commit().unwrap();
+        assert_eq!(fs::read(dst_dir.path().join("a.bnk")).unwrap(), b"new a");
+        assert_eq!(fs::read(dst_dir.path().join("b.bnk")).unwrap(), b"new b");
+
+        // second source is missing, nothing should be touched
+        fs::write(src_dir.path().join("a.bnk"), b"newer a").unwrap();
+        let mut deployment = Deployment::new();
+        deployment.add(src_dir.path().join("a.bnk"), dst_dir.path().join("a.bnk"));
+        deployment.add(
+            src_dir.path().join("missing.bnk"),
+            dst_dir.path().join("c.bnk"),
+        );
+        assert!(deployment.commit().is_err());
+        assert_eq!(fs::read(dst_dir.path().join("a.bnk")).unwrap(), b"new a");
+        assert!(!dst_dir.path().join("c.bnk").exists());
+
+        // files of the same name are staged apart, one target is deployed once
+        let target = dst_dir.path().join("a.bnk");
+        assert_ne!(
+            staging_path(&target),
+            staging_path(&dst_dir.path().join("sub").join("a.bnk"))
+        );
+        assert_eq!(staging_path(&target).parent(), target.parent());
+        let mut deployment = Deployment::new();
+        deployment.add(src_dir.path().join("a.bnk"), &target);
+        deployment.add(src_dir.path().join("b.bnk"), &target);
+        assert!(matches!(
+            deployment.commit(),
+            Err(DeployError::DuplicateTarget(path)) if path == target
+        ));
+        assert_eq!(fs::read(&target).unwrap(), b"new a");
+
+        // placed files are restored on rollback
+        let item_a = DeployItem {
+            source: src_dir.path().join("a.bnk"),
+            target: dst_dir.path().join("a.bnk"),
+        };
+        let item_d = DeployItem {
+            source: src_dir.path().join("b.bnk"),
+            target: dst_dir.path().join("d.bnk"),
+        };
+        let staged_a = Deployment::stage(&item_a).unwrap();
+        let staged_d = Deployment::stage(&item_d).unwrap();
+        let backup_a = Deployment::place(&item_a, &staged_a).unwrap();
+        let backup_d = Deployment::place(&item_d, &staged_d).unwrap();
+        assert_eq!(fs::read(dst_dir.path().join("a.bnk")).unwrap(), b"newer a");
+        Deployment::rollback(&[(&item_a, backup_a), (&item_d, backup_d)]);
+        assert_eq!(fs::read(dst_dir.path().join("a.bnk")).unwrap(), b"new a");
+        assert!(!dst_dir.path().join("d.bnk").exists());
+
+        // no leftovers
+        let leftovers = fs::read_dir(dst_dir.path())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| {
+                let name = e.file_name().to_string_lossy().to_string();
+                name.ends_with(STAGING_EXT) || name.ends_with(BACKUP_SUFFIX)
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn test_tool_written_files() {
+        let game_dir = tempfile::tempdir().unwrap();
+        let game_root = std::path::absolute(game_dir.path()).unwrap();
+        let sound_dir = game_root.join("natives/STM/Sound/Wwise");
+        fs::create_dir_all(&sound_dir).unwrap();
+        let path = sound_dir.join("Wp00_Cmn.sbnk.1.X64");
+        fs::write(&path, b"vanilla").unwrap();
+        assert!(!is_tool_written(&path, &game_root).unwrap());
+
+        let backup = backup_vanilla(&path, &game_root, vanilla_backup_dir(&game_root)).unwrap();
+        assert!(backup.starts_with(game_root.join(GAME_DATA_DIR)));
+        fs::write(&path, b"modded").unwrap();
+        record_written(&path, &game_root).unwrap();
+        assert!(is_tool_written(&path, &game_root).unwrap());
+        // replaced by something else since
+        fs::write(&path, b"other mod").unwrap();
+        assert!(!is_tool_written(&path, &game_root).unwrap());
+        assert_eq!(fs::read(backup).unwrap(), b"vanilla");
+    }
+
+    #[test]
+    fn test_natives_relative_path() {
+        let path = Path::new("/games/MonsterHunterWilds/natives/STM/Sound/Wwise/a.sbnk.1.X64");
+        assert_eq!(
+            natives_relative_path(path).unwrap(),
+            Path::new("natives/STM/Sound/Wwise/a.sbnk.1.X64")
+        );
+        assert_eq!(natives_relative_path("/mods/a.sbnk.1.X64"), None);
+        assert_eq!(natives_relative_path("/games/natives"), None);
+    }
+
+    #[test]
+    fn test_backup_vanilla() {
+        let game_dir = tempfile::tempdir().unwrap();
+        let backup_dir = tempfile::tempdir().unwrap();
+        let bundle = game_dir.path().join("natives/STM/Sound/a.sbnk.1.X64");
+        fs::create_dir_all(bundle.parent().unwrap()).unwrap();
+        fs::write(&bundle, b"original").unwrap();
+        assert_eq!(game_root(&bundle), None);
+
+        fs::write(game_dir.path().join("re_chunk_000.pak"), b"").unwrap();
+        let root = game_root(&bundle).unwrap();
+        assert_eq!(root, game_dir.path());
+        let backup = backup_vanilla(&bundle, &root, backup_dir.path()).unwrap();
+        assert_eq!(
+            backup,
+            backup_dir.path().join("natives/STM/Sound/a.sbnk.1.X64")
+        );
+        assert_eq!(fs::read(&backup).unwrap(), b"original");
+
+        // the backup stays the original file
+        fs::write(&bundle, b"modded").unwrap();
+        backup_vanilla(&bundle, &root, backup_dir.path()).unwrap();
+        assert_eq!(fs::read(&backup).unwrap(), b"original");
+    }
+}
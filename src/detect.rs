@@ -0,0 +1,232 @@
+use std::{io::Read, path::Path};
+
+/// Length of file header used for content detection.
+const SNIFF_LENGTH: usize = 64;
+
+/// Wwise specific codec IDs in `fmt ` chunk of wem files.
+const WEM_CODECS: &[u16] = &[
+    0x0002, // Wwise IMA ADPCM
+    0x3039, // Opus (old)
+    0x3040, // Opus
+    0x3041, // Opus WEM
+    0x8311, // PTADPCM
+    0xFFFF, // Vorbis
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputFileType {
+    Project,
+    GeneralAudio(&'static str),
+    Wem,
+    Bnk,
+    Pck,
+    /// Metadata file inside a project, e.g. `project.json`.
+    Metadata(&'static str),
+    /// Archive files which must be extracted first.
+    Archive(&'static str),
+}
+
+impl InputFileType {
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return None;
+        }
+        if path.is_dir() {
+            // check project.json
+            if path.join("project.json").is_file() {
+                return Some(InputFileType::Project);
+            } else {
+                return None;
+            }
+        }
+
+        // known metadata file names
+        let file_name = path.file_name().and_then(|name| name.to_str())?;
+        match file_name {
+            "project.json" => return Some(InputFileType::Metadata("project")),
+            "bank.json" => return Some(InputFileType::Metadata("bank")),
+            "pck.json" => return Some(InputFileType::Metadata("pck")),
+            "media.json" => return Some(InputFileType::Metadata("media")),
+            _ => {}
+        }
+
+        // content check
+        let mut header = Vec::with_capacity(SNIFF_LENGTH);
+        let file = std::fs::File::open(path).ok()?;
+        file.take(SNIFF_LENGTH as u64)
+            .read_to_end(&mut header)
+            .ok()?;
+        let file_ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+        if let Some(result) = Self::from_content(&header) {
+            // wem files with PCM codec cannot be distinguished from wav by content
+            if result == InputFileType::GeneralAudio("wav") && file_ext.as_deref() == Some("wem") {
+                return Some(InputFileType::Wem);
+            }
+            return Some(result);
+        }
+
+        // ext check
+        match file_ext?.as_str() {
+            "wav" => Some(InputFileType::GeneralAudio("wav")),
+            "ogg" => Some(InputFileType::GeneralAudio("ogg")),
+            "aac" => Some(InputFileType::GeneralAudio("aac")),
+            "flac" => Some(InputFileType::GeneralAudio("flac")),
+            "mp3" => Some(InputFileType::GeneralAudio("mp3")),
+            _ => None,
+        }
+    }
+
+    /// Detect file type from the leading bytes of a file.
+    pub fn from_content(header: &[u8]) -> Option<Self> {
+        let magic = header.get(0..4)?;
+        match magic {
+            b"BKHD" => return Some(InputFileType::Bnk),
+            b"AKPK" => return Some(InputFileType::Pck),
+            b"RIFX" => return Some(InputFileType::Wem),
+            b"RIFF" => {
+                if header.get(8..12)? != b"WAVE" {
+                    return None;
+                }
+                let codec = riff_codec(header);
+                return match codec {
+                    Some(codec) if WEM_CODECS.contains(&codec) => Some(InputFileType::Wem),
+                    _ => Some(InputFileType::GeneralAudio("wav")),
+                };
+            }
+            b"OggS" => return Some(InputFileType::GeneralAudio("ogg")),
+            b"fLaC" => return Some(InputFileType::GeneralAudio("flac")),
+            b"KPKA" => return Some(InputFileType::Archive("pak")),
+            b"PK\x03\x04" => return Some(InputFileType::Archive("zip")),
+            [0x37, 0x7A, 0xBC, 0xAF] => return Some(InputFileType::Archive("7z")),
+            _ => {}
+        }
+        if magic.starts_with(b"ID3") {
+            return Some(InputFileType::GeneralAudio("mp3"));
+        }
+        if header.get(4..8) == Some(b"ftyp".as_slice()) {
+            return Some(InputFileType::GeneralAudio("aac"));
+        }
+        // frame sync
+        if magic[0] == 0xFF && magic[1] & 0xE0 == 0xE0 {
+            let layer = (magic[1] >> 1) & 0b11;
+            return match layer {
+                // ADTS header of aac
+                0 if magic[1] & 0xF0 == 0xF0 => Some(InputFileType::GeneralAudio("aac")),
+                0 => None,
+                _ => Some(InputFileType::GeneralAudio("mp3")),
+            };
+        }
+        None
+    }
+
+    #[allow(clippy::match_like_matches_macro)]
+    pub fn similar_to(&self, other: &Self) -> bool {
+        match (self, other) {
+            (InputFileType::GeneralAudio(_), InputFileType::GeneralAudio(_)) => true,
+            (InputFileType::Wem, InputFileType::Wem) => true,
+            (InputFileType::Bnk, InputFileType::Bnk) => true,
+            (InputFileType::Pck, InputFileType::Pck) => true,
+            (InputFileType::Archive(a), InputFileType::Archive(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Describe what the tool does with this type of input.
+    pub fn action(&self) -> &'static str {
+        match self {
+            InputFileType::Project => "Repack the project into a bundle (package-project)",
+            InputFileType::GeneralAudio(_) => "Convert to wem (sound-to-wem)",
+            InputFileType::Wem => "No direct action, can be used as a replace file",
+            InputFileType::Bnk | InputFileType::Pck => {
+                "Unpack into a project folder (unpack-bundle)"
+            }
+            InputFileType::Metadata(_) => {
+                "No direct action, use the project folder containing it instead"
+            }
+            InputFileType::Archive("pak") => "Unpack bundles inside (unpack-bundle)",
+            InputFileType::Archive(_) => "Not supported, extract the archive first",
+        }
+    }
+}
+
+/// Find the codec ID in `fmt ` chunk of a RIFF header.
+fn riff_codec(header: &[u8]) -> Option<u16> {
+    let mut pos = 12;
+    while pos + 8 <= header.len() {
+        let chunk_id = &header[pos..pos + 4];
+        let chunk_size = u32::from_le_bytes(header[pos + 4..pos + 8].try_into().unwrap());
+        if chunk_id == b"fmt " {
+            let codec = header.get(pos + 8..pos + 10)?;
+            return Some(u16::from_le_bytes([codec[0], codec[1]]));
+        }
+        pos = pos.checked_add(8)?.checked_add(chunk_size as usize)?;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_test_files() {
+        let cases = [
+            ("test_files/Wp00_Cmn_m.sbnk.1.X64", InputFileType::Bnk),
+            ("test_files/Cat_cmn_m.spck.1.X64", InputFileType::Pck),
+            (
+                "test_files/test_sound.wav",
+                InputFileType::GeneralAudio("wav"),
+            ),
+            (
+                "test_files/test_sound.mp3",
+                InputFileType::GeneralAudio("mp3"),
+            ),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(InputFileType::from_path(path), Some(expected), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_detect_wem_content() {
+        let mut header = vec![];
+        header.extend_from_slice(b"RIFF\0\0\0\0WAVE");
+        header.extend_from_slice(b"fmt \x18\0\0\0");
+        header.extend_from_slice(&0xFFFFu16.to_le_bytes());
+        assert_eq!(
+            InputFileType::from_content(&header),
+            Some(InputFileType::Wem)
+        );
+    }
+
+    #[test]
+    fn test_detect_fuzz() {
+        // xorshift, deterministic
+        let mut state = 0x2545F4914F6CDD1Du64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let prefixes: [&[u8]; 6] = [
+            b"",
+            b"RIFF",
+            b"RIFF\0\0\0\0WAVE",
+            b"RIFF\0\0\0\0WAVEfmt ",
+            b"\xFF",
+            b"ID",
+        ];
+        for _ in 0..20000 {
+            let prefix = prefixes[(next() % prefixes.len() as u64) as usize];
+            let len = (next() % 40) as usize;
+            let mut data = prefix.to_vec();
+            data.extend((0..len).map(|_| next() as u8));
+            let _ = InputFileType::from_content(&data);
+        }
+    }
+}
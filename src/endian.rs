@@ -0,0 +1,100 @@
+//! Byte order of bundles.
+//!
+//! Wwise writes banks and packages in the byte order of the target platform.
+//! Little endian is the default, big endian bundles are detected from their
+//! version field and written back in the same order.
+
+use std::io;
+
+use byteorder::{BE, LE, ReadBytesExt, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+
+/// Largest version number of a plausible bundle header.
+const MAX_VERSION: u32 = 0xFFFF;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+impl Endian {
+    /// Byte order of a header by its version field, which is a small number
+    /// only when read in the right order. Little endian if both look right.
+    pub fn detect(version: [u8; 4]) -> Self {
+        if u32::from_le_bytes(version) > MAX_VERSION && u32::from_be_bytes(version) <= MAX_VERSION {
+            Endian::Big
+        } else {
+            Endian::Little
+        }
+    }
+
+    /// Byte order of a header with the version field at `offset`, keeping
+    /// the stream position. Little endian if the stream is too short.
+    pub fn detect_at<R>(reader: &mut R, offset: u64) -> io::Result<Self>
+    where
+        R: io::Read + io::Seek,
+    {
+        let position = reader.stream_position()?;
+        reader.seek(io::SeekFrom::Start(position + offset))?;
+        let mut version = [0; 4];
+        let endian = match reader.read_exact(&mut version) {
+            Ok(()) => Self::detect(version),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Endian::Little,
+            Err(e) => return Err(e),
+        };
+        reader.seek(io::SeekFrom::Start(position))?;
+        Ok(endian)
+    }
+
+    pub fn is_little(&self) -> bool {
+        *self == Endian::Little
+    }
+
+    pub fn read_u16<R: io::Read>(self, reader: &mut R) -> io::Result<u16> {
+        match self {
+            Endian::Little => reader.read_u16::<LE>(),
+            Endian::Big => reader.read_u16::<BE>(),
+        }
+    }
+
+    pub fn read_u32<R: io::Read>(self, reader: &mut R) -> io::Result<u32> {
+        match self {
+            Endian::Little => reader.read_u32::<LE>(),
+            Endian::Big => reader.read_u32::<BE>(),
+        }
+    }
+
+    pub fn write_u16<W: io::Write>(self, writer: &mut W, value: u16) -> io::Result<()> {
+        match self {
+            Endian::Little => writer.write_u16::<LE>(value),
+            Endian::Big => writer.write_u16::<BE>(value),
+        }
+    }
+
+    pub fn write_u32<W: io::Write>(self, writer: &mut W, value: u32) -> io::Result<()> {
+        match self {
+            Endian::Little => writer.write_u32::<LE>(value),
+            Endian::Big => writer.write_u32::<BE>(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Endian::detect(145u32.to_le_bytes()), Endian::Little);
+        assert_eq!(Endian::detect(145u32.to_be_bytes()), Endian::Big);
+        assert_eq!(Endian::detect([0; 4]), Endian::Little);
+
+        let mut reader = io::Cursor::new([&b"AKPK"[..], &[0; 4], &1u32.to_be_bytes()].concat());
+        assert_eq!(Endian::detect_at(&mut reader, 8).unwrap(), Endian::Big);
+        assert_eq!(reader.position(), 0);
+        assert_eq!(Endian::detect_at(&mut reader, 12).unwrap(), Endian::Little);
+    }
+}
@@ -33,6 +33,7 @@ impl FFmpegError {
     }
 }
 
+#[derive(Clone)]
 pub struct FFmpegCli {
     program_path: PathBuf,
 }
@@ -80,9 +81,30 @@ impl FFmpegCli {
         input: impl AsRef<Path>,
         output: impl AsRef<Path>,
     ) -> Result<()> {
-        let input = input.as_ref();
-        let output = output.as_ref();
+        self.transcode(input.as_ref(), output.as_ref(), &[])
+    }
+
+    /// Transcode with an audio filter graph, e.g. `alimiter=limit=0.9`.
+    pub fn transcode_with_filter(
+        &self,
+        input: impl AsRef<Path>,
+        output: impl AsRef<Path>,
+        filter: &str,
+    ) -> Result<()> {
+        self.transcode(input.as_ref(), output.as_ref(), &["-af", filter])
+    }
+
+    /// Transcode with output options, e.g. `["-b:a", "192k"]`.
+    pub fn transcode_with_args(
+        &self,
+        input: impl AsRef<Path>,
+        output: impl AsRef<Path>,
+        args: &[&str],
+    ) -> Result<()> {
+        self.transcode(input.as_ref(), output.as_ref(), args)
+    }
 
+    fn transcode(&self, input: &Path, output: &Path, extra_args: &[&str]) -> Result<()> {
         let program_path: &Path = self.program_path.as_ref();
         let result = Command::new(program_path)
             .args([
@@ -91,9 +113,9 @@ impl FFmpegCli {
                 "warning",
                 "-i",
                 input.to_str().unwrap(),
-                "-y",
-                output.to_str().unwrap(),
             ])
+            .args(extra_args)
+            .args(["-y", output.to_str().unwrap()])
             .output()
             .map_err(FFmpegError::CommandExecutionFailed)?;
 
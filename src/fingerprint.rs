@@ -0,0 +1,116 @@
+use std::{fs::File, io::Read, path::Path, time::UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{deploy, endian::Endian, utils};
+
+type Result<T> = std::result::Result<T, FingerprintError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum FingerprintError {
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("Not a BNK or PCK file.")]
+    UnknownFormat,
+}
+
+/// Identity of a bundle file, used to detect bundles changed by game updates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleFingerprint {
+    /// Format version in BKHD or AKPK header.
+    pub version: u32,
+    pub size: u64,
+    /// SHA-256 of the whole file in hex.
+    pub sha256: String,
+    /// Modified time in seconds since Unix epoch, restored on output by
+    /// `timestamp = "original"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BundleChange {
+    Unchanged,
+    /// Same format version but different content.
+    Modified,
+    VersionChanged {
+        from: u32,
+        to: u32,
+    },
+}
+
+impl BundleFingerprint {
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        // both BKHD and AKPK headers: magic, length, version
+        let mut header = [0u8; 12];
+        File::open(path)?.read_exact(&mut header)?;
+        if &header[0..4] != b"BKHD" && &header[0..4] != b"AKPK" {
+            return Err(FingerprintError::UnknownFormat);
+        }
+        let version_bytes: [u8; 4] = header[8..12].try_into().unwrap();
+        let version = Endian::detect(version_bytes).read_u32(&mut &version_bytes[..])?;
+
+        let metadata = path.metadata()?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs());
+
+        Ok(Self {
+            version,
+            size: metadata.len(),
+            sha256: utils::to_hex(&deploy::file_sha256(path)?),
+            modified,
+        })
+    }
+
+    /// Compare with the fingerprint of a bundle found now.
+    pub fn compare(&self, current: &Self) -> BundleChange {
+        if self.version != current.version {
+            BundleChange::VersionChanged {
+                from: self.version,
+                to: current.version,
+            }
+        } else if self.sha256 != current.sha256 || self.size != current.size {
+            BundleChange::Modified
+        } else {
+            BundleChange::Unchanged
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint() {
+        let bnk = BundleFingerprint::from_path("test_files/Wp00_Cmn_m.sbnk.1.X64").unwrap();
+        assert_eq!(bnk.version, 145);
+        assert_eq!(bnk.compare(&bnk.clone()), BundleChange::Unchanged);
+        let pck = BundleFingerprint::from_path("test_files/Cat_cmn_m.spck.1.X64").unwrap();
+        let changed = BundleFingerprint {
+            version: bnk.version,
+            ..pck.clone()
+        };
+        assert_eq!(bnk.compare(&changed), BundleChange::Modified);
+        assert_eq!(
+            pck.compare(&bnk),
+            BundleChange::VersionChanged { from: 1, to: 145 }
+        );
+        assert!(BundleFingerprint::from_path("test_files/test_sound.wav").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let big_endian = dir.path().join("Big.sbnk.1.X64");
+        let mut data = b"BKHD".to_vec();
+        data.extend(8u32.to_be_bytes());
+        data.extend(145u32.to_be_bytes());
+        data.extend([0; 4]);
+        std::fs::write(&big_endian, data).unwrap();
+        let big_endian = BundleFingerprint::from_path(&big_endian).unwrap();
+        assert_eq!(big_endian.version, 145);
+    }
+}
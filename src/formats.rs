@@ -0,0 +1,93 @@
+//! Formats of dumped entries, stored as `formats.json` in project folder.
+//!
+//! Codec, sample rate and channels of the original wems are recorded on dump,
+//! replace files are converted to match them unless told otherwise:
+//!
+//! ```json
+//! { "500001": { "codec": "vorbis", "sample_rate": 48000, "channels": 2, "bitrate": 96 } }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::wwise::WemFormat;
+
+type Result<T> = std::result::Result<T, FormatsError>;
+
+pub const FORMATS_FILE_NAME: &str = "formats.json";
+
+#[derive(Debug, thiserror::Error)]
+pub enum FormatsError {
+    #[error("Failed to access {}: {source}", path.display())]
+    File { path: PathBuf, source: io::Error },
+
+    #[error("Bad entry formats file {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+}
+
+impl FormatsError {
+    /// Attach the path to an IO error.
+    fn file(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| FormatsError::File { path, source }
+    }
+}
+
+/// Formats of original wems by entry ID.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct EntryFormats {
+    formats: BTreeMap<u32, WemFormat>,
+}
+
+impl EntryFormats {
+    /// Load from project folder, `None` for projects dumped before it was recorded.
+    pub fn load(project_path: impl AsRef<Path>) -> Result<Option<Self>> {
+        let path = project_path.as_ref().join(FORMATS_FILE_NAME);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = fs::read(&path).map_err(FormatsError::file(&path))?;
+        serde_json::from_slice(&content)
+            .map(Some)
+            .map_err(|source| FormatsError::Parse { path, source })
+    }
+
+    pub fn save(&self, project_path: impl AsRef<Path>) -> Result<()> {
+        let path = project_path.as_ref().join(FORMATS_FILE_NAME);
+        let content = serde_json::to_vec_pretty(self).map_err(|source| FormatsError::Parse {
+            path: path.clone(),
+            source,
+        })?;
+        fs::write(&path, content).map_err(FormatsError::file(&path))?;
+        Ok(())
+    }
+
+    /// Record the format of an entry, the first one is kept for entries of
+    /// the same ID. Wems of unknown codecs are not recorded.
+    pub fn add(&mut self, id: u32, wem: &[u8]) {
+        if let Some(format) = WemFormat::detect(wem) {
+            self.formats.entry(id).or_insert(format);
+        }
+    }
+
+    pub fn get(&self, id: u32) -> Option<&WemFormat> {
+        self.formats.get(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.formats.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.formats.is_empty()
+    }
+}
@@ -0,0 +1,526 @@
+//! Graphical frontend for unpacking bundles, assigning replace files and
+//! repacking, built with the `gui` feature.
+//!
+//! Long operations run on a worker thread, their logs still go to the console.
+//! Selections are saved to the session file of the project as they change,
+//! and offered to resume when the project is opened again.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use eframe::egui;
+use mhws_sound_tool::{
+    config::{Config, PathsConfig},
+    detect::InputFileType,
+    project::{self, IdOrIndex, MediaEntry, SoundToolProject},
+    session::Session,
+};
+
+const REPLACE_DIR: &str = "replace";
+
+pub fn run() -> eyre::Result<()> {
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("MHWS Sound Tool")
+            .with_inner_size([900.0, 600.0])
+            .with_drag_and_drop(true),
+        ..Default::default()
+    };
+    eframe::run_native(
+        "MHWS Sound Tool",
+        options,
+        Box::new(|_cc| Ok(Box::new(SoundToolApp::new()))),
+    )
+    .map_err(|e| eyre::eyre!("Failed to start GUI: {}", e))
+}
+
+/// Result of a worker task.
+enum TaskResult {
+    Unpacked(PathBuf),
+    Repacked(PathBuf),
+    Failed(String),
+}
+
+/// Project opened in the window.
+struct OpenProject {
+    project: SoundToolProject,
+    entries: Vec<MediaEntry>,
+    /// Replace files in `replace/` by target.
+    replacements: HashMap<IdOrIndex, PathBuf>,
+}
+
+impl OpenProject {
+    fn load(path: &Path) -> eyre::Result<Self> {
+        let project = SoundToolProject::from_path(path)?;
+        let entries = project.media_entries()?;
+        let mut open = Self {
+            project,
+            entries,
+            replacements: HashMap::new(),
+        };
+        open.reload_replacements()?;
+        Ok(open)
+    }
+
+    fn reload_replacements(&mut self) -> eyre::Result<()> {
+        self.replacements = project::replace_files(self.project.project_path())?;
+        Ok(())
+    }
+
+    /// Replace file of an entry, by ID or by index, relative to `replace/`.
+    fn replacement(&self, entry: &MediaEntry) -> Option<String> {
+        let replace_root = self.project.project_path().join(REPLACE_DIR);
+        self.replacements
+            .get(&IdOrIndex::Id(entry.id))
+            .or_else(|| self.replacements.get(&IdOrIndex::Index(entry.idx)))
+            .map(|path| {
+                let relative = path.strip_prefix(&replace_root).unwrap_or(path);
+                relative.to_string_lossy().replace('\\', "/")
+            })
+    }
+
+    /// Position of the entry with index `idx` and ID `id` in `entries`.
+    fn position(&self, (idx, id): (u32, u32)) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| entry.idx == idx && entry.id == id)
+    }
+
+    /// Replace target of an entry, by index if another entry shares its ID,
+    /// as in PCK files with the same sound in several languages.
+    fn target(&self, entry: &MediaEntry) -> IdOrIndex {
+        let shared = self
+            .entries
+            .iter()
+            .any(|other| other.id == entry.id && other.idx != entry.idx);
+        if shared {
+            IdOrIndex::Index(entry.idx)
+        } else {
+            IdOrIndex::Id(entry.id)
+        }
+    }
+
+    /// Copy a sound file to `replace/<id>.<ext>`, or `replace/[<index>].<ext>`
+    /// if the ID is shared, removing former replace files of the entry.
+    fn assign(&mut self, entry_pos: usize, source: &Path) -> eyre::Result<()> {
+        let Some(InputFileType::GeneralAudio(_) | InputFileType::Wem) =
+            InputFileType::from_path(source)
+        else {
+            eyre::bail!("Unsupported replace file: {}", source.display());
+        };
+        self.unassign(entry_pos)?;
+        let replace_root = self.project.project_path().join(REPLACE_DIR);
+        fs::create_dir_all(&replace_root)?;
+        let ext = source.extension().unwrap_or_default().to_string_lossy();
+        let file_name = format!(
+            "{}.{}",
+            self.target(&self.entries[entry_pos]),
+            ext.to_ascii_lowercase()
+        );
+        fs::copy(source, replace_root.join(&file_name))?;
+        log::info!("Replace: {} -> {}", source.display(), file_name);
+        self.reload_replacements()
+    }
+
+    /// Remove replace files of an entry, a file by shared ID is kept for
+    /// the other entries.
+    fn unassign(&mut self, entry_pos: usize) -> eyre::Result<()> {
+        let entry = &self.entries[entry_pos];
+        let mut targets = vec![IdOrIndex::Index(entry.idx)];
+        if self.target(entry) == IdOrIndex::Id(entry.id) {
+            targets.push(IdOrIndex::Id(entry.id));
+        }
+        for target in targets {
+            if let Some(path) = self.replacements.get(&target) {
+                fs::remove_file(path)?;
+            }
+        }
+        self.reload_replacements()
+    }
+}
+
+/// Saved session of a project, not resumed yet.
+struct ResumeOffer {
+    project_path: PathBuf,
+    session: Session,
+}
+
+#[derive(Default)]
+struct SoundToolApp {
+    project: Option<OpenProject>,
+    /// Index and ID of the selected entry.
+    selected: Option<(u32, u32)>,
+    session: Session,
+    resume: Option<ResumeOffer>,
+    task: Option<Receiver<TaskResult>>,
+    status: String,
+}
+
+impl SoundToolApp {
+    /// Offers to resume the session of the project opened last.
+    fn new() -> Self {
+        let mut app = Self::default();
+        let last_project = Config::global().lock().gui.last_project.clone();
+        if let Some(project_path) = last_project.map(PathBuf::from) {
+            app.resume = saved_session(&project_path).map(|session| ResumeOffer {
+                project_path,
+                session,
+            });
+        }
+        app
+    }
+
+    fn open_project(&mut self, path: &Path) {
+        match OpenProject::load(path) {
+            Ok(project) => {
+                let project_path = project.project.project_path().to_path_buf();
+                self.status = format!(
+                    "Opened {} ({} entries).",
+                    project_path.display(),
+                    project.entries.len()
+                );
+                self.project = Some(project);
+                self.selected = None;
+                self.session = Session::default();
+                self.resume = saved_session(&project_path).map(|session| ResumeOffer {
+                    project_path: project_path.clone(),
+                    session,
+                });
+                let mut config = Config::global().lock();
+                config.gui.last_project = Some(project_path.to_string_lossy().to_string());
+                config.save();
+            }
+            Err(e) => self.status = format!("Failed to open project: {:#}", e),
+        }
+    }
+
+    /// Open the project of the offered session if needed, and restore its selections.
+    fn resume_session(&mut self) {
+        let Some(offer) = self.resume.take() else {
+            return;
+        };
+        let is_open = self
+            .project
+            .as_ref()
+            .is_some_and(|project| project.project.project_path() == offer.project_path);
+        if !is_open {
+            self.open_project(&offer.project_path);
+            self.resume = None;
+        }
+        let Some(project) = &self.project else {
+            return;
+        };
+        self.selected = offer.session.selected.and_then(|idx| {
+            project
+                .entries
+                .iter()
+                .find(|entry| entry.idx == idx)
+                .map(|entry| (entry.idx, entry.id))
+        });
+        self.session = offer.session;
+        self.status = format!(
+            "Resumed session, {} of {} entries done.",
+            self.session.done.len(),
+            project.entries.len()
+        );
+    }
+
+    /// Discard the offered session.
+    fn start_over(&mut self) {
+        if let Some(offer) = self.resume.take()
+            && let Err(e) = Session::remove(&offer.project_path)
+        {
+            self.status = format!("Failed to remove session: {}", e);
+        }
+    }
+
+    fn select(&mut self, pos: usize) {
+        let Some(project) = &self.project else {
+            return;
+        };
+        let entry = &project.entries[pos];
+        self.selected = Some((entry.idx, entry.id));
+        self.session.selected = Some(entry.idx);
+        self.save_session();
+    }
+
+    fn toggle_done(&mut self, pos: usize) {
+        let Some(project) = &self.project else {
+            return;
+        };
+        let idx = project.entries[pos].idx;
+        if !self.session.done.remove(&idx) {
+            self.session.done.insert(idx);
+        }
+        self.save_session();
+    }
+
+    fn save_session(&mut self) {
+        // a saved session is kept until it is resumed or discarded
+        let (Some(project), None) = (&self.project, &self.resume) else {
+            return;
+        };
+        if let Err(e) = self.session.save(project.project.project_path()) {
+            self.status = format!("Failed to save session: {}", e);
+        }
+    }
+
+    fn handle_dropped(&mut self, ctx: &egui::Context, paths: Vec<PathBuf>) {
+        for path in paths {
+            match InputFileType::from_path(&path) {
+                Some(InputFileType::Project) => self.open_project(&path),
+                Some(InputFileType::Bnk | InputFileType::Pck) => self.start_unpack(ctx, path),
+                Some(InputFileType::GeneralAudio(_) | InputFileType::Wem) => {
+                    let Some((project, selected)) = self.project.as_mut().and_then(|project| {
+                        let pos = project.position(self.selected?)?;
+                        Some((project, pos))
+                    }) else {
+                        self.status = "Select an entry to replace first.".to_string();
+                        continue;
+                    };
+                    self.status = match project.assign(selected, &path) {
+                        Ok(()) => format!("Assigned {}.", path.display()),
+                        Err(e) => format!("Failed to assign replace file: {:#}", e),
+                    };
+                }
+                _ => self.status = format!("Unsupported file: {}", path.display()),
+            }
+        }
+    }
+
+    fn is_busy(&self) -> bool {
+        self.task.is_some()
+    }
+
+    /// Run `task` on a worker thread, its result is polled every frame.
+    fn spawn(
+        &mut self,
+        ctx: &egui::Context,
+        status: String,
+        task: impl FnOnce() -> TaskResult + Send + 'static,
+    ) {
+        if self.is_busy() {
+            self.status = "Another task is running.".to_string();
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = sender.send(task());
+            ctx.request_repaint();
+        });
+        self.task = Some(receiver);
+        self.status = status;
+    }
+
+    fn start_unpack(&mut self, ctx: &egui::Context, input: PathBuf) {
+        let output_root = output_dir(PathsConfig::PROJECTS_DIR, &input);
+        self.spawn(
+            ctx,
+            format!("Unpacking {}...", input.display()),
+            move || match mhws_sound_tool::dump(&input, &output_root) {
+                Ok(project) => TaskResult::Unpacked(project.project_path().to_path_buf()),
+                Err(e) => TaskResult::Failed(format!("Failed to unpack: {}", e)),
+            },
+        );
+    }
+
+    fn start_repack(&mut self, ctx: &egui::Context) {
+        let Some(project) = &self.project else {
+            return;
+        };
+        let project_path = project.project.project_path().to_path_buf();
+        let output_root = output_dir(PathsConfig::BUNDLES_DIR, &project_path);
+        self.spawn(
+            ctx,
+            "Repacking...".to_string(),
+            move || match mhws_sound_tool::repack(&project_path, &output_root) {
+                Ok(stats) => {
+                    stats.log_summary();
+                    TaskResult::Repacked(stats.output_path)
+                }
+                Err(e) => TaskResult::Failed(format!("Failed to repack: {}", e)),
+            },
+        );
+    }
+
+    fn poll_task(&mut self) {
+        let Some(receiver) = &self.task else {
+            return;
+        };
+        let Ok(result) = receiver.try_recv() else {
+            return;
+        };
+        self.task = None;
+        match result {
+            TaskResult::Unpacked(project_path) => self.open_project(&project_path),
+            TaskResult::Repacked(output_path) => {
+                self.status = format!("Repacked to {}.", output_path.display());
+            }
+            TaskResult::Failed(message) => self.status = message,
+        }
+    }
+
+    fn entry_table(&mut self, ui: &mut egui::Ui) {
+        let Some(project) = &mut self.project else {
+            ui.centered_and_justified(|ui| {
+                ui.label("Drop a BNK or PCK file to unpack, or a project folder to open.");
+            });
+            return;
+        };
+        let mut unassign = None;
+        let mut select = None;
+        let mut toggle_done = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("entries")
+                .num_columns(6)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.strong("Done");
+                    ui.strong("Index");
+                    ui.strong("ID");
+                    ui.strong("Language");
+                    ui.strong("Replace file");
+                    ui.label("");
+                    ui.end_row();
+                    for (pos, entry) in project.entries.iter().enumerate() {
+                        let mut done = self.session.done.contains(&entry.idx);
+                        if ui.checkbox(&mut done, "").changed() {
+                            toggle_done = Some(pos);
+                        }
+                        let selected = self.selected == Some((entry.idx, entry.id));
+                        if ui
+                            .selectable_label(selected, format!("[{}]", entry.idx))
+                            .clicked()
+                        {
+                            select = Some(pos);
+                        }
+                        ui.label(entry.id.to_string());
+                        ui.label(entry.language.as_deref().unwrap_or("-"));
+                        match project.replacement(entry) {
+                            Some(file_name) => {
+                                ui.label(file_name);
+                                if ui.small_button("Remove").clicked() {
+                                    unassign = Some(pos);
+                                }
+                            }
+                            None => {
+                                ui.label("-");
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+        if let Some(pos) = unassign
+            && let Err(e) = project.unassign(pos)
+        {
+            self.status = format!("Failed to remove replace file: {:#}", e);
+        }
+        if let Some(pos) = select {
+            self.select(pos);
+        }
+        if let Some(pos) = toggle_done {
+            self.toggle_done(pos);
+        }
+    }
+
+    fn resume_bar(&mut self, ui: &mut egui::Ui) {
+        let Some(offer) = &self.resume else {
+            return;
+        };
+        let selected = offer
+            .session
+            .selected
+            .map(|idx| format!(", at entry [{}]", idx))
+            .unwrap_or_default();
+        let text = format!(
+            "Unfinished session of {}: {} entries done{}.",
+            offer.project_path.display(),
+            offer.session.done.len(),
+            selected
+        );
+        let (resume, start_over) = ui
+            .horizontal(|ui| {
+                ui.label(text);
+                (
+                    ui.button("Resume").clicked(),
+                    ui.button("Start over").clicked(),
+                )
+            })
+            .inner;
+        if resume {
+            self.resume_session();
+        } else if start_over {
+            self.start_over();
+        }
+    }
+}
+
+impl eframe::App for SoundToolApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.poll_task();
+        let dropped: Vec<PathBuf> = ctx.input(|input| {
+            input
+                .raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| file.path.clone())
+                .collect()
+        });
+        if !dropped.is_empty() {
+            self.handle_dropped(ctx, dropped);
+        }
+
+        egui::TopBottomPanel::top("toolbar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let can_repack = self.project.is_some() && !self.is_busy();
+                if ui
+                    .add_enabled(can_repack, egui::Button::new("Repack"))
+                    .clicked()
+                {
+                    self.start_repack(ctx);
+                }
+                if let Some(project) = &self.project {
+                    ui.label(project.project.project_path().display().to_string());
+                }
+            });
+            self.resume_bar(ui);
+        });
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if self.is_busy() {
+                    ui.spinner();
+                }
+                ui.label(&self.status);
+            });
+            ui.small("Select an entry and drop a sound file onto the window to replace it.");
+        });
+        egui::CentralPanel::default().show(ctx, |ui| self.entry_table(ui));
+    }
+}
+
+/// Saved session of a project, `None` if there is nothing to resume.
+fn saved_session(project_path: &Path) -> Option<Session> {
+    match Session::load(project_path) {
+        Ok(session) => session.filter(|session| !session.is_empty()),
+        Err(e) => {
+            log::warn!("Failed to load session: {}", e);
+            None
+        }
+    }
+}
+
+/// Output directory of a kind, from config or next to `input`.
+fn output_dir(kind: &str, input: &Path) -> PathBuf {
+    Config::global()
+        .lock()
+        .paths
+        .output_dir(kind)
+        .unwrap_or_else(|| input.parent().unwrap_or(Path::new(".")).to_path_buf())
+}
@@ -0,0 +1,253 @@
+//! SHA-256 checksums of released files, published as `hashes.txt`.
+//!
+//! The format is the same as `sha256sum`, one `<hash>  <file name>` per line,
+//! so downloads can also be checked with `sha256sum -c hashes.txt`.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{deploy, utils};
+
+type Result<T> = std::result::Result<T, HashesError>;
+
+pub const HASHES_FILE_NAME: &str = "hashes.txt";
+
+#[derive(Debug, thiserror::Error)]
+pub enum HashesError {
+    #[error("Failed to access {}: {source}", path.display())]
+    File { path: PathBuf, source: io::Error },
+
+    #[error("Bad line {line} in {path}: expected '<sha256>  <file name>'")]
+    BadLine { path: PathBuf, line: usize },
+    #[error("{0} is not in the directory of hashes file")]
+    OutsideDirectory(PathBuf),
+    #[error("Bad file name '{name}' at line {line} in {path}: not a file in its directory")]
+    UnsafeFileName {
+        path: PathBuf,
+        line: usize,
+        name: String,
+    },
+}
+
+impl HashesError {
+    /// Attach the path to an IO error.
+    fn file(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| HashesError::File { path, source }
+    }
+}
+
+/// Result of checking a file against its recorded hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HashStatus {
+    Ok,
+    Mismatch,
+    Missing,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HashCheck {
+    pub file_name: String,
+    pub status: HashStatus,
+}
+
+/// Hashes by file name relative to the directory of `hashes.txt`.
+#[derive(Debug, Clone, Default)]
+pub struct HashList {
+    hashes: BTreeMap<String, String>,
+}
+
+impl HashList {
+    /// Load a hashes file, empty if it does not exist.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let mut list = Self::default();
+        for (i, line) in fs::read_to_string(path)
+            .map_err(HashesError::file(path))?
+            .lines()
+            .enumerate()
+        {
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+            // `*` marks binary mode of sha256sum
+            let (hash, file_name) = line
+                .split_once(' ')
+                .map(|(hash, name)| (hash, name.trim_start_matches([' ', '*'])))
+                .filter(|(hash, name)| {
+                    hash.len() == 64
+                        && hash.chars().all(|c| c.is_ascii_hexdigit())
+                        && !name.is_empty()
+                })
+                .ok_or_else(|| HashesError::BadLine {
+                    path: path.to_path_buf(),
+                    line: i + 1,
+                })?;
+            // names are joined onto the directory when verifying
+            if !is_plain_file_name(file_name) {
+                return Err(HashesError::UnsafeFileName {
+                    path: path.to_path_buf(),
+                    line: i + 1,
+                    name: file_name.to_string(),
+                });
+            }
+            list.hashes
+                .insert(file_name.to_string(), hash.to_ascii_lowercase());
+        }
+        Ok(list)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let content: String = self
+            .hashes
+            .iter()
+            .map(|(file_name, hash)| format!("{}  {}\n", hash, file_name))
+            .collect();
+        fs::write(path, content).map_err(HashesError::file(path))
+    }
+
+    /// Hash a file in `root` and record it by its file name.
+    pub fn add_file(&mut self, root: impl AsRef<Path>, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let file_name = path
+            .strip_prefix(root)
+            .ok()
+            .map(|relative| relative.to_string_lossy().to_string())
+            .filter(|file_name| is_plain_file_name(file_name))
+            .ok_or_else(|| HashesError::OutsideDirectory(path.to_path_buf()))?;
+        let hash = utils::to_hex(&deploy::file_sha256(path).map_err(HashesError::file(path))?);
+        self.hashes.insert(file_name, hash);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Check recorded files under `root`.
+    pub fn verify(&self, root: impl AsRef<Path>) -> Result<Vec<HashCheck>> {
+        let root = root.as_ref();
+        let mut checks = vec![];
+        for (file_name, hash) in &self.hashes {
+            let path = root.join(file_name);
+            let status = if !path.is_file() {
+                HashStatus::Missing
+            } else if utils::to_hex(&deploy::file_sha256(&path).map_err(HashesError::file(&path))?)
+                == *hash
+            {
+                HashStatus::Ok
+            } else {
+                HashStatus::Mismatch
+            };
+            checks.push(HashCheck {
+                file_name: file_name.clone(),
+                status,
+            });
+        }
+        Ok(checks)
+    }
+}
+
+/// Whether a recorded name is a file directly in the directory, without
+/// separators or `..`.
+fn is_plain_file_name(name: &str) -> bool {
+    !name.contains(['/', '\\'])
+        && matches!(
+            Path::new(name).components().collect::<Vec<_>>()[..],
+            [Component::Normal(_)]
+        )
+}
+
+/// Add files to the hashes file in their directory, returns its path.
+///
+/// Files already recorded get their hashes updated.
+pub fn update_hashes(dir: impl AsRef<Path>, files: &[impl AsRef<Path>]) -> Result<PathBuf> {
+    let dir = dir.as_ref();
+    let path = dir.join(HASHES_FILE_NAME);
+    let mut list = HashList::load(&path)?;
+    for file in files {
+        list.add_file(dir, file)?;
+    }
+    list.save(&path)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashes_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let bundle = dir.path().join("Wp00_Cmn.sbnk.1.X64");
+        let sidecar = dir.path().join("Wp00_Cmn.sbnk.1.X64.modinfo.json");
+        fs::write(&bundle, b"bundle").unwrap();
+        fs::write(&sidecar, b"{}").unwrap();
+
+        let path = update_hashes(dir.path(), &[&bundle]).unwrap();
+        update_hashes(dir.path(), &[&sidecar]).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with(
+            "1e6ed65d77d6364eeaed5a745ba5c4985ae2b700dd85d7cf7f027bdf294a33fc  Wp00_Cmn.sbnk.1.X64\n"
+        ));
+
+        let list = HashList::load(&path).unwrap();
+        assert_eq!(list.len(), 2);
+        let statuses = || -> Vec<HashStatus> {
+            list.verify(dir.path())
+                .unwrap()
+                .into_iter()
+                .map(|check| check.status)
+                .collect()
+        };
+        assert_eq!(statuses(), [HashStatus::Ok, HashStatus::Ok]);
+
+        fs::write(&bundle, b"tampered").unwrap();
+        fs::remove_file(&sidecar).unwrap();
+        assert_eq!(statuses(), [HashStatus::Mismatch, HashStatus::Missing]);
+
+        let hash = "0".repeat(64);
+        for name in [
+            "../secret.txt",
+            "sub/file",
+            "sub\\file",
+            "..",
+            "/etc/passwd",
+        ] {
+            fs::write(&path, format!("{}  {}\n", hash, name)).unwrap();
+            assert!(
+                matches!(
+                    HashList::load(&path),
+                    Err(HashesError::UnsafeFileName { line: 1, .. })
+                ),
+                "{}",
+                name
+            );
+        }
+        assert!(matches!(
+            HashList::default().add_file(dir.path(), dir.path().join("sub").join("file")),
+            Err(HashesError::OutsideDirectory(_))
+        ));
+
+        let missing = dir.path().join("missing").join(HASHES_FILE_NAME);
+        assert!(matches!(
+            HashList::default().save(&missing),
+            Err(HashesError::File { path, .. }) if path == missing
+        ));
+    }
+}
@@ -0,0 +1,1888 @@
+//! Analyze and patch HIRC objects of sound banks.
+//!
+//! Only the common `NodeBaseParams` head of hierarchy objects is parsed,
+//! which is enough to follow the parent / bus routing and edit properties.
+//! The rest of the object data is kept untouched.
+//!
+//! [`HircObject`] is a typed view of common object types written to
+//! `bank.json`, with unparsed parts kept as raw bytes. Objects which do not
+//! encode back to identical bytes are kept raw as a whole.
+//!
+//! Object layouts changed between Wwise versions, the parsed parts are read
+//! by the [`HircLayout`] of the bank version.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{self, Read},
+};
+
+use byteorder::{LE, ReadBytesExt, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bnk::{Bnk, HircEntry, SectionPayload},
+    metadiff::ChangeKind,
+    utils,
+};
+
+type Result<T> = std::result::Result<T, HircError>;
+
+pub const HIRC_SOUND: u8 = 2;
+pub const HIRC_ACTION: u8 = 3;
+pub const HIRC_EVENT: u8 = 4;
+pub const HIRC_RANDOM_SEQUENCE_CONTAINER: u8 = 5;
+pub const HIRC_SWITCH_CONTAINER: u8 = 6;
+pub const HIRC_ACTOR_MIXER: u8 = 7;
+pub const HIRC_LAYER_CONTAINER: u8 = 9;
+
+/// Volume property, f32 in dB.
+pub const PROP_VOLUME: u8 = 0x00;
+/// Pitch property, f32 in cents.
+pub const PROP_PITCH: u8 = 0x02;
+/// Loop property of sounds in the `V137` layout, u32 count of plays,
+/// 0 for infinite. Sounds without it play once. Property IDs were renumbered
+/// between versions, see [`HircLayout::loop_prop`].
+pub const PROP_LOOP: u8 = 0x3A;
+
+/// Stream type of sources with media in the bank, others are streamed or
+/// prefetched.
+pub const STREAM_TYPE_IN_MEMORY: u8 = 0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum HircError {
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("Failed to parse HIRC object {id}: {source}")]
+    Parse { id: u32, source: io::Error },
+    #[error(
+        "HIRC object {id} has the {layout:?} layout of an older bank version, which is not verified for editing."
+    )]
+    UnverifiedLayout { id: u32, layout: HircLayout },
+    #[error("Sounds of {target} are in several containers: {containers:?}, target one of them.")]
+    AmbiguousContainer { target: u32, containers: Vec<u32> },
+}
+
+/// Layout of HIRC objects, by the bank version in BKHD.
+///
+/// Only differences in the parts parsed by this module are told apart:
+///
+/// | Layout | Versions | Event action count | Effect slot flags | Metadata params |
+/// |--------|----------|--------------------|-------------------|-----------------|
+/// | `V89`  | 89-122   | u32                | 2 bytes           | no              |
+/// | `V123` | 123-136  | variable length    | 2 bytes           | no              |
+/// | `V137` | 137-     | variable length    | 1 byte            | yes             |
+///
+/// Objects of older banks are kept raw. Only `V137`, the layout of the game's
+/// banks, is checked against real banks, properties are not edited in others
+/// and random / sequence containers, whose children follow the whole
+/// `NodeBaseParams`, are kept raw.
+/// `AkBankSourceData` of sounds has 14 bytes from `V123`, sounds of `V89`
+/// banks carry file fields in some of its versions and are kept raw.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum HircLayout {
+    /// Before version 89, objects are not parsed.
+    Legacy,
+    V89,
+    V123,
+    #[default]
+    V137,
+}
+
+impl HircLayout {
+    pub fn for_version(version: u32) -> Self {
+        match version {
+            ..89 => HircLayout::Legacy,
+            89..123 => HircLayout::V89,
+            123..137 => HircLayout::V123,
+            _ => HircLayout::V137,
+        }
+    }
+
+    pub fn is_latest(&self) -> bool {
+        *self == HircLayout::default()
+    }
+
+    fn var_action_count(self) -> bool {
+        self >= HircLayout::V123
+    }
+
+    /// Share set and rendered flags of effect slots packed in one byte.
+    fn packed_fx_flags(self) -> bool {
+        self >= HircLayout::V137
+    }
+
+    fn has_metadata(self) -> bool {
+        self >= HircLayout::V137
+    }
+
+    /// ID of the loop property, `None` if not known for the layout.
+    pub fn loop_prop(self) -> Option<u8> {
+        (self == HircLayout::V137).then_some(PROP_LOOP)
+    }
+
+    /// Whether the layout is checked against real banks, so objects may be edited.
+    pub fn is_verified(self) -> bool {
+        self == HircLayout::V137
+    }
+
+    /// Size of `AkBankSourceData` before inline plugin params,
+    /// `None` if it is not known for all versions of the layout.
+    fn source_data_size(self) -> Option<usize> {
+        (self >= HircLayout::V123).then_some(14)
+    }
+}
+
+/// Parsed head of `NodeBaseParams` of a hierarchy object.
+#[derive(Debug, Clone)]
+pub struct NodeBase {
+    pub override_bus_id: u32,
+    pub direct_parent_id: u32,
+    /// Raw property values by property ID.
+    pub props: Vec<(u8, u32)>,
+    /// Offset of the property bundle in entry data.
+    props_offset: usize,
+}
+
+impl NodeBase {
+    /// Parse the node base of a HIRC entry, returns `None` for
+    /// object types without node base params.
+    pub fn from_entry(entry: &HircEntry) -> Result<Option<Self>> {
+        let Some(start) = node_base_offset(entry)? else {
+            return Ok(None);
+        };
+        Self::from_data(&entry.data, start, entry.layout)
+            .map(Some)
+            .map_err(|source| HircError::Parse {
+                id: entry.id,
+                source,
+            })
+    }
+
+    fn from_data(data: &[u8], start: usize, layout: HircLayout) -> io::Result<Self> {
+        let mut reader = io::Cursor::new(data);
+        reader.set_position(start as u64);
+        let params = NodeParams::read(&mut reader, layout)?;
+        let props: Vec<(u8, u32)> = params.props.iter().map(|p| (p.id, p.value)).collect();
+        let props_offset = reader.position() as usize - (1 + props.len() * 5);
+
+        Ok(Self {
+            override_bus_id: params.override_bus_id,
+            direct_parent_id: params.direct_parent_id,
+            props,
+            props_offset,
+        })
+    }
+
+    pub fn prop(&self, id: u8) -> Option<u32> {
+        self.props
+            .iter()
+            .find(|(prop_id, _)| *prop_id == id)
+            .map(|(_, value)| *value)
+    }
+
+    pub fn prop_f32(&self, id: u8) -> Option<f32> {
+        self.prop(id).map(f32::from_bits)
+    }
+
+    /// Byte length of the property bundle.
+    fn props_length(&self) -> usize {
+        1 + self.props.len() * 5
+    }
+}
+
+/// Offset of `NodeBaseParams` in entry data.
+fn node_base_offset(entry: &HircEntry) -> Result<Option<usize>> {
+    if entry.layout == HircLayout::Legacy {
+        return Ok(None);
+    }
+    match entry.type_id {
+        HIRC_SOUND => {
+            // AkBankSourceData: plugin id, stream type, source id, media size, source bits
+            let Some(mut offset) = entry.layout.source_data_size() else {
+                return Ok(None);
+            };
+            let plugin_id = entry
+                .data
+                .get(0..4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+                .ok_or_else(|| HircError::Parse {
+                    id: entry.id,
+                    source: io::ErrorKind::UnexpectedEof.into(),
+                })?;
+            // source plugins carry their params inline
+            if plugin_id & 0x0F == 2 {
+                let size = entry
+                    .data
+                    .get(offset..offset + 4)
+                    .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+                    .ok_or_else(|| HircError::Parse {
+                        id: entry.id,
+                        source: io::ErrorKind::UnexpectedEof.into(),
+                    })?;
+                offset += 4 + size as usize;
+            }
+            Ok(Some(offset))
+        }
+        HIRC_RANDOM_SEQUENCE_CONTAINER
+        | HIRC_SWITCH_CONTAINER
+        | HIRC_ACTOR_MIXER
+        | HIRC_LAYER_CONTAINER => Ok(Some(0)),
+        _ => Ok(None),
+    }
+}
+
+/// Typed HIRC object, see module docs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum HircObject {
+    Sound(SoundObject),
+    Event(EventObject),
+    Action(ActionObject),
+    RanSeqContainer(RanSeqContainerObject),
+    SwitchContainer(SwitchContainerObject),
+}
+
+/// Head of `NodeBaseParams`, shared by sounds and containers.
+///
+/// Metadata fields are only written in layouts which have them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeParams {
+    pub override_parent_fx: u8,
+    /// Bypass bits, present only with effects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fx_bypass: Option<u8>,
+    pub fx: Vec<FxSlot>,
+    pub override_parent_metadata: u8,
+    pub metadata: Vec<FxSlot>,
+    pub override_attachment_params: u8,
+    pub override_bus_id: u32,
+    pub direct_parent_id: u32,
+    pub bit_vector: u8,
+    pub props: Vec<Prop>,
+}
+
+/// Effect or metadata plugin slot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FxSlot {
+    pub index: u8,
+    pub id: u32,
+    /// Share set in bit 0, rendered in bit 1. Separate bytes in older layouts.
+    pub flags: u8,
+}
+
+/// Property with raw value, floats are stored as bits.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Prop {
+    pub id: u8,
+    pub value: u32,
+}
+
+/// `AkBankSourceData` of a sound.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceData {
+    pub plugin_id: u32,
+    /// [`STREAM_TYPE_IN_MEMORY`], or streamed.
+    pub stream_type: u8,
+    /// Wem ID of the source.
+    pub source_id: u32,
+    pub media_size: u32,
+    pub source_bits: u8,
+    /// Inline params of source plugins.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin_params: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SoundObject {
+    pub source: SourceData,
+    pub node: NodeParams,
+    /// Remaining node params, kept raw.
+    pub rest: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventObject {
+    /// IDs of actions, in order.
+    pub actions: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionObject {
+    pub action_type: u16,
+    /// Target object or bus ID.
+    pub target_id: u32,
+    pub is_bus: u8,
+    /// Properties and action specific params, kept raw.
+    pub rest: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RanSeqContainerObject {
+    pub node: NodeParams,
+    /// Remaining node params and playback settings, kept raw. Their size is
+    /// read by layout to locate the children.
+    pub params: Vec<u8>,
+    pub children: Vec<u32>,
+    pub playlist: Vec<PlaylistItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlaylistItem {
+    pub id: u32,
+    pub weight: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwitchContainerObject {
+    pub node: NodeParams,
+    /// Remaining node params, switch groups and children, kept raw.
+    pub rest: Vec<u8>,
+}
+
+impl HircObject {
+    /// Parse object data of a HIRC entry, `None` for other types or data
+    /// which cannot be encoded back to identical bytes.
+    pub fn parse(type_id: u8, data: &[u8], layout: HircLayout) -> Option<Self> {
+        if layout == HircLayout::Legacy {
+            return None;
+        }
+        let mut reader = io::Cursor::new(data);
+        let object = match type_id {
+            HIRC_SOUND => {
+                layout.source_data_size()?;
+                let source = SourceData::read(&mut reader).ok()?;
+                let node = NodeParams::read(&mut reader, layout).ok()?;
+                HircObject::Sound(SoundObject {
+                    source,
+                    node,
+                    rest: read_rest(&mut reader),
+                })
+            }
+            HIRC_EVENT => {
+                let count = if layout.var_action_count() {
+                    read_var(&mut reader).ok()?
+                } else {
+                    reader.read_u32::<LE>().ok()?
+                };
+                let mut actions = vec![];
+                for _ in 0..count {
+                    actions.push(reader.read_u32::<LE>().ok()?);
+                }
+                HircObject::Event(EventObject { actions })
+            }
+            HIRC_ACTION => HircObject::Action(ActionObject {
+                action_type: reader.read_u16::<LE>().ok()?,
+                target_id: reader.read_u32::<LE>().ok()?,
+                is_bus: reader.read_u8().ok()?,
+                rest: read_rest(&mut reader),
+            }),
+            HIRC_RANDOM_SEQUENCE_CONTAINER => {
+                // the rest of node params is only known for the verified layout
+                if !layout.is_verified() {
+                    return None;
+                }
+                let node = NodeParams::read(&mut reader, layout).ok()?;
+                let params_start = reader.position() as usize;
+                skip_node_tail(&mut reader).ok()?;
+                skip(&mut reader, RANSEQ_SETTINGS_SIZE).ok()?;
+                let params = data[params_start..reader.position() as usize].to_vec();
+                let mut children = vec![];
+                for _ in 0..reader.read_u32::<LE>().ok()? {
+                    children.push(reader.read_u32::<LE>().ok()?);
+                }
+                let mut playlist = vec![];
+                for _ in 0..reader.read_u16::<LE>().ok()? {
+                    playlist.push(PlaylistItem {
+                        id: reader.read_u32::<LE>().ok()?,
+                        weight: reader.read_i32::<LE>().ok()?,
+                    });
+                }
+                HircObject::RanSeqContainer(RanSeqContainerObject {
+                    node,
+                    params,
+                    children,
+                    playlist,
+                })
+            }
+            HIRC_SWITCH_CONTAINER => {
+                let node = NodeParams::read(&mut reader, layout).ok()?;
+                HircObject::SwitchContainer(SwitchContainerObject {
+                    node,
+                    rest: read_rest(&mut reader),
+                })
+            }
+            _ => return None,
+        };
+        // trailing data not covered, or non canonical encoding
+        (reader.position() as usize == data.len() && object.to_bytes(layout) == data)
+            .then_some(object)
+    }
+
+    pub fn type_id(&self) -> u8 {
+        match self {
+            HircObject::Sound(_) => HIRC_SOUND,
+            HircObject::Event(_) => HIRC_EVENT,
+            HircObject::Action(_) => HIRC_ACTION,
+            HircObject::RanSeqContainer(_) => HIRC_RANDOM_SEQUENCE_CONTAINER,
+            HircObject::SwitchContainer(_) => HIRC_SWITCH_CONTAINER,
+        }
+    }
+
+    /// Encode to object data of a HIRC entry.
+    pub fn to_bytes(&self, layout: HircLayout) -> Vec<u8> {
+        let mut data = vec![];
+        match self {
+            HircObject::Sound(sound) => {
+                sound.source.write(&mut data);
+                sound.node.write(&mut data, layout);
+                data.extend_from_slice(&sound.rest);
+            }
+            HircObject::Event(event) => {
+                if layout.var_action_count() {
+                    write_var(&mut data, event.actions.len() as u32);
+                } else {
+                    data.extend_from_slice(&(event.actions.len() as u32).to_le_bytes());
+                }
+                for id in &event.actions {
+                    data.extend_from_slice(&id.to_le_bytes());
+                }
+            }
+            HircObject::Action(action) => {
+                data.extend_from_slice(&action.action_type.to_le_bytes());
+                data.extend_from_slice(&action.target_id.to_le_bytes());
+                data.push(action.is_bus);
+                data.extend_from_slice(&action.rest);
+            }
+            HircObject::RanSeqContainer(container) => {
+                container.node.write(&mut data, layout);
+                data.extend_from_slice(&container.params);
+                data.extend_from_slice(&(container.children.len() as u32).to_le_bytes());
+                for id in &container.children {
+                    data.extend_from_slice(&id.to_le_bytes());
+                }
+                data.extend_from_slice(&(container.playlist.len() as u16).to_le_bytes());
+                for item in &container.playlist {
+                    data.extend_from_slice(&item.id.to_le_bytes());
+                    data.extend_from_slice(&item.weight.to_le_bytes());
+                }
+            }
+            HircObject::SwitchContainer(container) => {
+                container.node.write(&mut data, layout);
+                data.extend_from_slice(&container.rest);
+            }
+        }
+        data
+    }
+}
+
+impl SourceData {
+    fn read<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        let plugin_id = reader.read_u32::<LE>()?;
+        let stream_type = reader.read_u8()?;
+        let source_id = reader.read_u32::<LE>()?;
+        let media_size = reader.read_u32::<LE>()?;
+        let source_bits = reader.read_u8()?;
+        // source plugins carry their params inline
+        let plugin_params = if plugin_id & 0x0F == 2 {
+            let size = reader.read_u32::<LE>()?;
+            // read up to the end of object first, size is not trusted
+            let mut params = vec![];
+            reader.take(size as u64).read_to_end(&mut params)?;
+            if params.len() != size as usize {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            Some(params)
+        } else {
+            None
+        };
+        Ok(Self {
+            plugin_id,
+            stream_type,
+            source_id,
+            media_size,
+            source_bits,
+            plugin_params,
+        })
+    }
+
+    fn write(&self, data: &mut Vec<u8>) {
+        data.extend_from_slice(&self.plugin_id.to_le_bytes());
+        data.push(self.stream_type);
+        data.extend_from_slice(&self.source_id.to_le_bytes());
+        data.extend_from_slice(&self.media_size.to_le_bytes());
+        data.push(self.source_bits);
+        if let Some(params) = &self.plugin_params {
+            data.extend_from_slice(&(params.len() as u32).to_le_bytes());
+            data.extend_from_slice(params);
+        }
+    }
+}
+
+impl NodeParams {
+    fn read<R: io::Read>(reader: &mut R, layout: HircLayout) -> io::Result<Self> {
+        // NodeInitialFxParams
+        let override_parent_fx = reader.read_u8()?;
+        let num_fx = reader.read_u8()?;
+        let fx_bypass = match num_fx {
+            0 => None,
+            _ => Some(reader.read_u8()?),
+        };
+        let fx = FxSlot::read_list(reader, num_fx, layout)?;
+        // NodeMetadataParams
+        let (override_parent_metadata, metadata) = if layout.has_metadata() {
+            let override_parent_metadata = reader.read_u8()?;
+            let num_metadata = reader.read_u8()?;
+            (
+                override_parent_metadata,
+                FxSlot::read_list(reader, num_metadata, layout)?,
+            )
+        } else {
+            (0, vec![])
+        };
+
+        let override_attachment_params = reader.read_u8()?;
+        let override_bus_id = reader.read_u32::<LE>()?;
+        let direct_parent_id = reader.read_u32::<LE>()?;
+        let bit_vector = reader.read_u8()?;
+
+        // AkPropBundle: all IDs first, then all values
+        let count = reader.read_u8()? as usize;
+        let mut prop_ids = vec![0; count];
+        reader.read_exact(&mut prop_ids)?;
+        let mut props = Vec::with_capacity(count);
+        for id in prop_ids {
+            props.push(Prop {
+                id,
+                value: reader.read_u32::<LE>()?,
+            });
+        }
+
+        Ok(Self {
+            override_parent_fx,
+            fx_bypass,
+            fx,
+            override_parent_metadata,
+            metadata,
+            override_attachment_params,
+            override_bus_id,
+            direct_parent_id,
+            bit_vector,
+            props,
+        })
+    }
+
+    fn write(&self, data: &mut Vec<u8>, layout: HircLayout) {
+        data.push(self.override_parent_fx);
+        data.push(self.fx.len() as u8);
+        if !self.fx.is_empty() {
+            data.push(self.fx_bypass.unwrap_or_default());
+        }
+        FxSlot::write_list(data, &self.fx, layout);
+        if layout.has_metadata() {
+            data.push(self.override_parent_metadata);
+            data.push(self.metadata.len() as u8);
+            FxSlot::write_list(data, &self.metadata, layout);
+        }
+
+        data.push(self.override_attachment_params);
+        data.extend_from_slice(&self.override_bus_id.to_le_bytes());
+        data.extend_from_slice(&self.direct_parent_id.to_le_bytes());
+        data.push(self.bit_vector);
+
+        data.push(self.props.len() as u8);
+        data.extend(self.props.iter().map(|prop| prop.id));
+        for prop in &self.props {
+            data.extend_from_slice(&prop.value.to_le_bytes());
+        }
+    }
+}
+
+impl FxSlot {
+    fn read_list<R: io::Read>(
+        reader: &mut R,
+        count: u8,
+        layout: HircLayout,
+    ) -> io::Result<Vec<Self>> {
+        let mut slots = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let index = reader.read_u8()?;
+            let id = reader.read_u32::<LE>()?;
+            let flags = if layout.packed_fx_flags() {
+                reader.read_u8()?
+            } else {
+                let (share_set, rendered) = (reader.read_u8()?, reader.read_u8()?);
+                // bools, other values would not be written back
+                if share_set > 1 || rendered > 1 {
+                    return Err(io::ErrorKind::InvalidData.into());
+                }
+                share_set | rendered << 1
+            };
+            slots.push(Self { index, id, flags });
+        }
+        Ok(slots)
+    }
+
+    fn write_list(data: &mut Vec<u8>, slots: &[Self], layout: HircLayout) {
+        for slot in slots {
+            data.push(slot.index);
+            data.extend_from_slice(&slot.id.to_le_bytes());
+            if layout.packed_fx_flags() {
+                data.push(slot.flags);
+            } else {
+                data.push(slot.flags & 1);
+                data.push(slot.flags >> 1 & 1);
+            }
+        }
+    }
+}
+
+fn read_rest(reader: &mut io::Cursor<&[u8]>) -> Vec<u8> {
+    let mut rest = vec![];
+    reader.read_to_end(&mut rest).unwrap();
+    rest
+}
+
+/// Variable length integer, 7 bits per byte with the high bit as continuation.
+fn read_var<R: io::Read>(reader: &mut R) -> io::Result<u32> {
+    let mut value = 0u32;
+    loop {
+        let byte = reader.read_u8()?;
+        value = (value << 7) | (byte & 0x7F) as u32;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+fn write_var(data: &mut Vec<u8>, value: u32) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut value = value >> 7;
+    while value > 0 {
+        bytes.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    data.extend(bytes.iter().rev());
+}
+
+/// Size of random / sequence container settings after `NodeBaseParams`:
+/// loop count and its modifiers, transition time and its modifiers, avoid
+/// repeat count, transition, random and play modes and the flag byte.
+const RANSEQ_SETTINGS_SIZE: u64 = 24;
+
+/// Skip the rest of `NodeBaseParams` after the property bundle in the
+/// `V137` layout: ranged properties, positioning, aux sends, advanced
+/// settings, states and RTPCs.
+fn skip_node_tail<R: io::Read>(reader: &mut R) -> io::Result<()> {
+    // AkPropBundle of ranged properties: IDs first, then min and max values
+    let count = reader.read_u8()? as u64;
+    skip(reader, count * 9)?;
+
+    // PositioningParams, 3D settings only when overridden
+    let positioning = reader.read_u8()?;
+    if positioning & 0b11 == 0b11 {
+        let _bits_3d = reader.read_u8()?;
+        // other position types than emitter carry automation paths
+        if positioning >> 5 & 0b11 != 0 {
+            // path mode, transition time
+            skip(reader, 5)?;
+            let vertices = reader.read_u32::<LE>()? as u64;
+            skip(reader, vertices * 16)?;
+            // playlist items, then ranges of each
+            let items = reader.read_u32::<LE>()? as u64;
+            skip(reader, items * (8 + 12))?;
+        }
+    }
+
+    // AuxParams, the reflections aux bus follows the aux sends
+    let aux = reader.read_u8()?;
+    if aux >> 3 & 1 != 0 {
+        skip(reader, 16)?;
+    }
+    skip(reader, 4)?;
+
+    // AdvSettingsParams
+    skip(reader, 6)?;
+
+    // StateChunk: state properties, then state groups
+    for _ in 0..read_var(reader)? {
+        read_var(reader)?;
+        // accumulation type, in dB
+        skip(reader, 2)?;
+    }
+    for _ in 0..read_var(reader)? {
+        // state group ID, sync type
+        skip(reader, 5)?;
+        let states = read_var(reader)? as u64;
+        skip(reader, states * 8)?;
+    }
+
+    // InitialRTPC
+    for _ in 0..reader.read_u16::<LE>()? {
+        // RTPC ID, type, accumulation
+        skip(reader, 6)?;
+        read_var(reader)?;
+        // curve ID, scaling
+        skip(reader, 5)?;
+        let points = reader.read_u16::<LE>()? as u64;
+        skip(reader, points * 12)?;
+    }
+    Ok(())
+}
+
+fn skip<R: io::Read>(reader: &mut R, length: u64) -> io::Result<()> {
+    if io::copy(&mut reader.take(length), &mut io::sink())? != length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+/// Refuse edits of objects whose layout is not verified.
+fn check_editable(entry: &HircEntry) -> Result<()> {
+    if entry.layout.is_verified() {
+        Ok(())
+    } else {
+        Err(HircError::UnverifiedLayout {
+            id: entry.id,
+            layout: entry.layout,
+        })
+    }
+}
+
+/// Set a property of a HIRC entry, adding it if not present.
+pub fn set_prop(entry: &mut HircEntry, id: u8, value: u32) -> Result<()> {
+    check_editable(entry)?;
+    let Some(node) = NodeBase::from_entry(entry)? else {
+        return Ok(());
+    };
+    let mut props = node.props.clone();
+    match props.iter_mut().find(|(prop_id, _)| *prop_id == id) {
+        Some((_, old)) => *old = value,
+        None => props.push((id, value)),
+    }
+    write_props(entry, &node, &props)
+}
+
+/// Remove a property of a HIRC entry, if present.
+pub fn remove_prop(entry: &mut HircEntry, id: u8) -> Result<()> {
+    check_editable(entry)?;
+    let Some(node) = NodeBase::from_entry(entry)? else {
+        return Ok(());
+    };
+    if node.prop(id).is_none() {
+        return Ok(());
+    }
+    let mut props = node.props.clone();
+    props.retain(|(prop_id, _)| *prop_id != id);
+    write_props(entry, &node, &props)
+}
+
+/// Replace the property bundle of a HIRC entry.
+fn write_props(entry: &mut HircEntry, node: &NodeBase, props: &[(u8, u32)]) -> Result<()> {
+    let mut bundle = vec![props.len() as u8];
+    bundle.extend(props.iter().map(|(id, _)| *id));
+    for (_, value) in props {
+        bundle.write_u32::<LE>(*value)?;
+    }
+    let range = node.props_offset..node.props_offset + node.props_length();
+    entry.data.splice(range, bundle);
+    entry.length = entry.data.len() as u32 + 4;
+
+    Ok(())
+}
+
+/// Parent / bus routing graph of hierarchy objects in a bank.
+#[derive(Debug, Default)]
+pub struct HircGraph {
+    nodes: HashMap<u32, NodeBase>,
+}
+
+impl HircGraph {
+    pub fn from_entries(entries: &[HircEntry]) -> Result<Self> {
+        let mut nodes = HashMap::new();
+        for entry in entries {
+            if let Some(node) = NodeBase::from_entry(entry)? {
+                nodes.insert(entry.id, node);
+            }
+        }
+        Ok(Self { nodes })
+    }
+
+    pub fn get(&self, id: u32) -> Option<&NodeBase> {
+        self.nodes.get(&id)
+    }
+
+    /// Iterate ancestors inside this bank, nearest first.
+    pub fn ancestors(&self, id: u32) -> impl Iterator<Item = u32> + '_ {
+        let mut current = id;
+        let mut visited = HashSet::from([id]);
+        std::iter::from_fn(move || {
+            let parent = self.nodes.get(&current)?.direct_parent_id;
+            // guard against malformed cycles, also those above the start
+            if parent == 0 || !visited.insert(parent) || !self.nodes.contains_key(&parent) {
+                return None;
+            }
+            current = parent;
+            Some(parent)
+        })
+    }
+
+    /// Bus the object outputs to, `None` if routed by a parent outside this bank.
+    pub fn output_bus(&self, id: u32) -> Option<u32> {
+        std::iter::once(id)
+            .chain(self.ancestors(id))
+            .filter_map(|id| self.nodes.get(&id))
+            .map(|node| node.override_bus_id)
+            .find(|&bus| bus != 0)
+    }
+
+    /// Topmost objects routed to the bus.
+    ///
+    /// Properties like volume are inherited by children, so editing these
+    /// objects affects everything under the bus exactly once.
+    pub fn bus_roots(&self, bus_id: u32) -> Vec<u32> {
+        let mut roots: Vec<u32> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.override_bus_id == bus_id)
+            .map(|(id, _)| *id)
+            .filter(|&id| {
+                self.ancestors(id)
+                    .all(|p| self.output_bus(p) != Some(bus_id))
+            })
+            .collect();
+        roots.sort();
+        roots
+    }
+}
+
+/// Bus referenced by ID or by name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BusRef {
+    Id(u32),
+    Name(String),
+}
+
+impl BusRef {
+    pub fn id(&self) -> u32 {
+        match self {
+            BusRef::Id(id) => *id,
+            BusRef::Name(name) => utils::fnv1_32(name),
+        }
+    }
+}
+
+impl std::fmt::Display for BusRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BusRef::Id(id) => write!(f, "{}", id),
+            BusRef::Name(name) => write!(f, "'{}'", name),
+        }
+    }
+}
+
+/// Global HIRC transformation, selected in `project.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum HircPreset {
+    /// Add volume to all sounds routed to a bus.
+    BusVolume { bus: BusRef, db: f32 },
+}
+
+impl std::fmt::Display for HircPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HircPreset::BusVolume { bus, db } => write!(f, "bus {} volume {:+} dB", bus, db),
+        }
+    }
+}
+
+impl HircPreset {
+    /// Apply to HIRC section of the bank, returns IDs of changed objects.
+    pub fn apply(&self, bank: &mut Bnk) -> Result<Vec<u32>> {
+        let Some(section) = bank
+            .sections
+            .iter_mut()
+            .find(|section| matches!(section.payload, SectionPayload::Hirc { .. }))
+        else {
+            return Ok(vec![]);
+        };
+        let SectionPayload::Hirc { entries } = &mut section.payload else {
+            unreachable!()
+        };
+
+        let graph = HircGraph::from_entries(entries)?;
+        let changed = match self {
+            HircPreset::BusVolume { bus, db } => {
+                let targets = graph.bus_roots(bus.id());
+                for entry in entries.iter_mut() {
+                    if !targets.contains(&entry.id) {
+                        continue;
+                    }
+                    let volume = graph
+                        .get(entry.id)
+                        .and_then(|node| node.prop_f32(PROP_VOLUME));
+                    let volume = volume.unwrap_or(0.0) + db;
+                    set_prop(entry, PROP_VOLUME, volume.to_bits())?;
+                }
+                targets
+            }
+        };
+
+        section.section_length = 4 + entries.iter().map(|e| 5 + e.length).sum::<u32>();
+        Ok(changed)
+    }
+}
+
+/// Property edit of HIRC objects, `[[props]]` in `project.toml`.
+///
+/// Values are added to the current ones of the objects, properties of
+/// parent objects like actor-mixers add up for their children in game.
+/// Loop replaces the current one, and is only set on sounds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "PropEditRepr", try_from = "PropEditRepr")]
+pub struct PropEdit {
+    pub target: PropTarget,
+    /// Volume in dB.
+    pub volume: Option<f32>,
+    /// Pitch in cents.
+    pub pitch: Option<f32>,
+    pub looping: Option<LoopSetting>,
+}
+
+/// Objects of a [`PropEdit`], `object = …` or `source = …` in `project.toml`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropTarget {
+    /// ID of a sound, container or actor-mixer.
+    Object(u32),
+    /// Source wem ID, for all sounds playing it.
+    Source(u32),
+}
+
+impl std::fmt::Display for PropTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PropTarget::Object(id) => write!(f, "object {}", id),
+            PropTarget::Source(id) => write!(f, "source {}", id),
+        }
+    }
+}
+
+/// [`PropEdit`] in `project.toml`, with exactly one of `object` or `source`.
+#[derive(Serialize, Deserialize)]
+struct PropEditRepr {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    object: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source: Option<u32>,
+    #[serde(default)]
+    volume: Option<f32>,
+    #[serde(default)]
+    pitch: Option<f32>,
+    #[serde(default, rename = "loop")]
+    looping: Option<LoopSetting>,
+}
+
+impl From<PropEdit> for PropEditRepr {
+    fn from(edit: PropEdit) -> Self {
+        let (object, source) = match edit.target {
+            PropTarget::Object(id) => (Some(id), None),
+            PropTarget::Source(id) => (None, Some(id)),
+        };
+        PropEditRepr {
+            object,
+            source,
+            volume: edit.volume,
+            pitch: edit.pitch,
+            looping: edit.looping,
+        }
+    }
+}
+
+impl TryFrom<PropEditRepr> for PropEdit {
+    type Error = String;
+
+    fn try_from(repr: PropEditRepr) -> std::result::Result<Self, Self::Error> {
+        let target = match (repr.object, repr.source) {
+            (Some(id), None) => PropTarget::Object(id),
+            (None, Some(id)) => PropTarget::Source(id),
+            _ => {
+                return Err(
+                    "props need either `object` for a HIRC object ID or `source` for a wem ID."
+                        .to_string(),
+                );
+            }
+        };
+        Ok(PropEdit {
+            target,
+            volume: repr.volume,
+            pitch: repr.pitch,
+            looping: repr.looping,
+        })
+    }
+}
+
+/// Loop of sounds, `true` to loop infinitely, `false` to play once,
+/// or a count of plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LoopSetting {
+    Enabled(bool),
+    Count(u32),
+}
+
+impl LoopSetting {
+    /// Value of the loop property, `None` to remove it.
+    fn prop_value(self) -> Option<u32> {
+        match self {
+            LoopSetting::Enabled(true) => Some(0),
+            LoopSetting::Enabled(false) | LoopSetting::Count(1) => None,
+            LoopSetting::Count(count) => Some(count),
+        }
+    }
+}
+
+impl std::fmt::Display for LoopSetting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.prop_value() {
+            Some(0) => write!(f, "infinite"),
+            Some(count) => write!(f, "{} plays", count),
+            None => write!(f, "off"),
+        }
+    }
+}
+
+impl std::fmt::Display for PropEdit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.target)?;
+        if let Some(volume) = self.volume {
+            write!(f, " volume {:+} dB", volume)?;
+        }
+        if let Some(pitch) = self.pitch {
+            write!(f, " pitch {:+} cents", pitch)?;
+        }
+        if let Some(looping) = self.looping {
+            write!(f, " loop {}", looping)?;
+        }
+        Ok(())
+    }
+}
+
+impl PropEdit {
+    /// Apply to HIRC section of the bank, returns IDs of changed objects.
+    pub fn apply(&self, bank: &mut Bnk) -> Result<Vec<u32>> {
+        let Some(section) = bank
+            .sections
+            .iter_mut()
+            .find(|section| matches!(section.payload, SectionPayload::Hirc { .. }))
+        else {
+            return Ok(vec![]);
+        };
+        let SectionPayload::Hirc { entries } = &mut section.payload else {
+            unreachable!()
+        };
+
+        let edits: Vec<(u8, f32)> = [(PROP_VOLUME, self.volume), (PROP_PITCH, self.pitch)]
+            .into_iter()
+            .filter_map(|(id, delta)| Some((id, delta?)))
+            .collect();
+        let mut changed = vec![];
+        for entry in entries.iter_mut() {
+            let is_target = match self.target {
+                PropTarget::Object(id) => entry.id == id,
+                PropTarget::Source(id) => sound_source_id(entry) == Some(id),
+            };
+            if !is_target {
+                continue;
+            }
+            check_editable(entry)?;
+            let Some(node) = NodeBase::from_entry(entry)? else {
+                continue;
+            };
+            for &(id, delta) in &edits {
+                let value = node.prop_f32(id).unwrap_or(0.0) + delta;
+                set_prop(entry, id, value.to_bits())?;
+            }
+            let looping = self.looping.filter(|_| entry.type_id == HIRC_SOUND);
+            if edits.is_empty() && looping.is_none() {
+                continue;
+            }
+            if let Some(looping) = looping {
+                let prop = entry
+                    .layout
+                    .loop_prop()
+                    .ok_or(HircError::UnverifiedLayout {
+                        id: entry.id,
+                        layout: entry.layout,
+                    })?;
+                match looping.prop_value() {
+                    Some(value) => set_prop(entry, prop, value)?,
+                    None => remove_prop(entry, prop)?,
+                }
+            }
+            changed.push(entry.id);
+        }
+
+        section.section_length = 4 + entries.iter().map(|e| 5 + e.length).sum::<u32>();
+        Ok(changed)
+    }
+}
+
+/// Source wem ID of a sound, also of sounds kept raw, the ID follows the
+/// plugin ID and stream type in every layout.
+fn sound_source_id(entry: &HircEntry) -> Option<u32> {
+    if entry.type_id != HIRC_SOUND {
+        return None;
+    }
+    let bytes = entry.data.get(5..9)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Rewrite source wem IDs of the sounds in HIRC section of the bank, by
+/// `remap` of old IDs to new ones. Returns `(sound ID, old source ID)` of
+/// changed sounds.
+///
+/// Media size of in-memory sources is set to the size of the new wem in
+/// `media_sizes`, by wem ID, and kept if not found. Sounds of older banks
+/// which are kept raw are not changed.
+pub fn remap_sources(
+    bank: &mut Bnk,
+    remap: &HashMap<u32, u32>,
+    media_sizes: &HashMap<u32, u32>,
+) -> Vec<(u32, u32)> {
+    let Some(section) = bank
+        .sections
+        .iter_mut()
+        .find(|section| matches!(section.payload, SectionPayload::Hirc { .. }))
+    else {
+        return vec![];
+    };
+    let SectionPayload::Hirc { entries } = &mut section.payload else {
+        unreachable!()
+    };
+
+    let mut changed = vec![];
+    for entry in entries
+        .iter_mut()
+        .filter(|entry| entry.type_id == HIRC_SOUND)
+    {
+        let Some(HircObject::Sound(mut sound)) =
+            HircObject::parse(entry.type_id, &entry.data, entry.layout)
+        else {
+            continue;
+        };
+        let old_id = sound.source.source_id;
+        let Some(&new_id) = remap.get(&old_id) else {
+            continue;
+        };
+        sound.source.source_id = new_id;
+        if sound.source.stream_type == STREAM_TYPE_IN_MEMORY
+            && let Some(&size) = media_sizes.get(&new_id)
+        {
+            sound.source.media_size = size;
+        }
+        entry.data = HircObject::Sound(sound).to_bytes(entry.layout);
+        entry.length = entry.data.len() as u32 + 4;
+        changed.push((entry.id, old_id));
+    }
+
+    section.section_length = 4 + entries.iter().map(|e| 5 + e.length).sum::<u32>();
+    changed
+}
+
+/// Name of a HIRC object type.
+pub fn type_name(type_id: u8) -> &'static str {
+    match type_id {
+        1 => "State",
+        HIRC_SOUND => "Sound",
+        HIRC_ACTION => "Action",
+        HIRC_EVENT => "Event",
+        HIRC_RANDOM_SEQUENCE_CONTAINER => "RanSeqContainer",
+        HIRC_SWITCH_CONTAINER => "SwitchContainer",
+        HIRC_ACTOR_MIXER => "ActorMixer",
+        8 => "Bus",
+        HIRC_LAYER_CONTAINER => "LayerContainer",
+        10 => "MusicSegment",
+        11 => "MusicTrack",
+        12 => "MusicSwitch",
+        13 => "MusicRanSeq",
+        14 => "Attenuation",
+        15 => "DialogueEvent",
+        16 => "FxShareSet",
+        17 => "FxCustom",
+        18 => "AuxBus",
+        19 => "LFO",
+        20 => "Envelope",
+        21 => "AudioDevice",
+        22 => "TimeMod",
+        _ => "Unknown",
+    }
+}
+
+/// Change of a HIRC object between two versions of a bank.
+#[derive(Debug, Clone, Serialize)]
+pub struct HircChange {
+    pub id: u32,
+    pub kind: ChangeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_type: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_type: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_size: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_size: Option<u32>,
+}
+
+impl std::fmt::Display for HircChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let size = |size: Option<u32>| size.unwrap_or_default();
+        match (self.kind, self.old_type, self.new_type) {
+            (ChangeKind::Added, _, Some(new_type)) => write!(
+                f,
+                "+ {} {}: {} bytes",
+                type_name(new_type),
+                self.id,
+                size(self.new_size)
+            ),
+            (ChangeKind::Removed, Some(old_type), _) => write!(
+                f,
+                "- {} {}: {} bytes",
+                type_name(old_type),
+                self.id,
+                size(self.old_size)
+            ),
+            (_, Some(old_type), Some(new_type)) if old_type != new_type => write!(
+                f,
+                "~ {} {}: type -> {}, {} -> {} bytes",
+                type_name(old_type),
+                self.id,
+                type_name(new_type),
+                size(self.old_size),
+                size(self.new_size)
+            ),
+            _ => write!(
+                f,
+                "~ {} {}: {} -> {} bytes",
+                type_name(self.new_type.unwrap_or_default()),
+                self.id,
+                size(self.old_size),
+                size(self.new_size)
+            ),
+        }
+    }
+}
+
+/// Object counts and changes of a HIRC object type.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HircTypeStats {
+    pub type_id: u8,
+    pub name: &'static str,
+    pub old_count: usize,
+    pub new_count: usize,
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+}
+
+/// HIRC objects compared between two versions of a bank, matched by ID.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HircDiff {
+    /// Statistics by type, changed objects are counted under their new type.
+    pub types: Vec<HircTypeStats>,
+    /// Changes sorted by ID.
+    pub changes: Vec<HircChange>,
+}
+
+impl HircDiff {
+    pub fn new(old: &[HircEntry], new: &[HircEntry]) -> Self {
+        let old_map: HashMap<u32, &HircEntry> = old.iter().map(|e| (e.id, e)).collect();
+        let new_map: HashMap<u32, &HircEntry> = new.iter().map(|e| (e.id, e)).collect();
+        let mut types: BTreeMap<u8, HircTypeStats> = BTreeMap::new();
+        fn stats(types: &mut BTreeMap<u8, HircTypeStats>, type_id: u8) -> &mut HircTypeStats {
+            types.entry(type_id).or_insert_with(|| HircTypeStats {
+                type_id,
+                name: type_name(type_id),
+                ..Default::default()
+            })
+        }
+        let mut changes = vec![];
+
+        for entry in old {
+            stats(&mut types, entry.type_id).old_count += 1;
+            if !new_map.contains_key(&entry.id) {
+                stats(&mut types, entry.type_id).removed += 1;
+                changes.push(HircChange {
+                    id: entry.id,
+                    kind: ChangeKind::Removed,
+                    old_type: Some(entry.type_id),
+                    new_type: None,
+                    old_size: Some(entry.length),
+                    new_size: None,
+                });
+            }
+        }
+        for entry in new {
+            stats(&mut types, entry.type_id).new_count += 1;
+            let (kind, old_entry) = match old_map.get(&entry.id) {
+                None => (ChangeKind::Added, None),
+                Some(old_entry)
+                    if old_entry.type_id != entry.type_id || old_entry.data != entry.data =>
+                {
+                    (ChangeKind::Changed, Some(*old_entry))
+                }
+                Some(_) => continue,
+            };
+            let type_stats = stats(&mut types, entry.type_id);
+            match kind {
+                ChangeKind::Added => type_stats.added += 1,
+                _ => type_stats.changed += 1,
+            }
+            changes.push(HircChange {
+                id: entry.id,
+                kind,
+                old_type: old_entry.map(|e| e.type_id),
+                new_type: Some(entry.type_id),
+                old_size: old_entry.map(|e| e.length),
+                new_size: Some(entry.length),
+            });
+        }
+        changes.sort_by_key(|change| change.id);
+
+        Self {
+            types: types.into_values().collect(),
+            changes,
+        }
+    }
+
+    /// Compare HIRC sections of two banks, banks without HIRC have no objects.
+    pub fn from_banks(old: &Bnk, new: &Bnk) -> Self {
+        Self::new(hirc_entries(old), hirc_entries(new))
+    }
+}
+
+/// Random or sequence container of a target, and the source wem IDs of its
+/// sounds in child order, sounds of nested containers included.
+///
+/// `target` is the container ID, or the ID or source ID of one of its sounds.
+/// A source shared by sounds of several containers is an error listing them.
+pub fn container_sources(entries: &[HircEntry], target: u32) -> Result<Option<(u32, Vec<u32>)>> {
+    let objects: HashMap<u32, HircObject> = entries
+        .iter()
+        .filter(|entry| matches!(entry.type_id, HIRC_SOUND | HIRC_RANDOM_SEQUENCE_CONTAINER))
+        .filter_map(|entry| {
+            let object = HircObject::parse(entry.type_id, &entry.data, entry.layout)?;
+            Some((entry.id, object))
+        })
+        .collect();
+    let container_id = match objects.get(&target) {
+        Some(HircObject::RanSeqContainer(_)) => target,
+        _ => {
+            let mut containers: Vec<u32> = objects
+                .iter()
+                .filter_map(|(&id, object)| match object {
+                    HircObject::Sound(sound)
+                        if id == target || sound.source.source_id == target =>
+                    {
+                        Some(sound.node.direct_parent_id)
+                    }
+                    _ => None,
+                })
+                .filter(|parent| {
+                    matches!(objects.get(parent), Some(HircObject::RanSeqContainer(_)))
+                })
+                .collect();
+            containers.sort_unstable();
+            containers.dedup();
+            match containers[..] {
+                [] => return Ok(None),
+                [container_id] => container_id,
+                _ => return Err(HircError::AmbiguousContainer { target, containers }),
+            }
+        }
+    };
+    let Some(HircObject::RanSeqContainer(container)) = objects.get(&container_id) else {
+        return Ok(None);
+    };
+    let mut sources = vec![];
+    let mut visited = HashSet::from([container_id]);
+    push_sources(&objects, &container.children, &mut visited, &mut sources);
+    Ok(Some((container_id, sources)))
+}
+
+/// Push sources of `children`, containers in `visited` are not entered again.
+fn push_sources(
+    objects: &HashMap<u32, HircObject>,
+    children: &[u32],
+    visited: &mut HashSet<u32>,
+    sources: &mut Vec<u32>,
+) {
+    for child in children {
+        match objects.get(child) {
+            Some(HircObject::Sound(sound)) if !sources.contains(&sound.source.source_id) => {
+                sources.push(sound.source.source_id);
+            }
+            Some(HircObject::RanSeqContainer(container)) if visited.insert(*child) => {
+                push_sources(objects, &container.children, visited, sources)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// HIRC entries of a bank, empty if it has none.
+pub fn hirc_entries(bank: &Bnk) -> &[HircEntry] {
+    bank.sections
+        .iter()
+        .find_map(|section| match &section.payload {
+            SectionPayload::Hirc { entries } => Some(entries.as_slice()),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_BNK: &str = "test_files/Wp00_Cmn.sbnk.1.X64";
+
+    fn load_bank() -> Bnk {
+        let mut reader = io::BufReader::new(std::fs::File::open(TEST_BNK).unwrap());
+        Bnk::from_reader(&mut reader).unwrap()
+    }
+
+    #[test]
+    fn test_parse_graph() {
+        let bank = load_bank();
+        let entries = hirc_entries(&bank);
+        let graph = HircGraph::from_entries(entries).unwrap();
+        let node_count = entries
+            .iter()
+            .filter(|e| node_base_offset(e).unwrap().is_some())
+            .count();
+        assert_eq!(graph.nodes.len(), node_count);
+        // every sound has a parent
+        for entry in entries.iter().filter(|e| e.type_id == HIRC_SOUND) {
+            assert_ne!(graph.get(entry.id).unwrap().direct_parent_id, 0);
+        }
+    }
+
+    #[test]
+    fn test_ancestors_cycle() {
+        let node = |parent| NodeBase {
+            override_bus_id: 0,
+            direct_parent_id: parent,
+            props: vec![],
+            props_offset: 0,
+        };
+        // 1 -> 2 -> 3 -> 2
+        let graph = HircGraph {
+            nodes: HashMap::from([(1, node(2)), (2, node(3)), (3, node(2))]),
+        };
+        assert_eq!(graph.ancestors(1).collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(graph.ancestors(3).collect::<Vec<_>>(), [2]);
+        assert_eq!(graph.output_bus(1), None);
+    }
+
+    #[test]
+    fn test_typed_objects() {
+        let bank = load_bank();
+        let entries = hirc_entries(&bank);
+        let mut typed = HashMap::new();
+        for entry in entries {
+            if let Some(object) = HircObject::parse(entry.type_id, &entry.data, entry.layout) {
+                assert_eq!(object.type_id(), entry.type_id);
+                *typed.entry(entry.type_id).or_insert(0) += 1;
+            }
+        }
+        // all objects of supported types are parsed in the test bank
+        for type_id in [
+            HIRC_SOUND,
+            HIRC_EVENT,
+            HIRC_ACTION,
+            HIRC_RANDOM_SEQUENCE_CONTAINER,
+            HIRC_SWITCH_CONTAINER,
+        ] {
+            let count = entries.iter().filter(|e| e.type_id == type_id).count();
+            assert_eq!(typed.get(&type_id).copied().unwrap_or(0), count);
+        }
+
+        let container = entries
+            .iter()
+            .find(|e| e.type_id == HIRC_RANDOM_SEQUENCE_CONTAINER)
+            .unwrap();
+        let Some(HircObject::RanSeqContainer(object)) =
+            HircObject::parse(container.type_id, &container.data, container.layout)
+        else {
+            unreachable!()
+        };
+        let graph = HircGraph::from_entries(entries).unwrap();
+        assert!(!object.children.is_empty());
+        for child in &object.children {
+            assert_eq!(graph.get(*child).unwrap().direct_parent_id, container.id);
+        }
+        // children are located by layout, not guessed from the tail
+        let mut extended = container.data.clone();
+        extended.extend_from_slice(&[0; 4]);
+        assert_eq!(
+            HircObject::parse(container.type_id, &extended, container.layout),
+            None
+        );
+        assert_eq!(
+            HircObject::parse(container.type_id, &container.data, HircLayout::V123),
+            None
+        );
+
+        let mut data = vec![];
+        write_var(&mut data, 300);
+        assert_eq!(data, [0x82, 0x2C]);
+        assert_eq!(read_var(&mut io::Cursor::new(data)).unwrap(), 300);
+    }
+
+    #[test]
+    fn test_layouts() {
+        assert_eq!(HircLayout::for_version(88), HircLayout::Legacy);
+        assert_eq!(HircLayout::for_version(120), HircLayout::V89);
+        assert_eq!(HircLayout::for_version(135), HircLayout::V123);
+        assert_eq!(HircLayout::for_version(145), HircLayout::V137);
+        // property IDs of older versions are not verified
+        assert_eq!(HircLayout::V137.loop_prop(), Some(PROP_LOOP));
+        assert_eq!(HircLayout::V123.loop_prop(), None);
+
+        let event = HircObject::Event(EventObject {
+            actions: vec![700001, 700002],
+        });
+        let old = event.to_bytes(HircLayout::V89);
+        assert_eq!(&old[..4], 2u32.to_le_bytes());
+        assert_eq!(
+            HircObject::parse(HIRC_EVENT, &old, HircLayout::V89),
+            Some(event.clone())
+        );
+        assert_eq!(HircObject::parse(HIRC_EVENT, &old, HircLayout::V137), None);
+        assert_eq!(
+            HircObject::parse(HIRC_EVENT, &old, HircLayout::Legacy),
+            None
+        );
+        assert_eq!(event.to_bytes(HircLayout::V123)[0], 2);
+
+        // older layouts have two flag bytes per effect and no metadata
+        let mut node = vec![0, 1, 0, 0];
+        node.extend_from_slice(&777u32.to_le_bytes());
+        node.extend_from_slice(&[0, 1, 0]);
+        node.extend_from_slice(&[0; 8]);
+        node.extend_from_slice(&[0, 0]);
+        let params = NodeParams::read(&mut io::Cursor::new(&node), HircLayout::V123).unwrap();
+        assert_eq!(params.fx[0].flags, 2);
+        let mut data = vec![];
+        params.write(&mut data, HircLayout::V123);
+        assert_eq!(data, node);
+
+        // properties of unverified layouts are never edited
+        let mut entry = HircEntry {
+            type_id: HIRC_ACTOR_MIXER,
+            length: node.len() as u32 + 4,
+            id: 600001,
+            data: node.clone(),
+            layout: HircLayout::V123,
+        };
+        assert!(matches!(
+            set_prop(&mut entry, PROP_VOLUME, 0),
+            Err(HircError::UnverifiedLayout { id: 600001, .. })
+        ));
+        assert_eq!(entry.data, node);
+
+        // source data of sounds is not known for every version of V89
+        let mut sound = vec![0; 14];
+        sound.extend_from_slice(&node);
+        assert_eq!(HircObject::parse(HIRC_SOUND, &sound, HircLayout::V89), None);
+        assert!(HircObject::parse(HIRC_SOUND, &sound, HircLayout::V123).is_some());
+    }
+
+    #[test]
+    fn test_container_sources() {
+        let bank = load_bank();
+        let entries = hirc_entries(&bank);
+        let (container_id, sources) = entries
+            .iter()
+            .filter(|e| e.type_id == HIRC_RANDOM_SEQUENCE_CONTAINER)
+            .find_map(|e| {
+                container_sources(entries, e.id)
+                    .unwrap()
+                    .filter(|(_, s)| s.len() > 1)
+            })
+            .unwrap();
+        // found by the source of a sound too
+        assert_eq!(
+            container_sources(entries, sources[1]).unwrap(),
+            Some((container_id, sources.clone()))
+        );
+        assert_eq!(container_sources(entries, 1).unwrap(), None);
+
+        // a copy of a sound in container 1, which also contains itself
+        let object = |id| {
+            let entry = entries.iter().find(|e| e.id == id).unwrap();
+            HircObject::parse(entry.type_id, &entry.data, entry.layout).unwrap()
+        };
+        let HircObject::RanSeqContainer(mut container) = object(container_id) else {
+            panic!("not a container");
+        };
+        let sound_id = container.children[0];
+        let HircObject::Sound(mut sound) = object(sound_id) else {
+            panic!("not a sound");
+        };
+        let source_id = sound.source.source_id;
+        sound.node.direct_parent_id = 1;
+        container.children = vec![2, 1];
+        container.playlist = vec![PlaylistItem { id: 2, weight: 1 }];
+        let entry = |id, object: HircObject| {
+            let data = object.to_bytes(HircLayout::V137);
+            HircEntry {
+                type_id: object.type_id(),
+                length: data.len() as u32 + 4,
+                id,
+                data,
+                layout: HircLayout::V137,
+            }
+        };
+        let mut entries = entries.to_vec();
+        entries.push(entry(1, HircObject::RanSeqContainer(container)));
+        entries.push(entry(2, HircObject::Sound(sound)));
+        assert_eq!(
+            container_sources(&entries, 1).unwrap(),
+            Some((1, vec![source_id]))
+        );
+        match container_sources(&entries, source_id) {
+            Err(HircError::AmbiguousContainer { containers, .. }) => {
+                assert_eq!(containers, [1, container_id])
+            }
+            other => panic!("expected ambiguous container, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_bus_volume_preset() {
+        let mut bank = load_bank();
+        let graph = HircGraph::from_entries(hirc_entries(&bank)).unwrap();
+        let bus_id = graph
+            .nodes
+            .values()
+            .map(|node| node.override_bus_id)
+            .find(|&bus| bus != 0)
+            .unwrap();
+        let roots = graph.bus_roots(bus_id);
+        assert!(!roots.is_empty());
+        let before: Vec<f32> = roots
+            .iter()
+            .map(|id| graph.get(*id).unwrap().prop_f32(PROP_VOLUME).unwrap_or(0.0))
+            .collect();
+
+        let preset = HircPreset::BusVolume {
+            bus: BusRef::Id(bus_id),
+            db: -6.0,
+        };
+        let changed = preset.apply(&mut bank).unwrap();
+        assert_eq!(changed, roots);
+
+        let graph = HircGraph::from_entries(hirc_entries(&bank)).unwrap();
+        for (id, before) in roots.iter().zip(before) {
+            let after = graph.get(*id).unwrap().prop_f32(PROP_VOLUME).unwrap();
+            assert_eq!(after, before - 6.0);
+        }
+
+        // written bank can be read back
+        let mut buf = io::Cursor::new(vec![]);
+        bank.write_to(&mut buf).unwrap();
+        buf.set_position(0);
+        let reread = Bnk::from_reader(&mut buf).unwrap();
+        assert_eq!(hirc_entries(&reread).len(), hirc_entries(&bank).len());
+    }
+
+    #[test]
+    fn test_prop_edit() {
+        let mut bank = load_bank();
+        let (sound_id, source_id, parent_id) = hirc_entries(&bank)
+            .iter()
+            .find_map(
+                |entry| match HircObject::parse(entry.type_id, &entry.data, entry.layout) {
+                    Some(HircObject::Sound(sound)) => Some((
+                        entry.id,
+                        sound.source.source_id,
+                        sound.node.direct_parent_id,
+                    )),
+                    _ => None,
+                },
+            )
+            .unwrap();
+        let graph = HircGraph::from_entries(hirc_entries(&bank)).unwrap();
+        let volume = graph.get(sound_id).unwrap().prop_f32(PROP_VOLUME);
+        let parent_pitch = graph.get(parent_id).unwrap().prop_f32(PROP_PITCH);
+
+        // sounds by source wem ID
+        let edit = PropEdit {
+            target: PropTarget::Source(source_id),
+            volume: Some(-6.0),
+            pitch: None,
+            looping: None,
+        };
+        assert!(edit.apply(&mut bank).unwrap().contains(&sound_id));
+        // object IDs are not source IDs
+        let edit = PropEdit {
+            target: PropTarget::Object(source_id),
+            ..edit
+        };
+        assert!(!edit.apply(&mut bank).unwrap().contains(&sound_id));
+        let edit = PropEdit {
+            target: PropTarget::Object(parent_id),
+            volume: None,
+            pitch: Some(-100.0),
+            looping: None,
+        };
+        assert_eq!(edit.apply(&mut bank).unwrap(), [parent_id]);
+        assert_eq!(
+            edit.to_string(),
+            format!("object {} pitch -100 cents", parent_id)
+        );
+
+        let graph = HircGraph::from_entries(hirc_entries(&bank)).unwrap();
+        let sound = graph.get(sound_id).unwrap();
+        assert_eq!(
+            sound.prop_f32(PROP_VOLUME),
+            Some(volume.unwrap_or(0.0) - 6.0)
+        );
+        let parent = graph.get(parent_id).unwrap();
+        assert_eq!(
+            parent.prop_f32(PROP_PITCH),
+            Some(parent_pitch.unwrap_or(0.0) - 100.0)
+        );
+        let edit = PropEdit {
+            target: PropTarget::Object(1),
+            volume: Some(1.0),
+            pitch: None,
+            looping: None,
+        };
+        assert!(edit.apply(&mut bank).unwrap().is_empty());
+
+        // loop is only set on sounds
+        let edit = PropEdit {
+            target: PropTarget::Object(parent_id),
+            volume: None,
+            pitch: None,
+            looping: Some(LoopSetting::Enabled(true)),
+        };
+        assert!(edit.apply(&mut bank).unwrap().is_empty());
+        let edit = PropEdit {
+            target: PropTarget::Object(sound_id),
+            volume: None,
+            pitch: None,
+            looping: Some(LoopSetting::Enabled(true)),
+        };
+        assert_eq!(edit.apply(&mut bank).unwrap(), [sound_id]);
+        assert_eq!(
+            edit.to_string(),
+            format!("object {} loop infinite", sound_id)
+        );
+        let graph = HircGraph::from_entries(hirc_entries(&bank)).unwrap();
+        assert_eq!(graph.get(sound_id).unwrap().prop(PROP_LOOP), Some(0));
+        assert_eq!(
+            graph.get(sound_id).unwrap().prop_f32(PROP_VOLUME),
+            Some(volume.unwrap_or(0.0) - 6.0)
+        );
+        let edit = PropEdit {
+            looping: Some(LoopSetting::Enabled(false)),
+            ..edit
+        };
+        edit.apply(&mut bank).unwrap();
+        let graph = HircGraph::from_entries(hirc_entries(&bank)).unwrap();
+        assert_eq!(graph.get(sound_id).unwrap().prop(PROP_LOOP), None);
+        let loops: PropEdit = toml::from_str("source = 1\nloop = 3").unwrap();
+        assert_eq!(loops.target, PropTarget::Source(1));
+        assert_eq!(loops.looping, Some(LoopSetting::Count(3)));
+        // one kind of target, the IDs are of different objects
+        assert!(toml::from_str::<PropEdit>("object = 1\nsource = 1").is_err());
+        assert!(toml::from_str::<PropEdit>("target = 1").is_err());
+    }
+
+    #[test]
+    fn test_remap_sources() {
+        let mut bank = load_bank();
+        let (sound_id, source_id) = hirc_entries(&bank)
+            .iter()
+            .find_map(
+                |entry| match HircObject::parse(entry.type_id, &entry.data, entry.layout) {
+                    Some(HircObject::Sound(sound))
+                        if sound.source.stream_type == STREAM_TYPE_IN_MEMORY =>
+                    {
+                        Some((entry.id, sound.source.source_id))
+                    }
+                    _ => None,
+                },
+            )
+            .unwrap();
+        let length = hirc_entries(&bank).len();
+
+        let sizes = HashMap::from([(123456789, 4321)]);
+        let changed = remap_sources(&mut bank, &HashMap::from([(source_id, 123456789)]), &sizes);
+        assert!(changed.contains(&(sound_id, source_id)));
+        let entry = hirc_entries(&bank)
+            .iter()
+            .find(|entry| entry.id == sound_id)
+            .unwrap();
+        let Some(HircObject::Sound(sound)) =
+            HircObject::parse(entry.type_id, &entry.data, entry.layout)
+        else {
+            panic!("sound not parsed");
+        };
+        assert_eq!(sound.source.source_id, 123456789);
+        assert_eq!(sound.source.media_size, 4321);
+        let remap = HashMap::from([(source_id, 1)]);
+        assert!(remap_sources(&mut bank, &remap, &sizes).is_empty());
+
+        let mut buf = io::Cursor::new(vec![]);
+        bank.write_to(&mut buf).unwrap();
+        buf.set_position(0);
+        let reread = Bnk::from_reader(&mut buf).unwrap();
+        assert_eq!(hirc_entries(&reread).len(), length);
+    }
+
+    #[test]
+    fn test_hirc_diff() {
+        let entry = |type_id, id, data: &[u8]| HircEntry {
+            type_id,
+            length: data.len() as u32 + 4,
+            id,
+            data: data.to_vec(),
+            layout: HircLayout::default(),
+        };
+        let old = [
+            entry(HIRC_SOUND, 1, &[0; 8]),
+            entry(HIRC_SOUND, 2, &[0; 8]),
+            entry(HIRC_EVENT, 3, &[0; 4]),
+        ];
+        let new = [
+            entry(HIRC_SOUND, 1, &[0; 8]),
+            entry(HIRC_SOUND, 2, &[1; 12]),
+            entry(HIRC_ACTION, 4, &[0; 6]),
+        ];
+        let diff = HircDiff::new(&old, &new);
+        let changes: Vec<(u32, ChangeKind)> = diff.changes.iter().map(|c| (c.id, c.kind)).collect();
+        assert_eq!(
+            changes,
+            [
+                (2, ChangeKind::Changed),
+                (3, ChangeKind::Removed),
+                (4, ChangeKind::Added)
+            ]
+        );
+        assert_eq!(diff.changes[0].to_string(), "~ Sound 2: 12 -> 16 bytes");
+
+        let sound = diff.types.iter().find(|t| t.type_id == HIRC_SOUND).unwrap();
+        assert_eq!((sound.old_count, sound.new_count, sound.changed), (2, 2, 1));
+        let event = diff.types.iter().find(|t| t.type_id == HIRC_EVENT).unwrap();
+        assert_eq!((event.old_count, event.new_count, event.removed), (1, 0, 1));
+    }
+}
@@ -0,0 +1,285 @@
+//! Managed history of packaged outputs, to roll a project back to an
+//! earlier build.
+//!
+//! When enabled in config, every packaged bundle is copied to
+//! `history/<project>-<hash>/<build>/` next to `config.toml`, and listed in
+//! `history/<project>-<hash>/history.json`, the hash of the project path
+//! telling projects of the same name apart. Only the last builds up to a
+//! limit are kept, older ones are deleted.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{deploy, utils};
+
+type Result<T> = std::result::Result<T, HistoryError>;
+
+pub const HISTORY_DIR: &str = "history";
+const INDEX_FILE_NAME: &str = "history.json";
+
+#[derive(Debug, thiserror::Error)]
+pub enum HistoryError {
+    #[error("Failed to access {}: {source}", path.display())]
+    File { path: PathBuf, source: io::Error },
+
+    #[error("Bad history index {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error("Build {0} not found in history.")]
+    BuildNotFound(u32),
+    #[error("Build {id} in history is missing or modified: {}", path.display())]
+    BuildCorrupted { id: u32, path: PathBuf },
+}
+
+impl HistoryError {
+    /// Attach the path to an IO error.
+    fn file(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| HistoryError::File { path, source }
+    }
+}
+
+/// A packaged output kept in history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Build {
+    /// Sequential number in the history of its project, from 1.
+    pub id: u32,
+    /// Seconds since Unix epoch when recorded.
+    pub time: u64,
+    pub file_name: String,
+    /// Path the bundle was packaged to, where it is restored by default.
+    pub output_path: PathBuf,
+    pub size: u64,
+    /// SHA-256 of the bundle in hex.
+    pub sha256: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HistoryIndex {
+    project_path: PathBuf,
+    #[serde(default)]
+    builds: Vec<Build>,
+}
+
+/// Builds of a project, oldest first.
+#[derive(Debug)]
+pub struct History {
+    dir: PathBuf,
+    index: HistoryIndex,
+}
+
+impl History {
+    /// History of a project under history root `root`, empty if none recorded.
+    pub fn open(root: impl AsRef<Path>, project_path: impl AsRef<Path>) -> Result<Self> {
+        let project_path = project_path.as_ref();
+        let project_path = fs::canonicalize(project_path).unwrap_or(project_path.to_path_buf());
+        let name = project_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        // projects of the same name in other folders are kept apart
+        let dir = root.as_ref().join(format!(
+            "{}-{:08x}",
+            name,
+            utils::fnv1_32(&project_path.to_string_lossy())
+        ));
+        let mut history = Self::load(dir)?;
+        history.index.project_path = project_path;
+        Ok(history)
+    }
+
+    /// History of a project next to `config.toml`.
+    pub fn installed(project_path: impl AsRef<Path>) -> Result<Self> {
+        Self::open(HISTORY_DIR, project_path)
+    }
+
+    /// Histories of all projects under history root `root`.
+    pub fn all(root: impl AsRef<Path>) -> Result<Vec<Self>> {
+        let root = root.as_ref();
+        let mut histories = vec![];
+        if !root.is_dir() {
+            return Ok(histories);
+        }
+        for entry in fs::read_dir(root).map_err(HistoryError::file(root))? {
+            let path = entry.map_err(HistoryError::file(root))?.path();
+            if path.join(INDEX_FILE_NAME).is_file() {
+                histories.push(Self::load(path)?);
+            }
+        }
+        histories.sort_by(|a, b| a.index.project_path.cmp(&b.index.project_path));
+        Ok(histories)
+    }
+
+    fn load(dir: PathBuf) -> Result<Self> {
+        let path = dir.join(INDEX_FILE_NAME);
+        let index = if path.is_file() {
+            serde_json::from_slice(&fs::read(&path).map_err(HistoryError::file(&path))?)
+                .map_err(|source| HistoryError::Parse { path, source })?
+        } else {
+            HistoryIndex::default()
+        };
+        Ok(Self { dir, index })
+    }
+
+    pub fn project_path(&self) -> &Path {
+        &self.index.project_path
+    }
+
+    pub fn builds(&self) -> &[Build] {
+        &self.index.builds
+    }
+
+    /// Path of the bundle of a build in history.
+    pub fn bundle_path(&self, build: &Build) -> PathBuf {
+        self.dir.join(build.id.to_string()).join(&build.file_name)
+    }
+
+    /// Copy a packaged bundle into history, keeping the last `keep` builds.
+    pub fn record(&mut self, output_path: impl AsRef<Path>, keep: usize) -> Result<&Build> {
+        let output_path = output_path.as_ref();
+        let id = self.index.builds.last().map_or(1, |build| build.id + 1);
+        let build = Build {
+            id,
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_secs())
+                .unwrap_or_default(),
+            file_name: output_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            output_path: fs::canonicalize(output_path).map_err(HistoryError::file(output_path))?,
+            size: fs::metadata(output_path)
+                .map_err(HistoryError::file(output_path))?
+                .len(),
+            sha256: utils::to_hex(
+                &deploy::file_sha256(output_path).map_err(HistoryError::file(output_path))?,
+            ),
+        };
+        let bundle_path = self.bundle_path(&build);
+        let build_dir = bundle_path.parent().unwrap();
+        fs::create_dir_all(build_dir).map_err(HistoryError::file(build_dir))?;
+        fs::copy(output_path, &bundle_path).map_err(HistoryError::file(&bundle_path))?;
+        self.index.builds.push(build);
+
+        let expired = self.index.builds.len().saturating_sub(keep.max(1));
+        for build in self.index.builds.drain(..expired) {
+            let build_dir = self.dir.join(build.id.to_string());
+            if let Err(e) = fs::remove_dir_all(&build_dir) {
+                log::warn!("Failed to delete {}: {}", build_dir.display(), e);
+            }
+        }
+        self.save()?;
+        Ok(self.index.builds.last().unwrap())
+    }
+
+    /// Copy a build back to `target`, or where it was packaged to.
+    /// Returns the restored path.
+    pub fn restore(&self, id: u32, target: Option<&Path>) -> Result<PathBuf> {
+        let build = self
+            .index
+            .builds
+            .iter()
+            .find(|build| build.id == id)
+            .ok_or(HistoryError::BuildNotFound(id))?;
+        let bundle_path = self.bundle_path(build);
+        let intact = bundle_path.is_file()
+            && utils::to_hex(
+                &deploy::file_sha256(&bundle_path).map_err(HistoryError::file(&bundle_path))?,
+            ) == build.sha256;
+        if !intact {
+            return Err(HistoryError::BuildCorrupted {
+                id,
+                path: bundle_path,
+            });
+        }
+        let target = target.unwrap_or(&build.output_path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(HistoryError::file(parent))?;
+        }
+        // copied next to the target first, so a failed copy leaves it untouched
+        let mut staging = target.as_os_str().to_owned();
+        staging.push(".restore");
+        fs::copy(&bundle_path, &staging).map_err(HistoryError::file(&staging))?;
+        fs::rename(&staging, target).map_err(HistoryError::file(target))?;
+        Ok(target.to_path_buf())
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = self.dir.join(INDEX_FILE_NAME);
+        let content =
+            serde_json::to_vec_pretty(&self.index).map_err(|source| HistoryError::Parse {
+                path: path.clone(),
+                source,
+            })?;
+        fs::write(&path, content).map_err(HistoryError::file(&path))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join(HISTORY_DIR);
+        let project_path = dir.path().join("Synth.sbnk.1.X64.project");
+        fs::create_dir_all(&project_path).unwrap();
+        let output_path = dir.path().join("Synth.sbnk.1.X64");
+
+        let mut history = History::open(&root, &project_path).unwrap();
+        for build in 1..=3u8 {
+            fs::write(&output_path, [build; 4]).unwrap();
+            assert_eq!(history.record(&output_path, 2).unwrap().id, build as u32);
+        }
+        // only the last 2 builds are kept
+        let history = History::open(&root, &project_path).unwrap();
+        let ids: Vec<u32> = history.builds().iter().map(|build| build.id).collect();
+        assert_eq!(ids, [2, 3]);
+        assert!(
+            !root
+                .read_dir()
+                .unwrap()
+                .next()
+                .unwrap()
+                .unwrap()
+                .path()
+                .join("1")
+                .exists()
+        );
+        assert!(matches!(
+            history.restore(1, None),
+            Err(HistoryError::BuildNotFound(1))
+        ));
+
+        let restored = history.restore(2, None).unwrap();
+        assert_eq!(restored, fs::canonicalize(&output_path).unwrap());
+        assert_eq!(fs::read(&output_path).unwrap(), [2; 4]);
+        let copy = dir.path().join("copy").join("Synth.sbnk.1.X64");
+        history.restore(3, Some(&copy)).unwrap();
+        assert_eq!(fs::read(&copy).unwrap(), [3; 4]);
+
+        // a modified build is not restored
+        fs::write(history.bundle_path(&history.builds()[0]), [9; 4]).unwrap();
+        assert!(matches!(
+            history.restore(2, None),
+            Err(HistoryError::BuildCorrupted { id: 2, .. })
+        ));
+
+        let all = History::all(&root).unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(
+            all[0].project_path(),
+            fs::canonicalize(&project_path).unwrap()
+        );
+    }
+}
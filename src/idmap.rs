@@ -0,0 +1,169 @@
+//! ID remap table for porting a project to a similar bundle.
+//!
+//! Stored as `idmap.toml` in project folder. Replace files and directives
+//! targeting IDs of another bundle are retargeted on repack:
+//!
+//! ```toml
+//! # bundle the replace files were made for, required by `align`
+//! from_bundle = "Wp01_Cmn_m.sbnk.1.X64"
+//! # map unlisted IDs to the entry at the same index
+//! align = "index"
+//!
+//! [ids]
+//! 123456 = 654321
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::project::IdOrIndex;
+
+type Result<T> = std::result::Result<T, IdMapError>;
+
+pub const ID_MAP_FILE_NAME: &str = "idmap.toml";
+
+#[derive(Debug, thiserror::Error)]
+pub enum IdMapError {
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("Failed to parse {ID_MAP_FILE_NAME}: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("Bad ID in {ID_MAP_FILE_NAME}: {0}")]
+    BadId(String),
+    #[error("align = \"index\" in {ID_MAP_FILE_NAME} requires from_bundle.")]
+    MissingBundle,
+}
+
+/// How IDs not listed in `[ids]` are mapped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Align {
+    /// Unlisted IDs are kept.
+    #[default]
+    None,
+    /// Unlisted IDs are mapped to the entry at the same index.
+    Index,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdMapFile {
+    from_bundle: Option<PathBuf>,
+    #[serde(default)]
+    align: Align,
+    #[serde(default)]
+    ids: BTreeMap<String, u32>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IdMap {
+    /// Bundle the IDs are aligned from, relative to project folder or its parent folder.
+    pub from_bundle: Option<PathBuf>,
+    pub align: Align,
+    /// Old ID to new ID.
+    ids: HashMap<u32, u32>,
+}
+
+impl IdMap {
+    /// Load remap table from project folder, returns empty table if not present.
+    pub fn load(project_path: impl AsRef<Path>) -> Result<Self> {
+        let path = project_path.as_ref().join(ID_MAP_FILE_NAME);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)?;
+        let file: IdMapFile = toml::from_str(&content)?;
+        if file.align != Align::None && file.from_bundle.is_none() {
+            return Err(IdMapError::MissingBundle);
+        }
+        let mut ids = HashMap::new();
+        for (old_id, new_id) in file.ids {
+            let old_id = old_id
+                .trim()
+                .parse()
+                .map_err(|_| IdMapError::BadId(old_id.clone()))?;
+            ids.insert(old_id, new_id);
+        }
+        Ok(Self {
+            from_bundle: file.from_bundle,
+            align: file.align,
+            ids,
+        })
+    }
+
+    /// Map IDs of `from_bundle` to entries at the same index of the current
+    /// bundle, both in index order. Listed IDs and IDs shared by both bundles
+    /// are kept. Returns the number of IDs added.
+    pub fn align_by_index(&mut self, old_ids: &[u32], new_ids: &[u32]) -> usize {
+        let mut added = 0;
+        for (&old_id, &new_id) in old_ids.iter().zip(new_ids) {
+            let shared = new_ids.contains(&old_id) || old_ids.contains(&new_id);
+            if shared || self.ids.contains_key(&old_id) {
+                continue;
+            }
+            self.ids.insert(old_id, new_id);
+            added += 1;
+        }
+        added
+    }
+
+    /// Target in the current bundle, indices are never remapped.
+    pub fn map(&self, target: IdOrIndex) -> IdOrIndex {
+        match target {
+            IdOrIndex::Id(id) => IdOrIndex::Id(self.ids.get(&id).copied().unwrap_or(id)),
+            IdOrIndex::Index(_) => target,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_id_map() {
+        let project_path = tempfile::tempdir().unwrap();
+        let id_map = IdMap::load(project_path.path()).unwrap();
+        assert_eq!(id_map.map(IdOrIndex::Id(100)), IdOrIndex::Id(100));
+
+        let path = project_path.path().join(ID_MAP_FILE_NAME);
+        fs::write(&path, "align = \"index\"\n").unwrap();
+        assert!(matches!(
+            IdMap::load(project_path.path()),
+            Err(IdMapError::MissingBundle)
+        ));
+        fs::write(
+            &path,
+            r#"
+            from_bundle = "Other.sbnk.1.X64"
+            align = "index"
+
+            [ids]
+            100 = 999
+            "#,
+        )
+        .unwrap();
+        let mut id_map = IdMap::load(project_path.path()).unwrap();
+        assert_eq!(id_map.map(IdOrIndex::Id(100)), IdOrIndex::Id(999));
+        assert_eq!(id_map.map(IdOrIndex::Index(0)), IdOrIndex::Index(0));
+
+        // 100 is listed, 300 is shared by both bundles
+        let added = id_map.align_by_index(&[100, 200, 300, 400], &[10, 20, 30, 300]);
+        assert_eq!(added, 1);
+        assert_eq!(id_map.map(IdOrIndex::Id(100)), IdOrIndex::Id(999));
+        assert_eq!(id_map.map(IdOrIndex::Id(200)), IdOrIndex::Id(20));
+        assert_eq!(id_map.map(IdOrIndex::Id(300)), IdOrIndex::Id(300));
+        assert_eq!(id_map.map(IdOrIndex::Id(400)), IdOrIndex::Id(400));
+
+        fs::write(&path, "[ids]\nabc = 1\n").unwrap();
+        assert!(matches!(
+            IdMap::load(project_path.path()),
+            Err(IdMapError::BadId(_))
+        ));
+    }
+}
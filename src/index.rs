@@ -0,0 +1,132 @@
+//! Unified index of media across dumped bundles.
+//!
+//! Written as `index.json` in the output root of batch dumps, mapping every
+//! media ID to all bundles it is found in.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::project::{ProjectError, SoundToolProject};
+
+type Result<T> = std::result::Result<T, IndexError>;
+
+pub const INDEX_FILE_NAME: &str = "index.json";
+
+#[derive(Debug, thiserror::Error)]
+pub enum IndexError {
+    #[error("Index IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("Failed to parse {INDEX_FILE_NAME}: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Failed to read project: {0}")]
+    Project(#[from] ProjectError),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MediaIndex {
+    /// Media ID to all locations it is found.
+    pub media: BTreeMap<u32, Vec<MediaLocation>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediaLocation {
+    /// Bundle path relative to the dump root, with `/` separators.
+    pub bundle: String,
+    pub index: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Entry file name in the dumped project.
+    pub name: String,
+}
+
+impl MediaIndex {
+    /// Load index file, returns empty index if not present.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let reader = io::BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut writer = io::BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Add entries of a dumped project, replacing those previously added
+    /// for the same bundle. Returns the number of entries added.
+    pub fn add_project(&mut self, bundle: &str, project: &SoundToolProject) -> Result<usize> {
+        self.remove_bundle(bundle);
+        let entries = project.media_entries()?;
+        for entry in &entries {
+            self.media.entry(entry.id).or_default().push(MediaLocation {
+                bundle: bundle.to_string(),
+                index: entry.idx,
+                language: entry.language.clone(),
+                name: entry.file_name.clone(),
+            });
+        }
+        Ok(entries.len())
+    }
+
+    pub fn remove_bundle(&mut self, bundle: &str) {
+        self.media.retain(|_, locations| {
+            locations.retain(|location| location.bundle != bundle);
+            !locations.is_empty()
+        });
+    }
+
+    /// All locations of a media ID.
+    #[allow(dead_code)]
+    pub fn find(&self, id: u32) -> &[MediaLocation] {
+        self.media.get(&id).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_media_index() {
+        let output_root = tempfile::tempdir().unwrap();
+        let project =
+            SoundToolProject::dump_pck("test_files/Cat_cmn_m.spck.1.X64", output_root.path())
+                .unwrap();
+        let entries = project.media_entries().unwrap();
+        assert!(!entries.is_empty());
+
+        let mut index = MediaIndex::default();
+        let added = index
+            .add_project("sound/Cat_cmn_m.spck.1.X64", &project)
+            .unwrap();
+        assert_eq!(added, entries.len());
+        // adding again replaces previous entries
+        index
+            .add_project("sound/Cat_cmn_m.spck.1.X64", &project)
+            .unwrap();
+        let locations = index.find(entries[0].id);
+        assert_eq!(locations.len(), 1);
+        assert_eq!(locations[0].index, entries[0].idx);
+        assert_eq!(locations[0].name, entries[0].file_name);
+        assert!(locations[0].language.is_some());
+
+        let index_path = output_root.path().join(INDEX_FILE_NAME);
+        index.save(&index_path).unwrap();
+        let loaded = MediaIndex::load(&index_path).unwrap();
+        assert_eq!(loaded.find(entries[0].id), locations);
+        index.remove_bundle("sound/Cat_cmn_m.spck.1.X64");
+        assert!(index.media.is_empty());
+    }
+}
@@ -0,0 +1,302 @@
+//! Label files of decoded previews, carrying the entry ID and the loop points
+//! and markers of the wem, so replacements can be edited to the same regions.
+//!
+//! Loops are read from the `smpl` chunk, markers from `cue ` with names from
+//! `labl` in `LIST/adtl`. Positions are in samples, at the sample rate of `fmt `.
+
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Format of label files written next to decoded files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelFormat {
+    /// Audacity label track, import with File > Import > Labels.
+    Audacity,
+    /// CUE sheet, exact sample positions are kept in `REM` lines.
+    Cue,
+}
+
+impl LabelFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            LabelFormat::Audacity => "txt",
+            LabelFormat::Cue => "cue",
+        }
+    }
+}
+
+/// Loop region, `end` is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Loop {
+    pub start: u32,
+    pub end: u32,
+}
+
+/// Marker of a `cue ` point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Marker {
+    pub position: u32,
+    pub name: Option<String>,
+}
+
+/// Loop points and markers of a RIFF file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Markers {
+    pub sample_rate: u32,
+    pub loops: Vec<Loop>,
+    pub markers: Vec<Marker>,
+}
+
+impl Markers {
+    /// Read from wem or wav data, empty if it is not a RIFF file.
+    pub fn from_riff(data: &[u8]) -> Self {
+        let mut this = Self::default();
+        if data.len() < 12 || &data[0..4] != b"RIFF" {
+            return this;
+        }
+        let mut cue_ids = vec![];
+        let mut names = vec![];
+        for (id, body) in chunks(&data[12..]) {
+            match id {
+                b"fmt " if body.len() >= 8 => this.sample_rate = read_u32(body, 4),
+                b"smpl" if body.len() >= 36 => {
+                    let count = read_u32(body, 28) as usize;
+                    for i in 0..count {
+                        let pos = 36 + i * 24;
+                        if pos + 24 > body.len() {
+                            break;
+                        }
+                        this.loops.push(Loop {
+                            start: read_u32(body, pos + 8),
+                            // inclusive in smpl
+                            end: read_u32(body, pos + 12).saturating_add(1),
+                        });
+                    }
+                }
+                b"cue " if body.len() >= 4 => {
+                    let count = read_u32(body, 0) as usize;
+                    for i in 0..count {
+                        let pos = 4 + i * 24;
+                        if pos + 24 > body.len() {
+                            break;
+                        }
+                        cue_ids.push(read_u32(body, pos));
+                        this.markers.push(Marker {
+                            position: read_u32(body, pos + 20),
+                            name: None,
+                        });
+                    }
+                }
+                b"LIST" if body.starts_with(b"adtl") => {
+                    for (sub_id, sub_body) in chunks(&body[4..]) {
+                        if sub_id == b"labl" && sub_body.len() >= 4 {
+                            let text = &sub_body[4..];
+                            let text =
+                                &text[..text.iter().position(|&b| b == 0).unwrap_or(text.len())];
+                            names.push((
+                                read_u32(sub_body, 0),
+                                String::from_utf8_lossy(text).to_string(),
+                            ));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        for (marker, cue_id) in this.markers.iter_mut().zip(cue_ids) {
+            marker.name = names
+                .iter()
+                .find(|(id, _)| *id == cue_id)
+                .map(|(_, name)| name.clone());
+        }
+        this
+    }
+
+    fn seconds(&self, samples: u32) -> f64 {
+        samples as f64 / self.sample_rate.max(1) as f64
+    }
+
+    fn marker_name(marker: &Marker, i: usize) -> String {
+        marker
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("Marker {}", i + 1))
+    }
+
+    /// Audacity label track, tab separated `start end label` in seconds.
+    pub fn to_audacity(&self, id: u32) -> String {
+        let mut text = format!("0.000000\t0.000000\t{}\n", id);
+        for (i, region) in self.loops.iter().enumerate() {
+            let _ = writeln!(
+                text,
+                "{:.6}\t{:.6}\tLoop {}",
+                self.seconds(region.start),
+                self.seconds(region.end),
+                i + 1
+            );
+        }
+        for (i, marker) in self.markers.iter().enumerate() {
+            let seconds = self.seconds(marker.position);
+            let name = Self::marker_name(marker, i);
+            let _ = writeln!(text, "{:.6}\t{:.6}\t{}", seconds, seconds, name);
+        }
+        text
+    }
+
+    /// CUE sheet of `audio_file_name`, a track for the entry and each loop
+    /// start and marker.
+    pub fn to_cue_sheet(&self, id: u32, audio_file_name: &str) -> String {
+        let mut text = format!("REM ENTRY {}\n", id);
+        let _ = writeln!(text, "REM SAMPLE_RATE {}", self.sample_rate);
+        for (i, region) in self.loops.iter().enumerate() {
+            let _ = writeln!(
+                text,
+                "REM LOOP {} START {} END {}",
+                i + 1,
+                region.start,
+                region.end
+            );
+        }
+        let _ = writeln!(text, "TITLE \"{}\"", id);
+        let _ = writeln!(text, "FILE \"{}\" WAVE", audio_file_name);
+        let mut tracks = vec![(0, id.to_string())];
+        for (i, region) in self.loops.iter().enumerate() {
+            tracks.push((region.start, format!("Loop {} start", i + 1)));
+        }
+        for (i, marker) in self.markers.iter().enumerate() {
+            tracks.push((marker.position, Self::marker_name(marker, i)));
+        }
+        // tracks must be in order
+        tracks[1..].sort_by_key(|(position, _)| *position);
+        for (number, (position, title)) in tracks.iter().enumerate() {
+            let _ = writeln!(text, "  TRACK {:02} AUDIO", number + 1);
+            let _ = writeln!(text, "    TITLE \"{}\"", title.replace('"', "'"));
+            let _ = writeln!(text, "    INDEX 01 {}", self.cue_time(*position));
+        }
+        text
+    }
+
+    /// `mm:ss:ff` with 75 frames per second.
+    fn cue_time(&self, samples: u32) -> String {
+        let frames = (self.seconds(samples) * 75.0).round() as u64;
+        format!(
+            "{:02}:{:02}:{:02}",
+            frames / 75 / 60,
+            frames / 75 % 60,
+            frames % 75
+        )
+    }
+}
+
+/// Write the label file of an entry next to its decoded file, returns its path.
+pub fn write_labels(
+    wem_data: &[u8],
+    id: u32,
+    audio_path: &Path,
+    format: LabelFormat,
+) -> io::Result<PathBuf> {
+    let markers = Markers::from_riff(wem_data);
+    let text = match format {
+        LabelFormat::Audacity => markers.to_audacity(id),
+        LabelFormat::Cue => {
+            let audio_file_name = audio_path.file_name().unwrap_or_default().to_string_lossy();
+            markers.to_cue_sheet(id, &audio_file_name)
+        }
+    };
+    let path = audio_path.with_extension(format.extension());
+    fs::write(&path, text)?;
+    Ok(path)
+}
+
+/// Chunks of RIFF data after the form type, truncated chunks are skipped.
+fn chunks(mut data: &[u8]) -> impl Iterator<Item = (&[u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        if data.len() < 8 {
+            return None;
+        }
+        let id: &[u8; 4] = data[0..4].try_into().unwrap();
+        let size = read_u32(data, 4) as usize;
+        let body = data.get(8..8 + size)?;
+        data = data.get(8 + size + (size & 1)..).unwrap_or_default();
+        Some((id, body))
+    })
+}
+
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(body);
+        if body.len() & 1 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn u32s(values: &[u32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_markers() {
+        let fmt = u32s(&[0x0001_FFFF, 48000, 0, 0]);
+        let mut smpl = u32s(&[0; 7]);
+        smpl.extend(u32s(&[1, 0, 0, 0, 24000, 96000 - 1, 0, 0]));
+        let cue = u32s(&[1, 7, 0, 0, 0, 0, 12000]);
+        let mut adtl = b"adtl".to_vec();
+        adtl.extend(chunk(b"labl", &[&u32s(&[7])[..], b"Hit\0"].concat()));
+        let body = [
+            &b"WAVE"[..],
+            &chunk(b"fmt ", &fmt),
+            &chunk(b"cue ", &cue),
+            &chunk(b"LIST", &adtl),
+            &chunk(b"smpl", &smpl),
+            &chunk(b"data", &[0; 5]),
+        ]
+        .concat();
+        let data = [&b"RIFF"[..], &(body.len() as u32).to_le_bytes(), &body].concat();
+
+        let markers = Markers::from_riff(&data);
+        assert_eq!(markers.sample_rate, 48000);
+        assert_eq!(
+            markers.loops,
+            [Loop {
+                start: 24000,
+                end: 96000
+            }]
+        );
+        assert_eq!(markers.markers[0].position, 12000);
+        assert_eq!(markers.markers[0].name.as_deref(), Some("Hit"));
+
+        assert_eq!(
+            markers.to_audacity(500002),
+            "0.000000\t0.000000\t500002\n\
+             0.500000\t2.000000\tLoop 1\n\
+             0.250000\t0.250000\tHit\n"
+        );
+        let cue_sheet = markers.to_cue_sheet(500002, "[001]500002.wav");
+        assert!(cue_sheet.contains("REM LOOP 1 START 24000 END 96000\n"));
+        assert!(cue_sheet.contains("FILE \"[001]500002.wav\" WAVE\n"));
+        // marker before loop start
+        assert!(cue_sheet.contains("  TRACK 02 AUDIO\n    TITLE \"Hit\"\n    INDEX 01 00:00:19\n"));
+        assert!(
+            cue_sheet
+                .contains("  TRACK 03 AUDIO\n    TITLE \"Loop 1 start\"\n    INDEX 01 00:00:38\n")
+        );
+
+        assert_eq!(Markers::from_riff(b"OggS"), Markers::default());
+    }
+}
@@ -0,0 +1,157 @@
+//! Offsets and lengths of entry data in bundles.
+//!
+//! BNK entries are addressed from the start of DATA section payload,
+//! PCK entries from the start of file. Both are [`DataOffset`]s from the start
+//! of their data region, converted to file positions only when seeking.
+
+use std::{
+    io::{self, Read},
+    ops::{Add, AddAssign},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Length of entry data in bytes.
+#[repr(transparent)]
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct ByteLen(pub u32);
+
+/// Byte offset from the start of a data region.
+#[repr(transparent)]
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct DataOffset(pub u32);
+
+impl ByteLen {
+    pub fn of(data: &[u8]) -> Self {
+        Self(data.len() as u32)
+    }
+
+    pub fn get(self) -> u32 {
+        self.0
+    }
+
+    pub fn as_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl DataOffset {
+    pub const ZERO: Self = Self(0);
+
+    pub fn get(self) -> u32 {
+        self.0
+    }
+
+    /// Round up to a multiple of `alignment`, 0 or 1 for no alignment.
+    /// `None` if the result does not fit in u32.
+    pub fn align_up(self, alignment: u32) -> Option<Self> {
+        self.0.checked_next_multiple_of(alignment.max(1)).map(Self)
+    }
+
+    /// Offset `length` bytes after this one, `None` if it does not fit in u32.
+    pub fn checked_add(self, length: ByteLen) -> Option<Self> {
+        self.0.checked_add(length.0).map(Self)
+    }
+
+    /// Position in file, with the data region starting at `region_start`.
+    pub fn file_position(self, region_start: u64) -> u64 {
+        region_start + self.0 as u64
+    }
+
+    /// Length from `start` to this offset, `None` if `start` is after this offset.
+    pub fn distance_from(self, start: DataOffset) -> Option<ByteLen> {
+        self.0.checked_sub(start.0).map(ByteLen)
+    }
+}
+
+impl Add<ByteLen> for DataOffset {
+    type Output = DataOffset;
+
+    fn add(self, rhs: ByteLen) -> Self::Output {
+        DataOffset(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign<ByteLen> for DataOffset {
+    fn add_assign(&mut self, rhs: ByteLen) {
+        self.0 += rhs.0;
+    }
+}
+
+impl std::fmt::Display for ByteLen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::fmt::Display for DataOffset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0x{:X}", self.0)
+    }
+}
+
+/// Place blocks of `(length, alignment)` one after another from `start`.
+///
+/// Returns the offset of each block and the end offset, `None` if they do
+/// not fit in u32. Lengths may come from untrusted tables.
+pub fn place_blocks(
+    start: DataOffset,
+    blocks: impl IntoIterator<Item = (ByteLen, u32)>,
+) -> Option<(Vec<DataOffset>, DataOffset)> {
+    let mut offsets = vec![];
+    let mut offset = start;
+    for (length, alignment) in blocks {
+        offset = offset.align_up(alignment)?;
+        offsets.push(offset);
+        offset = offset.checked_add(length)?;
+    }
+    Some((offsets, offset))
+}
+
+/// Write zero bytes of `length`.
+pub fn write_padding<W: io::Write>(writer: &mut W, length: ByteLen) -> io::Result<()> {
+    io::copy(&mut io::repeat(0).take(length.get() as u64), writer)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_place_blocks() {
+        let (offsets, end) = place_blocks(
+            DataOffset(6),
+            [(ByteLen(10), 16), (ByteLen(3), 1), (ByteLen(1), 4)],
+        )
+        .unwrap();
+        assert_eq!(
+            offsets,
+            vec![DataOffset(16), DataOffset(26), DataOffset(32)]
+        );
+        assert_eq!(end, DataOffset(33));
+        assert_eq!(end.distance_from(offsets[2]), Some(ByteLen(1)));
+        assert_eq!(offsets[0].distance_from(end), None);
+        assert_eq!(DataOffset(5).align_up(0), Some(DataOffset(5)));
+        assert_eq!(DataOffset(u32::MAX).align_up(16), None);
+        assert_eq!(
+            place_blocks(DataOffset(16), [(ByteLen(u32::MAX - 16), 1)]),
+            Some((vec![DataOffset(16)], DataOffset(u32::MAX)))
+        );
+        assert_eq!(place_blocks(DataOffset(16), [(ByteLen(u32::MAX), 1)]), None);
+        assert_eq!(
+            place_blocks(
+                DataOffset(16),
+                [(ByteLen(0x8000_0000), 1), (ByteLen(1), 0x8000_0000)]
+            ),
+            None
+        );
+        assert_eq!(DataOffset(16).file_position(100), 116);
+    }
+}
@@ -0,0 +1,171 @@
+//! Dump and repack Wwise sound bundles (BNK and PCK) of Monster Hunter Wilds.
+//!
+//! The library never waits for user input. Paths of external tools are
+//! read from `config.toml`, or searched in default locations unless a
+//! prompt is set with [`transcode::set_tool_path_prompt`].
+//!
+//! ```no_run
+//! let project = mhws_sound_tool::dump("Wp00_Cmn.sbnk.1.X64", "out")?;
+//! // edit files in the project folder, then
+//! mhws_sound_tool::repack(project.project_path(), "out")?;
+//! # Ok::<(), mhws_sound_tool::project::ProjectError>(())
+//! ```
+
+pub mod bnk;
+pub mod config;
+pub mod create;
+pub mod datapack;
+#[cfg(feature = "bundled-audio")]
+pub mod decoder;
+pub mod deploy;
+pub mod detect;
+pub mod endian;
+pub mod ffmpeg;
+pub mod fingerprint;
+pub mod formats;
+pub mod hashes;
+pub mod hirc;
+pub mod history;
+pub mod idmap;
+pub mod index;
+pub mod labels;
+pub mod layout;
+pub mod lock;
+pub mod manifest;
+pub mod metadiff;
+pub mod modinfo;
+pub mod names;
+pub mod order;
+pub mod pak;
+pub mod pck;
+#[cfg(feature = "playback")]
+pub mod playback;
+pub mod preflight;
+pub mod project;
+pub mod render;
+pub mod report;
+pub mod rules;
+pub mod serve;
+pub mod session;
+pub mod similar;
+pub mod stmg;
+pub mod store;
+pub mod transcode;
+pub mod utils;
+pub mod verify;
+pub mod vgmstream;
+pub mod watch;
+pub mod wav;
+pub mod workspace;
+pub mod wwise;
+
+use std::{collections::HashMap, fs, path::Path};
+
+use detect::InputFileType;
+use project::{ProjectError, RepackStats, SoundToolProject};
+use transcode::{TranscodeError, WemConversion};
+use wwise::Conversion;
+
+/// Dump a BNK or PCK bundle to a project folder under `output_root`.
+pub fn dump(
+    input: impl AsRef<Path>,
+    output_root: impl AsRef<Path>,
+) -> Result<SoundToolProject, ProjectError> {
+    let input = input.as_ref();
+    match InputFileType::from_path(input) {
+        Some(InputFileType::Bnk) => SoundToolProject::dump_bnk(input, output_root),
+        Some(InputFileType::Pck) => SoundToolProject::dump_pck(input, output_root),
+        _ => Err(ProjectError::UnsupportedInput(input.to_path_buf())),
+    }
+}
+
+/// Dump a bundle without media files, for versioning its structure.
+///
+/// See [`SoundToolProject::strip_media`], the bundle is needed to repack.
+pub fn dump_meta_only(
+    input: impl AsRef<Path>,
+    output_root: impl AsRef<Path>,
+) -> Result<SoundToolProject, ProjectError> {
+    let project = dump(&input, output_root)?;
+    project.strip_media(input)?;
+    Ok(project)
+}
+
+/// Repack the project folder at `project_path` to a bundle under `output_root`.
+pub fn repack(
+    project_path: impl AsRef<Path>,
+    output_root: impl AsRef<Path>,
+) -> Result<RepackStats, ProjectError> {
+    SoundToolProject::from_path(project_path)?.repack(output_root)
+}
+
+/// Convert sound files to wem files in `output_dir`, named after the inputs.
+///
+/// Inputs other than wav are transcoded with ffmpeg first. Fails before
+/// converting if two inputs would be written to the same wem.
+pub fn convert(
+    inputs: &[impl AsRef<Path>],
+    output_dir: impl AsRef<Path>,
+    platform: &str,
+) -> Result<(), TranscodeError> {
+    let output_dir = output_dir.as_ref();
+    let temp_dir = tempfile::tempdir()?;
+    let mut conversion = WemConversion::new().platform(platform);
+    let mut to_transcode = vec![];
+    // compared ignoring case, like file names on Windows
+    let mut outputs: HashMap<String, &Path> = HashMap::new();
+    for input in inputs {
+        let input = input.as_ref();
+        if !input.is_file() {
+            return Err(TranscodeError::FileNotFound(input.to_path_buf()));
+        }
+        let output = output_dir.join(Path::new(input.file_stem().unwrap()).with_extension("wem"));
+        let key = output.to_string_lossy().to_lowercase();
+        if let Some(first) = outputs.insert(key, input) {
+            return Err(TranscodeError::DuplicateOutput {
+                first: first.to_path_buf(),
+                second: input.to_path_buf(),
+                output,
+            });
+        }
+        if input.extension().unwrap_or_default() == "wav" {
+            conversion = conversion.add_file_to(input, &output, Conversion::default());
+        } else {
+            // inputs of the same name in different folders are staged apart
+            let wav_path = temp_dir.path().join(utils::staging_name(input, "wav"));
+            conversion = conversion.add_file_to(&wav_path, &output, Conversion::default());
+            to_transcode.push((input, wav_path));
+        }
+    }
+    let sources: Vec<&Path> = to_transcode.iter().map(|(input, _)| *input).collect();
+    let wavs = transcode::sounds_to_wav(&sources)?;
+    for ((_, wav_path), wav_data) in to_transcode.iter().zip(wavs) {
+        fs::write(wav_path, wav_data)?;
+    }
+
+    fs::create_dir_all(output_dir)?;
+    transcode::ensure_converted(&conversion.run()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_duplicate_outputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let inputs = [dir.path().join("a/x.mp3"), dir.path().join("b/X.ogg")];
+        for input in &inputs {
+            fs::create_dir_all(input.parent().unwrap()).unwrap();
+            fs::write(input, "sound").unwrap();
+        }
+        let output_dir = dir.path().join("out");
+        let err = convert(&inputs, &output_dir, "Windows").unwrap_err();
+        assert!(matches!(
+            err,
+            TranscodeError::DuplicateOutput { first, second, .. }
+                if first == inputs[0] && second == inputs[1]
+        ));
+        assert!(!output_dir.exists());
+    }
+}
@@ -0,0 +1,304 @@
+//! Project lock file, preventing concurrent repacks of the same project.
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicU32, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+type Result<T> = std::result::Result<T, LockError>;
+
+pub const LOCK_FILE_NAME: &str = ".project.lock";
+/// Locks older than this are stale even if the owner process is still found,
+/// as process IDs may be reused.
+const MAX_LOCK_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+/// Unreadable locks are partially written by their owner for a moment,
+/// older ones are left by an owner crashed while writing.
+const UNREADABLE_LOCK_GRACE: Duration = Duration::from_secs(10);
+
+#[derive(Debug, thiserror::Error)]
+pub enum LockError {
+    #[error("Lock IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error(
+        "Another instance is running on this project. \
+        If not, remove the lock file: {}", path.display()
+    )]
+    Locked {
+        /// Owner process ID, `None` if the lock is unreadable.
+        pid: Option<u32>,
+        path: PathBuf,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LockInfo {
+    pid: u32,
+    /// Unix timestamp in seconds.
+    created: u64,
+}
+
+impl LockInfo {
+    fn is_stale(&self) -> bool {
+        let age = now().saturating_sub(self.created);
+        age > MAX_LOCK_AGE.as_secs() || !process_exists(self.pid)
+    }
+}
+
+/// Lock of a project directory, released on drop.
+#[derive(Debug)]
+pub struct ProjectLock {
+    path: PathBuf,
+}
+
+impl ProjectLock {
+    /// Acquire the lock of a project, taking over stale locks left by
+    /// crashed instances.
+    pub fn acquire(project_path: impl AsRef<Path>) -> Result<Self> {
+        Self::acquire_file(project_path.as_ref().join(LOCK_FILE_NAME))
+    }
+
+    /// Acquire a lock file at `path`, same as [`Self::acquire`].
+    pub fn acquire_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let info = LockInfo {
+            pid: process::id(),
+            created: now(),
+        };
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    file.write_all(serde_json::to_string(&info).unwrap().as_bytes())?;
+                    return Ok(Self { path });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            // unreadable lock may be partially written by the owner
+            let content = fs::read(&path).unwrap_or_default();
+            let owner = serde_json::from_slice::<LockInfo>(&content).ok();
+            match owner {
+                Some(owner) if !owner.is_stale() => {
+                    return Err(LockError::Locked {
+                        pid: Some(owner.pid),
+                        path,
+                    });
+                }
+                Some(owner) => {
+                    warn!(
+                        "Removing stale lock of pid {}: {}",
+                        owner.pid,
+                        path.display()
+                    );
+                    remove_stale(&path, &content)?;
+                }
+                None if !is_abandoned(&path) => {
+                    return Err(LockError::Locked { pid: None, path });
+                }
+                None => {
+                    warn!("Removing unreadable stale lock: {}", path.display());
+                    remove_stale(&path, &content)?;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ProjectLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Remove a stale lock read as `stale`, unless another instance has
+/// replaced it since.
+///
+/// Instances may find the same stale lock at once. The lock is moved aside
+/// before checking its content, so only one of them removes it, and the
+/// others put back the fresh lock they moved.
+fn remove_stale(path: &Path, stale: &[u8]) -> io::Result<()> {
+    static TAKEOVERS: AtomicU32 = AtomicU32::new(0);
+    let mut moved = path.as_os_str().to_owned();
+    moved.push(format!(
+        ".{}-{}.stale",
+        process::id(),
+        TAKEOVERS.fetch_add(1, Ordering::Relaxed)
+    ));
+    let moved = PathBuf::from(moved);
+    match fs::rename(path, &moved) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    }
+    if fs::read(&moved).ok().as_deref() != Some(stale) {
+        // fails if yet another instance has locked it meanwhile
+        match fs::hard_link(&moved, path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => {
+                let _ = fs::remove_file(&moved);
+                return Err(e);
+            }
+        }
+    }
+    fs::remove_file(&moved)
+}
+
+/// Whether an unreadable lock file is older than the grace period.
+fn is_abandoned(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age > UNREADABLE_LOCK_GRACE)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(target_os = "linux")]
+fn process_exists(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+#[cfg(windows)]
+fn process_exists(pid: u32) -> bool {
+    let output = process::Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/FO", "CSV", "/NH"])
+        .output();
+    match output {
+        Ok(output) => tasklist_has_pid(&String::from_utf8_lossy(&output.stdout), pid),
+        // cannot tell, keep the lock
+        Err(_) => true,
+    }
+}
+
+/// Whether CSV output of `tasklist` lists the process ID, compared by
+/// field as other numbers of a line may contain it.
+#[cfg(any(windows, test))]
+fn tasklist_has_pid(output: &str, pid: u32) -> bool {
+    let pid = pid.to_string();
+    output.lines().any(|line| {
+        // "image name","pid","session name","session","memory"
+        line.trim().trim_matches('"').split("\",\"").nth(1) == Some(pid.as_str())
+    })
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn process_exists(pid: u32) -> bool {
+    process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_lock() {
+        let project_path = tempfile::tempdir().unwrap();
+        let lock_path = project_path.path().join(LOCK_FILE_NAME);
+
+        let lock = ProjectLock::acquire(project_path.path()).unwrap();
+        assert!(lock_path.is_file());
+        let err = ProjectLock::acquire(project_path.path()).unwrap_err();
+        assert!(matches!(err, LockError::Locked { pid, .. } if pid == Some(process::id())));
+        drop(lock);
+        assert!(!lock_path.exists());
+
+        // stale lock of an exited process
+        let mut child = process::Command::new(env!("CARGO"))
+            .arg("--version")
+            .stdout(process::Stdio::null())
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        let stale = LockInfo {
+            pid,
+            created: now(),
+        };
+        fs::write(&lock_path, serde_json::to_string(&stale).unwrap()).unwrap();
+        let _lock = ProjectLock::acquire(project_path.path()).unwrap();
+        let content = fs::read_to_string(&lock_path).unwrap();
+        let info: LockInfo = serde_json::from_str(&content).unwrap();
+        assert_eq!(info.pid, process::id());
+    }
+
+    #[test]
+    fn test_stale_lock_takeover() {
+        let project_path = tempfile::tempdir().unwrap();
+        let lock_path = project_path.path().join(LOCK_FILE_NAME);
+        // owner not found as process IDs do not go this high
+        let stale = LockInfo {
+            pid: u32::MAX,
+            created: now(),
+        };
+        for _ in 0..50 {
+            fs::write(&lock_path, serde_json::to_string(&stale).unwrap()).unwrap();
+            let barrier = std::sync::Barrier::new(8);
+            let locks: Vec<Result<ProjectLock>> = std::thread::scope(|scope| {
+                let handles: Vec<_> = (0..8)
+                    .map(|_| {
+                        scope.spawn(|| {
+                            barrier.wait();
+                            ProjectLock::acquire(project_path.path())
+                        })
+                    })
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap()).collect()
+            });
+            assert_eq!(locks.iter().filter(|lock| lock.is_ok()).count(), 1);
+            assert!(lock_path.is_file());
+            drop(locks);
+            assert_eq!(fs::read_dir(project_path.path()).unwrap().count(), 0);
+        }
+    }
+
+    #[test]
+    fn test_unreadable_lock() {
+        let project_path = tempfile::tempdir().unwrap();
+        let lock_path = project_path.path().join(LOCK_FILE_NAME);
+
+        // may be being written by its owner
+        fs::write(&lock_path, "").unwrap();
+        let err = ProjectLock::acquire(project_path.path()).unwrap_err();
+        assert!(matches!(err, LockError::Locked { pid: None, .. }));
+
+        // left by an owner crashed while writing
+        let file = fs::File::options().write(true).open(&lock_path).unwrap();
+        file.set_modified(SystemTime::now() - UNREADABLE_LOCK_GRACE * 2)
+            .unwrap();
+        drop(file);
+        let _lock = ProjectLock::acquire(project_path.path()).unwrap();
+        let content = fs::read_to_string(&lock_path).unwrap();
+        assert!(serde_json::from_str::<LockInfo>(&content).is_ok());
+    }
+
+    #[test]
+    fn test_tasklist_pid() {
+        let output = "\"WwiseConsole.exe\",\"41234\",\"Console\",\"1\",\"12,340 K\"\r\n";
+        assert!(tasklist_has_pid(output, 41234));
+        assert!(!tasklist_has_pid(output, 1234));
+        assert!(!tasklist_has_pid(output, 340));
+        assert!(!tasklist_has_pid(
+            "INFO: No tasks are running which match the specified criteria.",
+            1234
+        ));
+    }
+}
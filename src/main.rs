@@ -1,26 +1,53 @@
-mod bnk;
-mod config;
-mod ffmpeg;
-mod pck;
-mod project;
-mod transcode;
-mod utils;
-mod wwise;
+#[cfg(feature = "gui")]
+mod gui;
+mod output;
+mod prompt;
+#[cfg(feature = "self-update")]
+mod update;
 
 use std::{
-    env, fs,
-    io::Read,
+    collections::BTreeMap,
+    env, fs, io,
     path::{Path, PathBuf},
     sync::atomic::{self, AtomicBool},
 };
 
 use clap::Parser;
 use colored::Colorize;
-use config::Config;
 use dialoguer::Input;
 use eyre::Context;
-use log::{error, info};
-use project::SoundToolProject;
+use log::{error, info, warn};
+use mhws_sound_tool::{
+    bnk,
+    config::{Config, PathsConfig},
+    create,
+    datapack::{DataPack, DataPacks, SearchMatch},
+    deploy,
+    detect::InputFileType,
+    fingerprint::{BundleChange, BundleFingerprint},
+    hashes::{self, HashList, HashStatus},
+    hirc,
+    history::{self, History},
+    index,
+    labels::{self, LabelFormat},
+    metadiff,
+    modinfo::ModInfo,
+    names::NameList,
+    pak::PakReader,
+    project::{self, EntrySort, IdOrIndex, OutputPolicy, SoundToolProject},
+    render, report,
+    rules::RuleSet,
+    serve, similar,
+    transcode::{self, DecodeFormat},
+    utils, verify,
+    watch::ProjectWatcher,
+    workspace::Workspace,
+    wwise,
+};
+use output::{FailureClass, OutputFormat};
+
+const VERSION_CHECK_FLAG: &str = "--version-check";
+const JSON_LOGS_FLAG: &str = "--json-logs";
 
 #[cfg(not(test))]
 static INTERACTIVE_MODE: AtomicBool = AtomicBool::new(true);
@@ -35,35 +62,222 @@ struct Cli {
     /// won't block waiting for user input.
     #[arg(long, default_value = "false")]
     no_interact: bool,
+    /// Read answers of interactive prompts from stdin, one per line,
+    /// as `key = value` or bare values in prompt order.
+    #[arg(long, global = true)]
+    stdin_answers: bool,
+    /// Read answers of interactive prompts from a file,
+    /// same format as `--stdin-answers`.
+    #[arg(long, global = true)]
+    answers: Option<String>,
+    /// Check GitHub releases for a newer version on start.
+    #[arg(long, global = true)]
+    version_check: bool,
+    /// Output format. `json` prints an object of results on stdout, with
+    /// files written, entries replaced and warnings, and implies `--no-interact`.
+    #[arg(long, global = true, value_enum, default_value = "text")]
+    format: OutputFormat,
+    /// Write log events as JSON lines, to stderr instead of the colored log,
+    /// or to a file next to it with `--json-logs=<FILE>`.
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        num_args = 0..=1,
+        require_equals = true
+    )]
+    json_logs: Option<Option<String>>,
 }
 
 #[derive(Debug, clap::Subcommand)]
 enum Command {
     PackageProject(CmdPackageProject),
     UnpackBundle(CmdUnpackBundle),
+    CreatePck(CmdCreatePck),
+    Rebase(CmdRebase),
     SoundToWem(CmdSoundToWem),
+    Transcode(CmdTranscode),
+    Render(CmdRender),
+    Extract(CmdExtract),
+    #[cfg(feature = "playback")]
+    Play(CmdPlay),
+    Deploy(CmdDeploy),
+    Detect(CmdDetect),
+    List(CmdList),
+    DiffMeta(CmdDiffMeta),
+    DiffHirc(CmdDiffHirc),
+    Serve(CmdServe),
+    Report(CmdReport),
+    Validate(CmdValidate),
+    Stats(CmdStats),
+    Verify(CmdVerify),
+    Similar(CmdSimilar),
+    Watch(CmdWatch),
+    StripMedia(CmdStripMedia),
+    Hash(CmdHash),
+    Hashes(CmdHashes),
+    #[command(subcommand)]
+    Datapack(CmdDatapack),
+    #[command(subcommand)]
+    History(CmdHistory),
+    #[cfg(feature = "self-update")]
+    SelfUpdate(CmdSelfUpdate),
+    /// Open the graphical frontend.
+    #[cfg(feature = "gui")]
+    Gui,
 }
 
 #[derive(Debug, clap::Args)]
 struct CmdPackageProject {
     /// Input project directory path.
-    #[arg(short, long)]
-    input: String,
+    #[arg(short, long, required_unless_present = "workspace")]
+    input: Option<String>,
+    /// Package all projects of a workspace, a `workspace.toml` or its folder.
+    #[arg(long, conflicts_with = "input")]
+    workspace: Option<String>,
     /// Output root path.
     #[arg(short, long)]
     output: Option<String>,
+    /// Replace an existing bundle in output root, instead of appending `.new`
+    /// to the output name. The replaced bundle is kept as `.bak`, once.
+    #[arg(long)]
+    overwrite: bool,
+    /// Append a suffix to the output name, replacing a previous output
+    /// of the same name.
+    #[arg(
+        long,
+        conflicts_with = "overwrite",
+        value_parser = clap::builder::NonEmptyStringValueParser::new()
+    )]
+    suffix: Option<String>,
+    /// Deploy the output to a game or mod manager folder, `paths.deploy_root`
+    /// in config if not given. It is placed at the `natives/...` path of the
+    /// bundle the project was dumped from.
+    #[arg(long, num_args = 0..=1, default_missing_value = "")]
+    deploy: Option<String>,
+    /// Allow replacing original files of the game install, they are backed
+    /// up to `.mhws-sound-tool/vanilla-backups` in the game folder first.
+    #[arg(long)]
+    allow_vanilla_overwrite: bool,
+    /// Apply the replace rules of `rules.toml` next to `config.toml` to
+    /// entries the project leaves unchanged.
+    #[arg(long)]
+    rules: bool,
+}
+
+impl CmdPackageProject {
+    fn output_policy(&self) -> OutputPolicy {
+        match &self.suffix {
+            Some(suffix) => OutputPolicy::Suffix(suffix.clone()),
+            None if self.overwrite => OutputPolicy::Overwrite,
+            None => OutputPolicy::Rename,
+        }
+    }
 }
 
 #[derive(Debug, clap::Args)]
 struct CmdUnpackBundle {
     /// Input bundle file path.
     ///
-    /// Support BNK and PCK formats.
+    /// Support BNK and PCK formats, or a folder of loose WEM files
+    /// when `--template` is specified. Other folders are searched
+    /// recursively for bundles, and an `index.json` of all media is
+    /// written to output root.
+    ///
+    /// Unencrypted PAK archives, e.g. of mods, are supported too. Retail
+    /// game archives encrypt their entry table and are refused, extract
+    /// their bundles with a game unpacker first.
     #[arg(short, long)]
     input: String,
     /// Output root path.
     #[arg(short, long)]
     output: Option<String>,
+    /// Template bundle file path.
+    ///
+    /// Used when input is a folder of loose WEM files, the files are
+    /// matched to entries of the template by ID.
+    #[arg(short, long)]
+    template: Option<String>,
+    /// Store dumped entries in a compressed `media.store` instead of loose files.
+    #[arg(long)]
+    store: bool,
+    /// Dump metadata only, with an index of entry sizes and hashes
+    /// instead of media files. Packaging then reads unmodified entries
+    /// from the original bundle, in or next to the project folder.
+    #[arg(long, conflicts_with = "store")]
+    meta_only: bool,
+    /// Skip damaged sections and entries with warnings instead of failing,
+    /// to salvage what is readable.
+    #[arg(long)]
+    lenient: bool,
+    /// Game version the bundle comes from, e.g. `1.010.00.00`.
+    ///
+    /// Shown in warnings when the project is repacked onto a changed bundle.
+    #[arg(long)]
+    game_version: Option<String>,
+    /// Also decode dumped wem files to playable files in `decoded/` folder,
+    /// `wav` by default. Requires vgmstream-cli, and ffmpeg for `ogg`.
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "wav")]
+    decode: Option<DecodeFormatArg>,
+    /// Also write label files of entry IDs and loop points next to
+    /// decoded files, for editing replacements in an audio editor.
+    #[arg(long, value_enum, requires = "decode")]
+    labels: Option<LabelFormatArg>,
+    /// Name list to resolve IDs with, repeatable.
+    ///
+    /// A text file of one name per line, or `<id> <name>`, or a
+    /// `SoundbanksInfo.xml`. Dumped files are renamed to
+    /// `[000]<id>_<name>.wem` and metadata objects get a `name` field.
+    #[arg(long)]
+    names: Vec<String>,
+    /// Don't resolve names with installed data packs.
+    #[arg(long)]
+    no_datapacks: bool,
+    /// Path list of unencrypted PAK input, repeatable. A text file of one path per line,
+    /// e.g. `natives/STM/Sound/Wwise/Wp00_Cmn.sbnk.1.X64`.
+    ///
+    /// Bundles in the list are extracted to their paths, others are named
+    /// by their path hash.
+    #[arg(long)]
+    pak_list: Vec<String>,
+    /// Also unpack banks embedded in PCK files to nested projects in
+    /// `banks/` folder of the project.
+    ///
+    /// Nested projects are packaged into the PCK with it.
+    #[arg(long)]
+    nested: bool,
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdCreatePck {
+    /// Input directory of wem and bnk files, named by ID.
+    ///
+    /// Files in a subfolder named after a language belong to that language.
+    /// Language IDs, names of files not named by ID and alignment are set
+    /// in an optional `pck.toml` in the directory.
+    #[arg(short, long)]
+    input: String,
+    /// Output PCK file path, defaults to `<input>.spck.1.X64` next to the input.
+    #[arg(short, long)]
+    output: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdRebase {
+    /// Input project directory path.
+    #[arg(short, long)]
+    input: String,
+    /// Updated bundle file path.
+    #[arg(long)]
+    new_source: String,
+    /// Output root path, defaults to the parent of input project.
+    ///
+    /// If the new project replaces the input one, the old project is kept as `.bak`.
+    #[arg(short, long)]
+    output: Option<String>,
+    /// Game version the updated bundle comes from.
+    #[arg(long)]
+    game_version: Option<String>,
 }
 
 #[derive(Debug, clap::Args)]
@@ -88,66 +302,421 @@ struct CmdSoundToWem {
     /// this option is required.
     #[arg(long)]
     ffmpeg: Option<String>,
+    /// Wwise target platform, default from config.
+    ///
+    /// Outputs of platforms other than Windows are written
+    /// to a subfolder named after the platform.
+    #[arg(long)]
+    platform: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum InputFileType {
-    Project,
-    GeneralAudio(&'static str),
-    Wem,
-    Bnk,
-    Pck,
+#[derive(Debug, clap::Args)]
+struct CmdTranscode {
+    /// Input sound files or directories.
+    ///
+    /// Sound files in directories are converted recursively.
+    #[arg(short, long, required = true)]
+    input: Vec<String>,
+    /// Output directory path, defaults to next to the inputs.
+    ///
+    /// Files in input directories keep their folder structure.
+    #[arg(short, long)]
+    output: Option<String>,
+    /// Output format extension, e.g. ogg, mp3, flac, wav.
+    #[arg(long)]
+    to: String,
+    /// Audio bitrate, e.g. 192k.
+    #[arg(long)]
+    bitrate: Option<String>,
+    /// Sample rate in Hz.
+    #[arg(long)]
+    sample_rate: Option<u32>,
+    /// Channel count.
+    #[arg(long)]
+    channels: Option<u32>,
 }
 
-impl InputFileType {
-    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
-        let path = path.as_ref();
-        if !path.exists() {
-            return None;
-        }
-        if path.is_dir() {
-            // check project.json
-            if path.join("project.json").is_file() {
-                return Some(InputFileType::Project);
-            } else {
-                return None;
-            }
+#[derive(Debug, clap::Args)]
+struct CmdRender {
+    /// Input project directory, bundle or wem file path.
+    #[arg(short, long)]
+    input: String,
+    /// Unique ID of the entry to render, required unless the input is a wem file.
+    #[arg(long, conflicts_with = "index")]
+    id: Option<u32>,
+    /// Sequential index of the entry to render, instead of `--id`.
+    #[arg(long)]
+    index: Option<u32>,
+    /// Output PNG image path.
+    #[arg(long)]
+    out: String,
+    /// Render mode.
+    #[arg(long, value_enum, default_value = "spectrogram")]
+    mode: RenderModeArg,
+    /// Image width in pixels.
+    #[arg(long, default_value = "1200")]
+    width: u32,
+    /// Image height in pixels.
+    #[arg(long, default_value = "400")]
+    height: u32,
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdExtract {
+    /// Input BNK, PCK file or project directory path.
+    #[arg(short, long)]
+    input: String,
+    /// Unique IDs of entries to extract.
+    #[arg(long, required_unless_present_any = ["index", "language"])]
+    id: Vec<u32>,
+    /// Sequential indices of entries to extract.
+    #[arg(long)]
+    index: Vec<u32>,
+    /// Extract all wem entries of a language of a PCK file, e.g. `sfx`.
+    #[arg(long, conflicts_with_all = ["id", "index"])]
+    language: Option<String>,
+    /// Output file path for a single entry, or directory path,
+    /// defaults to next to input.
+    ///
+    /// Files in a directory are named by entry ID.
+    #[arg(short, long)]
+    output: Option<String>,
+    /// Decode entries to playable files, `wav` by default.
+    /// Requires vgmstream-cli, and ffmpeg for `ogg`.
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "wav")]
+    decode: Option<DecodeFormatArg>,
+    /// Also write label files of entry IDs and loop points next to
+    /// decoded files.
+    #[arg(long, value_enum, requires = "decode")]
+    labels: Option<LabelFormatArg>,
+}
+
+#[cfg(feature = "playback")]
+#[derive(Debug, clap::Args)]
+struct CmdPlay {
+    /// Input wem file, project directory or bundle path.
+    #[arg(short, long)]
+    input: String,
+    /// Unique ID of the entry to play, required unless input is a wem file.
+    #[arg(long, conflicts_with = "index")]
+    id: Option<u32>,
+    /// Sequential index of the entry to play.
+    #[arg(long)]
+    index: Option<u32>,
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdDeploy {
+    /// Input bundle file paths.
+    #[arg(short, long, required = true)]
+    input: Vec<String>,
+    /// Target directory, e.g. the game or mod manager folder,
+    /// `paths.deploy_root` in config if not given.
+    ///
+    /// All inputs are deployed at once, if any of them fails,
+    /// the files already placed are rolled back.
+    #[arg(short, long)]
+    target: Option<String>,
+    /// Allow replacing original files of the game install, they are backed
+    /// up to `.mhws-sound-tool/vanilla-backups` in the game folder first.
+    #[arg(long)]
+    allow_vanilla_overwrite: bool,
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdDetect {
+    /// Input paths to detect.
+    #[arg(required = true)]
+    input: Vec<String>,
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdList {
+    /// Input BNK or PCK file path.
+    #[arg(short, long)]
+    input: String,
+    /// Print entries as JSON.
+    #[arg(long)]
+    json: bool,
+    /// Sort entries, sizes and durations descending.
+    #[arg(long, value_enum, default_value = "index")]
+    sort_by: EntrySortArg,
+    /// Only list the first N entries after sorting.
+    #[arg(long)]
+    top: Option<usize>,
+    /// Only list entries of a language of a PCK file, e.g. `sfx`.
+    #[arg(long)]
+    language: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdDiffMeta {
+    /// Input project directory path.
+    #[arg(short, long)]
+    input: String,
+    /// Original bundle file path, defaults to the source bundle
+    /// in or next to the project directory.
+    #[arg(long)]
+    original: Option<String>,
+    /// Print changes as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdDiffHirc {
+    /// Old BNK file path, e.g. from before a game update.
+    old: String,
+    /// New BNK file path.
+    new: String,
+    /// Print statistics and changes as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdServe {
+    /// Input PCK file path.
+    #[arg(short, long)]
+    input: String,
+    /// Listen address.
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+    /// Listen port.
+    #[arg(long, default_value = "8080")]
+    port: u16,
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdWatch {
+    /// Input project directory path.
+    #[arg(short, long)]
+    input: String,
+    /// Output root path, defaults to the parent of project directory.
+    #[arg(short, long)]
+    output: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdStripMedia {
+    /// Input project directory path.
+    #[arg(short, long)]
+    input: String,
+    /// Original bundle file path, defaults to the source bundle in or
+    /// next to the project directory, or where it was dumped from.
+    #[arg(long)]
+    original: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdHash {
+    /// Names to hash to Wwise IDs.
+    #[arg(required = true)]
+    names: Vec<String>,
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdHashes {
+    /// Files to record in `hashes.txt` of their directory,
+    /// or hashes files to check with `--verify`.
+    #[arg(required = true)]
+    input: Vec<String>,
+    /// Check files listed in the given hashes files.
+    #[arg(long)]
+    verify: bool,
+}
+
+/// Manage data packs of known names and bundle descriptions.
+#[derive(Debug, clap::Subcommand)]
+enum CmdDatapack {
+    /// List installed data packs.
+    List,
+    /// Install a data pack from a JSON file or URL, to the user data folder.
+    Install {
+        /// File path, or an http(s) URL.
+        source: String,
+        /// Expected SHA-256 of the pack in hex, required for URLs.
+        #[arg(long)]
+        sha256: Option<String>,
+    },
+    /// Remove an installed data pack.
+    Remove { name: String },
+    /// Search names, IDs and bundle descriptions of installed data packs.
+    Search {
+        query: String,
+        /// Print matches as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Restore earlier packaged outputs of projects, kept in `history/`
+/// next to `config.toml`.
+#[derive(Debug, clap::Subcommand)]
+enum CmdHistory {
+    /// List recorded builds of a project, or of all projects.
+    List {
+        /// Project directory path.
+        #[arg(short, long)]
+        input: Option<String>,
+        /// Print builds as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Restore a recorded build to the path it was packaged to.
+    Restore {
+        /// Project directory path.
+        #[arg(short, long)]
+        input: String,
+        /// Build number, see `history list`.
+        #[arg(long)]
+        build: u32,
+        /// Output file path instead of the packaged path.
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdReport {
+    /// Input project directory path.
+    #[arg(short, long)]
+    input: String,
+    /// Output directory path, defaults to the project directory.
+    #[arg(short, long)]
+    output: Option<String>,
+    /// Only write the entry table, without decoding audio previews.
+    #[arg(long)]
+    no_audio: bool,
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdValidate {
+    /// Input project directory path.
+    #[arg(short, long, required_unless_present = "workspace")]
+    input: Option<String>,
+    /// Validate all projects of a workspace, a `workspace.toml` or its folder.
+    #[arg(long, conflicts_with = "input")]
+    workspace: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdStats {
+    /// Input project directory path.
+    #[arg(short, long, required_unless_present = "workspace")]
+    input: Option<String>,
+    /// Count all projects of a workspace, a `workspace.toml` or its folder.
+    #[arg(long, conflicts_with = "input")]
+    workspace: Option<String>,
+    /// Print the counts as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdVerify {
+    /// Input BNK or PCK file path.
+    #[arg(short, long)]
+    input: String,
+    /// Also require the repacked file to be byte identical.
+    #[arg(long)]
+    exact: bool,
+    /// Print the result as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdSimilar {
+    /// Input project directory, BNK or PCK file path.
+    #[arg(short, long)]
+    input: String,
+    /// Distance under which entries are grouped, from 0 to 1.
+    /// Lower values give smaller groups of closer sounds.
+    #[arg(long, default_value_t = similar::DEFAULT_THRESHOLD)]
+    threshold: f32,
+    /// Print groups as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+#[cfg(feature = "self-update")]
+#[derive(Debug, clap::Args)]
+struct CmdSelfUpdate {
+    /// Only check for a newer version, without downloading.
+    #[arg(long)]
+    check: bool,
+}
+
+/// Command line choice of [`DecodeFormat`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum DecodeFormatArg {
+    Wav,
+    /// Vorbis in ogg, re-encoded from wav by ffmpeg.
+    Ogg,
+}
+
+impl From<DecodeFormatArg> for DecodeFormat {
+    fn from(format: DecodeFormatArg) -> Self {
+        match format {
+            DecodeFormatArg::Wav => DecodeFormat::Wav,
+            DecodeFormatArg::Ogg => DecodeFormat::Ogg,
         }
+    }
+}
 
-        // ext check
-        let file_ext = path.extension().and_then(|ext| ext.to_str())?;
-        let result = match file_ext {
-            "wav" => Some(InputFileType::GeneralAudio("wav")),
-            "ogg" => Some(InputFileType::GeneralAudio("ogg")),
-            "aac" => Some(InputFileType::GeneralAudio("aac")),
-            "flac" => Some(InputFileType::GeneralAudio("flac")),
-            "mp3" => Some(InputFileType::GeneralAudio("mp3")),
-            _ => None,
-        };
-        if result.is_some() {
-            return result;
+/// Command line choice of [`LabelFormat`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum LabelFormatArg {
+    /// Audacity label track, import with File > Import > Labels.
+    Audacity,
+    /// CUE sheet, exact sample positions are kept in `REM` lines.
+    Cue,
+}
+
+impl From<LabelFormatArg> for LabelFormat {
+    fn from(format: LabelFormatArg) -> Self {
+        match format {
+            LabelFormatArg::Audacity => LabelFormat::Audacity,
+            LabelFormatArg::Cue => LabelFormat::Cue,
         }
+    }
+}
+
+/// Command line choice of [`EntrySort`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum EntrySortArg {
+    Index,
+    Id,
+    /// Largest first.
+    Size,
+    /// Longest first, entries without duration last.
+    Duration,
+}
 
-        // magic check
-        let mut magic = [0; 4];
-        let mut file = std::fs::File::open(path).ok()?;
-        file.read_exact(&mut magic).ok()?;
-        match &magic {
-            b"BKHD" => Some(InputFileType::Bnk),
-            b"AKPK" => Some(InputFileType::Pck),
-            b"RIFF" => Some(InputFileType::Wem),
-            _ => None,
+impl From<EntrySortArg> for EntrySort {
+    fn from(sort: EntrySortArg) -> Self {
+        match sort {
+            EntrySortArg::Index => EntrySort::Index,
+            EntrySortArg::Id => EntrySort::Id,
+            EntrySortArg::Size => EntrySort::Size,
+            EntrySortArg::Duration => EntrySort::Duration,
         }
     }
+}
+
+/// Command line choice of [`render::RenderMode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum RenderModeArg {
+    Waveform,
+    Spectrogram,
+}
 
-    #[allow(clippy::match_like_matches_macro)]
-    pub fn similar_to(&self, other: &Self) -> bool {
-        match (self, other) {
-            (InputFileType::GeneralAudio(_), InputFileType::GeneralAudio(_)) => true,
-            (InputFileType::Wem, InputFileType::Wem) => true,
-            (InputFileType::Bnk, InputFileType::Bnk) => true,
-            (InputFileType::Pck, InputFileType::Pck) => true,
-            _ => false,
+impl From<RenderModeArg> for render::RenderMode {
+    fn from(mode: RenderModeArg) -> Self {
+        match mode {
+            RenderModeArg::Waveform => render::RenderMode::Waveform,
+            RenderModeArg::Spectrogram => render::RenderMode::Spectrogram,
         }
     }
 }
@@ -155,12 +724,16 @@ impl InputFileType {
 fn main() -> eyre::Result<()> {
     std::panic::set_hook(Box::new(panic_hook));
 
-    println!(
-        "{} v{}{}",
-        "MHWS Sound Tool".magenta().bold(),
-        env!("CARGO_PKG_VERSION"),
-        " - by @Eigeen".dimmed()
-    );
+    // stderr, keeps stdout clean for machine readable output
+    // stderr of JSON logs only has events
+    if !env::args().any(|arg| arg.starts_with(JSON_LOGS_FLAG)) {
+        eprintln!(
+            "{} v{}{}",
+            "MHWS Sound Tool".magenta().bold(),
+            env!("CARGO_PKG_VERSION"),
+            " - by @Eigeen".dimmed()
+        );
+    }
 
     // init logger
     let mut builder = env_logger::builder();
@@ -169,12 +742,42 @@ fn main() -> eyre::Result<()> {
     } else {
         builder.filter_level(log::LevelFilter::Debug);
     }
-    builder.format_timestamp(None).init();
+    output::CollectingLogger::init(builder.format_timestamp(None).build())?;
+
+    #[cfg(feature = "self-update")]
+    {
+        let replaced_exe = Config::global().lock().update.replaced_exe.take();
+        if let Some(old_path) = replaced_exe {
+            update::cleanup(Path::new(&old_path));
+            Config::global().lock().save();
+        }
+        let version_check = env::args().any(|arg| arg == VERSION_CHECK_FLAG)
+            || Config::global().lock().update.check_on_start;
+        if version_check {
+            match update::check() {
+                Ok(Some(release)) => eprintln!(
+                    "{} {} is available: {}",
+                    "New version".green().bold(),
+                    release.tag_name,
+                    release.html_url
+                ),
+                Ok(None) => {}
+                Err(e) => log::warn!("Version check failed: {}", e),
+            }
+        }
+    }
 
-    if let Err(e) = main_entry() {
-        error!("{:#}", e);
+    let result = main_entry();
+    if output::is_json() {
+        output::print_result(result.as_ref().err().map(|e| e.as_ref() as _));
+    } else if let Err(e) = &result {
+        let exit_code = FailureClass::of(e.as_ref()).exit_code();
+        error!(code = "command-failed", exit_code = exit_code; "{:#}", e);
     }
     wait_for_exit();
+    if let Err(e) = &result {
+        std::process::exit(FailureClass::of(e.as_ref()).exit_code());
+    }
 
     Ok(())
 }
@@ -187,8 +790,17 @@ fn panic_hook(info: &std::panic::PanicHookInfo) {
 
 fn main_entry() -> eyre::Result<()> {
     // drag and drop support, try to detect if all params are file paths
-    let args = env::args().collect::<Vec<_>>();
+    let args = env::args()
+        .filter(|arg| arg != VERSION_CHECK_FLAG)
+        .collect::<Vec<_>>();
     if args.len() < 2 {
+        // started without arguments, e.g. by double click
+        #[cfg(feature = "gui")]
+        {
+            INTERACTIVE_MODE.store(false, atomic::Ordering::SeqCst);
+            return gui::run();
+        }
+        #[cfg(not(feature = "gui"))]
         eyre::bail!("Usage: {} <input> ...", args[0]);
     }
 
@@ -223,17 +835,36 @@ fn main_entry() -> eyre::Result<()> {
             eyre::bail!("Input paths must be of the same type");
         }
     }
+    // outputs go next to inputs, unless an output root is configured
+    let output_dir = |kind: &str| {
+        Config::global()
+            .lock()
+            .paths
+            .output_dir(kind)
+            .map(|dir| dir.to_string_lossy().to_string())
+    };
     // build cli args
     match file_type {
         InputFileType::Project => {
             for input in input_paths {
                 let cmd = Command::PackageProject(CmdPackageProject {
-                    input: input.to_string_lossy().to_string(),
-                    output: None,
+                    input: Some(input.to_string_lossy().to_string()),
+                    workspace: None,
+                    output: output_dir(PathsConfig::BUNDLES_DIR),
+                    overwrite: false,
+                    suffix: None,
+                    deploy: None,
+                    allow_vanilla_overwrite: false,
+                    rules: false,
                 });
                 let cli = Cli {
                     command: cmd,
                     no_interact: false,
+                    stdin_answers: false,
+                    answers: None,
+                    version_check: false,
+                    format: OutputFormat::Text,
+                    json_logs: None,
                 };
                 cli_main(&cli)?;
             }
@@ -244,25 +875,47 @@ fn main_entry() -> eyre::Result<()> {
                     .iter()
                     .map(|p| p.to_string_lossy().to_string())
                     .collect(),
-                output: None,
+                output: output_dir(PathsConfig::WEM_DIR),
                 ffmpeg: None,
                 wwise_console: "".to_string(),
+                platform: None,
             });
             let cli = Cli {
                 command: cmd,
                 no_interact: false,
+                stdin_answers: false,
+                answers: None,
+                version_check: false,
+                format: OutputFormat::Text,
+                json_logs: None,
             };
             cli_main(&cli)?;
         }
-        InputFileType::Bnk | InputFileType::Pck => {
+        InputFileType::Bnk | InputFileType::Pck | InputFileType::Archive("pak") => {
             for input in input_paths {
                 let cmd = Command::UnpackBundle(CmdUnpackBundle {
                     input: input.to_string_lossy().to_string(),
-                    output: None,
+                    output: output_dir(PathsConfig::PROJECTS_DIR),
+                    template: None,
+                    store: false,
+                    meta_only: false,
+                    lenient: false,
+                    game_version: None,
+                    decode: None,
+                    labels: None,
+                    names: vec![],
+                    no_datapacks: false,
+                    pak_list: vec![],
+                    nested: false,
                 });
                 let cli = Cli {
                     command: cmd,
                     no_interact: false,
+                    stdin_answers: false,
+                    answers: None,
+                    version_check: false,
+                    format: OutputFormat::Text,
+                    json_logs: None,
                 };
                 cli_main(&cli)?;
             }
@@ -276,35 +929,92 @@ fn main_entry() -> eyre::Result<()> {
 }
 
 fn cli_main(cli: &Cli) -> eyre::Result<()> {
-    if cli.no_interact {
+    output::set_format(cli.format);
+    if let Some(path) = &cli.json_logs {
+        output::set_json_logs(path.as_deref().map(Path::new))
+            .context("Failed to open JSON log file")?;
+    }
+    if cli.no_interact || output::is_json() {
         INTERACTIVE_MODE.store(false, atomic::Ordering::SeqCst);
     }
+    prompt::init_answers(cli.stdin_answers, cli.answers.as_deref().map(Path::new))
+        .context("Failed to read prompt answers")?;
+    transcode::set_tool_path_prompt(prompt::CliPrompt);
     match &cli.command {
         Command::PackageProject(cmd) => {
-            info!("Input: {}", cmd.input);
+            if let Some(workspace) = &cmd.workspace {
+                return package_workspace(cmd, workspace);
+            }
+            let input = cmd.input.as_deref().unwrap_or_default();
+            info!("Input: {}", input);
             if let Some(output) = &cmd.output {
                 info!("Output: {}", output);
             }
-            let project =
-                SoundToolProject::from_path(&cmd.input).context("Failed to load project")?;
-
-            let output_root = cmd.output.as_ref().map(PathBuf::from).unwrap_or_else(|| {
-                Path::new(&cmd.input)
-                    .parent()
-                    .unwrap_or_else(|| {
-                        let input_dir = Path::new(&cmd.input).parent().unwrap_or(Path::new("."));
-                        input_dir
-                    })
-                    .to_path_buf()
-            });
-            project
-                .repack(&output_root)
-                .context("Failed to repack project")?;
+            package_project(
+                cmd,
+                Path::new(input),
+                cmd.output.as_deref().map(Path::new),
+                None,
+                &package_rules(cmd, None)?,
+            )?;
         }
         Command::UnpackBundle(cmd) => {
             let input = Path::new(&cmd.input);
+            if let Some(template) = &cmd.template {
+                if !input.is_dir() {
+                    return Err(output::failure(
+                        FailureClass::Input,
+                        format!("Input directory not found: {}", input.display()),
+                    )
+                    .into());
+                }
+                info!("Input: {}", cmd.input);
+                info!("Template: {}", template);
+                if let Some(output) = &cmd.output {
+                    info!("Output: {}", output);
+                }
+                let output_root = cmd
+                    .output
+                    .as_ref()
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| input.parent().unwrap_or(Path::new(".")).to_path_buf());
+
+                let file_type = InputFileType::from_path(template)
+                    .ok_or(eyre::eyre!("Unsupported template file type"))?;
+                let mut project = match file_type {
+                    InputFileType::Bnk => {
+                        SoundToolProject::dump_bnk_with(template, &output_root, cmd.lenient)
+                            .context("Failed to dump template bnk")?
+                    }
+                    InputFileType::Pck => {
+                        SoundToolProject::dump_pck_with(template, &output_root, cmd.lenient)
+                            .context("Failed to dump template pck")?
+                    }
+                    other => eyre::bail!("Unsupported template file type: {:?}", other),
+                };
+                let imported = project
+                    .import_loose_wems(input)
+                    .context("Failed to import loose wem files")?;
+                info!("Imported {} loose wem files.", imported);
+                finish_dump(cmd, Path::new(template), &mut project)?;
+                return Ok(());
+            }
+            if input.is_dir() {
+                info!("Input: {}", cmd.input);
+                let output_root = cmd
+                    .output
+                    .as_ref()
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| input.to_path_buf());
+                info!("Output: {}", output_root.display());
+                return dump_directory(cmd, input, &output_root);
+            }
             if !input.is_file() {
-                eyre::bail!("Input file not found: {}", input.display())
+                return Err(output::failure(
+                    FailureClass::Input,
+                    format!("Input file not found: {}", input.display()),
+                )
+                .into());
             }
             info!("Input: {}", cmd.input);
             if let Some(output) = &cmd.output {
@@ -315,18 +1025,31 @@ fn cli_main(cli: &Cli) -> eyre::Result<()> {
                 .as_ref()
                 .map(PathBuf::from)
                 .unwrap_or_else(|| input.parent().unwrap_or(Path::new(".")).to_path_buf());
-
-            let file_type = InputFileType::from_path(&cmd.input)
-                .ok_or(eyre::eyre!("Unsupported input file type"))?;
-            match file_type {
-                InputFileType::Bnk => {
-                    SoundToolProject::dump_bnk(input, &output_root).context("Failed to dump bnk")?
-                }
-                InputFileType::Pck => {
-                    SoundToolProject::dump_pck(input, &output_root).context("Failed to dump pck")?
-                }
-                other => eyre::bail!("Unsupported input file type: {:?}", other),
-            };
+            if InputFileType::from_path(input) == Some(InputFileType::Archive("pak")) {
+                return dump_pak(cmd, input, &output_root);
+            }
+            dump_bundle(cmd, input, &output_root)?;
+        }
+        Command::Rebase(cmd) => {
+            info!("Input: {}", cmd.input);
+            info!("New source: {}", cmd.new_source);
+            let project =
+                SoundToolProject::from_path(&cmd.input).context("Failed to load project")?;
+            let output_root = cmd.output.as_ref().map(PathBuf::from).unwrap_or_else(|| {
+                Path::new(&cmd.input)
+                    .parent()
+                    .unwrap_or(Path::new("."))
+                    .to_path_buf()
+            });
+            let (mut new_project, _) = project
+                .rebase(&cmd.new_source, &output_root)
+                .context("Failed to rebase project")?;
+            output::file_written(new_project.project_path());
+            if let Some(game_version) = &cmd.game_version {
+                new_project
+                    .set_game_version(game_version)
+                    .context("Failed to write project metadata")?;
+            }
         }
         Command::SoundToWem(cmd) => {
             if cmd.input.is_empty() {
@@ -359,50 +1082,1348 @@ fn cli_main(cli: &Cli) -> eyre::Result<()> {
                 let first_file_dir = Path::new(&cmd.input[0]).parent().unwrap_or(Path::new("."));
                 first_file_dir.to_path_buf()
             });
-            // create temp dir
-            let temp_dir = tempfile::tempdir()?;
-            let temp_dir = temp_dir.path().join("sound2wem");
-            if temp_dir.exists() {
-                fs::remove_dir_all(&temp_dir)?;
-                fs::create_dir_all(&temp_dir)?;
+            let platform = cmd
+                .platform
+                .clone()
+                .unwrap_or_else(|| Config::global().lock().transcode.platform.clone());
+            // the platform names the output folder
+            wwise::check_platform(&platform)?;
+            let output_dir = if platform == wwise::DEFAULT_PLATFORM {
+                output_dir
+            } else {
+                output_dir.join(&platform)
+            };
+            info!("Platform: {}", platform);
+            mhws_sound_tool::convert(&cmd.input, &output_dir, &platform)
+                .context("Failed to convert to wem")?;
+            for input in &cmd.input {
+                let file_stem = Path::new(input).file_stem().unwrap_or_default();
+                output::file_written(output_dir.join(file_stem).with_extension("wem"));
+            }
+        }
+        Command::Transcode(cmd) => {
+            let format = cmd.to.trim_start_matches('.').to_ascii_lowercase();
+            let output_dir = cmd.output.as_ref().map(PathBuf::from);
+            let mut files = vec![];
+            for input in &cmd.input {
+                info!("Input: {}", input);
+                files.extend(transcode_outputs(
+                    Path::new(input),
+                    output_dir.as_deref(),
+                    &format,
+                )?);
+            }
+            if files.is_empty() {
+                eyre::bail!("No sound file found in inputs.");
+            }
+            for (_, output) in &files {
+                if let Some(parent) = output.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+            }
+            let conversion = transcode::SoundConversion {
+                format,
+                bitrate: cmd.bitrate.clone(),
+                sample_rate: cmd.sample_rate,
+                channels: cmd.channels,
+            };
+            transcode::convert_sounds(&files, &conversion).context("Failed to transcode")?;
+            for (_, output) in &files {
+                output::file_written(output);
+            }
+            info!("Transcoded {} files.", files.len());
+        }
+        Command::Render(cmd) => {
+            info!("Input: {}", cmd.input);
+            let target = match (cmd.id, cmd.index) {
+                (Some(id), _) => Some(IdOrIndex::Id(id)),
+                (None, Some(index)) => Some(IdOrIndex::Index(index)),
+                (None, None) => None,
+            };
+            // a wem file is the entry itself, bundles and projects need a target
+            let is_wem = InputFileType::from_path(&cmd.input) == Some(InputFileType::Wem);
+            let wem_data = match target {
+                _ if is_wem => {
+                    fs::read(&cmd.input).context(format!("Failed to read {}", cmd.input))?
+                }
+                Some(target) => project::read_entry(&cmd.input, target)
+                    .context(format!("Failed to read entry {}", target))?,
+                None => {
+                    return Err(output::failure(
+                        FailureClass::Input,
+                        "Either --id or --index is required for a bundle or project.",
+                    )
+                    .into());
+                }
+            };
+            let wav_data = transcode::wem_to_wav(&wem_data).context("Failed to decode wem")?;
+            render::render_wav(
+                &wav_data,
+                cmd.mode.into(),
+                &cmd.out,
+                (cmd.width, cmd.height),
+            )
+            .context("Failed to render image")?;
+            info!("Output: {}", cmd.out);
+            output::file_written(&cmd.out);
+        }
+        Command::Extract(cmd) => {
+            info!("Input: {}", cmd.input);
+            let targets: Vec<IdOrIndex> = cmd
+                .id
+                .iter()
+                .map(|&id| IdOrIndex::Id(id))
+                .chain(cmd.index.iter().map(|&index| IdOrIndex::Index(index)))
+                .collect();
+            let entries = match &cmd.language {
+                Some(language) => project::read_language_entries(&cmd.input, language),
+                None => project::read_entries(&cmd.input, &targets),
+            }
+            .context("Failed to read entries")?;
+            let decode = cmd.decode.map(DecodeFormat::from);
+            let ext = decode.map_or("wem", |format| format.extension());
+            // a single entry is written to the output path, unless it is a directory
+            let output_file = cmd.output.as_ref().map(PathBuf::from).filter(|output| {
+                entries.len() == 1 && !output.is_dir() && output.extension().is_some()
+            });
+            // not in a project directory, files are not named like dumped ones
+            let output_dir = cmd.output.as_ref().map(PathBuf::from).unwrap_or_else(|| {
+                let input = Path::new(&cmd.input);
+                input.parent().unwrap_or(Path::new(".")).to_path_buf()
+            });
+            for entry in entries {
+                let data = match decode {
+                    Some(format) => transcode::decode_wem(&entry.data, format)
+                        .context(format!("Failed to decode entry {}", entry.id))?,
+                    None => entry.data.clone(),
+                };
+                let output_path = match &output_file {
+                    Some(output_file) => output_file.clone(),
+                    None => output_dir.join(format!("{}.{}", entry.id, ext)),
+                };
+                if let Some(parent) = output_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&output_path, data)?;
+                output::file_written(&output_path);
+                if let Some(label_format) = cmd.labels {
+                    let label_path = labels::write_labels(
+                        &entry.data,
+                        entry.id,
+                        &output_path,
+                        label_format.into(),
+                    )?;
+                    output::file_written(label_path);
+                }
+                info!(
+                    "{}: [{}] {} -> {}",
+                    "Extract".cyan(),
+                    entry.idx,
+                    entry.id,
+                    output_path.display()
+                );
+            }
+        }
+        #[cfg(feature = "playback")]
+        Command::Play(cmd) => {
+            info!("Input: {}", cmd.input);
+            let wem_data = match (cmd.id, cmd.index) {
+                (Some(id), _) => project::read_entry(&cmd.input, IdOrIndex::Id(id)),
+                (None, Some(index)) => project::read_entry(&cmd.input, IdOrIndex::Index(index)),
+                (None, None) => {
+                    if InputFileType::from_path(&cmd.input) != Some(InputFileType::Wem) {
+                        eyre::bail!("Either --id or --index is required.");
+                    }
+                    fs::read(&cmd.input).map_err(Into::into)
+                }
+            }
+            .context("Failed to read entry")?;
+            let wav_data = transcode::wem_to_wav(&wem_data).context("Failed to decode wem")?;
+            info!("{}: press Ctrl+C to stop.", "Play".cyan());
+            let duration =
+                mhws_sound_tool::playback::play_wav(wav_data).context("Failed to play sound")?;
+            if let Some(duration) = duration {
+                info!("Played {:.2}s.", duration.as_secs_f32());
+            }
+        }
+        Command::Deploy(cmd) => {
+            let target_dir = deploy_root(cmd.target.as_deref())?;
+            info!("Target: {}", target_dir.display());
+            let mut deployment = deploy::Deployment::new();
+            for input in &cmd.input {
+                let input = Path::new(input);
+                if !input.is_file() {
+                    return Err(output::failure(
+                        FailureClass::Input,
+                        format!("Input file not found: {}", input.display()),
+                    )
+                    .into());
+                }
+                let target = target_dir.join(input.file_name().unwrap());
+                guard_vanilla(&target, cmd.allow_vanilla_overwrite)?;
+                deployment.add(input, target);
+            }
+            let deployed = deployment.commit().context("Failed to deploy files")?;
+            for path in deployed {
+                record_game_file(&path);
+                info!("{}: {}", "Deployed".green(), path.display());
+                output::file_written(path);
+            }
+        }
+        Command::Detect(cmd) => {
+            for input in &cmd.input {
+                match InputFileType::from_path(input) {
+                    Some(file_type) => {
+                        info!("{}: {:?}", input, file_type);
+                        info!("  -> {}", file_type.action());
+                        if matches!(file_type, InputFileType::Bnk | InputFileType::Pck) {
+                            let fingerprint = BundleFingerprint::from_path(input)
+                                .context("Failed to fingerprint bundle")?;
+                            info!(
+                                "  version {}, sha256 {}",
+                                fingerprint.version, fingerprint.sha256
+                            );
+                            if let Some(modinfo) =
+                                ModInfo::read(input).context("Failed to read mod info")?
+                            {
+                                info!(
+                                    "  mod {} {} by {}, tool {}, project {}",
+                                    modinfo.name,
+                                    modinfo.version.as_deref().unwrap_or("-"),
+                                    modinfo.author.as_deref().unwrap_or("-"),
+                                    modinfo.tool_version,
+                                    modinfo.project_hash
+                                );
+                            }
+                        }
+                    }
+                    None => info!("{}: {}", input, "Unknown".yellow()),
+                }
+            }
+        }
+        Command::List(cmd) => {
+            let mut entries =
+                project::list_entries(&cmd.input).context("Failed to list entries")?;
+            if let Some(language) = &cmd.language {
+                entries.retain(|entry| {
+                    entry
+                        .language
+                        .as_ref()
+                        .is_some_and(|name| name.eq_ignore_ascii_case(language))
+                });
+            }
+            let total = entries.len();
+            EntrySort::from(cmd.sort_by).sort(&mut entries);
+            if let Some(top) = cmd.top {
+                entries.truncate(top);
+            }
+            if cmd.json || output::is_json() {
+                return output::print_json(&entries).map_err(Into::into);
+            }
+            info!("Input: {}", cmd.input);
+            println!(
+                "{:<5} {:>6} {:>12} {:>10} {:>9} {:>10}  Language",
+                "Type", "Index", "ID", "Size", "Duration", "Offset"
+            );
+            for entry in &entries {
+                println!(
+                    "{:<5} {:>6} {:>12} {:>10} {:>9} {:>10}  {}",
+                    entry.kind,
+                    entry.index,
+                    entry.id,
+                    utils::human_size(entry.size as u64),
+                    entry
+                        .duration
+                        .map_or_else(|| "-".to_string(), utils::human_duration),
+                    entry.offset,
+                    entry.language.as_deref().unwrap_or("-")
+                );
+            }
+            if entries.len() < total {
+                info!("{} of {} entries.", entries.len(), total);
             } else {
-                fs::create_dir_all(&temp_dir)?;
+                info!("{} entries.", total);
+            }
+        }
+        Command::DiffMeta(cmd) => {
+            let project =
+                SoundToolProject::from_path(&cmd.input).context("Failed to load project")?;
+            let original = match &cmd.original {
+                Some(original) => PathBuf::from(original),
+                None => project
+                    .find_source_bundle()
+                    .context("Original bundle not found, specify it with --original")?,
+            };
+            let change = project
+                .compare_bundle(&original)
+                .context("Failed to fingerprint original bundle")?;
+            if change.is_some_and(|change| change != BundleChange::Unchanged) {
+                warn!(
+                    "{} is not the bundle this project was dumped from, changes may not be edits.",
+                    original.display()
+                );
+            }
+            let changes = metadiff::diff(
+                &project
+                    .original_metadata(&original)
+                    .context("Failed to read original bundle")?,
+                &project
+                    .metadata()
+                    .context("Failed to read project metadata")?,
+            );
+            if cmd.json || output::is_json() {
+                return output::print_json(&changes).map_err(Into::into);
+            }
+            info!("Input: {}", cmd.input);
+            info!("Original: {}", original.display());
+            for change in &changes {
+                println!("{}", change);
+            }
+            info!("{} changes.", changes.len());
+        }
+        Command::DiffHirc(cmd) => {
+            let read_bank = |path: &str| -> eyre::Result<bnk::Bnk> {
+                let mut reader = io::BufReader::new(
+                    fs::File::open(path).context(format!("Failed to open {}", path))?,
+                );
+                bnk::Bnk::from_reader(&mut reader).context(format!("Failed to read bnk {}", path))
+            };
+            let diff = hirc::HircDiff::from_banks(&read_bank(&cmd.old)?, &read_bank(&cmd.new)?);
+            if cmd.json || output::is_json() {
+                return output::print_json(&diff).map_err(Into::into);
+            }
+            info!("Old: {}", cmd.old);
+            info!("New: {}", cmd.new);
+            for change in &diff.changes {
+                println!("{}", change);
             }
-            // transcode to wav in temp dir
+            println!(
+                "{:<18}{:>8}{:>8}{:>8}{:>8}{:>8}",
+                "Type", "Old", "New", "Added", "Removed", "Changed"
+            );
+            for stats in &diff.types {
+                println!(
+                    "{:<18}{:>8}{:>8}{:>8}{:>8}{:>8}",
+                    stats.name,
+                    stats.old_count,
+                    stats.new_count,
+                    stats.added,
+                    stats.removed,
+                    stats.changed
+                );
+            }
+            info!("{} changes.", diff.changes.len());
+        }
+        Command::Serve(cmd) => {
+            info!("Input: {}", cmd.input);
+            let server = serve::PreviewServer::bind(&cmd.input, (cmd.host.as_str(), cmd.port))
+                .context("Failed to start preview server")?;
+            info!(
+                "Preview server listening on http://{}",
+                server.local_addr()?
+            );
+            server.serve_forever()?;
+        }
+        Command::Watch(cmd) => {
+            info!("Input: {}", cmd.input);
+            let output_root = cmd.output.as_ref().map(PathBuf::from).unwrap_or_else(|| {
+                Path::new(&cmd.input)
+                    .parent()
+                    .unwrap_or(Path::new("."))
+                    .to_path_buf()
+            });
+            info!("Output: {}", output_root.display());
+            let mut watcher = ProjectWatcher::new(&cmd.input, &output_root);
+            watcher
+                .run(|result| {
+                    match result {
+                        Ok(stats) => stats.log_summary(),
+                        Err(e) => error!("Failed to repack project: {}", e),
+                    }
+                    info!(
+                        "{}: waiting for changes, press Ctrl+C to stop.",
+                        "Watch".cyan()
+                    );
+                })
+                .context("Failed to watch project")?;
+        }
+        Command::CreatePck(cmd) => {
+            info!("Input: {}", cmd.input);
+            let input = Path::new(&cmd.input);
+            let output = match &cmd.output {
+                Some(output) => PathBuf::from(output),
+                None => {
+                    let file_name = format!(
+                        "{}.spck.1.X64",
+                        input.file_name().unwrap_or_default().to_string_lossy()
+                    );
+                    OutputPolicy::Rename
+                        .output_path(input.parent().unwrap_or(Path::new("")), &file_name)
+                }
+            };
+            let created = create::create_pck(input, &output).context("Failed to create PCK")?;
+            info!(
+                "{}: {} banks, {} wems, {} bytes.",
+                "CreatePck".cyan(),
+                created.bnk_count,
+                created.wem_count,
+                created.size
+            );
+            info!("Output: {}", output.display());
+            output::file_written(&output);
+        }
+        Command::StripMedia(cmd) => {
+            info!("Input: {}", cmd.input);
+            let project =
+                SoundToolProject::from_path(&cmd.input).context("Failed to load project")?;
+            let original = match &cmd.original {
+                Some(original) => PathBuf::from(original),
+                None => project
+                    .find_source_bundle()
+                    .context("Original bundle not found, specify it with --original")?,
+            };
+            project
+                .strip_media(&original)
+                .context("Failed to strip media files")?;
+        }
+        Command::Hash(cmd) => {
+            if output::is_json() {
+                let hashes: Vec<_> = cmd
+                    .names
+                    .iter()
+                    .map(|name| serde_json::json!({ "id": utils::fnv1_32(name), "name": name }))
+                    .collect();
+                return output::print_json(&hashes).map_err(Into::into);
+            }
+            for name in &cmd.names {
+                println!("{}\t{}", utils::fnv1_32(name), name);
+            }
+        }
+        Command::Hashes(cmd) if cmd.verify => {
+            let mut failed = 0;
+            for input in &cmd.input {
+                let input = Path::new(input);
+                info!("Input: {}", input.display());
+                let list = HashList::load(input).context("Failed to read hashes file")?;
+                if list.is_empty() {
+                    eyre::bail!("No hash found in {}", input.display());
+                }
+                let checks = list
+                    .verify(input.parent().unwrap_or(Path::new(".")))
+                    .context("Failed to verify files")?;
+                for check in checks {
+                    match check.status {
+                        HashStatus::Ok => info!("{}: {}", "OK".green(), check.file_name),
+                        HashStatus::Mismatch => {
+                            error!("{}: {}", "Mismatch".red(), check.file_name);
+                            failed += 1;
+                        }
+                        HashStatus::Missing => {
+                            error!("{}: {}", "Missing".red(), check.file_name);
+                            failed += 1;
+                        }
+                    }
+                }
+            }
+            if failed > 0 {
+                return Err(output::failure(
+                    FailureClass::Check,
+                    format!("{} files failed verification.", failed),
+                )
+                .into());
+            }
+        }
+        Command::Hashes(cmd) => {
             for input in &cmd.input {
                 let input = Path::new(input);
                 if !input.is_file() {
-                    eyre::bail!("Input file not found: {}", input.display())
+                    return Err(output::failure(
+                        FailureClass::Input,
+                        format!("Input file not found: {}", input.display()),
+                    )
+                    .into());
                 }
-                if input.extension().unwrap_or_default() == "wav" {
-                    // copy to temp dir
-                    let out_file = temp_dir.join(input.file_name().unwrap());
-                    fs::copy(input, &out_file)?;
-                } else {
-                    // transcode to wav in temp dir
-                    let mut data =
-                        transcode::sounds_to_wav(&[input]).context("Failed to transcode to wav")?;
-                    let data = data.pop().unwrap();
-                    // 写入临时文件
-                    let ff_out_file_name =
-                        Path::new(input.file_stem().unwrap()).with_extension("wav");
-                    let ff_out_file = temp_dir.join(ff_out_file_name);
-                    fs::write(&ff_out_file, &data).context(format!(
-                        "Failed to write transcoded data {}",
-                        ff_out_file.display()
-                    ))?;
+                let dir = input.parent().unwrap_or(Path::new("."));
+                let path =
+                    hashes::update_hashes(dir, &[input]).context("Failed to update hashes")?;
+                output::file_written(&path);
+                info!(
+                    "{}: {} -> {}",
+                    "Hashes".cyan(),
+                    input.display(),
+                    path.display()
+                );
+            }
+        }
+        Command::Datapack(CmdDatapack::List) => {
+            let packs = DataPacks::installed().context("Failed to load data packs")?;
+            if output::is_json() {
+                let list: Vec<_> = packs
+                    .packs()
+                    .iter()
+                    .map(|pack| {
+                        serde_json::json!({
+                            "name": pack.name,
+                            "version": pack.version,
+                            "description": pack.description,
+                            "names": pack.len(),
+                            "bundles": pack.bundles.len(),
+                        })
+                    })
+                    .collect();
+                return output::print_json(&list).map_err(Into::into);
+            }
+            for pack in packs.packs() {
+                println!(
+                    "{} {} ({} names, {} bundles){}",
+                    pack.name,
+                    pack.version.as_deref().unwrap_or("-"),
+                    pack.len(),
+                    pack.bundles.len(),
+                    pack.description
+                        .as_ref()
+                        .map(|description| format!(": {}", description))
+                        .unwrap_or_default()
+                );
+            }
+            info!("{} data packs installed.", packs.packs().len());
+        }
+        Command::Datapack(CmdDatapack::Install { source, sha256 }) => {
+            let is_url = source.starts_with("http://") || source.starts_with("https://");
+            if is_url && sha256.is_none() {
+                eyre::bail!("Pass --sha256 of the data pack to verify the download.");
+            }
+            let data = if is_url {
+                #[cfg(feature = "self-update")]
+                {
+                    info!("Downloading {}", source);
+                    update::download(source).context("Failed to download data pack")?
+                }
+                #[cfg(not(feature = "self-update"))]
+                eyre::bail!("Downloading requires a build with the `self-update` feature.")
+            } else {
+                fs::read(source).with_context(|| format!("Failed to read {}", source))?
+            };
+            if let Some(sha256) = sha256 {
+                DataPack::verify_sha256(&data, sha256).context("Failed to verify data pack")?;
+            }
+            let mut packs = DataPacks::installed().context("Failed to load data packs")?;
+            let pack = packs
+                .install(&data)
+                .context("Failed to install data pack")?;
+            info!(
+                "{}: {} installed, {} names.",
+                "DataPack".cyan(),
+                pack.name,
+                pack.len()
+            );
+        }
+        Command::Datapack(CmdDatapack::Remove { name }) => {
+            let mut packs = DataPacks::installed().context("Failed to load data packs")?;
+            packs.remove(name).context("Failed to remove data pack")?;
+            info!("{}: {} removed.", "DataPack".cyan(), name);
+        }
+        Command::Datapack(CmdDatapack::Search { query, json }) => {
+            let packs = DataPacks::installed().context("Failed to load data packs")?;
+            if packs.is_empty() {
+                warn!("No data packs installed, see `datapack install`.");
+            }
+            let matches = packs.search(query);
+            if *json || output::is_json() {
+                return output::print_json(&matches).map_err(Into::into);
+            }
+            for found in &matches {
+                match found {
+                    SearchMatch::Name { id, name, pack } => {
+                        println!("{:>12}  {}  [{}]", id, name, pack)
+                    }
+                    SearchMatch::Bundle {
+                        file_name,
+                        description,
+                        pack,
+                    } => println!(
+                        "{:>12}  {}: {}  [{}]",
+                        "bundle", file_name, description, pack
+                    ),
+                }
+            }
+            info!("{} matches.", matches.len());
+        }
+        Command::History(CmdHistory::List { input, json }) => {
+            let histories = match input {
+                Some(input) => vec![History::installed(input).context("Failed to load history")?],
+                None => History::all(history::HISTORY_DIR).context("Failed to load history")?,
+            };
+            if *json || output::is_json() {
+                let list: Vec<_> = histories
+                    .iter()
+                    .map(|history| {
+                        serde_json::json!({
+                            "project_path": history.project_path(),
+                            "builds": history.builds(),
+                        })
+                    })
+                    .collect();
+                return output::print_json(&list).map_err(Into::into);
+            }
+            for history in &histories {
+                println!("{}", history.project_path().display());
+                for build in history.builds() {
+                    println!(
+                        "{:>6}  {}  {:>10}  {}  {}",
+                        build.id,
+                        utils::format_utc_time(build.time),
+                        utils::human_size(build.size),
+                        &build.sha256[..8],
+                        build.output_path.display()
+                    );
                 }
             }
-            // to wem
-            transcode::wavs_to_wem(&temp_dir, &output_dir)?;
+            let count: usize = histories.iter().map(|history| history.builds().len()).sum();
+            info!("{} builds.", count);
         }
+        Command::History(CmdHistory::Restore {
+            input,
+            build,
+            output,
+        }) => {
+            let history = History::installed(input).context("Failed to load history")?;
+            let restored = history
+                .restore(*build, output.as_deref().map(Path::new))
+                .context("Failed to restore build")?;
+            info!(
+                "{}: build {} -> {}",
+                "Restore".cyan(),
+                build,
+                restored.display()
+            );
+            output::file_written(&restored);
+        }
+        Command::Report(cmd) => {
+            info!("Input: {}", cmd.input);
+            let project =
+                SoundToolProject::from_path(&cmd.input).context("Failed to load project")?;
+            let output = cmd.output.as_deref().unwrap_or(&cmd.input);
+            info!("Output: {}", output);
+            let summary = report::write_report(&project, output, !cmd.no_audio)
+                .context("Failed to write report")?;
+            output::file_written(&summary.path);
+            info!(
+                "{}: {} entries, {} previews -> {}",
+                "Report".cyan(),
+                summary.entries,
+                summary.previews,
+                summary.path.display()
+            );
+        }
+        Command::Validate(cmd) => {
+            if let Some(workspace) = &cmd.workspace {
+                return validate_workspace(workspace);
+            }
+            let input = cmd.input.as_deref().unwrap_or_default();
+            info!("Input: {}", input);
+            let issues = project::validate_project(input).context("Failed to validate project")?;
+            for issue in &issues {
+                error!("{}", issue);
+            }
+            if !issues.is_empty() {
+                return Err(output::failure(
+                    FailureClass::Check,
+                    format!("{} problems found in project.", issues.len()),
+                )
+                .into());
+            }
+            info!("{}: No problems found.", "Validate".cyan());
+        }
+        Command::Stats(cmd) => {
+            let (projects, datapacks_dir) = match &cmd.workspace {
+                Some(workspace) => {
+                    let workspace = load_workspace(workspace)?;
+                    let projects = workspace
+                        .members()
+                        .iter()
+                        .map(|member| (workspace.member_name(member), member.clone()))
+                        .collect();
+                    (projects, workspace.datapacks_dir())
+                }
+                None => {
+                    let input = cmd.input.as_deref().unwrap_or_default();
+                    (vec![(input.to_string(), PathBuf::from(input))], None)
+                }
+            };
+            let packs = match datapacks_dir {
+                Some(dir) => DataPacks::load(dir),
+                None => DataPacks::installed(),
+            }
+            .unwrap_or_else(|e| {
+                warn!("Failed to load data packs: {}", e);
+                DataPacks::default()
+            });
+            let mut list = vec![];
+            for (name, path) in projects {
+                let stats = project::project_stats(&path)
+                    .with_context(|| format!("Failed to read project {}", name))?;
+                let last_build = History::installed(&path)
+                    .ok()
+                    .and_then(|history| history.builds().last().map(|build| build.id));
+                list.push(ProjectSummary {
+                    description: packs
+                        .bundle_description(&stats.source_file_name)
+                        .map(str::to_string),
+                    name,
+                    stats,
+                    last_build,
+                });
+            }
+            if cmd.json || output::is_json() {
+                return output::print_json(&list).map_err(Into::into);
+            }
+            for summary in &list {
+                let stats = &summary.stats;
+                println!(
+                    "{}  {} entries, {} replaced, {} removed, last build {}",
+                    summary.name.bold(),
+                    stats.entries,
+                    stats.replacements,
+                    stats.removals,
+                    summary
+                        .last_build
+                        .map_or("-".to_string(), |id| id.to_string())
+                );
+                if let Some(description) = &summary.description {
+                    println!("  {}", description);
+                }
+            }
+            if list.len() > 1 {
+                info!(
+                    "{}: {} projects, {} entries, {} replaced, {} removed.",
+                    "Stats".cyan(),
+                    list.len(),
+                    list.iter().map(|s| s.stats.entries).sum::<usize>(),
+                    list.iter().map(|s| s.stats.replacements).sum::<usize>(),
+                    list.iter().map(|s| s.stats.removals).sum::<usize>()
+                );
+            }
+        }
+        Command::Verify(cmd) => {
+            info!("Input: {}", cmd.input);
+            let report =
+                verify::verify_bundle(&cmd.input).context("Failed to round-trip bundle")?;
+            if cmd.json || output::is_json() {
+                output::print_json(&report)?;
+            } else {
+                for mismatch in &report.mismatches {
+                    println!("{}", mismatch);
+                }
+                info!(
+                    "{}: {} entries, {} -> {} bytes.",
+                    "Verify".cyan(),
+                    report.entries,
+                    report.original_size,
+                    report.repacked_size
+                );
+                match report.first_difference {
+                    None => info!("Repacked file is byte identical."),
+                    Some(offset) => info!("Repacked file differs from byte {:#X}.", offset),
+                }
+            }
+            if !report.is_ok(cmd.exact) {
+                return Err(output::failure(
+                    FailureClass::Check,
+                    format!("{} does not round-trip.", cmd.input),
+                )
+                .into());
+            }
+            info!("{}: {} round-trips safely.", "Verify".cyan(), cmd.input);
+        }
+        Command::Similar(cmd) => {
+            info!("Input: {}", cmd.input);
+            let groups = similar::find_similar(&cmd.input, cmd.threshold)
+                .context("Failed to compare entries")?;
+            if cmd.json || output::is_json() {
+                return output::print_json(&groups).map_err(Into::into);
+            }
+            for (i, group) in groups.iter().enumerate() {
+                println!("Group {}: {} entries", i + 1, group.len());
+                for entry in group {
+                    println!(
+                        "  [{:03}] {:>12} {:>9}",
+                        entry.idx,
+                        entry.id,
+                        utils::human_duration(entry.duration)
+                    );
+                }
+            }
+            info!(
+                "{}: {} groups of {} entries.",
+                "Similar".cyan(),
+                groups.len(),
+                groups.iter().map(Vec::len).sum::<usize>()
+            );
+        }
+        #[cfg(feature = "gui")]
+        Command::Gui => {
+            INTERACTIVE_MODE.store(false, atomic::Ordering::SeqCst);
+            gui::run()?;
+        }
+        #[cfg(feature = "self-update")]
+        Command::SelfUpdate(cmd) => {
+            let current = update::Version::current();
+            if cmd.check {
+                match update::check().context("Failed to check for updates")? {
+                    Some(release) => info!(
+                        "New version {} is available: {}",
+                        release.tag_name, release.html_url
+                    ),
+                    None => info!("Already up to date ({}).", current),
+                }
+                return Ok(());
+            }
+            match update::self_update().context("Failed to update")? {
+                Some(release) => {
+                    let old_path = update::old_exe_path()?;
+                    let mut config = Config::global().lock();
+                    config.update.replaced_exe = Some(old_path.to_string_lossy().to_string());
+                    config.save();
+                    info!(
+                        "Updated {} -> {}, restart the tool to use the new version.",
+                        current, release.tag_name
+                    )
+                }
+                None => info!("Already up to date ({}).", current),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Extract bundles of a PAK archive to their paths under output root,
+/// and dump each next to it.
+fn dump_pak(cmd: &CmdUnpackBundle, input: &Path, output_root: &Path) -> eyre::Result<()> {
+    let mut path_list = vec![];
+    for list in &cmd.pak_list {
+        let content =
+            fs::read_to_string(list).with_context(|| format!("Failed to read {}", list))?;
+        path_list.extend(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|line| line.contains(".sbnk.") || line.contains(".spck."))
+                .map(String::from),
+        );
+    }
+    let mut pak = PakReader::open(input).context("Failed to open PAK file")?;
+    let bundles = pak
+        .bundles(&path_list)
+        .context("Failed to find bundles in PAK file")?;
+    if bundles.is_empty() {
+        warn!("No BNK or PCK bundles found in {}.", input.display());
+    }
+    for bundle in &bundles {
+        let bundle_path = output_root.join(&bundle.path);
+        let bundle_dir = bundle_path.parent().unwrap_or(output_root);
+        fs::create_dir_all(bundle_dir)?;
+        let data = pak
+            .read(&bundle.entry)
+            .with_context(|| format!("Failed to read {} from PAK file", bundle.path))?;
+        fs::write(&bundle_path, data)?;
+        output::file_written(&bundle_path);
+        info!("{}: {}", "Extract".cyan(), bundle.path);
+        dump_bundle(cmd, &bundle_path, bundle_dir)?;
+    }
+    info!(
+        "{} bundles unpacked from {}.",
+        bundles.len(),
+        input.display()
+    );
+    Ok(())
+}
+
+/// Dump a bundle file with the options of unpack command.
+fn dump_bundle(
+    cmd: &CmdUnpackBundle,
+    input: &Path,
+    output_root: &Path,
+) -> eyre::Result<SoundToolProject> {
+    let file_type =
+        InputFileType::from_path(input).ok_or(eyre::eyre!("Unsupported input file type"))?;
+    let mut project = match file_type {
+        InputFileType::Bnk => SoundToolProject::dump_bnk_with(input, output_root, cmd.lenient)
+            .context("Failed to dump bnk")?,
+        InputFileType::Pck => SoundToolProject::dump_pck_with(input, output_root, cmd.lenient)
+            .context("Failed to dump pck")?,
+        other => eyre::bail!("Unsupported input file type: {:?}", other),
+    };
+    // before the options of the PCK, which may strip its banks
+    if cmd.nested {
+        let nested = project
+            .dump_nested_banks()
+            .context("Failed to dump nested banks")?;
+        for (bank_path, mut nested_project) in nested {
+            finish_dump(cmd, &bank_path, &mut nested_project)?;
+        }
+    }
+    finish_dump(cmd, input, &mut project)?;
+    Ok(project)
+}
+
+/// Deploy folder given in command line, or configured.
+fn deploy_root(target: Option<&str>) -> eyre::Result<PathBuf> {
+    target
+        .map(PathBuf::from)
+        .or_else(|| Config::global().lock().paths.deploy_root())
+        .ok_or_else(|| {
+            eyre::eyre!("No deploy folder given, and `paths.deploy_root` is not set in config.")
+        })
+}
+
+/// Project counts printed by stats command.
+#[derive(serde::Serialize)]
+struct ProjectSummary {
+    name: String,
+    #[serde(flatten)]
+    stats: project::ProjectStats,
+    /// From the data pack of the source bundle.
+    description: Option<String>,
+    last_build: Option<u32>,
+}
+
+fn load_workspace(path: &str) -> eyre::Result<Workspace> {
+    info!("Workspace: {}", path);
+    let workspace = Workspace::from_path(path).context("Failed to load workspace")?;
+    if workspace.members().is_empty() {
+        warn!("Workspace has no member projects.");
+    }
+    Ok(workspace)
+}
+
+/// Replace rules of `--rules`, matching names of the data packs in
+/// `datapacks_dir` or the installed ones, none without the flag.
+fn package_rules(cmd: &CmdPackageProject, datapacks_dir: Option<&Path>) -> eyre::Result<RuleSet> {
+    if cmd.rules {
+        RuleSet::installed_with(datapacks_dir).context("Failed to load rules")
+    } else {
+        Ok(RuleSet::default())
+    }
+}
+
+/// Package a project to `output_root`, or next to the project. `default_deploy`
+/// replaces `paths.deploy_root` of config when `--deploy` has no value.
+fn package_project(
+    cmd: &CmdPackageProject,
+    input: &Path,
+    output_root: Option<&Path>,
+    default_deploy: Option<&Path>,
+    rules: &RuleSet,
+) -> eyre::Result<()> {
+    let project = SoundToolProject::from_path(input).context("Failed to load project")?;
+
+    let output_root = output_root
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| input.parent().unwrap_or(Path::new(".")).to_path_buf());
+    let policy = cmd.output_policy();
+    if policy == OutputPolicy::Overwrite {
+        guard_vanilla(
+            &policy.output_path(&output_root, project.source_file_name()),
+            cmd.allow_vanilla_overwrite,
+        )?;
+    }
+    let stats = project
+        .repack_with_rules(&output_root, &policy, rules)
+        .context("Failed to repack project")?;
+    stats.log_summary();
+    record_game_file(&stats.output_path);
+    output::file_written(&stats.output_path);
+    output::entries_replaced(stats.replaced());
+    let keep = Config::global().lock().history.keep;
+    if keep > 0 {
+        match History::installed(input)
+            .and_then(|mut history| history.record(&stats.output_path, keep).cloned())
+        {
+            Ok(build) => info!("{}: recorded as build {}.", "History".cyan(), build.id),
+            Err(e) => warn!("Failed to record output in history: {}", e),
+        }
+    }
+    if let Some(target) = &cmd.deploy {
+        let deploy_root = match default_deploy {
+            Some(root) if target.is_empty() => root.to_path_buf(),
+            _ => deploy_root(Some(target.as_str()).filter(|target| !target.is_empty()))?,
+        };
+        let relative_path = project.natives_path().unwrap_or_else(|| {
+            warn!(
+                "Path of the source bundle under natives is unknown, deployed to the root folder."
+            );
+            PathBuf::from(project.source_file_name())
+        });
+        let target = deploy_root.join(relative_path);
+        guard_vanilla(&target, cmd.allow_vanilla_overwrite)?;
+        let mut deployment = deploy::Deployment::new();
+        deployment.add(&stats.output_path, target);
+        for path in deployment.commit().context("Failed to deploy output")? {
+            record_game_file(&path);
+            info!("{}: {}", "Deployed".green(), path.display());
+            output::file_written(path);
+        }
+    }
+    Ok(())
+}
+
+fn package_workspace(cmd: &CmdPackageProject, path: &str) -> eyre::Result<()> {
+    let workspace = load_workspace(path)?;
+    let output_root = cmd
+        .output
+        .as_ref()
+        .map(PathBuf::from)
+        .or_else(|| workspace.output_root());
+    if let Some(output_root) = &output_root {
+        info!("Output: {}", output_root.display());
+        check_shared_outputs(&workspace)?;
+        fs::create_dir_all(output_root)?;
+    }
+    // data packs of the workspace replace the installed ones for rules
+    let rules = package_rules(cmd, workspace.datapacks_dir().as_deref())?;
+    // conversion defaults of the workspace apply to all members
+    let transcode = workspace.transcode().map(|transcode| {
+        std::mem::replace(&mut Config::global().lock().transcode, transcode.clone())
+    });
+
+    let members = workspace.members();
+    let mut failed = 0;
+    for (i, member) in members.iter().enumerate() {
+        let name = workspace.member_name(member);
+        info!(
+            "{} [{}/{}] {}",
+            "Package".cyan(),
+            i + 1,
+            members.len(),
+            name
+        );
+        let result = package_project(
+            cmd,
+            member,
+            output_root.as_deref(),
+            workspace.deploy_root().as_deref(),
+            &rules,
+        );
+        if let Err(e) = result {
+            error!("Failed to package {}: {:#}", name, e);
+            failed += 1;
+        }
+    }
+    if let Some(transcode) = transcode {
+        Config::global().lock().transcode = transcode;
+    }
+    info!(
+        "Packaged {} of {} projects.",
+        members.len() - failed,
+        members.len()
+    );
+    if failed > 0 {
+        eyre::bail!("{} projects failed to package", failed);
+    }
+    Ok(())
+}
+
+/// Refuse members packaged to the same file of a shared output root, bundles
+/// of the same name from different folders.
+fn check_shared_outputs(workspace: &Workspace) -> eyre::Result<()> {
+    let mut outputs: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for member in workspace.members() {
+        // members failing to load are reported when packaged
+        if let Ok(project) = SoundToolProject::from_path(member) {
+            outputs
+                .entry(project.source_file_name().to_string())
+                .or_default()
+                .push(workspace.member_name(member));
+        }
+    }
+    let conflicts: Vec<String> = outputs
+        .into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .map(|(file_name, members)| format!("{} by {}", file_name, members.join(", ")))
+        .collect();
+    if !conflicts.is_empty() {
+        return Err(output::failure(
+            FailureClass::Project,
+            format!(
+                "Members write the same output file into output_root: {}",
+                conflicts.join("; ")
+            ),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn validate_workspace(path: &str) -> eyre::Result<()> {
+    let workspace = load_workspace(path)?;
+    let mut problems = 0;
+    for member in workspace.members() {
+        let name = workspace.member_name(member);
+        let issues = project::validate_project(member)
+            .with_context(|| format!("Failed to validate project {}", name))?;
+        for issue in &issues {
+            error!("{}: {}", name, issue);
+        }
+        problems += issues.len();
     }
+    if problems > 0 {
+        return Err(output::failure(
+            FailureClass::Check,
+            format!(
+                "{} problems found in {} projects.",
+                problems,
+                workspace.members().len()
+            ),
+        )
+        .into());
+    }
+    info!(
+        "{}: No problems found in {} projects.",
+        "Validate".cyan(),
+        workspace.members().len()
+    );
+    Ok(())
+}
 
+/// Refuse to replace a file of a game install unless allowed, and back it up
+/// if allowed.
+fn guard_vanilla(target: &Path, allow: bool) -> eyre::Result<()> {
+    if !target.is_file() {
+        return Ok(());
+    }
+    let Some(game_root) = deploy::game_root(target) else {
+        return Ok(());
+    };
+    // written by an earlier package or deploy
+    if deploy::is_tool_written(target, &game_root).context("Failed to check game file")? {
+        return Ok(());
+    }
+    if !allow {
+        return Err(output::failure(
+            FailureClass::Protected,
+            format!(
+                "{} is a file of the game install {}, add --allow-vanilla-overwrite to replace it.",
+                target.display(),
+                game_root.display()
+            ),
+        )
+        .into());
+    }
+    let backup = deploy::backup_vanilla(target, &game_root, deploy::vanilla_backup_dir(&game_root))
+        .context("Failed to back up game file")?;
+    info!("{}: {}", "Backup".cyan(), backup.display());
     Ok(())
 }
 
+/// Record a file written into a game install, see [`guard_vanilla`].
+fn record_game_file(path: &Path) {
+    if let Some(game_root) = deploy::game_root(path)
+        && let Err(e) = deploy::record_written(path, &game_root)
+    {
+        warn!("Failed to record {} as written: {}", path.display(), e);
+    }
+}
+
+/// Apply options of unpack command to a project dumped from `source`.
+fn finish_dump(
+    cmd: &CmdUnpackBundle,
+    source: &Path,
+    project: &mut SoundToolProject,
+) -> eyre::Result<()> {
+    if let Some(game_version) = &cmd.game_version {
+        project
+            .set_game_version(game_version)
+            .context("Failed to write project metadata")?;
+    }
+    let mut names = NameList::default();
+    if !cmd.no_datapacks {
+        let packs = DataPacks::installed().context("Failed to load data packs")?;
+        let file_name = source.file_name().unwrap_or_default().to_string_lossy();
+        if let Some(description) = packs.bundle_description(&file_name) {
+            info!("{}: {}", "DataPack".cyan(), description);
+        }
+        names = packs.name_list();
+    }
+    for path in &cmd.names {
+        names
+            .add_file(path)
+            .with_context(|| format!("Failed to load name list {}", path))?;
+    }
+    if !names.is_empty() {
+        project
+            .annotate_names(&names)
+            .context("Failed to annotate names")?;
+    }
+    if cmd.store {
+        project
+            .pack_media_store()
+            .context("Failed to create media store")?;
+    }
+    if let Some(format) = cmd.decode {
+        let decoded = project
+            .decode_media_with(format.into(), cmd.labels.map(LabelFormat::from))
+            .context("Failed to decode wem files")?;
+        info!(
+            "{}: {} files decoded to {}/.",
+            "Decode".cyan(),
+            decoded,
+            project::DECODED_DIR
+        );
+    }
+    if cmd.meta_only {
+        project
+            .strip_media(source)
+            .context("Failed to create metadata only project")?;
+    }
+    output::file_written(project.project_path());
+    Ok(())
+}
+
+/// Dump all bundles in a directory recursively, keeping the folder structure,
+/// and update `index.json` in output root.
+fn dump_directory(cmd: &CmdUnpackBundle, input: &Path, output_root: &Path) -> eyre::Result<()> {
+    let mut bundles = vec![];
+    let mut dirs = vec![input.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                // skip dumped projects, they contain bnk entries
+                if !path.join("project.json").is_file() {
+                    dirs.push(path);
+                }
+                continue;
+            }
+            if matches!(
+                InputFileType::from_path(&path),
+                Some(InputFileType::Bnk | InputFileType::Pck)
+            ) {
+                bundles.push(path);
+            }
+        }
+    }
+    bundles.sort();
+    if bundles.is_empty() {
+        eyre::bail!("No bundle found in {}", input.display());
+    }
+
+    let index_path = output_root.join(index::INDEX_FILE_NAME);
+    let mut media_index = index::MediaIndex::load(&index_path).context("Failed to load index")?;
+    let mut failed = 0;
+    for (i, bundle) in bundles.iter().enumerate() {
+        let relative = bundle.strip_prefix(input).unwrap_or(bundle);
+        let bundle_name = relative.to_string_lossy().replace('\\', "/");
+        info!(
+            "{} [{}/{}] {}",
+            "Dump".cyan(),
+            i + 1,
+            bundles.len(),
+            bundle_name
+        );
+        let bundle_output = output_root.join(relative.parent().unwrap_or(Path::new("")));
+        fs::create_dir_all(&bundle_output)?;
+        let result = dump_bundle(cmd, bundle, &bundle_output).and_then(|project| {
+            media_index
+                .add_project(&bundle_name, &project)
+                .context("Failed to index project")
+        });
+        if let Err(e) = result {
+            error!("Failed to dump {}: {:#}", bundle_name, e);
+            failed += 1;
+        }
+    }
+    fs::create_dir_all(output_root)?;
+    media_index
+        .save(&index_path)
+        .context("Failed to save index")?;
+    output::file_written(&index_path);
+    info!(
+        "Dumped {} of {} bundles, {} media IDs indexed in {}.",
+        bundles.len() - failed,
+        bundles.len(),
+        media_index.media.len(),
+        index_path.display()
+    );
+    if failed > 0 {
+        eyre::bail!("{} bundles failed to dump", failed);
+    }
+    Ok(())
+}
+
+/// Pairs of input and output paths of transcode command.
+///
+/// Sound files in a directory input are found recursively, and their outputs
+/// keep the folder structure under `output_dir`.
+fn transcode_outputs(
+    input: &Path,
+    output_dir: Option<&Path>,
+    format: &str,
+) -> eyre::Result<Vec<(PathBuf, PathBuf)>> {
+    let is_sound = |path: &Path| {
+        matches!(
+            InputFileType::from_path(path),
+            Some(InputFileType::GeneralAudio(_))
+        )
+    };
+    let mut files = vec![];
+    if input.is_dir() {
+        let mut sounds = vec![];
+        let mut dirs = vec![input.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if is_sound(&path) {
+                    sounds.push(path);
+                }
+            }
+        }
+        sounds.sort();
+        let output_root = output_dir.unwrap_or(input);
+        for sound in sounds {
+            let relative = sound.strip_prefix(input).unwrap_or(&sound);
+            let output = output_root.join(relative).with_extension(format);
+            files.push((sound, output));
+        }
+    } else if input.is_file() {
+        if !is_sound(input) {
+            return Err(output::failure(
+                FailureClass::Input,
+                format!("Unsupported input file type: {}", input.display()),
+            )
+            .into());
+        }
+        let output_root = output_dir.unwrap_or(input.parent().unwrap_or(Path::new(".")));
+        let output = output_root
+            .join(input.file_name().unwrap())
+            .with_extension(format);
+        files.push((input.to_path_buf(), output));
+    } else {
+        return Err(output::failure(
+            FailureClass::Input,
+            format!("Input not found: {}", input.display()),
+        )
+        .into());
+    }
+    files.retain(|(input, output)| {
+        if input == output {
+            warn!(
+                "Skipped {}, it is already {}, specify another --output.",
+                input.display(),
+                format
+            );
+            return false;
+        }
+        true
+    });
+    Ok(files)
+}
+
 fn wait_for_exit() {
-    if INTERACTIVE_MODE.load(atomic::Ordering::SeqCst) {
+    if prompt::is_interactive() {
         let _: String = Input::new()
             .allow_empty(true)
             .with_prompt("Press Enter to exit")
@@ -0,0 +1,492 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    hirc::{HircPreset, PropEdit},
+    modinfo::ModInfoConfig,
+    project::IdOrIndex,
+    utils,
+    wwise::{self, Conversion},
+};
+
+type Result<T> = std::result::Result<T, ManifestError>;
+
+pub const MANIFEST_FILE_NAME: &str = "project.toml";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ManifestError {
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("Failed to parse {MANIFEST_FILE_NAME}: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("Invalid replace directive for {0}: {1}")]
+    InvalidDirective(IdOrIndex, &'static str),
+    #[error("Source {0} is remapped more than once.")]
+    DuplicateRemap(u32),
+    #[error(
+        "Unknown Wwise platform '{0}', expected one of {platforms:?}.",
+        platforms = wwise::PLATFORMS
+    )]
+    UnknownPlatform(String),
+}
+
+/// User editable project settings, stored as `project.toml` in project folder.
+///
+/// Unlike `project.json`, this file is never written by the tool.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectManifest {
+    /// HIRC transformations applied on repack.
+    #[serde(default, rename = "preset")]
+    pub presets: Vec<HircPreset>,
+    /// Alignment of wem data in bank DATA section, 1 for no padding.
+    /// Defaults to the alignment of the source bank.
+    #[serde(default)]
+    pub data_alignment: Option<u32>,
+    /// Wwise target platform of replace files conversion,
+    /// overrides `transcode.platform` in config. Can be overridden per entry.
+    #[serde(default)]
+    pub platform: Option<String>,
+    /// Keep sample rate and channels of replace files, instead of converting
+    /// them to the format of the originals.
+    #[serde(default)]
+    pub keep_input_format: bool,
+    /// Fail packaging on replace files matching no entry, instead of
+    /// skipping them with a warning.
+    #[serde(default)]
+    pub strict_replace: bool,
+    /// Size policy of replaced entries, can be overridden per entry.
+    #[serde(default)]
+    pub size_policy: SizePolicy,
+    /// Store identical wem data of PCK entries only once, e.g. voice lines
+    /// shared by several languages.
+    #[serde(default)]
+    pub share_duplicates: bool,
+    /// Mod metadata written with the output, none if not set.
+    #[serde(default)]
+    pub modinfo: Option<ModInfoConfig>,
+    /// Per entry replace directives.
+    #[serde(default)]
+    pub replace: Vec<ReplaceDirective>,
+    /// Folders of variations spread over the sounds of random containers.
+    #[serde(default)]
+    pub variations: Vec<VariationDirective>,
+    /// Source wem IDs rewritten in HIRC sounds.
+    #[serde(default)]
+    pub remap: Vec<SourceRemap>,
+    /// Property edits of HIRC objects applied on repack.
+    #[serde(default)]
+    pub props: Vec<PropEdit>,
+    /// Wem entries dropped from the output, unique ID or `"[index]"`.
+    #[serde(default)]
+    pub remove: Vec<IdOrIndex>,
+    /// Modified time of the output bundle.
+    #[serde(default)]
+    pub timestamp: OutputTimestamp,
+}
+
+/// How replacement data larger or smaller than the original entry is handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SizePolicy {
+    /// Any size is accepted.
+    #[default]
+    Free,
+    /// Smaller data is padded with zeros to the original length,
+    /// larger data is rejected.
+    Pad,
+    /// Larger data is rejected.
+    MaxOriginal,
+}
+
+/// Modified time set on the output bundle, some mod managers order or
+/// detect conflicts of files by it.
+///
+/// `"now"`, `"original"` for the time of the bundle the project was dumped
+/// from, or a fixed UTC time like `"2025-01-01T00:00:00Z"`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum OutputTimestamp {
+    #[default]
+    Now,
+    Original,
+    /// Seconds since Unix epoch.
+    Fixed(u64),
+}
+
+impl TryFrom<String> for OutputTimestamp {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        match value.as_str() {
+            "now" => Ok(Self::Now),
+            "original" => Ok(Self::Original),
+            time => utils::parse_utc_time(time).map(Self::Fixed).ok_or_else(|| {
+                format!(
+                    "invalid timestamp '{}', expected \"now\", \"original\" or UTC time like \"2025-01-01T00:00:00Z\"",
+                    time
+                )
+            }),
+        }
+    }
+}
+
+impl From<OutputTimestamp> for String {
+    fn from(value: OutputTimestamp) -> Self {
+        match value {
+            OutputTimestamp::Now => "now".to_string(),
+            OutputTimestamp::Original => "original".to_string(),
+            OutputTimestamp::Fixed(secs) => utils::format_utc_time(secs),
+        }
+    }
+}
+
+/// Replace directive of a single entry, `[[replace]]` in manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplaceDirective {
+    /// Target entry, unique ID or `"[index]"`.
+    pub target: IdOrIndex,
+    /// Copy the entry from another BNK or PCK bundle,
+    /// relative to project folder or its parent folder.
+    pub from_bundle: Option<PathBuf>,
+    pub from_id: Option<u32>,
+    pub from_index: Option<u32>,
+    /// Overrides the project `size_policy` for this entry.
+    pub size_policy: Option<SizePolicy>,
+    /// Conversion of replace sound files, detected from the original entry if not set.
+    pub conversion: Option<Conversion>,
+    /// Overrides the project `platform` for this entry.
+    pub platform: Option<String>,
+}
+
+impl ReplaceDirective {
+    /// Entry to copy from `from_bundle`.
+    pub fn source_entry(&self) -> Option<IdOrIndex> {
+        self.from_id
+            .map(IdOrIndex::Id)
+            .or(self.from_index.map(IdOrIndex::Index))
+    }
+
+    fn validate(&self) -> Result<()> {
+        let invalid = |reason| Err(ManifestError::InvalidDirective(self.target, reason));
+        if self.from_id.is_some() && self.from_index.is_some() {
+            return invalid("from_id and from_index are exclusive");
+        }
+        if self.from_bundle.is_none()
+            && self.source_entry().is_none()
+            && self.size_policy.is_none()
+            && self.conversion.is_none()
+            && self.platform.is_none()
+        {
+            return invalid(
+                "sets nothing, expected from_bundle, size_policy, conversion or platform",
+            );
+        }
+        match (&self.from_bundle, self.source_entry()) {
+            (Some(_), None) => invalid("from_bundle requires from_id or from_index"),
+            (None, Some(_)) => invalid("from_id and from_index require from_bundle"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Replace the sounds of a random container with a folder of variations,
+/// `[[variations]]` in manifest.
+///
+/// The sounds are found in HIRC, files are assigned to them in name order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariationDirective {
+    /// Random container ID, or the wem ID of one of its sounds.
+    pub target: u32,
+    /// Folder of sound files, relative to project folder.
+    pub folder: PathBuf,
+    #[serde(default)]
+    pub fill: VariationFill,
+}
+
+/// `[[remap]]` in manifest.
+///
+/// Sounds playing wem `from` play wem `to` instead, e.g. a wem added with a
+/// new ID in place of a removed one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceRemap {
+    pub from: u32,
+    pub to: u32,
+}
+
+/// How sounds are filled when a folder has fewer variations than the container.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VariationFill {
+    /// Start over from the first file.
+    #[default]
+    Cycle,
+    /// Keep the original sounds.
+    Keep,
+}
+
+impl VariationFill {
+    /// Source IDs paired with the files assigned to them.
+    pub fn assign<T: Clone>(self, sources: &[u32], files: &[T]) -> Vec<(u32, T)> {
+        if files.is_empty() {
+            return vec![];
+        }
+        let count = match self {
+            VariationFill::Cycle => sources.len(),
+            VariationFill::Keep => sources.len().min(files.len()),
+        };
+        sources
+            .iter()
+            .take(count)
+            .enumerate()
+            .map(|(i, &source)| (source, files[i % files.len()].clone()))
+            .collect()
+    }
+}
+
+impl ProjectManifest {
+    /// Load manifest from project folder, returns default if not present.
+    pub fn load(project_path: impl AsRef<Path>) -> Result<Self> {
+        let path = project_path.as_ref().join(MANIFEST_FILE_NAME);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)?;
+        let manifest: Self = toml::from_str(&content)?;
+        for directive in &manifest.replace {
+            directive.validate()?;
+        }
+        let platforms = manifest.platform.iter();
+        for platform in platforms.chain(manifest.replace.iter().flat_map(|d| &d.platform)) {
+            if wwise::check_platform(platform).is_err() {
+                return Err(ManifestError::UnknownPlatform(platform.clone()));
+            }
+        }
+        for (i, remap) in manifest.remap.iter().enumerate() {
+            if manifest.remap[..i]
+                .iter()
+                .any(|other| other.from == remap.from)
+            {
+                return Err(ManifestError::DuplicateRemap(remap.from));
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Whether an entry is removed by its index or ID.
+    pub fn is_removed(&self, idx: u32, id: u32) -> bool {
+        self.remove
+            .iter()
+            .any(|target| *target == IdOrIndex::Index(idx) || *target == IdOrIndex::Id(id))
+    }
+
+    /// Size policy of an entry, directives targeting the index take precedence.
+    pub fn size_policy(&self, idx: u32, id: u32) -> SizePolicy {
+        self.find_override(idx, id, |directive| directive.size_policy)
+            .unwrap_or(self.size_policy)
+    }
+
+    /// Conversion override of an entry, directives targeting the index take precedence.
+    pub fn conversion(&self, idx: u32, id: u32) -> Option<Conversion> {
+        self.find_override(idx, id, |directive| directive.conversion)
+    }
+
+    /// Target platform of an entry, directives targeting the index take
+    /// precedence. `None` for `transcode.platform` in config.
+    pub fn platform(&self, idx: u32, id: u32) -> Option<String> {
+        self.find_override(idx, id, |directive| directive.platform.clone())
+            .or_else(|| self.platform.clone())
+    }
+
+    fn find_override<T>(
+        &self,
+        idx: u32,
+        id: u32,
+        field: impl Fn(&ReplaceDirective) -> Option<T>,
+    ) -> Option<T> {
+        [IdOrIndex::Index(idx), IdOrIndex::Id(id)]
+            .iter()
+            .find_map(|target| {
+                self.replace
+                    .iter()
+                    .filter(|directive| directive.target == *target)
+                    .find_map(&field)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hirc::BusRef;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_presets() {
+        let manifest: ProjectManifest = toml::from_str(
+            r#"
+            [[preset]]
+            type = "bus-volume"
+            bus = "Master Audio Bus"
+            db = -6.0
+
+            [[preset]]
+            type = "bus-volume"
+            bus = 4232434793
+            db = 3
+            "#,
+        )
+        .unwrap();
+        assert_eq!(manifest.presets.len(), 2);
+        let HircPreset::BusVolume { bus, db } = &manifest.presets[0];
+        assert_eq!(bus.id(), 3803692087);
+        assert_eq!(*db, -6.0);
+        let HircPreset::BusVolume { bus, .. } = &manifest.presets[1];
+        assert!(matches!(bus, BusRef::Id(4232434793)));
+        assert_eq!(manifest.data_alignment, None);
+    }
+
+    #[test]
+    fn test_parse_replace_directives() {
+        let manifest: ProjectManifest = toml::from_str(
+            r#"
+            [[replace]]
+            target = 8242880
+            from_bundle = "Wp01_Cmn_m.sbnk.1.X64"
+            from_id = 999
+
+            [[replace]]
+            target = "[3]"
+            from_bundle = "Wp01_Cmn_m.sbnk.1.X64"
+            "#,
+        )
+        .unwrap();
+        let first = &manifest.replace[0];
+        assert_eq!(first.target, IdOrIndex::Id(8242880));
+        assert_eq!(first.source_entry(), Some(IdOrIndex::Id(999)));
+        assert!(first.validate().is_ok());
+        let second = &manifest.replace[1];
+        assert_eq!(second.target, IdOrIndex::Index(3));
+        assert!(second.validate().is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(MANIFEST_FILE_NAME),
+            "[[replace]]\ntarget = 100\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            ProjectManifest::load(dir.path()),
+            Err(ManifestError::InvalidDirective(IdOrIndex::Id(100), _))
+        ));
+    }
+
+    #[test]
+    fn test_entry_overrides() {
+        let manifest: ProjectManifest = toml::from_str(
+            r#"
+            size_policy = "max-original"
+            platform = "PS5"
+            remove = [300, "[4]"]
+
+            [[replace]]
+            target = 100
+            size_policy = "pad"
+
+            [[replace]]
+            target = "[2]"
+            size_policy = "free"
+            conversion = "adpcm"
+            platform = "Switch"
+            "#,
+        )
+        .unwrap();
+        assert!(manifest.replace.iter().all(|d| d.validate().is_ok()));
+        assert_eq!(manifest.size_policy(0, 100), SizePolicy::Pad);
+        assert_eq!(manifest.size_policy(2, 100), SizePolicy::Free);
+        assert_eq!(manifest.size_policy(1, 200), SizePolicy::MaxOriginal);
+        assert_eq!(ProjectManifest::default().size_policy, SizePolicy::Free);
+        assert_eq!(manifest.conversion(2, 200), Some(Conversion::Adpcm));
+        assert_eq!(manifest.conversion(0, 100), None);
+        assert_eq!(manifest.platform(2, 200).as_deref(), Some("Switch"));
+        assert_eq!(manifest.platform(0, 100).as_deref(), Some("PS5"));
+        assert_eq!(ProjectManifest::default().platform(0, 100), None);
+        assert!(manifest.is_removed(0, 300));
+        assert!(manifest.is_removed(4, 100));
+        assert!(!manifest.is_removed(3, 100));
+    }
+
+    #[test]
+    fn test_unknown_platform() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(MANIFEST_FILE_NAME);
+        fs::write(&path, "platform = \"../x\"").unwrap();
+        assert!(matches!(
+            ProjectManifest::load(dir.path()),
+            Err(ManifestError::UnknownPlatform(platform)) if platform == "../x"
+        ));
+        fs::write(&path, "[[replace]]\ntarget = 100\nplatform = \"windows\"").unwrap();
+        assert!(matches!(
+            ProjectManifest::load(dir.path()),
+            Err(ManifestError::UnknownPlatform(_))
+        ));
+    }
+
+    #[test]
+    fn test_variations() {
+        let manifest: ProjectManifest = toml::from_str(
+            r#"
+            [[variations]]
+            target = 600001
+            folder = "hits"
+            fill = "keep"
+            "#,
+        )
+        .unwrap();
+        let directive = &manifest.variations[0];
+        assert_eq!(directive.folder, Path::new("hits"));
+        assert_eq!(directive.fill, VariationFill::Keep);
+
+        let sources = [1, 2, 3, 4, 5];
+        assert_eq!(
+            VariationFill::Cycle.assign(&sources, &["a", "b"]),
+            [(1, "a"), (2, "b"), (3, "a"), (4, "b"), (5, "a")]
+        );
+        assert_eq!(
+            VariationFill::Keep.assign(&sources, &["a", "b"]),
+            [(1, "a"), (2, "b")]
+        );
+        assert_eq!(
+            VariationFill::Keep.assign(&sources[..1], &["a", "b"]),
+            [(1, "a")]
+        );
+        assert!(
+            VariationFill::Cycle
+                .assign::<&str>(&sources, &[])
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let parse = |timestamp: &str| {
+            toml::from_str::<ProjectManifest>(&format!("timestamp = \"{}\"", timestamp))
+                .map(|manifest| manifest.timestamp)
+        };
+        assert_eq!(ProjectManifest::default().timestamp, OutputTimestamp::Now);
+        assert_eq!(parse("original").unwrap(), OutputTimestamp::Original);
+        assert_eq!(
+            parse("2025-01-01T00:00:00Z").unwrap(),
+            OutputTimestamp::Fixed(1735689600)
+        );
+        assert!(parse("tomorrow").is_err());
+        assert_eq!(
+            String::from(OutputTimestamp::Fixed(1735689600)),
+            "2025-01-01T00:00:00Z"
+        );
+    }
+}
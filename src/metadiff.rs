@@ -0,0 +1,271 @@
+//! Semantic diff of edited project metadata against the original bundle.
+//!
+//! Sections are matched by magic and entries by ID, so reordered or
+//! removed entries are reported as such instead of as shifted fields.
+
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::names::{NAME_KEY, REFERENCED_KEY};
+
+/// Byte arrays longer than this are compared as a whole.
+const BLOB_THRESHOLD: usize = 16;
+/// Longest value shown in a change.
+const MAX_VALUE_LENGTH: usize = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A single change, `path` like `sections[BKHD].content.language_id`.
+#[derive(Debug, Clone, Serialize)]
+pub struct MetaChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<String>,
+}
+
+impl fmt::Display for MetaChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let old = self.old.as_deref().unwrap_or_default();
+        let new = self.new.as_deref().unwrap_or_default();
+        match self.kind {
+            ChangeKind::Added => write!(f, "+ {}: {}", self.path, new),
+            ChangeKind::Removed => write!(f, "- {}: {}", self.path, old),
+            ChangeKind::Changed => write!(f, "~ {}: {} -> {}", self.path, old, new),
+        }
+    }
+}
+
+/// Changes from `original` to `edited` metadata.
+pub fn diff(original: &Value, edited: &Value) -> Vec<MetaChange> {
+    let mut changes = vec![];
+    diff_value("", original, edited, &mut changes);
+    changes
+}
+
+/// Keys added on dump for reading, ignored on repack.
+fn is_annotation(key: &str) -> bool {
+    key == NAME_KEY || key == REFERENCED_KEY
+}
+
+fn diff_value(path: &str, old: &Value, new: &Value, changes: &mut Vec<MetaChange>) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for (key, old_value) in old_map {
+                // resolved names are annotations, not edits
+                if is_annotation(key) {
+                    continue;
+                }
+                let path = join_field(path, key);
+                match new_map.get(key) {
+                    Some(new_value) => diff_value(&path, old_value, new_value, changes),
+                    None => changes.push(removed(path, old_value)),
+                }
+            }
+            for (key, new_value) in new_map {
+                if !is_annotation(key) && !old_map.contains_key(key) {
+                    changes.push(added(join_field(path, key), new_value));
+                }
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items)) => {
+            if is_blob(old_items) || is_blob(new_items) {
+                if old_items != new_items {
+                    changes.push(MetaChange {
+                        path: path.to_string(),
+                        kind: ChangeKind::Changed,
+                        old: Some(summarize(old)),
+                        new: Some(summarize(new)),
+                    });
+                }
+                return;
+            }
+            match (keyed(old_items), keyed(new_items)) {
+                (Some(old_keyed), Some(new_keyed)) => {
+                    for (key, old_item) in &old_keyed {
+                        let path = format!("{}[{}]", path, key);
+                        match new_keyed.iter().find(|(new_key, _)| new_key == key) {
+                            Some((_, new_item)) => diff_value(&path, old_item, new_item, changes),
+                            None => changes.push(removed(path, old_item)),
+                        }
+                    }
+                    for (key, new_item) in &new_keyed {
+                        if !old_keyed.iter().any(|(old_key, _)| old_key == key) {
+                            changes.push(added(format!("{}[{}]", path, key), new_item));
+                        }
+                    }
+                    let common_order = |a: &[(String, &Value)], b: &[(String, &Value)]| {
+                        a.iter()
+                            .filter(|(key, _)| b.iter().any(|(other, _)| other == key))
+                            .map(|(key, _)| key.clone())
+                            .collect::<Vec<_>>()
+                    };
+                    if common_order(&old_keyed, &new_keyed) != common_order(&new_keyed, &old_keyed)
+                    {
+                        changes.push(MetaChange {
+                            path: path.to_string(),
+                            kind: ChangeKind::Changed,
+                            old: Some("original order".to_string()),
+                            new: Some("reordered".to_string()),
+                        });
+                    }
+                }
+                _ => {
+                    for (i, (old_item, new_item)) in old_items.iter().zip(new_items).enumerate() {
+                        diff_value(&format!("{}[{}]", path, i), old_item, new_item, changes);
+                    }
+                    for (i, old_item) in old_items.iter().enumerate().skip(new_items.len()) {
+                        changes.push(removed(format!("{}[{}]", path, i), old_item));
+                    }
+                    for (i, new_item) in new_items.iter().enumerate().skip(old_items.len()) {
+                        changes.push(added(format!("{}[{}]", path, i), new_item));
+                    }
+                }
+            }
+        }
+        _ if old != new => changes.push(MetaChange {
+            path: path.to_string(),
+            kind: ChangeKind::Changed,
+            old: Some(summarize(old)),
+            new: Some(summarize(new)),
+        }),
+        _ => {}
+    }
+}
+
+fn join_field(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn added(path: String, value: &Value) -> MetaChange {
+    MetaChange {
+        path,
+        kind: ChangeKind::Added,
+        old: None,
+        new: Some(summarize(value)),
+    }
+}
+
+fn removed(path: String, value: &Value) -> MetaChange {
+    MetaChange {
+        path,
+        kind: ChangeKind::Removed,
+        old: Some(summarize(value)),
+        new: None,
+    }
+}
+
+fn is_blob(items: &[Value]) -> bool {
+    items.len() > BLOB_THRESHOLD
+        && items
+            .iter()
+            .all(|item| item.as_u64().is_some_and(|b| b <= 255))
+}
+
+/// Key of each item if all items are objects with a unique key,
+/// sections by magic, entries by ID and strings by index.
+fn keyed(items: &[Value]) -> Option<Vec<(String, &Value)>> {
+    let mut keyed = Vec::with_capacity(items.len());
+    for item in items {
+        let key = if let Some(magic) = item.get("magic").and_then(Value::as_array) {
+            magic
+                .iter()
+                .map(|b| b.as_u64().map(|b| b as u8 as char))
+                .collect::<Option<String>>()?
+        } else if let Some(id) = item.get("id") {
+            format!("id={}", id)
+        } else if let Some(index) = item.get("index") {
+            format!("index={}", index)
+        } else {
+            return None;
+        };
+        if keyed.iter().any(|(other, _)| *other == key) {
+            return None;
+        }
+        keyed.push((key, item));
+    }
+    Some(keyed)
+}
+
+fn summarize(value: &Value) -> String {
+    if let Value::Array(items) = value
+        && is_blob(items)
+    {
+        return format!("<{} bytes>", items.len());
+    }
+    let text = value.to_string();
+    if text.chars().count() > MAX_VALUE_LENGTH {
+        let short: String = text.chars().take(MAX_VALUE_LENGTH).collect();
+        format!("{}...", short)
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::project::SoundToolProject;
+
+    #[test]
+    fn test_diff() {
+        let original = json!({
+            "version": 1,
+            "entries": [{"id": 1, "v": 0}, {"id": 2, "v": 0}, {"id": 3, "v": 0}],
+            "data": vec![0; 20],
+        });
+        let edited = json!({
+            "version": 2,
+            "entries": [{"id": 3, "v": 0}, {"id": 1, "v": 5}, {"id": 4, "v": 0}],
+            "data": vec![1; 20],
+        });
+        let changes: Vec<String> = diff(&original, &edited)
+            .iter()
+            .map(|change| change.to_string())
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                "~ data: <20 bytes> -> <20 bytes>",
+                "~ entries[id=1].v: 0 -> 5",
+                "- entries[id=2]: {\"id\":2,\"v\":0}",
+                "+ entries[id=4]: {\"id\":4,\"v\":0}",
+                "~ entries: original order -> reordered",
+                "~ version: 1 -> 2",
+            ]
+        );
+        assert!(diff(&original, &original).is_empty());
+    }
+
+    #[test]
+    fn test_diff_project_metadata() {
+        let input = "test_files/Wp00_Cmn.sbnk.1.X64";
+        let output_root = tempfile::tempdir().unwrap();
+        let project = SoundToolProject::dump_bnk(input, output_root.path()).unwrap();
+        let original = project.original_metadata(input).unwrap();
+        assert!(diff(&original, &project.metadata().unwrap()).is_empty());
+
+        let mut edited = original.clone();
+        edited["sections"][0]["content"]["project_id"] = json!(1);
+        edited["sections"][0]["content"][NAME_KEY] = json!("Init");
+        let changes = diff(&original, &edited);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "sections[BKHD].content.project_id");
+    }
+}
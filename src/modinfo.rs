@@ -0,0 +1,238 @@
+//! Mod metadata written with repacked bundles.
+//!
+//! Enabled by a `[modinfo]` table in `project.toml`:
+//!
+//! ```toml
+//! [modinfo]
+//! name = "Quiet Great Sword"
+//! author = "someone"
+//! version = "1.2.0"
+//! # "sidecar" for `<output>.modinfo.json`, "section" for a custom
+//! # `MODI` section in BNK output, ignored by the game
+//! embed = "sidecar"
+//! ```
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    bnk::{Bnk, Section, SectionPayload},
+    lock::LOCK_FILE_NAME,
+    project::DECODED_DIR,
+    session::SESSION_FILE_NAME,
+    utils,
+};
+
+type Result<T> = std::result::Result<T, ModInfoError>;
+
+/// Magic of the custom bank section.
+pub const MODINFO_MAGIC: [u8; 4] = *b"MODI";
+pub const MODINFO_SUFFIX: &str = ".modinfo.json";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ModInfoError {
+    #[error("Failed to access {}: {source}", path.display())]
+    File { path: PathBuf, source: io::Error },
+
+    #[error("Bad mod info: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl ModInfoError {
+    /// Attach the path to an IO error.
+    fn file(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| ModInfoError::File { path, source }
+    }
+}
+
+/// Where mod info is written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Embed {
+    /// `.modinfo.json` next to the output.
+    #[default]
+    Sidecar,
+    /// Custom section in BNK output, PCK output falls back to sidecar.
+    Section,
+}
+
+/// `[modinfo]` in `project.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModInfoConfig {
+    pub name: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub embed: Embed,
+    /// Record SHA-256 of outputs in `hashes.txt` next to them.
+    #[serde(default)]
+    pub hashes: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModInfo {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Version of the tool the bundle is repacked with.
+    pub tool_version: String,
+    /// SHA-256 of the source project files, see [`project_hash`].
+    pub project_hash: String,
+}
+
+impl ModInfo {
+    pub fn new(config: &ModInfoConfig, project_path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            name: config.name.clone(),
+            author: config.author.clone(),
+            version: config.version.clone(),
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            project_hash: project_hash(project_path)?,
+        })
+    }
+
+    /// Sidecar path of a bundle.
+    pub fn sidecar_path(bundle_path: impl AsRef<Path>) -> PathBuf {
+        let mut path = bundle_path.as_ref().as_os_str().to_owned();
+        path.push(MODINFO_SUFFIX);
+        PathBuf::from(path)
+    }
+
+    pub fn write_sidecar(&self, bundle_path: impl AsRef<Path>) -> Result<PathBuf> {
+        let path = Self::sidecar_path(bundle_path);
+        fs::write(&path, serde_json::to_string_pretty(self)?).map_err(ModInfoError::file(&path))?;
+        Ok(path)
+    }
+
+    /// Custom bank section carrying this info as JSON.
+    pub fn to_section(&self) -> Result<Section> {
+        let data = serde_json::to_vec(self)?;
+        Ok(Section {
+            magic: MODINFO_MAGIC,
+            section_length: data.len() as u32,
+            payload: SectionPayload::Unk { data },
+        })
+    }
+
+    /// Mod info of a bundle, from its sidecar or custom bank section.
+    pub fn read(bundle_path: impl AsRef<Path>) -> Result<Option<Self>> {
+        let bundle_path = bundle_path.as_ref();
+        let sidecar_path = Self::sidecar_path(bundle_path);
+        if sidecar_path.is_file() {
+            let content =
+                fs::read_to_string(&sidecar_path).map_err(ModInfoError::file(&sidecar_path))?;
+            return Ok(Some(serde_json::from_str(&content)?));
+        }
+        let mut reader = io::BufReader::new(
+            fs::File::open(bundle_path).map_err(ModInfoError::file(bundle_path))?,
+        );
+        let Ok(bank) = Bnk::from_reader(&mut reader) else {
+            return Ok(None);
+        };
+        Self::from_bank(&bank)
+    }
+
+    pub fn from_bank(bank: &Bnk) -> Result<Option<Self>> {
+        for section in &bank.sections {
+            if let (MODINFO_MAGIC, SectionPayload::Unk { data }) = (section.magic, &section.payload)
+            {
+                return Ok(Some(serde_json::from_slice(data)?));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Hash of all files in project folder, by relative path and content.
+/// Decoded previews and the project lock are skipped.
+pub fn project_hash(project_path: impl AsRef<Path>) -> Result<String> {
+    let project_path = project_path.as_ref();
+    let mut files = vec![];
+    collect_files(project_path, project_path, &mut files)?;
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for relative in files {
+        hasher.update(relative.as_bytes());
+        hasher.update([0]);
+        let path = project_path.join(&relative);
+        let data = fs::read(&path).map_err(ModInfoError::file(&path))?;
+        hasher.update((data.len() as u64).to_le_bytes());
+        hasher.update(data);
+    }
+    Ok(utils::to_hex(&hasher.finalize()))
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<()> {
+    for entry in fs::read_dir(dir).map_err(ModInfoError::file(dir))? {
+        let path = entry.map_err(ModInfoError::file(dir))?.path();
+        let relative = path
+            .strip_prefix(root)
+            .unwrap()
+            .to_string_lossy()
+            .replace('\\', "/");
+        if relative == DECODED_DIR || relative == LOCK_FILE_NAME || relative == SESSION_FILE_NAME {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            files.push(relative);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modinfo() {
+        let project_path = tempfile::tempdir().unwrap();
+        fs::write(project_path.path().join("project.json"), "{}").unwrap();
+        fs::create_dir(project_path.path().join(DECODED_DIR)).unwrap();
+        let config: ModInfoConfig = toml::from_str("name = \"Test\"\nembed = \"section\"").unwrap();
+        assert_eq!(config.embed, Embed::Section);
+        let info = ModInfo::new(&config, project_path.path()).unwrap();
+
+        // decoded previews do not change the hash
+        fs::write(project_path.path().join(DECODED_DIR).join("0.wav"), "x").unwrap();
+        assert_eq!(
+            project_hash(project_path.path()).unwrap(),
+            info.project_hash
+        );
+        fs::write(project_path.path().join("project.json"), "{ }").unwrap();
+        assert_ne!(
+            project_hash(project_path.path()).unwrap(),
+            info.project_hash
+        );
+
+        let bundle_path = project_path.path().join("Test.sbnk.1.X64");
+        let sidecar_path = info.write_sidecar(&bundle_path).unwrap();
+        assert!(
+            sidecar_path
+                .to_string_lossy()
+                .ends_with(".X64.modinfo.json")
+        );
+        assert_eq!(ModInfo::read(&bundle_path).unwrap(), Some(info.clone()));
+
+        let mut bank = Bnk {
+            sections: vec![],
+            endian: Default::default(),
+        };
+        assert_eq!(ModInfo::from_bank(&bank).unwrap(), None);
+        bank.sections.push(info.to_section().unwrap());
+        assert_eq!(ModInfo::from_bank(&bank).unwrap(), Some(info));
+    }
+}
@@ -0,0 +1,214 @@
+//! Names of Wwise objects resolved from name lists.
+//!
+//! Most Wwise IDs are FNV-1 hashes of object names, see [`utils::fnv1_32`].
+//! A name list is a text file of one name per line, or `<id> <name>` for
+//! IDs not derived from names, or a `SoundbanksInfo.xml` written by Wwise.
+
+use std::{collections::HashMap, fs, io, path::Path, sync::LazyLock};
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::utils;
+
+// <Event Id="123" Name="Play_foo" ObjectPath="..."/>
+static REG_XML_OBJECT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"<\w+\s[^>]*?\bId="(\d+)"[^>]*?\bName="([^"]+)""#).unwrap());
+// <File Id="123" Language="SFX"><ShortName>foo.wav</ShortName>
+static REG_XML_FILE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)<File\s[^>]*?\bId="(\d+)"[^>]*>\s*<ShortName>([^<]+)</ShortName>"#).unwrap()
+});
+
+/// Key of names added to metadata, see [`NameList::annotate_json`].
+pub const NAME_KEY: &str = "name";
+/// Key of bank names of STID entries, which are edits of the bank rather than
+/// annotations.
+pub const BANK_NAME_KEY: &str = "bank_name";
+/// Key marking STID entries of banks referenced by a bank, an annotation like
+/// [`NAME_KEY`].
+pub const REFERENCED_KEY: &str = "referenced";
+
+/// Longest name appended to dumped file names.
+const MAX_FILE_NAME_SUFFIX: usize = 64;
+
+#[derive(Debug, Clone, Default)]
+pub struct NameList {
+    names: HashMap<u32, String>,
+}
+
+impl NameList {
+    pub fn from_files(paths: &[impl AsRef<Path>]) -> io::Result<Self> {
+        let mut list = Self::default();
+        for path in paths {
+            list.add_file(path)?;
+        }
+        Ok(list)
+    }
+
+    /// Add names from a text list, or `SoundbanksInfo.xml` by extension.
+    /// Returns the count of names added.
+    pub fn add_file(&mut self, path: impl AsRef<Path>) -> io::Result<usize> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+        let is_xml = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("xml"));
+        Ok(if is_xml {
+            self.add_xml(&content)
+        } else {
+            self.add_text(&content)
+        })
+    }
+
+    pub fn add_text(&mut self, content: &str) -> usize {
+        let mut added = 0;
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let explicit = line
+                .split_once(char::is_whitespace)
+                .and_then(|(id, name)| Some((id.parse().ok()?, name.trim())));
+            match explicit {
+                Some((id, name)) => self.names.insert(id, name.to_string()),
+                None => self.names.insert(utils::fnv1_32(line), line.to_string()),
+            };
+            added += 1;
+        }
+        added
+    }
+
+    pub fn add_xml(&mut self, content: &str) -> usize {
+        let mut added = 0;
+        for captures in REG_XML_OBJECT.captures_iter(content) {
+            if let Ok(id) = captures[1].parse() {
+                self.names.insert(id, captures[2].to_string());
+                added += 1;
+            }
+        }
+        // media IDs are not hashes, file names are the only names of them
+        for captures in REG_XML_FILE.captures_iter(content) {
+            if let Ok(id) = captures[1].parse() {
+                let short_name = Path::new(&captures[2]).file_stem().unwrap_or_default();
+                self.names
+                    .insert(id, short_name.to_string_lossy().to_string());
+                added += 1;
+            }
+        }
+        added
+    }
+
+    pub fn insert(&mut self, id: u32, name: impl Into<String>) {
+        self.names.insert(id, name.into());
+    }
+
+    pub fn get(&self, id: u32) -> Option<&str> {
+        self.names.get(&id).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Add a `name` field to every JSON object with a resolved `id`, objects
+    /// already named by the bank itself, like STID entries, are kept.
+    /// Returns the count of objects annotated.
+    pub fn annotate_json(&self, value: &mut Value) -> usize {
+        match value {
+            Value::Object(map) => {
+                let mut annotated = 0;
+                let name = map
+                    .get("id")
+                    .and_then(Value::as_u64)
+                    .and_then(|id| self.get(id as u32));
+                if let Some(name) = name
+                    && !map.contains_key(NAME_KEY)
+                    && !map.contains_key(BANK_NAME_KEY)
+                {
+                    map.insert(NAME_KEY.to_string(), Value::String(name.to_string()));
+                    annotated += 1;
+                }
+                for value in map.values_mut() {
+                    annotated += self.annotate_json(value);
+                }
+                annotated
+            }
+            Value::Array(items) => items.iter_mut().map(|item| self.annotate_json(item)).sum(),
+            _ => 0,
+        }
+    }
+}
+
+/// Name made safe to append to a file name.
+pub fn file_name_suffix(name: &str) -> String {
+    name.chars()
+        .take(MAX_FILE_NAME_SUFFIX)
+        .map(|c| {
+            if c.is_control() || r#"<>:"/\|?*"#.contains(c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect::<String>()
+        .trim_end_matches(['.', ' '])
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_name_list() {
+        let mut names = NameList::default();
+        let added = names.add_text(
+            "
+            # events
+            Play_wp00_swing
+            123456 wp00_hit.wav
+            ",
+        );
+        assert_eq!(added, 2);
+        assert_eq!(
+            names.get(utils::fnv1_32("play_wp00_swing")),
+            Some("Play_wp00_swing")
+        );
+        assert_eq!(names.get(123456), Some("wp00_hit.wav"));
+
+        let added = names.add_xml(
+            r#"
+            <SoundBank Id="11" Language="SFX">
+                <ShortName>Wp00_Cmn</ShortName>
+                <Events>
+                    <Event Id="22" Name="Play_wp00_draw" ObjectPath="\Events\Play_wp00_draw"/>
+                </Events>
+                <Media>
+                    <File Id="33" Language="SFX">
+                        <ShortName>wp00_draw_01.wav</ShortName>
+                        <Path>SFX\wp00_draw_01_10C4C929.wem</Path>
+                    </File>
+                </Media>
+            </SoundBank>
+            "#,
+        );
+        assert_eq!(added, 2);
+        assert_eq!(names.get(22), Some("Play_wp00_draw"));
+        assert_eq!(names.get(33), Some("wp00_draw_01"));
+
+        let mut value = json!({"id": 22, "entries": [{"id": 33}, {"id": 44}]});
+        assert_eq!(names.annotate_json(&mut value), 2);
+        assert_eq!(value["name"], "Play_wp00_draw");
+        assert_eq!(value["entries"][0]["name"], "wp00_draw_01");
+        assert!(value["entries"][1].get("name").is_none());
+
+        assert_eq!(file_name_suffix("a/b:c?. "), "a_b_c_");
+    }
+}
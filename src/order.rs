@@ -0,0 +1,171 @@
+//! Canonical order of dumped entries, stored as `order.json` in project folder.
+//!
+//! Dumped files are named `[index]id.wem`, where index is the position of the
+//! entry in the DIDX section of a BNK, or in the wem or bnk table of a PCK.
+//! Replace files named by index depend on it, so the order is recorded on dump
+//! and checked against the files on disk on repack:
+//!
+//! ```json
+//! { "wem": [500001, 500002], "bnk": [] }
+//! ```
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+type Result<T> = std::result::Result<T, OrderError>;
+
+pub const ORDER_FILE_NAME: &str = "order.json";
+
+#[derive(Debug, thiserror::Error)]
+pub enum OrderError {
+    #[error("Failed to access {}: {source}", path.display())]
+    File { path: PathBuf, source: io::Error },
+
+    #[error("Bad entry order file {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error(
+        "File {file} does not match the dumped order, entry [{idx}] is {}.",
+        .expected.map_or("not in the bundle".to_string(), |id| id.to_string())
+    )]
+    Mismatch {
+        file: String,
+        idx: u32,
+        expected: Option<u32>,
+    },
+    #[error("Files {first} and {second} are the same entry [{idx}], keep only one of them.")]
+    Duplicate {
+        idx: u32,
+        first: String,
+        second: String,
+    },
+}
+
+impl OrderError {
+    /// Attach the path to an IO error.
+    fn file(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| OrderError::File { path, source }
+    }
+}
+
+/// IDs of dumped entries by index.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryOrder {
+    #[serde(default)]
+    pub wem: Vec<u32>,
+    /// Banks in a PCK.
+    #[serde(default)]
+    pub bnk: Vec<u32>,
+}
+
+impl EntryOrder {
+    /// Load from project folder, `None` for projects dumped before it was recorded.
+    pub fn load(project_path: impl AsRef<Path>) -> Result<Option<Self>> {
+        let path = project_path.as_ref().join(ORDER_FILE_NAME);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = fs::read(&path).map_err(OrderError::file(&path))?;
+        serde_json::from_slice(&content)
+            .map(Some)
+            .map_err(|source| OrderError::Parse { path, source })
+    }
+
+    pub fn save(&self, project_path: impl AsRef<Path>) -> Result<()> {
+        let path = project_path.as_ref().join(ORDER_FILE_NAME);
+        let content = serde_json::to_vec_pretty(self).map_err(|source| OrderError::Parse {
+            path: path.clone(),
+            source,
+        })?;
+        fs::write(&path, content).map_err(OrderError::file(&path))?;
+        Ok(())
+    }
+
+    /// IDs of entries with the extension of dumped files, `wem` or `bnk`.
+    pub fn ids(&self, ext: &str) -> &[u32] {
+        match ext {
+            "bnk" => &self.bnk,
+            _ => &self.wem,
+        }
+    }
+
+    /// Check `(file name, index, id)` of dumped files against the order.
+    ///
+    /// Missing entries are allowed, every file must be the entry at its index
+    /// and at most one file may exist for an index.
+    pub fn check<'a>(
+        &self,
+        ext: &str,
+        files: impl IntoIterator<Item = (&'a str, u32, u32)>,
+    ) -> Result<()> {
+        let ids = self.ids(ext);
+        let mut seen: Vec<Option<&str>> = vec![None; ids.len()];
+        for (file, idx, id) in files {
+            let expected = ids.get(idx as usize).copied();
+            if expected != Some(id) {
+                return Err(OrderError::Mismatch {
+                    file: file.to_string(),
+                    idx,
+                    expected,
+                });
+            }
+            if let Some(first) = seen[idx as usize].replace(file) {
+                return Err(OrderError::Duplicate {
+                    idx,
+                    first: first.to_string(),
+                    second: file.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_order() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(EntryOrder::load(dir.path()).unwrap(), None);
+        let order = EntryOrder {
+            wem: vec![300, 100, 200],
+            bnk: vec![],
+        };
+        order.save(dir.path()).unwrap();
+        let order = EntryOrder::load(dir.path()).unwrap().unwrap();
+
+        // missing entries are fine
+        order
+            .check("wem", [("[000]300.wem", 0, 300), ("[002]200.wem", 2, 200)])
+            .unwrap();
+        assert!(matches!(
+            order.check("wem", [("[001]200.wem", 1, 200)]),
+            Err(OrderError::Mismatch {
+                idx: 1,
+                expected: Some(100),
+                ..
+            })
+        ));
+        assert!(matches!(
+            order.check("wem", [("[003]400.wem", 3, 400)]),
+            Err(OrderError::Mismatch { expected: None, .. })
+        ));
+        assert!(matches!(
+            order.check(
+                "wem",
+                [("[001]100.wem", 1, 100), ("[001]100_foo.wem", 1, 100)]
+            ),
+            Err(OrderError::Duplicate { idx: 1, .. })
+        ));
+        assert!(order.check("bnk", [("[000]300.bnk", 0, 300)]).is_err());
+    }
+}
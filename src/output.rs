@@ -0,0 +1,464 @@
+//! Machine readable output of commands, with `--format json`.
+//!
+//! Log lines still go to stderr, warnings and errors are also collected.
+//! When the command finishes, one JSON object is printed to stdout:
+//!
+//! ```json
+//! {
+//!   "success": true,
+//!   "files_written": ["bgm/Wp00_Cmn.sbnk.1.X64.new"],
+//!   "entries_replaced": 3,
+//!   "warnings": [],
+//!   "errors": []
+//! }
+//! ```
+//!
+//! Failures exit with the code of their class, in both formats.
+//!
+//! With `--json-logs`, log lines are written as JSON events, one per line,
+//! to stderr instead of the colored log, or to a file next to it. Events of
+//! interest carry a `code` and the `entry` or `file` they are about:
+//!
+//! ```json
+//! {"level":"info","target":"mhws_sound_tool::project","message":"Replace: Wem file '123456' replaced by ID.","code":"entry-replaced","entry":123456}
+//! ```
+
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        LazyLock,
+        atomic::{self, AtomicBool},
+    },
+};
+
+use log::{
+    Level, Log, Metadata, Record,
+    kv::{self, VisitSource, VisitValue},
+};
+use mhws_sound_tool::{
+    history::HistoryError,
+    order::OrderError,
+    project::{ProjectError, ValidationIssue},
+    transcode::TranscodeError,
+    workspace::WorkspaceError,
+    wwise::WwiseError,
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+
+static JSON_MODE: AtomicBool = AtomicBool::new(false);
+static OUTPUT: LazyLock<Mutex<CommandOutput>> =
+    LazyLock::new(|| Mutex::new(CommandOutput::default()));
+static JSON_LOGS: Mutex<Option<JsonLogSink>> = Mutex::new(None);
+
+/// Where JSON log events go.
+enum JsonLogSink {
+    /// Instead of the colored log.
+    Stderr,
+    /// Next to the colored log.
+    File(fs::File),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Colored log lines.
+    #[default]
+    Text,
+    /// A JSON object of results on stdout, logs on stderr only.
+    Json,
+}
+
+/// Class of a failed command, each exits with its own code.
+///
+/// Code 2 is left to clap, for bad command line arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailureClass {
+    General,
+    /// Input file not found or not supported.
+    Input,
+    /// Bad project, e.g. broken metadata or replace files.
+    Project,
+    /// External tool like WwiseConsole or vgmstream not found.
+    Tool,
+    /// Converting sounds failed.
+    Conversion,
+    Io,
+    /// A check like `validate` or `verify` found problems.
+    Check,
+    /// Refused to replace a file of the game install.
+    Protected,
+}
+
+impl FailureClass {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            FailureClass::General => 1,
+            FailureClass::Input => 3,
+            FailureClass::Project => 4,
+            FailureClass::Tool => 5,
+            FailureClass::Conversion => 6,
+            FailureClass::Io => 7,
+            FailureClass::Check => 8,
+            FailureClass::Protected => 9,
+        }
+    }
+
+    /// Class of an error by the errors in its chain.
+    pub fn of(error: &(dyn std::error::Error + 'static)) -> Self {
+        let find = |class_of: &dyn Fn(&(dyn std::error::Error + 'static)) -> Option<Self>| {
+            chain(error).find_map(class_of)
+        };
+        let explicit = find(&|e| e.downcast_ref::<Failure>().map(|failure| failure.class));
+        let tool = find(&|e| {
+            let missing = matches!(
+                e.downcast_ref::<TranscodeError>(),
+                Some(TranscodeError::ToolNotFound(_) | TranscodeError::ToolNotConfigured(_))
+            ) || matches!(
+                e.downcast_ref::<WwiseError>(),
+                Some(WwiseError::WwiseConsoleNotFound)
+            );
+            missing.then_some(FailureClass::Tool)
+        });
+        let input = find(&|e| {
+            let missing = matches!(
+                e.downcast_ref::<ProjectError>(),
+                Some(
+                    ProjectError::UnsupportedInput(_)
+                        | ProjectError::MissingMetadata(_)
+                        | ProjectError::BundleNotFound(_)
+                        | ProjectError::EntryNotFound { .. }
+                        | ProjectError::LanguageNotFound { .. }
+                )
+            ) || matches!(
+                e.downcast_ref::<TranscodeError>(),
+                Some(TranscodeError::FileNotFound(_))
+            ) || matches!(
+                e.downcast_ref::<HistoryError>(),
+                Some(HistoryError::BuildNotFound(_))
+            ) || matches!(
+                e.downcast_ref::<WorkspaceError>(),
+                Some(WorkspaceError::NotFound(_) | WorkspaceError::MemberNotFound(_))
+            ) || e
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound);
+            missing.then_some(FailureClass::Input)
+        });
+        let conversion = find(&|e| {
+            (e.is::<TranscodeError>() || e.is::<WwiseError>()).then_some(FailureClass::Conversion)
+        });
+        let project = find(&|e| {
+            (e.is::<ProjectError>() || e.is::<OrderError>() || e.is::<ValidationIssue>())
+                .then_some(FailureClass::Project)
+        });
+        let io = find(&|e| e.is::<std::io::Error>().then_some(FailureClass::Io));
+        explicit
+            .or(tool)
+            .or(input)
+            .or(conversion)
+            .or(project)
+            .or(io)
+            .unwrap_or(FailureClass::General)
+    }
+}
+
+/// Error of a given failure class, for failures not carried by a typed error.
+#[derive(Debug)]
+pub struct Failure {
+    pub class: FailureClass,
+    pub message: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// Error of a given failure class, to return from a command.
+pub fn failure(class: FailureClass, message: impl Into<String>) -> Failure {
+    Failure {
+        class,
+        message: message.into(),
+    }
+}
+
+/// An error and its sources, outermost first.
+fn chain<'a>(
+    error: &'a (dyn std::error::Error + 'static),
+) -> impl Iterator<Item = &'a (dyn std::error::Error + 'static)> {
+    std::iter::successors(Some(error), |e| e.source())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FailureOutput {
+    pub class: FailureClass,
+    pub exit_code: i32,
+    pub message: String,
+}
+
+/// Results of a command, printed as JSON.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CommandOutput {
+    pub success: bool,
+    pub files_written: Vec<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entries_replaced: Option<usize>,
+    pub warnings: Vec<String>,
+    pub errors: Vec<String>,
+    /// Results of commands with JSON output of their own, e.g. `list`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<FailureOutput>,
+}
+
+pub fn set_format(format: OutputFormat) {
+    let json = format == OutputFormat::Json;
+    JSON_MODE.store(json, atomic::Ordering::SeqCst);
+    if json {
+        colored::control::set_override(false);
+    }
+}
+
+pub fn is_json() -> bool {
+    JSON_MODE.load(atomic::Ordering::SeqCst)
+}
+
+/// Write log events as JSON lines to `path`, or to stderr instead of the
+/// colored log if `None`.
+pub fn set_json_logs(path: Option<&Path>) -> io::Result<()> {
+    let sink = match path {
+        Some(path) => JsonLogSink::File(fs::File::create(path)?),
+        None => {
+            colored::control::set_override(false);
+            JsonLogSink::Stderr
+        }
+    };
+    *JSON_LOGS.lock() = Some(sink);
+    Ok(())
+}
+
+pub fn file_written(path: impl AsRef<Path>) {
+    OUTPUT
+        .lock()
+        .files_written
+        .push(path.as_ref().to_path_buf());
+}
+
+pub fn entries_replaced(count: usize) {
+    *OUTPUT.lock().entries_replaced.get_or_insert(0) += count;
+}
+
+/// Print a result as JSON, or keep it for the result object with `--format json`.
+pub fn print_json(value: &impl Serialize) -> serde_json::Result<()> {
+    if is_json() {
+        OUTPUT.lock().data = Some(serde_json::to_value(value)?);
+    } else {
+        println!("{}", serde_json::to_string_pretty(value)?);
+    }
+    Ok(())
+}
+
+/// Print the result object of a finished command, failed with `error`.
+pub fn print_result(error: Option<&(dyn std::error::Error + 'static)>) {
+    let mut output = OUTPUT.lock().clone();
+    output.success = error.is_none();
+    if let Some(e) = error {
+        let class = FailureClass::of(e);
+        let message: Vec<String> = chain(e).map(|e| e.to_string()).collect();
+        output.failure = Some(FailureOutput {
+            class,
+            exit_code: class.exit_code(),
+            message: message.join(": "),
+        });
+    }
+    match serde_json::to_string_pretty(&output) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Failed to serialize output: {}", e),
+    }
+}
+
+/// Logger collecting warnings and errors for the result object.
+pub struct CollectingLogger {
+    inner: env_logger::Logger,
+}
+
+impl CollectingLogger {
+    pub fn init(inner: env_logger::Logger) -> Result<(), log::SetLoggerError> {
+        let max_level = inner.filter();
+        log::set_boxed_logger(Box::new(Self { inner }))?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+}
+
+impl Log for CollectingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if is_json() && self.inner.matches(record) {
+            let message = record.args().to_string();
+            match record.level() {
+                Level::Error => OUTPUT.lock().errors.push(message),
+                Level::Warn => OUTPUT.lock().warnings.push(message),
+                _ => {}
+            }
+        }
+        if self.inner.matches(record) {
+            let mut json_logs = JSON_LOGS.lock();
+            let written = match json_logs.as_mut() {
+                Some(JsonLogSink::Stderr) => {
+                    let _ = writeln!(io::stderr().lock(), "{}", log_event(record));
+                    true
+                }
+                Some(JsonLogSink::File(file)) => {
+                    let _ = writeln!(file, "{}", log_event(record));
+                    false
+                }
+                None => false,
+            };
+            if written {
+                return;
+            }
+        }
+        self.inner.log(record);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// JSON event of a log record, with its key-values as fields.
+fn log_event(record: &Record) -> serde_json::Value {
+    struct Fields(serde_json::Map<String, serde_json::Value>);
+
+    impl<'kvs> VisitSource<'kvs> for Fields {
+        fn visit_pair(
+            &mut self,
+            key: kv::Key<'kvs>,
+            value: kv::Value<'kvs>,
+        ) -> Result<(), kv::Error> {
+            let mut field = Field(None);
+            value.visit(&mut field)?;
+            if let Some(value) = field.0 {
+                self.0.insert(key.to_string(), value);
+            }
+            Ok(())
+        }
+    }
+
+    /// Value of a field, `None` for optional values which are missing.
+    struct Field(Option<serde_json::Value>);
+
+    impl<'v> VisitValue<'v> for Field {
+        fn visit_any(&mut self, value: kv::Value) -> Result<(), kv::Error> {
+            self.0 = Some(value.to_string().into());
+            Ok(())
+        }
+
+        fn visit_null(&mut self) -> Result<(), kv::Error> {
+            Ok(())
+        }
+
+        fn visit_u64(&mut self, value: u64) -> Result<(), kv::Error> {
+            self.0 = Some(value.into());
+            Ok(())
+        }
+
+        fn visit_i64(&mut self, value: i64) -> Result<(), kv::Error> {
+            self.0 = Some(value.into());
+            Ok(())
+        }
+    }
+
+    let mut fields = serde_json::Map::new();
+    fields.insert(
+        "level".to_string(),
+        record.level().as_str().to_ascii_lowercase().into(),
+    );
+    fields.insert("target".to_string(), record.target().into());
+    fields.insert(
+        "message".to_string(),
+        strip_ansi(&record.args().to_string()).into(),
+    );
+    let mut fields = Fields(fields);
+    let _ = record.key_values().visit(&mut fields);
+    serde_json::Value::Object(fields.0)
+}
+
+/// Text without ANSI color codes.
+fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // skip to the final byte of the escape sequence
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_class() {
+        let error = ProjectError::UnsupportedInput("a.txt".into());
+        assert_eq!(FailureClass::of(&error), FailureClass::Input);
+        // found by the source of the error
+        let error = ProjectError::Transcode(TranscodeError::ToolNotFound("vgmstream-cli"));
+        assert_eq!(FailureClass::of(&error), FailureClass::Tool);
+        let error = ProjectError::BadFileName("x".to_string());
+        assert_eq!(FailureClass::of(&error), FailureClass::Project);
+        let error = failure(FailureClass::Check, "2 problems found.");
+        assert_eq!(FailureClass::of(&error).exit_code(), 8);
+        assert_eq!(FailureClass::of(&fmt::Error), FailureClass::General);
+    }
+
+    #[test]
+    fn test_log_event() {
+        let path = PathBuf::from("replace/a.wav");
+        let event = log_event(
+            &Record::builder()
+                .level(Level::Error)
+                .target("mhws_sound_tool::transcode")
+                .args(format_args!("\x1b[36mReplace\x1b[0m: failed"))
+                .key_values(&[
+                    ("code", kv::Value::from("conversion-failed")),
+                    ("entry", kv::Value::from(123456u32)),
+                    ("file", kv::Value::from_display(&path.display())),
+                    ("language", kv::Value::null()),
+                    ("note", kv::Value::from("")),
+                ])
+                .build(),
+        );
+        assert_eq!(
+            event,
+            serde_json::json!({
+                "level": "error",
+                "target": "mhws_sound_tool::transcode",
+                "message": "Replace: failed",
+                "code": "conversion-failed",
+                "entry": 123456,
+                "file": "replace/a.wav",
+                "note": "",
+            })
+        );
+    }
+}
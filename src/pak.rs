@@ -0,0 +1,437 @@
+//! Reader of unencrypted RE Engine `.pak` archives, like those of mods or
+//! archives rebuilt by community tools.
+//!
+//! Entries are located by the hash of their path, names are only known from
+//! a path list. Bundles not in the list are found by their content.
+//! Archives with an encrypted entry table or encrypted entries are not supported:
+//! the key of the table is encrypted with the game's private RSA key, which
+//! is not shipped with this tool. Retail archives of the game encrypt their
+//! table, so their bundles need to be extracted with a game unpacker first.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use byteorder::{LE, ReadBytesExt};
+use flate2::read::DeflateDecoder;
+
+use crate::detect::InputFileType;
+
+type Result<T> = std::result::Result<T, PakError>;
+
+const PAK_MAGIC: &[u8; 4] = b"KPKA";
+/// Entry table is encrypted with the game key.
+const FEATURE_ENCRYPTED_TABLE: u16 = 0x8;
+const HEADER_SIZE: u64 = 16;
+const ENTRY_SIZE_V4: usize = 48;
+/// Seed of path hashes.
+const PATH_HASH_SEED: u32 = 0xFFFFFFFF;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PakError {
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("Failed to access {}: {source}", path.display())]
+    File { path: PathBuf, source: io::Error },
+
+    #[error("Invalid magic of PAK file: {0:X?}")]
+    InvalidMagic([u8; 4]),
+    #[error("Unsupported PAK version {0}.{1}")]
+    UnsupportedVersion(u8, u8),
+    #[error(
+        "PAK entry table is encrypted (features {0:#X}), which is not supported. Extract the bundles with a game unpacker first."
+    )]
+    EncryptedEntryTable(u16),
+    #[error("Unsupported PAK features: {0:#X}")]
+    UnsupportedFeature(u16),
+    #[error("PAK entry {0:016X} is encrypted.")]
+    EncryptedEntry(u64),
+    #[error("PAK entry table of {files} files does not fit in the archive of {length} bytes.")]
+    TruncatedEntryTable { files: u32, length: u64 },
+    #[error("Unknown compression {1} of PAK entry {0:016X}")]
+    UnknownCompression(u64, u64),
+}
+
+impl PakError {
+    /// Attach the path to an IO error.
+    fn file(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| PakError::File { path, source }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PakCompression {
+    None,
+    Deflate,
+    Zstd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PakEntry {
+    /// Hash of uppercase path in high 32 bits, of lowercase path in low 32 bits.
+    pub hash: u64,
+    pub offset: u64,
+    pub compressed_size: u64,
+    pub size: u64,
+    /// Compression in low 4 bits, encryption in bits 16-23.
+    pub attributes: u64,
+    pub checksum: u64,
+}
+
+impl PakEntry {
+    fn from_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let hash_lower = reader.read_u32::<LE>()?;
+        let hash_upper = reader.read_u32::<LE>()?;
+        Ok(PakEntry {
+            hash: (hash_upper as u64) << 32 | hash_lower as u64,
+            offset: reader.read_u64::<LE>()?,
+            compressed_size: reader.read_u64::<LE>()?,
+            size: reader.read_u64::<LE>()?,
+            attributes: reader.read_u64::<LE>()?,
+            checksum: reader.read_u64::<LE>()?,
+        })
+    }
+
+    pub fn compression(&self) -> Result<PakCompression> {
+        match self.attributes & 0xF {
+            0 => Ok(PakCompression::None),
+            1 => Ok(PakCompression::Deflate),
+            2 => Ok(PakCompression::Zstd),
+            other => Err(PakError::UnknownCompression(self.hash, other)),
+        }
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.attributes & 0x00FF_0000 != 0
+    }
+}
+
+/// A BNK or PCK bundle found in an archive.
+#[derive(Debug, Clone)]
+pub struct PakBundle {
+    /// Path from the path list, or a name derived from the hash.
+    pub path: String,
+    pub file_type: InputFileType,
+    pub entry: PakEntry,
+}
+
+impl PakBundle {
+    pub fn file_name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+}
+
+pub struct PakReader<R> {
+    reader: R,
+    pub major_version: u8,
+    pub minor_version: u8,
+    pub feature: u16,
+    entries: Vec<PakEntry>,
+}
+
+impl PakReader<io::BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        Self::new(io::BufReader::new(
+            File::open(path).map_err(PakError::file(path))?,
+        ))
+    }
+}
+
+impl<R> PakReader<R>
+where
+    R: Read + Seek,
+{
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != PAK_MAGIC {
+            return Err(PakError::InvalidMagic(magic));
+        }
+        let major_version = reader.read_u8()?;
+        let minor_version = reader.read_u8()?;
+        let feature = reader.read_u16::<LE>()?;
+        let total_files = reader.read_u32::<LE>()?;
+        let _fingerprint = reader.read_u32::<LE>()?;
+        if major_version != 4 {
+            return Err(PakError::UnsupportedVersion(major_version, minor_version));
+        }
+        if feature & FEATURE_ENCRYPTED_TABLE != 0 {
+            return Err(PakError::EncryptedEntryTable(feature));
+        }
+        if feature != 0 {
+            return Err(PakError::UnsupportedFeature(feature));
+        }
+        // count is untrusted, check before allocating the table
+        let length = reader.seek(io::SeekFrom::End(0))?;
+        if HEADER_SIZE + total_files as u64 * ENTRY_SIZE_V4 as u64 > length {
+            return Err(PakError::TruncatedEntryTable {
+                files: total_files,
+                length,
+            });
+        }
+        reader.seek(io::SeekFrom::Start(HEADER_SIZE))?;
+
+        let mut table = vec![0; total_files as usize * ENTRY_SIZE_V4];
+        reader.read_exact(&mut table)?;
+        let mut table = io::Cursor::new(table);
+        let entries = (0..total_files)
+            .map(|_| PakEntry::from_reader(&mut table))
+            .collect::<io::Result<_>>()?;
+
+        Ok(PakReader {
+            reader,
+            major_version,
+            minor_version,
+            feature,
+            entries,
+        })
+    }
+
+    pub fn entries(&self) -> &[PakEntry] {
+        &self.entries
+    }
+
+    /// Find an entry by its path, e.g. `natives/STM/Sound/Wwise/Wp00_Cmn.sbnk.1.X64`.
+    pub fn find(&self, path: &str) -> Option<&PakEntry> {
+        let hash = path_hash(path);
+        self.entries.iter().find(|entry| entry.hash == hash)
+    }
+
+    /// Decompressed data of an entry.
+    pub fn read(&mut self, entry: &PakEntry) -> Result<Vec<u8>> {
+        // size is untrusted, not allocated up front
+        let mut data = vec![];
+        self.entry_reader(entry)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Decompressed data of an entry, up to `length` bytes.
+    fn read_head(&mut self, entry: &PakEntry, length: u64) -> Result<Vec<u8>> {
+        let mut data = vec![];
+        self.entry_reader(entry)?
+            .take(length)
+            .read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn entry_reader(&mut self, entry: &PakEntry) -> Result<Box<dyn Read + '_>> {
+        if entry.is_encrypted() {
+            return Err(PakError::EncryptedEntry(entry.hash));
+        }
+        let compression = entry.compression()?;
+        self.reader.seek(io::SeekFrom::Start(entry.offset))?;
+        let raw = (&mut self.reader).take(entry.compressed_size);
+        Ok(match compression {
+            PakCompression::None => Box::new(raw),
+            PakCompression::Deflate => Box::new(DeflateDecoder::new(raw)),
+            PakCompression::Zstd => Box::new(zstd::Decoder::new(raw)?),
+        })
+    }
+
+    /// BNK and PCK bundles in the archive.
+    ///
+    /// Entries matching paths of `path_list` are named after them, other
+    /// entries are checked by content and named by hash. Encrypted entries
+    /// are skipped.
+    pub fn bundles(&mut self, path_list: &[String]) -> Result<Vec<PakBundle>> {
+        let paths: HashMap<u64, &String> = path_list
+            .iter()
+            .map(|path| (path_hash(path), path))
+            .collect();
+        let mut bundles = vec![];
+        for entry in self.entries.clone() {
+            if entry.is_encrypted() || entry.compression().is_err() {
+                continue;
+            }
+            let head = self.read_head(&entry, 4)?;
+            let file_type = match InputFileType::from_content(&head) {
+                Some(file_type @ (InputFileType::Bnk | InputFileType::Pck)) => file_type,
+                _ => continue,
+            };
+            let path = paths
+                .get(&entry.hash)
+                .map(|path| path.to_string())
+                .unwrap_or_else(|| {
+                    let ext = if file_type == InputFileType::Bnk {
+                        "sbnk"
+                    } else {
+                        "spck"
+                    };
+                    format!("{:016X}.{}.1.X64", entry.hash, ext)
+                });
+            bundles.push(PakBundle {
+                path,
+                file_type,
+                entry,
+            });
+        }
+        Ok(bundles)
+    }
+}
+
+/// Hash of an entry path, case insensitive.
+pub fn path_hash(path: &str) -> u64 {
+    let utf16 = |s: String| -> Vec<u8> { s.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+    let path = path.replace('\\', "/");
+    let lower = murmur3_32(&utf16(path.to_lowercase()), PATH_HASH_SEED);
+    let upper = murmur3_32(&utf16(path.to_uppercase()), PATH_HASH_SEED);
+    (upper as u64) << 32 | lower as u64
+}
+
+/// 32-bit MurmurHash3 (x86).
+fn murmur3_32(data: &[u8], seed: u32) -> u32 {
+    const C1: u32 = 0xCC9E2D51;
+    const C2: u32 = 0x1B873593;
+    let mix = |k: u32| k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+
+    let mut hash = seed;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        hash ^= mix(u32::from_le_bytes(chunk.try_into().unwrap()));
+        hash = hash
+            .rotate_left(13)
+            .wrapping_mul(5)
+            .wrapping_add(0xE6546B64);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        let k = tail
+            .iter()
+            .rev()
+            .fold(0u32, |k, &byte| (k << 8) | byte as u32);
+        hash ^= mix(k);
+    }
+
+    hash ^= data.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85EBCA6B);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xC2B2AE35);
+    hash ^ (hash >> 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use byteorder::WriteBytesExt;
+    use flate2::{Compression, write::DeflateEncoder};
+
+    use super::*;
+
+    const BNK_PATH: &str = "natives/STM/Sound/Wwise/Wp00_Cmn.sbnk.1.X64";
+
+    /// Archive of `(path, data, compression)` entries.
+    fn build_pak(entries: &[(&str, &[u8], u64)]) -> Vec<u8> {
+        let table_end = 16 + entries.len() * ENTRY_SIZE_V4;
+        let mut table = vec![];
+        let mut data = vec![];
+        for (path, content, compression) in entries {
+            let stored = match compression {
+                0 => content.to_vec(),
+                1 => {
+                    let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+                    encoder.write_all(content).unwrap();
+                    encoder.finish().unwrap()
+                }
+                _ => zstd::encode_all(*content, 0).unwrap(),
+            };
+            let hash = path_hash(path);
+            table.write_u32::<LE>(hash as u32).unwrap();
+            table.write_u32::<LE>((hash >> 32) as u32).unwrap();
+            table
+                .write_u64::<LE>((table_end + data.len()) as u64)
+                .unwrap();
+            table.write_u64::<LE>(stored.len() as u64).unwrap();
+            table.write_u64::<LE>(content.len() as u64).unwrap();
+            table.write_u64::<LE>(*compression).unwrap();
+            table.write_u64::<LE>(0).unwrap();
+            data.extend_from_slice(&stored);
+        }
+        let mut pak = PAK_MAGIC.to_vec();
+        pak.extend_from_slice(&[4, 0, 0, 0]);
+        pak.write_u32::<LE>(entries.len() as u32).unwrap();
+        pak.write_u32::<LE>(0).unwrap();
+        pak.extend_from_slice(&table);
+        pak.extend_from_slice(&data);
+        pak
+    }
+
+    #[test]
+    fn test_murmur3() {
+        assert_eq!(murmur3_32(b"", 0), 0);
+        assert_eq!(murmur3_32(b"hello", 0), 0x248BFA47);
+        assert_eq!(murmur3_32(b"", 1), 0x514E28B7);
+        assert_eq!(
+            murmur3_32(b"The quick brown fox jumps over the lazy dog", 0x9747B28C),
+            0x2FA826CD
+        );
+        assert_eq!(path_hash(BNK_PATH), path_hash(&BNK_PATH.to_lowercase()));
+    }
+
+    #[test]
+    fn test_pak_bundles() {
+        let bnk = std::fs::read("test_files/Wp00_Cmn_m.sbnk.1.X64").unwrap();
+        let pck = std::fs::read("test_files/Cat_cmn_m.spck.1.X64").unwrap();
+        let pck_path = "natives/STM/Sound/Wwise/Cat_cmn_m.spck.1.X64";
+        let pak = build_pak(&[
+            (BNK_PATH, &bnk, 1),
+            ("natives/STM/GUI/ui.tex", b"TEX\0data", 0),
+            (pck_path, &pck, 2),
+        ]);
+        let mut reader = PakReader::new(io::Cursor::new(pak)).unwrap();
+        assert_eq!(reader.entries().len(), 3);
+        let entry = *reader.find(BNK_PATH).unwrap();
+        assert_eq!(reader.read(&entry).unwrap(), bnk);
+
+        let bundles = reader.bundles(&[pck_path.to_string()]).unwrap();
+        assert_eq!(bundles.len(), 2);
+        // not in the path list
+        assert_eq!(bundles[0].file_type, InputFileType::Bnk);
+        assert_eq!(
+            bundles[0].file_name(),
+            format!("{:016X}.sbnk.1.X64", path_hash(BNK_PATH))
+        );
+        assert_eq!(bundles[1].file_name(), "Cat_cmn_m.spck.1.X64");
+        assert_eq!(reader.read(&bundles[1].entry).unwrap(), pck);
+    }
+
+    #[test]
+    fn test_pak_header_errors() {
+        // header of a retail archive: version 4.1, encrypted table,
+        // followed by the table and 128-byte encrypted key
+        let mut retail = PAK_MAGIC.to_vec();
+        retail.extend_from_slice(&[4, 1]);
+        retail.write_u16::<LE>(FEATURE_ENCRYPTED_TABLE).unwrap();
+        retail.write_u32::<LE>(2).unwrap();
+        retail.write_u32::<LE>(0x12345678).unwrap();
+        retail.extend_from_slice(&[0xA5; 2 * ENTRY_SIZE_V4 + 128]);
+        assert!(matches!(
+            PakReader::new(io::Cursor::new(retail)),
+            Err(PakError::EncryptedEntryTable(FEATURE_ENCRYPTED_TABLE))
+        ));
+
+        let mut unknown = build_pak(&[]);
+        unknown[6] = 0x1;
+        assert!(matches!(
+            PakReader::new(io::Cursor::new(unknown)),
+            Err(PakError::UnsupportedFeature(0x1))
+        ));
+
+        // a bogus file count is not allocated
+        let mut truncated = build_pak(&[("natives/STM/GUI/ui.tex", b"TEX\0data", 0)]);
+        truncated[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            PakReader::new(io::Cursor::new(truncated)),
+            Err(PakError::TruncatedEntryTable {
+                files: u32::MAX,
+                ..
+            })
+        ));
+    }
+}
@@ -1,9 +1,13 @@
 use std::io;
 
-use byteorder::{LE, ReadBytesExt, WriteBytesExt};
+use log::warn;
 use serde::{Deserialize, Serialize};
 
-use crate::utils;
+use crate::{
+    endian::Endian,
+    layout::{self, ByteLen, DataOffset},
+    utils,
+};
 
 type Result<T> = std::result::Result<T, PckError>;
 
@@ -14,8 +18,21 @@ pub enum PckError {
 
     #[error("Invalid magic of PCK file: {0:X?}")]
     InvalidMagic([u8; 4]),
+    #[deprecated(note = "no longer returned, malformed tables are reported by their own variants")]
     #[error("Assertion failed: {0}")]
     Assertion(String),
+    #[error("{table} table at offset {offset} has invalid length {length}.")]
+    InvalidTableLength {
+        table: &'static str,
+        offset: u64,
+        length: u64,
+    },
+    #[error("Language name at offset {0} is outside the language table.")]
+    StringOutOfBounds(u64),
+    #[error("Entry {id} at offset {offset} of the file table points past 4 GiB.")]
+    EntryOffsetOverflow { id: u32, offset: u64 },
+    #[error("Entry data of the package does not fit in 4 GiB.")]
+    DataTooLarge,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,20 +43,57 @@ pub struct PckHeader {
     pub bnk_entries: Vec<PckFileEntry>,
     pub wem_entries: Vec<PckFileEntry>,
     pub external_entries: Vec<u32>,
+    /// Byte order of the package, written back in the same order.
+    #[serde(default, skip_serializing_if = "Endian::is_little")]
+    pub endian: Endian,
     #[serde(skip)]
-    bnk_positions: Vec<u32>,
+    bnk_positions: Vec<DataOffset>,
     #[serde(skip)]
-    wem_positions: Vec<u32>,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum FileType {
-    Bnk,
-    Wem,
+    wem_positions: Vec<DataOffset>,
 }
 
 impl PckHeader {
+    /// Header of a new package, entry data is placed with [`PckFileEntry::set_data`].
+    pub fn new(
+        version: u32,
+        string_table: Vec<PckString>,
+        bnk_entries: Vec<PckFileEntry>,
+        wem_entries: Vec<PckFileEntry>,
+    ) -> Self {
+        Self {
+            header_length: 0,
+            version,
+            string_table,
+            bnk_entries,
+            wem_entries,
+            // count of the external table, always empty
+            external_entries: vec![0],
+            endian: Endian::Little,
+            bnk_positions: Vec::new(),
+            wem_positions: Vec::new(),
+        }
+    }
+
     pub fn from_reader<R>(reader: &mut R) -> Result<Self>
+    where
+        R: io::Read + io::Seek,
+    {
+        Self::read(reader, false)
+    }
+
+    /// Read the header of a possibly damaged package, skipping what cannot
+    /// be read with a warning.
+    ///
+    /// Unreadable strings are skipped, entry tables are cut at the first
+    /// unreadable entry, and entries with data outside the file are dropped.
+    pub fn from_reader_lenient<R>(reader: &mut R) -> Result<Self>
+    where
+        R: io::Read + io::Seek,
+    {
+        Self::read(reader, true)
+    }
+
+    fn read<R>(reader: &mut R, lenient: bool) -> Result<Self>
     where
         R: io::Read + io::Seek,
     {
@@ -48,12 +102,13 @@ impl PckHeader {
         if &magic != b"AKPK" {
             return Err(PckError::InvalidMagic(magic));
         }
-        let header_length = reader.read_u32::<LE>()?;
-        let version = reader.read_u32::<LE>()?;
-        let language_length = reader.read_u32::<LE>()?;
-        let bnk_table_length = reader.read_u32::<LE>()?;
-        let wem_table_length = reader.read_u32::<LE>()?;
-        let external_table_length = reader.read_u32::<LE>()?;
+        let endian = Endian::detect_at(reader, 4)?;
+        let header_length = endian.read_u32(reader)?;
+        let version = endian.read_u32(reader)?;
+        let language_length = endian.read_u32(reader)?;
+        let _bnk_table_length = endian.read_u32(reader)?;
+        let _wem_table_length = endian.read_u32(reader)?;
+        let external_table_length = endian.read_u32(reader)?;
 
         // read strings
         #[derive(Debug)]
@@ -62,18 +117,60 @@ impl PckHeader {
             index: u32,
         }
         let string_start_pos = reader.stream_position()?;
-        let string_count = reader.read_u32::<LE>()?;
+        let stream_length = reader.seek(io::SeekFrom::End(0))?;
+        reader.seek(io::SeekFrom::Start(string_start_pos))?;
+        let string_end_pos = string_start_pos + language_length as u64;
+        if string_end_pos > stream_length && !lenient {
+            return Err(PckError::InvalidTableLength {
+                table: "Language",
+                offset: string_start_pos,
+                length: language_length as u64,
+            });
+        }
+        let mut string_count = endian.read_u32(reader)?;
+        // count, then offset and index of each string
+        let max_string_count = (language_length.saturating_sub(4) / 8)
+            .min((stream_length.saturating_sub(string_start_pos + 4) / 8) as u32);
+        if string_count > max_string_count {
+            if !lenient {
+                return Err(PckError::InvalidTableLength {
+                    table: "Language",
+                    offset: string_start_pos,
+                    length: 4 + 8 * string_count as u64,
+                });
+            }
+            warn!(
+                "Language table lists {} names, only {} are read.",
+                string_count, max_string_count
+            );
+            string_count = max_string_count;
+        }
         let mut entries = Vec::with_capacity(string_count as usize);
         for _ in 0..string_count {
             entries.push(PckStringEntry {
-                offset: reader.read_u32::<LE>()?,
-                index: reader.read_u32::<LE>()?,
+                offset: endian.read_u32(reader)?,
+                index: endian.read_u32(reader)?,
             });
         }
         let mut string_table = Vec::with_capacity(string_count as usize);
         for entry in entries {
-            reader.seek(io::SeekFrom::Start(string_start_pos + entry.offset as u64))?;
-            let wstr = utils::string_from_utf16_reader(reader)?;
+            let position = string_start_pos + entry.offset as u64;
+            if position >= string_end_pos {
+                if !lenient {
+                    return Err(PckError::StringOutOfBounds(position));
+                }
+                warn!("Language {} name is out of bounds, skipped.", entry.index);
+                continue;
+            }
+            reader.seek(io::SeekFrom::Start(position))?;
+            let wstr = match utils::string_from_utf16_reader(reader, endian) {
+                Ok(wstr) => wstr,
+                Err(e) if lenient => {
+                    warn!("Language {} name is damaged, skipped: {}", entry.index, e);
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
             string_table.push(PckString {
                 index: entry.index,
                 value: wstr,
@@ -83,27 +180,70 @@ impl PckHeader {
             string_start_pos + language_length as u64,
         ))?;
 
-        let bnk_count = reader.read_u32::<LE>()?;
-        let mut bnk_entries = Vec::with_capacity(bnk_count as usize);
-        for _ in 0..bnk_count {
-            let mut buf = [0u8; 20];
-            reader.read_exact(&mut buf)?;
-            let entry: PckFileEntry = unsafe { std::mem::transmute(buf) };
-            bnk_entries.push(entry);
-        }
+        let mut truncated = false;
+        let mut read_table = |reader: &mut R, kind: &'static str| -> Result<Vec<PckFileEntry>> {
+            let mut entries = vec![];
+            if truncated {
+                return Ok(entries);
+            }
+            let offset = reader.stream_position()?;
+            let count = match endian.read_u32(reader) {
+                Ok(count) => count,
+                Err(e) if lenient => {
+                    warn!("{} table is damaged, skipped: {}", kind, e);
+                    truncated = true;
+                    return Ok(entries);
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let length = 4 + count as u64 * PckFileEntry::LENGTH;
+            if offset + length > stream_length && !lenient {
+                return Err(PckError::InvalidTableLength {
+                    table: kind,
+                    offset,
+                    length,
+                });
+            }
+            for i in 0..count {
+                match PckFileEntry::from_reader(reader, endian) {
+                    Ok(entry) => entries.push(entry),
+                    Err(e @ PckError::EntryOffsetOverflow { .. }) if lenient => {
+                        warn!("{} table entry {} of {} is skipped: {}", kind, i, count, e);
+                    }
+                    Err(e) if lenient => {
+                        warn!("{} table is cut at entry {} of {}: {}", kind, i, count, e);
+                        truncated = true;
+                        break;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(entries)
+        };
+        let bnk_entries = read_table(reader, "BNK")?;
+        let wem_entries = read_table(reader, "WEM")?;
 
-        let wem_count = reader.read_u32::<LE>()?;
-        let mut wem_entries = Vec::with_capacity(wem_count as usize);
-        for _ in 0..wem_count {
-            let mut buf = [0u8; 20];
-            reader.read_exact(&mut buf)?;
-            let entry: PckFileEntry = unsafe { std::mem::transmute(buf) };
-            wem_entries.push(entry);
+        let external_start_pos = reader.stream_position()?;
+        if external_start_pos + external_table_length as u64 > stream_length && !lenient {
+            return Err(PckError::InvalidTableLength {
+                table: "External",
+                offset: external_start_pos,
+                length: external_table_length as u64,
+            });
         }
-
-        let mut unk_struct_data = vec![0u32; external_table_length as usize / 4];
-        for i in 0..(external_table_length / 4) {
-            unk_struct_data[i as usize] = reader.read_u32::<LE>()?;
+        let external_count =
+            external_table_length.min(stream_length.saturating_sub(external_start_pos) as u32) / 4;
+        let mut unk_struct_data = vec![0u32; external_count as usize];
+        for i in 0..external_count {
+            match endian.read_u32(reader) {
+                Ok(value) => unk_struct_data[i as usize] = value,
+                Err(e) if lenient => {
+                    warn!("External table is damaged, skipped: {}", e);
+                    unk_struct_data.truncate(i as usize);
+                    break;
+                }
+                Err(e) => return Err(e.into()),
+            }
         }
 
         let mut header = PckHeader {
@@ -113,57 +253,88 @@ impl PckHeader {
             bnk_entries,
             wem_entries,
             external_entries: unk_struct_data,
+            endian,
             bnk_positions: Vec::new(),
             wem_positions: Vec::new(),
         };
 
-        header.calculate_file_positions();
+        header.calculate_file_positions()?;
+        if lenient {
+            header.drop_out_of_bounds(stream_length);
+        }
 
         Ok(header)
     }
 
-    fn calculate_file_positions(&mut self) {
-        let mut all_entries: Vec<(PckFileEntry, FileType)> = self
+    /// Positions are recalculated from entry lengths and alignments in the
+    /// order of entry offsets, instead of trusting the offsets themselves.
+    /// Entries sharing the same data are placed once.
+    fn calculate_file_positions(&mut self) -> Result<()> {
+        let mut all_entries: Vec<(DataOffset, ByteLen, u32)> = self
             .bnk_entries
             .iter()
-            .map(|e| (e.clone(), FileType::Bnk))
-            .chain(self.wem_entries.iter().map(|e| (e.clone(), FileType::Wem)))
+            .chain(self.wem_entries.iter())
+            .map(|e| (e.data_offset(), e.byte_len(), e.block_size()))
             .collect();
 
-        all_entries.sort_by_key(|(entry, _)| entry.offset);
-        
-        let mut sorted_positions = Vec::with_capacity(all_entries.len());
-        let mut current_pos = self.get_data_offset_start();
+        all_entries.sort_by_key(|(offset, _, _)| *offset);
+        all_entries.dedup_by_key(|(offset, length, _)| (*offset, *length));
 
-        for (entry, _) in &all_entries {
-            let alignment = entry.padding_block_size as u32;
+        let (sorted_positions, _) = layout::place_blocks(
+            self.get_data_offset_start(),
+            all_entries
+                .iter()
+                .map(|(_, length, block_size)| (*length, *block_size)),
+        )
+        .ok_or(PckError::DataTooLarge)?;
 
-            if alignment > 1 && current_pos % alignment != 0 {
-                current_pos += alignment - (current_pos % alignment);
-            }
-            
-            sorted_positions.push(current_pos);
-            current_pos += entry.length as u32;
-        }
-        
         let mut pos_map = std::collections::HashMap::new();
-        for (i, (entry, _)) in all_entries.iter().enumerate() {
-            pos_map.insert(entry.id, sorted_positions[i]);
+        for (i, (offset, length, _)) in all_entries.iter().enumerate() {
+            pos_map.insert((*offset, *length), sorted_positions[i]);
         }
 
-        self.bnk_positions = self.bnk_entries
-            .iter()
-            .map(|e| *pos_map.get(&e.id).unwrap_or(&0))
-            .collect();
-            
-        self.wem_positions = self.wem_entries
-            .iter()
-            .map(|e| *pos_map.get(&e.id).unwrap_or(&0))
-            .collect();
+        let position = |e: &PckFileEntry| {
+            pos_map
+                .get(&(e.data_offset(), e.byte_len()))
+                .copied()
+                .unwrap_or_default()
+        };
+        self.bnk_positions = self.bnk_entries.iter().map(position).collect();
+        self.wem_positions = self.wem_entries.iter().map(position).collect();
+        Ok(())
+    }
+
+    /// Drop entries with data past `file_length`, with a warning.
+    fn drop_out_of_bounds(&mut self, file_length: u64) {
+        let in_bounds = |kind: &str, entries: &[PckFileEntry], positions: &[DataOffset]| {
+            entries
+                .iter()
+                .zip(positions)
+                .enumerate()
+                .map(|(i, (entry, position))| {
+                    let end = position.file_position(0) + entry.length.get() as u64;
+                    let keep = end <= file_length;
+                    if !keep {
+                        warn!(
+                            "{} entry [{}] {} is out of bounds, skipped.",
+                            kind, i, entry.id
+                        );
+                    }
+                    keep
+                })
+                .collect::<Vec<bool>>()
+        };
+        let keep_bnk = in_bounds("BNK", &self.bnk_entries, &self.bnk_positions);
+        let keep_wem = in_bounds("WEM", &self.wem_entries, &self.wem_positions);
+        retain_by(&mut self.bnk_entries, &keep_bnk);
+        retain_by(&mut self.bnk_positions, &keep_bnk);
+        retain_by(&mut self.wem_entries, &keep_wem);
+        retain_by(&mut self.wem_positions, &keep_wem);
     }
 
-    pub fn get_data_offset_start(&self) -> u32 {
-        self.header_size() as u32 + 8 // 4 (magic) + 4 (header_length)
+    /// Offset of the first entry data, right after the header.
+    pub fn get_data_offset_start(&self) -> DataOffset {
+        DataOffset(self.header_size() as u32 + 8) // 4 (magic) + 4 (header_length)
     }
 
     pub fn wem_reader<'a, R>(&'a self, reader: R, index: usize) -> Option<PckFileReader<'a, R>>
@@ -174,9 +345,9 @@ impl PckHeader {
             return None;
         }
         let entry = &self.wem_entries[index];
-        let start_pos = self.wem_positions[index];
-        
-        Some(PckFileReader::new(reader, entry, u64::from(start_pos)))
+        let start_pos = self.wem_positions[index].file_position(0);
+
+        Some(PckFileReader::new(reader, entry, start_pos))
     }
 
     pub fn bnk_reader<'a, R>(&'a self, reader: R, index: usize) -> Option<PckFileReader<'a, R>>
@@ -187,36 +358,78 @@ impl PckHeader {
             return None;
         }
         let entry = &self.bnk_entries[index];
-        let start_pos = self.bnk_positions[index];
-        
-        Some(PckFileReader::new(reader, entry, u64::from(start_pos)))
+        let start_pos = self.bnk_positions[index].file_position(0);
+
+        Some(PckFileReader::new(reader, entry, start_pos))
+    }
+
+    /// ID of a language by its name in the string table, case insensitive.
+    pub fn language_id(&self, name: &str) -> Option<u32> {
+        self.string_table
+            .iter()
+            .find(|string| string.value.eq_ignore_ascii_case(name))
+            .map(|string| string.index)
+    }
+
+    /// All entries with the positions of their data, bnk entries first.
+    pub fn entries(&self) -> impl Iterator<Item = PckEntryInfo<'_>> {
+        let bnk = self
+            .bnk_entries
+            .iter()
+            .zip(&self.bnk_positions)
+            .enumerate()
+            .map(|(index, (entry, position))| PckEntryInfo {
+                kind: PckEntryKind::Bnk,
+                index,
+                entry,
+                position: position.file_position(0),
+            });
+        let wem = self
+            .wem_entries
+            .iter()
+            .zip(&self.wem_positions)
+            .enumerate()
+            .map(|(index, (entry, position))| PckEntryInfo {
+                kind: PckEntryKind::Wem,
+                index,
+                entry,
+                position: position.file_position(0),
+            });
+        bnk.chain(wem)
+    }
+
+    /// Entries of a language, see [`PckHeader::language_id`].
+    pub fn entries_for_language(&self, language_id: u32) -> impl Iterator<Item = PckEntryInfo<'_>> {
+        self.entries()
+            .filter(move |info| info.entry.language_id == language_id)
     }
 
     pub fn write_to<W>(&self, writer: &mut W) -> io::Result<()>
     where
         W: io::Write + io::Seek,
     {
+        let endian = self.endian;
         writer.write_all(b"AKPK")?;
-        writer.write_u32::<LE>(0)?; // header_length
-        writer.write_u32::<LE>(self.version)?;
-        writer.write_u32::<LE>(0)?; // language_length
-        writer.write_u32::<LE>(0)?; // bnk_table_length
-        writer.write_u32::<LE>(0)?; // wem_table_length
-        writer.write_u32::<LE>(0)?; // external_table_length
+        endian.write_u32(writer, 0)?; // header_length
+        endian.write_u32(writer, self.version)?;
+        endian.write_u32(writer, 0)?; // language_length
+        endian.write_u32(writer, 0)?; // bnk_table_length
+        endian.write_u32(writer, 0)?; // wem_table_length
+        endian.write_u32(writer, 0)?; // external_table_length
 
         // write strings
         let language_size = utils::calc_write_size(writer, |writer| {
-            writer.write_u32::<LE>(self.string_table.len() as u32)?; // string_count
+            endian.write_u32(writer, self.string_table.len() as u32)?; // string_count
             let mut utf16_strings = vec![];
             for string in &self.string_table {
-                utf16_strings.push(utils::string_to_utf16_bytes(&string.value));
+                utf16_strings.push(utils::string_to_utf16_bytes(&string.value, endian));
             }
             // calculate offsets and write string entries
             let mut offset = size_of::<u32>() + size_of::<u32>() * 2 * self.string_table.len();
             utf16_strings.iter().zip(&self.string_table).try_for_each(
                 |(utf16_bytes, pck_string)| -> io::Result<()> {
-                    writer.write_u32::<LE>(offset as u32)?;
-                    writer.write_u32::<LE>(pck_string.index)?;
+                    endian.write_u32(writer, offset as u32)?;
+                    endian.write_u32(writer, pck_string.index)?;
                     offset += utf16_bytes.len();
                     Ok(())
                 },
@@ -228,19 +441,17 @@ impl PckHeader {
             Ok(())
         })?;
 
-        writer.write_u32::<LE>(self.bnk_entries.len() as u32)?;
+        endian.write_u32(writer, self.bnk_entries.len() as u32)?;
         for entry in &self.bnk_entries {
-            let buf: [u8; 20] = unsafe { std::mem::transmute(entry.clone()) };
-            writer.write_all(&buf)?;
+            entry.write_to(writer, endian)?;
         }
 
-        writer.write_u32::<LE>(self.wem_entries.len() as u32)?;
+        endian.write_u32(writer, self.wem_entries.len() as u32)?;
         for entry in &self.wem_entries {
-            let buf: [u8; 20] = unsafe { std::mem::transmute(entry.clone()) };
-            writer.write_all(&buf)?;
+            entry.write_to(writer, endian)?;
         }
         for data in &self.external_entries {
-            writer.write_u32::<LE>(*data)?;
+            endian.write_u32(writer, *data)?;
         }
 
         let bnk_table_size = self.bnk_table_size();
@@ -254,12 +465,12 @@ impl PckHeader {
         let end_pos = writer.stream_position()?;
 
         writer.seek(io::SeekFrom::Start(4))?;
-        writer.write_u32::<LE>(header_size as u32)?;
+        endian.write_u32(writer, header_size as u32)?;
         writer.seek(io::SeekFrom::Current(4))?;
-        writer.write_u32::<LE>(language_size as u32)?;
-        writer.write_u32::<LE>(bnk_table_size as u32)?;
-        writer.write_u32::<LE>(wem_table_size as u32)?;
-        writer.write_u32::<LE>(unk_struct_size as u32)?;
+        endian.write_u32(writer, language_size as u32)?;
+        endian.write_u32(writer, bnk_table_size as u32)?;
+        endian.write_u32(writer, wem_table_size as u32)?;
+        endian.write_u32(writer, unk_struct_size as u32)?;
 
         writer.seek(io::SeekFrom::Start(end_pos))?;
 
@@ -275,12 +486,12 @@ impl PckHeader {
     }
 
     fn bnk_table_size(&self) -> usize {
-        4 + self.bnk_entries.len() * size_of::<PckFileEntry>()
+        4 + self.bnk_entries.len() * PckFileEntry::LENGTH as usize
     }
 
     fn wem_table_size(&self) -> usize {
         // entries_count(val) + entries_size
-        4 + self.wem_entries.len() * size_of::<PckFileEntry>()
+        4 + self.wem_entries.len() * PckFileEntry::LENGTH as usize
     }
 
     fn external_entries_size(&self) -> usize {
@@ -291,7 +502,7 @@ impl PckHeader {
         let mut size = 0;
         // strings size
         for string in &self.string_table {
-            size += utils::string_to_utf16_bytes(&string.value).len();
+            size += utils::string_to_utf16_bytes(&string.value, self.endian).len();
         }
         // entries size = count(val) + entry*count
         size += 4 + self.string_table.len() * 8;
@@ -299,27 +510,157 @@ impl PckHeader {
     }
 }
 
-#[repr(C)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "PckFileEntryRepr", into = "PckFileEntryRepr")]
 pub struct PckFileEntry {
     pub id: u32,
     pub padding_block_size: u32,
-    pub length: u32,
-    pub offset: u32,
+    pub length: ByteLen,
+    /// Offset of entry data from the start of file, a multiple of block size.
+    pub offset: DataOffset,
     pub language_id: u32,
 }
 
+/// PCK entry in `pck.json`, with offset in blocks as stored in the file table.
+#[derive(Serialize, Deserialize)]
+struct PckFileEntryRepr {
+    id: u32,
+    padding_block_size: u32,
+    length: u32,
+    offset: u32,
+    language_id: u32,
+}
+
+impl From<PckFileEntry> for PckFileEntryRepr {
+    fn from(entry: PckFileEntry) -> Self {
+        Self {
+            id: entry.id,
+            padding_block_size: entry.padding_block_size,
+            length: entry.length.get(),
+            offset: entry.offset.get() / entry.block_size(),
+            language_id: entry.language_id,
+        }
+    }
+}
+
+impl TryFrom<PckFileEntryRepr> for PckFileEntry {
+    type Error = String;
+
+    fn try_from(repr: PckFileEntryRepr) -> std::result::Result<Self, Self::Error> {
+        // offset and block size are untrusted, their product may not fit
+        let offset = repr
+            .offset
+            .checked_mul(repr.padding_block_size.max(1))
+            .ok_or_else(|| {
+                format!(
+                    "offset of entry {} is {} blocks of {} bytes, past 4 GiB",
+                    repr.id, repr.offset, repr.padding_block_size
+                )
+            })?;
+        Ok(Self {
+            id: repr.id,
+            padding_block_size: repr.padding_block_size,
+            length: ByteLen(repr.length),
+            offset: DataOffset(offset),
+            language_id: repr.language_id,
+        })
+    }
+}
+
+impl PckFileEntry {
+    /// Length of an entry in the file table.
+    const LENGTH: u64 = 20;
+
+    fn from_reader<R: io::Read + io::Seek>(reader: &mut R, endian: Endian) -> Result<Self> {
+        let offset = reader.stream_position()?;
+        let repr = PckFileEntryRepr {
+            id: endian.read_u32(reader)?,
+            padding_block_size: endian.read_u32(reader)?,
+            length: endian.read_u32(reader)?,
+            offset: endian.read_u32(reader)?,
+            language_id: endian.read_u32(reader)?,
+        };
+        let id = repr.id;
+        Self::try_from(repr).map_err(|_| PckError::EntryOffsetOverflow { id, offset })
+    }
+
+    fn write_to<W: io::Write>(&self, writer: &mut W, endian: Endian) -> io::Result<()> {
+        endian.write_u32(writer, self.id)?;
+        endian.write_u32(writer, self.padding_block_size)?;
+        endian.write_u32(writer, self.length.get())?;
+        endian.write_u32(writer, self.offset.get() / self.block_size())?;
+        endian.write_u32(writer, self.language_id)
+    }
+
+    /// Alignment of entry data, the file table stores `offset` in blocks of it.
+    pub fn block_size(&self) -> u32 {
+        self.padding_block_size.max(1)
+    }
+
+    pub fn byte_len(&self) -> ByteLen {
+        self.length
+    }
+
+    pub fn data_offset(&self) -> DataOffset {
+        self.offset
+    }
+
+    /// Set offset and length of entry data, `offset` must be aligned to block size.
+    pub fn set_data(&mut self, offset: DataOffset, length: ByteLen) {
+        debug_assert!(offset.get().is_multiple_of(self.block_size()));
+        self.offset = offset;
+        self.length = length;
+    }
+}
+
+/// Table of a PCK entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PckEntryKind {
+    Bnk,
+    Wem,
+}
+
+/// Entry of a PCK file with the absolute position of its data, which may
+/// differ from the offset stored in the entry.
+#[derive(Debug, Clone, Copy)]
+pub struct PckEntryInfo<'a> {
+    pub kind: PckEntryKind,
+    /// Index in the table of its kind.
+    pub index: usize,
+    pub entry: &'a PckFileEntry,
+    /// Position of entry data from the start of file.
+    pub position: u64,
+}
+
+impl<'a> PckEntryInfo<'a> {
+    /// Reader of the entry data in the PCK file.
+    pub fn reader<R>(&self, reader: R) -> PckFileReader<'a, R>
+    where
+        R: io::Read + io::Seek,
+    {
+        PckFileReader::new(reader, self.entry, self.position)
+    }
+}
+
+/// Keep items whose flag at the same index is set.
+fn retain_by<T>(items: &mut Vec<T>, keep: &[bool]) {
+    let mut keep = keep.iter();
+    items.retain(|_| *keep.next().unwrap());
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PckString {
     pub index: u32,
     pub value: String,
 }
 
+/// Reader of entry data in a PCK file, borrowing the entry from its header.
+///
+/// Positions of [`io::Seek`] are relative to the entry data.
+/// See [`OwnedPckFileReader`] for a reader not tied to the header.
 pub struct PckFileReader<'a, R> {
-    reader: R,
+    inner: OwnedPckFileReader<R>,
     entry: &'a PckFileEntry,
-    start_pos: u64,
-    read_size: usize,
 }
 
 impl<'a, R> PckFileReader<'a, R>
@@ -328,12 +669,18 @@ where
 {
     fn new(reader: R, entry: &'a PckFileEntry, start_pos: u64) -> Self {
         PckFileReader {
-            reader,
+            inner: OwnedPckFileReader::new(reader, start_pos, entry.length.get() as u64),
             entry,
-            start_pos,
-            read_size: 0,
         }
     }
+
+    pub fn entry(&self) -> &'a PckFileEntry {
+        self.entry
+    }
+
+    pub fn into_owned(self) -> OwnedPckFileReader<R> {
+        self.inner
+    }
 }
 
 impl<R> io::Read for PckFileReader<'_, R>
@@ -341,31 +688,155 @@ where
     R: io::Read + io::Seek,
 {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        if self.read_size == 0 && self.entry.length > 0 {
-            self.reader.seek(io::SeekFrom::Start(self.start_pos))?;
+        self.inner.read(buf)
+    }
+}
+
+impl<R> io::Seek for PckFileReader<'_, R>
+where
+    R: io::Read + io::Seek,
+{
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl<R> io::BufRead for PckFileReader<'_, R>
+where
+    R: io::BufRead + io::Seek,
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
+
+/// Reader of a byte range of a PCK file, e.g. entry data.
+///
+/// The inner reader is positioned lazily on first read after creation or
+/// seek, and assumed to stay there, so it must not be moved by others
+/// in between, e.g. by sharing it with another reader.
+pub struct OwnedPckFileReader<R> {
+    reader: R,
+    start_pos: u64,
+    length: u64,
+    /// Position relative to `start_pos`.
+    pos: u64,
+    /// Whether the inner reader is at `start_pos + pos`.
+    synced: bool,
+}
+
+impl<R> OwnedPckFileReader<R>
+where
+    R: io::Read + io::Seek,
+{
+    /// Reader of `length` bytes at `start_pos` of the file.
+    pub fn new(reader: R, start_pos: u64, length: u64) -> Self {
+        OwnedPckFileReader {
+            reader,
+            start_pos,
+            length,
+            pos: 0,
+            synced: false,
         }
-        
-        let available = self.entry.length as usize - self.read_size;
-        if available == 0 {
-            return Ok(0);
+    }
+
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn remaining(&self) -> u64 {
+        self.length.saturating_sub(self.pos)
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        if !self.synced {
+            self.reader
+                .seek(io::SeekFrom::Start(self.start_pos + self.pos))?;
+            self.synced = true;
         }
-        
-        let read_limit = buf.len().min(available);
+        Ok(())
+    }
+}
+
+impl<R> io::Read for OwnedPckFileReader<R>
+where
+    R: io::Read + io::Seek,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read_limit = (buf.len() as u64).min(self.remaining()) as usize;
         if read_limit == 0 {
-            return Ok(0); 
+            return Ok(0);
         }
-
+        self.sync()?;
         let bytes_read = self.reader.read(&mut buf[..read_limit])?;
-        self.read_size += bytes_read;
+        self.pos += bytes_read as u64;
         Ok(bytes_read)
     }
 }
 
+impl<R> io::Seek for OwnedPckFileReader<R>
+where
+    R: io::Read + io::Seek,
+{
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            io::SeekFrom::Start(offset) => Some(offset),
+            io::SeekFrom::End(offset) => self.length.checked_add_signed(offset),
+            io::SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        let Some(new_pos) = new_pos else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek to a negative or overflowing position",
+            ));
+        };
+        if new_pos != self.pos {
+            self.pos = new_pos;
+            self.synced = false;
+        }
+        Ok(self.pos)
+    }
+}
+
+impl<R> io::BufRead for OwnedPckFileReader<R>
+where
+    R: io::BufRead + io::Seek,
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let remaining = self.remaining();
+        if remaining == 0 {
+            return Ok(&[]);
+        }
+        self.sync()?;
+        let buf = self.reader.fill_buf()?;
+        let available = (buf.len() as u64).min(remaining) as usize;
+        Ok(&buf[..available])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let amt = (amt as u64).min(self.remaining());
+        self.reader.consume(amt as usize);
+        self.pos += amt;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
         fs,
-        io::{Cursor, Read},
+        io::{BufRead, Cursor, Read, Seek},
     };
 
     use super::*;
@@ -383,14 +854,181 @@ mod tests {
         assert_eq!(pck.wem_table_size(), 6664);
         assert_eq!(pck.external_entries_size(), 4);
         assert_eq!(pck.header_size(), 6712);
-        assert_eq!(pck.get_data_offset_start(), 6720);
+        assert_eq!(pck.get_data_offset_start(), DataOffset(6720));
         // eprintln!("pck: {:?}", pck);
         for i in 0..pck.wem_entries.len() {
             let mut wem_reader = pck.wem_reader(Cursor::new(&mut input), i).unwrap();
             let mut buf = vec![];
             wem_reader.read_to_end(&mut buf).unwrap();
-            assert_eq!(buf.len(), pck.wem_entries[i].length as usize);
+            assert_eq!(buf.len(), pck.wem_entries[i].length.as_usize());
             assert_eq!(&buf[0..4], b"RIFF");
+            assert_eq!(pck.wem_entries[i].data_offset(), pck.wem_positions[i]);
         }
+
+        // `pck.json` keeps offsets in blocks, like the file table
+        let entry = PckFileEntry {
+            id: 1,
+            padding_block_size: 16,
+            length: ByteLen(10),
+            offset: DataOffset(32),
+            language_id: 0,
+        };
+        let json = serde_json::to_value(&entry).unwrap();
+        assert_eq!(json["offset"], 2);
+        let parsed: PckFileEntry = serde_json::from_value(json).unwrap();
+        assert_eq!((parsed.offset, parsed.length), (entry.offset, entry.length));
+    }
+
+    #[test]
+    fn test_pck_file_reader_seek() {
+        let input = fs::read(INPUT).unwrap();
+        let pck = PckHeader::from_reader(&mut Cursor::new(&input)).unwrap();
+        let entry = &pck.wem_entries[1];
+        let start = pck.wem_positions[1].file_position(0) as usize;
+        let data = &input[start..start + entry.length.as_usize()];
+
+        let mut reader = pck
+            .wem_reader(io::BufReader::new(Cursor::new(&input)), 1)
+            .unwrap();
+        assert_eq!(reader.entry().id, entry.id);
+        let mut head = [0; 4];
+        reader.read_exact(&mut head).unwrap();
+        assert_eq!(&head, b"RIFF");
+        assert_eq!(
+            reader.seek(io::SeekFrom::End(-4)).unwrap(),
+            data.len() as u64 - 4
+        );
+        let mut tail = vec![];
+        reader.read_to_end(&mut tail).unwrap();
+        assert_eq!(tail, &data[data.len() - 4..]);
+        assert!(
+            reader
+                .seek(io::SeekFrom::Current(-(data.len() as i64) - 1))
+                .is_err()
+        );
+
+        // owned reader outlives the header, and reads lines within entry bounds
+        let mut owned = reader.into_owned();
+        drop(pck);
+        owned.seek(io::SeekFrom::Start(0)).unwrap();
+        let mut all = vec![];
+        while !owned.fill_buf().unwrap().is_empty() {
+            let buf = owned.fill_buf().unwrap();
+            all.extend_from_slice(buf);
+            let len = buf.len();
+            owned.consume(len);
+        }
+        assert_eq!(all, data);
+        assert_eq!(owned.len(), data.len() as u64);
+    }
+
+    #[test]
+    fn test_pck_lenient() {
+        let input = fs::read(INPUT).unwrap();
+        let pck = PckHeader::from_reader(&mut Cursor::new(&input)).unwrap();
+        let last = pck.wem_entries.len() - 1;
+        let truncated = &input[..pck.wem_positions[last].file_position(0) as usize + 1];
+        let salvaged = PckHeader::from_reader_lenient(&mut Cursor::new(truncated)).unwrap();
+        assert_eq!(salvaged.wem_entries.len(), last);
+        assert_eq!(salvaged.wem_positions.len(), last);
+
+        // wem table cut in the middle, before any of the data
+        let table_start = pck.header_size() as usize + 8
+            - pck.wem_table_size() as usize
+            - pck.external_entries_size() as usize;
+        let truncated = &input[..table_start + 4 + 20 * 10 + 7];
+        assert!(PckHeader::from_reader(&mut Cursor::new(truncated)).is_err());
+        let salvaged = PckHeader::from_reader_lenient(&mut Cursor::new(truncated)).unwrap();
+        assert_eq!(salvaged.wem_entries.len(), 0);
+    }
+
+    #[test]
+    fn test_pck_malformed() {
+        let input = fs::read(INPUT).unwrap();
+        let pck = PckHeader::from_reader(&mut Cursor::new(&input)).unwrap();
+        let language_start = 28;
+        let bnk_table_start = language_start + pck.language_size();
+        let wem_table_start = bnk_table_start + pck.bnk_table_size();
+
+        // wem count larger than the file
+        let mut crafted = input.clone();
+        crafted[wem_table_start..wem_table_start + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let err = PckHeader::from_reader(&mut Cursor::new(&crafted)).unwrap_err();
+        assert!(matches!(
+            err,
+            PckError::InvalidTableLength { table: "WEM", offset, .. } if offset == wem_table_start as u64
+        ));
+
+        // language name outside the language table
+        let mut crafted = input.clone();
+        crafted[language_start + 4..language_start + 8].copy_from_slice(&1000u32.to_le_bytes());
+        assert!(matches!(
+            PckHeader::from_reader(&mut Cursor::new(&crafted)),
+            Err(PckError::StringOutOfBounds(1028))
+        ));
+        let salvaged = PckHeader::from_reader_lenient(&mut Cursor::new(&crafted)).unwrap();
+        assert!(salvaged.string_table.is_empty());
+        assert_eq!(salvaged.wem_entries.len(), pck.wem_entries.len());
+    }
+
+    #[test]
+    fn test_pck_offset_overflow() {
+        let entry = PckFileEntry {
+            id: 1,
+            padding_block_size: 0x10000,
+            length: ByteLen(4),
+            offset: DataOffset::ZERO,
+            language_id: 0,
+        };
+        let header = PckHeader::new(1, vec![], vec![], vec![entry]);
+        let mut writer = Cursor::new(vec![]);
+        header.write_to(&mut writer).unwrap();
+        let input = writer.into_inner();
+        // the only entry, followed by the external table count
+        let entry_start = input.len() - 4 - PckFileEntry::LENGTH as usize;
+
+        // 0x10000 blocks of 0x10000 bytes
+        let mut crafted = input.clone();
+        crafted[entry_start + 12..entry_start + 16].copy_from_slice(&0x10000u32.to_le_bytes());
+        assert!(matches!(
+            PckHeader::from_reader(&mut Cursor::new(&crafted)),
+            Err(PckError::EntryOffsetOverflow { id: 1, offset }) if offset == entry_start as u64
+        ));
+        let salvaged = PckHeader::from_reader_lenient(&mut Cursor::new(&crafted)).unwrap();
+        assert!(salvaged.wem_entries.is_empty());
+
+        // data placed after the header runs past 4 GiB
+        let mut crafted = input.clone();
+        crafted[entry_start + 8..entry_start + 12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            PckHeader::from_reader(&mut Cursor::new(&crafted)),
+            Err(PckError::DataTooLarge)
+        ));
+    }
+
+    #[test]
+    fn test_entries_for_language() {
+        let input = fs::read(INPUT).unwrap();
+        let mut pck = PckHeader::from_reader(&mut Cursor::new(&input)).unwrap();
+        let sfx = pck.language_id("SFX").unwrap();
+        assert_eq!(pck.language_id("klingon"), None);
+        assert_eq!(pck.entries_for_language(sfx).count(), 333);
+        assert_eq!(pck.entries_for_language(sfx + 1).count(), 0);
+
+        // positions are the computed ones, not the stored offsets
+        pck.wem_entries[1].language_id = sfx + 1;
+        let block_size = pck.wem_entries[1].block_size();
+        pck.wem_entries[1].offset += ByteLen(block_size);
+        let other: Vec<_> = pck.entries_for_language(sfx + 1).collect();
+        assert_eq!(other.len(), 1);
+        assert_eq!((other[0].kind, other[0].index), (PckEntryKind::Wem, 1));
+        assert_eq!(other[0].position, pck.wem_positions[1].file_position(0));
+        let mut data = vec![];
+        other[0]
+            .reader(Cursor::new(&input))
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(pck.entries_for_language(sfx).count(), 332);
     }
 }
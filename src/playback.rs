@@ -0,0 +1,30 @@
+//! Audio playback for auditioning sounds, built with the `playback` feature.
+
+use std::{io::Cursor, time::Duration};
+
+use rodio::{Decoder, OutputStreamBuilder, Sink, Source, decoder::DecoderError};
+
+type Result<T> = std::result::Result<T, PlaybackError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PlaybackError {
+    #[error("Failed to open audio output: {0}")]
+    Stream(#[from] rodio::StreamError),
+    #[error("Failed to decode audio: {0}")]
+    Decode(#[from] DecoderError),
+}
+
+/// Play wav file data on the default output device, blocks until it ends.
+///
+/// Returns the duration of the sound, if known.
+pub fn play_wav(wav_data: Vec<u8>) -> Result<Option<Duration>> {
+    let mut stream = OutputStreamBuilder::open_default_stream()?;
+    // dropping the stream at the end is expected, not worth a warning
+    stream.log_on_drop(false);
+    let source = Decoder::new(Cursor::new(wav_data))?;
+    let duration = source.total_duration();
+    let sink = Sink::connect_new(stream.mixer());
+    sink.append(source);
+    sink.sleep_until_end();
+    Ok(duration)
+}
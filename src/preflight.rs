@@ -0,0 +1,133 @@
+use crate::wav;
+
+type Result<T> = std::result::Result<T, PreflightError>;
+
+/// Sample magnitude regarded as clipped.
+const CLIP_THRESHOLD: f32 = 0.999;
+/// DC offset magnitude worth a warning.
+const DC_OFFSET_THRESHOLD: f32 = 0.01;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PreflightError {
+    #[error("Failed to read wav data: {0}")]
+    Wav(#[from] hound::Error),
+}
+
+/// Level analysis result of a wav file.
+#[derive(Debug, Clone, Default)]
+pub struct WavReport {
+    /// Absolute peak sample value, 1.0 is full scale.
+    pub peak: f32,
+    /// Count of samples at or above full scale.
+    pub clipped_samples: usize,
+    /// Largest DC offset of all channels.
+    pub dc_offset: f32,
+    /// RMS level of all samples.
+    pub rms: f32,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl WavReport {
+    pub fn peak_db(&self) -> f32 {
+        to_db(self.peak)
+    }
+
+    pub fn rms_db(&self) -> f32 {
+        to_db(self.rms)
+    }
+
+    pub fn is_clipping(&self) -> bool {
+        self.clipped_samples > 0
+    }
+
+    pub fn has_dc_offset(&self) -> bool {
+        self.dc_offset.abs() >= DC_OFFSET_THRESHOLD
+    }
+}
+
+/// Analyze wav file data for clipping and DC offset.
+pub fn analyze_wav(wav_data: &[u8]) -> Result<WavReport> {
+    let (spec, samples) = wav::read_samples(wav_data)?;
+    let channels = spec.channels.max(1) as usize;
+    let mut report = WavReport {
+        sample_rate: spec.sample_rate,
+        channels: spec.channels,
+        ..Default::default()
+    };
+    if samples.is_empty() {
+        return Ok(report);
+    }
+
+    let mut channel_sums = vec![0f64; channels];
+    let mut square_sum = 0f64;
+    for (i, &sample) in samples.iter().enumerate() {
+        let magnitude = sample.abs();
+        report.peak = report.peak.max(magnitude);
+        if magnitude >= CLIP_THRESHOLD {
+            report.clipped_samples += 1;
+        }
+        channel_sums[i % channels] += sample as f64;
+        square_sum += (sample as f64) * (sample as f64);
+    }
+    let frames = (samples.len() / channels).max(1) as f64;
+    report.dc_offset =
+        channel_sums
+            .iter()
+            .map(|sum| (sum / frames) as f32)
+            .fold(0f32, |max, offset| {
+                if offset.abs() > max.abs() {
+                    offset
+                } else {
+                    max
+                }
+            });
+    report.rms = (square_sum / samples.len() as f64).sqrt() as f32;
+
+    Ok(report)
+}
+
+fn to_db(value: f32) -> f32 {
+    20.0 * value.max(1e-9).log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    fn make_wav(samples: &[i16]) -> Vec<u8> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut cursor = io::Cursor::new(vec![]);
+        let mut writer = hound::WavWriter::new(&mut cursor, spec).unwrap();
+        for &s in samples {
+            writer.write_sample(s).unwrap();
+        }
+        writer.finalize().unwrap();
+        cursor.into_inner()
+    }
+
+    #[test]
+    fn test_analyze_clipping() {
+        let wav = make_wav(&[0, 16384, i16::MAX, i16::MIN, -16384, 0]);
+        let report = analyze_wav(&wav).unwrap();
+        assert!(report.is_clipping());
+        assert_eq!(report.clipped_samples, 2);
+        assert!(report.peak_db() > -0.01);
+        assert!(!report.has_dc_offset());
+    }
+
+    #[test]
+    fn test_analyze_dc_offset() {
+        let wav = make_wav(&[3000; 100]);
+        let report = analyze_wav(&wav).unwrap();
+        assert!(!report.is_clipping());
+        assert!(report.has_dc_offset());
+    }
+}
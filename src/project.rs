@@ -1,22 +1,269 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     fs::{self, File},
-    io::{self, Write, Seek},
+    io::{self, Read, Seek, Write},
+    mem,
     path::{Path, PathBuf},
     sync::LazyLock,
+    time::{Duration, Instant, UNIX_EPOCH},
 };
 
 use colored::Colorize;
-use eyre::Context;
 use indexmap::IndexMap;
-use log::{info, warn};
+use log::{debug, info, warn};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-use crate::{bnk, pck, transcode};
+use crate::{
+    bnk, deploy,
+    detect::InputFileType,
+    fingerprint::{BundleChange, BundleFingerprint, FingerprintError},
+    formats::{self, EntryFormats, FormatsError},
+    hashes::{self, HashesError},
+    hirc,
+    idmap::{Align, IdMap, IdMapError},
+    labels::{self, LabelFormat},
+    layout::{self, ByteLen, DataOffset},
+    lock::{LockError, ProjectLock},
+    manifest::{ManifestError, OutputTimestamp, ProjectManifest, SizePolicy},
+    modinfo::{self, Embed, ModInfo, ModInfoError},
+    names::{self, NameList, REFERENCED_KEY},
+    order::{self, EntryOrder, OrderError},
+    pck,
+    rules::{RuleAction, RuleSet, RulesError},
+    store::{self, MediaStore, MediaStoreWriter, StoreError},
+    transcode::{self, DecodeFormat, TranscodeError, WemConversion},
+    utils,
+    wwise::{self, Conversion, WemFormat},
+};
+
+type Result<T> = std::result::Result<T, ProjectError>;
+
+/// Folder of decoded playable files in project folder.
+pub const DECODED_DIR: &str = "decoded";
+/// Folder of new entries in project folder, see [`load_additions`].
+pub const ADD_DIR: &str = "add";
+/// Folder of banks extracted from a PCK project and their nested projects,
+/// see [`SoundToolProject::dump_nested_banks`].
+pub const BANKS_DIR: &str = "banks";
+/// Suffix of the replaced bundle kept by [`OutputPolicy::Overwrite`].
+pub const BACKUP_SUFFIX: &str = ".bak";
+const STAGING_SUFFIX: &str = ".writing";
 
 // [001]12345678
 static REG_WEM_NAME: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\[(\d+)\](\d+)").unwrap());
+static REG_NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\d+").unwrap());
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProjectError {
+    #[error("Project IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("Failed to access {}: {source}", path.display())]
+    File { path: PathBuf, source: io::Error },
+    #[error("Metadata file not found: {}", .0.display())]
+    MissingMetadata(PathBuf),
+    #[error("Failed to parse metadata file {}: {source}", path.display())]
+    BadMetadata {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error("Failed to write metadata: {0}")]
+    WriteMetadata(#[from] serde_json::Error),
+    #[error("Failed to parse bnk file: {0}")]
+    Bnk(#[from] bnk::BnkError),
+    #[error("Failed to parse pck file: {0}")]
+    Pck(#[from] pck::PckError),
+    #[error("{0}")]
+    Manifest(#[from] ManifestError),
+    #[error("{0}")]
+    Store(#[from] StoreError),
+    #[error("{0}")]
+    Lock(#[from] LockError),
+    #[error("{0}")]
+    IdMap(#[from] IdMapError),
+    #[error("Failed to fingerprint bundle: {0}")]
+    Fingerprint(#[from] FingerprintError),
+    #[error("Failed to write mod info: {0}")]
+    ModInfo(#[from] ModInfoError),
+    #[error("Failed to write hashes: {0}")]
+    Hashes(#[from] HashesError),
+    #[error("{0}")]
+    Rules(#[from] RulesError),
+    #[error("{0}")]
+    Order(#[from] OrderError),
+    #[error("{0}")]
+    Formats(#[from] FormatsError),
+    #[error("Failed to apply preset {preset}: {source}")]
+    Preset {
+        preset: String,
+        source: hirc::HircError,
+    },
+    #[error("Failed to edit props of {edit}: {source}")]
+    Props {
+        edit: String,
+        source: hirc::HircError,
+    },
+    #[error("Bad file name: {0}")]
+    BadFileName(String),
+    #[error("Replace file {0} does not match any entry in the project.")]
+    UnmatchedReplacement(IdOrIndex),
+    #[error("Added file {0} must be named by ID.")]
+    AdditionByIndex(IdOrIndex),
+    #[error("Added file '{0}' exists in the bundle, put it in replace folder instead.")]
+    AdditionExists(u32),
+    #[error("Language '{0}' of added files not found in the bundle.")]
+    UnknownLanguage(String),
+    #[error("Failed to transcode replace files: {0}")]
+    Transcode(#[from] TranscodeError),
+    #[error("{0}")]
+    Decode(TranscodeError),
+    #[error("Unsupported input file type: {}", .0.display())]
+    UnsupportedInput(PathBuf),
+    #[error("Entry {target} not found in {}", path.display())]
+    EntryNotFound { target: IdOrIndex, path: PathBuf },
+    #[error("Language '{language}' not found in {}", path.display())]
+    LanguageNotFound { language: String, path: PathBuf },
+    #[error("Bundle not found in or next to project: {}", .0.display())]
+    BundleNotFound(PathBuf),
+    #[error("Failed to copy entry for {target} from {}: {source}", bundle.display())]
+    CopyFromBundle {
+        target: IdOrIndex,
+        bundle: PathBuf,
+        source: Box<ProjectError>,
+    },
+    #[error("Internal: no data for entry [{0}]")]
+    MissingEntryData(u32),
+    #[error("Cannot rebase {kind} project onto {}", source_path.display())]
+    RebaseMismatch {
+        kind: &'static str,
+        source_path: PathBuf,
+    },
+    #[error("Project already exists: {}", .0.display())]
+    ProjectExists(PathBuf),
+    #[error(
+        "Replacement for {target} is {size} bytes, larger than the original {original_size} bytes."
+    )]
+    ReplacementTooLarge {
+        target: IdOrIndex,
+        size: usize,
+        original_size: usize,
+    },
+    #[error("No random or sequence container found for variations target {0}.")]
+    NoContainer(u32),
+    #[error("Failed to find container of variations target {target}: {source}")]
+    Variations {
+        target: u32,
+        source: hirc::HircError,
+    },
+    #[error("Written bundle {} failed verification, not placed: {reason}", path.display())]
+    OutputCheck { path: PathBuf, reason: String },
+}
+
+impl ProjectError {
+    /// Attach the path to an IO error.
+    fn file(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| ProjectError::File { path, source }
+    }
+}
+
+/// How the output bundle is named if a file of its name exists in output root.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum OutputPolicy {
+    /// Append `.new` until the name is free.
+    #[default]
+    Rename,
+    /// Replace the existing file. It is kept as `<name>.bak` first, an existing
+    /// backup is never replaced, so it stays the original bundle.
+    Overwrite,
+    /// Write to the source file name with a suffix, replacing a previous output.
+    Suffix(String),
+}
+
+impl OutputPolicy {
+    /// Path of the output bundle named `file_name` under `output_root`.
+    pub fn output_path(&self, output_root: &Path, file_name: &str) -> PathBuf {
+        match self {
+            OutputPolicy::Rename => {
+                let mut output_path = output_root.join(file_name).into_os_string();
+                while Path::new(&output_path).exists() {
+                    output_path.push(".new");
+                }
+                PathBuf::from(output_path)
+            }
+            OutputPolicy::Overwrite => output_root.join(file_name),
+            OutputPolicy::Suffix(suffix) => output_root.join(format!("{}{}", file_name, suffix)),
+        }
+    }
+
+    /// Backup of the replaced file, only kept by [`OutputPolicy::Overwrite`].
+    pub fn backup_path(&self, output_path: &Path) -> Option<PathBuf> {
+        (*self == OutputPolicy::Overwrite).then(|| with_suffix(output_path, BACKUP_SUFFIX))
+    }
+
+    /// Path the output is written to before moved into place, so a replaced
+    /// bundle stays readable while writing.
+    fn staging_path(&self, output_path: &Path) -> PathBuf {
+        with_suffix(output_path, STAGING_SUFFIX)
+    }
+
+    /// Staging file of an output, removed unless it is placed.
+    fn stage<'a>(&'a self, output_path: &'a Path) -> StagedOutput<'a> {
+        StagedOutput {
+            policy: self,
+            output_path,
+            path: self.staging_path(output_path),
+            placed: false,
+        }
+    }
+
+    /// Move the written output from staging path into place.
+    fn place(&self, output_path: &Path) -> Result<()> {
+        if let Some(backup) = self.backup_path(output_path)
+            && output_path.is_file()
+            && !backup.exists()
+        {
+            fs::rename(output_path, &backup).map_err(ProjectError::file(output_path))?;
+            info!("{}: {}", "Backup".cyan(), backup.display());
+        }
+        fs::rename(self.staging_path(output_path), output_path)
+            .map_err(ProjectError::file(output_path))
+    }
+}
+
+/// Output written to its staging path, see [`OutputPolicy::stage`].
+///
+/// Removed when dropped before it is placed, so a failed write leaves no
+/// staging file behind.
+struct StagedOutput<'a> {
+    policy: &'a OutputPolicy,
+    output_path: &'a Path,
+    path: PathBuf,
+    placed: bool,
+}
+
+impl StagedOutput<'_> {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Move the output into place, see [`OutputPolicy::place`].
+    fn place(mut self) -> Result<()> {
+        self.policy.place(self.output_path)?;
+        self.placed = true;
+        Ok(())
+    }
+}
+
+impl Drop for StagedOutput<'_> {
+    fn drop(&mut self) {
+        if !self.placed {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SoundToolProject {
@@ -25,44 +272,166 @@ pub enum SoundToolProject {
 }
 
 impl SoundToolProject {
-    pub fn from_path(path: impl AsRef<Path>) -> eyre::Result<Self> {
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
         let project_path = path.as_ref();
 
         let project_json_path = project_path.join("project.json");
         if !project_json_path.is_file() {
-            eyre::bail!(
-                "Project metadata file not found: {}",
-                project_json_path.display()
-            )
+            return Err(ProjectError::MissingMetadata(project_json_path));
         }
-        let project_content = fs::read_to_string(project_json_path)
-            .context("Failed to read project metadata file")?;
+        let project_content = fs::read_to_string(&project_json_path)
+            .map_err(ProjectError::file(&project_json_path))?;
         let mut project: SoundToolProject =
-            serde_json::from_str(&project_content).context("Failed to parse project data")?;
+            serde_json::from_str(&project_content).map_err(|source| ProjectError::BadMetadata {
+                path: project_json_path,
+                source,
+            })?;
         project.set_project_path(project_path);
+        project.resolve_source_path();
 
         Ok(project)
     }
 
-    pub fn repack(&self, output_root: impl AsRef<Path>) -> eyre::Result<()> {
-        match self {
-            SoundToolProject::Bnk(project) => project.repack(output_root),
-            SoundToolProject::Pck(project) => project.repack(output_root),
+    pub fn repack(&self, output_root: impl AsRef<Path>) -> Result<RepackStats> {
+        self.repack_with(output_root, &OutputPolicy::default())
+    }
+
+    /// Repack with an output naming policy.
+    pub fn repack_with(
+        &self,
+        output_root: impl AsRef<Path>,
+        policy: &OutputPolicy,
+    ) -> Result<RepackStats> {
+        self.repack_with_rules(output_root, policy, &RuleSet::default())
+    }
+
+    /// Repack with an output naming policy, applying user level replace
+    /// rules to entries the project leaves unchanged.
+    pub fn repack_with_rules(
+        &self,
+        output_root: impl AsRef<Path>,
+        policy: &OutputPolicy,
+        rules: &RuleSet,
+    ) -> Result<RepackStats> {
+        let output_root = output_root.as_ref();
+        fs::create_dir_all(output_root).map_err(ProjectError::file(output_root))?;
+        let _lock = ProjectLock::acquire(self.project_path())?;
+        self.check_target_bundle(output_root.join(self.source_file_name()))?;
+        let mut stats = match self {
+            SoundToolProject::Bnk(project) => project.repack(output_root, policy, rules)?,
+            SoundToolProject::Pck(project) => project.repack(output_root, policy, rules)?,
+        };
+
+        // compare with the original bundle, backed up, next to output or project
+        let backup = policy.backup_path(&stats.output_path);
+        let original = [Some(output_root), self.project_path().parent()]
+            .into_iter()
+            .flatten()
+            .map(|dir| dir.join(self.source_file_name()))
+            // written to the source file name if it was free in output root
+            .filter(|path| *path != stats.output_path)
+            .chain(backup)
+            .find(|path| path.is_file());
+        if let Some(original) = original {
+            let original_ids = entry_ids(&original)?;
+            stats.added = Some(
+                stats
+                    .entry_ids
+                    .iter()
+                    .filter(|id| !original_ids.contains(id))
+                    .count(),
+            );
+            stats.removed = Some(
+                original_ids
+                    .iter()
+                    .filter(|id| !stats.entry_ids.contains(id))
+                    .count(),
+            );
+            stats.original_size = Some(fs::metadata(&original)?.len());
+        } else if let Some(fingerprint) = self.source_fingerprint() {
+            stats.original_size = Some(fingerprint.size);
+        }
+
+        let manifest = ProjectManifest::load(self.project_path())?;
+        if let Some(config) = &manifest.modinfo {
+            let embedded = config.embed == Embed::Section && matches!(self, Self::Bnk(_));
+            let mut outputs = vec![stats.output_path.clone()];
+            if !embedded {
+                if config.embed == Embed::Section {
+                    warn!("Mod info can only be embedded in BNK, written as sidecar instead.");
+                }
+                let modinfo = ModInfo::new(config, self.project_path())?;
+                let path = modinfo.write_sidecar(&stats.output_path)?;
+                info!("{}: {}", "ModInfo".cyan(), path.display());
+                outputs.push(path);
+            }
+            if config.hashes {
+                let path = hashes::update_hashes(output_root, &outputs)?;
+                info!("{}: {}", "Hashes".cyan(), path.display());
+            }
         }
+        self.set_output_timestamp(&stats.output_path, manifest.timestamp)?;
+
+        Ok(stats)
+    }
+
+    /// Set the modified time of the output bundle by project manifest.
+    fn set_output_timestamp(&self, output_path: &Path, timestamp: OutputTimestamp) -> Result<()> {
+        let secs = match timestamp {
+            OutputTimestamp::Now => return Ok(()),
+            OutputTimestamp::Fixed(secs) => secs,
+            // the source bundle may have been overwritten by the output,
+            // so its time recorded on dump is preferred
+            OutputTimestamp::Original => {
+                let recorded = self.source_fingerprint().and_then(|f| f.modified);
+                let source = || {
+                    let path = self.source_path().filter(|path| *path != output_path)?;
+                    let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+                    modified
+                        .duration_since(UNIX_EPOCH)
+                        .ok()
+                        .map(|d| d.as_secs())
+                };
+                let Some(secs) = recorded.or_else(source) else {
+                    warn!(
+                        "Modified time of the original bundle is unknown, output timestamp not set."
+                    );
+                    return Ok(());
+                };
+                secs
+            }
+        };
+        let time = UNIX_EPOCH + Duration::from_secs(secs);
+        File::options()
+            .write(true)
+            .open(output_path)
+            .and_then(|file| file.set_modified(time))
+            .map_err(ProjectError::file(output_path))?;
+        info!("{}: {}", "Timestamp".cyan(), utils::format_utc_time(secs));
+        Ok(())
+    }
+
+    pub fn dump_bnk(input_path: impl AsRef<Path>, output_root: impl AsRef<Path>) -> Result<Self> {
+        Self::dump_bnk_with(input_path, output_root, false)
     }
 
-    pub fn dump_bnk(
+    /// Dump a bank, with `lenient` skipping damaged parts with warnings
+    /// instead of failing.
+    pub fn dump_bnk_with(
         input_path: impl AsRef<Path>,
         output_root: impl AsRef<Path>,
-    ) -> eyre::Result<Self> {
+        lenient: bool,
+    ) -> Result<Self> {
         let input_path = input_path.as_ref();
         let output_root = output_root.as_ref();
 
-        let file = File::open(input_path)?;
+        let file = File::open(input_path).map_err(ProjectError::file(input_path))?;
         let mut reader = io::BufReader::new(file);
-        let bank = bnk::Bnk::from_reader(&mut reader)
-            .map_err(|e| eyre::Report::new(e))
-            .context("Failed to parse bnk file")?;
+        let bank = if lenient {
+            bnk::Bnk::from_reader_lenient(&mut reader)?
+        } else {
+            bnk::Bnk::from_reader(&mut reader)?
+        };
         let source_name = input_path.file_name().unwrap().to_string_lossy();
         let mut project_path = output_root
             .join(source_name.as_ref())
@@ -70,36 +439,42 @@ impl SoundToolProject {
             .to_string();
         project_path.push_str(".project");
         let project_path = PathBuf::from(project_path);
-        fs::create_dir_all(&project_path).context("Failed to create project directory")?;
+        fs::create_dir_all(&project_path).map_err(ProjectError::file(&project_path))?;
 
         // dump bnk data
-        let mut didx_entries = vec![];
+        let mut didx_entries = None;
+        let mut data_alignment = None;
+        let mut order = EntryOrder::default();
+        let mut formats = EntryFormats::default();
 
         for section in &bank.sections {
             match &section.payload {
                 bnk::SectionPayload::Didx { entries } => {
-                    didx_entries = entries.clone();
+                    didx_entries = Some(entries.clone());
+                    order.wem = entries.iter().map(|entry| entry.id).collect();
                 }
                 bnk::SectionPayload::Data { data_list } => {
-                    if didx_entries.is_empty() {
-                        eyre::bail!("DIDX section must before DATA section.")
-                    }
+                    let Some(didx_entries) = &didx_entries else {
+                        return Err(bnk::BnkError::MissingDidx.into());
+                    };
+                    data_alignment =
+                        bnk::DataAlignment::detect(didx_entries, ByteLen(section.section_length));
                     data_list
                         .iter()
                         .enumerate()
                         .zip(didx_entries.iter())
-                        .try_for_each(|((idx, data), entry)| -> eyre::Result<()> {
+                        .try_for_each(|((idx, data), entry)| -> Result<()> {
                             let file_name = if didx_entries.len() < 1000 {
                                 format!("[{:03}]{}.wem", idx, entry.id)
                             } else {
                                 format!("[{:04}]{}.wem", idx, entry.id)
                             };
                             let file_path = project_path.join(file_name);
-                            let mut file = File::create(&file_path)
-                                .context("Failed to create wem output file")
-                                .context(format!("Path: {}", file_path.display()))?;
+                            let mut file =
+                                File::create(&file_path).map_err(ProjectError::file(&file_path))?;
                             file.write_all(data)
-                                .context("Failed to write wem data to file")?;
+                                .map_err(ProjectError::file(&file_path))?;
+                            formats.add(entry.id, data);
                             Ok(())
                         })?;
                 }
@@ -108,47 +483,72 @@ impl SoundToolProject {
         }
 
         // 导出其余部分
-        let mut meta_bank = bank.clone();
-        meta_bank.sections.retain(|sec| {
-            !matches!(
-                &sec.payload,
-                bnk::SectionPayload::Didx { .. } | bnk::SectionPayload::Data { .. }
-            )
-        });
+        let mut meta_bank = serde_json::to_value(bank_metadata(&bank))?;
         let meta_bank_path = project_path.join("bank.json");
         info!("Metadata: {}", meta_bank_path.display());
-        let mut meta_bank_file = File::create(&meta_bank_path)
-            .context("Failed to create bank meta file")
-            .context(format!("Path: {}", meta_bank_path.display()))?;
+        let bank_id = bank
+            .sections
+            .iter()
+            .find_map(|section| match section.payload {
+                bnk::SectionPayload::Bkhd { id, .. } => Some(id),
+                _ => None,
+            });
+        for entry in bank.bank_names() {
+            if Some(entry.id) == bank_id {
+                info!("{}: {} {}", "Bank".cyan(), entry.id, entry.name);
+            } else {
+                info!("{}: {} {}", "Referenced bank".cyan(), entry.id, entry.name);
+            }
+        }
+        label_bank_names(&mut meta_bank, bank.bank_names(), bank_id);
+        let mut meta_bank_file =
+            File::create(&meta_bank_path).map_err(ProjectError::file(&meta_bank_path))?;
         let mut writer = io::BufWriter::new(&mut meta_bank_file);
-        serde_json::to_writer(&mut writer, &meta_bank)
-            .context("Failed to write bank meta to file")?;
+        serde_json::to_writer(&mut writer, &meta_bank)?;
+        order.save(&project_path)?;
+        formats.save(&project_path)?;
 
         // 创建project
         let this = Self::Bnk(BnkProject {
             metadata_file: "bank.json".to_string(),
             source_file_name: source_name.to_string(),
+            source_fingerprint: Some(BundleFingerprint::from_path(input_path)?),
+            source_path: fs::canonicalize(input_path).ok(),
+            game_version: None,
+            data_alignment,
             project_path: PathBuf::from(&project_path),
         });
-        this.write_project_metadata(&project_path)
-            .context("Failed to write project metadata")?;
-        info!("Output: {}", project_path.display());
+        this.write_project_metadata(&project_path)?;
+        info!(
+            code = "output-written", file:% = project_path.display();
+            "Output: {}",
+            project_path.display()
+        );
 
         Ok(this)
     }
 
-    pub fn dump_pck(
+    pub fn dump_pck(input_path: impl AsRef<Path>, output_root: impl AsRef<Path>) -> Result<Self> {
+        Self::dump_pck_with(input_path, output_root, false)
+    }
+
+    /// Dump a package, with `lenient` skipping damaged parts with warnings
+    /// instead of failing.
+    pub fn dump_pck_with(
         input_path: impl AsRef<Path>,
         output_root: impl AsRef<Path>,
-    ) -> eyre::Result<Self> {
+        lenient: bool,
+    ) -> Result<Self> {
         let input_path = input_path.as_ref();
         let output_root = output_root.as_ref();
 
-        let file = File::open(input_path)?;
+        let file = File::open(input_path).map_err(ProjectError::file(input_path))?;
         let mut reader = io::BufReader::new(file);
-        let pck = pck::PckHeader::from_reader(&mut reader)
-            .map_err(|e| eyre::Report::new(e))
-            .context("Failed to parse pck file")?;
+        let pck = if lenient {
+            pck::PckHeader::from_reader_lenient(&mut reader)?
+        } else {
+            pck::PckHeader::from_reader(&mut reader)?
+        };
         let source_name = input_path.file_name().unwrap().to_string_lossy();
         let mut project_path = output_root
             .join(source_name.as_ref())
@@ -156,7 +556,7 @@ impl SoundToolProject {
             .to_string();
         project_path.push_str(".project");
         let project_path = PathBuf::from(&project_path);
-        fs::create_dir_all(&project_path).context("Failed to create project directory")?;
+        fs::create_dir_all(&project_path).map_err(ProjectError::file(&project_path))?;
 
         // dump pck data
         for i in 0..pck.bnk_entries.len() {
@@ -167,14 +567,26 @@ impl SoundToolProject {
                 format!("[{:04}]{}.bnk", i, entry.id)
             };
             let file_path = project_path.join(file_name);
-            let mut file = File::create(&file_path)
-                .context("Failed to create bnk output file")
-                .context(format!("Path: {}", file_path.display()))?;
+            let mut file = File::options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&file_path)
+                .map_err(ProjectError::file(&file_path))?;
 
             let mut bnk_reader = pck.bnk_reader(&mut reader, i).unwrap();
-            io::copy(&mut bnk_reader, &mut file).context("Failed to write wem data to file")?;
+            io::copy(&mut bnk_reader, &mut file).map_err(ProjectError::file(&file_path))?;
+            // label the bank by the name in its STID section, if any
+            file.seek(io::SeekFrom::Start(0))
+                .map_err(ProjectError::file(&file_path))?;
+            let names = bnk::Bnk::read_bank_names(&mut io::BufReader::new(&mut file));
+            if let Some(name) = names.iter().flatten().find(|name| name.id == entry.id) {
+                info!("{}: [{}] {} {}", "Bank".cyan(), i, entry.id, name.name);
+            }
         }
 
+        let mut formats = EntryFormats::default();
         for i in 0..pck.wem_entries.len() {
             let entry = &pck.wem_entries[i];
             let file_name = if pck.wem_entries.len() < 1000 {
@@ -183,534 +595,3358 @@ impl SoundToolProject {
                 format!("[{:04}]{}.wem", i, entry.id)
             };
             let file_path = project_path.join(file_name);
-            let mut file = File::create(&file_path)
-                .context("Failed to create wem output file")
-                .context(format!("Path: {}", file_path.display()))?;
+            let mut file = File::create(&file_path).map_err(ProjectError::file(&file_path))?;
 
+            let mut data = vec![];
             let mut wem_reader = pck.wem_reader(&mut reader, i).unwrap();
-            io::copy(&mut wem_reader, &mut file).context("Failed to write wem data to file")?;
+            wem_reader
+                .read_to_end(&mut data)
+                .map_err(ProjectError::file(&file_path))?;
+            file.write_all(&data)
+                .map_err(ProjectError::file(&file_path))?;
+            formats.add(entry.id, &data);
         }
 
         // 导出其余部分
         let meta_pck_path = project_path.join("pck.json");
         info!("Metadata: {}", meta_pck_path.display());
-        let mut meta_pck_file = File::create(&meta_pck_path)
-            .context("Failed to create pck meta file")
-            .context(format!("Path: {}", meta_pck_path.display()))?;
+        let mut meta_pck_file =
+            File::create(&meta_pck_path).map_err(ProjectError::file(&meta_pck_path))?;
         let mut writer = io::BufWriter::new(&mut meta_pck_file);
-        serde_json::to_writer(&mut writer, &pck).context("Failed to write pck meta to file")?;
+        serde_json::to_writer(&mut writer, &pck)?;
+        EntryOrder {
+            wem: pck.wem_entries.iter().map(|entry| entry.id).collect(),
+            bnk: pck.bnk_entries.iter().map(|entry| entry.id).collect(),
+        }
+        .save(&project_path)?;
+        formats.save(&project_path)?;
 
         // 创建project
         let this = Self::Pck(PckProject {
             metadata_file: "pck.json".to_string(),
             source_file_name: source_name.to_string(),
+            source_fingerprint: Some(BundleFingerprint::from_path(input_path)?),
+            source_path: fs::canonicalize(input_path).ok(),
+            game_version: None,
             project_path: project_path.clone(),
         });
-        this.write_project_metadata(&project_path)
-            .context("Failed to write project metadata")?;
-        info!("Output: {}", project_path.display());
+        this.write_project_metadata(&project_path)?;
+        info!(
+            code = "output-written", file:% = project_path.display();
+            "Output: {}",
+            project_path.display()
+        );
 
         Ok(this)
     }
 
-    fn set_project_path(&mut self, project_path: impl AsRef<Path>) {
-        match self {
-            SoundToolProject::Bnk(project) => {
-                project.project_path = project_path.as_ref().to_path_buf()
-            }
-            SoundToolProject::Pck(project) => {
-                project.project_path = project_path.as_ref().to_path_buf()
+    /// Import a folder of loose wem files into this project.
+    ///
+    /// Files are matched to project entries by ID, any number in the file name
+    /// equal to an entry ID is accepted. Matched files are copied into `replace/`.
+    ///
+    /// Returns the count of imported files.
+    pub fn import_loose_wems(&self, input_dir: impl AsRef<Path>) -> Result<usize> {
+        let input_dir = input_dir.as_ref();
+        let project_path = self.project_path();
+
+        // collect entry IDs from dumped files
+        let store = open_store(project_path)?;
+        let mut entry_ids = HashMap::new();
+        for file in list_dumped_files(project_path, store.as_ref(), "wem")? {
+            entry_ids.insert(file.id, file.idx);
+        }
+
+        let replace_root = project_path.join("replace");
+        let mut imported = 0;
+        for entry in fs::read_dir(input_dir)? {
+            let path = entry?.path();
+            if !path.is_file() || path.extension().unwrap_or_default() != "wem" {
+                continue;
             }
+            let file_stem = path.file_stem().unwrap().to_string_lossy();
+            let matched_id = REG_NUMBER
+                .find_iter(&file_stem)
+                .filter_map(|m| m.as_str().parse::<u32>().ok())
+                .find(|id| entry_ids.contains_key(id));
+            let Some(id) = matched_id else {
+                warn!(
+                    "Loose wem '{}' does not match any entry in template, skipped.",
+                    path.display()
+                );
+                continue;
+            };
+
+            fs::create_dir_all(&replace_root).map_err(ProjectError::file(&replace_root))?;
+            let to = replace_root.join(format!("{}.wem", id));
+            fs::copy(&path, &to).map_err(ProjectError::file(&path))?;
+            info!(
+                "{}: '{}' -> [{}]{}",
+                "Import".cyan(),
+                path.file_name().unwrap().to_string_lossy(),
+                entry_ids[&id],
+                id
+            );
+            imported += 1;
         }
-    }
 
-    /// Create project metadata file `project.json`.
-    fn write_project_metadata(&self, dir_path: impl AsRef<Path>) -> eyre::Result<()> {
-        let metadata_path = dir_path.as_ref().join("project.json");
-        info!("Project Metadata: {}", metadata_path.display());
-        let mut project_file = File::create(&metadata_path)
-            .context("Failed to create project file")
-            .context(format!("Path: {}", metadata_path.display()))?;
-        let mut writer = io::BufWriter::new(&mut project_file);
-        serde_json::to_writer(&mut writer, &self)
-            .context("Failed to write project data to file")?;
-        Ok(())
+        Ok(imported)
     }
-}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BnkProject {
-    metadata_file: String,
-    source_file_name: String,
-    #[serde(skip)]
-    project_path: PathBuf,
-}
+    /// Extract banks embedded in a PCK project to `banks/<id>.bnk`, and dump
+    /// each to a nested project next to it.
+    ///
+    /// Nested projects are repacked into the PCK in place of the dumped banks.
+    /// Returns the extracted banks and their projects, empty for BNK projects.
+    pub fn dump_nested_banks(&self) -> Result<Vec<(PathBuf, SoundToolProject)>> {
+        let banks_dir = self.project_path().join(BANKS_DIR);
+        let mut nested = vec![];
+        for bank_path in self.extract_banks()? {
+            let project = Self::dump_bnk(&bank_path, &banks_dir)?;
+            nested.push((bank_path, project));
+        }
+        Ok(nested)
+    }
 
-impl BnkProject {
-    pub fn repack(&self, output_root: impl AsRef<Path>) -> eyre::Result<()> {
-        let output_root = output_root.as_ref();
+    /// Extract banks embedded in a PCK project to `banks/<id>.bnk`.
+    fn extract_banks(&self) -> Result<Vec<PathBuf>> {
+        let SoundToolProject::Pck(_) = self else {
+            return Ok(vec![]);
+        };
+        let project_path = self.project_path();
+        let banks_dir = project_path.join(BANKS_DIR);
+        let store = open_store(project_path)?;
+        let mut files = list_dumped_files(project_path, store.as_ref(), "bnk")?;
+        files.sort_by_key(|file| file.idx);
+        let mut bank_paths = vec![];
+        for file in files {
+            fs::create_dir_all(&banks_dir).map_err(ProjectError::file(&banks_dir))?;
+            let bank_path = banks_dir.join(format!("{}.bnk", file.id));
+            fs::write(&bank_path, file.read(store.as_ref())?)
+                .map_err(ProjectError::file(&bank_path))?;
+            bank_paths.push(bank_path);
+        }
+        Ok(bank_paths)
+    }
 
-        let bank_meta_path = self.project_path.join(&self.metadata_file);
-        if !bank_meta_path.is_file() {
-            eyre::bail!("Bnk metadata file not found: {}", bank_meta_path.display())
+    /// Move dumped entry files into a compressed media store.
+    ///
+    /// Files already in the store are kept. Returns the count of stored files.
+    pub fn pack_media_store(&self) -> Result<usize> {
+        let project_path = self.project_path();
+        let old_store = open_store(project_path)?;
+        let mut files = vec![];
+        for ext in ["bnk", "wem"] {
+            files.extend(list_dumped_files(project_path, old_store.as_ref(), ext)?);
+        }
+        // read everything first, the old store is overwritten
+        let mut contents = Vec::with_capacity(files.len());
+        for file in &files {
+            contents.push(file.read(old_store.as_ref())?);
         }
-        let bank_meta_content = fs::read_to_string(&bank_meta_path)?;
-        let mut bank: bnk::Bnk = serde_json::from_str(&bank_meta_content)?;
 
-        // 导出bnk
-        // 读取wem
-        let mut wem_files = vec![];
-        for entry in fs::read_dir(&self.project_path)? {
-            let entry = entry?;
-            let path = entry.path();
-            if !path.is_file() || path.extension().unwrap_or_default() != "wem" {
-                continue;
+        let mut writer = MediaStoreWriter::create(project_path)?;
+        let mut unique = 0;
+        for (file, data) in files.iter().zip(&contents) {
+            if writer.add(&file.name, data)? {
+                unique += 1;
             }
-
-            // 解析wem文件名
-            struct WemInfo {
-                idx: u32,
-                id: u32,
-                data: Vec<u8>,
+        }
+        writer.finish()?;
+        for file in &files {
+            if let Some(path) = &file.path {
+                fs::remove_file(path).map_err(ProjectError::file(path))?;
             }
-            let file_stem = path.file_stem().unwrap().to_string_lossy();
-            let (idx, id) = parse_wem_name(&file_stem)?;
-            let data = fs::read(path)?;
-            wem_files.push(WemInfo { idx, id, data });
         }
+        info!(
+            "{}: {} files stored, {} unique.",
+            "Store".cyan(),
+            files.len(),
+            unique
+        );
 
-        // 读取replace
-        let replace_root = self.project_path.join("replace");
-        let replace_data = if replace_root.is_dir() {
-            load_replace_files(replace_root).context("Failed to load replace files")?
-        } else {
-            HashMap::new()
+        Ok(files.len())
+    }
+
+    /// Remove dumped entry files identical to the source bundle, keeping an
+    /// index of their data in it. The bundle is then required to repack, see
+    /// [`Self::find_source_bundle`]. Modified files are kept as loose files.
+    ///
+    /// Returns the count of indexed files.
+    pub fn strip_media(&self, source: impl AsRef<Path>) -> Result<usize> {
+        let source = source.as_ref();
+        let project_path = self.project_path();
+        let store = open_store(project_path)?;
+        let mut files = vec![];
+        for ext in ["bnk", "wem"] {
+            files.extend(list_dumped_files(project_path, store.as_ref(), ext)?);
+        }
+        let mut contents = Vec::with_capacity(files.len());
+        for file in &files {
+            contents.push(file.read(store.as_ref())?);
+        }
+
+        // ranges of entries in source bundle, by kind and index
+        let mut reader =
+            io::BufReader::new(File::open(source).map_err(ProjectError::file(source))?);
+        let data_position = match self {
+            SoundToolProject::Bnk(_) => match bnk::Bnk::data_position(&mut reader)? {
+                Some(position) => position,
+                None => {
+                    info!(
+                        "{}: {} has no embedded media, nothing to strip.",
+                        "Strip".cyan(),
+                        self.source_file_name()
+                    );
+                    return Ok(0);
+                }
+            },
+            SoundToolProject::Pck(_) => 0,
         };
-        // 应用replace
-        for wem in wem_files.iter_mut() {
-            if let Some(rep_data) = replace_data.get(&IdOrIndex::Index(wem.idx)) {
-                wem.data = rep_data.clone();
-                info!(
-                    "{}: Wem file [{}] replaced by index.",
-                    "Replace".cyan(),
-                    wem.idx
-                );
-                continue;
-            }
-            if let Some(rep_data) = replace_data.get(&IdOrIndex::Id(wem.id)) {
-                wem.data = rep_data.clone();
-                info!(
-                    "{}: Wem file '{}' replaced by ID.",
-                    "Replace".cyan(),
-                    wem.id
-                );
-                continue;
+        let ranges: HashMap<(&str, u32), (u64, u32)> = list_entries(source)?
+            .into_iter()
+            .map(|entry| {
+                (
+                    (entry.kind, entry.index),
+                    (data_position + entry.offset as u64, entry.size),
+                )
+            })
+            .collect();
+        let mut entries = Vec::with_capacity(files.len());
+        let mut modified = vec![];
+        for (file, data) in files.iter().zip(&contents) {
+            let kind = Path::new(&file.name)
+                .extension()
+                .unwrap_or_default()
+                .to_string_lossy();
+            let (offset, size) = ranges.get(&(kind.as_ref(), file.idx)).ok_or_else(|| {
+                ProjectError::EntryNotFound {
+                    target: IdOrIndex::Index(file.idx),
+                    path: source.to_path_buf(),
+                }
+            })?;
+            let mut original = vec![0; *size as usize];
+            reader.seek(io::SeekFrom::Start(*offset))?;
+            reader.read_exact(&mut original)?;
+            if original == *data {
+                entries.push((file.name.as_str(), *offset, data.as_slice()));
+            } else {
+                modified.push((file, data));
             }
         }
+        let indexed = entries.len();
+        MediaStore::index_bundle(project_path, self.source_file_name(), entries)?;
 
-        wem_files.sort_by_key(|wem| wem.idx);
-        // 构造didx
-        let mut didx_entries = vec![];
-        let mut offset = 0;
-        for wem in &wem_files {
-            didx_entries.push(bnk::DidxEntry {
-                id: wem.id,
-                offset,
-                length: wem.data.len() as u32,
-            });
-            // no padding
-            offset += wem.data.len() as u32;
+        for file in &files {
+            if let Some(path) = &file.path
+                && !modified
+                    .iter()
+                    .any(|(modified, _)| modified.name == file.name)
+            {
+                fs::remove_file(path).map_err(ProjectError::file(path))?;
+            }
         }
-
-        // 构造bank
-        bank.sections.insert(
-            1,
-            bnk::Section::new(bnk::SectionPayload::Didx {
-                entries: didx_entries,
-            }),
-        );
-        bank.sections.insert(
-            2,
-            bnk::Section::new(bnk::SectionPayload::Data {
-                data_list: wem_files.into_iter().map(|wem| wem.data).collect(),
-            }),
+        // modified files only in the old store are written out
+        for (file, data) in &modified {
+            if file.path.is_none() {
+                let path = project_path.join(&file.name);
+                fs::write(&path, data).map_err(ProjectError::file(&path))?;
+            }
+        }
+        let store_path = project_path.join(store::STORE_FILE_NAME);
+        if store_path.is_file() {
+            fs::remove_file(&store_path).map_err(ProjectError::file(&store_path))?;
+        }
+        info!(
+            "{}: {} entries indexed in {}, {} modified files kept.",
+            "Strip".cyan(),
+            indexed,
+            self.source_file_name(),
+            modified.len()
         );
+        if self.find_source_bundle().is_err() {
+            warn!(
+                "Place {} in or next to the project folder before packaging.",
+                self.source_file_name()
+            );
+        }
 
-        // 导出bank
-        // project dir name
-        let mut output_path = output_root
-            .join(&self.source_file_name)
-            .to_string_lossy()
-            .to_string();
-        loop {
-            if Path::new(&output_path).exists() {
-                output_path.push_str(".new");
-            } else {
-                break;
+        Ok(indexed)
+    }
+
+    /// Annotate dumped files and metadata with names resolved from `names`.
+    ///
+    /// Loose dumped files are renamed to `[000]<id>_<name>.wem`, and objects
+    /// in the metadata file with a resolved `id` get a `name` field, which is
+    /// ignored on repack. Returns the count of files renamed.
+    pub fn annotate_names(&self, names: &NameList) -> Result<usize> {
+        let project_path = self.project_path();
+        let mut renamed = 0;
+        for ext in ["bnk", "wem"] {
+            for file in list_dumped_files(project_path, None, ext)? {
+                let (Some(path), Some(name)) = (&file.path, names.get(file.id)) else {
+                    continue;
+                };
+                let file_stem = path.file_stem().unwrap().to_string_lossy();
+                let prefix = REG_WEM_NAME.find(&file_stem).unwrap().as_str();
+                let new_path = project_path.join(format!(
+                    "{}_{}.{}",
+                    prefix,
+                    names::file_name_suffix(name),
+                    ext
+                ));
+                if new_path != *path {
+                    fs::rename(path, &new_path).map_err(ProjectError::file(path))?;
+                }
+                renamed += 1;
             }
         }
 
-        let output_file = File::create(&output_path)?;
-        let mut writer = io::BufWriter::new(output_file);
-        bank.write_to(&mut writer)?;
-
-        info!("Output: {}", output_path);
+        let mut metadata = self.metadata()?;
+        let annotated = names.annotate_json(&mut metadata);
+        let metadata_path = self.metadata_path();
+        let file = File::create(&metadata_path).map_err(ProjectError::file(&metadata_path))?;
+        let mut writer = io::BufWriter::new(file);
+        serde_json::to_writer(&mut writer, &metadata)?;
+        writer.flush()?;
+        info!(
+            "{}: {} files renamed, {} metadata objects named.",
+            "Names".cyan(),
+            renamed,
+            annotated
+        );
 
-        Ok(())
+        Ok(renamed)
     }
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PckProject {
-    metadata_file: String,
-    source_file_name: String,
-    #[serde(skip)]
-    project_path: PathBuf,
-}
 
-impl PckProject {
-    pub fn repack(&self, output_root: impl AsRef<Path>) -> eyre::Result<()> {
+    /// Migrate the project onto an updated source bundle.
+    ///
+    /// The new bundle is dumped into `output_root` and user files are carried over.
+    /// Replace files are remapped if their entries moved or changed IDs, files of
+    /// removed entries are moved to `orphaned/`. Nested projects in `banks/` are
+    /// rebased onto the banks of the new bundle, those of removed banks are moved
+    /// to `orphaned/banks/`. If the new project takes the path of this one, this
+    /// project is kept as a `.bak` folder.
+    pub fn rebase(
+        &self,
+        new_source: impl AsRef<Path>,
+        output_root: impl AsRef<Path>,
+    ) -> Result<(SoundToolProject, RebaseReport)> {
+        let new_source = new_source.as_ref();
         let output_root = output_root.as_ref();
-
-        let pck_header_path = self.project_path.join(&self.metadata_file);
-        if !pck_header_path.is_file() {
-            eyre::bail!("PCK metadata file not found: {}", pck_header_path.display())
+        let old_path = self.project_path();
+        let file_type = InputFileType::from_path(new_source);
+        let (kind, metadata_file, type_matched) = match self {
+            SoundToolProject::Bnk(project) => (
+                "bnk",
+                &project.metadata_file,
+                matches!(file_type, Some(InputFileType::Bnk)),
+            ),
+            SoundToolProject::Pck(project) => (
+                "pck",
+                &project.metadata_file,
+                matches!(file_type, Some(InputFileType::Pck)),
+            ),
+        };
+        if !type_matched {
+            return Err(ProjectError::RebaseMismatch {
+                kind,
+                source_path: new_source.to_path_buf(),
+            });
         }
-        let pck_header_content = fs::read_to_string(&pck_header_path)?;
-        let mut pck_header: pck::PckHeader = serde_json::from_str(&pck_header_content)?;
 
-        // create bnk metadata
-        struct BnkMetadata {
-            idx: u32,
-            file_size: u32,
-            file_path: Option<String>,
-            data: Option<Vec<u8>>,
-        }
-        let mut bnk_metadata_map = IndexMap::new();
-        for entry in fs::read_dir(&self.project_path)? {
+        // dump into a staging folder, the old project may take the same path
+        fs::create_dir_all(output_root).map_err(ProjectError::file(output_root))?;
+        let staging = tempfile::tempdir_in(output_root)?;
+        let mut new_project = match self {
+            SoundToolProject::Bnk(_) => Self::dump_bnk(new_source, staging.path())?,
+            SoundToolProject::Pck(_) => Self::dump_pck(new_source, staging.path())?,
+        };
+        let staged_path = new_project.project_path().to_path_buf();
+
+        let old_store = open_store(old_path)?;
+        let old_entries = list_dumped_files(old_path, old_store.as_ref(), "wem")?;
+        let new_entries = list_dumped_files(&staged_path, None, "wem")?;
+        let mut report = RebaseReport::default();
+        let mapping =
+            EntryMapping::build(&old_entries, old_store.as_ref(), &new_entries, &mut report)?;
+
+        // carry over user files
+        for entry in fs::read_dir(old_path)? {
             let entry = entry?;
-            let path = entry.path();
-            if !path.is_file() || path.extension().unwrap_or_default() != "bnk" {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name == "replace" || name == BANKS_DIR || is_generated_file(&name, metadata_file) {
                 continue;
             }
-            let file_stem = path.file_stem().unwrap().to_string_lossy();
-            let (idx, id) = parse_wem_name(&file_stem)?;
-            bnk_metadata_map.insert(
-                id,
-                BnkMetadata {
-                    idx,
-                    file_size: path.metadata()?.len() as u32,
-                    file_path: Some(path.to_string_lossy().to_string()),
-                    data: None,
-                },
-            );
+            let dest = staged_path.join(&name);
+            if entry.file_type()?.is_dir() {
+                utils::copy_dir_all(entry.path(), &dest)
+                    .map_err(ProjectError::file(entry.path()))?;
+            } else {
+                fs::copy(entry.path(), &dest).map_err(ProjectError::file(entry.path()))?;
+            }
         }
-        // create wem metadata
-        struct WemMetadata {
-            idx: u32,
-            file_size: u32,
-            file_path: Option<String>,
-            data: Option<Vec<u8>>,
+        let replace_root = old_path.join("replace");
+        if replace_root.is_dir() {
+            let new_replace_root = staged_path.join("replace");
+            fs::create_dir_all(&new_replace_root).map_err(ProjectError::file(&new_replace_root))?;
+            // subfolders are kept, files in them are remapped as well
+            for path in list_sound_files(&replace_root, true)? {
+                let relative = path.strip_prefix(&replace_root).unwrap();
+                let sub_dir = relative.parent().unwrap_or(Path::new(""));
+                let target = path
+                    .file_stem()
+                    .and_then(|stem| IdOrIndex::parse(stem.to_string_lossy().trim()));
+                let dest = match target.map(|target| (target, mapping.map(target))) {
+                    // not a replace file, keep as is
+                    None => new_replace_root.join(relative),
+                    Some((target, Some(new_target))) => {
+                        let new_name = match path.extension() {
+                            Some(ext) => format!("{}.{}", new_target, ext.to_string_lossy()),
+                            None => new_target.to_string(),
+                        };
+                        if new_target != target {
+                            report.remapped.push((target, new_target));
+                        }
+                        new_replace_root.join(sub_dir).join(new_name)
+                    }
+                    Some((_, None)) => {
+                        report.orphaned.push(relative.to_path_buf());
+                        staged_path.join("orphaned").join(relative)
+                    }
+                };
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent).map_err(ProjectError::file(parent))?;
+                }
+                fs::copy(&path, &dest).map_err(ProjectError::file(&path))?;
+            }
         }
-        let mut wem_metadata_map = IndexMap::new();
-        for entry in fs::read_dir(&self.project_path)? {
-            let entry = entry?;
-            let path = entry.path();
-            if !path.is_file() || path.extension().unwrap_or_default() != "wem" {
-                continue;
+        let old_banks_dir = old_path.join(BANKS_DIR);
+        if old_banks_dir.is_dir() {
+            rebase_nested_banks(&new_project, &old_banks_dir, &mut report)?;
+        }
+        // directives in project.toml are never rewritten, only reported
+        let manifest = ProjectManifest::load(old_path)?;
+        for directive in &manifest.replace {
+            if mapping.map(directive.target) != Some(directive.target) {
+                report.stale_directives.push(directive.target);
             }
-            let file_stem = path.file_stem().unwrap().to_string_lossy();
-            let (idx, id) = parse_wem_name(&file_stem)?;
-            wem_metadata_map.insert(
-                id,
-                WemMetadata {
-                    idx,
-                    file_size: path.metadata()?.len() as u32,
-                    file_path: Some(path.to_string_lossy().to_string()),
-                    data: None,
-                },
-            );
         }
-        // replace files
-        let replace_root = self.project_path.join("replace");
-        let replace_data = if replace_root.is_dir() {
-            load_replace_files(replace_root).context("Failed to load replace files")?
-        } else {
-            HashMap::new()
+        if old_store.is_some() {
+            new_project.pack_media_store()?;
+        }
+
+        // move the new project into place
+        let target_path = output_root.join(staged_path.file_name().unwrap());
+        if target_path.exists() {
+            if fs::canonicalize(&target_path)? != fs::canonicalize(old_path)? {
+                return Err(ProjectError::ProjectExists(target_path));
+            }
+            let backup_path = backup_path(&target_path);
+            fs::rename(&target_path, &backup_path).map_err(ProjectError::file(&target_path))?;
+            info!("Backup: {}", backup_path.display());
+        }
+        fs::rename(&staged_path, &target_path).map_err(ProjectError::file(&staged_path))?;
+        new_project.set_project_path(&target_path);
+        // the source path is relative to the staging folder
+        new_project.write_project_metadata(&target_path)?;
+        report.log();
+        info!("Output: {}", target_path.display());
+
+        Ok((new_project, report))
+    }
+
+    /// Tag the project with the game version its source bundle comes from.
+    pub fn set_game_version(&mut self, game_version: impl Into<String>) -> Result<()> {
+        let game_version = Some(game_version.into());
+        match self {
+            SoundToolProject::Bnk(project) => project.game_version = game_version,
+            SoundToolProject::Pck(project) => project.game_version = game_version,
+        }
+        self.write_project_metadata(self.project_path())
+    }
+
+    pub fn game_version(&self) -> Option<&str> {
+        match self {
+            SoundToolProject::Bnk(project) => project.game_version.as_deref(),
+            SoundToolProject::Pck(project) => project.game_version.as_deref(),
+        }
+    }
+
+    pub fn source_file_name(&self) -> &str {
+        match self {
+            SoundToolProject::Bnk(project) => &project.source_file_name,
+            SoundToolProject::Pck(project) => &project.source_file_name,
+        }
+    }
+
+    /// Path the source bundle was dumped from, it may have moved since.
+    pub fn source_path(&self) -> Option<&Path> {
+        match self {
+            SoundToolProject::Bnk(project) => project.source_path.as_deref(),
+            SoundToolProject::Pck(project) => project.source_path.as_deref(),
+        }
+    }
+
+    /// Path of the source bundle relative to the game folder, from the
+    /// `natives` folder of the path recorded on dump.
+    pub fn natives_path(&self) -> Option<PathBuf> {
+        self.source_path().and_then(deploy::natives_relative_path)
+    }
+
+    pub fn source_fingerprint(&self) -> Option<&BundleFingerprint> {
+        match self {
+            SoundToolProject::Bnk(project) => project.source_fingerprint.as_ref(),
+            SoundToolProject::Pck(project) => project.source_fingerprint.as_ref(),
+        }
+    }
+
+    /// Compare a bundle with the one this project was dumped from.
+    ///
+    /// Returns `None` for projects dumped without a fingerprint.
+    pub fn compare_bundle(&self, bundle_path: impl AsRef<Path>) -> Result<Option<BundleChange>> {
+        let Some(fingerprint) = self.source_fingerprint() else {
+            return Ok(None);
         };
-        // replace wems
-        for (&id, wem) in wem_metadata_map.iter_mut() {
+        let current = BundleFingerprint::from_path(bundle_path)?;
+        Ok(Some(fingerprint.compare(&current)))
+    }
+
+    /// Warn if the bundle about to be replaced differs from the dumped one,
+    /// usually the game was updated after the project was created.
+    fn check_target_bundle(&self, bundle_path: impl AsRef<Path>) -> Result<()> {
+        let bundle_path = bundle_path.as_ref();
+        if !bundle_path.is_file() {
+            return Ok(());
+        }
+        let dumped_from = self
+            .game_version()
+            .map(|version| format!(" (dumped from game version {})", version))
+            .unwrap_or_default();
+        match self.compare_bundle(bundle_path)? {
+            None | Some(BundleChange::Unchanged) => return Ok(()),
+            Some(BundleChange::Modified) => warn!(
+                "Bundle {} differs from the one this project was dumped from{}.",
+                bundle_path.display(),
+                dumped_from
+            ),
+            Some(BundleChange::VersionChanged { from, to }) => warn!(
+                "Bundle {} has version {}, project was dumped from version {}{}.",
+                bundle_path.display(),
+                to,
+                from,
+                dumped_from
+            ),
+        }
+        warn!(
+            "Entries may have changed after a game update, consider `rebase --new-source {}`.",
+            bundle_path.display()
+        );
+        Ok(())
+    }
+
+    /// Dumped wem entries of the project, sorted by index.
+    pub fn media_entries(&self) -> Result<Vec<MediaEntry>> {
+        let project_path = self.project_path();
+        let store = open_store(project_path)?;
+        // language names of PCK entries by index, IDs repeat across languages
+        let mut languages = vec![];
+        if let SoundToolProject::Pck(project) = self {
+            let header_path = project_path.join(&project.metadata_file);
+            let content =
+                fs::read_to_string(&header_path).map_err(ProjectError::file(&header_path))?;
+            let header: pck::PckHeader =
+                serde_json::from_str(&content).map_err(|source| ProjectError::BadMetadata {
+                    path: header_path,
+                    source,
+                })?;
+            languages = header
+                .wem_entries
+                .iter()
+                .map(|entry| {
+                    header
+                        .string_table
+                        .iter()
+                        .find(|string| string.index == entry.language_id)
+                        .map(|language| language.value.clone())
+                })
+                .collect();
+        }
+
+        let mut entries: Vec<MediaEntry> = list_dumped_files(project_path, store.as_ref(), "wem")?
+            .into_iter()
+            .map(|file| MediaEntry {
+                idx: file.idx,
+                id: file.id,
+                language: languages.get(file.idx as usize).cloned().flatten(),
+                file_name: file.name,
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry.idx);
+        Ok(entries)
+    }
+
+    /// Decode dumped wem entries to playable files in `decoded/` folder,
+    /// returns the number of files decoded.
+    ///
+    /// Entries failed to decode are skipped with a warning.
+    pub fn decode_media(&self, format: DecodeFormat) -> Result<usize> {
+        self.decode_media_with(format, None)
+    }
+
+    /// Decode dumped wem files, with label files of their IDs and loop points
+    /// next to the decoded files, see [`labels`].
+    pub fn decode_media_with(
+        &self,
+        format: DecodeFormat,
+        label_format: Option<LabelFormat>,
+    ) -> Result<usize> {
+        let decoded_root = self.project_path().join(DECODED_DIR);
+        fs::create_dir_all(&decoded_root).map_err(ProjectError::file(&decoded_root))?;
+        let entries = self.media_entries()?;
+        let mut decoded = 0;
+        for (i, entry) in entries.iter().enumerate() {
+            let data = self.read_media(entry)?;
+            let output_data = match transcode::decode_wem(&data, format) {
+                Ok(output_data) => output_data,
+                Err(
+                    e @ (TranscodeError::ToolNotFound(_) | TranscodeError::ToolNotConfigured(_)),
+                ) => {
+                    return Err(ProjectError::Decode(e));
+                }
+                Err(e) => {
+                    warn!("Failed to decode {}: {}", entry.file_name, e);
+                    continue;
+                }
+            };
+            let file_name = Path::new(&entry.file_name).with_extension(format.extension());
+            let output_path = decoded_root.join(file_name);
+            fs::write(&output_path, output_data).map_err(ProjectError::file(&output_path))?;
+            if let Some(label_format) = label_format {
+                labels::write_labels(&data, entry.id, &output_path, label_format)
+                    .map_err(ProjectError::file(&output_path))?;
+            }
+            debug!(
+                "[{}/{}] Decoded: {}",
+                i + 1,
+                entries.len(),
+                output_path.display()
+            );
+            decoded += 1;
+        }
+        Ok(decoded)
+    }
+
+    /// Read data of a dumped wem entry.
+    pub fn read_media(&self, entry: &MediaEntry) -> Result<Vec<u8>> {
+        let path = self.project_path().join(&entry.file_name);
+        if path.is_file() {
+            return fs::read(&path).map_err(ProjectError::file(&path));
+        }
+        let store = open_store(self.project_path())?
+            .ok_or_else(|| StoreError::FileNotFound(entry.file_name.clone()))?;
+        Ok(store.read(&entry.file_name)?)
+    }
+
+    /// Edited metadata of the project, `bank.json` or `pck.json`.
+    pub fn metadata(&self) -> Result<serde_json::Value> {
+        let path = self.metadata_path();
+        if !path.is_file() {
+            return Err(ProjectError::MissingMetadata(path));
+        }
+        let content = fs::read_to_string(&path)?;
+        serde_json::from_str(&content).map_err(|source| ProjectError::BadMetadata { path, source })
+    }
+
+    /// Metadata of the bundle as freshly dumped, to compare with [`Self::metadata`].
+    pub fn original_metadata(&self, original: impl AsRef<Path>) -> Result<serde_json::Value> {
+        let original = original.as_ref();
+        let file = File::open(original).map_err(ProjectError::file(original))?;
+        let mut reader = io::BufReader::new(file);
+        let metadata = match self {
+            SoundToolProject::Bnk(_) => {
+                serde_json::to_value(bank_metadata(&bnk::Bnk::from_reader(&mut reader)?))?
+            }
+            SoundToolProject::Pck(_) => {
+                serde_json::to_value(pck::PckHeader::from_reader(&mut reader)?)?
+            }
+        };
+        Ok(metadata)
+    }
+
+    fn metadata_path(&self) -> PathBuf {
+        let metadata_file = match self {
+            SoundToolProject::Bnk(project) => &project.metadata_file,
+            SoundToolProject::Pck(project) => &project.metadata_file,
+        };
+        self.project_path().join(metadata_file)
+    }
+
+    /// Source bundle of the project, in or next to the project folder,
+    /// or at the path it was dumped from.
+    pub fn find_source_bundle(&self) -> Result<PathBuf> {
+        find_bundle(self.project_path(), Path::new(self.source_file_name())).or_else(|e| match self
+            .source_path()
+        {
+            Some(path) if path.is_file() => Ok(path.to_path_buf()),
+            _ => Err(e),
+        })
+    }
+
+    pub fn project_path(&self) -> &Path {
+        match self {
+            SoundToolProject::Bnk(project) => &project.project_path,
+            SoundToolProject::Pck(project) => &project.project_path,
+        }
+    }
+
+    fn set_project_path(&mut self, project_path: impl AsRef<Path>) {
+        match self {
+            SoundToolProject::Bnk(project) => {
+                project.project_path = project_path.as_ref().to_path_buf()
+            }
+            SoundToolProject::Pck(project) => {
+                project.project_path = project_path.as_ref().to_path_buf()
+            }
+        }
+    }
+
+    fn source_path_mut(&mut self) -> &mut Option<PathBuf> {
+        match self {
+            SoundToolProject::Bnk(project) => &mut project.source_path,
+            SoundToolProject::Pck(project) => &mut project.source_path,
+        }
+    }
+
+    /// Resolve the source path recorded relative to the project folder.
+    fn resolve_source_path(&mut self) {
+        let project_path = self.project_path().to_path_buf();
+        if let Some(path) = self.source_path_mut()
+            && path.is_relative()
+        {
+            let joined = project_path.join(&*path);
+            *path = fs::canonicalize(&joined).unwrap_or(joined);
+        }
+    }
+
+    /// Create project metadata file `project.json`.
+    ///
+    /// The source path is written relative to `dir_path`, so it does not
+    /// reveal the local folders and keeps working if both are moved together.
+    fn write_project_metadata(&self, dir_path: impl AsRef<Path>) -> Result<()> {
+        let dir_path = dir_path.as_ref();
+        let metadata_path = dir_path.join("project.json");
+        info!("Project Metadata: {}", metadata_path.display());
+        let mut project = self.clone();
+        let relative = fs::canonicalize(dir_path)
+            .ok()
+            .zip(self.source_path())
+            .and_then(|(dir, path)| utils::relative_path(&dir, path));
+        *project.source_path_mut() = relative;
+        let mut project_file =
+            File::create(&metadata_path).map_err(ProjectError::file(&metadata_path))?;
+        let mut writer = io::BufWriter::new(&mut project_file);
+        serde_json::to_writer(&mut writer, &project)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BnkProject {
+    metadata_file: String,
+    source_file_name: String,
+    /// Fingerprint of the source bundle, missing in old projects.
+    #[serde(default)]
+    source_fingerprint: Option<BundleFingerprint>,
+    /// Path of the source bundle when dumped, relative to the project folder,
+    /// absolute in old projects. Missing in older projects, or if the bundle
+    /// is on another drive.
+    #[serde(default)]
+    source_path: Option<PathBuf>,
+    /// User given game version tag of the source bundle.
+    #[serde(default)]
+    game_version: Option<String>,
+    /// Alignment of wem data in the source bank, missing in old projects
+    /// or if the source layout is irregular.
+    #[serde(default)]
+    data_alignment: Option<bnk::DataAlignment>,
+    #[serde(skip)]
+    project_path: PathBuf,
+}
+
+impl BnkProject {
+    pub fn repack(
+        &self,
+        output_root: impl AsRef<Path>,
+        policy: &OutputPolicy,
+        rules: &RuleSet,
+    ) -> Result<RepackStats> {
+        let output_root = output_root.as_ref();
+        let mut stats = RepackStats::default();
+        let mut phase_start = Instant::now();
+
+        let bank_meta_path = self.project_path.join(&self.metadata_file);
+        if !bank_meta_path.is_file() {
+            return Err(ProjectError::MissingMetadata(bank_meta_path));
+        }
+        let bank_meta_content = fs::read_to_string(&bank_meta_path)?;
+        let mut bank: bnk::Bnk = serde_json::from_str(&bank_meta_content).map_err(|source| {
+            ProjectError::BadMetadata {
+                path: bank_meta_path,
+                source,
+            }
+        })?;
+
+        let (manifest, id_map) = load_remapped_manifest(&self.project_path)?;
+
+        // 导出bnk
+        // 读取wem
+        struct WemInfo {
+            idx: u32,
+            id: u32,
+            data: Vec<u8>,
+            /// Replaced or added, checked in the written bank.
+            changed: bool,
+        }
+        let store = open_store(&self.project_path)?;
+        let dumped_files = list_dumped_files(&self.project_path, store.as_ref(), "wem")?;
+        check_order(&self.project_path, "wem", &dumped_files)?;
+        let mut wem_files = vec![];
+        for file in &dumped_files {
+            let data = file.read(store.as_ref())?;
+            wem_files.push(WemInfo {
+                idx: file.idx,
+                id: file.id,
+                data,
+                changed: false,
+            });
+        }
+
+        stats.end_phase("Load", &mut phase_start);
+
+        // 读取replace
+        let mut replace_data = load_replacements(&self.project_path, &manifest, &id_map)?;
+        check_replacements(
+            &replace_data,
+            wem_files.iter().map(|wem| (wem.idx, wem.id)),
+            manifest.strict_replace,
+        )?;
+        let rule_removed = apply_rules(
+            &self.project_path,
+            &self.source_file_name,
+            &manifest,
+            &dumped_files,
+            &mut replace_data,
+            rules,
+        )?;
+        // 应用replace
+        for wem in wem_files.iter_mut() {
+            let policy = manifest.size_policy(wem.idx, wem.id);
             if let Some(rep_data) = replace_data.get(&IdOrIndex::Index(wem.idx)) {
-                wem.file_path = None;
-                wem.data = Some(rep_data.clone());
+                wem.data =
+                    sized_replacement(policy, IdOrIndex::Index(wem.idx), rep_data, wem.data.len())?;
+                wem.changed = true;
+                stats.replaced_by_index += 1;
                 info!(
+                    code = "entry-replaced", entry = wem.id;
                     "{}: Wem file [{}] replaced by index.",
                     "Replace".cyan(),
                     wem.idx
                 );
                 continue;
             }
-            if let Some(rep_data) = replace_data.get(&IdOrIndex::Id(id)) {
-                wem.file_path = None;
-                wem.data = Some(rep_data.clone());
-                info!("{}: Wem file '{}' replaced by ID.", "Replace".cyan(), id);
+            if let Some(rep_data) = replace_data.get(&IdOrIndex::Id(wem.id)) {
+                wem.data =
+                    sized_replacement(policy, IdOrIndex::Id(wem.id), rep_data, wem.data.len())?;
+                wem.changed = true;
+                stats.replaced_by_id += 1;
+                info!(
+                    code = "entry-replaced", entry = wem.id;
+                    "{}: Wem file '{}' replaced by ID.",
+                    "Replace".cyan(),
+                    wem.id
+                );
                 continue;
             }
         }
-        wem_metadata_map.sort_unstable_by(|_, value_a, _, value_b| value_a.idx.cmp(&value_b.idx));
+        stats.end_phase("Replace", &mut phase_start);
 
-        // update header BNK entries
-        info!("Updating BNK entries...");
-        let mut drop_bnk_idx_list = vec![];
-        for (i, entry) in pck_header.bnk_entries.iter().enumerate() {
-            if !bnk_metadata_map.contains_key(&entry.id) {
-                drop_bnk_idx_list.push(i);
+        // 移除条目
+        check_removals(&manifest, wem_files.iter().map(|wem| (wem.idx, wem.id)));
+        wem_files.retain(|wem| {
+            let removed = manifest.is_removed(wem.idx, wem.id) || rule_removed.contains(&wem.id);
+            if removed {
+                info!(
+                    code = "entry-removed", entry = wem.id;
+                    "{}: Wem file '{}' removed.",
+                    "Remove".cyan(),
+                    wem.id
+                );
+            }
+            !removed
+        });
+
+        wem_files.sort_by_key(|wem| wem.idx);
+        // 添加新wem，ID有序时保持有序
+        let ids_sorted = wem_files.is_sorted_by_key(|wem| wem.id);
+        let first_idx = wem_files.last().map_or(0, |wem| wem.idx + 1);
+        for (idx, addition) in (first_idx..).zip(load_additions(&self.project_path, &manifest)?) {
+            if let Some(language) = addition.language {
+                return Err(ProjectError::UnknownLanguage(language));
+            }
+            if wem_files.iter().any(|wem| wem.id == addition.id) {
+                return Err(ProjectError::AdditionExists(addition.id));
+            }
+            info!(
+                code = "entry-added", entry = addition.id;
+                "{}: Wem file '{}' added.",
+                "Add".cyan(),
+                addition.id
+            );
+            wem_files.push(WemInfo {
+                idx,
+                id: addition.id,
+                data: addition.data,
+                changed: true,
+            });
+        }
+        if ids_sorted {
+            wem_files.sort_by_key(|wem| wem.id);
+        }
+        stats.entry_ids = wem_files.iter().map(|wem| wem.id).collect();
+        // 构造didx
+        let source_alignment = self.data_alignment.unwrap_or_default();
+        let alignment = match manifest.data_alignment {
+            Some(entry) => bnk::DataAlignment {
+                entry,
+                pad_end: source_alignment.pad_end,
+            },
+            None => source_alignment,
+        };
+        info!(
+            "{}: {} bytes{}",
+            "Alignment".cyan(),
+            alignment.entry,
+            if alignment.pad_end {
+                ", end padded"
+            } else {
+                ""
+            }
+        );
+        let layout = bnk::BnkLayout::plan(
+            wem_files.iter().map(|wem| (wem.id, ByteLen::of(&wem.data))),
+            alignment,
+        )?;
+
+        let hashes: Vec<Option<[u8; 32]>> = wem_files
+            .iter()
+            .map(|wem| wem.changed.then(|| Sha256::digest(&wem.data).into()))
+            .collect();
+
+        // 构造bank
+        let (didx, data) =
+            layout.into_sections(wem_files.into_iter().map(|wem| wem.data).collect());
+        let bnk::SectionPayload::Didx { entries } = &didx.payload else {
+            unreachable!("layout builds a DIDX section");
+        };
+        let expected: Vec<ExpectedEntry> = entries
+            .iter()
+            .zip(hashes)
+            .map(|(entry, sha256)| ExpectedEntry {
+                kind: "wem",
+                id: entry.id,
+                size: entry.length.get(),
+                offset: entry.offset.get(),
+                sha256,
+            })
+            .collect();
+        // 应用预设, after media sizes are known for remapped sources
+        let media_sizes: HashMap<u32, u32> = entries
+            .iter()
+            .map(|entry| (entry.id, entry.length.get()))
+            .collect();
+        for preset in &manifest.presets {
+            let changed = preset
+                .apply(&mut bank)
+                .map_err(|source| ProjectError::Preset {
+                    preset: preset.to_string(),
+                    source,
+                })?;
+            if changed.is_empty() {
+                warn!("Preset {} matched no objects in this bank.", preset);
+            } else {
+                info!(
+                    "{}: {} applied to {} objects.",
+                    "Preset".cyan(),
+                    preset,
+                    changed.len()
+                );
+            }
+        }
+        if !manifest.remap.is_empty() {
+            let remap: HashMap<u32, u32> = manifest
+                .remap
+                .iter()
+                .map(|remap| (remap.from, remap.to))
+                .collect();
+            let changed = hirc::remap_sources(&mut bank, &remap, &media_sizes);
+            for remap in &manifest.remap {
+                if !media_sizes.contains_key(&remap.to) {
+                    warn!(
+                        "Remap target {} is not a wem of this bank, sounds playing it need it streamed.",
+                        remap.to
+                    );
+                }
+                let count = changed
+                    .iter()
+                    .filter(|(_, old_id)| *old_id == remap.from)
+                    .count();
+                if count == 0 {
+                    warn!(
+                        "Remap source {} matched no sounds in this bank.",
+                        remap.from
+                    );
+                } else {
+                    info!(
+                        "{}: {} sounds play '{}' instead of '{}'.",
+                        "Remap".cyan(),
+                        count,
+                        remap.to,
+                        remap.from
+                    );
+                }
+            }
+        }
+        for edit in &manifest.props {
+            let changed = edit
+                .apply(&mut bank)
+                .map_err(|source| ProjectError::Props {
+                    edit: edit.to_string(),
+                    source,
+                })?;
+            if changed.is_empty() {
+                warn!("Props of {} matched no objects in this bank.", edit.target);
+            } else {
+                info!(
+                    "{}: {} applied to {} objects.",
+                    "Props".cyan(),
+                    edit,
+                    changed.len()
+                );
             }
         }
-        for i in drop_bnk_idx_list.iter().rev() {
-            let entry = pck_header.bnk_entries.remove(*i);
+        // like Wwise, banks without media have neither section
+        if entries.is_empty() {
+            info!(
+                "{}: no wem files, DIDX and DATA sections omitted.",
+                "Media".cyan()
+            );
+        } else {
+            bank.sections.insert(1, didx);
+            bank.sections.insert(2, data);
+        }
+        if let Some(config) = &manifest.modinfo
+            && config.embed == Embed::Section
+        {
+            // replace mod info of a bundle repacked before
+            bank.sections
+                .retain(|section| section.magic != modinfo::MODINFO_MAGIC);
+            let modinfo = ModInfo::new(config, &self.project_path)?;
+            bank.sections.push(modinfo.to_section()?);
+            info!("{}: embedded as custom section.", "ModInfo".cyan());
+        }
+
+        // 导出bank
+        // project dir name
+        let output_path = policy.output_path(output_root, &self.source_file_name);
+
+        let staged = policy.stage(&output_path);
+        let output_file = File::create(staged.path())?;
+        let mut writer = io::BufWriter::new(output_file);
+        bank.write_to(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        stats.end_phase("Write", &mut phase_start);
+        check_written(staged.path(), &expected)?;
+        staged.place()?;
+        stats.end_phase("Verify", &mut phase_start);
+
+        info!(
+            code = "output-written", file:% = output_path.display();
+            "Output: {}",
+            output_path.display()
+        );
+        stats.output_size = fs::metadata(&output_path)?.len();
+        stats.output_path = output_path;
+
+        Ok(stats)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PckProject {
+    metadata_file: String,
+    source_file_name: String,
+    /// Fingerprint of the source bundle, missing in old projects.
+    #[serde(default)]
+    source_fingerprint: Option<BundleFingerprint>,
+    /// Path of the source bundle when dumped, relative to the project folder,
+    /// absolute in old projects. Missing in older projects, or if the bundle
+    /// is on another drive.
+    #[serde(default)]
+    source_path: Option<PathBuf>,
+    /// User given game version tag of the source bundle.
+    #[serde(default)]
+    game_version: Option<String>,
+    #[serde(skip)]
+    project_path: PathBuf,
+}
+
+impl PckProject {
+    pub fn repack(
+        &self,
+        output_root: impl AsRef<Path>,
+        policy: &OutputPolicy,
+        rules: &RuleSet,
+    ) -> Result<RepackStats> {
+        let output_root = output_root.as_ref();
+        let mut stats = RepackStats::default();
+        let mut phase_start = Instant::now();
+
+        let pck_header_path = self.project_path.join(&self.metadata_file);
+        if !pck_header_path.is_file() {
+            return Err(ProjectError::MissingMetadata(pck_header_path));
+        }
+        let pck_header_content = fs::read_to_string(&pck_header_path)?;
+        let mut pck_header: pck::PckHeader =
+            serde_json::from_str(&pck_header_content).map_err(|source| {
+                ProjectError::BadMetadata {
+                    path: pck_header_path,
+                    source,
+                }
+            })?;
+
+        // create bnk metadata
+        struct BnkMetadata {
+            idx: u32,
+            file_size: u32,
+            file_path: Option<String>,
+            data: Option<Vec<u8>>,
+        }
+        let store = open_store(&self.project_path)?;
+        let mut bnk_metadata_map = IndexMap::new();
+        let dumped_banks = list_dumped_files(&self.project_path, store.as_ref(), "bnk")?;
+        check_order(&self.project_path, "bnk", &dumped_banks)?;
+        for file in dumped_banks {
+            let nested = repack_nested_bank(&self.project_path, file.id)?;
+            let (file_path, data) = match (nested, &file.path) {
+                (Some(data), _) => (None, Some(data)),
+                (None, Some(path)) => (Some(path.to_string_lossy().to_string()), None),
+                (None, None) => (None, Some(file.read(store.as_ref())?)),
+            };
+            bnk_metadata_map.insert(
+                file.id,
+                BnkMetadata {
+                    idx: file.idx,
+                    file_size: data.as_ref().map_or(file.size, |data| data.len() as u64) as u32,
+                    file_path,
+                    data,
+                },
+            );
+        }
+        // create wem metadata
+        struct WemMetadata {
+            idx: u32,
+            file_size: u32,
+            file_path: Option<String>,
+            data: Option<Vec<u8>>,
+        }
+        let dumped_files = list_dumped_files(&self.project_path, store.as_ref(), "wem")?;
+        check_order(&self.project_path, "wem", &dumped_files)?;
+        // keyed by (index, id), the same ID may appear more than once
+        let mut wem_metadata_map = IndexMap::new();
+        for file in &dumped_files {
+            let data = match file.path {
+                Some(_) => None,
+                None => Some(file.read(store.as_ref())?),
+            };
+            wem_metadata_map.insert(
+                (file.idx, file.id),
+                WemMetadata {
+                    idx: file.idx,
+                    file_size: file.size as u32,
+                    file_path: file
+                        .path
+                        .as_ref()
+                        .map(|path| path.to_string_lossy().to_string()),
+                    data,
+                },
+            );
+        }
+        stats.end_phase("Load", &mut phase_start);
+
+        // replace files
+        let (manifest, id_map) = load_remapped_manifest(&self.project_path)?;
+        if !manifest.remap.is_empty() {
+            warn!("Sources can only be remapped in BNK, remap of this package is skipped.");
+        }
+        if !manifest.props.is_empty() {
+            warn!("Props can only be edited in BNK, props of this package are skipped.");
+        }
+        let mut replace_data = load_replacements(&self.project_path, &manifest, &id_map)?;
+        check_replacements(
+            &replace_data,
+            wem_metadata_map.keys().copied(),
+            manifest.strict_replace,
+        )?;
+        let rule_removed = apply_rules(
+            &self.project_path,
+            &self.source_file_name,
+            &manifest,
+            &dumped_files,
+            &mut replace_data,
+            rules,
+        )?;
+        // replace wems
+        for (&(_, id), wem) in wem_metadata_map.iter_mut() {
+            let policy = manifest.size_policy(wem.idx, id);
+            let original_size = wem.file_size as usize;
+            if let Some(rep_data) = replace_data.get(&IdOrIndex::Index(wem.idx)) {
+                let rep_data =
+                    sized_replacement(policy, IdOrIndex::Index(wem.idx), rep_data, original_size)?;
+                wem.file_path = None;
+                wem.file_size = rep_data.len() as u32;
+                wem.data = Some(rep_data);
+                stats.replaced_by_index += 1;
+                info!(
+                    code = "entry-replaced", entry = id;
+                    "{}: Wem file [{}] replaced by index.",
+                    "Replace".cyan(),
+                    wem.idx
+                );
+                continue;
+            }
+            if let Some(rep_data) = replace_data.get(&IdOrIndex::Id(id)) {
+                let rep_data =
+                    sized_replacement(policy, IdOrIndex::Id(id), rep_data, original_size)?;
+                wem.file_path = None;
+                wem.file_size = rep_data.len() as u32;
+                wem.data = Some(rep_data);
+                stats.replaced_by_id += 1;
+                info!(
+                    code = "entry-replaced", entry = id;
+                    "{}: Wem file '{}' replaced by ID.",
+                    "Replace".cyan(),
+                    id
+                );
+                continue;
+            }
+        }
+        wem_metadata_map.sort_unstable_by(|_, value_a, _, value_b| value_a.idx.cmp(&value_b.idx));
+        stats.end_phase("Replace", &mut phase_start);
+
+        // remove entries listed in manifest, missing files are warned below
+        check_removals(&manifest, wem_metadata_map.keys().copied());
+        let removed_keys: Vec<(u32, u32)> = wem_metadata_map
+            .keys()
+            .copied()
+            .filter(|&(idx, id)| manifest.is_removed(idx, id) || rule_removed.contains(&id))
+            .collect();
+        wem_metadata_map.retain(|key, _| !removed_keys.contains(key));
+        // (index, id) of header wem entries, kept in step with them
+        let mut wem_keys: Vec<(u32, u32)> = pck_header
+            .wem_entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (i as u32, entry.id))
+            .collect();
+        let mut keys = wem_keys.iter();
+        pck_header
+            .wem_entries
+            .retain(|_| !removed_keys.contains(keys.next().unwrap()));
+        wem_keys.retain(|key| !removed_keys.contains(key));
+        for (_, id) in &removed_keys {
+            info!(
+                code = "entry-removed", entry = *id;
+                "{}: Wem file '{}' removed.",
+                "Remove".cyan(),
+                id
+            );
+        }
+
+        // update header BNK entries
+        info!("Updating BNK entries...");
+        let mut drop_bnk_idx_list = vec![];
+        for (i, entry) in pck_header.bnk_entries.iter().enumerate() {
+            if !bnk_metadata_map.contains_key(&entry.id) {
+                drop_bnk_idx_list.push(i);
+            }
+        }
+        for i in drop_bnk_idx_list.iter().rev() {
+            let entry = pck_header.bnk_entries.remove(*i);
+            warn!(
+                "BNK file {} included in original PCK, but not found in project, removed.",
+                entry.id
+            );
+        }
+        // BKHD language of banks should match their language in PCK
+        for entry in &pck_header.bnk_entries {
+            let metadata = bnk_metadata_map.get(&entry.id).unwrap();
+            let header = match (&metadata.data, &metadata.file_path) {
+                (Some(data), _) => bnk::Bnk::read_header(&mut io::Cursor::new(data)),
+                (None, Some(file_path)) => {
+                    bnk::Bnk::read_header(&mut io::BufReader::new(File::open(file_path)?))
+                }
+                (None, None) => return Err(ProjectError::MissingEntryData(metadata.idx)),
+            };
+            let language_id = match header {
+                Ok(bnk::Section {
+                    payload:
+                        bnk::SectionPayload::Bkhd {
+                            language_id: Some(language_id),
+                            ..
+                        },
+                    ..
+                }) => language_id,
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!("Failed to read header of BNK file {}: {}", entry.id, e);
+                    continue;
+                }
+            };
+            let Some(language) = pck_header
+                .string_table
+                .iter()
+                .find(|string| string.index == entry.language_id)
+            else {
+                continue;
+            };
+            if language_id != utils::fnv1_32(&language.value) {
+                warn!(
+                    "BNK file {} has language ID {} in BKHD, which does not match its language '{}' in PCK.",
+                    entry.id, language_id, language.value
+                );
+            }
+        }
+        // update header WEM entries
+        print!("Updating WEM entries...");
+        let mut drop_wem_idx_list = vec![];
+        for (i, key) in wem_keys.iter().enumerate() {
+            if !wem_metadata_map.contains_key(key) {
+                drop_wem_idx_list.push(i);
+            }
+        }
+        for i in drop_wem_idx_list.iter().rev() {
+            wem_keys.remove(*i);
+            let entry = pck_header.wem_entries.remove(*i);
+            warn!(
+                "Wem file {} included in original PCK, but not found in project, removed.",
+                entry.id
+            );
+        }
+        if !drop_wem_idx_list.is_empty() || !drop_bnk_idx_list.is_empty() {
+            warn!(
+                "Entry count changed, will affect the original order ID, please use unique ID as reference."
+            );
+        }
+        // add new wems, keeping IDs sorted if they were
+        let ids_sorted = pck_header.wem_entries.is_sorted_by_key(|entry| entry.id);
+        let block_size = pck_header
+            .wem_entries
+            .iter()
+            .chain(&pck_header.bnk_entries)
+            .map(|entry| entry.padding_block_size)
+            .next()
+            .unwrap_or_default();
+        let first_idx = wem_metadata_map
+            .keys()
+            .map(|&(idx, _)| idx + 1)
+            .max()
+            .unwrap_or_default();
+        for (idx, addition) in (first_idx..).zip(load_additions(&self.project_path, &manifest)?) {
+            let language_id = match &addition.language {
+                None => 0,
+                Some(language) => pck_header
+                    .language_id(language)
+                    .ok_or_else(|| ProjectError::UnknownLanguage(language.clone()))?,
+            };
+            if wem_metadata_map.keys().any(|&(_, id)| id == addition.id) {
+                return Err(ProjectError::AdditionExists(addition.id));
+            }
+            let entry = pck::PckFileEntry {
+                id: addition.id,
+                padding_block_size: block_size,
+                length: ByteLen(0),
+                offset: DataOffset::ZERO,
+                language_id,
+            };
+            let position = if ids_sorted {
+                pck_header
+                    .wem_entries
+                    .partition_point(|other| other.id < addition.id)
+            } else {
+                pck_header.wem_entries.len()
+            };
+            pck_header.wem_entries.insert(position, entry);
+            wem_keys.insert(position, (idx, addition.id));
+            info!(
+                code = "entry-added", entry = addition.id;
+                "{}: Wem file '{}' added.",
+                "Add".cyan(),
+                addition.id
+            );
+            wem_metadata_map.insert(
+                (idx, addition.id),
+                WemMetadata {
+                    idx,
+                    file_size: addition.data.len() as u32,
+                    file_path: None,
+                    data: Some(addition.data),
+                },
+            );
+        }
+        // identical wem data is stored once, later entries point at the first copy
+        let mut shared_with: Vec<Option<usize>> = vec![None; pck_header.wem_entries.len()];
+        if manifest.share_duplicates {
+            let mut first_by_hash = HashMap::new();
+            for (i, (entry, key)) in pck_header.wem_entries.iter().zip(&wem_keys).enumerate() {
+                let metadata = wem_metadata_map.get(key).unwrap();
+                let hash = match (&metadata.data, &metadata.file_path) {
+                    (Some(data), _) => Sha256::digest(data),
+                    (None, Some(file_path)) => Sha256::digest(fs::read(file_path)?),
+                    (None, None) => return Err(ProjectError::MissingEntryData(metadata.idx)),
+                };
+                // offsets are in units of block size, which must match
+                match first_by_hash.get(&(hash, entry.block_size())) {
+                    Some(&first) => shared_with[i] = Some(first),
+                    None => {
+                        first_by_hash.insert((hash, entry.block_size()), i);
+                    }
+                }
+            }
+            let shared: Vec<&(u32, u32)> = wem_keys
+                .iter()
+                .zip(&shared_with)
+                .filter_map(|(key, first)| first.map(|_| key))
+                .collect();
+            if !shared.is_empty() {
+                let saved: u64 = shared
+                    .iter()
+                    .map(|key| wem_metadata_map.get(*key).unwrap().file_size as u64)
+                    .sum();
+                info!(
+                    "{}: {} wem entries share identical data, {} bytes saved.",
+                    "Share".cyan(),
+                    shared.len(),
+                    saved
+                );
+            }
+        }
+        // calculate offsets and lengths
+        info!("Calculating offsets and lengths for BNK and WEM entries...");
+        let mut lengths = vec![];
+        for entry in &pck_header.bnk_entries {
+            let metadata = bnk_metadata_map.get(&entry.id).unwrap();
+            lengths.push((ByteLen(metadata.file_size), entry.block_size()));
+        }
+        for (i, (entry, key)) in pck_header.wem_entries.iter().zip(&wem_keys).enumerate() {
+            let metadata = wem_metadata_map.get(key).unwrap();
+            if shared_with[i].is_none() {
+                lengths.push((ByteLen(metadata.file_size), entry.block_size()));
+            }
+        }
+        let (offsets, _) =
+            layout::place_blocks(pck_header.get_data_offset_start(), lengths.clone())
+                .ok_or(pck::PckError::DataTooLarge)?;
+        let mut placed = offsets.into_iter().zip(lengths);
+        for entry in pck_header.bnk_entries.iter_mut() {
+            let (offset, (length, _)) = placed.next().unwrap();
+            entry.set_data(offset, length);
+        }
+        let mut wem_data: Vec<(DataOffset, ByteLen)> = vec![];
+        for first in &shared_with {
+            let data = match first {
+                Some(first) => wem_data[*first],
+                None => {
+                    let (offset, (length, _)) = placed.next().unwrap();
+                    (offset, length)
+                }
+            };
+            wem_data.push(data);
+        }
+        for (entry, (offset, length)) in pck_header.wem_entries.iter_mut().zip(wem_data) {
+            entry.set_data(offset, length);
+        }
+        info!("Writing PCK header and data...");
+        let output_path = policy.output_path(output_root, &self.source_file_name);
+        // write header and data
+        let staged = policy.stage(&output_path);
+        let output_file = File::create(staged.path())?;
+        let mut writer = io::BufWriter::new(output_file);
+        pck_header.write_to(&mut writer)?;
+        // write BNK and WEM
+        for entry in &pck_header.bnk_entries {
+            // alignment
+            let cur_pos = DataOffset(writer.stream_position()? as u32);
+            let padding = entry
+                .data_offset()
+                .distance_from(cur_pos)
+                .unwrap_or_default();
+            layout::write_padding(&mut writer, padding)?;
+            // write data
+            let metadata = bnk_metadata_map.get(&entry.id).unwrap();
+            if let Some(data) = &metadata.data {
+                writer.write_all(data)?;
+            } else if let Some(file_path) = &metadata.file_path {
+                let mut input_file = File::open(file_path)?;
+                io::copy(&mut input_file, &mut writer)?;
+            } else {
+                return Err(ProjectError::MissingEntryData(metadata.idx));
+            }
+
+            // padded to the entry length
+            let padding = entry.length.get().saturating_sub(metadata.file_size);
+            layout::write_padding(&mut writer, ByteLen(padding))?;
+        }
+        for (i, (entry, key)) in pck_header.wem_entries.iter().zip(&wem_keys).enumerate() {
+            if shared_with[i].is_some() {
+                continue;
+            }
+            // alignment
+            let cur_pos = DataOffset(writer.stream_position()? as u32);
+            let padding = entry
+                .data_offset()
+                .distance_from(cur_pos)
+                .unwrap_or_default();
+            layout::write_padding(&mut writer, padding)?;
+            // write data
+            let metadata = wem_metadata_map.get(key).unwrap();
+            if let Some(data) = &metadata.data {
+                writer.write_all(data)?;
+            } else if let Some(file_path) = &metadata.file_path {
+                let mut input_file = File::open(file_path)?;
+                io::copy(&mut input_file, &mut writer)?;
+            } else {
+                return Err(ProjectError::MissingEntryData(metadata.idx));
+            }
+            // padded to the entry length
+            let padding = entry.length.get().saturating_sub(metadata.file_size);
+            layout::write_padding(&mut writer, ByteLen(padding))?;
+        }
+        writer.flush()?;
+        drop(writer);
+        stats.end_phase("Write", &mut phase_start);
+        // wem entries with data in memory are replaced, added or from the media store
+        let expected_entry =
+            |kind, entry: &pck::PckFileEntry, data: Option<&Vec<u8>>| ExpectedEntry {
+                kind,
+                id: entry.id,
+                size: entry.length.get(),
+                offset: entry.offset.get(),
+                sha256: data.map(|data| Sha256::digest(data).into()),
+            };
+        let expected: Vec<ExpectedEntry> = pck_header
+            .bnk_entries
+            .iter()
+            .map(|entry| expected_entry("bnk", entry, None))
+            .chain(
+                pck_header
+                    .wem_entries
+                    .iter()
+                    .zip(&wem_keys)
+                    .map(|(entry, key)| {
+                        let data = wem_metadata_map.get(key).unwrap().data.as_ref();
+                        expected_entry("wem", entry, data)
+                    }),
+            )
+            .collect();
+        check_written(staged.path(), &expected)?;
+        staged.place()?;
+        stats.end_phase("Verify", &mut phase_start);
+
+        info!(
+            code = "output-written", file:% = output_path.display();
+            "Output: {}",
+            output_path.display()
+        );
+        stats.entry_ids = pck_header
+            .wem_entries
+            .iter()
+            .map(|entry| entry.id)
+            .collect();
+        stats.output_size = fs::metadata(&output_path)?.len();
+        stats.output_path = output_path;
+
+        Ok(stats)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "IdOrIndexRepr", into = "IdOrIndexRepr")]
+pub enum IdOrIndex {
+    Id(u32),
+    Index(u32),
+}
+
+/// Serialized form of [`IdOrIndex`], a number for ID, or a string like `"[3]"`.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum IdOrIndexRepr {
+    Id(u32),
+    Text(String),
+}
+
+impl TryFrom<IdOrIndexRepr> for IdOrIndex {
+    type Error = String;
+
+    fn try_from(value: IdOrIndexRepr) -> std::result::Result<Self, Self::Error> {
+        match value {
+            IdOrIndexRepr::Id(id) => Ok(IdOrIndex::Id(id)),
+            IdOrIndexRepr::Text(text) => IdOrIndex::parse(text.trim())
+                .ok_or_else(|| format!("expected ID or [index], found '{}'", text)),
+        }
+    }
+}
+
+impl From<IdOrIndex> for IdOrIndexRepr {
+    fn from(value: IdOrIndex) -> Self {
+        match value {
+            IdOrIndex::Id(id) => IdOrIndexRepr::Id(id),
+            IdOrIndex::Index(_) => IdOrIndexRepr::Text(value.to_string()),
+        }
+    }
+}
+
+impl IdOrIndex {
+    /// Parse `"[3]"` as index, or a number as ID.
+    pub fn parse(s: &str) -> Option<Self> {
+        if s.starts_with('[') && s.ends_with(']') {
+            s[1..s.len() - 1].parse().ok().map(IdOrIndex::Index)
+        } else {
+            s.parse().ok().map(IdOrIndex::Id)
+        }
+    }
+
+    fn _to_string(&self) -> String {
+        match self {
+            IdOrIndex::Id(id) => id.to_string(),
+            IdOrIndex::Index(index) => format!("[{}]", index),
+        }
+    }
+}
+
+impl std::fmt::Display for IdOrIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self._to_string())
+    }
+}
+
+/// Entry of a BNK or PCK file, listed without extracting.
+#[derive(Debug, Clone, Serialize)]
+pub struct BundleEntry {
+    /// `wem` or `bnk`, PCK files contain both.
+    pub kind: &'static str,
+    pub index: u32,
+    pub id: u32,
+    pub size: u32,
+    /// Offset of entry data, relative to DATA section in BNK files.
+    pub offset: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Duration in seconds of wem entries, parsed from `fmt ` chunk.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f32>,
+}
+
+/// Sort key of listed entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntrySort {
+    Index,
+    Id,
+    /// Largest first.
+    Size,
+    /// Longest first, entries without duration last.
+    Duration,
+}
+
+impl EntrySort {
+    pub fn sort(self, entries: &mut [BundleEntry]) {
+        match self {
+            EntrySort::Index => entries.sort_by_key(|entry| (entry.kind, entry.index)),
+            EntrySort::Id => entries.sort_by_key(|entry| entry.id),
+            EntrySort::Size => entries.sort_by_key(|entry| std::cmp::Reverse(entry.size)),
+            EntrySort::Duration => entries.sort_by(|a, b| {
+                let duration = |entry: &BundleEntry| entry.duration.unwrap_or(-1.0);
+                duration(b).total_cmp(&duration(a))
+            }),
+        }
+    }
+}
+
+/// Bytes read from the start of a PCK entry to find its duration.
+const DURATION_HEADER_SIZE: u32 = 4096;
+
+/// List entries of a bnk or pck file from its header.
+pub fn list_entries(input: impl AsRef<Path>) -> Result<Vec<BundleEntry>> {
+    let input = input.as_ref();
+    let mut reader = io::BufReader::new(File::open(input).map_err(ProjectError::file(input))?);
+    match InputFileType::from_path(input) {
+        Some(InputFileType::Bnk) => {
+            let bank = bnk::Bnk::from_reader(&mut reader)?;
+            let entries = bank
+                .sections
+                .iter()
+                .find_map(|section| match &section.payload {
+                    bnk::SectionPayload::Didx { entries } => Some(entries),
+                    _ => None,
+                });
+            let data_list = bank
+                .sections
+                .iter()
+                .find_map(|section| match &section.payload {
+                    bnk::SectionPayload::Data { data_list } => Some(data_list),
+                    _ => None,
+                });
+            Ok(entries
+                .into_iter()
+                .flatten()
+                .enumerate()
+                .map(|(i, entry)| BundleEntry {
+                    kind: "wem",
+                    index: i as u32,
+                    id: entry.id,
+                    size: entry.length.get(),
+                    offset: entry.offset.get(),
+                    language: None,
+                    duration: data_list
+                        .and_then(|data_list| data_list.get(i))
+                        .and_then(|data| wwise::wem_duration(data)),
+                })
+                .collect())
+        }
+        Some(InputFileType::Pck) => {
+            let pck = pck::PckHeader::from_reader(&mut reader)?;
+            let language = |language_id: u32| {
+                pck.string_table
+                    .iter()
+                    .find(|string| string.index == language_id)
+                    .map(|string| string.value.clone())
+            };
+            let mut entries = vec![];
+            for info in pck.entries() {
+                let entry = info.entry;
+                let (kind, duration) = match info.kind {
+                    pck::PckEntryKind::Bnk => ("bnk", None),
+                    pck::PckEntryKind::Wem => {
+                        let mut header =
+                            vec![0; entry.length.get().min(DURATION_HEADER_SIZE) as usize];
+                        reader.seek(io::SeekFrom::Start(info.position))?;
+                        reader.read_exact(&mut header)?;
+                        ("wem", wwise::wem_duration(&header))
+                    }
+                };
+                entries.push(BundleEntry {
+                    kind,
+                    index: info.index as u32,
+                    id: entry.id,
+                    size: entry.length.get(),
+                    offset: entry.offset.get(),
+                    language: language(entry.language_id),
+                    duration,
+                });
+            }
+            Ok(entries)
+        }
+        _ => Err(ProjectError::UnsupportedInput(input.to_path_buf())),
+    }
+}
+
+/// Entry expected in a written bundle.
+struct ExpectedEntry {
+    kind: &'static str,
+    id: u32,
+    size: u32,
+    offset: u32,
+    /// Hash of the data written, for wem entries to spot-check.
+    sha256: Option<[u8; 32]>,
+}
+
+/// Re-read a written bundle before it is moved into place, and check its
+/// entries and the data of spot-checked entries. A bundle failing the check
+/// is removed.
+fn check_written(path: &Path, expected: &[ExpectedEntry]) -> Result<()> {
+    let fail = |reason: String| {
+        let _ = fs::remove_file(path);
+        ProjectError::OutputCheck {
+            path: path.to_path_buf(),
+            reason,
+        }
+    };
+    let entries = list_entries(path).map_err(|e| fail(e.to_string()))?;
+    if entries.len() != expected.len() {
+        return Err(fail(format!(
+            "{} entries, expected {}",
+            entries.len(),
+            expected.len()
+        )));
+    }
+    let mut targets = vec![];
+    let mut hashes = vec![];
+    for (entry, expected) in entries.iter().zip(expected) {
+        if (entry.kind, entry.id, entry.size, entry.offset)
+            != (expected.kind, expected.id, expected.size, expected.offset)
+        {
+            return Err(fail(format!(
+                "{} [{}] is {} {} bytes at {}, expected {} {} bytes at {}",
+                entry.kind,
+                entry.index,
+                entry.id,
+                entry.size,
+                entry.offset,
+                expected.id,
+                expected.size,
+                expected.offset
+            )));
+        }
+        if let Some(sha256) = expected.sha256 {
+            targets.push(IdOrIndex::Index(entry.index));
+            hashes.push(sha256);
+        }
+    }
+    let written = read_entries(path, &targets).map_err(|e| fail(e.to_string()))?;
+    for (entry, sha256) in written.iter().zip(hashes) {
+        if <[u8; 32]>::from(Sha256::digest(&entry.data)) != sha256 {
+            return Err(fail(format!(
+                "data of wem [{}] {} differs from the data written",
+                entry.idx, entry.id
+            )));
+        }
+    }
+    debug!("Verified {} entries of {}", entries.len(), path.display());
+    Ok(())
+}
+
+/// IDs of wem entries in a bnk or pck file, in index order.
+pub fn entry_ids(input: impl AsRef<Path>) -> Result<Vec<u32>> {
+    Ok(list_entries(input)?
+        .into_iter()
+        .filter(|entry| entry.kind == "wem")
+        .map(|entry| entry.id)
+        .collect())
+}
+
+/// Read the original data of an entry from a project directory, bnk or pck file.
+pub fn read_entry(input: impl AsRef<Path>, target: IdOrIndex) -> Result<Vec<u8>> {
+    let input = input.as_ref();
+    if InputFileType::from_path(input) == Some(InputFileType::Wem) {
+        return Ok(fs::read(input)?);
+    }
+    let mut entries = read_entries(input, &[target])?;
+    Ok(entries.remove(0).data)
+}
+
+/// Read the original data of entries from a project directory, bnk or pck file,
+/// in order of `targets`. The bundle is read once for all targets.
+pub fn read_entries(input: impl AsRef<Path>, targets: &[IdOrIndex]) -> Result<Vec<EntryData>> {
+    let input = input.as_ref();
+    let file_type = InputFileType::from_path(input)
+        .ok_or_else(|| ProjectError::UnsupportedInput(input.to_path_buf()))?;
+    let mut found: Vec<Option<EntryData>> = vec![None; targets.len()];
+    let mut visit = |idx: u32, id: u32, read: &mut dyn FnMut() -> Result<Vec<u8>>| -> Result<()> {
+        let mut data = None;
+        for (target, slot) in targets.iter().zip(found.iter_mut()) {
+            let matched = match *target {
+                IdOrIndex::Id(target_id) => target_id == id,
+                IdOrIndex::Index(target_idx) => target_idx == idx,
+            };
+            if !matched || slot.is_some() {
+                continue;
+            }
+            if data.is_none() {
+                data = Some(read()?);
+            }
+            *slot = Some(EntryData {
+                idx,
+                id,
+                data: data.clone().unwrap(),
+            });
+        }
+        Ok(())
+    };
+
+    match file_type {
+        InputFileType::Project => {
+            let store = open_store(input)?;
+            for file in list_dumped_files(input, store.as_ref(), "wem")? {
+                visit(file.idx, file.id, &mut || file.read(store.as_ref()))?;
+            }
+        }
+        InputFileType::Bnk => {
+            let mut reader =
+                io::BufReader::new(File::open(input).map_err(ProjectError::file(input))?);
+            let bank = bnk::Bnk::from_reader(&mut reader)?;
+            let mut didx_entries: &[bnk::DidxEntry] = &[];
+            for section in &bank.sections {
+                match &section.payload {
+                    bnk::SectionPayload::Didx { entries } => didx_entries = entries,
+                    bnk::SectionPayload::Data { data_list } => {
+                        for (idx, (entry, data)) in didx_entries.iter().zip(data_list).enumerate() {
+                            visit(idx as u32, entry.id, &mut || Ok(data.clone()))?;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        InputFileType::Pck => {
+            let mut reader =
+                io::BufReader::new(File::open(input).map_err(ProjectError::file(input))?);
+            let pck = pck::PckHeader::from_reader(&mut reader)?;
+            for (idx, entry) in pck.wem_entries.iter().enumerate() {
+                visit(idx as u32, entry.id, &mut || {
+                    let mut data = vec![];
+                    pck.wem_reader(&mut reader, idx)
+                        .unwrap()
+                        .read_to_end(&mut data)?;
+                    Ok(data)
+                })?;
+            }
+        }
+        _ => return Err(ProjectError::UnsupportedInput(input.to_path_buf())),
+    }
+
+    targets
+        .iter()
+        .zip(found)
+        .map(|(&target, entry)| {
+            entry.ok_or_else(|| ProjectError::EntryNotFound {
+                target,
+                path: input.to_path_buf(),
+            })
+        })
+        .collect()
+}
+
+/// Read all wem entries of a language from a pck file.
+pub fn read_language_entries(input: impl AsRef<Path>, language: &str) -> Result<Vec<EntryData>> {
+    let input = input.as_ref();
+    if InputFileType::from_path(input) != Some(InputFileType::Pck) {
+        return Err(ProjectError::UnsupportedInput(input.to_path_buf()));
+    }
+    let mut reader = io::BufReader::new(File::open(input).map_err(ProjectError::file(input))?);
+    let pck = pck::PckHeader::from_reader(&mut reader)?;
+    let language_id = pck
+        .language_id(language)
+        .ok_or_else(|| ProjectError::LanguageNotFound {
+            language: language.to_string(),
+            path: input.to_path_buf(),
+        })?;
+    pck.entries_for_language(language_id)
+        .filter(|info| info.kind == pck::PckEntryKind::Wem)
+        .map(|info| {
+            let mut data = vec![];
+            info.reader(&mut reader).read_to_end(&mut data)?;
+            Ok(EntryData {
+                idx: info.index as u32,
+                id: info.entry.id,
+                data,
+            })
+        })
+        .collect()
+}
+
+/// Data of an entry read from a project or bundle, see [`read_entries`].
+#[derive(Debug, Clone)]
+pub struct EntryData {
+    pub idx: u32,
+    pub id: u32,
+    pub data: Vec<u8>,
+}
+
+/// Wem entry in a dumped project.
+#[derive(Debug, Clone)]
+pub struct MediaEntry {
+    pub idx: u32,
+    pub id: u32,
+    /// Language name, only known for PCK entries.
+    pub language: Option<String>,
+    /// File name in project folder or media store.
+    pub file_name: String,
+}
+
+/// Summary of a repack.
+#[derive(Debug, Default)]
+pub struct RepackStats {
+    pub output_path: PathBuf,
+    /// Wem entry IDs in output, in order.
+    pub entry_ids: Vec<u32>,
+    pub replaced_by_id: usize,
+    pub replaced_by_index: usize,
+    /// Entries compared with the original bundle, `None` if it is not found.
+    pub added: Option<usize>,
+    pub removed: Option<usize>,
+    pub output_size: u64,
+    pub original_size: Option<u64>,
+    /// Elapsed time of each phase.
+    pub phases: Vec<(&'static str, Duration)>,
+}
+
+impl RepackStats {
+    fn end_phase(&mut self, name: &'static str, start: &mut Instant) {
+        self.phases.push((name, start.elapsed()));
+        *start = Instant::now();
+    }
+
+    pub fn replaced(&self) -> usize {
+        self.replaced_by_id + self.replaced_by_index
+    }
+
+    /// Output size minus original size.
+    pub fn size_delta(&self) -> Option<i64> {
+        self.original_size
+            .map(|original| self.output_size as i64 - original as i64)
+    }
+
+    /// Print the summary table.
+    pub fn log_summary(&self) {
+        let unknown = || "-".to_string();
+        let rows = [
+            ("Entries", self.entry_ids.len().to_string()),
+            (
+                "Replaced",
+                format!(
+                    "{} ({} by ID, {} by index)",
+                    self.replaced(),
+                    self.replaced_by_id,
+                    self.replaced_by_index
+                ),
+            ),
+            ("Added", self.added.map_or_else(unknown, |n| n.to_string())),
+            (
+                "Removed",
+                self.removed.map_or_else(unknown, |n| n.to_string()),
+            ),
+            (
+                "Size",
+                match self.size_delta() {
+                    Some(delta) => format!("{} bytes ({:+})", self.output_size, delta),
+                    None => format!("{} bytes", self.output_size),
+                },
+            ),
+            (
+                "Time",
+                self.phases
+                    .iter()
+                    .map(|(name, elapsed)| format!("{} {:.2}s", name, elapsed.as_secs_f32()))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+        ];
+        info!("{}", "Summary".cyan());
+        for (name, value) in rows {
+            info!("  {:<10}{}", name, value);
+        }
+    }
+}
+
+/// Entry changes found when rebasing a project onto an updated bundle.
+#[derive(Debug, Default)]
+pub struct RebaseReport {
+    /// IDs of entries no longer in the bundle.
+    pub removed: Vec<u32>,
+    /// IDs of entries new in the bundle.
+    pub added: Vec<u32>,
+    /// Entries with identical data under a new ID, `(old, new)`.
+    pub renamed: Vec<(u32, u32)>,
+    /// Replace files moved to a new target, `(old, new)`.
+    pub remapped: Vec<(IdOrIndex, IdOrIndex)>,
+    /// Replace files of removed entries, moved to `orphaned/`.
+    pub orphaned: Vec<PathBuf>,
+    /// Nested projects of removed banks, moved to `orphaned/banks/`.
+    pub orphaned_banks: Vec<PathBuf>,
+    /// Replace directives in `project.toml` whose target changed.
+    pub stale_directives: Vec<IdOrIndex>,
+}
+
+impl RebaseReport {
+    fn log(&self) {
+        info!(
+            "{}: {} removed, {} added, {} changed ID.",
+            "Rebase".cyan(),
+            self.removed.len(),
+            self.added.len(),
+            self.renamed.len()
+        );
+        for id in &self.removed {
+            warn!("Entry {} no longer exists.", id);
+        }
+        for (old, new) in &self.renamed {
+            info!("{}: Entry {} -> {}", "Rebase".cyan(), old, new);
+        }
+        for (old, new) in &self.remapped {
+            info!("{}: Replace file {} -> {}", "Rebase".cyan(), old, new);
+        }
+        for file in &self.orphaned {
+            warn!(
+                "Replace file '{}' has no matching entry, moved to orphaned/.",
+                file.display()
+            );
+        }
+        for project in &self.orphaned_banks {
+            warn!(
+                "Nested project '{}' has no matching bank, moved to orphaned/.",
+                project.display()
+            );
+        }
+        for target in &self.stale_directives {
+            warn!(
+                "Replace directive for {} in {} needs update.",
+                target,
+                crate::manifest::MANIFEST_FILE_NAME
+            );
+        }
+    }
+}
+
+/// Mapping of entries from an old project to an updated one.
+struct EntryMapping {
+    /// Old index to old ID.
+    old_ids: HashMap<u32, u32>,
+    /// Old ID to old indices in order, more than one for duplicate IDs.
+    old_indices: HashMap<u32, Vec<u32>>,
+    /// New ID to new indices in order.
+    new_indices: HashMap<u32, Vec<u32>>,
+    /// Old ID to new ID, for entries that changed ID.
+    renamed: HashMap<u32, u32>,
+}
+
+impl EntryMapping {
+    /// Compare entries and fill in removed, added and renamed entries of the report.
+    ///
+    /// Entries are matched by ID, then removed and added entries by identical data.
+    /// Entries sharing an ID are matched in index order.
+    fn build(
+        old_entries: &[DumpedFile],
+        old_store: Option<&MediaStore>,
+        new_entries: &[DumpedFile],
+        report: &mut RebaseReport,
+    ) -> Result<Self> {
+        let old_ids: HashMap<u32, u32> = old_entries.iter().map(|f| (f.idx, f.id)).collect();
+        let indices_by_id = |entries: &[DumpedFile]| {
+            let mut indices: HashMap<u32, Vec<u32>> = HashMap::new();
+            for file in entries {
+                indices.entry(file.id).or_default().push(file.idx);
+            }
+            indices.values_mut().for_each(|indices| indices.sort());
+            indices
+        };
+        let old_indices = indices_by_id(old_entries);
+        let new_indices = indices_by_id(new_entries);
+
+        let mut added = HashMap::new();
+        for file in new_entries {
+            if !old_ids.values().any(|&id| id == file.id) {
+                added.insert(Sha256::digest(file.read(None)?), file);
+            }
+        }
+        let mut renamed = HashMap::new();
+        let mut old_sorted: Vec<&DumpedFile> = old_entries.iter().collect();
+        old_sorted.sort_by_key(|file| file.idx);
+        for file in old_sorted {
+            if new_indices.contains_key(&file.id) {
+                continue;
+            }
+            let hash = Sha256::digest(file.read(old_store)?);
+            match added.remove(&hash) {
+                Some(new_file) => {
+                    renamed.insert(file.id, new_file.id);
+                    report.renamed.push((file.id, new_file.id));
+                }
+                None => report.removed.push(file.id),
+            }
+        }
+        let mut added: Vec<&DumpedFile> = added.into_values().collect();
+        added.sort_by_key(|file| file.idx);
+        report.added = added.into_iter().map(|file| file.id).collect();
+
+        Ok(Self {
+            old_ids,
+            old_indices,
+            new_indices,
+            renamed,
+        })
+    }
+
+    /// Target in the new project, `None` if the entry was removed.
+    fn map(&self, target: IdOrIndex) -> Option<IdOrIndex> {
+        let (old_id, nth) = match target {
+            IdOrIndex::Id(id) => (id, 0),
+            IdOrIndex::Index(idx) => {
+                let id = *self.old_ids.get(&idx)?;
+                let nth = self.old_indices[&id].iter().position(|&i| i == idx)?;
+                (id, nth)
+            }
+        };
+        let new_id = self.renamed.get(&old_id).copied().unwrap_or(old_id);
+        let new_idx = *self.new_indices.get(&new_id)?.get(nth)?;
+        match target {
+            IdOrIndex::Id(_) => Some(IdOrIndex::Id(new_id)),
+            IdOrIndex::Index(_) => Some(IdOrIndex::Index(new_idx)),
+        }
+    }
+}
+
+/// Whether a file in project folder is created by dumping.
+fn is_generated_file(name: &str, metadata_file: &str) -> bool {
+    let path = Path::new(name);
+    let ext = path.extension().unwrap_or_default();
+    name == "project.json"
+        || name == metadata_file
+        || name == order::ORDER_FILE_NAME
+        || name == formats::FORMATS_FILE_NAME
+        || name == DECODED_DIR
+        || name == store::STORE_FILE_NAME
+        || name == store::STORE_INDEX_FILE_NAME
+        || ((ext == "wem" || ext == "bnk")
+            && parse_wem_name(&path.file_stem().unwrap().to_string_lossy()).is_ok())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// First unused backup path of a folder, `<path>.bak`, `<path>.bak1`, ...
+fn backup_path(path: &Path) -> PathBuf {
+    let path = path.to_string_lossy();
+    let mut backup = PathBuf::from(format!("{}.bak", path));
+    let mut n = 1;
+    while backup.exists() {
+        backup = PathBuf::from(format!("{}.bak{}", path, n));
+        n += 1;
+    }
+    backup
+}
+
+/// Dumped entry file of a project, stored loose or in the media store.
+struct DumpedFile {
+    name: String,
+    idx: u32,
+    id: u32,
+    size: u64,
+    /// Path of loose file, `None` if in media store.
+    path: Option<PathBuf>,
+}
+
+impl DumpedFile {
+    /// Name appended to the file name by [`SoundToolProject::annotate_names`].
+    fn annotated_name(&self) -> Option<String> {
+        let file_stem = Path::new(&self.name).file_stem()?.to_string_lossy();
+        let prefix = REG_WEM_NAME.find(&file_stem)?;
+        let name = file_stem[prefix.end()..].strip_prefix('_')?;
+        (!name.is_empty()).then(|| name.to_string())
+    }
+
+    fn read(&self, store: Option<&MediaStore>) -> Result<Vec<u8>> {
+        match (&self.path, store) {
+            (Some(path), _) => fs::read(path).map_err(ProjectError::file(path)),
+            (None, Some(store)) => Ok(store.read(&self.name)?),
+            (None, None) => Err(StoreError::FileNotFound(self.name.clone()).into()),
+        }
+    }
+}
+
+/// List dumped files with the extension, loose files take precedence over stored ones.
+fn list_dumped_files(
+    project_path: &Path,
+    store: Option<&MediaStore>,
+    ext: &str,
+) -> Result<Vec<DumpedFile>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(project_path)? {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().unwrap_or_default() != ext {
+            continue;
+        }
+        let file_stem = path.file_stem().unwrap().to_string_lossy();
+        let (idx, id) = parse_wem_name(&file_stem)?;
+        files.push(DumpedFile {
+            name: path.file_name().unwrap().to_string_lossy().to_string(),
+            idx,
+            id,
+            size: path.metadata()?.len(),
+            path: Some(path),
+        });
+    }
+    if let Some(store) = store {
+        for stored in store.files() {
+            let name_path = Path::new(&stored.name);
+            if name_path.extension().unwrap_or_default() != ext
+                || files.iter().any(|file| file.name == stored.name)
+            {
+                continue;
+            }
+            let file_stem = name_path.file_stem().unwrap().to_string_lossy();
+            let (idx, id) = parse_wem_name(&file_stem)?;
+            files.push(DumpedFile {
+                name: stored.name.clone(),
+                idx,
+                id,
+                size: store.file_size(&stored.name).unwrap_or_default(),
+                path: None,
+            });
+        }
+    }
+    Ok(files)
+}
+
+/// Rebase the nested projects of `old_banks_dir` onto the banks of a newly
+/// dumped PCK project, see [`SoundToolProject::dump_nested_banks`].
+///
+/// Banks without a nested project before get a new one, nested projects of
+/// banks no longer in the PCK are moved to `orphaned/banks/`.
+fn rebase_nested_banks(
+    new_project: &SoundToolProject,
+    old_banks_dir: &Path,
+    report: &mut RebaseReport,
+) -> Result<()> {
+    let new_banks_dir = new_project.project_path().join(BANKS_DIR);
+    let mut rebased = HashSet::new();
+    for bank_path in new_project.extract_banks()? {
+        let nested_name = with_suffix(Path::new(bank_path.file_name().unwrap()), ".project");
+        let old_nested_path = old_banks_dir.join(&nested_name);
+        if old_nested_path.join("project.json").is_file() {
+            SoundToolProject::from_path(&old_nested_path)?.rebase(&bank_path, &new_banks_dir)?;
+            rebased.insert(nested_name);
+        } else {
+            SoundToolProject::dump_bnk(&bank_path, &new_banks_dir)?;
+        }
+    }
+    for entry in fs::read_dir(old_banks_dir).map_err(ProjectError::file(old_banks_dir))? {
+        let entry = entry.map_err(ProjectError::file(old_banks_dir))?;
+        let name = PathBuf::from(entry.file_name());
+        if rebased.contains(&name) || !entry.path().join("project.json").is_file() {
+            continue;
+        }
+        let relative = Path::new(BANKS_DIR).join(&name);
+        let dest = new_project.project_path().join("orphaned").join(&relative);
+        utils::copy_dir_all(entry.path(), &dest).map_err(ProjectError::file(entry.path()))?;
+        report.orphaned_banks.push(relative);
+    }
+    Ok(())
+}
+
+/// Data of a bank in PCK repacked from its nested project, `None` if the
+/// bank has no nested project, see [`SoundToolProject::dump_nested_banks`].
+fn repack_nested_bank(project_path: &Path, id: u32) -> Result<Option<Vec<u8>>> {
+    let nested_path = project_path
+        .join(BANKS_DIR)
+        .join(format!("{}.bnk.project", id));
+    if !nested_path.join("project.json").is_file() {
+        return Ok(None);
+    }
+    let output_root = tempfile::tempdir()?;
+    let stats = SoundToolProject::from_path(&nested_path)?.repack(output_root.path())?;
+    info!(
+        "{}: Bank '{}' repacked from {}.",
+        "Nested".cyan(),
+        id,
+        nested_path.display()
+    );
+    Ok(Some(fs::read(&stats.output_path)?))
+}
+
+/// Check dumped files against the order recorded on dump, see [`crate::order`].
+fn check_order(project_path: &Path, ext: &str, files: &[DumpedFile]) -> Result<()> {
+    let Some(order) = EntryOrder::load(project_path)? else {
+        debug!("No entry order recorded, dumped files are trusted.");
+        return Ok(());
+    };
+    order.check(
+        ext,
+        files
+            .iter()
+            .map(|file| (file.name.as_str(), file.idx, file.id)),
+    )?;
+    Ok(())
+}
+
+/// 解析Wem名，返回 (index, id)
+fn parse_wem_name(name: &str) -> Result<(u32, u32)> {
+    let name = name.trim();
+    if let Some(captures) = REG_WEM_NAME.captures(name) {
+        let idx = captures.get(1).and_then(|m| m.as_str().parse::<u32>().ok());
+        let id = captures.get(2).and_then(|m| m.as_str().parse::<u32>().ok());
+        let Some(id) = id else {
+            return Err(ProjectError::BadFileName(name.to_string()));
+        };
+        Ok((idx.unwrap_or(u32::MAX), id))
+    } else {
+        Err(ProjectError::BadFileName(name.to_string()))
+    }
+}
+
+/// Warn about replace files matching none of the entries `(index, id)`,
+/// or fail on the first of them if `strict`.
+fn check_replacements(
+    replace_data: &HashMap<IdOrIndex, Vec<u8>>,
+    entries: impl Iterator<Item = (u32, u32)>,
+    strict: bool,
+) -> Result<()> {
+    let mut unmatched: Vec<IdOrIndex> = replace_data.keys().copied().collect();
+    for (idx, id) in entries {
+        unmatched.retain(|key| *key != IdOrIndex::Index(idx) && *key != IdOrIndex::Id(id));
+    }
+    unmatched.sort_by_key(|key| key.to_string());
+    if strict && let Some(key) = unmatched.first() {
+        return Err(ProjectError::UnmatchedReplacement(*key));
+    }
+    for key in unmatched {
+        warn!(
+            "Replace file {} does not match any entry in the project, skipped.",
+            key
+        );
+    }
+    Ok(())
+}
+
+/// Warn about remove targets matching none of the entries `(index, id)`.
+fn check_removals(manifest: &ProjectManifest, entries: impl Iterator<Item = (u32, u32)>) {
+    let mut unmatched = manifest.remove.clone();
+    for (idx, id) in entries {
+        unmatched.retain(|target| *target != IdOrIndex::Index(idx) && *target != IdOrIndex::Id(id));
+    }
+    for target in unmatched {
+        warn!(
+            "Remove target {} does not match any entry in the project.",
+            target
+        );
+    }
+}
+
+/// Replacement data of an entry after applying its size policy.
+fn sized_replacement(
+    policy: SizePolicy,
+    target: IdOrIndex,
+    data: &[u8],
+    original_size: usize,
+) -> Result<Vec<u8>> {
+    if policy != SizePolicy::Free && data.len() > original_size {
+        return Err(ProjectError::ReplacementTooLarge {
+            target,
+            size: data.len(),
+            original_size,
+        });
+    }
+    let mut data = data.to_vec();
+    if policy == SizePolicy::Pad && data.len() < original_size {
+        info!(
+            "{}: {} padded from {} to {} bytes.",
+            "Replace".cyan(),
+            target,
+            data.len(),
+            original_size
+        );
+        data.resize(original_size, 0);
+    }
+    Ok(data)
+}
+
+/// Load all replacements of a project, from `replace/` folder and
+/// directives in `project.toml`.
+///
+/// Directive targets of `manifest` are already remapped by
+/// [`load_remapped_manifest`], replace files are remapped by `id_map`.
+fn load_replacements(
+    project_path: &Path,
+    manifest: &ProjectManifest,
+    id_map: &IdMap,
+) -> Result<HashMap<IdOrIndex, Vec<u8>>> {
+    let replace_root = project_path.join("replace");
+    let mut replace_data = if replace_root.is_dir() {
+        load_sound_files(project_path, &replace_root, true, manifest, id_map)?
+    } else {
+        HashMap::new()
+    };
+
+    for directive in &manifest.replace {
+        let (Some(bundle), Some(source)) = (&directive.from_bundle, directive.source_entry())
+        else {
+            continue;
+        };
+        let target = directive.target;
+        if replace_data.contains_key(&target) {
+            warn!(
+                "Replace file {} exists, directive copying from '{}' ignored.",
+                target,
+                bundle.display()
+            );
+            continue;
+        }
+        let bundle_path = find_bundle(project_path, bundle)?;
+        let data = read_entry(&bundle_path, source).map_err(|e| ProjectError::CopyFromBundle {
+            target,
+            bundle: bundle_path.clone(),
+            source: Box::new(e),
+        })?;
+        info!(
+            "{}: {} copied from '{}' {}.",
+            "Replace".cyan(),
+            target,
+            bundle.display(),
+            source
+        );
+        replace_data.insert(target, data);
+    }
+
+    if !manifest.variations.is_empty() {
+        let variations = load_variations(project_path, manifest, &replace_data)?;
+        replace_data.extend(variations);
+    }
+
+    Ok(replace_data)
+}
+
+/// Load `[[variations]]` of manifest, sounds with a replace file are skipped.
+fn load_variations(
+    project_path: &Path,
+    manifest: &ProjectManifest,
+    replace_data: &HashMap<IdOrIndex, Vec<u8>>,
+) -> Result<HashMap<IdOrIndex, Vec<u8>>> {
+    let hirc_entries = project_hirc_entries(project_path)?;
+    // replace files may name sounds by index too
+    let store = open_store(project_path)?;
+    let dumped = list_dumped_files(project_path, store.as_ref(), "wem")?;
+    let is_replaced = |source: u32| {
+        replace_data.contains_key(&IdOrIndex::Id(source))
+            || dumped.iter().any(|file| {
+                file.id == source && replace_data.contains_key(&IdOrIndex::Index(file.idx))
+            })
+    };
+    // assigned files are copied here named by source ID, to be converted like replace files
+    let staging = tempfile::tempdir()?;
+    let mut assigned = HashSet::new();
+    for directive in &manifest.variations {
+        let (container_id, sources) = hirc::container_sources(&hirc_entries, directive.target)
+            .map_err(|source| ProjectError::Variations {
+                target: directive.target,
+                source,
+            })?
+            .ok_or(ProjectError::NoContainer(directive.target))?;
+        let folder = project_path.join(&directive.folder);
+        if !folder.is_dir() {
+            return Err(ProjectError::file(&folder)(io::ErrorKind::NotFound.into()));
+        }
+        let files: Vec<PathBuf> = list_sound_files(&folder, false)?
+            .into_iter()
+            .filter(|path| !is_junk_file(path) && is_sound_file(path))
+            .collect();
+        if files.is_empty() {
+            warn!("No sound files in variations folder {}.", folder.display());
+            continue;
+        }
+        if files.len() > sources.len() {
+            warn!(
+                "Variations folder {} has {} files, container {} has only {} sounds, the rest is unused.",
+                folder.display(),
+                files.len(),
+                container_id,
+                sources.len()
+            );
+        }
+        for (source, file) in directive.fill.assign(&sources, &files) {
+            if is_replaced(source) || !assigned.insert(source) {
+                warn!(
+                    "Sound {} of container {} is already replaced, variation {} ignored.",
+                    source,
+                    container_id,
+                    file.display()
+                );
+                continue;
+            }
+            let staged = staging
+                .path()
+                .join(source.to_string())
+                .with_extension(file.extension().unwrap_or_default());
+            fs::copy(&file, staged).map_err(ProjectError::file(&file))?;
+        }
+        info!(
+            "{}: {} files spread over {} sounds of container {}.",
+            "Variations".cyan(),
+            files.len(),
+            sources.len(),
+            container_id
+        );
+    }
+    load_sound_files(
+        project_path,
+        staging.path(),
+        false,
+        manifest,
+        &IdMap::default(),
+    )
+}
+
+/// HIRC entries of a project, of `bank.json` or the banks of a PCK.
+fn project_hirc_entries(project_path: &Path) -> Result<Vec<bnk::HircEntry>> {
+    let bank_meta_path = project_path.join("bank.json");
+    if bank_meta_path.is_file() {
+        let content = fs::read_to_string(&bank_meta_path)?;
+        let bank: bnk::Bnk =
+            serde_json::from_str(&content).map_err(|source| ProjectError::BadMetadata {
+                path: bank_meta_path,
+                source,
+            })?;
+        return Ok(hirc::hirc_entries(&bank).to_vec());
+    }
+    let store = open_store(project_path)?;
+    let mut entries = vec![];
+    for file in list_dumped_files(project_path, store.as_ref(), "bnk")? {
+        let data = file.read(store.as_ref())?;
+        let bank = bnk::Bnk::from_reader(&mut io::Cursor::new(data))?;
+        entries.extend_from_slice(hirc::hirc_entries(&bank));
+    }
+    Ok(entries)
+}
+
+/// Apply user level replace rules to dumped entries, see [`crate::rules`].
+///
+/// Entries replaced or removed by the project are skipped. Rule replacements
+/// are added to `replace_data`, returns IDs of entries removed by rules.
+fn apply_rules(
+    project_path: &Path,
+    bundle_file_name: &str,
+    manifest: &ProjectManifest,
+    files: &[DumpedFile],
+    replace_data: &mut HashMap<IdOrIndex, Vec<u8>>,
+    rules: &RuleSet,
+) -> Result<Vec<u32>> {
+    if rules.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut removed = vec![];
+    let tmp_dir = tempfile::tempdir()?;
+    let mut file_count = 0;
+    for file in files {
+        if manifest.is_removed(file.idx, file.id)
+            || replace_data.contains_key(&IdOrIndex::Index(file.idx))
+            || replace_data.contains_key(&IdOrIndex::Id(file.id))
+        {
+            continue;
+        }
+        let name = rules
+            .names
+            .get(file.id)
+            .map(str::to_string)
+            .or_else(|| file.annotated_name());
+        let Some(rule) = rules.rules.find(bundle_file_name, file.id, name.as_deref()) else {
+            continue;
+        };
+        match rule.action {
+            RuleAction::Remove => removed.push(file.id),
+            RuleAction::Mute => {
+                write_silent_wav(&tmp_dir.path().join(format!("{}.wav", file.id)))?;
+                file_count += 1;
+            }
+            RuleAction::Replace => {
+                let source = rule.file.as_ref().unwrap();
+                let ext = source.extension().unwrap_or_default().to_string_lossy();
+                let path = tmp_dir.path().join(format!("{}.{}", file.id, ext));
+                fs::copy(source, path).map_err(ProjectError::file(source))?;
+                file_count += 1;
+            }
+        }
+        info!(
+            "{}: Wem file '{}'{} matched rule, {:?}.",
+            "Rule".cyan(),
+            file.id,
+            name.map(|name| format!(" ({})", name)).unwrap_or_default(),
+            rule.action
+        );
+    }
+    if file_count > 0 {
+        let data = load_sound_files(
+            project_path,
+            tmp_dir.path(),
+            false,
+            manifest,
+            &IdMap::default(),
+        )?;
+        replace_data.extend(data);
+    }
+    Ok(removed)
+}
+
+/// Short silent wav used by mute rules.
+fn write_silent_wav(path: &Path) -> Result<()> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 48000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let write = || -> hound::Result<()> {
+        let mut writer = hound::WavWriter::create(path, spec)?;
+        for _ in 0..spec.sample_rate / 10 {
+            writer.write_sample(0i16)?;
+        }
+        writer.finalize()
+    };
+    write().map_err(|e| ProjectError::file(path)(io::Error::other(e)))
+}
+
+/// New wem entry from `add/` folder.
+struct Addition {
+    id: u32,
+    /// PCK language, by name of the subfolder.
+    language: Option<String>,
+    data: Vec<u8>,
+}
+
+/// Load new entries from `add/` folder, named by ID and transcoded like replace
+/// files. Files in subfolders are added to the PCK language of the folder name.
+fn load_additions(project_path: &Path, manifest: &ProjectManifest) -> Result<Vec<Addition>> {
+    let add_root = project_path.join(ADD_DIR);
+    if !add_root.is_dir() {
+        return Ok(vec![]);
+    }
+    // subfolders of add root are languages, deeper ones only group files
+    let mut dirs = vec![(add_root.clone(), None)];
+    for entry in fs::read_dir(&add_root)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            let language = path.file_name().unwrap().to_string_lossy().to_string();
+            dirs.push((path, Some(language)));
+        }
+    }
+    let mut additions = vec![];
+    for (dir, language) in dirs {
+        let recursive = language.is_some();
+        let files = load_sound_files(project_path, &dir, recursive, manifest, &IdMap::default())?;
+        for (target, data) in files {
+            let IdOrIndex::Id(id) = target else {
+                return Err(ProjectError::AdditionByIndex(target));
+            };
+            additions.push(Addition {
+                id,
+                language: language.clone(),
+                data,
+            });
+        }
+    }
+    additions.sort_by_key(|addition| addition.id);
+    Ok(additions)
+}
+
+/// Bank written to `bank.json`, wem data is dumped separately.
+fn bank_metadata(bank: &bnk::Bnk) -> bnk::Bnk {
+    let mut meta_bank = bank.clone();
+    meta_bank.sections.retain(|sec| {
+        !matches!(
+            &sec.payload,
+            bnk::SectionPayload::Didx { .. } | bnk::SectionPayload::Data { .. }
+        )
+    });
+    meta_bank
+}
+
+/// Label banks named in STID in bank metadata: objects with the ID of a bank
+/// get its name, like names from `--names`, and STID entries of other banks
+/// than `bank_id` are marked `referenced`.
+fn label_bank_names(
+    metadata: &mut serde_json::Value,
+    bank_names: &[bnk::StidEntry],
+    bank_id: Option<u32>,
+) {
+    let mut names = NameList::default();
+    for entry in bank_names {
+        names.insert(entry.id, &entry.name);
+    }
+    names.annotate_json(metadata);
+
+    let Some(sections) = metadata["sections"].as_array_mut() else {
+        return;
+    };
+    let stid_entries = sections
+        .iter_mut()
+        .filter(|section| section["type"] == "Stid")
+        .filter_map(|section| section["content"]["entries"].as_array_mut())
+        .flatten();
+    for entry in stid_entries {
+        if entry["id"].as_u64() != bank_id.map(u64::from)
+            && let Some(entry) = entry.as_object_mut()
+        {
+            entry.insert(REFERENCED_KEY.to_string(), true.into());
+        }
+    }
+}
+
+/// Media store of project, the source bundle of a metadata only project is
+/// also looked up at the path it was dumped from.
+fn open_store(project_path: &Path) -> Result<Option<MediaStore>> {
+    let source_path = SoundToolProject::from_path(project_path)
+        .ok()
+        .and_then(|project| project.source_path().map(Path::to_path_buf));
+    Ok(MediaStore::open_with_bundle(
+        project_path,
+        source_path.as_deref(),
+    )?)
+}
+
+/// Bundle referenced by project files, looked up in project folder, then next to it.
+pub(crate) fn find_bundle(project_path: &Path, bundle: &Path) -> Result<PathBuf> {
+    [Some(project_path), project_path.parent()]
+        .into_iter()
+        .flatten()
+        .map(|dir| dir.join(bundle))
+        .find(|path| path.is_file())
+        .ok_or_else(|| ProjectError::BundleNotFound(bundle.to_path_buf()))
+}
+
+/// Load `idmap.toml` of project, aligning IDs of `from_bundle` if requested.
+fn load_id_map(project_path: &Path) -> Result<IdMap> {
+    let mut id_map = IdMap::load(project_path)?;
+    if id_map.align == Align::Index
+        && let Some(bundle) = id_map.from_bundle.clone()
+    {
+        let old_ids = entry_ids(find_bundle(project_path, &bundle)?)?;
+        let store = open_store(project_path)?;
+        let mut new_entries = list_dumped_files(project_path, store.as_ref(), "wem")?;
+        new_entries.sort_by_key(|file| file.idx);
+        let new_ids: Vec<u32> = new_entries.iter().map(|file| file.id).collect();
+        if old_ids.len() != new_ids.len() {
             warn!(
-                "BNK file {} included in original PCK, but not found in project, removed.",
-                entry.id
+                "'{}' has {} entries, this project has {}, aligned by index anyway.",
+                bundle.display(),
+                old_ids.len(),
+                new_ids.len()
             );
         }
-        // update header WEM entries
-        print!("Updating WEM entries...");
-        let mut drop_wem_idx_list = vec![];
-        for (i, entry) in pck_header.wem_entries.iter().enumerate() {
-            if !wem_metadata_map.contains_key(&entry.id) {
-                drop_wem_idx_list.push(i);
+        let aligned = id_map.align_by_index(&old_ids, &new_ids);
+        info!(
+            "{}: {} IDs of '{}' aligned by index.",
+            "Remap".cyan(),
+            aligned,
+            bundle.display()
+        );
+    }
+    Ok(id_map)
+}
+
+/// Load the manifest of a project for repack, with targets of replace
+/// directives remapped by `idmap.toml`, so per-entry overrides like
+/// conversion and size policy follow their entries to the new IDs.
+fn load_remapped_manifest(project_path: &Path) -> Result<(ProjectManifest, IdMap)> {
+    let mut manifest = ProjectManifest::load(project_path)?;
+    let id_map = load_id_map(project_path)?;
+    for directive in &mut manifest.replace {
+        directive.target = remap_target(&id_map, directive.target);
+    }
+    Ok((manifest, id_map))
+}
+
+fn remap_target(id_map: &IdMap, target: IdOrIndex) -> IdOrIndex {
+    let new_target = id_map.map(target);
+    if new_target != target {
+        debug!("Remap: {} -> {}", target, new_target);
+    }
+    new_target
+}
+
+/// Conversion settings of a replace target.
+struct TargetFormat {
+    conversion: Conversion,
+    /// Format of the original entry, if known.
+    format: Option<WemFormat>,
+    /// Target platform, `None` for `transcode.platform` in config.
+    platform: Option<String>,
+}
+
+/// Conversion settings of a replace target, by its original entry.
+///
+/// The format is recorded in `formats.json` on dump, or detected from the
+/// original for older projects. The conversion is the manifest override,
+/// or the codec of the original. `originals` are the dumped wem files of
+/// the project, listed once by the caller.
+fn target_format(
+    manifest: &ProjectManifest,
+    formats: Option<&EntryFormats>,
+    store: Option<&MediaStore>,
+    originals: &[DumpedFile],
+    target: IdOrIndex,
+) -> Result<TargetFormat> {
+    let original = originals.iter().find(|file| match target {
+        IdOrIndex::Id(id) => file.id == id,
+        IdOrIndex::Index(idx) => file.idx == idx,
+    });
+    // unmatched targets are reported after loading
+    let Some(original) = original else {
+        return Ok(TargetFormat {
+            conversion: Conversion::default(),
+            format: None,
+            platform: manifest.platform.clone(),
+        });
+    };
+    let platform = manifest.platform(original.idx, original.id);
+    let format = match formats.and_then(|formats| formats.get(original.id)) {
+        Some(format) => Some(*format),
+        None => WemFormat::detect(&original.read(store)?),
+    };
+    if let Some(conversion) = manifest.conversion(original.idx, original.id) {
+        return Ok(TargetFormat {
+            conversion,
+            format,
+            platform,
+        });
+    }
+    let conversion = match format {
+        Some(format) => {
+            if format.codec != Conversion::default() {
+                info!("{}: {} uses {}.", "Conversion".cyan(), target, format.codec);
             }
+            format.codec
         }
-        for i in drop_wem_idx_list.iter().rev() {
-            let entry = pck_header.wem_entries.remove(*i);
-            warn!(
-                "Wem file {} included in original PCK, but not found in project, removed.",
-                entry.id
-            );
-        }
-        if !drop_wem_idx_list.is_empty() || !drop_bnk_idx_list.is_empty() {
+        None => {
             warn!(
-                "Entry count changed, will affect the original order ID, please use unique ID as reference."
+                "Unknown codec of {}, using {}.",
+                target,
+                Conversion::default()
             );
+            Conversion::default()
         }
-        // calculate offsets and lengths
-        info!("Calculating offsets and lengths for BNK and WEM entries...");
-        let mut offset = pck_header.get_data_offset_start();
-        for entry in pck_header.bnk_entries.iter_mut() {
-            let metadata = bnk_metadata_map.get(&entry.id).unwrap();
-            let alignment = entry.padding_block_size.max(1);
-            // alignment offset
-            if offset % alignment != 0 {
-                offset += alignment - (offset % alignment);
-            }
-            entry.offset = offset / alignment;
-            entry.length = metadata.file_size;
-          
-            offset += metadata.file_size;
-        }
-        for entry in pck_header.wem_entries.iter_mut() {
-            let metadata = wem_metadata_map.get(&entry.id).unwrap();
-            let alignment = entry.padding_block_size.max(1);
-            // alignment offset
-            if offset % alignment != 0 {
-                offset += alignment - (offset % alignment);
-            }
-            entry.offset = offset / alignment;
-            entry.length = metadata.file_size;
-            
-            offset += metadata.file_size;
-        }
-        info!("Writing PCK header and data...");
-        let mut output_path = output_root
-            .join(&self.source_file_name)
-            .to_string_lossy()
-            .to_string();
-        loop {
-            if Path::new(&output_path).exists() {
-                output_path.push_str(".new");
-            } else {
-                break;
-            }
-        }
-        // write header and data
-        let output_file = File::create(&output_path)?;
-        let mut writer = io::BufWriter::new(output_file);
-        pck_header.write_to(&mut writer)?;
-        // write BNK and WEM
-        for entry in &pck_header.bnk_entries {
-            // alignment
-            let alignment = entry.padding_block_size.max(1);
-            let cur_pos = writer.stream_position()? as u32;
-            if cur_pos % alignment != 0 {
-                let pad = alignment - (cur_pos % alignment);
-                writer.write_all(&vec![0u8; pad as usize])?;
-            }
-            // write data
-            let metadata = bnk_metadata_map.get(&entry.id).unwrap();
-            if let Some(data) = &metadata.data {
-                writer.write_all(data)?;
-            } else if let Some(file_path) = &metadata.file_path {
-                let mut input_file = File::open(file_path)?;
-                io::copy(&mut input_file, &mut writer)?;
-            } else {
-                eyre::bail!(
-                    "Internal: both data and file_path are None for BNK file: {}",
-                    metadata.idx
-                );
-            }
-          
-            let written = metadata.file_size;
-            if written < entry.length {
-                writer.write_all(&vec![0u8; (entry.length - written) as usize])?;
+    };
+    Ok(TargetFormat {
+        conversion,
+        format,
+        platform,
+    })
+}
+
+/// Sound files in a directory, sorted. Subdirectories are searched if
+/// `recursive`, they only group files, e.g. `replace/footsteps/123.wav`.
+/// Hidden directories like `.git` are skipped.
+fn list_sound_files(dir: &Path, recursive: bool) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_file() {
+                files.push(path);
+            } else if recursive
+                && path.is_dir()
+                && !path.file_name().unwrap().to_string_lossy().starts_with('.')
+            {
+                dirs.push(path);
             }
         }
-        for entry in &pck_header.wem_entries {
-            // alignment
-            let alignment = entry.padding_block_size.max(1);
-            let cur_pos = writer.stream_position()? as u32;
-            if cur_pos % alignment != 0 {
-            let pad = alignment - (cur_pos % alignment);
-            writer.write_all(&vec![0u8; pad as usize])?;
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Files left by file managers, skipped silently.
+const JUNK_FILE_NAMES: &[&str] = &["thumbs.db", "desktop.ini"];
+/// Extensions of sound files not detected by content.
+const SOUND_EXTENSIONS: &[&str] = &[
+    "wem", "wav", "mp3", "ogg", "flac", "aac", "m4a", "opus", "wma", "aif", "aiff",
+];
+
+/// Whether a file is left by a file manager or editor, e.g. `Thumbs.db`,
+/// `.DS_Store` or `~$notes.docx`. Hidden files are junk as well.
+fn is_junk_file(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    name.starts_with('.')
+        || name.starts_with("~$")
+        || JUNK_FILE_NAMES.contains(&name.to_lowercase().as_str())
+}
+
+/// Whether a file is a sound, by extension or content.
+fn is_sound_file(path: &Path) -> bool {
+    let ext = path
+        .extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase();
+    SOUND_EXTENSIONS.contains(&ext.as_str())
+        || matches!(
+            InputFileType::from_path(path),
+            Some(InputFileType::Wem | InputFileType::GeneralAudio(_))
+        )
+}
+
+/// Files in `replace/` folder of a project and its subfolders, by target.
+///
+/// Of files with the same target, the first in path order is used.
+pub fn replace_files(project_path: impl AsRef<Path>) -> Result<HashMap<IdOrIndex, PathBuf>> {
+    let replace_root = project_path.as_ref().join("replace");
+    let mut files = HashMap::new();
+    if !replace_root.is_dir() {
+        return Ok(files);
+    }
+    for path in list_sound_files(&replace_root, true)? {
+        if is_junk_file(&path) || !is_sound_file(&path) {
+            continue;
         }
-            // write data
-            let metadata = wem_metadata_map.get(&entry.id).unwrap();
-            if let Some(data) = &metadata.data {
-                writer.write_all(data)?;
-            } else if let Some(file_path) = &metadata.file_path {
-                let mut input_file = File::open(file_path)?;
-                io::copy(&mut input_file, &mut writer)?;
-            } else {
-                eyre::bail!(
-                    "Internal: both data and file_path are None for Wem file: {}",
-                    metadata.idx
-                );
-            }
-            let written = metadata.file_size;
-            if written < entry.length {
-                writer.write_all(&vec![0u8; (entry.length - written) as usize])?;
-            }
+        let file_stem = path.file_stem().unwrap().to_string_lossy();
+        if let Some(target) = IdOrIndex::parse(file_stem.trim()) {
+            files.entry(target).or_insert(path);
         }
+    }
+    Ok(files)
+}
 
-        info!("Output: {}", output_path);
+/// Overview of a project, see [`project_stats`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectStats {
+    pub source_file_name: String,
+    /// Dumped wem entries.
+    pub entries: usize,
+    /// Files in `replace/` folder.
+    pub replacements: usize,
+    /// Entries removed in `project.toml`.
+    pub removals: usize,
+}
 
-        Ok(())
-    }
+/// Count entries and changes of a project.
+pub fn project_stats(project_path: impl AsRef<Path>) -> Result<ProjectStats> {
+    let project_path = project_path.as_ref();
+    let project = SoundToolProject::from_path(project_path)?;
+    let manifest = ProjectManifest::load(project_path)?;
+    Ok(ProjectStats {
+        source_file_name: project.source_file_name().to_string(),
+        entries: project.media_entries()?.len(),
+        replacements: replace_files(project_path)?.len(),
+        removals: manifest.remove.len(),
+    })
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-enum IdOrIndex {
-    Id(u32),
-    Index(u32),
+/// Problem of a project found by [`validate_project`].
+#[derive(Debug, thiserror::Error)]
+pub enum ValidationIssue {
+    #[error("{0}")]
+    Project(#[from] ProjectError),
+    #[error("{0}")]
+    Order(#[from] OrderError),
+    #[error("Files {first} and {second} are the same entry [{idx}].")]
+    DuplicateIndex {
+        idx: u32,
+        first: String,
+        second: String,
+    },
+    #[error("File {0} is not a valid wem, RIFF header not found.")]
+    InvalidWem(String),
+    #[error("Replace file {file} targets {target}, which matches no entry in the project.")]
+    UnmatchedReplacement { file: String, target: IdOrIndex },
 }
 
-impl IdOrIndex {
-    fn from_str(s: &str) -> Option<Self> {
-        if s.starts_with('[') && s.ends_with(']') {
-            s[1..s.len() - 1].parse().ok().map(IdOrIndex::Index)
-        } else {
-            s.parse().ok().map(IdOrIndex::Id)
-        }
+/// Check a project before repacking, returns every problem found instead of
+/// stopping at the first one.
+///
+/// Checks metadata files, names and indices of dumped files, their order
+/// recorded in `order.json`, wem headers and targets of replace files.
+pub fn validate_project(project_path: impl AsRef<Path>) -> Result<Vec<ValidationIssue>> {
+    let project_path = project_path.as_ref();
+    let project = match SoundToolProject::from_path(project_path) {
+        Ok(project) => project,
+        Err(e) => return Ok(vec![e.into()]),
+    };
+    let mut issues: Vec<ValidationIssue> = vec![];
+
+    let metadata = project.metadata().and_then(|value| {
+        let parsed = match project {
+            SoundToolProject::Bnk(_) => serde_json::from_value::<bnk::Bnk>(value).map(|_| ()),
+            SoundToolProject::Pck(_) => serde_json::from_value::<pck::PckHeader>(value).map(|_| ()),
+        };
+        parsed.map_err(|source| ProjectError::BadMetadata {
+            path: project.metadata_path(),
+            source,
+        })
+    });
+    if let Err(e) = metadata {
+        issues.push(e.into());
     }
+    let store = open_store(project_path).unwrap_or_else(|e| {
+        issues.push(e.into());
+        None
+    });
+    let order = EntryOrder::load(project_path).unwrap_or_else(|e| {
+        issues.push(e.into());
+        None
+    });
 
-    fn _to_string(&self) -> String {
-        match self {
-            IdOrIndex::Id(id) => id.to_string(),
-            IdOrIndex::Index(index) => format!("[{}]", index),
+    // (file name, loose file path)
+    let mut files: Vec<(String, Option<PathBuf>)> = vec![];
+    for entry in fs::read_dir(project_path)? {
+        let path = entry?.path();
+        if path.is_file() {
+            files.push((
+                path.file_name().unwrap().to_string_lossy().to_string(),
+                Some(path),
+            ));
+        }
+    }
+    if let Some(store) = &store {
+        for stored in store.files() {
+            if !files.iter().any(|(name, _)| *name == stored.name) {
+                files.push((stored.name.clone(), None));
+            }
+        }
+    }
+    files.sort();
+    let exts: &[&str] = match project {
+        SoundToolProject::Bnk(_) => &["wem"],
+        SoundToolProject::Pck(_) => &["wem", "bnk"],
+    };
+    // (index, id) of wem entries
+    let mut entries = vec![];
+    for ext in exts {
+        let mut seen: HashMap<u32, &str> = HashMap::new();
+        for (name, path) in &files {
+            let name_path = Path::new(name);
+            if name_path.extension().unwrap_or_default() != *ext {
+                continue;
+            }
+            let (idx, id) = match parse_wem_name(&name_path.file_stem().unwrap().to_string_lossy())
+            {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    issues.push(e.into());
+                    continue;
+                }
+            };
+            match seen.get(&idx) {
+                Some(first) => issues.push(ValidationIssue::DuplicateIndex {
+                    idx,
+                    first: first.to_string(),
+                    second: name.clone(),
+                }),
+                None => {
+                    seen.insert(idx, name);
+                }
+            }
+            if let Some(order) = &order {
+                let expected = order.ids(ext).get(idx as usize).copied();
+                if expected != Some(id) {
+                    issues.push(
+                        OrderError::Mismatch {
+                            file: name.clone(),
+                            idx,
+                            expected,
+                        }
+                        .into(),
+                    );
+                }
+            }
+            if *ext == "wem" {
+                entries.push((idx, id));
+                if let Some(path) = path
+                    && !is_riff_file(path)?
+                {
+                    issues.push(ValidationIssue::InvalidWem(name.clone()));
+                }
+            }
         }
     }
-}
 
-impl std::fmt::Display for IdOrIndex {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self._to_string())
+    let replace_root = project_path.join("replace");
+    if replace_root.is_dir() {
+        let id_map = load_id_map(project_path).unwrap_or_else(|e| {
+            issues.push(e.into());
+            IdMap::default()
+        });
+        for path in list_sound_files(&replace_root, true)? {
+            if is_junk_file(&path) || !is_sound_file(&path) {
+                continue;
+            }
+            let file = path
+                .strip_prefix(project_path)
+                .unwrap_or(&path)
+                .display()
+                .to_string();
+            let file_stem = path.file_stem().unwrap().to_string_lossy();
+            let Some(target) = IdOrIndex::parse(file_stem.trim()) else {
+                issues.push(ProjectError::BadFileName(file).into());
+                continue;
+            };
+            let target = remap_target(&id_map, target);
+            if !entries
+                .iter()
+                .any(|(idx, id)| target == IdOrIndex::Index(*idx) || target == IdOrIndex::Id(*id))
+            {
+                issues.push(ValidationIssue::UnmatchedReplacement {
+                    file: file.clone(),
+                    target,
+                });
+            }
+            if path.extension().unwrap_or_default() == "wem" && !is_riff_file(&path)? {
+                issues.push(ValidationIssue::InvalidWem(file));
+            }
+        }
     }
+
+    Ok(issues)
 }
 
-/// 解析Wem名，返回 (index, id)
-fn parse_wem_name(name: &str) -> eyre::Result<(u32, u32)> {
-    let name = name.trim();
-    if let Some(captures) = REG_WEM_NAME.captures(name) {
-        let idx = captures.get(1).and_then(|m| m.as_str().parse::<u32>().ok());
-        let id = captures.get(2).and_then(|m| m.as_str().parse::<u32>().ok());
-        let Some(id) = id else {
-            eyre::bail!("Bad Wem file name, cannot parse Wem id. {}", name)
-        };
-        Ok((idx.unwrap_or(u32::MAX), id))
-    } else {
-        eyre::bail!("Bad Wem file name. {}", name)
+/// Whether a file starts with a `RIFF` or `RIFX` header of `WAVE` form.
+fn is_riff_file(path: &Path) -> Result<bool> {
+    let mut header = [0; 12];
+    let mut file = File::open(path).map_err(ProjectError::file(path))?;
+    if file.read_exact(&mut header).is_err() {
+        return Ok(false);
     }
+    Ok(matches!(&header[0..4], b"RIFF" | b"RIFX") && &header[8..12] == b"WAVE")
 }
 
-/// 加载replace目录下的替换文件，返回转码为wem后的文件数据。
+/// 加载目录下以ID或序号命名的声音文件，返回转码为wem后的文件数据。
 ///
 /// <index, Data>
-fn load_replace_files(replace_root: impl AsRef<Path>) -> eyre::Result<HashMap<IdOrIndex, Vec<u8>>> {
-    let replace_root = replace_root.as_ref();
-
-    let tmp_dir = tempfile::tempdir()?.path().join("wem_transcode");
-    if tmp_dir.exists() {
-        fs::remove_dir_all(&tmp_dir)?;
-        fs::create_dir_all(&tmp_dir)?;
-    } else {
-        fs::create_dir_all(&tmp_dir)?;
-    }
+fn load_sound_files(
+    project_path: &Path,
+    dir: &Path,
+    recursive: bool,
+    manifest: &ProjectManifest,
+    id_map: &IdMap,
+) -> Result<HashMap<IdOrIndex, Vec<u8>>> {
+    // 临时目录路径较短，替换文件路径过长时也能交给外部工具处理
+    let tmp_root = tempfile::tempdir()?;
+    let tmp_dir = tmp_root.path().join("wem_transcode");
+    fs::create_dir_all(&tmp_dir)?;
     let wem_out_dir = tmp_dir.join("output");
     if !wem_out_dir.exists() {
         fs::create_dir_all(&wem_out_dir)?;
     }
 
     let mut file_count = 0;
-    for entry in fs::read_dir(replace_root)? {
-        let entry = entry?;
-        let path = entry.path();
-        if !path.is_file() {
+    let formats = EntryFormats::load(project_path)?;
+    // originals of replace targets, listed once for all files
+    let store = open_store(project_path)?;
+    let originals = list_dumped_files(project_path, store.as_ref(), "wem")?;
+    let mut conversions: BTreeMap<Option<String>, WemConversion> = BTreeMap::new();
+    // (replace file, wav file in temp directory)
+    let mut to_transcode = vec![];
+    // 同一目标只取第一个文件
+    let mut targets: HashMap<IdOrIndex, PathBuf> = HashMap::new();
+    // 非声音文件，跳过并报告
+    let mut quarantined = vec![];
+    for path in list_sound_files(dir, recursive)? {
+        if is_junk_file(&path) {
+            debug!("Skipped junk file: {}", path.display());
+            continue;
+        }
+        if !is_sound_file(&path) {
+            quarantined.push(path);
             continue;
         }
         let file_stem = path.file_stem().unwrap().to_string_lossy();
         let file_stem = file_stem.trim();
-        let id_or_index = IdOrIndex::from_str(file_stem)
-            .ok_or(eyre::eyre!("Bad replace file name. {}", file_stem))?;
+        let id_or_index = IdOrIndex::parse(file_stem)
+            .ok_or_else(|| ProjectError::BadFileName(file_stem.to_string()))?;
+        if let Some(first) = targets.get(&id_or_index) {
+            warn!(
+                "Replace files {} and {} target the same entry {}, the latter is skipped.",
+                first.strip_prefix(dir).unwrap_or(first).display(),
+                path.strip_prefix(dir).unwrap_or(&path).display(),
+                id_or_index
+            );
+            continue;
+        }
+        targets.insert(id_or_index, path.clone());
         // ID数值过小时警告，以防混淆顺序ID和唯一ID
-        if let IdOrIndex::Id(id) = id_or_index {
-            if id < 500 {
-                warn!(
-                    "Replace file ID '{}' is too small, did you mean to use order index?",
-                    id
-                );
-            }
+        if let IdOrIndex::Id(id) = id_or_index
+            && id < 500
+        {
+            warn!(
+                "Replace file ID '{}' is too small, did you mean to use order index?",
+                id
+            );
         }
+        let id_or_index = remap_target(id_map, id_or_index);
 
         let file_ext = path.extension().unwrap_or_default().to_string_lossy();
+        let mut wav_file_path = tmp_dir.join(format!("{}.wav", id_or_index));
         if file_ext == "wem" {
-            // 无需转码
-            // 写入wem目录
-            let wem_file_path = wem_out_dir.join(path.file_name().unwrap());
-            fs::write(&wem_file_path, fs::read(&path)?).context("Failed to write WEM file")?;
-            file_count += 1;
-            continue;
-        }
-
-        let wav_data = if file_ext == "wav" {
+            let wem_data = fs::read(&path).map_err(ProjectError::file(&path))?;
+            if wwise::is_foreign_wem(&wem_data) {
+                // 其他游戏或平台的wem，解码后重新转码
+                info!(
+                    "{}: {} uses a codec the game cannot play, re-encoding.",
+                    "Foreign".cyan(),
+                    path.strip_prefix(dir).unwrap_or(&path).display()
+                );
+                fs::write(&wav_file_path, transcode::wem_to_wav(&wem_data)?)
+                    .map_err(ProjectError::file(&wav_file_path))?;
+            } else {
+                // 无需转码
+                // 写入wem目录
+                let wem_file_path = wem_out_dir.join(format!("{}.wem", id_or_index));
+                fs::write(&wem_file_path, wem_data).map_err(ProjectError::file(&wem_file_path))?;
+                file_count += 1;
+                continue;
+            }
+        } else if file_ext == "wav" {
             // 无需转码wav
-            fs::read(&path)?
+            wav_file_path = path.clone();
         } else {
-            // 先转码，再读取
-            let data = transcode::sounds_to_wav(&[&path])
-                .context("Failed to transcode replace file to WAV")?;
-            data.into_iter().next().unwrap()
+            // 其他格式稍后并行转码
+            to_transcode.push((path.clone(), wav_file_path.clone()));
+        }
+        let wem_file_path = wem_out_dir.join(format!("{}.wem", id_or_index));
+        let target = target_format(
+            manifest,
+            formats.as_ref(),
+            store.as_ref(),
+            &originals,
+            id_or_index,
+        )?;
+        // one conversion per target platform
+        let conversion = conversions.entry(target.platform).or_default();
+        *conversion = match target.format {
+            Some(format) if !manifest.keep_input_format => mem::take(conversion).add_file_matching(
+                wav_file_path,
+                wem_file_path,
+                target.conversion,
+                format,
+            ),
+            _ => mem::take(conversion).add_file_to(wav_file_path, wem_file_path, target.conversion),
         };
-        // 写入临时目录
-        let wav_file_path = tmp_dir.join(format!("{}.wav", id_or_index));
-        fs::write(&wav_file_path, wav_data).context("Failed to write transcoded WAV file")?;
         file_count += 1;
     }
+    if !quarantined.is_empty() {
+        warn!(
+            "Skipped {} files in {} which are not sounds:",
+            quarantined.len(),
+            dir.display()
+        );
+        for path in &quarantined {
+            warn!("  {}", path.strip_prefix(dir).unwrap_or(path).display());
+        }
+    }
     if file_count == 0 {
         return Ok(HashMap::new());
     }
+    if !to_transcode.is_empty() {
+        let inputs: Vec<&Path> = to_transcode
+            .iter()
+            .map(|(path, _)| path.as_path())
+            .collect();
+        let wavs = transcode::sounds_to_wav(&inputs)?;
+        for ((_, wav_file_path), wav_data) in to_transcode.iter().zip(wavs) {
+            fs::write(wav_file_path, wav_data)?;
+        }
+    }
 
-    // 转码wem
-    transcode::wavs_to_wem(&tmp_dir, &wem_out_dir).context("Failed to transcode WAVs to WEMs")?;
+    // 转码wem，仅wem文件时无需WwiseConsole
+    let mut results = vec![];
+    for (platform, conversion) in conversions {
+        results.extend(match platform {
+            Some(platform) => conversion.platform(platform).run()?,
+            None => conversion.run()?,
+        });
+    }
+    transcode::ensure_converted(&results)?;
     // 读取wem数据
     let mut replace_files = HashMap::new();
     for entry in fs::read_dir(&wem_out_dir)? {
@@ -723,8 +3959,8 @@ fn load_replace_files(replace_root: impl AsRef<Path>) -> eyre::Result<HashMap<Id
             continue;
         }
         let file_stem = path.file_stem().unwrap().to_string_lossy();
-        let id_or_index = IdOrIndex::from_str(&file_stem)
-            .ok_or_else(|| eyre::eyre!("Internal: bad Wem file name. {}", file_stem))?;
+        let id_or_index = IdOrIndex::parse(&file_stem)
+            .ok_or_else(|| ProjectError::BadFileName(file_stem.to_string()))?;
         let data = fs::read(&path)?;
         replace_files.insert(id_or_index, data);
     }
@@ -756,6 +3992,57 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_staged_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_path = dir.path().join("a.sbnk.1.X64");
+        let policy = OutputPolicy::default();
+
+        let staged = policy.stage(&output_path);
+        fs::write(staged.path(), b"partial").unwrap();
+        drop(staged);
+        assert!(!policy.staging_path(&output_path).exists());
+        assert!(!output_path.exists());
+
+        let staged = policy.stage(&output_path);
+        fs::write(staged.path(), b"bundle").unwrap();
+        staged.place().unwrap();
+        assert_eq!(fs::read(&output_path).unwrap(), b"bundle");
+        assert!(!policy.staging_path(&output_path).exists());
+    }
+
+    #[test]
+    fn test_check_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.sbnk.1.X64");
+        fs::copy(TEST_BNK, &path).unwrap();
+        let first = read_entry(&path, IdOrIndex::Index(0)).unwrap();
+        let mut expected: Vec<ExpectedEntry> = list_entries(&path)
+            .unwrap()
+            .into_iter()
+            .map(|entry| ExpectedEntry {
+                kind: entry.kind,
+                id: entry.id,
+                size: entry.size,
+                offset: entry.offset,
+                sha256: None,
+            })
+            .collect();
+        expected[0].sha256 = Some(Sha256::digest(&first).into());
+        check_written(&path, &expected).unwrap();
+
+        expected[0].sha256 = Some(Sha256::digest(b"other").into());
+        assert!(matches!(
+            check_written(&path, &expected),
+            Err(ProjectError::OutputCheck { .. })
+        ));
+        assert!(!path.exists());
+
+        fs::copy(TEST_BNK, &path).unwrap();
+        expected.pop();
+        assert!(check_written(&path, &expected).is_err());
+    }
+
     #[test]
     fn test_dump_bnk() {
         SoundToolProject::dump_bnk(TEST_BNK, "test_files").unwrap();
@@ -802,6 +4089,264 @@ mod tests {
         fs::remove_dir_all(project_path).unwrap();
     }
 
+    #[test]
+    fn test_import_loose_wems() {
+        let output_root = tempfile::tempdir().unwrap();
+        let project = SoundToolProject::dump_bnk(TEST_BNK, output_root.path()).unwrap();
+        // loose files from other tools
+        let loose_dir = tempfile::tempdir().unwrap();
+        let wem_data = fs::read(project.project_path().join("[001]8242880.wem")).unwrap();
+        fs::write(loose_dir.path().join("8242880.wem"), &wem_data).unwrap();
+        fs::write(loose_dir.path().join("sfx_16088711_copy.wem"), &wem_data).unwrap();
+        fs::write(loose_dir.path().join("12345.wem"), &wem_data).unwrap();
+
+        let imported = project.import_loose_wems(loose_dir.path()).unwrap();
+        assert_eq!(imported, 2);
+        let replace_dir = project.project_path().join("replace");
+        assert!(replace_dir.join("8242880.wem").is_file());
+        assert!(replace_dir.join("16088711.wem").is_file());
+    }
+
+    #[test]
+    fn test_media_store_repack() {
+        let output_root = tempfile::tempdir().unwrap();
+        let loose_dir = output_root.path().join("loose");
+        let stored_dir = output_root.path().join("stored");
+        fs::create_dir_all(&loose_dir).unwrap();
+        fs::create_dir_all(&stored_dir).unwrap();
+
+        let project = SoundToolProject::dump_pck(TEST_PCK, output_root.path()).unwrap();
+        let loose_entry = read_entry(project.project_path(), IdOrIndex::Index(0)).unwrap();
+        let stats = project.repack(&loose_dir).unwrap();
+        assert_eq!(stats.replaced(), 0);
+        assert_eq!(stats.phases.len(), 4);
+        assert_eq!(stats.size_delta(), Some(0));
+
+        assert!(project.pack_media_store().unwrap() > 0);
+        let leftovers = fs::read_dir(project.project_path())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().unwrap_or_default() == "wem")
+            .count();
+        assert_eq!(leftovers, 0);
+        let stored_entry = read_entry(project.project_path(), IdOrIndex::Index(0)).unwrap();
+        assert_eq!(stored_entry, loose_entry);
+
+        // bundle repacked from store is identical to the one from loose files
+        project.repack(&stored_dir).unwrap();
+        let file_name = Path::new(TEST_PCK).file_name().unwrap();
+        assert_eq!(
+            fs::read(loose_dir.join(file_name)).unwrap(),
+            fs::read(stored_dir.join(file_name)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_replace_from_bundle() {
+        let output_root = tempfile::tempdir().unwrap();
+        let project = SoundToolProject::dump_bnk(TEST_BNK, output_root.path()).unwrap();
+        fs::copy(TEST_PCK, output_root.path().join("other.spck")).unwrap();
+        fs::write(
+            project.project_path().join("project.toml"),
+            r#"
+            [[replace]]
+            target = "[3]"
+            from_bundle = "other.spck"
+            from_index = 0
+            "#,
+        )
+        .unwrap();
+
+        let repack_dir = output_root.path().join("repack");
+        fs::create_dir_all(&repack_dir).unwrap();
+        let project = SoundToolProject::from_path(project.project_path()).unwrap();
+        project.repack(&repack_dir).unwrap();
+
+        let file_name = Path::new(TEST_BNK).file_name().unwrap();
+        let replaced = read_entry(repack_dir.join(file_name), IdOrIndex::Index(3)).unwrap();
+        let expected = read_entry(TEST_PCK, IdOrIndex::Index(0)).unwrap();
+        assert_eq!(replaced, expected);
+    }
+
+    #[test]
+    fn test_bundle_fingerprint() {
+        let output_root = tempfile::tempdir().unwrap();
+        let mut project = SoundToolProject::dump_bnk(TEST_BNK, output_root.path()).unwrap();
+        project.set_game_version("1.000").unwrap();
+
+        let project = SoundToolProject::from_path(project.project_path()).unwrap();
+        assert_eq!(project.game_version(), Some("1.000"));
+        assert_eq!(
+            project.compare_bundle(TEST_BNK).unwrap(),
+            Some(BundleChange::Unchanged)
+        );
+        assert!(matches!(
+            project.compare_bundle(TEST_PCK).unwrap(),
+            Some(BundleChange::VersionChanged { from: 145, .. })
+        ));
+    }
+
+    /// Write a copy of the test bank with modified `(id, data)` entries.
+    fn write_modified_bank(path: &Path, modify: impl FnOnce(&mut Vec<(u32, Vec<u8>)>)) {
+        let mut reader = io::BufReader::new(File::open(TEST_BNK).unwrap());
+        let mut bank = bnk::Bnk::from_reader(&mut reader).unwrap();
+        let mut didx_entries = vec![];
+        let mut entries = vec![];
+        for section in &bank.sections {
+            match &section.payload {
+                bnk::SectionPayload::Didx { entries } => didx_entries = entries.clone(),
+                bnk::SectionPayload::Data { data_list } => {
+                    entries = didx_entries
+                        .iter()
+                        .zip(data_list)
+                        .map(|(entry, data)| (entry.id, data.clone()))
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+        modify(&mut entries);
+
+        let layout = bnk::BnkLayout::plan(
+            entries.iter().map(|(id, data)| (*id, ByteLen::of(data))),
+            bnk::DataAlignment::WWISE,
+        )
+        .unwrap();
+        let (didx, data) = layout.into_sections(entries.into_iter().map(|(_, d)| d).collect());
+        bank.sections
+            .retain(|sec| &sec.magic != b"DIDX" && &sec.magic != b"DATA");
+        bank.sections.insert(1, didx);
+        bank.sections.insert(2, data);
+        bank.write_to(&mut File::create(path).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_rebase() {
+        let root = tempfile::tempdir().unwrap();
+        let source = root.path().join("Wp00_Cmn_m.sbnk.1.X64");
+        fs::copy(TEST_BNK, &source).unwrap();
+        let project = SoundToolProject::dump_bnk(&source, root.path()).unwrap();
+        let mut files = list_dumped_files(project.project_path(), None, "wem").unwrap();
+        files.sort_by_key(|file| file.idx);
+        let ids: Vec<u32> = files.iter().map(|file| file.id).collect();
+        let replace_root = project.project_path().join("replace");
+        fs::create_dir_all(&replace_root).unwrap();
+        for name in [
+            format!("{}.wem", ids[0]),
+            "[2].wem".to_string(),
+            format!("{}.wem", ids[3]),
+            format!("{}.wem", ids[4]),
+        ] {
+            fs::write(replace_root.join(name), b"RIFF").unwrap();
+        }
+        fs::write(project.project_path().join("notes.txt"), "notes").unwrap();
+
+        // game patch: first entry removed, another one changed ID
+        write_modified_bank(&source, |entries| {
+            entries.remove(0);
+            entries[2].0 = 123456789;
+        });
+        let (new_project, report) = project.rebase(&source, root.path()).unwrap();
+        assert_eq!(report.removed, vec![ids[0]]);
+        assert_eq!(report.renamed, vec![(ids[3], 123456789)]);
+        assert!(report.added.is_empty());
+        assert_eq!(
+            report.orphaned,
+            vec![PathBuf::from(format!("{}.wem", ids[0]))]
+        );
+
+        let new_path = new_project.project_path();
+        assert_eq!(new_path, project.project_path());
+        let new_replace_root = new_path.join("replace");
+        assert!(new_replace_root.join("[1].wem").is_file());
+        assert!(new_replace_root.join("123456789.wem").is_file());
+        assert!(new_replace_root.join(format!("{}.wem", ids[4])).is_file());
+        assert!(
+            new_path
+                .join("orphaned")
+                .join(format!("{}.wem", ids[0]))
+                .is_file()
+        );
+        assert!(new_path.join("notes.txt").is_file());
+        assert!(
+            root.path()
+                .join("Wp00_Cmn_m.sbnk.1.X64.project.bak")
+                .join("notes.txt")
+                .is_file()
+        );
+        // the old source is changed
+        assert_eq!(
+            project.compare_bundle(&source).unwrap(),
+            Some(BundleChange::Modified)
+        );
+
+        // the source path is recorded relative to the moved project
+        let metadata: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(new_path.join("project.json")).unwrap())
+                .unwrap();
+        assert_eq!(
+            metadata["Bnk"]["source_path"],
+            Path::new("..")
+                .join("Wp00_Cmn_m.sbnk.1.X64")
+                .to_str()
+                .unwrap()
+        );
+        assert_eq!(
+            SoundToolProject::from_path(new_path).unwrap().source_path(),
+            Some(fs::canonicalize(&source).unwrap().as_path())
+        );
+    }
+
+    #[test]
+    fn test_read_entry() {
+        let by_index = read_entry(TEST_BNK, IdOrIndex::Index(1)).unwrap();
+        let by_id = read_entry(TEST_BNK, IdOrIndex::Id(8242880)).unwrap();
+        assert_eq!(by_index, by_id);
+        assert_eq!(&by_id[0..4], b"RIFF");
+
+        let pck_entry = read_entry(TEST_PCK, IdOrIndex::Index(0)).unwrap();
+        assert_eq!(&pck_entry[0..4], b"RIFF");
+        assert!(read_entry(TEST_BNK, IdOrIndex::Id(1)).is_err());
+    }
+
+    #[test]
+    fn test_list_entries() {
+        let entries = list_entries(TEST_BNK).unwrap();
+        assert_eq!(entries[1].id, 8242880);
+        assert_eq!(entries[1].index, 1);
+        let data = read_entry(TEST_BNK, IdOrIndex::Index(1)).unwrap();
+        assert_eq!(entries[1].size as usize, data.len());
+
+        let entries = list_entries(TEST_PCK).unwrap();
+        let wems: Vec<&BundleEntry> = entries.iter().filter(|e| e.kind == "wem").collect();
+        assert_eq!(wems[0].index, 0);
+        assert!(wems.iter().all(|e| e.language.is_some()));
+        let data = read_entry(TEST_PCK, IdOrIndex::Index(0)).unwrap();
+        assert_eq!(wems[0].size as usize, data.len());
+        assert_eq!(
+            entry_ids(TEST_PCK).unwrap(),
+            wems.iter().map(|e| e.id).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_check_replacements() {
+        let entries = [(0, 100), (1, 8242880)];
+        let mut replace_data = HashMap::new();
+        replace_data.insert(IdOrIndex::Id(8242880), vec![]);
+        replace_data.insert(IdOrIndex::Index(0), vec![]);
+        assert!(check_replacements(&replace_data, entries.into_iter(), true).is_ok());
+
+        replace_data.insert(IdOrIndex::Id(8242881), vec![]);
+        // only warned unless strict
+        assert!(check_replacements(&replace_data, entries.into_iter(), false).is_ok());
+        let err = check_replacements(&replace_data, entries.into_iter(), true).unwrap_err();
+        assert!(matches!(
+            err,
+            ProjectError::UnmatchedReplacement(IdOrIndex::Id(8242881))
+        ));
+    }
+
     #[test]
     fn test_bnk_replace() {
         // unpack
@@ -0,0 +1,165 @@
+//! Interactive prompts, with answers scriptable for automation.
+//!
+//! Answers are read from stdin (`--stdin-answers`), a file (`--answers`) or
+//! the file named by `MHWS_SOUND_TOOL_ANSWERS`. Each line is `key = value`
+//! for a specific prompt, or a bare value consumed by the next prompt
+//! without a keyed answer. Blank lines and `#` comments are ignored.
+//!
+//! Once answers are supplied, prompts never block: unanswered prompts
+//! take their defaults, as with `--no-interact`.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, Read},
+    path::Path,
+    sync::{LazyLock, atomic},
+};
+
+use dialoguer::{Input, theme::ColorfulTheme};
+use log::debug;
+use mhws_sound_tool::transcode::ToolPathPrompt;
+use parking_lot::Mutex;
+
+use crate::INTERACTIVE_MODE;
+
+pub const ANSWERS_ENV: &str = "MHWS_SOUND_TOOL_ANSWERS";
+
+static ANSWERS: LazyLock<Mutex<Option<Answers>>> = LazyLock::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Answers {
+    keyed: HashMap<String, String>,
+    positional: VecDeque<String>,
+}
+
+impl Answers {
+    pub fn parse(content: &str) -> Self {
+        let mut answers = Self::default();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once('=') {
+                Some((key, value)) if is_key(key.trim()) => {
+                    answers
+                        .keyed
+                        .insert(key.trim().to_string(), unquote(value.trim()).to_string());
+                }
+                _ => answers.positional.push_back(unquote(line).to_string()),
+            }
+        }
+        answers
+    }
+
+    fn take(&mut self, key: &str) -> Option<String> {
+        self.keyed
+            .remove(key)
+            .or_else(|| self.positional.pop_front())
+    }
+}
+
+/// Keys are snake case names, so Windows paths like `C:\a=b` stay values.
+fn is_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn unquote(value: &str) -> &str {
+    value.trim_matches(['"', '\''])
+}
+
+/// Load scripted answers, from stdin if `stdin` is set, then the given file,
+/// then the file in [`ANSWERS_ENV`]. Answers are loaded only once.
+pub fn init_answers(stdin: bool, file: Option<&Path>) -> io::Result<()> {
+    let mut answers = ANSWERS.lock();
+    if answers.is_some() {
+        return Ok(());
+    }
+    let env_file = std::env::var_os(ANSWERS_ENV);
+    let content = if stdin {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)?;
+        content
+    } else if let Some(file) = file.or(env_file.as_deref().map(Path::new)) {
+        std::fs::read_to_string(file)?
+    } else {
+        return Ok(());
+    };
+    *answers = Some(Answers::parse(&content));
+    Ok(())
+}
+
+/// Whether prompts may wait for user input.
+pub fn is_interactive() -> bool {
+    INTERACTIVE_MODE.load(atomic::Ordering::SeqCst) && ANSWERS.lock().is_none()
+}
+
+/// Ask for a text input.
+///
+/// Returns the scripted answer if any, otherwise prompts the user in
+/// interactive mode. Returns `default` when prompts are disabled.
+pub fn input(key: &str, prompt: &str, default: Option<&str>) -> Option<String> {
+    if let Some(answers) = ANSWERS.lock().as_mut()
+        && let Some(answer) = answers.take(key)
+    {
+        debug!("Scripted answer of {}: {}", key, answer);
+        return Some(answer);
+    }
+    if !is_interactive() {
+        return default.map(str::to_string);
+    }
+
+    let theme = ColorfulTheme::default();
+    let mut input = Input::<String>::with_theme(&theme).with_prompt(prompt);
+    if let Some(default) = default {
+        input = input.show_default(true).default(default.to_string());
+    }
+    let answer = input.interact_text().unwrap();
+    Some(unquote(answer.trim()).to_string())
+}
+
+/// Tool path prompt of the CLI.
+pub struct CliPrompt;
+
+impl ToolPathPrompt for CliPrompt {
+    fn is_interactive(&self) -> bool {
+        is_interactive()
+    }
+
+    fn input(&self, key: &str, prompt: &str, default: Option<&str>) -> Option<String> {
+        input(key, prompt, default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mhws_sound_tool::transcode::{FFMPEG_PATH, VGMSTREAM_PATH, WWISE_CONSOLE_PATH};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_answers() {
+        let mut answers = Answers::parse(
+            r#"
+            # tool paths
+            ffmpeg_path = "C:\tools\ffmpeg.exe"
+            D:\Wwise=2024\WwiseConsole.exe
+            vgmstream_path = /usr/bin/vgmstream-cli
+            "#,
+        );
+        assert_eq!(answers.take(FFMPEG_PATH).unwrap(), r"C:\tools\ffmpeg.exe");
+        assert_eq!(
+            answers.take(VGMSTREAM_PATH).unwrap(),
+            "/usr/bin/vgmstream-cli"
+        );
+        // no keyed answer, the next bare value is used
+        assert_eq!(
+            answers.take(WWISE_CONSOLE_PATH).unwrap(),
+            r"D:\Wwise=2024\WwiseConsole.exe"
+        );
+        assert_eq!(answers.take(FFMPEG_PATH), None);
+    }
+}
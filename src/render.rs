@@ -0,0 +1,209 @@
+use std::{f32::consts::PI, path::Path};
+
+use plotters::prelude::*;
+
+use crate::wav;
+
+type Result<T> = std::result::Result<T, RenderError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RenderError {
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("Failed to read wav data: {0}")]
+    Wav(#[from] hound::Error),
+    #[error("Failed to draw image: {0}")]
+    Drawing(String),
+    #[error("Audio contains no samples.")]
+    EmptyAudio,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    Waveform,
+    Spectrogram,
+}
+
+const FFT_SIZE: usize = 1024;
+const BACKGROUND: RGBColor = RGBColor(16, 16, 24);
+const WAVE_COLOR: RGBColor = RGBColor(96, 200, 255);
+
+/// Render wav file data to a PNG image.
+pub fn render_wav(
+    wav_data: &[u8],
+    mode: RenderMode,
+    output: impl AsRef<Path>,
+    size: (u32, u32),
+) -> Result<()> {
+    let samples = read_mono_samples(wav_data)?;
+    if samples.is_empty() {
+        return Err(RenderError::EmptyAudio);
+    }
+
+    let root = BitMapBackend::new(output.as_ref(), size).into_drawing_area();
+    root.fill(&BACKGROUND).map_err(drawing_error)?;
+    match mode {
+        RenderMode::Waveform => draw_waveform(&root, &samples, size)?,
+        RenderMode::Spectrogram => draw_spectrogram(&root, &samples, size)?,
+    }
+    root.present().map_err(drawing_error)?;
+
+    Ok(())
+}
+
+/// Read wav samples, mixed down to mono in range [-1.0, 1.0].
+pub(crate) fn read_mono_samples(wav_data: &[u8]) -> Result<Vec<f32>> {
+    let (spec, interleaved) = wav::read_samples(wav_data)?;
+    let channels = spec.channels.max(1) as usize;
+    Ok(interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect())
+}
+
+fn draw_waveform<DB: DrawingBackend>(
+    root: &DrawingArea<DB, plotters::coord::Shift>,
+    samples: &[f32],
+    (width, height): (u32, u32),
+) -> Result<()> {
+    let mid = height as i32 / 2;
+    let samples_per_column = samples.len().div_ceil(width as usize).max(1);
+    for (x, chunk) in samples.chunks(samples_per_column).enumerate() {
+        let (min, max) = chunk
+            .iter()
+            .fold((0f32, 0f32), |(min, max), &s| (min.min(s), max.max(s)));
+        let y_top = mid - (max.clamp(-1.0, 1.0) * mid as f32) as i32;
+        let y_bottom = mid - (min.clamp(-1.0, 1.0) * mid as f32) as i32;
+        root.draw(&PathElement::new(
+            vec![(x as i32, y_top), (x as i32, y_bottom)],
+            WAVE_COLOR,
+        ))
+        .map_err(drawing_error)?;
+    }
+    Ok(())
+}
+
+fn draw_spectrogram<DB: DrawingBackend>(
+    root: &DrawingArea<DB, plotters::coord::Shift>,
+    samples: &[f32],
+    (width, height): (u32, u32),
+) -> Result<()> {
+    let window: Vec<f32> = (0..FFT_SIZE)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos())
+        .collect();
+    let bins = FFT_SIZE / 2;
+    let hop = (samples.len() / width as usize).max(1);
+
+    let mut buf = vec![(0f32, 0f32); FFT_SIZE];
+    for x in 0..width {
+        let start = x as usize * hop;
+        if start >= samples.len() {
+            break;
+        }
+        for (i, slot) in buf.iter_mut().enumerate() {
+            let s = samples.get(start + i).copied().unwrap_or(0.0);
+            *slot = (s * window[i], 0.0);
+        }
+        fft(&mut buf);
+
+        for y in 0..height {
+            // low frequencies at the bottom
+            let bin = ((height - 1 - y) as usize * bins) / height as usize;
+            let (re, im) = buf[bin];
+            let magnitude = (re * re + im * im).sqrt() / FFT_SIZE as f32;
+            let db = 20.0 * magnitude.max(1e-9).log10();
+            // map [-100dB, 0dB] to [0, 1]
+            let level = ((db + 100.0) / 100.0).clamp(0.0, 1.0);
+            root.draw_pixel((x as i32, y as i32), &heat_color(level))
+                .map_err(drawing_error)?;
+        }
+    }
+    Ok(())
+}
+
+/// In-place iterative radix-2 FFT, length must be a power of two.
+pub(crate) fn fft(buf: &mut [(f32, f32)]) {
+    let n = buf.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            buf.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_re, w_im) = ((angle * k as f32).cos(), (angle * k as f32).sin());
+                let (a_re, a_im) = buf[start + k];
+                let (b_re, b_im) = buf[start + k + len / 2];
+                let (t_re, t_im) = (b_re * w_re - b_im * w_im, b_re * w_im + b_im * w_re);
+                buf[start + k] = (a_re + t_re, a_im + t_im);
+                buf[start + k + len / 2] = (a_re - t_re, a_im - t_im);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Black -> blue -> red -> yellow -> white.
+fn heat_color(level: f32) -> RGBColor {
+    const STOPS: [(f32, f32, f32); 5] = [
+        (0.0, 0.0, 0.0),
+        (0.1, 0.1, 0.6),
+        (0.8, 0.1, 0.2),
+        (1.0, 0.8, 0.1),
+        (1.0, 1.0, 1.0),
+    ];
+    let pos = level * (STOPS.len() - 1) as f32;
+    let idx = (pos as usize).min(STOPS.len() - 2);
+    let t = pos - idx as f32;
+    let (a, b) = (STOPS[idx], STOPS[idx + 1]);
+    let lerp = |a: f32, b: f32| ((a + (b - a) * t) * 255.0) as u8;
+    RGBColor(lerp(a.0, b.0), lerp(a.1, b.1), lerp(a.2, b.2))
+}
+
+fn drawing_error<E: std::error::Error + Send + Sync>(e: DrawingAreaErrorKind<E>) -> RenderError {
+    RenderError::Drawing(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fft_sine_peak() {
+        let mut buf: Vec<(f32, f32)> = (0..64)
+            .map(|i| ((2.0 * PI * 8.0 * i as f32 / 64.0).sin(), 0.0))
+            .collect();
+        fft(&mut buf);
+        let peak = (0..32)
+            .max_by(|&a, &b| {
+                let ma = buf[a].0.hypot(buf[a].1);
+                let mb = buf[b].0.hypot(buf[b].1);
+                ma.partial_cmp(&mb).unwrap()
+            })
+            .unwrap();
+        assert_eq!(peak, 8);
+    }
+
+    #[test]
+    fn test_render_wav() {
+        let wav_data = std::fs::read("test_files/test_sound.wav").unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        for mode in [RenderMode::Waveform, RenderMode::Spectrogram] {
+            let output = output_dir.path().join("render.png");
+            render_wav(&wav_data, mode, &output, (320, 120)).unwrap();
+            assert!(output.is_file());
+        }
+    }
+}
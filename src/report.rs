@@ -0,0 +1,320 @@
+//! Shareable HTML report of a project, listing entries with audio previews.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use log::{debug, warn};
+
+use crate::{
+    manifest::ProjectManifest,
+    project::{self, IdOrIndex, MediaEntry, ProjectError, SoundToolProject},
+    transcode::{self, TranscodeError},
+    utils::escape_html,
+};
+
+type Result<T> = std::result::Result<T, ReportError>;
+
+pub const REPORT_FILE_NAME: &str = "report.html";
+/// Subfolder of decoded previews, next to the report.
+pub const REPORT_AUDIO_DIR: &str = "report_audio";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReportError {
+    #[error("Report IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Project(#[from] ProjectError),
+}
+
+/// Result of writing a report.
+#[derive(Debug)]
+pub struct ReportSummary {
+    pub path: PathBuf,
+    pub entries: usize,
+    /// Entries with an audio preview.
+    pub previews: usize,
+}
+
+struct ReportRow {
+    entry: MediaEntry,
+    size: usize,
+    duration: Option<f32>,
+    replaced_by: Option<String>,
+    preview: Option<String>,
+}
+
+/// Write `report.html` of a project to output directory.
+///
+/// Replaced entries are previewed with their replace files, or the entries
+/// `[[replace]]` directives copy from other bundles, so the report sounds
+/// like the repacked bundle. Previews are encoded to ogg when ffmpeg is
+/// available, or kept as wav.
+pub fn write_report(
+    project: &SoundToolProject,
+    output_dir: impl AsRef<Path>,
+    with_audio: bool,
+) -> Result<ReportSummary> {
+    let output_dir = output_dir.as_ref();
+    let audio_dir = output_dir.join(REPORT_AUDIO_DIR);
+    fs::create_dir_all(output_dir)?;
+    if with_audio {
+        fs::create_dir_all(&audio_dir)?;
+    }
+    let replace_files = project::replace_files(project.project_path())?;
+    let manifest = ProjectManifest::load(project.project_path()).map_err(ProjectError::from)?;
+
+    let mut with_audio = with_audio;
+    let mut ogg_available = true;
+    let mut rows = vec![];
+    for entry in project.media_entries()? {
+        let data = project.read_media(&entry)?;
+        let replace_file = replace_files
+            .get(&IdOrIndex::Index(entry.idx))
+            .or_else(|| replace_files.get(&IdOrIndex::Id(entry.id)));
+        // replace files take precedence over directives, like on repack
+        let copied_from = replace_file
+            .is_none()
+            .then(|| copied_from(&manifest, &entry))
+            .flatten();
+        let replaced_by = match (replace_file, copied_from) {
+            (Some(path), _) => Some(path.file_name().unwrap().to_string_lossy().to_string()),
+            (None, Some((bundle, source))) => Some(format!("{} {}", bundle.display(), source)),
+            (None, None) => None,
+        };
+        let mut row = ReportRow {
+            size: data.len(),
+            duration: None,
+            replaced_by,
+            preview: None,
+            entry,
+        };
+        if !with_audio {
+            rows.push(row);
+            continue;
+        }
+
+        let preview = match copied_from {
+            Some((bundle, source)) => match project::find_bundle(project.project_path(), bundle)
+                .and_then(|bundle_path| project::read_entry(bundle_path, source))
+            {
+                Ok(copied) => preview_wav(&copied, None),
+                Err(e) => {
+                    warn!(
+                        "Failed to read entry [{}] copied from '{}': {}",
+                        row.entry.idx,
+                        bundle.display(),
+                        e
+                    );
+                    rows.push(row);
+                    continue;
+                }
+            },
+            None => preview_wav(&data, replace_file),
+        };
+        let wav = match preview {
+            Ok(wav) => wav,
+            Err(e @ (TranscodeError::ToolNotFound(_) | TranscodeError::ToolNotConfigured(_))) => {
+                warn!("Audio previews skipped: {}", e);
+                with_audio = false;
+                rows.push(row);
+                continue;
+            }
+            Err(e) => {
+                warn!("Failed to decode entry [{}]: {}", row.entry.idx, e);
+                rows.push(row);
+                continue;
+            }
+        };
+        row.duration = wav_duration(&wav);
+        let stem = format!("{}_{}", row.entry.idx, row.entry.id);
+        let mut preview = (format!("{}.wav", stem), wav);
+        if ogg_available {
+            match transcode::wav_to_ogg(&preview.1) {
+                Ok(ogg) => preview = (format!("{}.ogg", stem), ogg),
+                Err(e) => {
+                    warn!("Previews are kept as wav, failed to encode ogg: {}", e);
+                    ogg_available = false;
+                }
+            }
+        }
+        debug!("Preview: {}", preview.0);
+        fs::write(audio_dir.join(&preview.0), &preview.1)?;
+        row.preview = Some(format!("{}/{}", REPORT_AUDIO_DIR, preview.0));
+        rows.push(row);
+    }
+
+    let path = output_dir.join(REPORT_FILE_NAME);
+    let mut writer = io::BufWriter::new(fs::File::create(&path)?);
+    writer.write_all(render_html(project, &rows).as_bytes())?;
+    writer.flush()?;
+
+    Ok(ReportSummary {
+        path,
+        entries: rows.len(),
+        previews: rows.iter().filter(|row| row.preview.is_some()).count(),
+    })
+}
+
+/// Bundle and entry a `[[replace]]` directive copies to an entry.
+fn copied_from<'a>(
+    manifest: &'a ProjectManifest,
+    entry: &MediaEntry,
+) -> Option<(&'a Path, IdOrIndex)> {
+    [IdOrIndex::Index(entry.idx), IdOrIndex::Id(entry.id)]
+        .iter()
+        .find_map(|target| {
+            manifest
+                .replace
+                .iter()
+                .filter(|directive| directive.target == *target)
+                .find_map(|directive| {
+                    Some((directive.from_bundle.as_deref()?, directive.source_entry()?))
+                })
+        })
+}
+
+fn preview_wav(
+    data: &[u8],
+    replace_file: Option<&PathBuf>,
+) -> std::result::Result<Vec<u8>, TranscodeError> {
+    let Some(replace_file) = replace_file else {
+        return transcode::wem_to_wav(data);
+    };
+    match replace_file.extension().unwrap_or_default().to_str() {
+        Some("wem") => transcode::wem_to_wav(&fs::read(replace_file)?),
+        Some("wav") => Ok(fs::read(replace_file)?),
+        _ => Ok(transcode::sounds_to_wav(&[replace_file])?.remove(0)),
+    }
+}
+
+fn wav_duration(wav: &[u8]) -> Option<f32> {
+    let reader = hound::WavReader::new(io::Cursor::new(wav)).ok()?;
+    let sample_rate = reader.spec().sample_rate;
+    (sample_rate > 0).then(|| reader.duration() as f32 / sample_rate as f32)
+}
+
+fn render_html(project: &SoundToolProject, rows: &[ReportRow]) -> String {
+    let title = escape_html(project.source_file_name());
+    let mut meta = vec![
+        ("Source", project.source_file_name().to_string()),
+        ("Entries", rows.len().to_string()),
+        (
+            "Total size",
+            format!("{} bytes", rows.iter().map(|row| row.size).sum::<usize>()),
+        ),
+        (
+            "Replaced",
+            rows.iter()
+                .filter(|row| row.replaced_by.is_some())
+                .count()
+                .to_string(),
+        ),
+    ];
+    if let Some(version) = project.game_version() {
+        meta.push(("Game version", version.to_string()));
+    }
+    if let Some(fingerprint) = project.source_fingerprint() {
+        meta.push((
+            "Bundle",
+            format!(
+                "version {}, sha256 {}",
+                fingerprint.version, fingerprint.sha256
+            ),
+        ));
+    }
+    meta.push(("Tool version", env!("CARGO_PKG_VERSION").to_string()));
+    let meta: String = meta
+        .into_iter()
+        .map(|(name, value)| {
+            format!(
+                "<tr><th>{}</th><td>{}</td></tr>\n",
+                escape_html(name),
+                escape_html(&value)
+            )
+        })
+        .collect();
+
+    let mut entries = String::new();
+    for row in rows {
+        let duration = row
+            .duration
+            .map(|d| format!("{:.2}s", d))
+            .unwrap_or_else(|| "-".to_string());
+        let preview = match &row.preview {
+            Some(src) => format!(
+                "<audio controls preload=\"none\" src=\"{}\"></audio>",
+                escape_html(src)
+            ),
+            None => "-".to_string(),
+        };
+        entries += &format!(
+            "<tr{class}><td>{idx}</td><td>{id}</td><td>{lang}</td><td>{size}</td>\
+             <td>{duration}</td><td>{replaced}</td><td>{preview}</td></tr>\n",
+            class = if row.replaced_by.is_some() {
+                " class=\"replaced\""
+            } else {
+                ""
+            },
+            idx = row.entry.idx,
+            id = row.entry.id,
+            lang = escape_html(row.entry.language.as_deref().unwrap_or("-")),
+            size = row.size,
+            replaced = escape_html(row.replaced_by.as_deref().unwrap_or("-")),
+        );
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html><head><meta charset="utf-8"><title>{title}</title>
+<style>
+body {{ font-family: sans-serif; }}
+table {{ border-collapse: collapse; margin-bottom: 1em; }}
+th, td {{ border: 1px solid #ccc; padding: 2px 8px; text-align: left; }}
+tr.replaced {{ background: #fff4d6; }}
+</style></head>
+<body><h1>{title}</h1>
+<table>
+{meta}</table>
+<table>
+<tr><th>Index</th><th>ID</th><th>Language</th><th>Size</th><th>Duration</th><th>Replaced by</th><th>Preview</th></tr>
+{entries}</table>
+</body></html>
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_report() {
+        let output_root = tempfile::tempdir().unwrap();
+        let project =
+            SoundToolProject::dump_bnk("test_files/Wp00_Cmn_m.sbnk.1.X64", output_root.path())
+                .unwrap();
+        let entries = project.media_entries().unwrap();
+        let replace_root = project.project_path().join("replace");
+        fs::create_dir_all(&replace_root).unwrap();
+        fs::write(replace_root.join("[0].wem"), b"RIFF").unwrap();
+        fs::write(
+            project.project_path().join("project.toml"),
+            "[[replace]]\ntarget = \"[1]\"\nfrom_bundle = \"<Other>.sbnk.1.X64\"\nfrom_index = 2\n",
+        )
+        .unwrap();
+
+        let summary = write_report(&project, project.project_path(), false).unwrap();
+        assert_eq!(summary.entries, entries.len());
+        assert_eq!(summary.previews, 0);
+        let html = fs::read_to_string(&summary.path).unwrap();
+        assert!(html.contains("Wp00_Cmn_m.sbnk.1.X64"));
+        assert!(html.contains(&format!("<td>{}</td>", entries[0].id)));
+        assert!(html.contains("<tr class=\"replaced\"><td>0</td>"));
+        assert!(html.contains("<tr class=\"replaced\"><td>1</td>"));
+        assert!(html.contains("<td>&lt;Other&gt;.sbnk.1.X64 [2]</td>"));
+    }
+}
@@ -0,0 +1,290 @@
+//! User level replace rules applied to repacked bundles.
+//!
+//! Rules are read from `rules.toml` next to `config.toml`, and only applied
+//! when passed to [`crate::project::SoundToolProject::repack_with_rules`],
+//! e.g. by `package-project --rules`. They match entries
+//! by unique ID or by resolved name, from installed data packs or names
+//! appended to dumped file names:
+//!
+//! ```toml
+//! [[rule]]
+//! name = "*_Heartbeat*"
+//! action = "mute"
+//!
+//! [[rule]]
+//! bundle = "Wp00_*"
+//! id = 123456
+//! action = "replace"
+//! file = "sounds/quiet_swing.wem"
+//! ```
+//!
+//! Patterns are case insensitive, `*` matches any characters and `?` one.
+//! The first matching rule applies. Files and directives of a project take
+//! precedence over rules.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+use serde::Deserialize;
+
+use crate::{datapack::DataPacks, names::NameList};
+
+type Result<T> = std::result::Result<T, RulesError>;
+
+pub const RULES_FILE_NAME: &str = "rules.toml";
+
+#[derive(Debug, thiserror::Error)]
+pub enum RulesError {
+    #[error("Failed to access {}: {source}", path.display())]
+    File { path: PathBuf, source: io::Error },
+
+    #[error("Bad rules file {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("Invalid rule {index}: {reason}")]
+    InvalidRule { index: usize, reason: &'static str },
+}
+
+impl RulesError {
+    /// Attach the path to an IO error.
+    fn file(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| RulesError::File { path, source }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RuleAction {
+    /// Replace with a short silent sound.
+    Mute,
+    /// Remove the entry from the bundle.
+    Remove,
+    /// Replace with `file`.
+    Replace,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReplaceRule {
+    /// Pattern of bundle file names the rule applies to, all bundles if not set.
+    #[serde(default)]
+    pub bundle: Option<String>,
+    /// Pattern of resolved entry names.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Unique ID of the entry.
+    #[serde(default)]
+    pub id: Option<u32>,
+    pub action: RuleAction,
+    /// Replacement sound file of `replace` action, relative to the rules file.
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+}
+
+impl ReplaceRule {
+    /// Whether the rule matches an entry of a bundle.
+    pub fn matches(&self, bundle_file_name: &str, id: u32, name: Option<&str>) -> bool {
+        if let Some(pattern) = &self.bundle
+            && !glob_match(pattern, bundle_file_name)
+        {
+            return false;
+        }
+        if self.id.is_some_and(|rule_id| rule_id != id) {
+            return false;
+        }
+        match (&self.name, name) {
+            (Some(pattern), Some(name)) => glob_match(pattern, name),
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ReplaceRules {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<ReplaceRule>,
+}
+
+impl ReplaceRules {
+    /// Load rules from a file, empty if it does not exist.
+    ///
+    /// Relative replacement files are resolved against the folder of the file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path).map_err(RulesError::file(path))?;
+        let mut rules: Self = toml::from_str(&content).map_err(|source| RulesError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        for (index, rule) in rules.rules.iter_mut().enumerate() {
+            let invalid = |reason| RulesError::InvalidRule { index, reason };
+            if rule.name.is_none() && rule.id.is_none() {
+                return Err(invalid("name or id is required"));
+            }
+            match (rule.action, &mut rule.file) {
+                (RuleAction::Replace, None) => return Err(invalid("replace requires file")),
+                (RuleAction::Replace, Some(file)) => *file = base_dir.join(&*file),
+                (_, Some(_)) => return Err(invalid("file is only used by replace")),
+                _ => {}
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Rules next to `config.toml`.
+    pub fn installed() -> Result<Self> {
+        Self::load(RULES_FILE_NAME)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// First rule matching an entry of a bundle.
+    pub fn find(
+        &self,
+        bundle_file_name: &str,
+        id: u32,
+        name: Option<&str>,
+    ) -> Option<&ReplaceRule> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(bundle_file_name, id, name))
+    }
+}
+
+/// Rules applied on repack, with names entries are matched by.
+#[derive(Debug, Clone, Default)]
+pub struct RuleSet {
+    pub rules: ReplaceRules,
+    pub names: NameList,
+}
+
+impl RuleSet {
+    /// Rules next to `config.toml`, matching names of installed data packs.
+    pub fn installed() -> Result<Self> {
+        Self::installed_with(None)
+    }
+
+    /// Rules next to `config.toml`, matching names of the data packs in
+    /// `datapacks_dir`, or the installed ones.
+    pub fn installed_with(datapacks_dir: Option<&Path>) -> Result<Self> {
+        let rules = ReplaceRules::installed()?;
+        let names = if rules.is_empty() {
+            NameList::default()
+        } else {
+            let packs = match datapacks_dir {
+                Some(dir) => DataPacks::load(dir),
+                None => DataPacks::installed(),
+            };
+            match packs {
+                Ok(packs) => packs.name_list(),
+                Err(e) => {
+                    warn!(
+                        "Failed to load data packs, rules match annotated names only: {}",
+                        e
+                    );
+                    NameList::default()
+                }
+            }
+        };
+        Ok(Self { rules, names })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+/// Case insensitive match of `*` and `?` wildcards.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // position after the last `*`, and text position it was tried at
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*_Heartbeat*", "Play_heartbeat_loop"));
+        assert!(glob_match("wp00_*.sbnk.?.x64", "Wp00_Cmn.sbnk.1.X64"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("*_Heartbeat", "Play_Heartbeat_Loop"));
+        assert!(!glob_match("a?c", "ac"));
+    }
+
+    #[test]
+    fn test_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(RULES_FILE_NAME);
+        assert!(ReplaceRules::load(&path).unwrap().is_empty());
+
+        fs::write(
+            &path,
+            r#"
+            [[rule]]
+            name = "*_Heartbeat*"
+            action = "mute"
+
+            [[rule]]
+            bundle = "Wp00_*"
+            id = 123456
+            action = "replace"
+            file = "quiet.wem"
+            "#,
+        )
+        .unwrap();
+        let rules = ReplaceRules::load(&path).unwrap();
+        let rule = rules.find("Em0001.sbnk.1.X64", 1, Some("Play_Heartbeat_Loop"));
+        assert_eq!(rule.unwrap().action, RuleAction::Mute);
+        assert!(rules.find("Em0001.sbnk.1.X64", 123456, None).is_none());
+        let rule = rules.find("Wp00_Cmn.sbnk.1.X64", 123456, None).unwrap();
+        assert_eq!(
+            rule.file.as_deref(),
+            Some(dir.path().join("quiet.wem").as_path())
+        );
+
+        fs::write(&path, "[[rule]]\nid = 1\naction = \"replace\"\n").unwrap();
+        assert!(matches!(
+            ReplaceRules::load(&path),
+            Err(RulesError::InvalidRule { index: 0, .. })
+        ));
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use log::{debug, warn};
+
+use crate::{pck, transcode, utils};
+
+type Result<T> = std::result::Result<T, ServeError>;
+
+/// Idle connections are closed after this, so they do not hold a thread.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+/// Delay before accepting again after a failed accept, e.g. out of file handles.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Debug, thiserror::Error)]
+pub enum ServeError {
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("Failed to parse pck file: {0}")]
+    Pck(#[from] pck::PckError),
+}
+
+/// Read-only HTTP server previewing entries of a PCK file.
+///
+/// Only the PCK header is loaded at startup, entries are read from
+/// the file (and decoded) on demand.
+pub struct PreviewServer {
+    pck_path: PathBuf,
+    header: pck::PckHeader,
+    listener: TcpListener,
+}
+
+impl PreviewServer {
+    pub fn bind(pck_path: impl AsRef<Path>, addr: impl ToSocketAddrs) -> Result<Self> {
+        let pck_path = pck_path.as_ref().to_path_buf();
+        let mut reader = io::BufReader::new(File::open(&pck_path)?);
+        let header = pck::PckHeader::from_reader(&mut reader)?;
+        let listener = TcpListener::bind(addr)?;
+        Ok(Self {
+            pck_path,
+            header,
+            listener,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serve requests until the process is terminated, each connection on
+    /// its own thread. Failed accepts are logged and do not stop the server.
+    pub fn serve_forever(&self) -> Result<()> {
+        thread::scope(|scope| {
+            loop {
+                match self.listener.accept() {
+                    Ok((stream, peer)) => {
+                        scope.spawn(move || self.handle(stream, peer));
+                    }
+                    Err(e) => {
+                        warn!("Failed to accept connection: {}", e);
+                        thread::sleep(ACCEPT_RETRY_DELAY);
+                    }
+                }
+            }
+        })
+    }
+
+    /// Accept and handle a single connection.
+    #[cfg(test)]
+    fn accept_one(&self) -> Result<()> {
+        let (stream, peer) = self.listener.accept()?;
+        self.handle(stream, peer);
+        Ok(())
+    }
+
+    fn handle(&self, stream: TcpStream, peer: SocketAddr) {
+        debug!("Connection from {}", peer);
+        if let Err(e) = self.handle_connection(stream) {
+            warn!("Failed to handle request from {}: {}", peer, e);
+        }
+    }
+
+    fn handle_connection(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+        stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+        let mut request_line = String::new();
+        io::BufReader::new(&stream).read_line(&mut request_line)?;
+        let mut parts = request_line.split_whitespace();
+        let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+            return write_response(&mut stream, 400, "text/plain", b"Bad Request");
+        };
+        if method != "GET" {
+            return write_response(&mut stream, 405, "text/plain", b"Method Not Allowed");
+        }
+
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match segments.as_slice() {
+            [""] => {
+                let html = self.index_html();
+                write_response(
+                    &mut stream,
+                    200,
+                    "text/html; charset=utf-8",
+                    html.as_bytes(),
+                )
+            }
+            ["wem", index] | ["wav", index] => {
+                let Some(data) = index
+                    .parse()
+                    .ok()
+                    .and_then(|i| self.read_wem(i).transpose())
+                else {
+                    return write_response(&mut stream, 404, "text/plain", b"Not Found");
+                };
+                let data = data?;
+                if segments[0] == "wem" {
+                    return write_response(&mut stream, 200, "application/octet-stream", &data);
+                }
+                match transcode::wem_to_wav(&data) {
+                    Ok(wav) => write_response(&mut stream, 200, "audio/wav", &wav),
+                    Err(e) => {
+                        let message = format!("Failed to decode: {}", e);
+                        write_response(&mut stream, 500, "text/plain", message.as_bytes())
+                    }
+                }
+            }
+            _ => write_response(&mut stream, 404, "text/plain", b"Not Found"),
+        }
+    }
+
+    fn read_wem(&self, index: usize) -> io::Result<Option<Vec<u8>>> {
+        let mut file = io::BufReader::new(File::open(&self.pck_path)?);
+        let Some(mut reader) = self.header.wem_reader(&mut file, index) else {
+            return Ok(None);
+        };
+        let mut data = vec![];
+        io::copy(&mut reader, &mut data)?;
+        Ok(Some(data))
+    }
+
+    fn index_html(&self) -> String {
+        let title = utils::escape_html(
+            &self
+                .pck_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy(),
+        );
+        let mut rows = String::new();
+        for (i, entry) in self.header.wem_entries.iter().enumerate() {
+            rows += &format!(
+                "<tr><td>{i}</td><td>{id}</td><td>{lang}</td><td>{len}</td>\
+                 <td><audio controls preload=\"none\" src=\"/wav/{i}\"></audio></td>\
+                 <td><a href=\"/wem/{i}\">wem</a></td></tr>\n",
+                id = entry.id,
+                lang = entry.language_id,
+                len = entry.length,
+            );
+        }
+        format!(
+            r#"<!DOCTYPE html>
+<html><head><meta charset="utf-8"><title>{title}</title></head>
+<body><h1>{title}</h1>
+<table>
+<tr><th>Index</th><th>ID</th><th>Language</th><th>Size</th><th>Preview</th><th>Raw</th></tr>
+{rows}</table>
+</body></html>"#
+        )
+    }
+}
+
+fn write_response(
+    stream: &mut TcpStream,
+    status: u16,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    const TEST_PCK: &str = "test_files/Cat_cmn_m.spck.1.X64";
+
+    fn get(addr: SocketAddr, path: &str) -> Vec<u8> {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = vec![];
+        stream.read_to_end(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serve_wem() {
+        let server = PreviewServer::bind(TEST_PCK, "127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = std::thread::spawn(move || {
+            for _ in 0..3 {
+                server.accept_one().unwrap();
+            }
+        });
+
+        let index = get(addr, "/");
+        assert!(index.starts_with(b"HTTP/1.1 200"));
+        let wem = get(addr, "/wem/0");
+        assert!(wem.starts_with(b"HTTP/1.1 200"));
+        let body_start = wem.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        assert_eq!(&wem[body_start..body_start + 4], b"RIFF");
+        let missing = get(addr, "/wem/99999");
+        assert!(missing.starts_with(b"HTTP/1.1 404"));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_serve_concurrent() {
+        let server = PreviewServer::bind(TEST_PCK, "127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        // runs until the test process exits
+        std::thread::spawn(move || server.serve_forever());
+
+        // an idle connection does not block others
+        let _idle = TcpStream::connect(addr).unwrap();
+        let index = get(addr, "/");
+        assert!(index.starts_with(b"HTTP/1.1 200"));
+    }
+}
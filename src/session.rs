@@ -0,0 +1,118 @@
+//! Progress of a curation session in the GUI, stored as `.session.json` in
+//! project folder, so it can be resumed after the window is closed mid-way.
+//!
+//! Replace files are copied to `replace/` as soon as they are assigned, the
+//! session keeps what is not on disk otherwise:
+//!
+//! ```json
+//! { "selected": 12, "done": [0, 1, 2] }
+//! ```
+
+use std::{
+    collections::BTreeSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+type Result<T> = std::result::Result<T, SessionError>;
+
+pub const SESSION_FILE_NAME: &str = ".session.json";
+
+#[derive(Debug, thiserror::Error)]
+pub enum SessionError {
+    #[error("Failed to access {}: {source}", path.display())]
+    File { path: PathBuf, source: io::Error },
+
+    #[error("Bad session file {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+}
+
+impl SessionError {
+    /// Attach the path to an IO error.
+    fn file(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| SessionError::File { path, source }
+    }
+}
+
+/// Selections of a session, entries by index.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// Selected entry.
+    #[serde(default)]
+    pub selected: Option<u32>,
+    /// Entries marked done.
+    #[serde(default)]
+    pub done: BTreeSet<u32>,
+}
+
+impl Session {
+    /// Load from project folder, `None` if no session was saved.
+    pub fn load(project_path: impl AsRef<Path>) -> Result<Option<Self>> {
+        let path = project_path.as_ref().join(SESSION_FILE_NAME);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = fs::read(&path).map_err(SessionError::file(&path))?;
+        serde_json::from_slice(&content)
+            .map(Some)
+            .map_err(|source| SessionError::Parse { path, source })
+    }
+
+    /// Save to project folder, an empty session removes the file.
+    pub fn save(&self, project_path: impl AsRef<Path>) -> Result<()> {
+        if self.is_empty() {
+            return Self::remove(project_path);
+        }
+        let path = project_path.as_ref().join(SESSION_FILE_NAME);
+        let content = serde_json::to_vec_pretty(self).map_err(|source| SessionError::Parse {
+            path: path.clone(),
+            source,
+        })?;
+        fs::write(&path, content).map_err(SessionError::file(&path))?;
+        Ok(())
+    }
+
+    pub fn remove(project_path: impl AsRef<Path>) -> Result<()> {
+        let path = project_path.as_ref().join(SESSION_FILE_NAME);
+        if path.is_file() {
+            fs::remove_file(&path).map_err(SessionError::file(&path))?;
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.selected.is_none() && self.done.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(Session::load(dir.path()).unwrap(), None);
+        let session = Session {
+            selected: Some(12),
+            done: BTreeSet::from([0, 1, 2]),
+        };
+        session.save(dir.path()).unwrap();
+        assert_eq!(Session::load(dir.path()).unwrap(), Some(session));
+
+        Session::default().save(dir.path()).unwrap();
+        assert!(!dir.path().join(SESSION_FILE_NAME).exists());
+
+        fs::write(dir.path().join(SESSION_FILE_NAME), "{").unwrap();
+        assert!(matches!(
+            Session::load(dir.path()),
+            Err(SessionError::Parse { .. })
+        ));
+    }
+}
@@ -0,0 +1,339 @@
+//! Group entries of a bundle by audio similarity, to find the variants of a
+//! sound, e.g. the random footsteps of one container, and replace them together.
+//!
+//! Each entry is decoded and described by its duration and the shape of its
+//! average spectrum in log spaced bands. Groups are merged closest first as
+//! long as all of their entries are closer than a threshold.
+
+use std::{f32::consts::PI, io, path::Path};
+
+use log::warn;
+use serde::Serialize;
+
+use crate::{
+    detect::InputFileType,
+    project::{ProjectError, SoundToolProject},
+    render::{self, RenderError},
+    transcode::{self, TranscodeError},
+};
+
+type Result<T> = std::result::Result<T, SimilarError>;
+
+/// Default distance under which entries are grouped, from 0 to 1.
+pub const DEFAULT_THRESHOLD: f32 = 0.15;
+
+const FFT_SIZE: usize = 1024;
+const BAND_COUNT: usize = 24;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SimilarError {
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("Threshold must be from 0 to 1, got {0}")]
+    InvalidThreshold(f32),
+
+    #[error("{0}")]
+    Project(#[from] ProjectError),
+    #[error("{0}")]
+    Transcode(#[from] TranscodeError),
+    #[error("{0}")]
+    Wav(#[from] RenderError),
+}
+
+/// Audio features of an entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Features {
+    /// Duration in seconds.
+    pub duration: f32,
+    /// Average magnitude in log spaced bands, normalized to sum to 1.
+    pub bands: [f32; BAND_COUNT],
+}
+
+impl Features {
+    /// Features of wav data, `None` if it is silent or empty.
+    pub fn from_wav(wav_data: &[u8]) -> Result<Option<Self>> {
+        let sample_rate = hound::WavReader::new(io::Cursor::new(wav_data))
+            .map_err(RenderError::from)?
+            .spec()
+            .sample_rate;
+        let samples = render::read_mono_samples(wav_data)?;
+        if samples.is_empty() || sample_rate == 0 {
+            return Ok(None);
+        }
+
+        let window: Vec<f32> = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos())
+            .collect();
+        let mut spectrum = vec![0f32; FFT_SIZE / 2];
+        let mut buf = vec![(0f32, 0f32); FFT_SIZE];
+        for frame in samples.chunks(FFT_SIZE) {
+            for (i, slot) in buf.iter_mut().enumerate() {
+                *slot = (frame.get(i).copied().unwrap_or(0.0) * window[i], 0.0);
+            }
+            render::fft(&mut buf);
+            for (magnitude, (re, im)) in spectrum.iter_mut().zip(&buf) {
+                *magnitude += (re * re + im * im).sqrt();
+            }
+        }
+
+        let mut bands = [0f32; BAND_COUNT];
+        for (band, value) in bands.iter_mut().enumerate() {
+            let (start, end) = band_bins(band);
+            *value = spectrum[start..end].iter().sum::<f32>() / (end - start) as f32;
+        }
+        let total: f32 = bands.iter().sum();
+        if total <= f32::EPSILON {
+            return Ok(None);
+        }
+        bands.iter_mut().for_each(|value| *value /= total);
+
+        Ok(Some(Self {
+            duration: samples.len() as f32 / sample_rate as f32,
+            bands,
+        }))
+    }
+
+    /// Distance to other features, from 0 for the same sound to 1, the mean
+    /// of the relative duration difference and the spectral shape difference.
+    pub fn distance(&self, other: &Self) -> f32 {
+        let longer = self.duration.max(other.duration);
+        let duration = if longer > 0.0 {
+            (self.duration - other.duration).abs() / longer
+        } else {
+            0.0
+        };
+        let spectral = self
+            .bands
+            .iter()
+            .zip(&other.bands)
+            .map(|(a, b)| (a - b).abs())
+            .sum::<f32>()
+            / 2.0;
+        (duration + spectral) / 2.0
+    }
+}
+
+/// Range of spectrum bins of a band, log spaced from the lowest bin.
+fn band_bins(band: usize) -> (usize, usize) {
+    let bins = FFT_SIZE / 2;
+    let edge = |band: usize| (bins as f32).powf(band as f32 / BAND_COUNT as f32) as usize;
+    let start = edge(band).max(band + 1).min(bins - 1);
+    let end = edge(band + 1).max(start + 1).min(bins);
+    (start, end)
+}
+
+/// Groups of similar items by index, only groups of two or more, largest first.
+///
+/// Complete linkage clustering: the closest groups are merged while every
+/// item of one is closer than `threshold` to every item of the other, so
+/// distant items are not chained together through items in between.
+pub fn cluster(features: &[Features], threshold: f32) -> Vec<Vec<usize>> {
+    let count = features.len();
+    // distances between groups, a group is kept in the slot of one of its items
+    let mut distances = vec![0f32; count * count];
+    for i in 0..count {
+        for j in i + 1..count {
+            let distance = features[i].distance(&features[j]);
+            distances[i * count + j] = distance;
+            distances[j * count + i] = distance;
+        }
+    }
+
+    // nearest neighbor chain, merges happen in any order but the
+    // heights are the same as merging the closest pair each time
+    let mut parents: Vec<usize> = (0..count).collect();
+    let mut active = vec![true; count];
+    let mut remaining = count;
+    let mut chain: Vec<usize> = vec![];
+    while remaining > 1 {
+        if chain.is_empty() {
+            chain.extend(active.iter().position(|&is_active| is_active));
+        }
+        let a = chain[chain.len() - 1];
+        // prefer the previous group of the chain on ties, so the chain ends
+        let previous = chain.len().checked_sub(2).map(|i| chain[i]);
+        let mut nearest = previous;
+        let mut nearest_distance = previous.map_or(f32::INFINITY, |b| distances[a * count + b]);
+        for b in (0..count).filter(|&b| active[b] && b != a) {
+            if distances[a * count + b] < nearest_distance {
+                nearest = Some(b);
+                nearest_distance = distances[a * count + b];
+            }
+        }
+        let b = nearest.expect("another group is active");
+        if previous != Some(b) {
+            chain.push(b);
+            continue;
+        }
+
+        chain.truncate(chain.len() - 2);
+        if nearest_distance < threshold {
+            let (root_a, root_b) = (root(&mut parents, a), root(&mut parents, b));
+            parents[root_a.max(root_b)] = root_a.min(root_b);
+        }
+        for k in (0..count).filter(|&k| active[k] && k != a && k != b) {
+            let distance = distances[a * count + k].max(distances[b * count + k]);
+            distances[a * count + k] = distance;
+            distances[k * count + a] = distance;
+        }
+        active[b] = false;
+        remaining -= 1;
+    }
+
+    let mut groups: Vec<Vec<usize>> = vec![];
+    let mut group_of_root: Vec<Option<usize>> = vec![None; count];
+    for i in 0..count {
+        let r = root(&mut parents, i);
+        match group_of_root[r] {
+            Some(group) => groups[group].push(i),
+            None => {
+                group_of_root[r] = Some(groups.len());
+                groups.push(vec![i]);
+            }
+        }
+    }
+    groups.retain(|group| group.len() > 1);
+    groups.sort_by_key(|group| std::cmp::Reverse(group.len()));
+    groups
+}
+
+/// Root of an item in a union-find forest, compressing the path.
+fn root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Entry in a group of similar entries.
+#[derive(Debug, Clone, Serialize)]
+pub struct SimilarEntry {
+    pub idx: u32,
+    pub id: u32,
+    pub duration: f32,
+}
+
+/// Find groups of similar entries in a project folder, or a BNK or PCK file.
+///
+/// Entries which fail to decode are skipped with a warning.
+pub fn find_similar(input: impl AsRef<Path>, threshold: f32) -> Result<Vec<Vec<SimilarEntry>>> {
+    let input = input.as_ref();
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(SimilarError::InvalidThreshold(threshold));
+    }
+    let tmp_dir = tempfile::tempdir()?;
+    let project = match InputFileType::from_path(input) {
+        Some(InputFileType::Bnk) => SoundToolProject::dump_bnk(input, tmp_dir.path())?,
+        Some(InputFileType::Pck) => SoundToolProject::dump_pck(input, tmp_dir.path())?,
+        _ => SoundToolProject::from_path(input)?,
+    };
+
+    let mut entries = vec![];
+    let mut features = vec![];
+    for entry in project.media_entries()? {
+        let data = project.read_media(&entry)?;
+        let wav = match transcode::wem_to_wav(&data) {
+            Ok(wav) => wav,
+            Err(e @ (TranscodeError::ToolNotFound(_) | TranscodeError::ToolNotConfigured(_))) => {
+                return Err(e.into());
+            }
+            Err(e) => {
+                warn!("Skipped entry [{}], failed to decode: {}", entry.idx, e);
+                continue;
+            }
+        };
+        match Features::from_wav(&wav) {
+            Ok(Some(entry_features)) => {
+                entries.push(SimilarEntry {
+                    idx: entry.idx,
+                    id: entry.id,
+                    duration: entry_features.duration,
+                });
+                features.push(entry_features);
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Skipped entry [{}]: {}", entry.idx, e),
+        }
+    }
+
+    Ok(cluster(&features, threshold)
+        .into_iter()
+        .map(|group| group.into_iter().map(|i| entries[i].clone()).collect())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(frequency: f32, duration: f32) -> Vec<u8> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut cursor = io::Cursor::new(vec![]);
+        let mut writer = hound::WavWriter::new(&mut cursor, spec).unwrap();
+        for i in 0..(duration * 48000.0) as usize {
+            let t = i as f32 / 48000.0;
+            writer
+                .write_sample(((2.0 * PI * frequency * t).sin() * 16000.0) as i16)
+                .unwrap();
+        }
+        writer.finalize().unwrap();
+        cursor.into_inner()
+    }
+
+    #[test]
+    fn test_cluster() {
+        let sounds = [
+            tone(440.0, 0.50),
+            tone(8000.0, 2.0),
+            tone(450.0, 0.52),
+            tone(440.0, 0.55),
+            tone(8000.0, 1.9),
+            tone(440.0, 3.0),
+        ];
+        let features: Vec<Features> = sounds
+            .iter()
+            .map(|wav| Features::from_wav(wav).unwrap().unwrap())
+            .collect();
+        assert!((features[0].duration - 0.5).abs() < 0.001);
+        assert!(features[0].distance(&features[0]) < f32::EPSILON);
+        assert_eq!(
+            cluster(&features, DEFAULT_THRESHOLD),
+            [vec![0, 2, 3], vec![1, 4]]
+        );
+
+        assert_eq!(Features::from_wav(&tone(440.0, 0.0)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_cluster_no_chaining() {
+        // 0 and 2 are only close through 1
+        let features: Vec<Features> = [1.0, 1.25, 1.6]
+            .into_iter()
+            .map(|duration| Features {
+                duration,
+                bands: [1.0 / BAND_COUNT as f32; BAND_COUNT],
+            })
+            .collect();
+        assert!(features[1].distance(&features[2]) < DEFAULT_THRESHOLD);
+        assert!(features[0].distance(&features[2]) > DEFAULT_THRESHOLD);
+        assert_eq!(cluster(&features, DEFAULT_THRESHOLD), [vec![0, 1]]);
+        assert!(cluster(&features, 0.0).is_empty());
+        assert_eq!(cluster(&features, 1.0), [vec![0, 1, 2]]);
+    }
+
+    #[test]
+    fn test_invalid_threshold() {
+        for threshold in [-0.1, 1.5, f32::NAN] {
+            assert!(matches!(
+                find_similar("missing", threshold),
+                Err(SimilarError::InvalidThreshold(_))
+            ));
+        }
+    }
+}
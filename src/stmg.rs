@@ -0,0 +1,299 @@
+//! Typed STMG section of init and music banks: state groups with their
+//! transition times, switch groups driven by game parameters, and the
+//! default ramping of game parameters.
+//!
+//! Parsed for bank version 145, acoustic textures and fields of other
+//! versions following the game parameters are kept as raw bytes. A section
+//! which does not encode back to identical bytes is kept raw as a whole.
+
+use std::io::{self, Read};
+
+use byteorder::{LE, ReadBytesExt, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stmg {
+    pub volume_threshold: f32,
+    pub max_voices: u16,
+    pub max_dangerous_virtual_voices: u16,
+    pub state_groups: Vec<StateGroup>,
+    pub switch_groups: Vec<SwitchGroup>,
+    pub game_parameters: Vec<GameParameter>,
+    /// Acoustic textures, kept as is.
+    pub rest: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateGroup {
+    pub id: u32,
+    /// Transition time in milliseconds between states without a transition below.
+    pub default_transition_time: u32,
+    pub transitions: Vec<StateTransition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateTransition {
+    pub from: u32,
+    pub to: u32,
+    /// Transition time in milliseconds.
+    pub time: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwitchGroup {
+    pub id: u32,
+    /// Game parameter the switch follows.
+    pub rtpc_id: u32,
+    pub rtpc_type: u8,
+    pub points: Vec<SwitchPoint>,
+}
+
+/// Point of the graph mapping game parameter values to switches.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwitchPoint {
+    /// Game parameter value.
+    pub from: f32,
+    /// Switch ID.
+    pub to: u32,
+    pub interpolation: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameParameter {
+    pub id: u32,
+    pub default_value: f32,
+    pub ramp_type: u32,
+    pub ramp_up: f32,
+    pub ramp_down: f32,
+    pub built_in_param: u8,
+}
+
+impl Stmg {
+    /// Parse a STMG section payload, `None` if it does not encode back to
+    /// the same bytes.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let stmg = Self::from_reader(&mut io::Cursor::new(data)).ok()?;
+        let mut encoded = vec![];
+        stmg.write_to(&mut encoded).ok()?;
+        (encoded == data).then_some(stmg)
+    }
+
+    fn from_reader(reader: &mut io::Cursor<&[u8]>) -> io::Result<Self> {
+        let volume_threshold = reader.read_f32::<LE>()?;
+        let max_voices = reader.read_u16::<LE>()?;
+        let max_dangerous_virtual_voices = reader.read_u16::<LE>()?;
+
+        let mut state_groups = vec![];
+        for _ in 0..reader.read_u32::<LE>()? {
+            let id = reader.read_u32::<LE>()?;
+            let default_transition_time = reader.read_u32::<LE>()?;
+            let mut transitions = vec![];
+            for _ in 0..reader.read_u32::<LE>()? {
+                transitions.push(StateTransition {
+                    from: reader.read_u32::<LE>()?,
+                    to: reader.read_u32::<LE>()?,
+                    time: reader.read_u32::<LE>()?,
+                });
+            }
+            state_groups.push(StateGroup {
+                id,
+                default_transition_time,
+                transitions,
+            });
+        }
+
+        let mut switch_groups = vec![];
+        for _ in 0..reader.read_u32::<LE>()? {
+            let id = reader.read_u32::<LE>()?;
+            let rtpc_id = reader.read_u32::<LE>()?;
+            let rtpc_type = reader.read_u8()?;
+            let mut points = vec![];
+            for _ in 0..reader.read_u32::<LE>()? {
+                points.push(SwitchPoint {
+                    from: reader.read_f32::<LE>()?,
+                    to: reader.read_u32::<LE>()?,
+                    interpolation: reader.read_u32::<LE>()?,
+                });
+            }
+            switch_groups.push(SwitchGroup {
+                id,
+                rtpc_id,
+                rtpc_type,
+                points,
+            });
+        }
+
+        let mut game_parameters = vec![];
+        for _ in 0..reader.read_u32::<LE>()? {
+            game_parameters.push(GameParameter {
+                id: reader.read_u32::<LE>()?,
+                default_value: reader.read_f32::<LE>()?,
+                ramp_type: reader.read_u32::<LE>()?,
+                ramp_up: reader.read_f32::<LE>()?,
+                ramp_down: reader.read_f32::<LE>()?,
+                built_in_param: reader.read_u8()?,
+            });
+        }
+
+        let mut rest = vec![];
+        reader.read_to_end(&mut rest)?;
+        Ok(Self {
+            volume_threshold,
+            max_voices,
+            max_dangerous_virtual_voices,
+            state_groups,
+            switch_groups,
+            game_parameters,
+            rest,
+        })
+    }
+
+    pub fn write_to<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_f32::<LE>(self.volume_threshold)?;
+        writer.write_u16::<LE>(self.max_voices)?;
+        writer.write_u16::<LE>(self.max_dangerous_virtual_voices)?;
+
+        writer.write_u32::<LE>(self.state_groups.len() as u32)?;
+        for group in &self.state_groups {
+            writer.write_u32::<LE>(group.id)?;
+            writer.write_u32::<LE>(group.default_transition_time)?;
+            writer.write_u32::<LE>(group.transitions.len() as u32)?;
+            for transition in &group.transitions {
+                writer.write_u32::<LE>(transition.from)?;
+                writer.write_u32::<LE>(transition.to)?;
+                writer.write_u32::<LE>(transition.time)?;
+            }
+        }
+
+        writer.write_u32::<LE>(self.switch_groups.len() as u32)?;
+        for group in &self.switch_groups {
+            writer.write_u32::<LE>(group.id)?;
+            writer.write_u32::<LE>(group.rtpc_id)?;
+            writer.write_u8(group.rtpc_type)?;
+            writer.write_u32::<LE>(group.points.len() as u32)?;
+            for point in &group.points {
+                writer.write_f32::<LE>(point.from)?;
+                writer.write_u32::<LE>(point.to)?;
+                writer.write_u32::<LE>(point.interpolation)?;
+            }
+        }
+
+        writer.write_u32::<LE>(self.game_parameters.len() as u32)?;
+        for param in &self.game_parameters {
+            writer.write_u32::<LE>(param.id)?;
+            writer.write_f32::<LE>(param.default_value)?;
+            writer.write_u32::<LE>(param.ramp_type)?;
+            writer.write_f32::<LE>(param.ramp_up)?;
+            writer.write_f32::<LE>(param.ramp_down)?;
+            writer.write_u8(param.built_in_param)?;
+        }
+
+        writer.write_all(&self.rest)
+    }
+
+    /// Length of the encoded section payload.
+    pub fn byte_len(&self) -> u32 {
+        let state_groups: usize = self
+            .state_groups
+            .iter()
+            .map(|group| 12 + 12 * group.transitions.len())
+            .sum();
+        let switch_groups: usize = self
+            .switch_groups
+            .iter()
+            .map(|group| 13 + 12 * group.points.len())
+            .sum();
+        (8 + 4
+            + state_groups
+            + 4
+            + switch_groups
+            + 4
+            + 21 * self.game_parameters.len()
+            + self.rest.len()) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bnk::{Bnk, SectionPayload};
+
+    fn sample() -> Stmg {
+        Stmg {
+            volume_threshold: -80.0,
+            max_voices: 256,
+            max_dangerous_virtual_voices: 1000,
+            state_groups: vec![StateGroup {
+                id: 100,
+                default_transition_time: 500,
+                transitions: vec![StateTransition {
+                    from: 101,
+                    to: 102,
+                    time: 2000,
+                }],
+            }],
+            switch_groups: vec![SwitchGroup {
+                id: 200,
+                rtpc_id: 300,
+                rtpc_type: 0,
+                points: vec![
+                    SwitchPoint {
+                        from: 0.0,
+                        to: 201,
+                        interpolation: 9,
+                    },
+                    SwitchPoint {
+                        from: 50.0,
+                        to: 202,
+                        interpolation: 9,
+                    },
+                ],
+            }],
+            game_parameters: vec![GameParameter {
+                id: 300,
+                default_value: 0.5,
+                ramp_type: 0,
+                ramp_up: 1000.0,
+                ramp_down: 1000.0,
+                built_in_param: 0,
+            }],
+            rest: vec![0, 0, 0, 0],
+        }
+    }
+
+    #[test]
+    fn test_stmg() {
+        let stmg = sample();
+        let mut data = vec![];
+        stmg.write_to(&mut data).unwrap();
+        assert_eq!(data.len() as u32, stmg.byte_len());
+        assert_eq!(Stmg::parse(&data), Some(stmg.clone()));
+
+        // transition time edited in bank.json
+        let input = [
+            &b"BKHD"[..],
+            &8u32.to_le_bytes(),
+            &[0; 8],
+            b"STMG",
+            &(data.len() as u32).to_le_bytes(),
+            &data,
+        ]
+        .concat();
+        let bank = Bnk::from_reader(&mut io::Cursor::new(&input)).unwrap();
+        let mut json = serde_json::to_value(&bank).unwrap();
+        assert_eq!(json["sections"][1]["type"], "Stmg");
+        json["sections"][1]["content"]["state_groups"][0]["transitions"][0]["time"] = 750.into();
+        let edited: Bnk = serde_json::from_value(json).unwrap();
+        let mut output = io::Cursor::new(vec![]);
+        edited.write_to(&mut output).unwrap();
+        let output = Bnk::from_reader(&mut io::Cursor::new(output.into_inner())).unwrap();
+        let SectionPayload::Stmg(output) = &output.sections[1].payload else {
+            panic!("STMG not parsed");
+        };
+        assert_eq!(output.state_groups[0].transitions[0].time, 750);
+
+        // counts running past the end are not parsed
+        data[8] = 0xFF;
+        assert_eq!(Stmg::parse(&data), None);
+    }
+}
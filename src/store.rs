@@ -0,0 +1,339 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+
+use crate::utils;
+
+type Result<T> = std::result::Result<T, StoreError>;
+
+pub const STORE_FILE_NAME: &str = "media.store";
+pub const STORE_INDEX_FILE_NAME: &str = "media.json";
+
+const COMPRESSION_LEVEL: i32 = 9;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("Media store IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("Failed to parse media store index: {0}")]
+    Index(#[from] serde_json::Error),
+    #[error("File not found in media store: {0}")]
+    FileNotFound(String),
+    #[error("Media store is corrupted, hash mismatch: {0}")]
+    HashMismatch(String),
+    #[error(
+        "Original bundle {} not found in or next to project, or where it was dumped from, required for media.",
+        .0.display()
+    )]
+    BundleNotFound(PathBuf),
+    #[error("Entry {0} differs from the original bundle, it may have been updated.")]
+    BundleChanged(String),
+}
+
+/// Index of the media store, saved as `media.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StoreIndex {
+    /// File name to blob hash.
+    pub files: Vec<StoreFile>,
+    /// Compressed blobs in `media.store`, keyed by hex SHA-256 of raw data.
+    pub blobs: HashMap<String, StoreBlob>,
+    /// Bundle the blobs are read from uncompressed instead of `media.store`,
+    /// relative to project folder or its parent folder. Set in metadata
+    /// only projects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bundle: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoreFile {
+    pub name: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoreBlob {
+    pub offset: u64,
+    /// Compressed length in store.
+    pub length: u64,
+    /// Raw data size.
+    pub size: u64,
+}
+
+/// Content-addressed, zstd compressed pack of dumped entries.
+///
+/// Identical files are stored once, each blob is compressed separately
+/// so files can be read without decompressing the whole store.
+///
+/// Metadata only projects have an index of the original bundle instead,
+/// see [`StoreIndex::bundle`].
+pub struct MediaStore {
+    /// `media.store`, or the original bundle.
+    store_path: PathBuf,
+    index: StoreIndex,
+}
+
+impl MediaStore {
+    /// Open the store in project folder, returns `None` if not present.
+    pub fn open(project_path: impl AsRef<Path>) -> Result<Option<Self>> {
+        Self::open_with_bundle(project_path, None)
+    }
+
+    /// Open the store in project folder. The original bundle of an index is
+    /// looked up in or next to project folder, then at `bundle_path`.
+    pub fn open_with_bundle(
+        project_path: impl AsRef<Path>,
+        bundle_path: Option<&Path>,
+    ) -> Result<Option<Self>> {
+        let project_path = project_path.as_ref();
+        let index_path = project_path.join(STORE_INDEX_FILE_NAME);
+        if !index_path.is_file() {
+            return Ok(None);
+        }
+        let index_file = io::BufReader::new(File::open(index_path)?);
+        let index: StoreIndex = serde_json::from_reader(index_file)?;
+        let store_path = match &index.bundle {
+            Some(bundle) => [Some(project_path), project_path.parent()]
+                .into_iter()
+                .flatten()
+                .map(|dir| dir.join(bundle))
+                .chain(bundle_path.map(Path::to_path_buf))
+                .find(|path| path.is_file())
+                .ok_or_else(|| StoreError::BundleNotFound(bundle.clone()))?,
+            None => project_path.join(STORE_FILE_NAME),
+        };
+        if !store_path.is_file() {
+            return Ok(None);
+        }
+        Ok(Some(Self { store_path, index }))
+    }
+
+    /// Write an index of entries in the original bundle, for a project
+    /// without media files. Entries are file name, offset in bundle and data.
+    pub fn index_bundle<'a>(
+        project_path: impl AsRef<Path>,
+        bundle: impl AsRef<Path>,
+        entries: impl IntoIterator<Item = (&'a str, u64, &'a [u8])>,
+    ) -> Result<()> {
+        let mut index = StoreIndex {
+            bundle: Some(bundle.as_ref().to_path_buf()),
+            ..Default::default()
+        };
+        for (name, offset, data) in entries {
+            let hash = hex_sha256(data);
+            index.blobs.entry(hash.clone()).or_insert(StoreBlob {
+                offset,
+                length: data.len() as u64,
+                size: data.len() as u64,
+            });
+            index.files.push(StoreFile {
+                name: name.to_string(),
+                hash,
+            });
+        }
+        write_index(project_path.as_ref(), &index)
+    }
+
+    /// Whether media is read from the original bundle.
+    pub fn is_bundle_index(&self) -> bool {
+        self.index.bundle.is_some()
+    }
+
+    pub fn files(&self) -> &[StoreFile] {
+        &self.index.files
+    }
+
+    /// Raw size of a stored file.
+    pub fn file_size(&self, name: &str) -> Option<u64> {
+        let file = self.index.files.iter().find(|file| file.name == name)?;
+        self.index.blobs.get(&file.hash).map(|blob| blob.size)
+    }
+
+    pub fn read(&self, name: &str) -> Result<Vec<u8>> {
+        let file = self
+            .index
+            .files
+            .iter()
+            .find(|file| file.name == name)
+            .ok_or_else(|| StoreError::FileNotFound(name.to_string()))?;
+        let blob = self
+            .index
+            .blobs
+            .get(&file.hash)
+            .ok_or_else(|| StoreError::FileNotFound(name.to_string()))?;
+
+        let mut store_file = File::open(&self.store_path)?;
+        store_file.seek(io::SeekFrom::Start(blob.offset))?;
+        let mut data = Vec::with_capacity(blob.size as usize);
+        if self.is_bundle_index() {
+            store_file.take(blob.length).read_to_end(&mut data)?;
+            if hex_sha256(&data) != file.hash {
+                return Err(StoreError::BundleChanged(name.to_string()));
+            }
+            return Ok(data);
+        }
+        zstd::Decoder::new(store_file.take(blob.length))?.read_to_end(&mut data)?;
+        if hex_sha256(&data) != file.hash {
+            return Err(StoreError::HashMismatch(name.to_string()));
+        }
+        Ok(data)
+    }
+}
+
+/// Writer creating a new media store.
+///
+/// The store is written to a temp file in project folder and replaces an
+/// existing store only on [`finish`](Self::finish), so an interrupted write
+/// leaves the previous store intact.
+pub struct MediaStoreWriter {
+    project_path: PathBuf,
+    writer: io::BufWriter<NamedTempFile>,
+    index: StoreIndex,
+    offset: u64,
+}
+
+impl MediaStoreWriter {
+    pub fn create(project_path: impl AsRef<Path>) -> Result<Self> {
+        let project_path = project_path.as_ref().to_path_buf();
+        let writer = io::BufWriter::new(NamedTempFile::new_in(&project_path)?);
+        Ok(Self {
+            project_path,
+            writer,
+            index: StoreIndex::default(),
+            offset: 0,
+        })
+    }
+
+    /// Add a file, returns whether new data was written.
+    pub fn add(&mut self, name: &str, data: &[u8]) -> Result<bool> {
+        let hash = hex_sha256(data);
+        self.index.files.push(StoreFile {
+            name: name.to_string(),
+            hash: hash.clone(),
+        });
+        if self.index.blobs.contains_key(&hash) {
+            return Ok(false);
+        }
+
+        let compressed = zstd::encode_all(data, COMPRESSION_LEVEL)?;
+        self.writer.write_all(&compressed)?;
+        self.index.blobs.insert(
+            hash,
+            StoreBlob {
+                offset: self.offset,
+                length: compressed.len() as u64,
+                size: data.len() as u64,
+            },
+        );
+        self.offset += compressed.len() as u64;
+        Ok(true)
+    }
+
+    /// Move the store into place and write the index.
+    pub fn finish(self) -> Result<MediaStore> {
+        let store_path = self.project_path.join(STORE_FILE_NAME);
+        let store_file = self.writer.into_inner().map_err(|e| e.into_error())?;
+        persist(store_file, &store_path)?;
+        write_index(&self.project_path, &self.index)?;
+        Ok(MediaStore {
+            store_path,
+            index: self.index,
+        })
+    }
+}
+
+/// Write `media.json` through a temp file, replacing an existing index.
+fn write_index(project_path: &Path, index: &StoreIndex) -> Result<()> {
+    let mut index_writer = io::BufWriter::new(NamedTempFile::new_in(project_path)?);
+    serde_json::to_writer(&mut index_writer, index)?;
+    let index_file = index_writer.into_inner().map_err(|e| e.into_error())?;
+    persist(index_file, &project_path.join(STORE_INDEX_FILE_NAME))
+}
+
+/// Sync a temp file and rename it over `path`.
+fn persist(file: NamedTempFile, path: &Path) -> Result<()> {
+    file.as_file().sync_all()?;
+    file.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+fn hex_sha256(data: &[u8]) -> String {
+    utils::to_hex(&Sha256::digest(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = MediaStoreWriter::create(dir.path()).unwrap();
+        assert!(writer.add("[000]1.wem", &[1; 4096]).unwrap());
+        assert!(writer.add("[001]2.wem", &[2; 100]).unwrap());
+        // duplicate content is stored once
+        assert!(!writer.add("[002]3.wem", &[1; 4096]).unwrap());
+        writer.finish().unwrap();
+
+        let store = MediaStore::open(dir.path()).unwrap().unwrap();
+        assert_eq!(store.files().len(), 3);
+        assert_eq!(store.read("[002]3.wem").unwrap(), vec![1; 4096]);
+        assert_eq!(store.read("[001]2.wem").unwrap(), vec![2; 100]);
+        assert_eq!(store.file_size("[000]1.wem"), Some(4096));
+        assert!(store.read("[003]4.wem").is_err());
+        assert!(store.store_path.metadata().unwrap().len() < 4096);
+
+        // an unfinished writer leaves the previous store intact
+        let mut writer = MediaStoreWriter::create(dir.path()).unwrap();
+        writer.add("[000]5.wem", &[5; 10]).unwrap();
+        drop(writer);
+        let store = MediaStore::open(dir.path()).unwrap().unwrap();
+        assert_eq!(store.read("[001]2.wem").unwrap(), vec![2; 100]);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_bundle_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let project_path = dir.path().join("Test.sbnk.1.X64.project");
+        std::fs::create_dir(&project_path).unwrap();
+        let mut bundle = vec![0; 16];
+        bundle.extend_from_slice(&[1; 100]);
+        bundle.extend_from_slice(&[2; 50]);
+        MediaStore::index_bundle(
+            &project_path,
+            "Test.sbnk.1.X64",
+            [
+                ("[000]1.wem", 16, &bundle[16..116]),
+                ("[001]2.wem", 116, &bundle[116..166]),
+            ],
+        )
+        .unwrap();
+        assert!(matches!(
+            MediaStore::open(&project_path),
+            Err(StoreError::BundleNotFound(_))
+        ));
+
+        // found next to project folder
+        let bundle_path = dir.path().join("Test.sbnk.1.X64");
+        std::fs::write(&bundle_path, &bundle).unwrap();
+        let store = MediaStore::open(&project_path).unwrap().unwrap();
+        assert!(store.is_bundle_index());
+        assert_eq!(store.read("[001]2.wem").unwrap(), vec![2; 50]);
+        assert_eq!(store.file_size("[000]1.wem"), Some(100));
+
+        bundle[20] = 9;
+        std::fs::write(&bundle_path, &bundle).unwrap();
+        assert!(matches!(
+            store.read("[000]1.wem"),
+            Err(StoreError::BundleChanged(_))
+        ));
+    }
+}
@@ -1,113 +1,778 @@
 use std::{
-    fs,
+    fs, io,
     path::{Path, PathBuf},
-    sync::atomic,
+    sync::LazyLock,
 };
 
-use dialoguer::{Input, theme::ColorfulTheme};
-use eyre::Context;
-use log::{debug, info, warn};
+use colored::Colorize;
+use log::{debug, error, info, warn};
+use parking_lot::{Mutex, RwLock};
+use rayon::prelude::*;
 
+#[cfg(feature = "bundled-audio")]
+use crate::decoder;
 use crate::{
-    INTERACTIVE_MODE,
-    config::Config,
+    config::{Config, TranscodeConfig},
     ffmpeg::FFmpegCli,
-    wwise::{WwiseConsole, WwiseSource},
+    preflight, utils,
+    vgmstream::{VgmstreamCli, VgmstreamError},
+    wav,
+    wwise::{self, ConsoleMessage, Conversion, WemFormat, WwiseConsole, WwiseError, WwiseSource},
 };
 
-/// Transcode all wav files in input_dir to wem files in output_dir.
-pub fn wavs_to_wem(input_dir: impl AsRef<Path>, output_dir: impl AsRef<Path>) -> eyre::Result<()> {
-    let input_dir = input_dir.as_ref().canonicalize().context(format!(
-        "Failed to canonicalize input path: {}",
-        input_dir.as_ref().display()
-    ))?;
-    let output_dir = output_dir.as_ref();
-
-    // create wsource
-    let mut source = WwiseSource::new(input_dir.to_str().unwrap());
-    let read_dir = input_dir
-        .read_dir()
-        .context("Failed to read input directory")?;
-    for entry in read_dir {
-        let entry = entry.context("Failed to read input directory entry")?;
-        let path = entry.path();
-        if !path.is_file() {
-            continue;
+type Result<T> = std::result::Result<T, TranscodeError>;
+
+/// Prompt keys of tool paths, see [`ToolPathPrompt`].
+pub const FFMPEG_PATH: &str = "ffmpeg_path";
+pub const VGMSTREAM_PATH: &str = "vgmstream_path";
+pub const WWISE_CONSOLE_PATH: &str = "wwise_console_path";
+
+static TOOL_PATH_PROMPT: LazyLock<RwLock<Option<Box<dyn ToolPathPrompt>>>> =
+    LazyLock::new(|| RwLock::new(None));
+
+/// Asks for paths of external tools missing in config.
+///
+/// Without a prompt set, default paths are searched.
+pub trait ToolPathPrompt: Send + Sync {
+    /// Whether the prompt may wait for user input.
+    fn is_interactive(&self) -> bool;
+    /// Path for `key`, or `default` if there is no answer.
+    fn input(&self, key: &str, prompt: &str, default: Option<&str>) -> Option<String>;
+}
+
+/// Set the prompt used when a tool path is not configured.
+pub fn set_tool_path_prompt(prompt: impl ToolPathPrompt + 'static) {
+    *TOOL_PATH_PROMPT.write() = Some(Box::new(prompt));
+}
+
+fn is_interactive() -> bool {
+    TOOL_PATH_PROMPT
+        .read()
+        .as_ref()
+        .is_some_and(|prompt| prompt.is_interactive())
+}
+
+fn prompt_path(key: &str, prompt: &str, default: Option<&str>) -> Option<String> {
+    match TOOL_PATH_PROMPT.read().as_ref() {
+        Some(tool_prompt) => tool_prompt.input(key, prompt, default),
+        None => default.map(str::to_string),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TranscodeError {
+    #[error("Transcode IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("{0} not found.")]
+    ToolNotFound(&'static str),
+    #[error("Input file not found: {}", .0.display())]
+    FileNotFound(PathBuf),
+    #[error("{0} path is not set or found, and interactive mode is disabled.")]
+    ToolNotConfigured(&'static str),
+    #[error("Wwise error: {0}")]
+    Wwise(#[from] WwiseError),
+    #[error("Failed to decode wem: {0}")]
+    Decode(#[from] VgmstreamError),
+    #[error(
+        "{} and {} would both be converted to {}",
+        first.display(),
+        second.display(),
+        output.display()
+    )]
+    DuplicateOutput {
+        first: PathBuf,
+        second: PathBuf,
+        output: PathBuf,
+    },
+    #[error("Failed to convert {}: {source}", file.display())]
+    ConversionFailed {
+        file: PathBuf,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+}
+
+impl TranscodeError {
+    fn conversion_failed(
+        file: impl AsRef<Path>,
+        source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Self {
+        TranscodeError::ConversionFailed {
+            file: file.as_ref().to_path_buf(),
+            source: source.into(),
         }
-        debug!("Add source: {}", path.display());
-        source.add_source(path.to_str().unwrap());
-    }
-    // convert
-    let wconsole = require_wwise_console()?;
-    let wproject = wconsole.acquire_temp_project()?;
-    wproject
-        .convert_external_source(&source, output_dir.to_str().unwrap())
-        .context("Failed to convert to wem")?;
-    // mv to root
-    let ww_output_dir = output_dir.join("Windows");
-    if ww_output_dir.exists() {
-        let read_dir = ww_output_dir
-            .read_dir()
-            .context("Failed to read output directory")?;
-        for entry in read_dir {
-            let entry = entry?;
-            let path = entry.path();
-            if !path.is_file() {
+    }
+}
+
+/// Conversion of wav files to wem with WwiseConsole.
+///
+/// ```no_run
+/// # use mhws_sound_tool::{transcode::WemConversion, wwise::Conversion};
+/// let results = WemConversion::new()
+///     .add_file("swing.wav")
+///     .conversion(Conversion::Pcm)
+///     .output_dir("out")
+///     .run()?;
+/// for result in &results {
+///     println!("{}: {}", result.input.display(), result.is_converted());
+/// }
+/// # Ok::<(), mhws_sound_tool::transcode::TranscodeError>(())
+/// ```
+///
+/// WwiseConsole does not support long paths, sources are staged in a temp
+/// folder under short names. Outputs are moved out of the `<platform>/`
+/// folder WwiseConsole writes to, and the staging folder is deleted when
+/// the conversion ends, whether it succeeds or not.
+#[derive(Debug, Default)]
+pub struct WemConversion {
+    files: Vec<WemFile>,
+    conversion: Conversion,
+    platform: Option<String>,
+    output_dir: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+struct WemFile {
+    input: PathBuf,
+    output: Option<PathBuf>,
+    conversion: Option<Conversion>,
+    format: Option<WemFormat>,
+}
+
+/// Result of a file of [`WemConversion`].
+#[derive(Debug, Clone)]
+pub struct WemResult {
+    pub input: PathBuf,
+    /// Path of the wem, only written if converted.
+    pub output: PathBuf,
+    pub conversion: Conversion,
+    /// Target platform the wem is converted for.
+    pub platform: String,
+    /// Warnings and errors WwiseConsole reported for the file.
+    pub messages: Vec<ConsoleMessage>,
+    converted: bool,
+}
+
+impl WemResult {
+    pub fn is_converted(&self) -> bool {
+        self.converted
+    }
+
+    /// Error of a file without output, with the reasons WwiseConsole gave.
+    pub fn error(&self) -> Option<TranscodeError> {
+        if self.converted {
+            return None;
+        }
+        let reasons: Vec<&str> = self
+            .messages
+            .iter()
+            .map(|message| message.text.as_str())
+            .collect();
+        let reason = if reasons.is_empty() {
+            "WwiseConsole produced no output".to_string()
+        } else {
+            reasons.join("; ")
+        };
+        Some(TranscodeError::conversion_failed(&self.input, reason))
+    }
+}
+
+impl WemConversion {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a wav file, converted to a wem of the same name in output folder.
+    pub fn add_file(mut self, input: impl AsRef<Path>) -> Self {
+        self.files.push(WemFile {
+            input: input.as_ref().to_path_buf(),
+            output: None,
+            conversion: None,
+            format: None,
+        });
+        self
+    }
+
+    /// Add a wav file converted to `output` with its own conversion.
+    pub fn add_file_to(
+        mut self,
+        input: impl AsRef<Path>,
+        output: impl AsRef<Path>,
+        conversion: Conversion,
+    ) -> Self {
+        self.files.push(WemFile {
+            input: input.as_ref().to_path_buf(),
+            output: Some(output.as_ref().to_path_buf()),
+            conversion: Some(conversion),
+            format: None,
+        });
+        self
+    }
+
+    /// Add a wav file converted to `output` with its own conversion,
+    /// resampled to the sample rate and channels of `format` if different.
+    pub fn add_file_matching(
+        mut self,
+        input: impl AsRef<Path>,
+        output: impl AsRef<Path>,
+        conversion: Conversion,
+        format: WemFormat,
+    ) -> Self {
+        self.files.push(WemFile {
+            input: input.as_ref().to_path_buf(),
+            output: Some(output.as_ref().to_path_buf()),
+            conversion: Some(conversion),
+            format: Some(format),
+        });
+        self
+    }
+
+    /// Conversion of files added without one, [`Conversion::Vorbis`] by default.
+    pub fn conversion(mut self, conversion: Conversion) -> Self {
+        self.conversion = conversion;
+        self
+    }
+
+    /// Target platform, `transcode.platform` in config by default.
+    pub fn platform(mut self, platform: impl Into<String>) -> Self {
+        self.platform = Some(platform.into());
+        self
+    }
+
+    /// Folder of outputs named after inputs, next to the inputs by default.
+    pub fn output_dir(mut self, output_dir: impl AsRef<Path>) -> Self {
+        self.output_dir = Some(output_dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Convert all files, returns a result for each file in the order added.
+    ///
+    /// Files WwiseConsole produced no wem for are reported in their results,
+    /// other failures end the conversion.
+    pub fn run(self) -> Result<Vec<WemResult>> {
+        if self.files.is_empty() {
+            return Ok(vec![]);
+        }
+        let platform = self
+            .platform
+            .clone()
+            .unwrap_or_else(|| Config::global().lock().transcode.platform.clone());
+        let staging_dir = tempfile::tempdir()?;
+        let result = self.convert_in(staging_dir.path(), &platform);
+        if let Err(e) = staging_dir.close() {
+            warn!("Failed to delete staging folder: {}", e);
+        }
+        result
+    }
+
+    fn convert_in(&self, staging_root: &Path, platform: &str) -> Result<Vec<WemResult>> {
+        // stage sources and create wsource
+        let ww_output_root = staging_root.join("output");
+        fs::create_dir_all(&ww_output_root)?;
+        let transcode_config = Config::global().lock().transcode.clone();
+        let mut source = WwiseSource::new(staging_root.to_str().unwrap());
+        let mut results = vec![];
+        for file in &self.files {
+            if !file.input.is_file() {
+                return Err(TranscodeError::FileNotFound(file.input.clone()));
+            }
+            let staged_path = staging_root.join(utils::staging_name(&file.input, "wav"));
+            stage_source(&file.input, &staged_path)?;
+            preflight_wav(
+                &staged_path,
+                &file.input,
+                file.format.as_ref(),
+                &transcode_config,
+            )?;
+            let conversion = file.conversion.unwrap_or(self.conversion);
+            debug!("Add source: {} ({})", file.input.display(), conversion);
+            source.add_source_with_conversion(staged_path.to_str().unwrap(), conversion);
+            let output = match (&file.output, &self.output_dir) {
+                (Some(output), _) => output.clone(),
+                (None, output_dir) => {
+                    let file_name = Path::new(file.input.file_name().unwrap());
+                    output_dir
+                        .as_deref()
+                        .or(file.input.parent())
+                        .unwrap_or(Path::new("."))
+                        .join(file_name.with_extension("wem"))
+                }
+            };
+            results.push(WemResult {
+                input: file.input.clone(),
+                output,
+                conversion,
+                platform: platform.to_string(),
+                messages: vec![],
+                converted: false,
+            });
+        }
+        // convert
+        let wwise_config = Config::global().lock().wwise.clone();
+        let wconsole = require_wwise_console()?.with_limits(wwise_config.console_limits());
+        let wproject = wconsole.acquire_temp_project_in(
+            wwise_config.temp_project_root()?,
+            &wwise_config.temp_project_name,
+            platform,
+            wwise_config.max_temp_projects,
+        )?;
+        debug!("Target platform: {}", wproject.platform());
+        let messages =
+            match wproject.convert_external_source(&source, ww_output_root.to_str().unwrap()) {
+                Ok(messages) => messages,
+                // files converted before the failure are still usable
+                Err(WwiseError::ConversionFailed { messages, .. }) => messages,
+                Err(e) => return Err(e.into()),
+            };
+        for message in messages {
+            let result = message
+                .source
+                .as_ref()
+                .and_then(|s| source.sources().iter().position(|source| source == s))
+                .map(|i| &mut results[i]);
+            let text = match &result {
+                Some(result) => format!("{}: {}", result.input.display(), message.text),
+                None => message.to_string(),
+            };
+            let file = result
+                .as_ref()
+                .map(|result| result.input.display().to_string());
+            if message.is_error() {
+                error!(code = "conversion-failed", file = file; "WwiseConsole: {}", text);
+            } else {
+                warn!(code = "conversion-warning", file = file; "WwiseConsole: {}", text);
+            }
+            if let Some(result) = result {
+                result.messages.push(message);
+            }
+        }
+
+        // move outputs out of the platform folder of WwiseConsole
+        let ww_output_dir = ww_output_root.join(platform);
+        for (staged_source, result) in source.sources().iter().zip(&mut results) {
+            let staged_name = staged_source
+                .rsplit(['\\', '/'])
+                .next()
+                .unwrap_or(staged_source);
+            let staged_wem = ww_output_dir.join(Path::new(staged_name).with_extension("wem"));
+            if !staged_wem.is_file() {
                 continue;
             }
-            let to = output_dir.join(path.file_name().unwrap());
-            debug!("Output: {}", to.display());
-            fs::copy(&path, to)?;
+            if let Some(parent) = result.output.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            debug!("Output: {}", result.output.display());
+            move_file(&staged_wem, &result.output)?;
+            result.converted = true;
+        }
+        Ok(results)
+    }
+}
+
+/// Fail with the first file of results that was not converted, other
+/// failed files are logged, and so are files WwiseConsole said nothing about.
+pub fn ensure_converted(results: &[WemResult]) -> Result<()> {
+    let mut first = None;
+    for result in results {
+        let Some(e) = result.error() else {
+            continue;
+        };
+        // files with messages were logged with them during conversion
+        if first.is_some() || result.messages.is_empty() {
+            error!(code = "conversion-failed", file:% = result.input.display(); "{}", e);
+        }
+        first.get_or_insert(e);
+    }
+    first.map_or(Ok(()), Err)
+}
+
+/// Rename a file, or copy it across file systems.
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
+
+/// Copy a source to staging folder, sanitized. Sources which need no
+/// changes are linked instead where possible.
+fn stage_source(path: &Path, staged_path: &Path) -> Result<()> {
+    let data = fs::read(path)?;
+    match wav::sanitize(&data) {
+        Ok(sanitized) if sanitized.is_changed() => {
+            info!(
+                "{}: {}: {}.",
+                "Sanitize".cyan(),
+                path.file_name().unwrap().to_string_lossy(),
+                sanitized.changes.join(", ")
+            );
+            fs::write(staged_path, sanitized.data)?;
+        }
+        Ok(_) => {
+            if fs::hard_link(path, staged_path).is_err() {
+                fs::write(staged_path, data)?;
+            }
+        }
+        // leave it to WwiseConsole to report
+        Err(e) => {
+            warn!("Skip sanitizing {}: {}", path.display(), e);
+            fs::write(staged_path, data)?;
+        }
+    }
+    Ok(())
+}
+
+/// Check a staged wav file of `source` for clipping and DC offset.
+///
+/// When the limiter or silence trimming is enabled in config, or the file
+/// differs from the sample rate and channels of `target`, the staged file
+/// is rewritten, keeping loop points of its `smpl` chunk at the new sample
+/// rate and within the new length.
+fn preflight_wav(
+    path: &Path,
+    source: &Path,
+    target: Option<&WemFormat>,
+    transcode_config: &TranscodeConfig,
+) -> Result<()> {
+    let threshold = 10f32.powf(transcode_config.limiter_threshold_db / 20.0);
+    let trim_threshold = 10f32.powf(transcode_config.trim_threshold_db / 20.0);
+
+    let data = fs::read(path)?;
+    let report = match preflight::analyze_wav(&data) {
+        Ok(report) => report,
+        Err(e) => {
+            warn!("Skip pre-flight check of {}: {}", source.display(), e);
+            return Ok(());
+        }
+    };
+    let file_name = source.file_name().unwrap().to_string_lossy();
+    debug!(
+        "Pre-flight {}: peak {:.1} dBFS, rms {:.1} dBFS",
+        file_name,
+        report.peak_db(),
+        report.rms_db()
+    );
+    if report.is_clipping() {
+        warn!(
+            "{} has {} clipped samples, it may sound distorted in game.",
+            file_name, report.clipped_samples
+        );
+    }
+    if report.has_dc_offset() {
+        warn!("{} has DC offset {:.3}.", file_name, report.dc_offset);
+    }
+
+    let mut filters = vec![];
+    let mut applied = vec![];
+    if transcode_config.trim_silence {
+        if report.peak > trim_threshold {
+            filters.push(trim_silence_filter(transcode_config.trim_threshold_db));
+            applied.push("silence trimmed".to_string());
+        } else {
+            // trimming would leave nothing
+            warn!("{} is silent, skip trimming.", file_name);
+        }
+    }
+    if transcode_config.limiter && report.peak > threshold {
+        if report.has_dc_offset() {
+            filters.push("highpass=f=5".to_string());
+        }
+        filters.push(format!(
+            "alimiter=limit={:.4}:level=disabled",
+            threshold.max(0.0625)
+        ));
+        applied.push(format!(
+            "limiter applied (ceiling {:.1} dBFS)",
+            transcode_config.limiter_threshold_db
+        ));
+    }
+    if let Some(target) = target.filter(|target| {
+        target.sample_rate > 0
+            && target.channels > 0
+            && (target.sample_rate, target.channels) != (report.sample_rate, report.channels)
+    }) {
+        filters.push(format!(
+            "aformat=sample_rates={}:channel_layouts={}c",
+            target.sample_rate, target.channels
+        ));
+        applied.push(format!(
+            "converted to {} Hz, {} channels of the original",
+            target.sample_rate, target.channels
+        ));
+    }
+    if filters.is_empty() {
+        return Ok(());
+    }
+    let ffmpeg = require_ffmpeg()?;
+    let filtered_path = path.with_extension("filtered.wav");
+    ffmpeg
+        .transcode_with_filter(path, &filtered_path, &filters.join(","))
+        .map_err(|e| TranscodeError::conversion_failed(source, e))?;
+    // ffmpeg drops loop points, WwiseConsole reads them from `smpl`
+    if let Some(smpl) = wav::find_chunk(&data, b"smpl") {
+        let mut filtered = fs::read(&filtered_path)?;
+        if wav::find_chunk(&filtered, b"smpl").is_none() {
+            match wav::fit_loop_points(smpl, report.sample_rate, &filtered) {
+                Ok(fitted) => {
+                    if fitted.sample_rate != report.sample_rate {
+                        applied.push(format!("loop points moved to {} Hz", fitted.sample_rate));
+                    }
+                    if fitted.cut > 0 {
+                        warn!(
+                            "{} loops of {} end past the audio, they are cut to its end.",
+                            fitted.cut, file_name
+                        );
+                    }
+                    wav::append_chunk(&mut filtered, b"smpl", &fitted.smpl);
+                    fs::write(&filtered_path, filtered)?;
+                }
+                Err(e) => warn!("Loop points of {} are dropped: {}", file_name, e),
+            }
+        }
+        if transcode_config.trim_silence {
+            warn!(
+                "{} has loop points, they may be off after trimming silence.",
+                file_name
+            );
         }
-        // remove ww_output_dir "Windows"
-        let _ = fs::remove_dir_all(&ww_output_dir);
     }
+    // a linked source must not be changed in place
+    fs::remove_file(path)?;
+    fs::rename(&filtered_path, path)?;
+    info!("{}: {}.", file_name, applied.join(", "));
 
     Ok(())
 }
 
+/// Filter removing leading and trailing samples below the threshold.
+///
+/// `silenceremove` only trims reliably at the start, trailing silence is
+/// trimmed by reversing the audio twice.
+fn trim_silence_filter(threshold_db: f32) -> String {
+    let trim_start = format!(
+        "silenceremove=start_periods=1:start_threshold={:.1}dB",
+        threshold_db
+    );
+    format!("{0},areverse,{0},areverse", trim_start)
+}
+
 /// Transcode all sounds in inputs to wav files data.
-pub fn sounds_to_wav(inputs: &[impl AsRef<Path>]) -> eyre::Result<Vec<Vec<u8>>> {
+///
+/// With `bundled-audio` feature, common formats are decoded in-process,
+/// ffmpeg is only required for others.
+pub fn sounds_to_wav(inputs: &[impl AsRef<Path> + Sync]) -> Result<Vec<Vec<u8>>> {
+    let ffmpeg = Mutex::new(None);
+    let tmp_dir = tempfile::tempdir()?;
+    thread_pool()?.install(|| {
+        inputs
+            .par_iter()
+            .map(|input| sound_to_wav(input.as_ref(), &ffmpeg, tmp_dir.path()))
+            .collect()
+    })
+}
+
+/// Thread pool running transcoding processes concurrently,
+/// sized by `transcode.jobs` in config.
+fn thread_pool() -> Result<rayon::ThreadPool> {
+    let jobs = Config::global().lock().transcode.jobs;
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .map_err(|e| io::Error::other(e).into())
+}
+
+/// Transcode a sound to wav data, `ffmpeg` is shared by threads
+/// and located on first use.
+fn sound_to_wav(
+    input: &Path,
+    ffmpeg: &Mutex<Option<FFmpegCli>>,
+    tmp_dir: &Path,
+) -> Result<Vec<u8>> {
+    #[cfg(feature = "bundled-audio")]
+    if decoder::is_supported(input) {
+        debug!("Decoding: {}", input.display());
+        match decoder::decode_to_wav(input) {
+            Ok(data) => return Ok(data),
+            Err(e) => warn!(
+                "Failed to decode {} in-process, fallback to ffmpeg: {}",
+                input.display(),
+                e
+            ),
+        }
+    }
+    let ffmpeg = {
+        let mut ffmpeg = ffmpeg.lock();
+        match &*ffmpeg {
+            Some(ffmpeg) => ffmpeg.clone(),
+            None => ffmpeg.insert(require_ffmpeg()?).clone(),
+        }
+    };
+    // staged under short names, ffmpeg may not support long paths
+    let mut ffmpeg_input = input.to_path_buf();
+    if utils::is_long_path(input) {
+        let ext = input.extension().unwrap_or_default().to_string_lossy();
+        ffmpeg_input = tmp_dir.join(utils::staging_name(input, &ext));
+        fs::copy(input, &ffmpeg_input)?;
+    }
+    let output_path = tmp_dir.join(utils::staging_name(input, "wav"));
+    debug!("Transcoding: {}", input.display());
+    ffmpeg
+        .simple_transcode(&ffmpeg_input, &output_path)
+        .map_err(|e| TranscodeError::conversion_failed(input, e))?;
+
+    Ok(fs::read(&output_path)?)
+}
+
+/// Options of converting sounds with ffmpeg, see [`convert_sounds`].
+#[derive(Debug, Clone, Default)]
+pub struct SoundConversion {
+    /// Output extension, ffmpeg selects the container and codec by it, e.g. `ogg`.
+    pub format: String,
+    /// Audio bitrate, e.g. `192k`.
+    pub bitrate: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u32>,
+}
+
+impl SoundConversion {
+    /// Output options of ffmpeg, `params` of ffmpeg in config come first.
+    pub fn ffmpeg_args(&self, params: &[String]) -> Vec<String> {
+        let mut args = params.to_vec();
+        if let Some(bitrate) = &self.bitrate {
+            args.extend(["-b:a".to_string(), bitrate.clone()]);
+        }
+        if let Some(sample_rate) = self.sample_rate {
+            args.extend(["-ar".to_string(), sample_rate.to_string()]);
+        }
+        if let Some(channels) = self.channels {
+            args.extend(["-ac".to_string(), channels.to_string()]);
+        }
+        args
+    }
+}
+
+/// Convert sound files with ffmpeg, concurrently. `files` are pairs of
+/// input and output paths.
+pub fn convert_sounds(files: &[(PathBuf, PathBuf)], conversion: &SoundConversion) -> Result<()> {
     let ffmpeg = require_ffmpeg()?;
+    let params = Config::global()
+        .lock()
+        .get_bin_config("ffmpeg")
+        .map(|bin| bin.params.clone())
+        .unwrap_or_default();
+    let args = conversion.ffmpeg_args(&params);
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
     let tmp_dir = tempfile::tempdir()?;
-    let mut wavs = vec![];
-    for input in inputs {
-        let input = input.as_ref();
-        let file_stem = input.file_stem().unwrap().to_str().unwrap();
-        let output_file_name = Path::new(file_stem).with_extension("wav");
-        let output_path = tmp_dir.path().join(output_file_name);
-        debug!("Transcoding: {}", input.display());
-        ffmpeg.simple_transcode(input, &output_path)?;
+    thread_pool()?.install(|| {
+        files.par_iter().try_for_each(|(input, output)| {
+            // staged under short names, ffmpeg may not support long paths
+            let ffmpeg_input = if utils::is_long_path(input) {
+                let ext = input.extension().unwrap_or_default().to_string_lossy();
+                let staged = tmp_dir.path().join(utils::staging_name(input, &ext));
+                fs::copy(input, &staged)?;
+                staged
+            } else {
+                input.clone()
+            };
+            let staged_output = tmp_dir
+                .path()
+                .join(utils::staging_name(input, &conversion.format));
+            debug!("Transcoding: {}", input.display());
+            ffmpeg
+                .transcode_with_args(&ffmpeg_input, &staged_output, &args)
+                .map_err(|e| TranscodeError::conversion_failed(input, e))?;
+            fs::copy(&staged_output, output)?;
+            fs::remove_file(&staged_output)?;
+            info!("{}: {}", "Transcode".cyan(), output.display());
+            Ok(())
+        })
+    })
+}
 
-        let output_data =
-            fs::read(&output_path).context("Failed to read ffmpeg transcoded output file")?;
-        wavs.push(output_data);
+/// Playable format of decoded wem files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeFormat {
+    Wav,
+    /// Vorbis in ogg, re-encoded from wav by ffmpeg.
+    Ogg,
+}
+
+impl DecodeFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            DecodeFormat::Wav => "wav",
+            DecodeFormat::Ogg => "ogg",
+        }
+    }
+}
+
+/// Decode wem data to playable file data.
+pub fn decode_wem(wem_data: &[u8], format: DecodeFormat) -> Result<Vec<u8>> {
+    let wav_data = wem_to_wav(wem_data)?;
+    match format {
+        DecodeFormat::Wav => Ok(wav_data),
+        DecodeFormat::Ogg => wav_to_ogg(&wav_data),
     }
+}
 
-    Ok(wavs)
+/// Decode wem data to wav file data.
+///
+/// PCM wems are decoded in-process, other codecs require vgmstream.
+pub fn wem_to_wav(wem_data: &[u8]) -> Result<Vec<u8>> {
+    if let Some(wav_data) = wwise::pcm_wem_to_wav(wem_data) {
+        return Ok(wav_data);
+    }
+    let vgmstream = require_vgmstream()?;
+    let tmp_dir = tempfile::tempdir()?;
+    let input_path = tmp_dir.path().join("input.wem");
+    let output_path = tmp_dir.path().join("output.wav");
+    fs::write(&input_path, wem_data)?;
+    vgmstream.decode_to_wav(&input_path, &output_path)?;
+
+    let output_data = fs::read(&output_path)?;
+    Ok(output_data)
+}
+
+/// Encode wav file data to ogg (Vorbis) file data.
+pub fn wav_to_ogg(wav_data: &[u8]) -> Result<Vec<u8>> {
+    let ffmpeg = require_ffmpeg()?;
+    let tmp_dir = tempfile::tempdir()?;
+    let input_path = tmp_dir.path().join("input.wav");
+    let output_path = tmp_dir.path().join("output.ogg");
+    fs::write(&input_path, wav_data)?;
+    ffmpeg
+        .simple_transcode(&input_path, &output_path)
+        .map_err(|e| TranscodeError::conversion_failed(&input_path, e))?;
+
+    let output_data = fs::read(&output_path)?;
+    Ok(output_data)
 }
 
 /// Get ffmpeg instance from config, or update config with user input.
-fn require_ffmpeg() -> eyre::Result<FFmpegCli> {
+///
+/// With prompts disabled, default paths are searched.
+fn require_ffmpeg() -> Result<FFmpegCli> {
     let mut config = Config::global().lock();
     if let Some(ffmpeg_config) = config.get_bin_config("ffmpeg") {
         return FFmpegCli::new_with_path(PathBuf::from(&ffmpeg_config.path))
-            .ok_or(eyre::eyre!("FFmpeg not found"));
-    }
-    if !crate::INTERACTIVE_MODE.load(atomic::Ordering::SeqCst) {
-        eyre::bail!("ffmpeg path is not set, and interactive mode is disabled.");
+            .ok_or(TranscodeError::ToolNotFound("FFmpeg"));
     }
 
-    warn!("ffmpeg path is not set, please setup in config.toml.");
-    let ffmpeg_path: String = Input::with_theme(&ColorfulTheme::default())
-        .show_default(true)
-        .default("ffmpeg.exe".to_string())
-        .with_prompt("Input ffmpeg path")
-        .interact_text()
-        .unwrap();
-    let ffmpeg_path = ffmpeg_path.trim_matches(['\"', '\'']);
+    let interactive = is_interactive();
+    if interactive {
+        warn!("ffmpeg path is not set, please setup in config.toml.");
+    }
+    let input = prompt_path(
+        FFMPEG_PATH,
+        "Input ffmpeg path",
+        interactive.then_some("ffmpeg.exe"),
+    );
+    let Some(ffmpeg_path) = input else {
+        return FFmpegCli::new().map_err(|_| TranscodeError::ToolNotConfigured("ffmpeg"));
+    };
     let ffmpeg = FFmpegCli::new_with_path(PathBuf::from(ffmpeg_path))
-        .ok_or(eyre::eyre!("FFmpeg not found"))?;
+        .ok_or(TranscodeError::ToolNotFound("FFmpeg"))?;
     config.set_bin_config("ffmpeg", ffmpeg.program_path().to_string_lossy().as_ref());
     config.save();
     info!("FFmpeg path saved to config.toml.");
@@ -115,25 +780,66 @@ fn require_ffmpeg() -> eyre::Result<FFmpegCli> {
     Ok(ffmpeg)
 }
 
+/// Get vgmstream instance from config, or update config with user input.
+///
+/// With prompts disabled, default paths are searched.
+fn require_vgmstream() -> Result<VgmstreamCli> {
+    let mut config = Config::global().lock();
+    if let Some(vgmstream_config) = config.get_bin_config("vgmstream-cli") {
+        return VgmstreamCli::new_with_path(PathBuf::from(&vgmstream_config.path))
+            .ok_or(TranscodeError::ToolNotFound("vgmstream-cli"));
+    }
+
+    let interactive = is_interactive();
+    if interactive {
+        warn!("vgmstream-cli path is not set, please setup in config.toml.");
+    }
+    let input = prompt_path(
+        VGMSTREAM_PATH,
+        "Input vgmstream-cli path",
+        interactive.then_some("vgmstream-cli.exe"),
+    );
+    let Some(vgmstream_path) = input else {
+        return VgmstreamCli::new().map_err(|_| TranscodeError::ToolNotConfigured("vgmstream-cli"));
+    };
+    let vgmstream = VgmstreamCli::new_with_path(PathBuf::from(vgmstream_path))
+        .ok_or(TranscodeError::ToolNotFound("vgmstream-cli"))?;
+    config.set_bin_config(
+        "vgmstream-cli",
+        vgmstream.program_path().to_string_lossy().as_ref(),
+    );
+    config.save();
+    info!("vgmstream-cli path saved to config.toml.");
+
+    Ok(vgmstream)
+}
+
 /// Get wwise console instance from config, or update config with user input.
-fn require_wwise_console() -> eyre::Result<WwiseConsole> {
+///
+/// With prompts disabled, `WWISEROOT` and default paths are searched.
+fn require_wwise_console() -> Result<WwiseConsole> {
     let mut config = Config::global().lock();
+    let wine = config.wwise.wine();
     if let Some(wconsole_config) = config.get_bin_config("WwiseConsole") {
-        return Ok(WwiseConsole::new_with_path(PathBuf::from(
-            &wconsole_config.path,
-        ))?);
-    }
-    if !INTERACTIVE_MODE.load(atomic::Ordering::SeqCst) {
-        eyre::bail!("WwiseConsole path is not set, and interactive mode is disabled.");
+        return Ok(WwiseConsole::new_with_path_and_wine(
+            PathBuf::from(&wconsole_config.path),
+            wine,
+        )?);
     }
 
-    warn!("WwiseConsole path is not set, please setup in config.toml.");
-    let wconsole_path: String = Input::with_theme(&ColorfulTheme::default())
-        .with_prompt("Input WwiseConsole.exe path")
-        .interact_text()
-        .unwrap();
-    let wconsole_path = wconsole_path.trim_matches(['\"', '\'']);
-    let wconsole = WwiseConsole::new_with_path(PathBuf::from(wconsole_path))?;
+    let interactive = is_interactive();
+    if interactive {
+        warn!("WwiseConsole path is not set, please setup in config.toml.");
+    }
+    let input = prompt_path(WWISE_CONSOLE_PATH, "Input WwiseConsole.exe path", None);
+    let Some(wconsole_path) = input else {
+        let found = match &wine {
+            Some(wine) => WwiseConsole::new_with_wine(wine),
+            None => WwiseConsole::new(),
+        };
+        return found.map_err(|_| TranscodeError::ToolNotConfigured("WwiseConsole"));
+    };
+    let wconsole = WwiseConsole::new_with_path_and_wine(PathBuf::from(wconsole_path), wine)?;
     config.set_bin_config(
         "WwiseConsole",
         wconsole.program_path().to_string_lossy().as_ref(),
@@ -143,3 +849,43 @@ fn require_wwise_console() -> eyre::Result<WwiseConsole> {
 
     Ok(wconsole)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trim_silence_filter() {
+        assert_eq!(
+            trim_silence_filter(-60.0),
+            "silenceremove=start_periods=1:start_threshold=-60.0dB,areverse,\
+             silenceremove=start_periods=1:start_threshold=-60.0dB,areverse"
+        );
+    }
+
+    #[test]
+    fn test_preflight_skips_trimming_silent_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("silent.wav");
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..4800 {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        let data = fs::read(&path).unwrap();
+
+        // nothing to trim, so ffmpeg is not needed and the file is kept
+        let transcode_config = TranscodeConfig {
+            trim_silence: true,
+            ..Default::default()
+        };
+        preflight_wav(&path, &path, None, &transcode_config).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), data);
+    }
+}
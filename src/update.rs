@@ -0,0 +1,353 @@
+//! Check GitHub releases for newer versions and update the executable in place.
+
+use std::{
+    env, fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use mhws_sound_tool::utils;
+
+type Result<T> = std::result::Result<T, UpdateError>;
+
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/eigeen/mhws-sound-tool/releases/latest";
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Suffix of the replaced executable, removed on next start after an update.
+const OLD_EXE_SUFFIX: &str = ".old";
+/// Suffix of the checksum asset published next to an executable.
+const CHECKSUM_SUFFIX: &str = ".sha256";
+
+#[derive(Debug, thiserror::Error)]
+pub enum UpdateError {
+    #[error("Update IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("Failed to request {url}: {source}")]
+    Http {
+        url: String,
+        source: Box<ureq::Error>,
+    },
+    #[error("Bad version tag: {0}")]
+    BadVersion(String),
+    #[error("No executable for this platform found in release {0}.")]
+    NoAsset(String),
+    #[error("Downloaded file size mismatch, expected {expected}, got {actual}.")]
+    SizeMismatch { expected: u64, actual: u64 },
+    #[error("No SHA-256 checksum of {0} published in the release, refused to install it.")]
+    NoChecksum(String),
+    #[error("Bad SHA-256 checksum of {0} published in the release.")]
+    BadChecksum(String),
+    #[error("Downloaded file checksum mismatch, expected {expected}, got {actual}.")]
+    ChecksumMismatch { expected: String, actual: String },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub html_url: String,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+    pub size: u64,
+    /// Digest computed by GitHub, e.g. `sha256:<hex>`.
+    #[serde(default)]
+    pub digest: Option<String>,
+}
+
+impl Release {
+    pub fn version(&self) -> Result<Version> {
+        Version::parse(&self.tag_name).ok_or_else(|| UpdateError::BadVersion(self.tag_name.clone()))
+    }
+
+    /// Executable asset of the current platform.
+    fn executable_asset(&self) -> Option<&ReleaseAsset> {
+        self.assets.iter().find(|asset| {
+            let name = asset.name.to_lowercase();
+            name.starts_with(env!("CARGO_PKG_NAME"))
+                && if env::consts::EXE_SUFFIX.is_empty() {
+                    name.contains(env::consts::OS) && !name.contains('.')
+                } else {
+                    name.ends_with(env::consts::EXE_SUFFIX)
+                }
+        })
+    }
+
+    /// Expected SHA-256 of an asset, from the digest of the release API
+    /// or a `<name>.sha256` asset published next to it.
+    fn asset_sha256(&self, asset: &ReleaseAsset) -> Result<[u8; 32]> {
+        if let Some(digest) = &asset.digest
+            && let Some(hex) = digest.strip_prefix("sha256:")
+        {
+            return parse_sha256(hex).ok_or_else(|| UpdateError::BadChecksum(asset.name.clone()));
+        }
+        let checksum_name = format!("{}{}", asset.name, CHECKSUM_SUFFIX);
+        let checksum_asset = self
+            .assets
+            .iter()
+            .find(|a| a.name == checksum_name)
+            .ok_or_else(|| UpdateError::NoChecksum(asset.name.clone()))?;
+        let content = download(&checksum_asset.browser_download_url)?;
+        // `sha256sum` format: `<hex>  <file name>`
+        String::from_utf8_lossy(&content)
+            .split_whitespace()
+            .next()
+            .and_then(parse_sha256)
+            .ok_or_else(|| UpdateError::BadChecksum(asset.name.clone()))
+    }
+}
+
+fn parse_sha256(hex: &str) -> Option<[u8; 32]> {
+    let hex = hex.trim();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut hash = [0; 32];
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(hash)
+}
+
+/// Semantic version `major.minor.patch`, pre-release suffix ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version(pub u64, pub u64, pub u64);
+
+impl Version {
+    pub fn current() -> Self {
+        Self::parse(env!("CARGO_PKG_VERSION")).unwrap()
+    }
+
+    /// Parse tags like `v0.2.2` or `0.2.2-beta`.
+    pub fn parse(tag: &str) -> Option<Self> {
+        let tag = tag.trim().trim_start_matches(['v', 'V']);
+        let tag = tag.split(['-', '+']).next()?;
+        let mut parts = tag.split('.').map(|part| part.parse::<u64>());
+        let major = parts.next()?.ok()?;
+        let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+        let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self(major, minor, patch))
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "v{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout(TIMEOUT)
+        .user_agent(concat!(
+            env!("CARGO_PKG_NAME"),
+            "/",
+            env!("CARGO_PKG_VERSION")
+        ))
+        .build()
+}
+
+fn http_error(url: &str) -> impl FnOnce(ureq::Error) -> UpdateError {
+    let url = url.to_string();
+    move |source| UpdateError::Http {
+        url,
+        source: Box::new(source),
+    }
+}
+
+pub fn latest_release() -> Result<Release> {
+    let response = agent()
+        .get(LATEST_RELEASE_URL)
+        .set("Accept", "application/vnd.github+json")
+        .call()
+        .map_err(http_error(LATEST_RELEASE_URL))?;
+    Ok(response.into_json()?)
+}
+
+/// Download a file, e.g. a data pack.
+pub fn download(url: &str) -> Result<Vec<u8>> {
+    let response = agent().get(url).call().map_err(http_error(url))?;
+    let mut data = vec![];
+    response.into_reader().read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Latest release if it is newer than the running version.
+pub fn check() -> Result<Option<Release>> {
+    let release = latest_release()?;
+    if release.version()? > Version::current() {
+        Ok(Some(release))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Download the latest release and replace the running executable,
+/// after checking it against the SHA-256 published in the release.
+///
+/// Returns the installed release, `None` if already up to date.
+/// The replaced executable is left at [`old_exe_path`] to be removed by
+/// [`cleanup`] on next start.
+pub fn self_update() -> Result<Option<Release>> {
+    let Some(release) = check()? else {
+        return Ok(None);
+    };
+    let asset = release
+        .executable_asset()
+        .ok_or_else(|| UpdateError::NoAsset(release.tag_name.clone()))?;
+    let expected_sha256 = release.asset_sha256(asset)?;
+
+    let exe_path = env::current_exe()?;
+    let new_path = with_suffix(&exe_path, ".new");
+    let response = agent()
+        .get(&asset.browser_download_url)
+        .call()
+        .map_err(http_error(&asset.browser_download_url))?;
+    let mut data = vec![];
+    response.into_reader().read_to_end(&mut data)?;
+    if data.len() as u64 != asset.size {
+        return Err(UpdateError::SizeMismatch {
+            expected: asset.size,
+            actual: data.len() as u64,
+        });
+    }
+    let actual_sha256: [u8; 32] = Sha256::digest(&data).into();
+    if actual_sha256 != expected_sha256 {
+        return Err(UpdateError::ChecksumMismatch {
+            expected: utils::to_hex(&expected_sha256),
+            actual: utils::to_hex(&actual_sha256),
+        });
+    }
+    {
+        let mut file = fs::File::create(&new_path)?;
+        if let Err(e) = file.write_all(&data).and_then(|_| file.sync_all()) {
+            drop(file);
+            let _ = fs::remove_file(&new_path);
+            return Err(e.into());
+        }
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&new_path, fs::Permissions::from_mode(0o755))?;
+    }
+
+    // running executable can be renamed but not overwritten on Windows
+    let old_path = old_exe_path()?;
+    let _ = fs::remove_file(&old_path);
+    fs::rename(&exe_path, &old_path)?;
+    if let Err(e) = fs::rename(&new_path, &exe_path) {
+        fs::rename(&old_path, &exe_path)?;
+        return Err(e.into());
+    }
+    Ok(Some(release))
+}
+
+/// Path the running executable is moved to when replaced by an update.
+pub fn old_exe_path() -> io::Result<PathBuf> {
+    Ok(with_suffix(&env::current_exe()?, OLD_EXE_SUFFIX))
+}
+
+/// Remove the executable replaced by an update, called on the first start
+/// after it.
+pub fn cleanup(old_path: &Path) {
+    if let Err(e) = fs::remove_file(old_path)
+        && e.kind() != io::ErrorKind::NotFound
+    {
+        log::warn!("Failed to remove {}: {}", old_path.display(), e);
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_os_string();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(Version::parse("v0.2.2"), Some(Version(0, 2, 2)));
+        assert_eq!(Version::parse("1.0.0-beta.1"), Some(Version(1, 0, 0)));
+        assert_eq!(Version::parse("V1.3"), Some(Version(1, 3, 0)));
+        assert_eq!(Version::parse("nightly"), None);
+        assert_eq!(Version::parse("1.2.3.4"), None);
+        assert!(Version(0, 10, 0) > Version(0, 9, 9));
+        assert_eq!(
+            Version::current().to_string(),
+            format!("v{}", env!("CARGO_PKG_VERSION"))
+        );
+    }
+
+    #[test]
+    fn test_executable_asset() {
+        let asset = |name: &str| ReleaseAsset {
+            name: name.to_string(),
+            browser_download_url: String::new(),
+            size: 0,
+            digest: None,
+        };
+        let release = Release {
+            tag_name: "v9.0.0".to_string(),
+            html_url: String::new(),
+            assets: vec![
+                asset("mhws-sound-tool-source.zip"),
+                asset("mhws-sound-tool.exe"),
+                asset(&format!("mhws-sound-tool-{}", env::consts::OS)),
+            ],
+        };
+        let found = release.executable_asset().unwrap();
+        if cfg!(windows) {
+            assert_eq!(found.name, "mhws-sound-tool.exe");
+        } else {
+            assert_eq!(found.name, format!("mhws-sound-tool-{}", env::consts::OS));
+        }
+    }
+
+    #[test]
+    fn test_asset_sha256() {
+        let hex = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        let mut asset = ReleaseAsset {
+            name: "mhws-sound-tool.exe".to_string(),
+            browser_download_url: String::new(),
+            size: 4,
+            digest: Some(format!("sha256:{}", hex)),
+        };
+        let release = Release {
+            tag_name: "v9.0.0".to_string(),
+            html_url: String::new(),
+            assets: vec![],
+        };
+        let expected: [u8; 32] = Sha256::digest(b"test").into();
+        assert_eq!(release.asset_sha256(&asset).unwrap(), expected);
+        assert_eq!(parse_sha256(&hex.to_uppercase()), Some(expected));
+        assert_eq!(parse_sha256(&hex[1..]), None);
+
+        asset.digest = Some("sha256:not-a-hash".to_string());
+        assert!(matches!(
+            release.asset_sha256(&asset),
+            Err(UpdateError::BadChecksum(_))
+        ));
+        // nothing published, never installed unchecked
+        asset.digest = None;
+        assert!(matches!(
+            release.asset_sha256(&asset),
+            Err(UpdateError::NoChecksum(_))
+        ));
+    }
+}
@@ -1,12 +1,23 @@
-use std::io;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
 
-use byteorder::{LE, ReadBytesExt};
+use sha2::{Digest, Sha256};
+
+use crate::endian::Endian;
+
+/// Path length limit of Windows APIs and external tools without long path support.
+pub(crate) const MAX_PATH: usize = 260;
 
 /// Create String from UTF-16 string bytes with null terminator.
-pub fn string_from_utf16_reader<R: io::Read>(reader: &mut R) -> io::Result<String> {
+pub(crate) fn string_from_utf16_reader<R: io::Read>(
+    reader: &mut R,
+    endian: Endian,
+) -> io::Result<String> {
     let mut utf16_buf = vec![];
     loop {
-        let char = reader.read_u16::<LE>()?;
+        let char = endian.read_u16(reader)?;
         if char == 0 {
             break;
         }
@@ -15,15 +26,18 @@ pub fn string_from_utf16_reader<R: io::Read>(reader: &mut R) -> io::Result<Strin
     String::from_utf16(&utf16_buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
 }
 
-pub fn string_to_utf16_bytes(s: &str) -> Vec<u8> {
+pub(crate) fn string_to_utf16_bytes(s: &str, endian: Endian) -> Vec<u8> {
     s.encode_utf16()
         .chain(Some(0))
-        .flat_map(|wc| wc.to_le_bytes())
+        .flat_map(|wc| match endian {
+            Endian::Little => wc.to_le_bytes(),
+            Endian::Big => wc.to_be_bytes(),
+        })
         .collect()
 }
 
 /// Calculate the size of data written by a function that writes to a writer.
-pub fn calc_write_size<F, W>(writer: &mut W, f: F) -> io::Result<u64>
+pub(crate) fn calc_write_size<F, W>(writer: &mut W, f: F) -> io::Result<u64>
 where
     F: FnOnce(&mut W) -> io::Result<()>,
     W: io::Write + io::Seek,
@@ -32,3 +46,238 @@ where
     f(writer)?;
     Ok(writer.stream_position()? - pos)
 }
+
+/// Format bytes as lowercase hex string.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Format a byte count with binary units, e.g. `1.5 MiB`.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Format seconds as `m:ss.s`.
+pub fn human_duration(seconds: f32) -> String {
+    let tenths = (seconds * 10.0).round() as u64;
+    format!("{}:{:02}.{}", tenths / 600, tenths / 10 % 60, tenths % 10)
+}
+
+/// Parse a UTC time `YYYY-MM-DD`, `YYYY-MM-DDTHH:MM:SS` or with a trailing `Z`,
+/// as seconds since Unix epoch.
+pub(crate) fn parse_utc_time(s: &str) -> Option<u64> {
+    let s = s.trim().trim_end_matches(['Z', 'z']);
+    let (date, time) = s.split_once(['T', 't', ' ']).unwrap_or((s, "00:00:00"));
+    let fields = |s: &str, sep| -> Option<Vec<u64>> {
+        s.split(sep).map(|field| field.parse().ok()).collect()
+    };
+    let (&[year, month, day], &[hour, minute, second]) =
+        (fields(date, '-')?.as_slice(), fields(time, ':')?.as_slice())
+    else {
+        return None;
+    };
+    let is_leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        2 if is_leap_year => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    if year < 1970 || !(1..=12).contains(&month) || !(1..=days_in_month).contains(&day) {
+        return None;
+    }
+    if hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    // days from civil, with March as the first month of year
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era_day = (153 * m + 2) / 5 + day - 1;
+    let days = y * 365 + y / 4 - y / 100 + y / 400 + era_day - 719468;
+    Some(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+/// Format seconds since Unix epoch as UTC time `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn format_utc_time(secs: u64) -> String {
+    let (days, time) = (secs / 86400 + 719468, secs % 86400);
+    // civil from days, inverse of `parse_utc_time`
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let m = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * m + 2) / 5 + 1;
+    let (month, year) = if m < 10 {
+        (m + 3, era * 400 + year_of_era)
+    } else {
+        (m - 9, era * 400 + year_of_era + 1)
+    };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// 32-bit FNV-1 hash of lowercase name, used by Wwise to derive IDs from names.
+pub fn fnv1_32(name: &str) -> u32 {
+    name.to_lowercase()
+        .bytes()
+        .fold(2166136261u32, |hash, byte| {
+            hash.wrapping_mul(16777619) ^ byte as u32
+        })
+}
+
+/// Copy a directory recursively, creating the destination.
+pub(crate) fn copy_dir_all(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
+    let to = to.as_ref();
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let dest = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_all(entry.path(), dest)?;
+        } else {
+            fs::copy(entry.path(), dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether the path may fail in external tools on Windows.
+pub(crate) fn is_long_path(path: impl AsRef<Path>) -> bool {
+    path.as_ref().as_os_str().len() >= MAX_PATH
+}
+
+/// Remove the verbatim `\\?\` prefix, which external tools do not accept.
+///
+/// `\\?\UNC\server\share` becomes `\\server\share`.
+pub(crate) fn strip_verbatim(path: &str) -> String {
+    if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", unc)
+    } else {
+        path.strip_prefix(r"\\?\").unwrap_or(path).to_string()
+    }
+}
+
+/// Path of `path` relative to the folder `base`, both absolute. `None` if
+/// they have no common root, e.g. on different drives.
+pub(crate) fn relative_path(base: &Path, path: &Path) -> Option<PathBuf> {
+    let base: Vec<_> = base.components().collect();
+    let path: Vec<_> = path.components().collect();
+    let common = base.iter().zip(&path).take_while(|(a, b)| a == b).count();
+    if common == 0 {
+        return None;
+    }
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    relative.extend(&path[common..]);
+    Some(relative)
+}
+
+/// Escape text for HTML element content and attribute values.
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Short file name for staging a file in temp folder, derived from its path.
+pub(crate) fn staging_name(source: impl AsRef<Path>, ext: &str) -> String {
+    let hash = Sha256::digest(source.as_ref().to_string_lossy().as_bytes());
+    format!("{}.{}", to_hex(&hash[..8]), ext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_long_path_helpers() {
+        assert_eq!(strip_verbatim(r"\\?\C:\sounds\a.wav"), r"C:\sounds\a.wav");
+        assert_eq!(
+            strip_verbatim(r"\\?\UNC\server\share\a.wav"),
+            r"\\server\share\a.wav"
+        );
+        assert_eq!(strip_verbatim(r"C:\sounds\a.wav"), r"C:\sounds\a.wav");
+
+        let long = format!(r"C:\mods\{}\a.wav", "x".repeat(MAX_PATH));
+        assert!(is_long_path(&long));
+        let name = staging_name(&long, "wav");
+        assert_eq!(name.len(), 20);
+        assert_eq!(name, staging_name(&long, "wav"));
+        assert_ne!(name, staging_name(r"C:\mods\a.wav", "wav"));
+    }
+
+    #[test]
+    fn test_relative_path() {
+        let relative = |base: &str, path: &str| relative_path(Path::new(base), Path::new(path));
+        assert_eq!(
+            relative("/mods/work/a.project", "/mods/a.sbnk"),
+            Some(PathBuf::from("../../a.sbnk"))
+        );
+        assert_eq!(
+            relative("/mods/a.project", "/mods/a.project/banks/1.bnk"),
+            Some(PathBuf::from("banks/1.bnk"))
+        );
+        assert_eq!(relative("/mods/a.project", "a.sbnk"), None);
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html("<a & \"b\" 'c'>"),
+            "&lt;a &amp; &quot;b&quot; &#39;c&#39;&gt;"
+        );
+    }
+
+    #[test]
+    fn test_human_units() {
+        assert_eq!(human_size(1000), "1000 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(3 * 1024 * 1024), "3.0 MiB");
+        assert_eq!(human_duration(0.04), "0:00.0");
+        assert_eq!(human_duration(83.46), "1:23.5");
+    }
+
+    #[test]
+    fn test_utc_time() {
+        assert_eq!(parse_utc_time("1970-01-01"), Some(0));
+        assert_eq!(parse_utc_time("2000-03-01T00:00:00Z"), Some(951868800));
+        assert_eq!(parse_utc_time("2025-02-28 12:30:05"), Some(1740745805));
+        assert_eq!(parse_utc_time("2025-13-01"), None);
+        assert_eq!(parse_utc_time("2025-02-31"), None);
+        assert_eq!(parse_utc_time("2025-04-31"), None);
+        assert_eq!(parse_utc_time("2025-02-29"), None);
+        assert_eq!(parse_utc_time("2024-02-29"), Some(1709164800));
+        assert_eq!(parse_utc_time("2100-02-29"), None);
+        assert_eq!(parse_utc_time("2000-02-29"), Some(951782400));
+        assert_eq!(parse_utc_time("yesterday"), None);
+        for secs in [0, 951868800, 1740745805, 4102444799] {
+            assert_eq!(parse_utc_time(&format_utc_time(secs)), Some(secs));
+        }
+        assert_eq!(format_utc_time(1740745805), "2025-02-28T12:30:05Z");
+    }
+}
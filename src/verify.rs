@@ -0,0 +1,277 @@
+//! Round-trip check of a bundle: dump it, repack the project unchanged and
+//! compare the result with the original.
+//!
+//! Entries are compared by ID, size and offset, sections of BNK files by
+//! magic and length. Byte identity is reported separately, as a bundle may
+//! round-trip safely with different padding.
+
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{
+    detect::InputFileType,
+    endian::Endian,
+    project::{self, BundleEntry, OutputPolicy, ProjectError, SoundToolProject},
+    rules::RuleSet,
+};
+
+type Result<T> = std::result::Result<T, VerifyError>;
+
+/// Size of chunks compared for byte identity.
+const COMPARE_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum VerifyError {
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("Failed to access {}: {source}", path.display())]
+    File { path: PathBuf, source: io::Error },
+
+    #[error("{0}")]
+    Project(#[from] ProjectError),
+}
+
+impl VerifyError {
+    /// Attach the path to an IO error.
+    fn file(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| VerifyError::File { path, source }
+    }
+}
+
+/// A difference between the original and the repacked bundle, `path` like
+/// `sections[2]` or `wem[3]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Mismatch {
+    pub path: String,
+    pub original: String,
+    pub repacked: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "~ {}: {} -> {}", self.path, self.original, self.repacked)
+    }
+}
+
+/// Result of a round-trip check.
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyReport {
+    pub original_size: u64,
+    pub repacked_size: u64,
+    /// Entries of the original bundle, wem and bnk.
+    pub entries: usize,
+    /// Offset of the first differing byte, `None` if byte identical.
+    pub first_difference: Option<u64>,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl VerifyReport {
+    pub fn is_identical(&self) -> bool {
+        self.first_difference.is_none()
+    }
+
+    /// Whether the bundle round-trips, byte identical if `exact`.
+    pub fn is_ok(&self, exact: bool) -> bool {
+        self.mismatches.is_empty() && (!exact || self.is_identical())
+    }
+}
+
+/// Dump a bnk or pck file to a temp folder, repack it unchanged and compare.
+///
+/// Replace rules are never applied, so differences come from the tool only.
+pub fn verify_bundle(input: impl AsRef<Path>) -> Result<VerifyReport> {
+    let input = input.as_ref();
+    let tmp_dir = tempfile::tempdir()?;
+    let project = match InputFileType::from_path(input) {
+        Some(InputFileType::Bnk) => SoundToolProject::dump_bnk(input, tmp_dir.path())?,
+        Some(InputFileType::Pck) => SoundToolProject::dump_pck(input, tmp_dir.path())?,
+        _ => return Err(ProjectError::UnsupportedInput(input.to_path_buf()).into()),
+    };
+    let stats = project.repack_with_rules(
+        tmp_dir.path().join("repacked"),
+        &OutputPolicy::default(),
+        &RuleSet::default(),
+    )?;
+    let repacked = stats.output_path;
+
+    let mut mismatches = vec![];
+    compare_list(
+        "sections",
+        &section_layout(input).map_err(VerifyError::file(input))?,
+        &section_layout(&repacked).map_err(VerifyError::file(&repacked))?,
+        |(magic, length)| format!("{} {} bytes", String::from_utf8_lossy(magic), length),
+        &mut mismatches,
+    );
+    let original_entries = project::list_entries(input)?;
+    let repacked_entries = project::list_entries(&repacked)?;
+    for kind in ["bnk", "wem"] {
+        let of_kind = |entries: &[BundleEntry]| -> Vec<(u32, u32, u32, Option<String>)> {
+            entries
+                .iter()
+                .filter(|entry| entry.kind == kind)
+                .map(|entry| (entry.id, entry.size, entry.offset, entry.language.clone()))
+                .collect()
+        };
+        compare_list(
+            kind,
+            &of_kind(&original_entries),
+            &of_kind(&repacked_entries),
+            |(id, size, offset, language)| match language {
+                Some(language) => format!("{} ({}) {} bytes at {}", id, language, size, offset),
+                None => format!("{} {} bytes at {}", id, size, offset),
+            },
+            &mut mismatches,
+        );
+    }
+
+    Ok(VerifyReport {
+        original_size: fs::metadata(input).map_err(VerifyError::file(input))?.len(),
+        repacked_size: fs::metadata(&repacked)
+            .map_err(VerifyError::file(&repacked))?
+            .len(),
+        entries: original_entries.len(),
+        first_difference: first_difference(input, &repacked)?,
+        mismatches,
+    })
+}
+
+/// Compare items by index, and the count if it differs.
+fn compare_list<T: PartialEq>(
+    name: &str,
+    original: &[T],
+    repacked: &[T],
+    describe: impl Fn(&T) -> String,
+    mismatches: &mut Vec<Mismatch>,
+) {
+    if original.len() != repacked.len() {
+        mismatches.push(Mismatch {
+            path: format!("{}.len", name),
+            original: original.len().to_string(),
+            repacked: repacked.len().to_string(),
+        });
+    }
+    for (i, (a, b)) in original.iter().zip(repacked).enumerate() {
+        if a != b {
+            mismatches.push(Mismatch {
+                path: format!("{}[{}]", name, i),
+                original: describe(a),
+                repacked: describe(b),
+            });
+        }
+    }
+}
+
+/// Magic and length of sections of a BNK file, or the header of a PCK file.
+fn section_layout(path: &Path) -> io::Result<Vec<([u8; 4], u32)>> {
+    let file_len = fs::metadata(path)?.len();
+    let mut reader = io::BufReader::new(File::open(path)?);
+    // version follows the first section of a BNK, the header of a PCK
+    let endian = Endian::detect_at(&mut reader, 8)?;
+    let mut sections = vec![];
+    let mut position = 0;
+    while position + 8 <= file_len {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        let length = endian.read_u32(&mut reader)?;
+        sections.push((magic, length));
+        // entries of a PCK follow its header without sections
+        if &magic == b"AKPK" {
+            break;
+        }
+        reader.seek_relative(length as i64)?;
+        position += 8 + length as u64;
+    }
+    Ok(sections)
+}
+
+/// Offset of the first differing byte of two files, `None` if identical.
+fn first_difference(a: &Path, b: &Path) -> Result<Option<u64>> {
+    let mut reader_a = io::BufReader::new(File::open(a).map_err(VerifyError::file(a))?);
+    let mut reader_b = io::BufReader::new(File::open(b).map_err(VerifyError::file(b))?);
+    let mut buf_a = vec![0; COMPARE_CHUNK_SIZE];
+    let mut buf_b = vec![0; COMPARE_CHUNK_SIZE];
+    let mut offset = 0;
+    loop {
+        let len_a = read_full(&mut reader_a, &mut buf_a).map_err(VerifyError::file(a))?;
+        let len_b = read_full(&mut reader_b, &mut buf_b).map_err(VerifyError::file(b))?;
+        if let Some(pos) = buf_a[..len_a]
+            .iter()
+            .zip(&buf_b[..len_b])
+            .position(|(x, y)| x != y)
+        {
+            return Ok(Some(offset + pos as u64));
+        }
+        if len_a != len_b {
+            return Ok(Some(offset + len_a.min(len_b) as u64));
+        }
+        if len_a == 0 {
+            return Ok(None);
+        }
+        offset += len_a as u64;
+    }
+}
+
+/// Fill the buffer unless the end is reached, returns the bytes read.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_first_difference() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, data: &[u8]| -> PathBuf {
+            let path = dir.path().join(name);
+            fs::write(&path, data).unwrap();
+            path
+        };
+        let data: Vec<u8> = (0..COMPARE_CHUNK_SIZE * 2 + 10).map(|i| i as u8).collect();
+        let a = write("a", &data);
+        assert_eq!(first_difference(&a, &write("b", &data)).unwrap(), None);
+        let mut changed = data.clone();
+        changed[COMPARE_CHUNK_SIZE + 3] ^= 1;
+        assert_eq!(
+            first_difference(&a, &write("c", &changed)).unwrap(),
+            Some(COMPARE_CHUNK_SIZE as u64 + 3)
+        );
+        assert_eq!(
+            first_difference(&a, &write("d", &data[..100])).unwrap(),
+            Some(100)
+        );
+    }
+
+    #[test]
+    fn test_compare_list() {
+        let mut mismatches = vec![];
+        compare_list(
+            "wem",
+            &[1, 2, 3],
+            &[1, 5],
+            |n| n.to_string(),
+            &mut mismatches,
+        );
+        assert_eq!(
+            mismatches.iter().map(|m| m.to_string()).collect::<Vec<_>>(),
+            ["~ wem.len: 3 -> 2", "~ wem[1]: 2 -> 5"]
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    env, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+type Result<T> = std::result::Result<T, VgmstreamError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum VgmstreamError {
+    #[error("vgmstream module IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("vgmstream-cli executable not found.")]
+    VgmstreamNotFound,
+    #[error("Command failed: {code:?}\n{stdout}\n{stderr}")]
+    CommandFailed {
+        code: Option<i32>,
+        stdout: String,
+        stderr: String,
+    },
+    #[error("Command execution failed: {0}")]
+    CommandExecutionFailed(io::Error),
+}
+
+impl VgmstreamError {
+    fn command_failed(code: Option<i32>, stdout: &[u8], stderr: &[u8]) -> Self {
+        VgmstreamError::CommandFailed {
+            code,
+            stdout: String::from_utf8_lossy(stdout).to_string(),
+            stderr: String::from_utf8_lossy(stderr).to_string(),
+        }
+    }
+}
+
+/// vgmstream command line decoder, used to decode wem files.
+pub struct VgmstreamCli {
+    program_path: PathBuf,
+}
+
+impl VgmstreamCli {
+    pub fn new() -> Result<Self> {
+        let mut try_paths = vec![];
+        // env
+        if let Ok(path) = env::var("VGMSTREAM_PATH") {
+            try_paths.push(PathBuf::from(path));
+        }
+        // inside exe dir
+        let exe_path = env::current_exe()?;
+        let exe_dir = exe_path.parent().unwrap();
+        try_paths.push(exe_dir.join("vgmstream-cli"));
+        try_paths.push(exe_dir.join("vgmstream").join("vgmstream-cli"));
+        // inside cwd
+        let cwd = env::current_dir()?;
+        try_paths.push(cwd.join("vgmstream-cli"));
+        // global
+        try_paths.push(PathBuf::from("vgmstream-cli"));
+
+        for path in try_paths {
+            if Self::test_vgmstream_cli(&path) {
+                return Ok(Self { program_path: path });
+            };
+        }
+
+        Err(VgmstreamError::VgmstreamNotFound)
+    }
+
+    pub fn new_with_path(program_path: PathBuf) -> Option<Self> {
+        if !Self::test_vgmstream_cli(&program_path) {
+            return None;
+        }
+        Some(Self { program_path })
+    }
+
+    pub fn program_path(&self) -> &Path {
+        self.program_path.as_ref()
+    }
+
+    /// Decode a wem file to wav.
+    pub fn decode_to_wav(&self, input: impl AsRef<Path>, output: impl AsRef<Path>) -> Result<()> {
+        let input = input.as_ref();
+        let output = output.as_ref();
+
+        let result = Command::new(&self.program_path)
+            .args(["-o", output.to_str().unwrap(), input.to_str().unwrap()])
+            .output()
+            .map_err(VgmstreamError::CommandExecutionFailed)?;
+
+        if !result.status.success() {
+            return Err(VgmstreamError::command_failed(
+                result.status.code(),
+                &result.stdout,
+                &result.stderr,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Test if the vgmstream-cli can be executed.
+    fn test_vgmstream_cli(program_path: impl AsRef<Path>) -> bool {
+        // vgmstream-cli prints usage and exits with non-zero code without input,
+        // so only check if the process can be spawned.
+        Command::new(program_path.as_ref())
+            .args(["-V"])
+            .output()
+            .is_ok()
+    }
+}
@@ -0,0 +1,197 @@
+//! Repack a project automatically when its files change.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::{
+    lock::LOCK_FILE_NAME,
+    project::{ADD_DIR, BANKS_DIR, ProjectError, RepackStats, SoundToolProject},
+    session::SESSION_FILE_NAME,
+};
+
+type Result<T> = std::result::Result<T, WatchError>;
+
+/// Changes within this time of each other are repacked once.
+pub const DEBOUNCE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, thiserror::Error)]
+pub enum WatchError {
+    #[error("Failed to access {}: {source}", path.display())]
+    File { path: PathBuf, source: io::Error },
+
+    #[error("File watcher error: {0}")]
+    Notify(#[from] notify::Error),
+    #[error("{0}")]
+    Project(#[from] ProjectError),
+}
+
+impl WatchError {
+    /// Attach the path to an IO error.
+    fn file(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| WatchError::File { path, source }
+    }
+}
+
+pub struct ProjectWatcher {
+    project_path: PathBuf,
+    output_root: PathBuf,
+    /// Output of the previous repack, replaced by the next one.
+    last_output: Option<PathBuf>,
+}
+
+impl ProjectWatcher {
+    pub fn new(project_path: impl AsRef<Path>, output_root: impl AsRef<Path>) -> Self {
+        Self {
+            project_path: project_path.as_ref().to_path_buf(),
+            output_root: output_root.as_ref().to_path_buf(),
+            last_output: None,
+        }
+    }
+
+    /// Repack once. The output of the previous repack is removed first,
+    /// so repeated repacks do not pile up `.new` files.
+    pub fn repack(&mut self) -> Result<RepackStats> {
+        if let Some(last_output) = self.last_output.take()
+            && last_output.is_file()
+        {
+            fs::remove_file(&last_output).map_err(WatchError::file(&last_output))?;
+        }
+        let project = SoundToolProject::from_path(&self.project_path)?;
+        let stats = project.repack(&self.output_root)?;
+        self.last_output = Some(stats.output_path.clone());
+        Ok(stats)
+    }
+
+    /// Repack now and on every change of wem, replace or settings files.
+    ///
+    /// Failed repacks are passed to `on_repack` and watching continues.
+    /// Returns only if the watcher fails.
+    pub fn run(&mut self, mut on_repack: impl FnMut(Result<RepackStats>)) -> Result<()> {
+        // event paths are reported under the watched path
+        let watch_root =
+            fs::canonicalize(&self.project_path).map_err(WatchError::file(&self.project_path))?;
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        watcher.watch(&watch_root, RecursiveMode::Recursive)?;
+
+        let result = self.repack();
+        on_repack(result);
+        while let Ok(event) = rx.recv() {
+            let mut changed = is_change(&watch_root, event?);
+            while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+                changed |= is_change(&watch_root, event?);
+            }
+            if changed {
+                let result = self.repack();
+                on_repack(result);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_change(project_path: &Path, event: notify::Event) -> bool {
+    !matches!(event.kind, EventKind::Access(_))
+        && event
+            .paths
+            .iter()
+            .any(|path| is_watched(project_path, path))
+}
+
+/// Whether a change of `path` triggers a repack: files in `replace` and `add`,
+/// and wem, metadata and settings files in project folder, and the same
+/// files of nested projects in `banks`.
+pub fn is_watched(project_path: &Path, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(project_path) else {
+        return false;
+    };
+    let mut components = relative.components();
+    let Some(first) = components.next() else {
+        return false;
+    };
+    if first.as_os_str() == "replace" || first.as_os_str() == ADD_DIR {
+        return true;
+    }
+    if first.as_os_str() == BANKS_DIR {
+        return components
+            .next()
+            .is_some_and(|nested| is_watched(&project_path.join(BANKS_DIR).join(nested), path));
+    }
+    if components.next().is_some()
+        || first.as_os_str() == LOCK_FILE_NAME
+        || first.as_os_str() == SESSION_FILE_NAME
+    {
+        return false;
+    }
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("wem" | "json" | "toml")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::DECODED_DIR;
+
+    #[test]
+    fn test_is_watched() {
+        let project_path = Path::new("mods/Wp00_Cmn.sbnk.1.X64.project");
+        let watched = [
+            "[000]123.wem",
+            "bank.json",
+            "project.toml",
+            "replace/123.mp3",
+            "replace/sub/[1].wav",
+            "add/japanese/900001.wem",
+            "banks/1001.bnk.project/[000]123.wem",
+            "banks/1001.bnk.project/project.toml",
+            "banks/1001.bnk.project/replace/123.mp3",
+        ];
+        for path in watched {
+            assert!(
+                is_watched(project_path, &project_path.join(path)),
+                "{}",
+                path
+            );
+        }
+        let ignored = [
+            LOCK_FILE_NAME,
+            SESSION_FILE_NAME,
+            "notes.txt",
+            "decoded/[000]123.wav",
+            "banks/1001.bnk",
+            "banks/1001.bnk.project/decoded/[000]123.wav",
+            "../Wp00_Cmn.sbnk.1.X64.new",
+        ];
+        for path in ignored {
+            assert!(
+                !is_watched(project_path, &project_path.join(path)),
+                "{}",
+                path
+            );
+        }
+        assert!(!is_watched(project_path, Path::new(DECODED_DIR)));
+    }
+
+    #[test]
+    fn test_repack_replaces_last_output() {
+        let input = "test_files/Wp00_Cmn.sbnk.1.X64";
+        let output_root = tempfile::tempdir().unwrap();
+        let project = SoundToolProject::dump_bnk(input, output_root.path()).unwrap();
+        fs::copy(input, output_root.path().join("Wp00_Cmn.sbnk.1.X64")).unwrap();
+
+        let mut watcher = ProjectWatcher::new(project.project_path(), output_root.path());
+        let first = watcher.repack().unwrap();
+        let second = watcher.repack().unwrap();
+        assert_eq!(first.output_path, second.output_path);
+        assert!(second.output_path.to_string_lossy().ends_with(".X64.new"));
+    }
+}
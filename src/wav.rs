@@ -0,0 +1,389 @@
+//! RIFF chunk sanitizer of source wav files.
+//!
+//! WwiseConsole fails on some chunk layouts written by audio editors,
+//! e.g. `JUNK` or `LIST` before `fmt `, odd sized chunks without padding
+//! or wrong RIFF sizes. Sources are rewritten with only the chunks needed
+//! for conversion, loop points in `smpl` and `cue ` are kept.
+
+use std::io;
+
+type Result<T> = std::result::Result<T, WavError>;
+
+/// Chunks kept by the sanitizer, others are stripped.
+const KEPT_CHUNKS: [&[u8; 4]; 5] = [b"fmt ", b"fact", b"data", b"cue ", b"smpl"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum WavError {
+    #[error("Not a RIFF WAVE file.")]
+    NotWave,
+    #[error("Missing '{0}' chunk.")]
+    MissingChunk(&'static str),
+}
+
+/// Sanitized wav data.
+#[derive(Debug, Clone)]
+pub struct Sanitized {
+    pub data: Vec<u8>,
+    /// Human readable changes made, empty if the layout was already clean.
+    pub changes: Vec<String>,
+}
+
+impl Sanitized {
+    pub fn is_changed(&self) -> bool {
+        !self.changes.is_empty()
+    }
+}
+
+/// Rewrite wav data with `fmt ` first, followed by other kept chunks in
+/// original order, with correct sizes and padding.
+pub fn sanitize(wav_data: &[u8]) -> Result<Sanitized> {
+    if wav_data.len() < 12 || &wav_data[0..4] != b"RIFF" || &wav_data[8..12] != b"WAVE" {
+        return Err(WavError::NotWave);
+    }
+    let mut changes = vec![];
+    let riff_size = u32::from_le_bytes(wav_data[4..8].try_into().unwrap()) as usize;
+    if riff_size + 8 != wav_data.len() {
+        changes.push(format!(
+            "RIFF size {} corrected to {}",
+            riff_size,
+            wav_data.len() - 8
+        ));
+    }
+
+    let mut chunks: Vec<(&[u8; 4], &[u8])> = vec![];
+    let mut pos = 12;
+    while pos + 8 <= wav_data.len() {
+        let id: &[u8; 4] = wav_data[pos..pos + 4].try_into().unwrap();
+        let size = u32::from_le_bytes(wav_data[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let start = pos + 8;
+        let mut end = start + size;
+        if end > wav_data.len() {
+            if id != b"data" {
+                changes.push(format!("truncated '{}' chunk dropped", chunk_name(id)));
+                break;
+            }
+            changes.push("truncated 'data' chunk shortened".to_string());
+            end = wav_data.len();
+        }
+        pos = end + (size & 1);
+        // some writers do not pad odd sized chunks
+        if size & 1 == 1
+            && !is_chunk_id(wav_data.get(pos..pos + 4))
+            && is_chunk_id(wav_data.get(end..end + 4))
+        {
+            changes.push(format!("missing padding of '{}' added", chunk_name(id)));
+            pos = end;
+        }
+
+        if !KEPT_CHUNKS.contains(&id) {
+            changes.push(format!("'{}' stripped", chunk_name(id)));
+        } else if chunks.iter().any(|(kept, _)| *kept == id) {
+            changes.push(format!("duplicate '{}' stripped", chunk_name(id)));
+        } else {
+            chunks.push((id, &wav_data[start..end]));
+        }
+    }
+
+    let Some(fmt_idx) = chunks.iter().position(|(id, _)| *id == b"fmt ") else {
+        return Err(WavError::MissingChunk("fmt "));
+    };
+    if !chunks.iter().any(|(id, _)| *id == b"data") {
+        return Err(WavError::MissingChunk("data"));
+    }
+    if fmt_idx != 0 {
+        changes.push("'fmt ' moved to front".to_string());
+        let fmt = chunks.remove(fmt_idx);
+        chunks.insert(0, fmt);
+    }
+
+    let mut data = Vec::with_capacity(wav_data.len());
+    data.extend_from_slice(b"RIFF\0\0\0\0WAVE");
+    for (id, body) in chunks {
+        data.extend_from_slice(id);
+        data.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        data.extend_from_slice(body);
+        if body.len() & 1 == 1 {
+            data.push(0);
+        }
+    }
+    let riff_size = (data.len() - 8) as u32;
+    data[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+    Ok(Sanitized { data, changes })
+}
+
+/// Loop points of a `smpl` chunk fitted to other audio.
+#[derive(Debug, Clone)]
+pub struct FittedLoops {
+    /// Body of the fitted `smpl` chunk.
+    pub smpl: Vec<u8>,
+    /// Sample rate of the audio, loop points were scaled if it differs.
+    pub sample_rate: u32,
+    /// Count of loops past the end of the audio, cut to its end or dropped.
+    pub cut: usize,
+}
+
+/// Fit loop points of `smpl` chunk body of audio at `from_rate` to the audio
+/// of `wav_data`, scaling them by the sample rates, and cutting loops to its
+/// length.
+pub fn fit_loop_points(smpl: &[u8], from_rate: u32, wav_data: &[u8]) -> Result<FittedLoops> {
+    let fmt = find_chunk(wav_data, b"fmt ")
+        .filter(|fmt| fmt.len() >= 14)
+        .ok_or(WavError::MissingChunk("fmt "))?;
+    let data = find_chunk(wav_data, b"data").ok_or(WavError::MissingChunk("data"))?;
+    if smpl.len() < 36 {
+        return Err(WavError::MissingChunk("smpl"));
+    }
+    let read_u32 =
+        |body: &[u8], pos: usize| u32::from_le_bytes(body[pos..pos + 4].try_into().unwrap());
+    let sample_rate = read_u32(fmt, 4);
+    let block_align = u16::from_le_bytes(fmt[12..14].try_into().unwrap()).max(1) as usize;
+    let frames = (data.len() / block_align) as u64;
+    let scale = |position: u64| {
+        if from_rate == 0 || from_rate == sample_rate {
+            position
+        } else {
+            (position * sample_rate as u64 + from_rate as u64 / 2) / from_rate as u64
+        }
+    };
+
+    let count = (read_u32(smpl, 28) as usize).min((smpl.len() - 36) / 24);
+    let mut loops = vec![];
+    let mut cut = 0;
+    for i in 0..count {
+        let mut sample_loop = smpl[36 + i * 24..60 + i * 24].to_vec();
+        let start = scale(read_u32(&sample_loop, 8) as u64);
+        // inclusive in smpl
+        let end = scale(read_u32(&sample_loop, 12) as u64 + 1);
+        if start >= frames {
+            cut += 1;
+            continue;
+        }
+        if end > frames {
+            cut += 1;
+        }
+        let end = end.min(frames).max(start + 1) - 1;
+        sample_loop[8..12].copy_from_slice(&(start as u32).to_le_bytes());
+        sample_loop[12..16].copy_from_slice(&(end as u32).to_le_bytes());
+        loops.push(sample_loop);
+    }
+
+    let mut fitted = smpl[..36].to_vec();
+    if sample_rate > 0 {
+        // sample period in nanoseconds
+        let period = (1_000_000_000 / sample_rate as u64) as u32;
+        fitted[8..12].copy_from_slice(&period.to_le_bytes());
+    }
+    fitted[28..32].copy_from_slice(&(loops.len() as u32).to_le_bytes());
+    fitted.extend(loops.concat());
+    // sampler specific data
+    fitted.extend_from_slice(&smpl[36 + count * 24..]);
+    Ok(FittedLoops {
+        smpl: fitted,
+        sample_rate,
+        cut,
+    })
+}
+
+/// Interleaved samples of wav data in range [-1.0, 1.0], with its format.
+pub(crate) fn read_samples(wav_data: &[u8]) -> hound::Result<(hound::WavSpec, Vec<f32>)> {
+    let mut reader = hound::WavReader::new(io::Cursor::new(wav_data))?;
+    let spec = reader.spec();
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<hound::Result<_>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<hound::Result<_>>()?
+        }
+    };
+    Ok((spec, samples))
+}
+
+/// Body of the first chunk `id` of wav data.
+pub fn find_chunk<'a>(wav_data: &'a [u8], id: &[u8; 4]) -> Option<&'a [u8]> {
+    let mut pos = 12;
+    while pos + 8 <= wav_data.len() {
+        let size = u32::from_le_bytes(wav_data[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let body = wav_data.get(pos + 8..pos + 8 + size)?;
+        if &wav_data[pos..pos + 4] == id {
+            return Some(body);
+        }
+        pos += 8 + size + (size & 1);
+    }
+    None
+}
+
+/// Append a chunk to wav data, with padding and corrected RIFF size.
+pub fn append_chunk(wav_data: &mut Vec<u8>, id: &[u8; 4], body: &[u8]) {
+    if wav_data.len() & 1 == 1 {
+        wav_data.push(0);
+    }
+    wav_data.extend_from_slice(id);
+    wav_data.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    wav_data.extend_from_slice(body);
+    if body.len() & 1 == 1 {
+        wav_data.push(0);
+    }
+    let riff_size = (wav_data.len() - 8) as u32;
+    wav_data[4..8].copy_from_slice(&riff_size.to_le_bytes());
+}
+
+fn is_chunk_id(bytes: Option<&[u8]>) -> bool {
+    bytes.is_some_and(|bytes| bytes.iter().all(|b| b.is_ascii_graphic() || *b == b' '))
+}
+
+fn chunk_name(id: &[u8; 4]) -> String {
+    String::from_utf8_lossy(id).trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &[u8; 4], body: &[u8], pad: bool) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(body);
+        if pad && body.len() & 1 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn riff(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body = chunks.concat();
+        let mut data = b"RIFF".to_vec();
+        data.extend_from_slice(&(body.len() as u32 + 4).to_le_bytes());
+        data.extend_from_slice(b"WAVE");
+        data.extend_from_slice(&body);
+        data
+    }
+
+    fn fmt_chunk() -> Vec<u8> {
+        // PCM, mono, 48000 Hz, 16 bit
+        let mut body = vec![];
+        for value in [1u16, 1] {
+            body.extend_from_slice(&value.to_le_bytes());
+        }
+        body.extend_from_slice(&48000u32.to_le_bytes());
+        body.extend_from_slice(&96000u32.to_le_bytes());
+        for value in [2u16, 16] {
+            body.extend_from_slice(&value.to_le_bytes());
+        }
+        chunk(b"fmt ", &body, true)
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let samples: Vec<u8> = [0i16, 1000, -1000, 0]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        let smpl = chunk(b"smpl", &[7; 60], true);
+        let input = riff(&[
+            chunk(b"JUNK", &[0; 28], true),
+            chunk(b"LIST", b"INFOISFT\x03\0\0\0abc", false),
+            fmt_chunk(),
+            chunk(b"data", &samples, true),
+            smpl.clone(),
+        ]);
+
+        let sanitized = sanitize(&input).unwrap();
+        assert!(sanitized.is_changed());
+        assert_eq!(
+            sanitized.data,
+            riff(&[fmt_chunk(), chunk(b"data", &samples, true), smpl])
+        );
+        let reader = hound::WavReader::new(io::Cursor::new(&sanitized.data)).unwrap();
+        assert_eq!(reader.duration(), 4);
+
+        // clean layout is kept as is
+        let clean = sanitize(&sanitized.data).unwrap();
+        assert!(!clean.is_changed());
+        assert_eq!(clean.data, sanitized.data);
+
+        assert!(matches!(sanitize(b"OggS"), Err(WavError::NotWave)));
+        assert!(matches!(
+            sanitize(&riff(&[fmt_chunk()])),
+            Err(WavError::MissingChunk("data"))
+        ));
+    }
+
+    #[test]
+    fn test_append_chunk() {
+        let smpl = [7; 61];
+        let mut data = riff(&[fmt_chunk(), chunk(b"data", &[0; 8], true)]);
+        assert_eq!(find_chunk(&data, b"smpl"), None);
+        append_chunk(&mut data, b"smpl", &smpl);
+        assert_eq!(
+            data,
+            riff(&[
+                fmt_chunk(),
+                chunk(b"data", &[0; 8], true),
+                chunk(b"smpl", &smpl, true)
+            ])
+        );
+        assert_eq!(find_chunk(&data, b"smpl"), Some(&smpl[..]));
+        assert_eq!(find_chunk(&data, b"data"), Some(&[0; 8][..]));
+        assert!(!sanitize(&data).unwrap().is_changed());
+    }
+
+    #[test]
+    fn test_read_samples() {
+        let samples: Vec<u8> = [0i16, 16384, i16::MIN, 0]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        let data = riff(&[fmt_chunk(), chunk(b"data", &samples, true)]);
+        let (spec, samples) = read_samples(&data).unwrap();
+        assert_eq!((spec.channels, spec.sample_rate), (1, 48000));
+        assert_eq!(samples, [0.0, 0.5, -1.0, 0.0]);
+        assert!(read_samples(b"OggS").is_err());
+    }
+
+    fn smpl_body(loops: &[(u32, u32)]) -> Vec<u8> {
+        let mut body = vec![0; 36];
+        body[8..12].copy_from_slice(&22675u32.to_le_bytes());
+        body[28..32].copy_from_slice(&(loops.len() as u32).to_le_bytes());
+        for (id, &(start, end)) in loops.iter().enumerate() {
+            for value in [id as u32, 0, start, end, 0, 0] {
+                body.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        body
+    }
+
+    #[test]
+    fn test_fit_loop_points() {
+        // 1 second at 48000 Hz, mono 16 bit
+        let data = riff(&[fmt_chunk(), chunk(b"data", &[0; 96000], true)]);
+        let smpl = smpl_body(&[(22050, 44099), (11025, 88199), (44100, 50000)]);
+
+        let fitted = fit_loop_points(&smpl, 44100, &data).unwrap();
+        assert_eq!(fitted.sample_rate, 48000);
+        assert_eq!(fitted.cut, 2);
+        assert_eq!(
+            fitted.smpl,
+            [
+                &smpl_body(&[(24000, 47999), (12000, 47999)])[..8],
+                &20833u32.to_le_bytes(),
+                &smpl_body(&[(24000, 47999), (12000, 47999)])[12..],
+            ]
+            .concat()
+        );
+
+        // same rate is only checked against the length
+        let smpl = smpl_body(&[(100, 199)]);
+        let fitted = fit_loop_points(&smpl, 48000, &data).unwrap();
+        assert_eq!(fitted.cut, 0);
+        assert_eq!(&fitted.smpl[12..], &smpl[12..]);
+
+        assert!(matches!(
+            fit_loop_points(&smpl, 44100, &riff(&[fmt_chunk()])),
+            Err(WavError::MissingChunk("data"))
+        ));
+    }
+}
@@ -0,0 +1,268 @@
+//! Workspaces of many projects of a mod, with shared settings.
+//!
+//! A workspace is a folder with a `workspace.toml`:
+//!
+//! ```toml
+//! # project folders, `*` for all projects in a folder
+//! members = ["weapons/*", "ui/Ui_Cmn.sbnk.1.X64.project"]
+//! exclude = ["weapons/Wp99_Cmn.sbnk.1.X64.project"]
+//! # packaged bundles, next to each project if not set
+//! output_root = "dist"
+//! # replaces `paths.deploy_root` of config
+//! deploy_root = "D:/Mods/MySounds"
+//! # data packs of the mod, instead of the installed ones for `--rules` and `stats`
+//! datapacks = "datapacks"
+//!
+//! # replaces `[transcode]` of config
+//! [transcode]
+//! limiter = true
+//! ```
+//!
+//! Paths are relative to the workspace folder.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::config::TranscodeConfig;
+
+type Result<T> = std::result::Result<T, WorkspaceError>;
+
+pub const WORKSPACE_FILE_NAME: &str = "workspace.toml";
+
+#[derive(Debug, thiserror::Error)]
+pub enum WorkspaceError {
+    #[error("Failed to access {}: {source}", path.display())]
+    File { path: PathBuf, source: io::Error },
+
+    #[error("Workspace file not found: {}", .0.display())]
+    NotFound(PathBuf),
+    #[error("Bad workspace file {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("Workspace member is not a project folder: {}", .0.display())]
+    MemberNotFound(PathBuf),
+}
+
+impl WorkspaceError {
+    /// Attach the path to an IO error.
+    fn file(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| WorkspaceError::File { path, source }
+    }
+}
+
+/// Content of `workspace.toml`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkspaceManifest {
+    #[serde(default)]
+    pub members: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub output_root: Option<PathBuf>,
+    #[serde(default)]
+    pub deploy_root: Option<PathBuf>,
+    #[serde(default)]
+    pub datapacks: Option<PathBuf>,
+    #[serde(default)]
+    pub transcode: Option<TranscodeConfig>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    root: PathBuf,
+    manifest: WorkspaceManifest,
+    members: Vec<PathBuf>,
+}
+
+impl Workspace {
+    /// Load a workspace from its `workspace.toml`, or the folder of it.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file_path = if path.is_dir() {
+            path.join(WORKSPACE_FILE_NAME)
+        } else {
+            path.to_path_buf()
+        };
+        if !file_path.is_file() {
+            return Err(WorkspaceError::NotFound(file_path));
+        }
+        let content = fs::read_to_string(&file_path).map_err(WorkspaceError::file(&file_path))?;
+        let manifest: WorkspaceManifest =
+            toml::from_str(&content).map_err(|source| WorkspaceError::Parse {
+                path: file_path.clone(),
+                source,
+            })?;
+        let root = file_path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+            .to_path_buf();
+        let members = resolve_members(&root, &manifest)?;
+        Ok(Self {
+            root,
+            manifest,
+            members,
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Project folders of the workspace, sorted.
+    pub fn members(&self) -> &[PathBuf] {
+        &self.members
+    }
+
+    /// Name of a member, its path in the workspace.
+    pub fn member_name(&self, member: &Path) -> String {
+        member
+            .strip_prefix(&self.root)
+            .unwrap_or(member)
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+
+    pub fn output_root(&self) -> Option<PathBuf> {
+        self.manifest
+            .output_root
+            .as_ref()
+            .map(|path| self.root.join(path))
+    }
+
+    pub fn deploy_root(&self) -> Option<PathBuf> {
+        self.manifest
+            .deploy_root
+            .as_ref()
+            .map(|path| self.root.join(path))
+    }
+
+    pub fn datapacks_dir(&self) -> Option<PathBuf> {
+        self.manifest
+            .datapacks
+            .as_ref()
+            .map(|path| self.root.join(path))
+    }
+
+    pub fn transcode(&self) -> Option<&TranscodeConfig> {
+        self.manifest.transcode.as_ref()
+    }
+}
+
+fn is_project(path: &Path) -> bool {
+    path.join("project.json").is_file()
+}
+
+fn resolve_members(root: &Path, manifest: &WorkspaceManifest) -> Result<Vec<PathBuf>> {
+    let excluded: Vec<PathBuf> = manifest
+        .exclude
+        .iter()
+        .map(|pattern| root.join(pattern))
+        .collect();
+    let mut members = vec![];
+    for pattern in &manifest.members {
+        // `*` is only supported as the last component
+        if let Some(dir) = pattern
+            .strip_suffix("/*")
+            .or(pattern.strip_suffix("\\*"))
+            .or((pattern == "*").then_some(""))
+        {
+            let dir = root.join(dir);
+            if !dir.is_dir() {
+                return Err(WorkspaceError::MemberNotFound(dir));
+            }
+            for entry in fs::read_dir(&dir).map_err(WorkspaceError::file(&dir))? {
+                let path = entry.map_err(WorkspaceError::file(&dir))?.path();
+                if is_project(&path) {
+                    members.push(path);
+                }
+            }
+        } else {
+            let path = root.join(pattern);
+            if !is_project(&path) {
+                return Err(WorkspaceError::MemberNotFound(path));
+            }
+            members.push(path);
+        }
+    }
+    members.retain(|member| !excluded.contains(member));
+    members.sort();
+    members.dedup();
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        for project in [
+            "weapons/Wp00.sbnk.1.X64.project",
+            "weapons/Wp01.sbnk.1.X64.project",
+            "weapons/Wp02.sbnk.1.X64.project",
+            "ui/Ui.sbnk.1.X64.project",
+        ] {
+            let path = dir.path().join(project);
+            fs::create_dir_all(&path).unwrap();
+            fs::write(path.join("project.json"), "{}").unwrap();
+        }
+        // not a project
+        fs::create_dir_all(dir.path().join("weapons/notes")).unwrap();
+        fs::write(
+            dir.path().join(WORKSPACE_FILE_NAME),
+            r#"
+members = ["weapons/*", "ui/Ui.sbnk.1.X64.project"]
+exclude = ["weapons/Wp01.sbnk.1.X64.project"]
+output_root = "dist"
+
+[transcode]
+limiter = true
+"#,
+        )
+        .unwrap();
+
+        let workspace = Workspace::from_path(dir.path()).unwrap();
+        let names: Vec<String> = workspace
+            .members()
+            .iter()
+            .map(|member| workspace.member_name(member))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "ui/Ui.sbnk.1.X64.project",
+                "weapons/Wp00.sbnk.1.X64.project",
+                "weapons/Wp02.sbnk.1.X64.project"
+            ]
+        );
+        assert_eq!(workspace.output_root(), Some(dir.path().join("dist")));
+        assert_eq!(workspace.deploy_root(), None);
+        let transcode = workspace.transcode().unwrap();
+        assert!(transcode.limiter);
+        assert_eq!(transcode.limiter_threshold_db, -1.0);
+
+        fs::write(
+            dir.path().join(WORKSPACE_FILE_NAME),
+            r#"members = ["ui/Missing.project"]"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            Workspace::from_path(dir.path().join(WORKSPACE_FILE_NAME)),
+            Err(WorkspaceError::MemberNotFound(_))
+        ));
+        assert!(matches!(
+            Workspace::from_path(dir.path().join("ui")),
+            Err(WorkspaceError::NotFound(_))
+        ));
+    }
+}
@@ -1,11 +1,58 @@
 use std::{
-    env, fs,
+    env,
+    ffi::OsString,
+    fs,
     io::{self, Write},
     path::{Path, PathBuf},
-    process::Command,
+    process::{self, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    endian::Endian,
+    lock::{LockError, ProjectLock},
+    utils,
 };
 
 const WWISE_BASE_DEFAULT_PATH: &str = r"C:\Program Files (x86)\Audiokinetic";
+/// Install folder of Wwise in a Wine prefix, relative to the prefix.
+const WINE_WWISE_BASE_PATH: &str = "drive_c/Program Files (x86)/Audiokinetic";
+/// WwiseConsole relative to a Wwise version folder.
+const CONSOLE_RELATIVE_PATH: [&str; 5] = ["Authoring", "x64", "Release", "bin", "WwiseConsole.exe"];
+/// Default target platform of conversion.
+pub const DEFAULT_PLATFORM: &str = "Windows";
+/// Target platforms of conversion, by their names in WwiseConsole.
+pub const PLATFORMS: [&str; 13] = [
+    "Windows",
+    "Mac",
+    "Linux",
+    "iOS",
+    "tvOS",
+    "Android",
+    "PS4",
+    "PS5",
+    "XboxOne",
+    "XboxOneGC",
+    "XboxSeriesX",
+    "Switch",
+    "Emscripten",
+];
+/// Default name of temp projects.
+pub const TEMP_PROJECT_NAME: &str = "SoundToolTemp";
+/// Default maximum number of temp projects of a platform used at the same time.
+pub const DEFAULT_MAX_TEMP_PROJECTS: usize = 4;
+/// Default time to wait for a free temp project or process.
+pub const DEFAULT_QUEUE_TIMEOUT: Duration = Duration::from_secs(600);
+/// Name of lock files limiting WwiseConsole processes, numbered by slot.
+const PROCESS_LOCK_NAME: &str = "WwiseConsole";
+/// Interval of checking for a free temp project while queued.
+const QUEUE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Age of source lists left by a crashed conversion, when they are removed.
+const STALE_SOURCE_LIST_AGE: Duration = Duration::from_secs(24 * 60 * 60);
 
 type Result<T> = std::result::Result<T, WwiseError>;
 
@@ -26,8 +73,23 @@ pub enum WwiseError {
     },
     #[error("Command execution failed: {0}")]
     CommandExecutionFailed(io::Error),
-    #[error("Assertion failed: {0}")]
-    Assertion(String),
+    #[error("Found WwiseConsole but failed to test: {}", .0.display())]
+    ConsoleTestFailed(PathBuf),
+    #[error("Project not exists after creation: {}", .0.display())]
+    ProjectNotCreated(PathBuf),
+    #[error("Unknown Wwise platform '{0}', expected one of {PLATFORMS:?}.")]
+    UnknownPlatform(String),
+    #[error("All {0} temp projects are in use by other conversions.")]
+    TempProjectsBusy(usize),
+    #[error("All {0} WwiseConsole processes are in use by other conversions.")]
+    ProcessesBusy(usize),
+    #[error("{0}")]
+    Lock(#[from] LockError),
+    #[error("Conversion failed: {code:?}\n{}", format_messages(messages))]
+    ConversionFailed {
+        code: Option<i32>,
+        messages: Vec<ConsoleMessage>,
+    },
 }
 
 impl WwiseError {
@@ -40,105 +102,282 @@ impl WwiseError {
     }
 }
 
+/// Scheduling priority of WwiseConsole processes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProcessPriority {
+    #[default]
+    Normal,
+    BelowNormal,
+    Idle,
+}
+
+impl ProcessPriority {
+    /// Niceness given to `nice` on Unix.
+    #[cfg(unix)]
+    fn niceness(self) -> Option<i32> {
+        match self {
+            ProcessPriority::Normal => None,
+            ProcessPriority::BelowNormal => Some(10),
+            ProcessPriority::Idle => Some(19),
+        }
+    }
+
+    /// Priority class creation flag on Windows.
+    #[cfg(windows)]
+    fn priority_class(self) -> u32 {
+        match self {
+            ProcessPriority::Normal => 0x0000_0020,
+            ProcessPriority::BelowNormal => 0x0000_4000,
+            ProcessPriority::Idle => 0x0000_0040,
+        }
+    }
+}
+
+/// Limits of WwiseConsole processes, keeping the machine responsive while
+/// several conversions run.
+#[derive(Debug, Clone, Default)]
+pub struct ConsoleLimits {
+    /// Maximum number of consoles converting at the same time, of all
+    /// platforms and tool instances. 0 for no limit besides temp projects.
+    pub max_processes: usize,
+    /// How long to wait for a free temp project or process slot before failing.
+    pub queue_timeout: Duration,
+    pub priority: ProcessPriority,
+    /// CPU cores the consoles may run on, all if empty.
+    pub affinity: Vec<usize>,
+}
+
 #[derive(Default)]
 pub struct WwiseConsole {
     console_path: PathBuf,
+    /// Wine program running the console off Windows.
+    wine: Option<PathBuf>,
+    limits: ConsoleLimits,
 }
 
 impl WwiseConsole {
     pub fn new() -> Result<Self> {
         if let Ok(root_path) = env::var("WWISEROOT") {
-            let root_path = PathBuf::from(root_path);
-            let console_path = root_path.join(r"Authoring\x64\Release\bin\WwiseConsole.exe");
+            let console_path = PathBuf::from(root_path).join(console_relative_path());
             if console_path.exists() {
-                if Self::test_console(&console_path) {
-                    return Ok(Self { console_path });
-                } else {
-                    return Err(WwiseError::Assertion(format!(
-                        "Found console but failed to test: {}",
-                        console_path.display()
-                    )));
-                }
+                return Self::new_with_path(console_path);
             }
         }
+        Self::find_in(Path::new(WWISE_BASE_DEFAULT_PATH), None)
+    }
 
-        // try to find in default path
-        let wwise_base_path = PathBuf::from(WWISE_BASE_DEFAULT_PATH);
-        if !wwise_base_path.exists() {
+    /// Search WwiseConsole installed in the Wine prefix, `WINEPREFIX` or `~/.wine`.
+    pub fn new_with_wine(wine: impl AsRef<Path>) -> Result<Self> {
+        let prefix = env::var_os("WINEPREFIX")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".wine")))
+            .ok_or(WwiseError::WwiseConsoleNotFound)?;
+        Self::find_in(&prefix.join(WINE_WWISE_BASE_PATH), Some(wine.as_ref()))
+    }
+
+    pub fn new_with_path(console_path: impl AsRef<Path>) -> Result<Self> {
+        Self::new_with_path_and_wine(console_path, None::<&Path>)
+    }
+
+    /// Console at the path, run by Wine if `wine` is set, e.g. `wine`.
+    pub fn new_with_path_and_wine(
+        console_path: impl AsRef<Path>,
+        wine: Option<impl AsRef<Path>>,
+    ) -> Result<Self> {
+        let console = Self {
+            console_path: console_path.as_ref().to_path_buf(),
+            wine: wine.map(|wine| wine.as_ref().to_path_buf()),
+            limits: ConsoleLimits::default(),
+        };
+        if !console.console_path.exists() {
             return Err(WwiseError::WwiseConsoleNotFound);
         }
+        if !console.test_console() {
+            return Err(WwiseError::ConsoleTestFailed(console.console_path));
+        }
 
-        let wwise_version_dirs = fs::read_dir(&wwise_base_path)?;
-        let mut console_path = None;
-        for entry in wwise_version_dirs {
-            let entry = entry?;
-            let path = entry.path();
+        Ok(console)
+    }
+
+    /// Search version folders of a Wwise install folder.
+    fn find_in(base_path: &Path, wine: Option<&Path>) -> Result<Self> {
+        if !base_path.exists() {
+            return Err(WwiseError::WwiseConsoleNotFound);
+        }
+        for entry in fs::read_dir(base_path)? {
+            let path = entry?.path();
             if !path.is_dir() {
                 continue;
             }
-            let path = path.join(r"Authoring\x64\Release\bin\WwiseConsole.exe");
-            if path.exists() {
-                console_path = Some(path);
-                break;
+            let console_path = path.join(console_relative_path());
+            if console_path.exists() {
+                return Self::new_with_path_and_wine(console_path, wine);
             }
         }
+        Err(WwiseError::WwiseConsoleNotFound)
+    }
 
-        if let Some(path) = console_path {
-            if Self::test_console(&path) {
-                Ok(Self { console_path: path })
-            } else {
-                Err(WwiseError::Assertion(format!(
-                    "Found console but failed to test: {}",
-                    path.display()
-                )))
-            }
-        } else {
-            Err(WwiseError::WwiseConsoleNotFound)
+    pub fn program_path(&self) -> &Path {
+        &self.console_path
+    }
+
+    /// Wine program the console is run by, `None` on Windows.
+    pub fn wine(&self) -> Option<&Path> {
+        self.wine.as_deref()
+    }
+
+    /// Run consoles with limits of concurrency, priority and CPU affinity.
+    pub fn with_limits(mut self, limits: ConsoleLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Command running the console, through Wine if set.
+    fn command(&self) -> Command {
+        let mut args: Vec<OsString> = vec![];
+        if let Some(wine) = &self.wine {
+            args.push(wine.into());
         }
+        args.push(self.console_path.clone().into());
+        limited_command(args, &self.limits)
     }
 
-    pub fn new_with_path(console_path: impl AsRef<Path>) -> Result<Self> {
-        let console_path = console_path.as_ref().to_path_buf();
-        if !console_path.exists() {
-            return Err(WwiseError::WwiseConsoleNotFound);
+    /// Run a console command and wait for its output.
+    fn run(&self, mut command: Command) -> io::Result<process::Output> {
+        let child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        #[cfg(windows)]
+        if !self.limits.affinity.is_empty() {
+            set_affinity(&child, &self.limits.affinity);
         }
-        if !Self::test_console(&console_path) {
-            return Err(WwiseError::Assertion(format!(
-                "Found console but failed to test: {}",
-                console_path.display()
-            )));
+        child.wait_with_output()
+    }
+
+    /// Path as seen by the console, with backslashes. Under Wine, absolute
+    /// paths of the host are on drive `Z:`, which Wine maps to `/` by default.
+    fn console_path_of(&self, path: &str) -> String {
+        let path = utils::strip_verbatim(&path.replace('/', "\\"));
+        if self.wine.is_some() && path.starts_with('\\') && !path.starts_with("\\\\") {
+            format!("Z:{}", path)
+        } else {
+            path
         }
+    }
 
-        Ok(Self { console_path })
+    /// Temp project targeting the platform, e.g. `Windows`, next to the exe.
+    ///
+    /// See [`Self::acquire_temp_project_in`].
+    pub fn acquire_temp_project(&self, platform: &str) -> Result<WwiseProject<'_>> {
+        let exe_path = env::current_exe()?;
+        self.acquire_temp_project_in(
+            exe_path.parent().unwrap(),
+            TEMP_PROJECT_NAME,
+            platform,
+            DEFAULT_MAX_TEMP_PROJECTS,
+        )
     }
 
-    pub fn program_path(&self) -> &Path {
-        &self.console_path
+    /// Temp project named `name` in `root`, targeting the platform.
+    ///
+    /// Each platform has its own projects, created on first use. The project
+    /// is locked until dropped, parallel conversions use numbered projects
+    /// like `name_2`, up to `max_projects`. Lock files left in the project
+    /// by a crashed WwiseConsole are removed.
+    ///
+    /// If all projects or process slots of the limits are in use, waits up
+    /// to the queue timeout for one to be released.
+    pub fn acquire_temp_project_in(
+        &self,
+        root: impl AsRef<Path>,
+        name: &str,
+        platform: &str,
+        max_projects: usize,
+    ) -> Result<WwiseProject<'_>> {
+        // the platform names projects and output folders
+        check_platform(platform)?;
+        let root = root.as_ref();
+        fs::create_dir_all(root)?;
+        let deadline = Instant::now() + self.limits.queue_timeout;
+        let mut queued = false;
+        loop {
+            match self.try_acquire_temp_project(root, name, platform, max_projects) {
+                Err(e @ (WwiseError::TempProjectsBusy(_) | WwiseError::ProcessesBusy(_)))
+                    if Instant::now() < deadline =>
+                {
+                    if !queued {
+                        info!("{} Queued until one is free.", e);
+                        queued = true;
+                    }
+                    thread::sleep(QUEUE_POLL_INTERVAL);
+                }
+                result => return result,
+            }
+        }
     }
 
-    pub fn acquire_temp_project(&self) -> Result<WwiseProject> {
-        const TEMP_PROJECT_NAME: &str = "SoundToolTemp";
+    fn try_acquire_temp_project(
+        &self,
+        root: &Path,
+        name: &str,
+        platform: &str,
+        max_projects: usize,
+    ) -> Result<WwiseProject<'_>> {
+        let process_lock = match self.limits.max_processes {
+            0 => None,
+            max_processes => Some(acquire_process_slot(root, max_processes)?),
+        };
+        let base_name = if platform == DEFAULT_PLATFORM {
+            name.to_string()
+        } else {
+            format!("{}_{}", name, platform)
+        };
+        let max_projects = max_projects.max(1);
+        for slot in 1..=max_projects {
+            let project_name = if slot == 1 {
+                base_name.clone()
+            } else {
+                format!("{}_{}", base_name, slot)
+            };
+            let lock = match ProjectLock::acquire_file(root.join(format!("{}.lock", project_name)))
+            {
+                Ok(lock) => lock,
+                Err(LockError::Locked { .. }) => continue,
+                Err(e) => return Err(e.into()),
+            };
 
-        let exe_path = env::current_exe()?;
-        let tool_dir = exe_path.parent().unwrap();
-        let proj_path = tool_dir
-            .join(TEMP_PROJECT_NAME)
-            .join(format!("{}.wproj", TEMP_PROJECT_NAME));
-        if proj_path.exists() {
-            let project = WwiseProject::new(self, proj_path);
+            let project_dir = root.join(&project_name);
+            let proj_path = project_dir.join(format!("{}.wproj", project_name));
+            let mut project = if proj_path.exists() {
+                remove_stale_locks(&project_dir)?;
+                WwiseProject::new(self, proj_path, platform)
+            } else {
+                // partially created by a crashed instance
+                if project_dir.exists() {
+                    warn!(
+                        "Removing incomplete temp project: {}",
+                        project_dir.display()
+                    );
+                    fs::remove_dir_all(&project_dir)?;
+                }
+                self.create_new_project(root, project_name, platform)?
+            };
+            project.lock = Some(lock);
+            project.process_lock = process_lock;
             return Ok(project);
         }
-
-        // not exist, try to create the project
-        let project = self.create_new_project(tool_dir, TEMP_PROJECT_NAME)?;
-        Ok(project)
+        Err(WwiseError::TempProjectsBusy(max_projects))
     }
 
     pub fn create_new_project(
         &self,
         root_path: impl AsRef<Path>,
         project_name: impl AsRef<str>,
-    ) -> Result<WwiseProject> {
+        platform: &str,
+    ) -> Result<WwiseProject<'_>> {
         let root_path = root_path.as_ref();
         let project_name = project_name.as_ref();
         if !root_path.exists() {
@@ -152,14 +391,15 @@ impl WwiseConsole {
             return Err(WwiseError::ProjectAlreadyExists(project_path));
         }
 
-        let result = Command::new(&self.console_path)
-            .args([
-                "create-new-project",
-                project_path.to_str().unwrap(),
-                "--platform",
-                "Windows",
-            ])
-            .output()
+        let mut command = self.command();
+        command.args([
+            "create-new-project",
+            &self.console_path_of(project_path.to_str().unwrap()),
+            "--platform",
+            platform,
+        ]);
+        let result = self
+            .run(command)
             .map_err(WwiseError::CommandExecutionFailed)?;
         if !result.status.success() {
             return Err(WwiseError::command_failed(
@@ -171,20 +411,16 @@ impl WwiseConsole {
 
         // check if the project exists
         if !project_path.exists() {
-            return Err(WwiseError::Assertion(format!(
-                "Project not exists after creation: {}",
-                project_path.display()
-            )));
+            return Err(WwiseError::ProjectNotCreated(project_path));
         }
-        Ok(WwiseProject::new(self, project_path))
+        Ok(WwiseProject::new(self, project_path, platform))
     }
 
     /// Test if the console can be executed.
-    fn test_console(console_path: impl AsRef<Path>) -> bool {
-        let result = Command::new(console_path.as_ref())
-            .args(["create-new-project", "--help"])
-            .output();
-        let Ok(result) = result else {
+    fn test_console(&self) -> bool {
+        let mut command = self.command();
+        command.args(["create-new-project", "--help"]);
+        let Ok(result) = self.run(command) else {
             return false;
         };
 
@@ -192,88 +428,505 @@ impl WwiseConsole {
     }
 }
 
+/// Check a target platform is one of [`PLATFORMS`].
+pub fn check_platform(platform: &str) -> Result<()> {
+    if PLATFORMS.contains(&platform) {
+        Ok(())
+    } else {
+        Err(WwiseError::UnknownPlatform(platform.to_string()))
+    }
+}
+
+fn console_relative_path() -> PathBuf {
+    CONSOLE_RELATIVE_PATH.iter().collect()
+}
+
+/// Lock one of `max_processes` process slots in `root`.
+fn acquire_process_slot(root: &Path, max_processes: usize) -> Result<ProjectLock> {
+    for slot in 1..=max_processes {
+        let path = root.join(format!("{}_{}.lock", PROCESS_LOCK_NAME, slot));
+        match ProjectLock::acquire_file(path) {
+            Ok(lock) => return Ok(lock),
+            Err(LockError::Locked { .. }) => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(WwiseError::ProcessesBusy(max_processes))
+}
+
+/// Command of program and arguments `args`, run by `nice` and `taskset`
+/// for the priority and CPU affinity of limits. Without `taskset`, the
+/// console runs on all cores.
+#[cfg(unix)]
+fn limited_command(mut args: Vec<OsString>, limits: &ConsoleLimits) -> Command {
+    if !limits.affinity.is_empty() {
+        if cfg!(target_os = "linux") && !in_path("taskset") {
+            warn!("taskset not found, WwiseConsole runs on all CPU cores.");
+        } else if cfg!(target_os = "linux") {
+            let cores: Vec<String> = limits
+                .affinity
+                .iter()
+                .map(|core| core.to_string())
+                .collect();
+            args.splice(
+                0..0,
+                ["taskset".into(), "-c".into(), cores.join(",").into()],
+            );
+        } else {
+            warn!("CPU affinity of WwiseConsole is only supported on Linux and Windows.");
+        }
+    }
+    if let Some(niceness) = limits.priority.niceness() {
+        args.splice(
+            0..0,
+            ["nice".into(), "-n".into(), niceness.to_string().into()],
+        );
+    }
+    let mut command = Command::new(&args[0]);
+    command.args(&args[1..]);
+    command
+}
+
+/// Command of program and arguments `args`, created in the priority class of
+/// limits. CPU affinity is set right after spawning, see [`set_affinity`].
+#[cfg(windows)]
+fn limited_command(args: Vec<OsString>, limits: &ConsoleLimits) -> Command {
+    use std::os::windows::process::CommandExt;
+
+    let mut command = Command::new(&args[0]);
+    command.args(&args[1..]);
+    command.creation_flags(limits.priority.priority_class());
+    command
+}
+
+#[cfg(not(any(unix, windows)))]
+fn limited_command(args: Vec<OsString>, _limits: &ConsoleLimits) -> Command {
+    let mut command = Command::new(&args[0]);
+    command.args(&args[1..]);
+    command
+}
+
+/// Set CPU affinity of a spawned process, the console keeps running on all
+/// cores if it fails.
+#[cfg(windows)]
+fn set_affinity(child: &process::Child, cores: &[usize]) {
+    use std::{ffi::c_void, os::windows::io::AsRawHandle};
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn SetProcessAffinityMask(process: *mut c_void, mask: usize) -> i32;
+    }
+
+    let mask = cores
+        .iter()
+        .filter(|core| **core < usize::BITS as usize)
+        .fold(0usize, |mask, core| mask | 1 << core);
+    // SAFETY: the process handle is owned by `child`, which outlives the call
+    if mask == 0 || unsafe { SetProcessAffinityMask(child.as_raw_handle(), mask) } == 0 {
+        warn!(
+            "Failed to set CPU affinity of WwiseConsole process {}.",
+            child.id()
+        );
+    }
+}
+
+/// Whether a program is found in `PATH`.
+#[cfg(unix)]
+fn in_path(program: &str) -> bool {
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+/// Remove lock files in a temp project, only called while holding its lock,
+/// so they are left by a crashed WwiseConsole.
+fn remove_stale_locks(project_dir: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(project_dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "lock") {
+            warn!("Removing stale Wwise lock: {}", path.display());
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a source list of its own for a conversion in `dir`, which is shared
+/// by all temp projects. Lists left by crashed conversions are removed.
+fn write_source_list(dir: &Path, xml: &str) -> io::Result<tempfile::TempPath> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let stale = path.extension().is_some_and(|ext| ext == "wsource")
+            && fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| {
+                    modified
+                        .elapsed()
+                        .is_ok_and(|age| age > STALE_SOURCE_LIST_AGE)
+                });
+        if stale && let Err(e) = fs::remove_file(&path) {
+            warn!(
+                "Failed to remove stale source list {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+    let mut file = tempfile::Builder::new()
+        .prefix("list_")
+        .suffix(".wsource")
+        .tempfile_in(dir)?;
+    file.write_all(xml.as_bytes())?;
+    // closed, so WwiseConsole can read it on any platform
+    Ok(file.into_temp_path())
+}
+
 pub struct WwiseProject<'a> {
     console: &'a WwiseConsole,
     project_path: PathBuf,
+    platform: String,
+    /// Lock of a temp project, released on drop.
+    lock: Option<ProjectLock>,
+    /// Process slot of [`ConsoleLimits::max_processes`], released on drop.
+    process_lock: Option<ProjectLock>,
 }
 
 impl<'a> WwiseProject<'a> {
-    fn new(console: &'a WwiseConsole, project_path: PathBuf) -> Self {
+    fn new(console: &'a WwiseConsole, project_path: PathBuf, platform: &str) -> Self {
         Self {
             console,
             project_path,
+            platform: platform.to_string(),
+            lock: None,
+            process_lock: None,
         }
     }
 
+    /// Target platform of conversion, outputs are written to
+    /// a subfolder of the same name.
+    pub fn platform(&self) -> &str {
+        &self.platform
+    }
+
     #[allow(dead_code)]
     pub fn project_path(&self) -> &Path {
         &self.project_path
     }
 
+    /// Convert external sources, returns warnings reported by WwiseConsole.
     pub fn convert_external_source(
         &self,
         wsource: &WwiseSource,
         output_dir: impl AsRef<str>,
-    ) -> Result<()> {
-        let xml = wsource.to_xml();
-        // write to temp file
-        let source_file_name = "list.wsource";
-        let source_file_path = self.project_path.parent().unwrap().join(source_file_name);
-        {
-            let mut file = fs::File::create(&source_file_path)?;
-            file.write_all(xml.as_bytes())?;
-        }
-
-        let output_path = output_dir.as_ref().replace("/", "\\").replace(r"\\?\", "");
-        let result = Command::new(&self.console.console_path)
-            .args([
-                "convert-external-source",
-                self.project_path.to_str().unwrap(),
-                "--source-file",
-                source_file_path.to_str().unwrap(),
-                "--output",
-                &output_path,
-            ])
-            .output()
+    ) -> Result<Vec<ConsoleMessage>> {
+        let xml = wsource.to_xml(|path| self.console.console_path_of(path));
+        // deleted when the conversion is done
+        let source_file_path = write_source_list(self.project_path.parent().unwrap(), &xml)?;
+
+        let output_path = self.console.console_path_of(output_dir.as_ref());
+        let mut command = self.console.command();
+        command.args([
+            "convert-external-source",
+            &self
+                .console
+                .console_path_of(self.project_path.to_str().unwrap()),
+            "--source-file",
+            &self
+                .console
+                .console_path_of(source_file_path.to_str().unwrap()),
+            "--output",
+            &output_path,
+            "--platform",
+            &self.platform,
+        ]);
+        let result = self
+            .console
+            .run(command)
             .map_err(WwiseError::CommandExecutionFailed)?;
+        let output = format!(
+            "{}\n{}",
+            String::from_utf8_lossy(&result.stdout),
+            String::from_utf8_lossy(&result.stderr)
+        );
+        let messages = ConsoleMessage::parse_output(&output, &wsource.sources);
         if !result.status.success() {
-            return Err(WwiseError::command_failed(
-                result.status.code(),
-                &result.stdout,
-                &result.stderr,
-            ));
+            if messages.is_empty() {
+                return Err(WwiseError::command_failed(
+                    result.status.code(),
+                    &result.stdout,
+                    &result.stderr,
+                ));
+            }
+            return Err(WwiseError::ConversionFailed {
+                code: result.status.code(),
+                messages,
+            });
         }
 
-        // TODO: check if the converted source exists
-        Ok(())
+        Ok(messages)
+    }
+}
+
+/// Codec of converted wem, selects a factory conversion ShareSet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Conversion {
+    #[default]
+    Vorbis,
+    Adpcm,
+    Pcm,
+    Opus,
+}
+
+impl Conversion {
+    /// Name of the conversion ShareSet in Wwise project.
+    pub fn share_set(self) -> &'static str {
+        match self {
+            Conversion::Vorbis => "Vorbis Quality High",
+            Conversion::Adpcm => "ADPCM As Input",
+            Conversion::Pcm => "PCM As Input",
+            Conversion::Opus => "Opus Quality High",
+        }
+    }
+
+    /// Detect codec from the format tag in `fmt ` chunk of wem data.
+    pub fn detect(wem: &[u8]) -> Option<Self> {
+        Self::from_format_tag(format_tag(wem)?)
+    }
+
+    fn from_format_tag(tag: u16) -> Option<Self> {
+        match tag {
+            0xFFFF => Some(Conversion::Vorbis),
+            0x0002 => Some(Conversion::Adpcm),
+            0x0001 | 0xFFFE => Some(Conversion::Pcm),
+            0x3040 | 0x3041 => Some(Conversion::Opus),
+            _ => None,
+        }
+    }
+}
+
+/// Format tag in `fmt ` chunk of little endian wem data.
+fn format_tag(wem: &[u8]) -> Option<u16> {
+    let tag = fmt_chunk(wem)?.get(0..2)?;
+    Some(u16::from_le_bytes([tag[0], tag[1]]))
+}
+
+/// Content of `fmt ` chunk of little endian wem data, cut at end of data.
+fn fmt_chunk(wem: &[u8]) -> Option<&[u8]> {
+    find_chunk(wem, b"fmt ")
+}
+
+/// Content of a chunk of little endian wem data, cut at end of data.
+fn find_chunk<'a>(wem: &'a [u8], id: &[u8; 4]) -> Option<&'a [u8]> {
+    if wem.get(0..4)? != b"RIFF" || wem.get(8..12)? != b"WAVE" {
+        return None;
+    }
+    let mut pos = 12;
+    while let Some(header) = wem.get(pos..pos + 8) {
+        let size = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+        if &header[0..4] == id {
+            return wem.get(pos + 8..(pos + 8 + size).min(wem.len()));
+        }
+        // chunks are word aligned
+        pos += 8 + size + (size & 1);
+    }
+    None
+}
+
+/// Codec and stream parameters of a wem, from its `fmt ` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WemFormat {
+    pub codec: Conversion,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Average bitrate in kbps, from file size and duration.
+    pub bitrate: u32,
+}
+
+impl WemFormat {
+    /// Format of little endian wem data, `None` for unknown codecs.
+    pub fn detect(wem: &[u8]) -> Option<Self> {
+        let codec = Conversion::detect(wem)?;
+        let fmt = fmt_chunk(wem)?.get(0..8)?;
+        let bitrate = wem_duration(wem)
+            .filter(|duration| *duration > 0.0)
+            .map_or(0, |duration| {
+                (wem.len() as f32 * 8.0 / duration / 1000.0).round() as u32
+            });
+        Some(Self {
+            codec,
+            sample_rate: u32::from_le_bytes(fmt[4..8].try_into().unwrap()),
+            channels: u16::from_le_bytes([fmt[2], fmt[3]]),
+            bitrate,
+        })
+    }
+}
+
+/// Size of `fmt ` chunk of Vorbis wems in the current layout, older Wwise
+/// versions use smaller chunks or a separate `vorb` chunk.
+const VORBIS_FMT_SIZE: usize = 0x42;
+
+/// Whether wem data is from another game or platform and uses a codec the
+/// game cannot play, e.g. big endian `RIFX`, XMA or Vorbis of an older
+/// layout. Such wems must be decoded and converted again instead of being
+/// packed as is.
+pub fn is_foreign_wem(wem: &[u8]) -> bool {
+    match wem.get(0..4) {
+        Some(b"RIFX") => true,
+        Some(b"RIFF") => match format_tag(wem).map(Conversion::from_format_tag) {
+            Some(None) => true,
+            Some(Some(Conversion::Vorbis)) => {
+                fmt_chunk(wem).is_some_and(|fmt| fmt.len() != VORBIS_FMT_SIZE)
+                    || find_chunk(wem, b"vorb").is_some()
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Decode a PCM wem of either byte order to wav data in-process.
+///
+/// `None` for other codecs or broken data, which are left to vgmstream.
+pub fn pcm_wem_to_wav(wem: &[u8]) -> Option<Vec<u8>> {
+    let endian = match wem.get(0..4)? {
+        b"RIFF" => Endian::Little,
+        b"RIFX" => Endian::Big,
+        _ => return None,
+    };
+    if wem.get(8..12)? != b"WAVE" {
+        return None;
+    }
+    let read_u16 = |bytes: &[u8]| endian.read_u16(&mut &bytes[..2]).unwrap();
+    let read_u32 = |bytes: &[u8]| endian.read_u32(&mut &bytes[..4]).unwrap();
+    let mut fmt = None;
+    let mut data = None;
+    let mut pos = 12;
+    while let Some(header) = wem.get(pos..pos + 8) {
+        let size = read_u32(&header[4..8]) as usize;
+        let body = wem.get(pos + 8..(pos + 8 + size).min(wem.len()));
+        match &header[0..4] {
+            b"fmt " => fmt = body,
+            b"data" => data = body,
+            _ => {}
+        }
+        pos += 8 + size + (size & 1);
+    }
+    let fmt = fmt?.get(0..16)?;
+    if !matches!(read_u16(&fmt[0..2]), 0x0001 | 0xFFFE) {
+        return None;
+    }
+    let channels = read_u16(&fmt[2..4]);
+    let sample_rate = read_u32(&fmt[4..8]);
+    let bits = read_u16(&fmt[14..16]);
+    if channels == 0 || !matches!(bits, 8 | 16 | 24 | 32) {
+        return None;
+    }
+    let sample_size = bits as usize / 8;
+    let block_align = channels as usize * sample_size;
+    let data = data?;
+    let data = &data[..data.len() - data.len() % block_align];
+
+    let mut wav = Vec::with_capacity(44 + data.len());
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    wav.extend_from_slice(&(block_align as u16).to_le_bytes());
+    wav.extend_from_slice(&bits.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    match endian {
+        Endian::Little => wav.extend_from_slice(data),
+        Endian::Big => {
+            for sample in data.chunks_exact(sample_size) {
+                wav.extend(sample.iter().rev());
+            }
+        }
+    }
+    Some(wav)
+}
+
+/// Duration of wem data in seconds, from its `fmt ` chunk.
+///
+/// Vorbis and Opus wems store the sample count in the `fmt ` extension,
+/// other codecs are estimated from `data` size and byte rate.
+pub fn wem_duration(wem: &[u8]) -> Option<f32> {
+    if wem.get(0..4)? != b"RIFF" || wem.get(8..12)? != b"WAVE" {
+        return None;
+    }
+    let mut fmt = None;
+    let mut data_size = None;
+    let mut pos = 12;
+    while let Some(header) = wem.get(pos..pos + 8) {
+        let size = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+        match &header[0..4] {
+            b"fmt " => fmt = wem.get(pos + 8..(pos + 8 + size).min(wem.len())),
+            b"data" => data_size = Some(size),
+            _ => {}
+        }
+        pos += 8 + size + (size & 1);
+    }
+    let fmt = fmt.filter(|fmt| fmt.len() >= 16)?;
+    let u32_at = |offset: usize| {
+        fmt.get(offset..offset + 4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    let tag = u16::from_le_bytes([fmt[0], fmt[1]]);
+    let sample_rate = u32_at(4)?;
+    let byte_rate = u32_at(8)?;
+    match tag {
+        0xFFFF | 0x3040 | 0x3041 if fmt.len() >= 0x1C && sample_rate > 0 => {
+            Some(u32_at(0x18)? as f32 / sample_rate as f32)
+        }
+        _ if byte_rate > 0 => Some(data_size? as f32 / byte_rate as f32),
+        _ => None,
+    }
+}
+
+impl std::fmt::Display for Conversion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.share_set())
     }
 }
 
 pub struct WwiseSource {
     root: String,
     sources: Vec<String>,
+    conversions: Vec<Conversion>,
 }
 
 impl WwiseSource {
     pub fn new(root: impl AsRef<str>) -> Self {
-        let root = root.as_ref().replace("/", "\\").replace(r"\\?\", "");
+        let root = utils::strip_verbatim(&root.as_ref().replace('/', "\\"));
         Self {
             root,
             sources: vec![],
+            conversions: vec![],
         }
     }
 
-    pub fn add_source(&mut self, source: impl AsRef<str>) {
-        let source = source.as_ref().replace("/", "\\").replace(r"\\?\", "");
+    pub fn add_source_with_conversion(&mut self, source: impl AsRef<str>, conversion: Conversion) {
+        let source = utils::strip_verbatim(&source.as_ref().replace('/', "\\"));
         self.sources.push(source);
+        self.conversions.push(conversion);
     }
 
-    fn to_xml(&self) -> String {
+    pub fn sources(&self) -> &[String] {
+        &self.sources
+    }
+
+    /// Source list file, with paths mapped to paths seen by the console.
+    fn to_xml(&self, console_path: impl Fn(&str) -> String) -> String {
         let mut sources = String::new();
-        for source in self.sources.iter() {
+        for (source, conversion) in self.sources.iter().zip(&self.conversions) {
             sources += &format!(
-                "    <Source Path=\"{}\" Conversion=\"Vorbis Quality High\"/>\n",
-                source
+                "    <Source Path=\"{}\" Conversion=\"{}\"/>\n",
+                console_path(source),
+                conversion.share_set()
             );
         }
         format!(
@@ -281,16 +934,406 @@ impl WwiseSource {
 <ExternalSourcesList SchemaVersion="1" Root="{root}">
 {sources}
 </ExternalSourcesList>"#,
-            root = self.root,
+            root = console_path(&self.root),
             sources = sources
         )
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageLevel {
+    Warning,
+    Error,
+}
+
+/// Warning or error message in WwiseConsole output.
+#[derive(Debug, Clone)]
+pub struct ConsoleMessage {
+    pub level: MessageLevel,
+    /// Source file the message refers to, as added to [`WwiseSource`].
+    pub source: Option<String>,
+    pub text: String,
+}
+
+impl ConsoleMessage {
+    /// Parse warnings and errors from console output, and associate them
+    /// with the source files mentioned.
+    pub fn parse_output(output: &str, sources: &[String]) -> Vec<Self> {
+        let mut messages = vec![];
+        for line in output.lines() {
+            let text = line.trim();
+            let lower = text.to_lowercase();
+            let level = if ["error", "fatal error"]
+                .iter()
+                .any(|prefix| starts_with_word(&lower, prefix))
+            {
+                MessageLevel::Error
+            } else if starts_with_word(&lower, "warning") {
+                MessageLevel::Warning
+            } else {
+                continue;
+            };
+            messages.push(ConsoleMessage {
+                level,
+                source: find_source(&lower, sources),
+                text: text.to_string(),
+            });
+        }
+        messages
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.level == MessageLevel::Error
+    }
+}
+
+impl std::fmt::Display for ConsoleMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.source {
+            Some(source) => write!(f, "file {}: {}", file_name(source), self.text),
+            None => write!(f, "{}", self.text),
+        }
+    }
+}
+
+fn starts_with_word(line: &str, word: &str) -> bool {
+    line.strip_prefix(word)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with([':', ' ', '\t', '[']))
+}
+
+/// Find the source mentioned in a lowercase line, full path preferred over file name.
+///
+/// The longest match wins, so `name.wav` does not shadow `long_name.wav`.
+fn find_source(line: &str, sources: &[String]) -> Option<String> {
+    let line = line.replace('/', "\\");
+    let longest_match = |key: fn(&str) -> &str| {
+        sources
+            .iter()
+            .filter(|source| line.contains(&key(source).to_lowercase()))
+            .max_by_key(|source| key(source).len())
+    };
+    longest_match(|source| source)
+        .or_else(|| longest_match(file_name))
+        .cloned()
+}
+
+fn file_name(source: &str) -> &str {
+    source.rsplit(['\\', '/']).next().unwrap_or(source)
+}
+
+fn format_messages(messages: &[ConsoleMessage]) -> String {
+    messages
+        .iter()
+        .map(|message| message.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_console_messages() {
+        let sources = vec![
+            r"C:\sounds\name.wav".to_string(),
+            r"C:\sounds\a_long_name.wav".to_string(),
+        ];
+        let output = "\
+Converting external sources...
+Error: C:/sounds/name.wav: Sample rate 1000 Hz is not supported.
+Warning: a_long_name.wav: Source is clipping.
+Error [General]: License not found.
+Errors: 2
+";
+        let messages = ConsoleMessage::parse_output(output, &sources);
+        assert_eq!(messages.len(), 3);
+        assert!(messages[0].is_error());
+        assert_eq!(messages[0].source.as_deref(), Some(sources[0].as_str()));
+        assert_eq!(messages[1].level, MessageLevel::Warning);
+        assert_eq!(messages[1].source.as_deref(), Some(sources[1].as_str()));
+        assert!(
+            messages[1]
+                .to_string()
+                .starts_with("file a_long_name.wav: ")
+        );
+        assert_eq!(messages[2].source, None);
+    }
+
+    #[test]
+    fn test_detect_conversion() {
+        let wem = |tag: u16| {
+            let mut data = b"RIFF\0\0\0\0WAVEJUNK\x03\0\0\0abc\0fmt \x18\0\0\0".to_vec();
+            data.extend_from_slice(&tag.to_le_bytes());
+            data.extend_from_slice(&[0; 22]);
+            data
+        };
+        assert_eq!(Conversion::detect(&wem(0xFFFF)), Some(Conversion::Vorbis));
+        assert_eq!(Conversion::detect(&wem(0x0002)), Some(Conversion::Adpcm));
+        assert_eq!(Conversion::detect(&wem(0xFFFE)), Some(Conversion::Pcm));
+        assert_eq!(Conversion::detect(&wem(0x3041)), Some(Conversion::Opus));
+        assert_eq!(Conversion::detect(&wem(0x1234)), None);
+        assert_eq!(Conversion::detect(b"RIFF"), None);
+        // Vorbis of the current layout, older ones have smaller `fmt `
+        // chunks or a `vorb` chunk
+        let vorbis = |fmt_size: u8, vorb: bool| {
+            let mut data = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+            data.extend_from_slice(&[fmt_size, 0, 0, 0, 0xFF, 0xFF]);
+            data.extend_from_slice(&vec![0; fmt_size as usize - 2]);
+            if vorb {
+                data.extend_from_slice(b"vorb\x2a\0\0\0");
+                data.extend_from_slice(&[0; 0x2a]);
+            }
+            data
+        };
+        assert!(!is_foreign_wem(&vorbis(0x42, false)));
+        assert!(is_foreign_wem(&vorbis(0x28, false)));
+        assert!(is_foreign_wem(&vorbis(0x18, true)));
+        assert!(!is_foreign_wem(&wem(0x0002)));
+        // XMA2
+        assert!(is_foreign_wem(&wem(0x0166)));
+        assert!(is_foreign_wem(b"RIFX\0\0\0\0WAVE"));
+        // unknown without `fmt `
+        assert!(!is_foreign_wem(b"RIFF\0\0\0\0WAVE"));
+
+        // 48000 Hz, 96000 bytes/s
+        let timed_wem = |tag: u16, extension: &[u8], data_size: u32| {
+            let mut fmt = tag.to_le_bytes().to_vec();
+            fmt.extend_from_slice(&1u16.to_le_bytes());
+            fmt.extend_from_slice(&48000u32.to_le_bytes());
+            fmt.extend_from_slice(&96000u32.to_le_bytes());
+            fmt.extend_from_slice(&[2, 0, 16, 0]);
+            fmt.extend_from_slice(extension);
+            let mut data = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+            data.extend_from_slice(&(fmt.len() as u32).to_le_bytes());
+            data.extend_from_slice(&fmt);
+            data.extend_from_slice(b"data");
+            data.extend_from_slice(&data_size.to_le_bytes());
+            data
+        };
+        // sample count at 0x18 of vorbis extension
+        let mut extension = vec![0; 8];
+        extension.extend_from_slice(&72000u32.to_le_bytes());
+        assert_eq!(wem_duration(&timed_wem(0xFFFF, &extension, 100)), Some(1.5));
+        assert_eq!(wem_duration(&timed_wem(0x0001, &[], 96000)), Some(1.0));
+        assert_eq!(wem_duration(&wem(0x0002)), None);
+        assert_eq!(
+            WemFormat::detect(&timed_wem(0xFFFF, &extension, 100)),
+            Some(WemFormat {
+                codec: Conversion::Vorbis,
+                sample_rate: 48000,
+                channels: 1,
+                // 56 bytes in 1.5 s
+                bitrate: 0,
+            })
+        );
+        assert_eq!(WemFormat::detect(&wem(0x1234)), None);
+
+        let mut source = WwiseSource::new("C:/sounds");
+        source.add_source_with_conversion("a.wav", Conversion::default());
+        source.add_source_with_conversion("b.wav", Conversion::Adpcm);
+        let xml = source.to_xml(|path| path.to_string());
+        assert!(xml.contains(r#"Path="a.wav" Conversion="Vorbis Quality High""#));
+        assert!(xml.contains(r#"Path="b.wav" Conversion="ADPCM As Input""#));
+    }
+
+    #[test]
+    fn test_pcm_wem_to_wav() {
+        // 16 bit stereo at 48000 Hz, two frames
+        let pcm_wem = |magic: &[u8; 4], to_bytes: fn(u32) -> [u8; 4], samples: &[u8]| {
+            let mut data = magic.to_vec();
+            data.extend_from_slice(&to_bytes(0));
+            data.extend_from_slice(b"WAVEfmt ");
+            data.extend_from_slice(&to_bytes(0x18));
+            let mut fmt = [0u8; 0x18];
+            let [tag, channels, block_align, bits] = [0xFFFEu16, 2, 4, 16].map(|value| {
+                let bytes = to_bytes(value as u32);
+                if magic == b"RIFX" {
+                    [bytes[2], bytes[3]]
+                } else {
+                    [bytes[0], bytes[1]]
+                }
+            });
+            fmt[0..2].copy_from_slice(&tag);
+            fmt[2..4].copy_from_slice(&channels);
+            fmt[4..8].copy_from_slice(&to_bytes(48000));
+            fmt[12..14].copy_from_slice(&block_align);
+            fmt[14..16].copy_from_slice(&bits);
+            data.extend_from_slice(&fmt);
+            data.extend_from_slice(b"data");
+            data.extend_from_slice(&to_bytes(samples.len() as u32));
+            data.extend_from_slice(samples);
+            data
+        };
+        let big = pcm_wem(b"RIFX", u32::to_be_bytes, &[0, 1, 0, 2, 0xFF, 0xFF, 0, 4]);
+        assert!(is_foreign_wem(&big));
+        let wav = pcm_wem_to_wav(&big).unwrap();
+        let mut reader = hound::WavReader::new(io::Cursor::new(&wav)).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        assert_eq!(reader.spec().sample_rate, 48000);
+        let samples: Vec<i16> = reader.samples().map(|sample| sample.unwrap()).collect();
+        assert_eq!(samples, [1, 2, -1, 4]);
+
+        let little = pcm_wem(
+            b"RIFF",
+            u32::to_le_bytes,
+            &[1, 0, 2, 0, 0xFF, 0xFF, 4, 0, 9],
+        );
+        assert_eq!(pcm_wem_to_wav(&little).unwrap(), wav);
+
+        let mut xma = big.clone();
+        xma[20..22].copy_from_slice(&0x0166u16.to_be_bytes());
+        assert_eq!(pcm_wem_to_wav(&xma), None);
+        assert_eq!(pcm_wem_to_wav(b"RIFX\0\0\0\0WAVE"), None);
+    }
+
+    #[test]
+    fn test_console_path_under_wine() {
+        let console = WwiseConsole {
+            console_path: PathBuf::from("WwiseConsole.exe"),
+            wine: Some(PathBuf::from("wine")),
+            ..Default::default()
+        };
+        assert_eq!(
+            console.console_path_of("/home/user/SoundToolTemp/a.wav"),
+            r"Z:\home\user\SoundToolTemp\a.wav"
+        );
+        assert_eq!(console.console_path_of(r"C:\a.wav"), r"C:\a.wav");
+        let mut source = WwiseSource::new("/tmp/src");
+        source.add_source_with_conversion("/tmp/src/a.wav", Conversion::default());
+        let xml = source.to_xml(|path| console.console_path_of(path));
+        assert!(xml.contains(r#"Root="Z:\tmp\src""#));
+        assert!(xml.contains(r#"Path="Z:\tmp\src\a.wav""#));
+
+        let native = WwiseConsole::default();
+        assert_eq!(native.console_path_of("C:/a.wav"), r"C:\a.wav");
+    }
+
+    #[test]
+    fn test_temp_project_slots() {
+        let console = WwiseConsole::default();
+        let root = tempfile::tempdir().unwrap();
+        for name in ["Temp", "Temp_2"] {
+            let project_dir = root.path().join(name);
+            fs::create_dir_all(&project_dir).unwrap();
+            fs::write(project_dir.join(format!("{}.wproj", name)), "").unwrap();
+        }
+        let stale_lock = root.path().join("Temp").join("Temp.wproj.lock");
+        fs::write(&stale_lock, "").unwrap();
+
+        let first = console
+            .acquire_temp_project_in(root.path(), "Temp", DEFAULT_PLATFORM, 2)
+            .unwrap();
+        assert!(first.project_path().ends_with("Temp/Temp.wproj"));
+        assert!(!stale_lock.exists());
+        let second = console
+            .acquire_temp_project_in(root.path(), "Temp", DEFAULT_PLATFORM, 2)
+            .unwrap();
+        assert!(second.project_path().ends_with("Temp_2/Temp_2.wproj"));
+        let busy = console.acquire_temp_project_in(root.path(), "Temp", DEFAULT_PLATFORM, 2);
+        assert!(matches!(busy, Err(WwiseError::TempProjectsBusy(2))));
+
+        drop(first);
+        let third = console
+            .acquire_temp_project_in(root.path(), "Temp", DEFAULT_PLATFORM, 2)
+            .unwrap();
+        assert!(third.project_path().ends_with("Temp/Temp.wproj"));
+        drop((second, third));
+        // platforms name project folders
+        assert!(matches!(
+            console.acquire_temp_project_in(root.path(), "Temp", "../x", 2),
+            Err(WwiseError::UnknownPlatform(platform)) if platform == "../x"
+        ));
+        assert!(!root.path().join("Temp_..").exists());
+
+        // one process at a time, queued until released
+        let console = WwiseConsole::default().with_limits(ConsoleLimits {
+            max_processes: 1,
+            queue_timeout: Duration::from_secs(10),
+            ..Default::default()
+        });
+        let first = console
+            .acquire_temp_project_in(root.path(), "Temp", DEFAULT_PLATFORM, 2)
+            .unwrap();
+        let queued = thread::scope(|scope| {
+            let queued = scope.spawn(|| {
+                console.acquire_temp_project_in(root.path(), "Temp", DEFAULT_PLATFORM, 2)
+            });
+            thread::sleep(QUEUE_POLL_INTERVAL);
+            drop(first);
+            queued.join().unwrap().unwrap().project_path().to_path_buf()
+        });
+        assert!(queued.ends_with("Temp/Temp.wproj"));
+    }
+
+    #[test]
+    fn test_concurrent_source_lists() {
+        let dir = tempfile::tempdir().unwrap();
+        let stale = dir.path().join("list.wsource");
+        fs::write(&stale, "").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&stale)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() - STALE_SOURCE_LIST_AGE * 2)
+            .unwrap();
+        let recent = dir.path().join("list_recent.wsource");
+        fs::write(&recent, "").unwrap();
+
+        let dir_path = dir.path();
+        let lists: Vec<(tempfile::TempPath, String)> = thread::scope(|scope| {
+            let writers: Vec<_> = (0..8)
+                .map(|i| {
+                    scope.spawn(move || {
+                        let xml = format!("<Source Path=\"{}.wav\"/>", i);
+                        let path = write_source_list(dir_path, &xml).unwrap();
+                        (path, xml)
+                    })
+                })
+                .collect();
+            writers.into_iter().map(|w| w.join().unwrap()).collect()
+        });
+        // each conversion reads its own list
+        for (path, xml) in &lists {
+            assert_eq!(fs::read_to_string(path).unwrap(), *xml);
+        }
+        let paths: Vec<PathBuf> = lists.iter().map(|(path, _)| path.to_path_buf()).collect();
+        assert!(!stale.exists());
+        assert!(recent.exists());
+
+        drop(lists);
+        assert!(paths.iter().all(|path| !path.exists()));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_limited_command() {
+        let console = WwiseConsole {
+            console_path: PathBuf::from("WwiseConsole.exe"),
+            wine: Some(PathBuf::from("wine")),
+            limits: ConsoleLimits {
+                priority: ProcessPriority::Idle,
+                affinity: vec![0, 2],
+                ..Default::default()
+            },
+        };
+        let command = console.command();
+        assert_eq!(command.get_program(), "nice");
+        let args: Vec<_> = command.get_args().collect();
+        // without taskset the console still runs, on all cores
+        let mut expected = vec!["-n", "19"];
+        if in_path("taskset") {
+            expected.extend(["taskset", "-c", "0,2"]);
+        }
+        expected.extend(["wine", "WwiseConsole.exe"]);
+        assert_eq!(args, expected);
+        assert!(in_path("sh"));
+        assert!(!in_path("no-such-program"));
+
+        let command = WwiseConsole::default().command();
+        assert_eq!(command.get_args().count(), 0);
+    }
+
     #[test]
     fn test_console() {
         let _console = WwiseConsole::new().unwrap();
@@ -299,7 +1342,7 @@ mod tests {
     #[test]
     fn test_acquire_temp_project() {
         let console = WwiseConsole::new().unwrap();
-        let project = console.acquire_temp_project().unwrap();
+        let project = console.acquire_temp_project(DEFAULT_PLATFORM).unwrap();
         assert!(project.project_path.exists());
     }
 
@@ -308,9 +1351,9 @@ mod tests {
         let console = WwiseConsole::new().unwrap();
         let root = env::current_dir().unwrap().join("test_files");
         let root_str = root.to_str().unwrap();
-        let project = console.acquire_temp_project().unwrap();
+        let project = console.acquire_temp_project(DEFAULT_PLATFORM).unwrap();
         let mut source = WwiseSource::new(root_str);
-        source.add_source("test_sound.wav");
+        source.add_source_with_conversion("test_sound.wav", Conversion::default());
         project.convert_external_source(&source, root_str).unwrap();
     }
 }
@@ -0,0 +1,57 @@
+mod common;
+
+use std::fs;
+
+use common::{BankBuilder, PckBuilder, fake_wem};
+
+#[test]
+fn directory_dump_builds_index() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("natives");
+    fs::create_dir_all(input.join("sound/sub")).unwrap();
+    BankBuilder::new(1001)
+        .wem(500001, fake_wem(1, 100))
+        .wem(500002, fake_wem(2, 200))
+        .write(input.join("sound/A.sbnk.1.X64"));
+    PckBuilder::new()
+        .language(1, "english")
+        .wem(500002, 1, fake_wem(2, 200))
+        .wem(500003, 0, fake_wem(3, 300))
+        .write(input.join("sound/sub/B.spck.1.X64"));
+    fs::write(input.join("sound/readme.txt"), "not a bundle").unwrap();
+
+    let output = dir.path().join("work");
+    common::run_tool([
+        "unpack-bundle".as_ref(),
+        "-i".as_ref(),
+        input.as_os_str(),
+        "-o".as_ref(),
+        output.as_os_str(),
+    ]);
+    assert!(output.join("sound/A.sbnk.1.X64.project").is_dir());
+    assert!(output.join("sound/sub/B.spck.1.X64.project").is_dir());
+
+    let index: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(output.join("index.json")).unwrap()).unwrap();
+    let media = &index["media"];
+    assert_eq!(media.as_object().unwrap().len(), 3);
+    let shared = media["500002"].as_array().unwrap();
+    assert_eq!(shared.len(), 2);
+    assert_eq!(shared[0]["bundle"], "sound/A.sbnk.1.X64");
+    assert_eq!(shared[0]["index"], 1);
+    assert_eq!(shared[1]["bundle"], "sound/sub/B.spck.1.X64");
+    assert_eq!(shared[1]["language"], "english");
+    assert_eq!(media["500003"][0]["language"], "sfx");
+
+    // dumping again replaces entries instead of duplicating them
+    common::run_tool([
+        "unpack-bundle".as_ref(),
+        "-i".as_ref(),
+        input.as_os_str(),
+        "-o".as_ref(),
+        output.as_os_str(),
+    ]);
+    let index: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(output.join("index.json")).unwrap()).unwrap();
+    assert_eq!(index["media"]["500002"].as_array().unwrap().len(), 2);
+}
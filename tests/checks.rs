@@ -0,0 +1,88 @@
+mod common;
+
+use std::fs;
+
+use common::{fake_wem, sample_bank, sample_pck};
+
+#[test]
+fn validate_reports_every_problem() {
+    use mhws_sound_tool::project::{self, IdOrIndex, ProjectError, ValidationIssue};
+
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    assert!(project::validate_project(&project_path).unwrap().is_empty());
+
+    fs::write(project_path.join("500001.wem"), fake_wem(1, 100)).unwrap();
+    fs::copy(
+        project_path.join("[001]500002.wem"),
+        project_path.join("[001]500002_copy.wem"),
+    )
+    .unwrap();
+    fs::write(project_path.join("[002]500003.wem"), b"not a wem").unwrap();
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    fs::write(replace_root.join("kick.wav"), b"RIFF").unwrap();
+    fs::write(replace_root.join("[7].wem"), fake_wem(2, 100)).unwrap();
+    fs::remove_file(project_path.join("bank.json")).unwrap();
+
+    let issues = project::validate_project(&project_path).unwrap();
+    let messages: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+    assert_eq!(issues.len(), 6, "{:#?}", messages);
+    assert!(matches!(
+        issues[0],
+        ValidationIssue::Project(ProjectError::MissingMetadata(_))
+    ));
+    assert!(matches!(
+        &issues[1],
+        ValidationIssue::Project(ProjectError::BadFileName(name)) if name == "500001"
+    ));
+    assert!(matches!(
+        issues[2],
+        ValidationIssue::DuplicateIndex { idx: 1, .. }
+    ));
+    assert!(matches!(&issues[3], ValidationIssue::InvalidWem(name) if name == "[002]500003.wem"));
+    assert!(matches!(
+        issues[4],
+        ValidationIssue::UnmatchedReplacement {
+            target: IdOrIndex::Index(7),
+            ..
+        }
+    ));
+    assert!(matches!(
+        &issues[5],
+        ValidationIssue::Project(ProjectError::BadFileName(name)) if name.ends_with("kick.wav")
+    ));
+}
+
+#[test]
+fn verify_round_trips_bundles() {
+    let dir = tempfile::tempdir().unwrap();
+    let bnk = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&bnk);
+    let pck = dir.path().join("Synth.spck.1.X64");
+    sample_pck(16).write(&pck);
+    // rules of the user never apply to the repacked bundle
+    let home = dir.path().join("home");
+    fs::create_dir_all(&home).unwrap();
+    fs::write(
+        home.join("rules.toml"),
+        "[[rule]]\nid = 500002\naction = \"remove\"\n",
+    )
+    .unwrap();
+    for input in [&bnk, &pck] {
+        common::run_tool_in(
+            &home,
+            [
+                "verify".as_ref(),
+                "-i".as_ref(),
+                input.as_os_str(),
+                "--exact".as_ref(),
+            ],
+        );
+        let report = mhws_sound_tool::verify::verify_bundle(input).unwrap();
+        assert!(report.is_identical());
+        assert!(report.mismatches.is_empty());
+    }
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use std::fs;
+
+use common::{fake_wem, sample_bank};
+
+#[test]
+fn failed_command_exits_with_error_code() {
+    let dir = tempfile::tempdir().unwrap();
+    let not_project = dir.path().join("not-a-project");
+    fs::create_dir_all(&not_project).unwrap();
+    let output = common::tool_output(
+        dir.path(),
+        [
+            "package-project".as_ref(),
+            "-i".as_ref(),
+            not_project.as_os_str(),
+        ],
+    );
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn json_format_reports_results() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    fs::write(replace_root.join("500001.wem"), fake_wem(10, 333)).unwrap();
+    fs::write(replace_root.join("[2].wem"), fake_wem(11, 5000)).unwrap();
+
+    let output = common::tool_output(
+        dir.path(),
+        [
+            "--format".as_ref(),
+            "json".as_ref(),
+            "package-project".as_ref(),
+            "-i".as_ref(),
+            project_path.as_os_str(),
+            "-o".as_ref(),
+            dir.path().join("out").as_os_str(),
+        ],
+    );
+    assert!(output.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["success"], true);
+    assert_eq!(result["entries_replaced"], 2);
+    let written = result["files_written"][0].as_str().unwrap();
+    assert!(std::path::Path::new(written).is_file());
+
+    let output = common::tool_output(
+        dir.path(),
+        [
+            "--format",
+            "json",
+            "package-project",
+            "-i",
+            "missing",
+            "-o",
+            "out",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(3));
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["success"], false);
+    assert_eq!(result["failure"]["class"], "input");
+}
+
+#[test]
+fn json_logs_stream_events() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    fs::write(replace_root.join("500001.wem"), fake_wem(10, 333)).unwrap();
+    fs::write(replace_root.join("[2].wem"), fake_wem(11, 5000)).unwrap();
+    let events = |log: &[u8]| -> Vec<serde_json::Value> {
+        String::from_utf8_lossy(log)
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    };
+
+    let output = common::tool_output(
+        dir.path(),
+        [
+            "--json-logs".as_ref(),
+            "package-project".as_ref(),
+            "-i".as_ref(),
+            project_path.as_os_str(),
+            "-o".as_ref(),
+            dir.path().join("out").as_os_str(),
+        ],
+    );
+    assert!(output.status.success());
+    // every line of stderr is an event
+    let events_of_run = events(&output.stderr);
+    let mut replaced: Vec<u64> = events_of_run
+        .iter()
+        .filter(|event| event["code"] == "entry-replaced")
+        .map(|event| event["entry"].as_u64().unwrap())
+        .collect();
+    replaced.sort();
+    assert_eq!(replaced, [500001, 500003]);
+    let written = events_of_run
+        .iter()
+        .find(|event| event["code"] == "output-written")
+        .unwrap();
+    assert_eq!(written["level"], "info");
+    assert!(std::path::Path::new(written["file"].as_str().unwrap()).is_file());
+
+    let output = common::tool_output(
+        dir.path(),
+        [
+            "--json-logs",
+            "package-project",
+            "-i",
+            "missing",
+            "-o",
+            "out",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(3));
+    let failed = events(&output.stderr).pop().unwrap();
+    assert_eq!(failed["code"], "command-failed");
+    assert_eq!(failed["level"], "error");
+    assert_eq!(failed["exit_code"], 3);
+
+    // next to the colored log
+    let log_path = dir.path().join("events.jsonl");
+    let mut json_logs = std::ffi::OsString::from("--json-logs=");
+    json_logs.push(&log_path);
+    let output = common::tool_output(
+        dir.path(),
+        [
+            json_logs.as_os_str(),
+            "package-project".as_ref(),
+            "-i".as_ref(),
+            project_path.as_os_str(),
+            "-o".as_ref(),
+            dir.path().join("out").as_os_str(),
+        ],
+    );
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("replaced by ID"));
+    let logged = events(&fs::read(&log_path).unwrap());
+    assert!(logged.iter().any(|event| event["code"] == "entry-replaced"));
+}
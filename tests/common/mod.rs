@@ -0,0 +1,434 @@
+//! Synthetic bundle fixtures and helpers for integration tests.
+//!
+//! Fixtures are generated in code, so round trips run without game data.
+//! Tests drive the built binary and compare dumped projects.
+
+#![allow(dead_code)]
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Fake wem data, unique for each seed.
+pub fn fake_wem(seed: u32, length: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(length.max(12));
+    data.extend_from_slice(b"RIFF");
+    data.extend_from_slice(&(length.max(12) as u32 - 8).to_le_bytes());
+    data.extend_from_slice(b"WAVE");
+    let mut state = seed.wrapping_mul(2654435761).wrapping_add(1);
+    while data.len() < length {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        data.push(state as u8);
+    }
+    data
+}
+
+fn push_u32(buf: &mut Vec<u8>, value: u32, big_endian: bool) {
+    if big_endian {
+        buf.extend_from_slice(&value.to_be_bytes());
+    } else {
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn push_section(buf: &mut Vec<u8>, magic: &[u8; 4], payload: &[u8], big_endian: bool) {
+    buf.extend_from_slice(magic);
+    push_u32(buf, payload.len() as u32, big_endian);
+    buf.extend_from_slice(payload);
+}
+
+/// Builder of synthetic `.bnk` files.
+#[derive(Debug, Clone)]
+pub struct BankBuilder {
+    version: u32,
+    bank_id: u32,
+    language_id: u32,
+    alignment: u32,
+    wems: Vec<(u32, Vec<u8>)>,
+    hirc: Vec<(u8, u32, Vec<u8>)>,
+    extra_sections: Vec<([u8; 4], Vec<u8>)>,
+    big_endian: bool,
+}
+
+impl BankBuilder {
+    pub fn new(bank_id: u32) -> Self {
+        Self {
+            version: 145,
+            bank_id,
+            language_id: 0,
+            alignment: 16,
+            wems: vec![],
+            hirc: vec![],
+            extra_sections: vec![],
+            big_endian: false,
+        }
+    }
+
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn language(mut self, language_id: u32) -> Self {
+        self.language_id = language_id;
+        self
+    }
+
+    /// Write in big endian, like banks of some consoles.
+    pub fn big_endian(mut self) -> Self {
+        self.big_endian = true;
+        self
+    }
+
+    /// Alignment of wem data in DATA section.
+    pub fn alignment(mut self, alignment: u32) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    pub fn wem(mut self, id: u32, data: Vec<u8>) -> Self {
+        self.wems.push((id, data));
+        self
+    }
+
+    pub fn hirc(mut self, type_id: u8, id: u32, data: Vec<u8>) -> Self {
+        self.hirc.push((type_id, id, data));
+        self
+    }
+
+    /// Section kept as is by the tool, e.g. `STID`.
+    pub fn section(mut self, magic: &[u8; 4], payload: Vec<u8>) -> Self {
+        self.extra_sections.push((*magic, payload));
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut buf = vec![];
+
+        let mut bkhd = vec![];
+        push_u32(&mut bkhd, self.version, self.big_endian);
+        push_u32(&mut bkhd, self.bank_id, self.big_endian);
+        push_u32(&mut bkhd, self.language_id, self.big_endian);
+        // feedback, alignment and project ID fields
+        push_u32(&mut bkhd, 0, self.big_endian);
+        push_u32(&mut bkhd, 0, self.big_endian);
+        push_section(&mut buf, b"BKHD", &bkhd, self.big_endian);
+
+        if !self.wems.is_empty() {
+            let mut didx = vec![];
+            let mut data = vec![];
+            for (id, wem) in &self.wems {
+                let offset = data.len().next_multiple_of(self.alignment.max(1) as usize);
+                data.resize(offset, 0);
+                push_u32(&mut didx, *id, self.big_endian);
+                push_u32(&mut didx, offset as u32, self.big_endian);
+                push_u32(&mut didx, wem.len() as u32, self.big_endian);
+                data.extend_from_slice(wem);
+            }
+            push_section(&mut buf, b"DIDX", &didx, self.big_endian);
+            push_section(&mut buf, b"DATA", &data, self.big_endian);
+        }
+
+        if !self.hirc.is_empty() {
+            let mut hirc = vec![];
+            push_u32(&mut hirc, self.hirc.len() as u32, self.big_endian);
+            for (type_id, id, data) in &self.hirc {
+                hirc.push(*type_id);
+                push_u32(&mut hirc, data.len() as u32 + 4, self.big_endian);
+                push_u32(&mut hirc, *id, self.big_endian);
+                hirc.extend_from_slice(data);
+            }
+            push_section(&mut buf, b"HIRC", &hirc, self.big_endian);
+        }
+
+        for (magic, payload) in &self.extra_sections {
+            push_section(&mut buf, magic, payload, self.big_endian);
+        }
+        buf
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) {
+        fs::write(path, self.build()).unwrap();
+    }
+}
+
+/// Magics of the sections of a bank, in order.
+pub fn bank_sections(bank: &[u8]) -> Vec<String> {
+    let mut sections = vec![];
+    let mut pos = 0;
+    while pos + 8 <= bank.len() {
+        sections.push(String::from_utf8_lossy(&bank[pos..pos + 4]).to_string());
+        let length = u32::from_le_bytes(bank[pos + 4..pos + 8].try_into().unwrap());
+        pos += 8 + length as usize;
+    }
+    sections
+}
+
+/// Builder of synthetic `.pck` files.
+#[derive(Debug, Clone)]
+pub struct PckBuilder {
+    languages: Vec<(u32, String)>,
+    block_size: u32,
+    /// `(id, language_id, data)`
+    bnks: Vec<(u32, u32, Vec<u8>)>,
+    wems: Vec<(u32, u32, Vec<u8>)>,
+    big_endian: bool,
+}
+
+impl PckBuilder {
+    pub fn new() -> Self {
+        Self {
+            languages: vec![(0, "sfx".to_string())],
+            block_size: 1,
+            bnks: vec![],
+            wems: vec![],
+            big_endian: false,
+        }
+    }
+
+    pub fn language(mut self, id: u32, name: &str) -> Self {
+        self.languages.push((id, name.to_string()));
+        self
+    }
+
+    /// Write in big endian, like packages of some consoles.
+    pub fn big_endian(mut self) -> Self {
+        self.big_endian = true;
+        self
+    }
+
+    /// Alignment of entry data, also the unit of entry offsets.
+    pub fn block_size(mut self, block_size: u32) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    pub fn bnk(mut self, id: u32, language_id: u32, data: Vec<u8>) -> Self {
+        self.bnks.push((id, language_id, data));
+        self
+    }
+
+    pub fn wem(mut self, id: u32, language_id: u32, data: Vec<u8>) -> Self {
+        self.wems.push((id, language_id, data));
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        // language string table, offsets relative to table start
+        let mut strings = vec![];
+        push_u32(&mut strings, self.languages.len() as u32, self.big_endian);
+        let mut offset = 4 + 8 * self.languages.len() as u32;
+        let mut string_data = vec![];
+        for (id, name) in &self.languages {
+            push_u32(&mut strings, offset, self.big_endian);
+            push_u32(&mut strings, *id, self.big_endian);
+            for unit in name.encode_utf16().chain(Some(0)) {
+                if self.big_endian {
+                    string_data.extend_from_slice(&unit.to_be_bytes());
+                } else {
+                    string_data.extend_from_slice(&unit.to_le_bytes());
+                }
+            }
+            offset = 4 + 8 * self.languages.len() as u32 + string_data.len() as u32;
+        }
+        strings.extend_from_slice(&string_data);
+
+        let bnk_table_length = 4 + 20 * self.bnks.len() as u32;
+        let wem_table_length = 4 + 20 * self.wems.len() as u32;
+        let external_table_length = 4;
+        let header_length =
+            20 + strings.len() as u32 + bnk_table_length + wem_table_length + external_table_length;
+
+        // data layout, BNK entries first
+        let block = self.block_size.max(1) as usize;
+        let mut position = 8 + header_length as usize;
+        let mut data = vec![];
+        let mut place = |entry_data: &[u8]| {
+            position = position.next_multiple_of(block);
+            let entry_offset = position;
+            data.resize(position - 8 - header_length as usize, 0);
+            data.extend_from_slice(entry_data);
+            position += entry_data.len();
+            entry_offset
+        };
+        let mut tables = vec![];
+        for entries in [&self.bnks, &self.wems] {
+            push_u32(&mut tables, entries.len() as u32, self.big_endian);
+            for (id, language_id, entry_data) in entries {
+                let entry_offset = place(entry_data);
+                push_u32(&mut tables, *id, self.big_endian);
+                push_u32(&mut tables, self.block_size, self.big_endian);
+                push_u32(&mut tables, entry_data.len() as u32, self.big_endian);
+                push_u32(&mut tables, (entry_offset / block) as u32, self.big_endian);
+                push_u32(&mut tables, *language_id, self.big_endian);
+            }
+        }
+
+        let mut buf = vec![];
+        buf.extend_from_slice(b"AKPK");
+        push_u32(&mut buf, header_length, self.big_endian);
+        push_u32(&mut buf, 1, self.big_endian);
+        push_u32(&mut buf, strings.len() as u32, self.big_endian);
+        push_u32(&mut buf, bnk_table_length, self.big_endian);
+        push_u32(&mut buf, wem_table_length, self.big_endian);
+        push_u32(&mut buf, external_table_length, self.big_endian);
+        buf.extend_from_slice(&strings);
+        buf.extend_from_slice(&tables);
+        push_u32(&mut buf, 0, self.big_endian); // external entries
+        buf.extend_from_slice(&data);
+        buf
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) {
+        fs::write(path, self.build()).unwrap();
+    }
+}
+
+/// Run the tool, panic with its output if it fails.
+pub fn run_tool<I, S>(args: I)
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<std::ffi::OsStr>,
+{
+    run_tool_in(Path::new("."), args);
+}
+
+/// Run the tool in a working directory, where it reads `config.toml`.
+pub fn run_tool_in<I, S>(current_dir: &Path, args: I)
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<std::ffi::OsStr>,
+{
+    let output = tool_output(current_dir, args);
+    assert!(
+        output.status.success(),
+        "tool failed:\n{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Run the tool in a working directory, returns its output whether it succeeds or not.
+pub fn tool_output<I, S>(current_dir: &Path, args: I) -> std::process::Output
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<std::ffi::OsStr>,
+{
+    tool_command(current_dir).args(args).output().unwrap()
+}
+
+/// Command of the tool in a working directory, to add arguments and environment to.
+pub fn tool_command(current_dir: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_mhws-sound-tool"));
+    command.current_dir(current_dir).arg("--no-interact");
+    command
+}
+
+/// Dump a bundle, returns the project path.
+pub fn dump(input: &Path, output_root: &Path) -> PathBuf {
+    run_tool([
+        "unpack-bundle".as_ref(),
+        "-i".as_ref(),
+        input.as_os_str(),
+        "-o".as_ref(),
+        output_root.as_os_str(),
+    ]);
+    let mut project_path = output_root
+        .join(input.file_name().unwrap())
+        .into_os_string();
+    project_path.push(".project");
+    PathBuf::from(project_path)
+}
+
+/// Repack a project, returns the output bundle path.
+pub fn repack(project_path: &Path, output_root: &Path) -> PathBuf {
+    run_tool([
+        "package-project".as_ref(),
+        "-i".as_ref(),
+        project_path.as_os_str(),
+        "-o".as_ref(),
+        output_root.as_os_str(),
+    ]);
+    let file_name = project_path.file_stem().unwrap();
+    output_root.join(file_name)
+}
+
+/// Dumped entries of a project with the extension, `index -> (id, data)`.
+pub fn dumped_entries(project_path: &Path, ext: &str) -> BTreeMap<u32, (u32, Vec<u8>)> {
+    let mut entries = BTreeMap::new();
+    for entry in fs::read_dir(project_path).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().unwrap_or_default() != ext {
+            continue;
+        }
+        // [001]12345678.wem
+        let stem = path.file_stem().unwrap().to_string_lossy();
+        let (index, id) = stem[1..].split_once(']').unwrap();
+        entries.insert(
+            index.parse().unwrap(),
+            (id.parse().unwrap(), fs::read(&path).unwrap()),
+        );
+    }
+    entries
+}
+
+/// Parsed metadata file of a project, e.g. `bank.json`.
+pub fn metadata(project_path: &Path, file_name: &str) -> serde_json::Value {
+    let content = fs::read_to_string(project_path.join(file_name)).unwrap();
+    serde_json::from_str(&content).unwrap()
+}
+
+/// Remove fields from all objects in a JSON value, for comparing metadata
+/// which is expected to differ only in those fields.
+pub fn strip_fields(value: &mut serde_json::Value, fields: &[&str]) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for field in fields {
+                map.remove(*field);
+            }
+            for value in map.values_mut() {
+                strip_fields(value, fields);
+            }
+        }
+        serde_json::Value::Array(values) => {
+            for value in values {
+                strip_fields(value, fields);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Bank of the round trip tests.
+pub fn sample_bank(alignment: u32) -> BankBuilder {
+    BankBuilder::new(1001)
+        .alignment(alignment)
+        .wem(500001, fake_wem(1, 1000))
+        .wem(500002, fake_wem(2, 37))
+        .wem(500003, fake_wem(3, 4096))
+        .hirc(2, 600001, vec![0; 20])
+        .hirc(7, 600002, vec![1; 9])
+        .section(
+            b"STID",
+            vec![
+                1, 0, 0, 0, 1, 0, 0, 0, 0xE9, 0x03, 0, 0, 4, b'T', b'e', b's', b't',
+            ],
+        )
+}
+
+/// Package of the round trip tests.
+pub fn sample_pck(block_size: u32) -> PckBuilder {
+    PckBuilder::new()
+        .language(1, "english")
+        .language(2, "japanese")
+        .block_size(block_size)
+        .bnk(1001, 0, sample_bank(16).build())
+        .wem(500001, 0, fake_wem(1, 1000))
+        .wem(500002, 1, fake_wem(2, 37))
+        .wem(500003, 2, fake_wem(3, 4096))
+}
@@ -0,0 +1,287 @@
+mod common;
+
+use std::fs;
+
+use common::{BankBuilder, PckBuilder, fake_wem, sample_bank};
+
+/// WwiseConsole stand-in, "converts" sources by copying them, except
+/// sources containing `BROKEN`.
+#[cfg(unix)]
+const STUB_CONSOLE: &str = r#"#!/bin/sh
+unix_path() { printf '%s' "$1" | tr '\\' '/'; }
+case "$1" in
+create-new-project)
+    [ "$2" = "--help" ] && exit 0
+    project=$(unix_path "$2")
+    mkdir -p "$(dirname "$project")" && touch "$project"
+    ;;
+convert-external-source)
+    output="$(unix_path "$6")/$8"
+    mkdir -p "$output"
+    grep -o 'Source Path="[^"]*"' "$(unix_path "$4")" | sed 's/^Source Path="//; s/"$//' |
+        while read -r source; do
+            source=$(unix_path "$source")
+            grep -q BROKEN "$source" && continue
+            cp "$source" "$output/$(basename "$source" .wav).wem"
+        done
+    ;;
+esac
+"#;
+
+#[cfg(unix)]
+#[test]
+fn wem_conversion_keeps_order_and_removes_staging() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let home = dir.path().join("home");
+    let temp = dir.path().join("temp");
+    fs::create_dir_all(&home).unwrap();
+    fs::create_dir_all(&temp).unwrap();
+    let console = dir.path().join("WwiseConsole.sh");
+    fs::write(&console, STUB_CONSOLE).unwrap();
+    fs::set_permissions(&console, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(
+        home.join("config.toml"),
+        format!(
+            "version = 1\n[wwise]\nwine = \"\"\ntemp_project_root = {:?}\n",
+            dir.path().join("projects")
+        ),
+    )
+    .unwrap();
+    let inputs = ["b", "a", "c"].map(|name| {
+        let path = dir.path().join(name).with_extension("wav");
+        fs::write(&path, format!("sound {}", name)).unwrap();
+        path
+    });
+
+    let convert = |inputs: &[std::path::PathBuf], platform: &str| {
+        let mut command = common::tool_command(&home);
+        command.env("TMPDIR", &temp).arg("sound-to-wem");
+        for input in inputs {
+            command.arg("-i").arg(input);
+        }
+        command
+            .arg("-o")
+            .arg(dir.path().join("out"))
+            .arg("--wwise-console")
+            .arg(&console)
+            .arg("--platform")
+            .arg(platform);
+        command.output().unwrap()
+    };
+    let output = convert(&inputs, "Windows");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // each output is of its own input
+    for name in ["a", "b", "c"] {
+        let wem = dir.path().join("out").join(name).with_extension("wem");
+        assert_eq!(fs::read_to_string(wem).unwrap(), format!("sound {}", name));
+    }
+    assert_eq!(fs::read_dir(&temp).unwrap().count(), 0);
+
+    // a file without output fails the conversion, staging is removed too
+    fs::write(&inputs[1], "BROKEN").unwrap();
+    let output = convert(&inputs, "Windows");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("a.wav"));
+    assert_eq!(fs::read_dir(&temp).unwrap().count(), 0);
+
+    // other platforms are written to a folder of their name, unknown ones refused
+    let output = convert(&inputs[..1], "PS5");
+    assert!(output.status.success());
+    assert!(dir.path().join("out/PS5/b.wem").is_file());
+    let output = convert(&inputs[..1], "../x");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown Wwise platform"));
+    assert!(!dir.path().join("x").exists());
+}
+
+#[test]
+fn replace_entries_convert_for_their_platform() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let home = dir.path().join("home");
+    fs::create_dir_all(&home).unwrap();
+    let console = dir.path().join("WwiseConsole.sh");
+    fs::write(&console, STUB_CONSOLE).unwrap();
+    fs::set_permissions(&console, fs::Permissions::from_mode(0o755)).unwrap();
+    let projects = dir.path().join("projects");
+    fs::write(
+        home.join("config.toml"),
+        format!(
+            "version = 1\n[wwise]\nwine = \"\"\ntemp_project_root = {:?}\n[[bin]]\nname = \"WwiseConsole\"\npath = {:?}\n",
+            projects, console
+        ),
+    )
+    .unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    fs::write(replace_root.join("500001.wav"), fake_wem(11, 80)).unwrap();
+    fs::write(replace_root.join("500002.wav"), fake_wem(12, 50)).unwrap();
+    fs::write(
+        project_path.join("project.toml"),
+        "platform = \"PS5\"\n\n[[replace]]\ntarget = \"[1]\"\nplatform = \"Switch\"\n",
+    )
+    .unwrap();
+
+    let output_root = dir.path().join("out");
+    common::run_tool_in(
+        &home,
+        [
+            "package-project".as_ref(),
+            "-i".as_ref(),
+            project_path.as_os_str(),
+            "-o".as_ref(),
+            output_root.as_os_str(),
+        ],
+    );
+    // each platform is converted in its own temp project
+    let mut used: Vec<String> = fs::read_dir(&projects)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with("SoundToolTemp"))
+        .collect();
+    used.sort();
+    assert_eq!(used, ["SoundToolTemp_PS5", "SoundToolTemp_Switch"]);
+    let packed = common::dump(
+        &output_root.join("Synth.sbnk.1.X64"),
+        &dir.path().join("check"),
+    );
+    let entries = common::dumped_entries(&packed, "wem");
+    assert_eq!(entries[&0], (500001, fake_wem(11, 80)));
+    assert_eq!(entries[&1], (500002, fake_wem(12, 50)));
+}
+
+#[test]
+fn foreign_wems_are_decoded_and_converted() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let home = dir.path().join("home");
+    fs::create_dir_all(&home).unwrap();
+    let console = dir.path().join("WwiseConsole.sh");
+    fs::write(&console, STUB_CONSOLE).unwrap();
+    fs::set_permissions(&console, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(
+        home.join("config.toml"),
+        format!(
+            "version = 1\n[wwise]\nwine = \"\"\ntemp_project_root = {:?}\n[[bin]]\nname = \"WwiseConsole\"\npath = {:?}\n",
+            dir.path().join("projects"),
+            console
+        ),
+    )
+    .unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    // big endian 16 bit mono PCM, as written for old consoles
+    let mut foreign = b"RIFX\0\0\0\x2cWAVEfmt \0\0\0\x10".to_vec();
+    foreign.extend_from_slice(&[0, 1, 0, 1, 0, 0, 0xBB, 0x80, 0, 1, 0x77, 0, 0, 2, 0, 16]);
+    foreign.extend_from_slice(b"data\0\0\0\x04\x12\x34\x56\x78");
+    fs::write(replace_root.join("500001.wem"), &foreign).unwrap();
+
+    let output_root = dir.path().join("out");
+    common::run_tool_in(
+        &home,
+        [
+            "package-project".as_ref(),
+            "-i".as_ref(),
+            project_path.as_os_str(),
+            "-o".as_ref(),
+            output_root.as_os_str(),
+        ],
+    );
+    let packed = common::dump(
+        &output_root.join("Synth.sbnk.1.X64"),
+        &dir.path().join("check"),
+    );
+    // converted from the decoded little endian wav, not packed as is
+    let (id, wem) = &common::dumped_entries(&packed, "wem")[&0];
+    assert_eq!(*id, 500001);
+    assert_eq!(&wem[0..4], b"RIFF");
+    assert!(wem.ends_with(b"data\x04\0\0\0\x34\x12\x78\x56"));
+}
+
+#[test]
+fn wem_replacements_need_no_wwise_console() {
+    let dir = tempfile::tempdir().unwrap();
+    let home = dir.path().join("home");
+    fs::create_dir_all(&home).unwrap();
+    fs::write(
+        home.join("config.toml"),
+        "version = 1\n[[bin]]\nname = \"WwiseConsole\"\npath = \"missing/WwiseConsole.exe\"\n",
+    )
+    .unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    fs::write(replace_root.join("500002.wem"), fake_wem(12, 50)).unwrap();
+
+    let output_root = dir.path().join("out");
+    common::run_tool_in(
+        &home,
+        [
+            "package-project".as_ref(),
+            "-i".as_ref(),
+            project_path.as_os_str(),
+            "-o".as_ref(),
+            output_root.as_os_str(),
+        ],
+    );
+    let output = output_root.join("Synth.sbnk.1.X64");
+    let redumped =
+        common::dumped_entries(&common::dump(&output, &dir.path().join("redump")), "wem");
+    assert_eq!(redumped[&1], (500002, fake_wem(12, 50)));
+}
+
+#[test]
+fn dump_records_entry_formats() {
+    let dir = tempfile::tempdir().unwrap();
+    // vorbis, 2 channels at 44100 Hz, 88200 samples in the fmt extension
+    let mut fmt = 0xFFFFu16.to_le_bytes().to_vec();
+    fmt.extend_from_slice(&2u16.to_le_bytes());
+    fmt.extend_from_slice(&44100u32.to_le_bytes());
+    fmt.extend_from_slice(&[0; 16]);
+    fmt.extend_from_slice(&88200u32.to_le_bytes());
+    let mut wem = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+    wem.extend_from_slice(&(fmt.len() as u32).to_le_bytes());
+    wem.extend_from_slice(&fmt);
+    wem.extend_from_slice(b"data");
+    wem.extend_from_slice(&8000u32.to_le_bytes());
+    wem.extend_from_slice(&[0; 8000]);
+    // 8064 bytes in 2 s
+    let expected = serde_json::json!({
+        "500001": { "codec": "vorbis", "sample_rate": 44100, "channels": 2, "bitrate": 32 }
+    });
+
+    let bank = dir.path().join("Synth.sbnk.1.X64");
+    BankBuilder::new(1001)
+        .wem(500001, wem.clone())
+        .wem(500002, fake_wem(2, 64))
+        .write(&bank);
+    let pck = dir.path().join("Synth.spck.1.X64");
+    PckBuilder::new()
+        .language(0, "sfx")
+        .wem(500001, 0, wem)
+        .wem(500002, 0, fake_wem(2, 64))
+        .write(&pck);
+    for input in [bank, pck] {
+        let project_path = common::dump(&input, &dir.path().join("work"));
+        // wems of unknown codecs are not recorded
+        assert_eq!(common::metadata(&project_path, "formats.json"), expected);
+        let output = common::repack(&project_path, &dir.path().join("out"));
+        assert_eq!(fs::read(&output).unwrap(), fs::read(&input).unwrap());
+    }
+}
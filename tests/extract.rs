@@ -0,0 +1,112 @@
+mod common;
+
+use std::fs;
+
+use common::{fake_wem, sample_bank, sample_pck};
+
+#[test]
+fn extract_entries_by_id_and_index() {
+    let dir = tempfile::tempdir().unwrap();
+    for (name, pck) in [("Synth.sbnk.1.X64", false), ("Synth.spck.1.X64", true)] {
+        let input = dir.path().join(name);
+        if pck {
+            sample_pck(16).write(&input);
+        } else {
+            sample_bank(16).write(&input);
+        }
+        let project_path = common::dump(&input, &dir.path().join("work"));
+        let original = common::dumped_entries(&project_path, "wem");
+
+        let out_dir = dir.path().join("extracted");
+        common::run_tool([
+            "extract".as_ref(),
+            "-i".as_ref(),
+            input.as_os_str(),
+            "--id".as_ref(),
+            "500003".as_ref(),
+            "--index".as_ref(),
+            "0".as_ref(),
+            "-o".as_ref(),
+            out_dir.as_os_str(),
+        ]);
+        assert_eq!(
+            fs::read(out_dir.join("500003.wem")).unwrap(),
+            original[&2].1
+        );
+        assert_eq!(
+            fs::read(out_dir.join("500001.wem")).unwrap(),
+            original[&0].1
+        );
+
+        // a single entry can be written to a file
+        let out_file = dir.path().join("single.wem");
+        common::run_tool([
+            "extract".as_ref(),
+            "-i".as_ref(),
+            project_path.as_os_str(),
+            "--index".as_ref(),
+            "1".as_ref(),
+            "-o".as_ref(),
+            out_file.as_os_str(),
+        ]);
+        assert_eq!(fs::read(&out_file).unwrap(), original[&1].1);
+    }
+}
+
+#[test]
+fn extract_entries_by_language() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.spck.1.X64");
+    sample_pck(16).write(&input);
+
+    let out_dir = dir.path().join("japanese");
+    common::run_tool([
+        "extract".as_ref(),
+        "-i".as_ref(),
+        input.as_os_str(),
+        "--language".as_ref(),
+        "Japanese".as_ref(),
+        "-o".as_ref(),
+        out_dir.as_os_str(),
+    ]);
+    let mut extracted: Vec<_> = fs::read_dir(&out_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    extracted.sort();
+    assert_eq!(extracted, ["500003.wem"]);
+    assert_eq!(
+        fs::read(out_dir.join("500003.wem")).unwrap(),
+        fake_wem(3, 4096)
+    );
+
+    let output = common::tool_output(
+        dir.path(),
+        [
+            "list".as_ref(),
+            "-i".as_ref(),
+            input.as_os_str(),
+            "--language".as_ref(),
+            "english".as_ref(),
+            "--json".as_ref(),
+        ],
+    );
+    assert!(output.status.success());
+    let listed: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    assert_eq!(listed[0]["id"], 500002);
+
+    // unknown languages are input errors
+    let output = common::tool_output(
+        dir.path(),
+        [
+            "extract".as_ref(),
+            "-i".as_ref(),
+            input.as_os_str(),
+            "--language".as_ref(),
+            "klingon".as_ref(),
+        ],
+    );
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("klingon"));
+}
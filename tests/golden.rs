@@ -0,0 +1,80 @@
+//! Tests on bundles committed in `tests/golden`, independent of the fixture
+//! builders. `expected/` holds the dumped entries and metadata of each bundle,
+//! and the bundle packaged with the files of `replace/`.
+
+mod common;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+/// Dumped files of a project with their SHA-256, in `sha256sum` format.
+fn entry_checksums(project_path: &Path) -> String {
+    let mut names: Vec<String> = fs::read_dir(project_path)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .filter(|name| name.ends_with(".wem") || name.ends_with(".bnk"))
+        .collect();
+    // same order as `sha256sum *.wem *.bnk`
+    names.sort_by_key(|name| (name.ends_with(".bnk"), name.clone()));
+    names
+        .iter()
+        .map(|name| {
+            let data = fs::read(project_path.join(name)).unwrap();
+            format!("{:x}  {}\n", Sha256::digest(&data), name)
+        })
+        .collect()
+}
+
+fn check_golden(file_name: &str, metadata_file: &str) {
+    let golden = golden_dir();
+    let expected = golden.join("expected");
+    let dir = tempfile::tempdir().unwrap();
+
+    let project_path = common::dump(&golden.join(file_name), &dir.path().join("work"));
+    assert_eq!(
+        entry_checksums(&project_path),
+        fs::read_to_string(expected.join(format!("{}.project.sha256", file_name))).unwrap(),
+        "dumped entries of {} differ",
+        file_name
+    );
+    let expected_metadata =
+        fs::read_to_string(expected.join(format!("{}.{}", file_name, metadata_file))).unwrap();
+    assert_eq!(
+        common::metadata(&project_path, metadata_file),
+        serde_json::from_str::<serde_json::Value>(&expected_metadata).unwrap(),
+        "{} of {} differs",
+        metadata_file,
+        file_name
+    );
+
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    for entry in fs::read_dir(golden.join("replace")).unwrap() {
+        let entry = entry.unwrap();
+        fs::copy(entry.path(), replace_root.join(entry.file_name())).unwrap();
+    }
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    assert!(
+        fs::read(&output).unwrap() == fs::read(expected.join(file_name)).unwrap(),
+        "packaged {} differs",
+        file_name
+    );
+}
+
+#[test]
+fn golden_bnk() {
+    check_golden("Synth.sbnk.1.X64", "bank.json");
+}
+
+#[test]
+fn golden_pck() {
+    check_golden("Synth.spck.1.X64", "pck.json");
+}
@@ -0,0 +1,270 @@
+mod common;
+
+use std::fs;
+
+use common::{BankBuilder, fake_wem};
+
+#[test]
+fn stid_bank_names_are_labeled_and_diffed() {
+    use mhws_sound_tool::{metadiff, project::SoundToolProject};
+
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    let stid = [
+        &[1, 0, 0, 0, 2, 0, 0, 0, 0xE9, 0x03, 0, 0, 4][..],
+        b"Test",
+        &[0xEA, 0x03, 0, 0, 3],
+        b"Cmn",
+    ]
+    .concat();
+    BankBuilder::new(1001)
+        .wem(500001, fake_wem(1, 100))
+        .section(b"STID", stid)
+        .write(&input);
+
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let mut metadata = common::metadata(&project_path, "bank.json");
+    let sections = metadata["sections"].as_array().unwrap();
+    let bkhd = sections.iter().find(|s| s["type"] == "Bkhd").unwrap();
+    assert_eq!(bkhd["content"]["name"], "Test");
+    let stid = sections.iter().find(|s| s["type"] == "Stid").unwrap();
+    assert!(stid["content"]["entries"][0].get("referenced").is_none());
+    assert_eq!(stid["content"]["entries"][1]["referenced"], true);
+
+    // renamed banks are edits, unlike the labels
+    let sections = metadata["sections"].as_array_mut().unwrap();
+    let stid = sections.iter_mut().find(|s| s["type"] == "Stid").unwrap();
+    stid["content"]["entries"][1]["bank_name"] = "Common".into();
+    fs::write(
+        project_path.join("bank.json"),
+        serde_json::to_string(&metadata).unwrap(),
+    )
+    .unwrap();
+    let project = SoundToolProject::from_path(&project_path).unwrap();
+    let changes = metadiff::diff(
+        &project.original_metadata(&input).unwrap(),
+        &project.metadata().unwrap(),
+    );
+    let changes: Vec<_> = changes.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        changes,
+        ["~ sections[STID].content.entries[id=1002].bank_name: \"Cmn\" -> \"Common\""]
+    );
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    let bank =
+        mhws_sound_tool::bnk::Bnk::from_reader(&mut fs::File::open(output).unwrap()).unwrap();
+    assert_eq!(bank.bank_names()[1].name, "Common");
+}
+
+#[test]
+fn variations_spread_over_random_container() {
+    use mhws_sound_tool::hirc::{
+        HircLayout, HircObject, NodeParams, RanSeqContainerObject, SoundObject, SourceData,
+    };
+
+    let node = |parent: u32| NodeParams {
+        override_parent_fx: 0,
+        fx_bypass: None,
+        fx: vec![],
+        override_parent_metadata: 0,
+        metadata: vec![],
+        override_attachment_params: 0,
+        override_bus_id: 0,
+        direct_parent_id: parent,
+        bit_vector: 0,
+        props: vec![],
+    };
+    let sound = |source_id: u32| {
+        HircObject::Sound(SoundObject {
+            source: SourceData {
+                plugin_id: 0x00040001,
+                stream_type: 0,
+                source_id,
+                media_size: 0,
+                source_bits: 0,
+                plugin_params: None,
+            },
+            node: node(800001),
+            rest: vec![0; 4],
+        })
+        .to_bytes(HircLayout::default())
+    };
+    let container = HircObject::RanSeqContainer(RanSeqContainerObject {
+        node: node(0),
+        // empty node params and playback settings
+        params: vec![0; 41],
+        children: vec![700001, 700002, 700003],
+        playlist: vec![],
+    })
+    .to_bytes(HircLayout::default());
+
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    BankBuilder::new(1001)
+        .wem(500001, fake_wem(1, 1000))
+        .wem(500002, fake_wem(2, 37))
+        .wem(500003, fake_wem(3, 4096))
+        .wem(500004, fake_wem(4, 64))
+        .hirc(2, 700001, sound(500001))
+        .hirc(2, 700002, sound(500002))
+        .hirc(2, 700003, sound(500003))
+        .hirc(5, 800001, container)
+        .write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let hits = project_path.join("hits");
+    fs::create_dir_all(&hits).unwrap();
+    fs::write(hits.join("a.wem"), fake_wem(10, 300)).unwrap();
+    fs::write(hits.join("b.wem"), fake_wem(11, 500)).unwrap();
+    fs::write(
+        project_path.join("project.toml"),
+        "[[variations]]\ntarget = 500002\nfolder = \"hits\"\n",
+    )
+    .unwrap();
+
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    let repacked = common::dump(&output, &dir.path().join("check"));
+    let entries = common::dumped_entries(&repacked, "wem");
+    assert_eq!(entries[&0], (500001, fake_wem(10, 300)));
+    assert_eq!(entries[&1], (500002, fake_wem(11, 500)));
+    assert_eq!(entries[&2], (500003, fake_wem(10, 300)));
+    assert_eq!(entries[&3], (500004, fake_wem(4, 64)));
+
+    // sounds with a replace file named by index are skipped too
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    fs::write(replace_root.join("[1].wem"), fake_wem(12, 700)).unwrap();
+    let output = common::repack(&project_path, &dir.path().join("out2"));
+    let repacked = common::dump(&output, &dir.path().join("check2"));
+    let entries = common::dumped_entries(&repacked, "wem");
+    assert_eq!(entries[&0], (500001, fake_wem(10, 300)));
+    assert_eq!(entries[&1], (500002, fake_wem(12, 700)));
+    assert_eq!(entries[&2], (500003, fake_wem(10, 300)));
+}
+
+#[test]
+fn remapped_sources_play_added_wems() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    BankBuilder::new(1001)
+        .wem(500001, fake_wem(1, 1000))
+        .wem(500002, fake_wem(2, 37))
+        .hirc(2, 700001, plain_sound(500001))
+        .hirc(2, 700002, plain_sound(500002))
+        .write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let add_root = project_path.join("add");
+    fs::create_dir_all(&add_root).unwrap();
+    fs::write(add_root.join("500009.wem"), fake_wem(9, 100)).unwrap();
+    fs::write(
+        project_path.join("project.toml"),
+        "remove = [500001]\n\n[[remap]]\nfrom = 500001\nto = 500009\n",
+    )
+    .unwrap();
+
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    let repacked = common::dump(&output, &dir.path().join("check"));
+    let metadata = common::metadata(&repacked, "bank.json");
+    let entries = metadata["sections"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|section| section["type"] == "Hirc")
+        .and_then(|section| section["content"]["entries"].as_array())
+        .unwrap();
+    assert_eq!(entries[0]["object"]["source"]["source_id"], 500009);
+    // in-memory sources take the size of the new wem
+    assert_eq!(entries[0]["object"]["source"]["media_size"], 100);
+    assert_eq!(entries[1]["object"]["source"]["source_id"], 500002);
+    let wems = common::dumped_entries(&repacked, "wem");
+    let ids: Vec<u32> = wems.values().map(|(id, _)| *id).collect();
+    assert_eq!(ids, [500002, 500009]);
+
+    // one source remapped twice is ambiguous
+    fs::write(
+        project_path.join("project.toml"),
+        "[[remap]]\nfrom = 500001\nto = 500009\n\n[[remap]]\nfrom = 500001\nto = 500002\n",
+    )
+    .unwrap();
+    let result = mhws_sound_tool::project::SoundToolProject::from_path(&project_path)
+        .unwrap()
+        .repack(dir.path().join("out2"));
+    assert!(matches!(
+        result,
+        Err(mhws_sound_tool::project::ProjectError::Manifest(
+            mhws_sound_tool::manifest::ManifestError::DuplicateRemap(500001)
+        ))
+    ));
+}
+
+/// Data of a sound object without properties playing wem `source_id`.
+fn plain_sound(source_id: u32) -> Vec<u8> {
+    use mhws_sound_tool::hirc::{HircLayout, HircObject, NodeParams, SoundObject, SourceData};
+
+    HircObject::Sound(SoundObject {
+        source: SourceData {
+            plugin_id: 0x00040001,
+            stream_type: 0,
+            source_id,
+            media_size: 0,
+            source_bits: 0,
+            plugin_params: None,
+        },
+        node: NodeParams {
+            override_parent_fx: 0,
+            fx_bypass: None,
+            fx: vec![],
+            override_parent_metadata: 0,
+            metadata: vec![],
+            override_attachment_params: 0,
+            override_bus_id: 0,
+            direct_parent_id: 0,
+            bit_vector: 0,
+            props: vec![],
+        },
+        rest: vec![0; 4],
+    })
+    .to_bytes(HircLayout::default())
+}
+
+#[test]
+fn props_edited_on_repack() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    BankBuilder::new(1001)
+        .wem(500001, fake_wem(1, 1000))
+        .wem(500002, fake_wem(2, 37))
+        .hirc(2, 700001, plain_sound(500001))
+        .hirc(2, 700002, plain_sound(500002))
+        .write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    fs::write(
+        project_path.join("project.toml"),
+        "[[props]]\nsource = 500001\nvolume = -6.0\n\n[[props]]\nobject = 700001\nvolume = -1.5\npitch = 200\n\n[[props]]\nobject = 700002\nloop = true\n",
+    )
+    .unwrap();
+
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    let repacked = common::dump(&output, &dir.path().join("check"));
+    let metadata = common::metadata(&repacked, "bank.json");
+    let entries = metadata["sections"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|section| section["type"] == "Hirc")
+        .and_then(|section| section["content"]["entries"].as_array())
+        .unwrap();
+    assert_eq!(
+        entries[0]["object"]["node"]["props"],
+        serde_json::json!([
+            { "id": 0, "value": (-7.5f32).to_bits() },
+            { "id": 2, "value": 200f32.to_bits() },
+        ])
+    );
+    assert_eq!(
+        entries[1]["object"]["node"]["props"],
+        serde_json::json!([{ "id": 0x3A, "value": 0 }])
+    );
+    // media is untouched
+    let wems = common::dumped_entries(&repacked, "wem");
+    assert_eq!(wems[&0], (500001, fake_wem(1, 1000)));
+}
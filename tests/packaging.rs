@@ -0,0 +1,316 @@
+mod common;
+
+use std::fs;
+
+use common::{fake_wem, sample_bank, sample_pck};
+
+#[test]
+fn repack_creates_missing_output_root() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+
+    let output_root = dir.path().join("out").join("nested");
+    let stats = mhws_sound_tool::repack(&project_path, &output_root).unwrap();
+    assert_eq!(stats.output_path, output_root.join("Synth.sbnk.1.X64"));
+    assert_eq!(
+        fs::read(stats.output_path).unwrap(),
+        fs::read(&input).unwrap()
+    );
+}
+
+#[test]
+fn modinfo_written_with_output() {
+    use mhws_sound_tool::{
+        hashes::{HASHES_FILE_NAME, HashList, HashStatus},
+        modinfo::ModInfo,
+    };
+
+    let dir = tempfile::tempdir().unwrap();
+    let bnk_input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&bnk_input);
+    let pck_input = dir.path().join("Synth.spck.1.X64");
+    sample_pck(16).write(&pck_input);
+
+    for input in [&bnk_input, &pck_input] {
+        let project = mhws_sound_tool::dump(input, dir.path().join("work")).unwrap();
+        fs::write(
+            project.project_path().join("project.toml"),
+            "[modinfo]\nname = \"Synth Mod\"\nversion = \"1.0\"\nembed = \"section\"\nhashes = true\n",
+        )
+        .unwrap();
+        let stats =
+            mhws_sound_tool::repack(project.project_path(), dir.path().join("out")).unwrap();
+        let modinfo = ModInfo::read(&stats.output_path).unwrap().unwrap();
+        assert_eq!(modinfo.name, "Synth Mod");
+        assert_eq!(modinfo.version.as_deref(), Some("1.0"));
+        assert_eq!(modinfo.tool_version, env!("CARGO_PKG_VERSION"));
+        // BNK output carries a custom section, PCK output a sidecar
+        let sidecar = ModInfo::sidecar_path(&stats.output_path);
+        assert_eq!(sidecar.is_file(), input == &pck_input);
+    }
+
+    // both outputs and the PCK sidecar are recorded
+    let hashes = HashList::load(dir.path().join("out").join(HASHES_FILE_NAME)).unwrap();
+    let checks = hashes.verify(dir.path().join("out")).unwrap();
+    assert_eq!(checks.len(), 3);
+    assert!(checks.iter().all(|check| check.status == HashStatus::Ok));
+}
+
+#[test]
+fn output_policy_overwrite_and_suffix() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+    let original = fs::read(&input).unwrap();
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    fs::write(
+        project_path.join("[000]500001.wem"),
+        common::fake_wem(7, 300),
+    )
+    .unwrap();
+    let package = |flags: &[&str]| {
+        let mut args = vec![
+            "package-project".as_ref(),
+            "-i".as_ref(),
+            project_path.as_os_str(),
+            "-o".as_ref(),
+            dir.path().as_os_str(),
+        ];
+        args.extend(flags.iter().map(std::ffi::OsStr::new));
+        common::run_tool(args);
+    };
+
+    // the backup keeps the original bundle over repeated overwrites
+    package(&["--overwrite"]);
+    package(&["--overwrite"]);
+    let backup = dir.path().join("Synth.sbnk.1.X64.bak");
+    assert!(fs::read(&backup).unwrap() == original);
+    let repacked = fs::read(&input).unwrap();
+    assert!(repacked != original);
+
+    package(&["--suffix", ".mod"]);
+    package(&["--suffix", ".mod"]);
+    assert!(fs::read(dir.path().join("Synth.sbnk.1.X64.mod")).unwrap() == repacked);
+    let mut names: Vec<String> = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .filter(|name| name.starts_with("Synth.sbnk"))
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "Synth.sbnk.1.X64",
+            "Synth.sbnk.1.X64.bak",
+            "Synth.sbnk.1.X64.mod"
+        ]
+    );
+}
+
+#[test]
+fn package_deploys_to_natives_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let natives_dir = dir.path().join("game/natives/STM/Sound/Wwise");
+    fs::create_dir_all(&natives_dir).unwrap();
+    let input = natives_dir.join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+
+    let mod_dir = dir.path().join("mods/Synth Mod");
+    common::run_tool([
+        "package-project".as_ref(),
+        "-i".as_ref(),
+        project_path.as_os_str(),
+        "-o".as_ref(),
+        dir.path().join("out").as_os_str(),
+        "--deploy".as_ref(),
+        mod_dir.as_os_str(),
+    ]);
+    let deployed = mod_dir.join("natives/STM/Sound/Wwise/Synth.sbnk.1.X64");
+    assert!(fs::read(deployed).unwrap() == fs::read(&input).unwrap());
+}
+
+#[test]
+fn output_timestamp_from_manifest() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+    let original_time = UNIX_EPOCH + Duration::from_secs(1700000000);
+    fs::File::options()
+        .write(true)
+        .open(&input)
+        .unwrap()
+        .set_modified(original_time)
+        .unwrap();
+    let project = mhws_sound_tool::dump(&input, dir.path().join("work")).unwrap();
+    let modified = |path: &std::path::Path| fs::metadata(path).unwrap().modified().unwrap();
+
+    for (timestamp, expected) in [
+        ("original", original_time),
+        (
+            "2025-01-01T00:00:00Z",
+            UNIX_EPOCH + Duration::from_secs(1735689600),
+        ),
+    ] {
+        fs::write(
+            project.project_path().join("project.toml"),
+            format!("timestamp = \"{}\"\n", timestamp),
+        )
+        .unwrap();
+        let stats =
+            mhws_sound_tool::repack(project.project_path(), dir.path().join("out")).unwrap();
+        assert_eq!(modified(&stats.output_path), expected);
+    }
+}
+
+#[test]
+fn history_restores_earlier_build() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let out_dir = dir.path().join("out");
+    // history is off by default
+    common::run_tool_in(
+        dir.path(),
+        [
+            "package-project".as_ref(),
+            "-i".as_ref(),
+            project_path.as_os_str(),
+            "-o".as_ref(),
+            out_dir.as_os_str(),
+        ],
+    );
+    assert!(!dir.path().join("history").exists());
+    fs::write(
+        dir.path().join("config.toml"),
+        "version = 1\n[history]\nkeep = 10\n",
+    )
+    .unwrap();
+    let package = || {
+        common::run_tool_in(
+            dir.path(),
+            [
+                "package-project".as_ref(),
+                "-i".as_ref(),
+                project_path.as_os_str(),
+                "-o".as_ref(),
+                out_dir.as_os_str(),
+                "--overwrite".as_ref(),
+            ],
+        )
+    };
+    package();
+    let output = out_dir.join("Synth.sbnk.1.X64");
+    let first = fs::read(&output).unwrap();
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    fs::write(replace_root.join("500001.wem"), fake_wem(10, 333)).unwrap();
+    package();
+    assert!(fs::read(&output).unwrap() != first);
+
+    let list = common::tool_output(
+        dir.path(),
+        [
+            "history".as_ref(),
+            "list".as_ref(),
+            "-i".as_ref(),
+            project_path.as_os_str(),
+            "--json".as_ref(),
+        ],
+    );
+    assert!(list.status.success());
+    let list: serde_json::Value = serde_json::from_slice(&list.stdout).unwrap();
+    let builds = list[0]["builds"].as_array().unwrap();
+    assert_eq!(builds.len(), 2);
+    assert_eq!(builds[0]["id"], 1);
+    assert!(dir.path().join("history").is_dir());
+
+    common::run_tool_in(
+        dir.path(),
+        [
+            "history".as_ref(),
+            "restore".as_ref(),
+            "-i".as_ref(),
+            project_path.as_os_str(),
+            "--build".as_ref(),
+            "1".as_ref(),
+        ],
+    );
+    assert!(fs::read(&output).unwrap() == first);
+}
+
+#[test]
+fn vanilla_overwrite_needs_permission() {
+    let dir = tempfile::tempdir().unwrap();
+    let game_dir = dir.path().join("MonsterHunterWilds");
+    let sound_dir = game_dir.join("natives/STM/Sound/Wwise");
+    fs::create_dir_all(&sound_dir).unwrap();
+    fs::write(game_dir.join("re_chunk_000.pak"), b"").unwrap();
+    let input = sound_dir.join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+    let original = fs::read(&input).unwrap();
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    fs::write(replace_root.join("500001.wem"), fake_wem(10, 333)).unwrap();
+
+    let package = |allow: bool| {
+        let mut args = vec![
+            "package-project".as_ref(),
+            "-i".as_ref(),
+            project_path.as_os_str(),
+            "-o".as_ref(),
+            sound_dir.as_os_str(),
+            "--overwrite".as_ref(),
+        ];
+        if allow {
+            args.push("--allow-vanilla-overwrite".as_ref());
+        }
+        common::tool_output(dir.path(), args)
+    };
+    let output = package(false);
+    assert_eq!(output.status.code(), Some(9));
+    assert_eq!(fs::read(&input).unwrap(), original);
+
+    assert!(package(true).status.success());
+    assert_ne!(fs::read(&input).unwrap(), original);
+    let backup =
+        game_dir.join(".mhws-sound-tool/vanilla-backups/natives/STM/Sound/Wwise/Synth.sbnk.1.X64");
+    assert_eq!(fs::read(&backup).unwrap(), original);
+
+    // written by the tool, replaced again without permission
+    fs::write(replace_root.join("500001.wem"), fake_wem(11, 333)).unwrap();
+    assert!(package(false).status.success());
+    assert_eq!(fs::read(&backup).unwrap(), original);
+}
+
+#[test]
+fn repack_stats_never_compare_output_with_itself() {
+    use mhws_sound_tool::project::SoundToolProject;
+
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    fs::write(project_path.join("project.toml"), "remove = [500002]\n").unwrap();
+    let project = SoundToolProject::from_path(&project_path).unwrap();
+
+    // nothing to compare with in the empty output root but the output itself
+    let stats = project.repack(dir.path().join("out")).unwrap();
+    assert_eq!(stats.removed, None);
+    assert_eq!(stats.added, None);
+    assert_eq!(
+        stats.original_size,
+        Some(fs::metadata(&input).unwrap().len())
+    );
+
+    fs::copy(&input, dir.path().join("work").join("Synth.sbnk.1.X64")).unwrap();
+    let stats = project.repack(dir.path().join("out2")).unwrap();
+    assert_eq!(stats.removed, Some(1));
+    assert_eq!(stats.added, Some(0));
+}
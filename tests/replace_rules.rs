@@ -0,0 +1,195 @@
+mod common;
+
+use std::fs;
+
+use common::{BankBuilder, fake_wem, sample_bank, sample_pck};
+
+#[test]
+fn replace_subfolders_group_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let original = common::dumped_entries(&project_path, "wem");
+    let replace_root = project_path.join("replace");
+    for sub_dir in ["footsteps/left", ".backup", "z"] {
+        fs::create_dir_all(replace_root.join(sub_dir)).unwrap();
+    }
+    fs::write(
+        replace_root.join("footsteps/left/500001.wem"),
+        fake_wem(10, 333),
+    )
+    .unwrap();
+    // hidden folders are skipped
+    fs::write(replace_root.join(".backup/500002.wem"), fake_wem(11, 444)).unwrap();
+    // of files with the same target, the first in path order is used
+    fs::write(replace_root.join("[2].wem"), fake_wem(12, 555)).unwrap();
+    fs::write(replace_root.join("z/500003.wem"), fake_wem(13, 666)).unwrap();
+    // junk and non-audio files are skipped
+    fs::write(replace_root.join("Thumbs.db"), b"junk").unwrap();
+    fs::write(replace_root.join(".DS_Store"), b"junk").unwrap();
+    fs::write(replace_root.join("footsteps/notes.txt"), b"louder").unwrap();
+    fs::write(replace_root.join("500002.psd"), b"8BPS").unwrap();
+    let output = common::repack(&project_path, &dir.path().join("out"));
+
+    let redumped =
+        common::dumped_entries(&common::dump(&output, &dir.path().join("redump")), "wem");
+    assert_eq!(redumped[&0], (500001, fake_wem(10, 333)));
+    assert_eq!(redumped[&1], original[&1]);
+    assert_eq!(redumped[&2], (500003, fake_wem(12, 555)));
+}
+
+#[test]
+fn idmap_retargets_replace_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let work = dir.path().join("work");
+    fs::create_dir_all(&work).unwrap();
+    // replace files were made for a parallel bank with other IDs
+    BankBuilder::new(1002)
+        .wem(700001, fake_wem(21, 100))
+        .wem(700002, fake_wem(22, 100))
+        .wem(700003, fake_wem(23, 100))
+        .write(work.join("Other.sbnk.1.X64"));
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+
+    let project_path = common::dump(&input, &work);
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    fs::write(replace_root.join("700002.wem"), fake_wem(30, 200)).unwrap();
+    fs::write(replace_root.join("700003.wem"), fake_wem(31, 300)).unwrap();
+    fs::write(
+        project_path.join("idmap.toml"),
+        "from_bundle = \"Other.sbnk.1.X64\"\nalign = \"index\"\n\n[ids]\n700003 = 500001\n",
+    )
+    .unwrap();
+    // overrides of the old ID follow the entry
+    fs::write(
+        project_path.join("project.toml"),
+        "[[replace]]\ntarget = 700003\nsize_policy = \"pad\"\n",
+    )
+    .unwrap();
+    let output = common::repack(&project_path, &dir.path().join("out"));
+
+    let redumped_path = common::dump(&output, &dir.path().join("redump"));
+    let redumped = common::dumped_entries(&redumped_path, "wem");
+    let mut padded = fake_wem(31, 300);
+    padded.resize(1000, 0);
+    assert_eq!(redumped[&0], (500001, padded));
+    assert_eq!(redumped[&1], (500002, fake_wem(30, 200)));
+    assert_eq!(redumped[&2], (500003, fake_wem(3, 4096)));
+}
+
+#[test]
+fn global_rules_apply_to_every_bundle_on_request() {
+    let dir = tempfile::tempdir().unwrap();
+    // user folder with config, data packs and rules
+    let home = dir.path().join("home");
+    fs::create_dir_all(home.join("datapacks")).unwrap();
+    fs::write(
+        home.join("datapacks").join("synth.json"),
+        r#"{"name": "synth", "ids": {"500002": "Play_Heartbeat_Loop"}}"#,
+    )
+    .unwrap();
+    fs::write(home.join("quiet.wem"), fake_wem(30, 80)).unwrap();
+    fs::write(
+        home.join("rules.toml"),
+        r#"
+        [[rule]]
+        name = "*_heartbeat*"
+        action = "remove"
+
+        [[rule]]
+        bundle = "*.spck.*"
+        id = 500003
+        action = "replace"
+        file = "quiet.wem"
+        "#,
+    )
+    .unwrap();
+
+    let bnk_input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&bnk_input);
+    let pck_input = dir.path().join("Synth.spck.1.X64");
+    sample_pck(16).write(&pck_input);
+    for input in [&bnk_input, &pck_input] {
+        let project_path = common::dump(input, &dir.path().join("work"));
+        // rules are opt-in
+        let plain_root = dir.path().join("plain");
+        common::run_tool_in(
+            &home,
+            [
+                "package-project".as_ref(),
+                "-i".as_ref(),
+                project_path.as_os_str(),
+                "-o".as_ref(),
+                plain_root.as_os_str(),
+            ],
+        );
+        let plain = plain_root.join(input.file_name().unwrap());
+        assert_eq!(fs::read(plain).unwrap(), fs::read(input).unwrap());
+
+        let output_root = dir.path().join("out");
+        common::run_tool_in(
+            &home,
+            [
+                "package-project".as_ref(),
+                "-i".as_ref(),
+                project_path.as_os_str(),
+                "-o".as_ref(),
+                output_root.as_os_str(),
+                "--rules".as_ref(),
+            ],
+        );
+        let output = output_root.join(input.file_name().unwrap());
+        let redumped =
+            common::dumped_entries(&common::dump(&output, &dir.path().join("redump")), "wem");
+        assert_eq!(redumped.len(), 2);
+        assert_eq!(redumped[&0], (500001, fake_wem(1, 1000)));
+        let expected = if input == &pck_input {
+            fake_wem(30, 80)
+        } else {
+            fake_wem(3, 4096)
+        };
+        assert_eq!(redumped[&1], (500003, expected));
+    }
+}
+
+#[test]
+fn renumbered_files_fail_order_check() {
+    use mhws_sound_tool::{order::OrderError, project::ProjectError};
+
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+
+    // a second copy of an entry is ambiguous
+    fs::copy(
+        project_path.join("[001]500002.wem"),
+        project_path.join("[001]500002_copy.wem"),
+    )
+    .unwrap();
+    assert!(matches!(
+        mhws_sound_tool::repack(&project_path, dir.path().join("out")),
+        Err(ProjectError::Order(OrderError::Duplicate { idx: 1, .. }))
+    ));
+    fs::remove_file(project_path.join("[001]500002_copy.wem")).unwrap();
+
+    // index of a file no longer matches the bundle
+    fs::remove_file(project_path.join("[002]500003.wem")).unwrap();
+    fs::rename(
+        project_path.join("[001]500002.wem"),
+        project_path.join("[002]500002.wem"),
+    )
+    .unwrap();
+    assert!(matches!(
+        mhws_sound_tool::repack(&project_path, dir.path().join("out")),
+        Err(ProjectError::Order(OrderError::Mismatch {
+            idx: 2,
+            expected: Some(500003),
+            ..
+        }))
+    ));
+}
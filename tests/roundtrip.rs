@@ -0,0 +1,796 @@
+mod common;
+
+use std::fs;
+
+use common::{BankBuilder, PckBuilder, fake_wem, sample_bank, sample_pck};
+
+#[test]
+fn bnk_unchanged_is_byte_identical() {
+    for alignment in [1, 4, 16] {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("Synth.sbnk.1.X64");
+        sample_bank(alignment).write(&input);
+
+        let project_path = common::dump(&input, &dir.path().join("work"));
+        // source alignment is kept by default
+        let output = common::repack(&project_path, &dir.path().join("out"));
+        assert!(
+            fs::read(&output).unwrap() == fs::read(&input).unwrap(),
+            "alignment {} output differs",
+            alignment
+        );
+
+        fs::write(
+            project_path.join("project.toml"),
+            format!("data_alignment = {}", alignment),
+        )
+        .unwrap();
+        let output = common::repack(&project_path, &dir.path().join("out-explicit"));
+        assert!(
+            fs::read(&output).unwrap() == fs::read(&input).unwrap(),
+            "explicit alignment {} output differs",
+            alignment
+        );
+    }
+}
+
+#[test]
+fn bnk_replace_roundtrip() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).language(1).write(&input);
+
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let original = common::dumped_entries(&project_path, "wem");
+    assert_eq!(original.len(), 3);
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    fs::write(replace_root.join("500001.wem"), fake_wem(10, 333)).unwrap();
+    fs::write(replace_root.join("[2].wem"), fake_wem(11, 5000)).unwrap();
+    let output = common::repack(&project_path, &dir.path().join("out"));
+
+    let redumped_path = common::dump(&output, &dir.path().join("redump"));
+    let redumped = common::dumped_entries(&redumped_path, "wem");
+    assert_eq!(redumped[&0], (500001, fake_wem(10, 333)));
+    assert_eq!(redumped[&1], original[&1]);
+    assert_eq!(redumped[&2], (500003, fake_wem(11, 5000)));
+    // everything except media is kept
+    let metadata = common::metadata(&project_path, "bank.json");
+    assert_eq!(metadata, common::metadata(&redumped_path, "bank.json"));
+    let sections = metadata["sections"].as_array().unwrap();
+    let stid = sections.iter().find(|s| s["type"] == "Stid").unwrap();
+    assert_eq!(
+        stid["content"]["entries"][0],
+        serde_json::json!({ "id": 1001, "bank_name": "Test" })
+    );
+}
+
+#[test]
+fn bnk_duplicate_ids_replaced_by_index() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    BankBuilder::new(1001)
+        .wem(500001, fake_wem(1, 100))
+        .wem(500002, fake_wem(2, 200))
+        .wem(500002, fake_wem(3, 300))
+        .wem(500003, fake_wem(4, 400))
+        .write(&input);
+
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let original = common::dumped_entries(&project_path, "wem");
+    assert_eq!(original.len(), 4);
+    assert_eq!(original[&2], (500002, fake_wem(3, 300)));
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    assert!(fs::read(&output).unwrap() == fs::read(&input).unwrap());
+
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    fs::write(replace_root.join("[2].wem"), fake_wem(10, 333)).unwrap();
+    let output = common::repack(&project_path, &dir.path().join("replaced"));
+    let redumped =
+        common::dumped_entries(&common::dump(&output, &dir.path().join("redump")), "wem");
+    assert_eq!(redumped.len(), 4);
+    assert_eq!(redumped[&1], original[&1]);
+    assert_eq!(redumped[&2], (500002, fake_wem(10, 333)));
+    assert_eq!(redumped[&3], original[&3]);
+}
+
+#[test]
+fn pck_duplicate_ids_replaced_and_removed_by_index() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.spck.1.X64");
+    PckBuilder::new()
+        .language(1, "english")
+        .language(2, "japanese")
+        .wem(500001, 0, fake_wem(1, 100))
+        .wem(500002, 1, fake_wem(2, 200))
+        .wem(500002, 2, fake_wem(3, 300))
+        .wem(500003, 0, fake_wem(4, 400))
+        .write(&input);
+
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let original = common::dumped_entries(&project_path, "wem");
+    assert_eq!(original[&2], (500002, fake_wem(3, 300)));
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    assert!(fs::read(&output).unwrap() == fs::read(&input).unwrap());
+
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    fs::write(replace_root.join("[2].wem"), fake_wem(10, 333)).unwrap();
+    fs::write(project_path.join("project.toml"), "remove = [\"[1]\"]\n").unwrap();
+    let output = common::repack(&project_path, &dir.path().join("replaced"));
+    let redumped =
+        common::dumped_entries(&common::dump(&output, &dir.path().join("redump")), "wem");
+    assert_eq!(redumped.len(), 3);
+    assert_eq!(redumped[&0], original[&0]);
+    assert_eq!(redumped[&1], (500002, fake_wem(10, 333)));
+    assert_eq!(redumped[&2], original[&3]);
+}
+
+#[test]
+fn media_entries_keep_languages_of_repeated_ids() {
+    use mhws_sound_tool::project::SoundToolProject;
+
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.spck.1.X64");
+    PckBuilder::new()
+        .language(1, "english")
+        .language(2, "japanese")
+        .wem(500002, 1, fake_wem(1, 100))
+        .wem(500002, 2, fake_wem(2, 100))
+        .write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let project = SoundToolProject::from_path(&project_path).unwrap();
+    let languages: Vec<_> = project
+        .media_entries()
+        .unwrap()
+        .into_iter()
+        .map(|entry| (entry.id, entry.language))
+        .collect();
+    assert_eq!(
+        languages,
+        [
+            (500002, Some("english".to_string())),
+            (500002, Some("japanese".to_string()))
+        ]
+    );
+}
+
+#[test]
+fn bnk_size_policy_pads_to_original() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    fs::write(project_path.join("project.toml"), "size_policy = \"pad\"\n").unwrap();
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    fs::write(replace_root.join("500001.wem"), fake_wem(10, 333)).unwrap();
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    assert_eq!(
+        fs::metadata(&output).unwrap().len(),
+        fs::metadata(&input).unwrap().len()
+    );
+
+    let redumped_path = common::dump(&output, &dir.path().join("redump"));
+    let (id, data) = &common::dumped_entries(&redumped_path, "wem")[&0];
+    assert_eq!(*id, 500001);
+    assert_eq!(data.len(), 1000);
+    assert_eq!(data[..333], fake_wem(10, 333)[..]);
+    assert!(data[333..].iter().all(|&b| b == 0));
+}
+
+#[test]
+fn pck_unchanged_is_byte_identical() {
+    for block_size in [1, 16] {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("Synth.spck.1.X64");
+        sample_pck(block_size).write(&input);
+
+        let project_path = common::dump(&input, &dir.path().join("work"));
+        let output = common::repack(&project_path, &dir.path().join("out"));
+        assert!(
+            fs::read(&output).unwrap() == fs::read(&input).unwrap(),
+            "block size {} output differs",
+            block_size
+        );
+    }
+}
+
+#[test]
+fn pck_replace_roundtrip() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.spck.1.X64");
+    sample_pck(16).write(&input);
+
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let original = common::dumped_entries(&project_path, "wem");
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    fs::write(replace_root.join("500002.wem"), fake_wem(20, 12345)).unwrap();
+    let output = common::repack(&project_path, &dir.path().join("out"));
+
+    let redumped_path = common::dump(&output, &dir.path().join("redump"));
+    let redumped = common::dumped_entries(&redumped_path, "wem");
+    assert_eq!(redumped[&0], original[&0]);
+    assert_eq!(redumped[&1], (500002, fake_wem(20, 12345)));
+    assert_eq!(redumped[&2], original[&2]);
+    assert_eq!(
+        common::dumped_entries(&redumped_path, "bnk"),
+        common::dumped_entries(&project_path, "bnk")
+    );
+    // languages and entry order are kept, only data placement changes
+    let mut original_meta = common::metadata(&project_path, "pck.json");
+    let mut redumped_meta = common::metadata(&redumped_path, "pck.json");
+    for meta in [&mut original_meta, &mut redumped_meta] {
+        common::strip_fields(meta, &["header_length", "offset", "length"]);
+    }
+    assert_eq!(original_meta, redumped_meta);
+}
+
+#[test]
+fn media_store_roundtrip() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.spck.1.X64");
+    sample_pck(1).write(&input);
+
+    let work = dir.path().join("work");
+    common::run_tool([
+        "unpack-bundle".as_ref(),
+        "-i".as_ref(),
+        input.as_os_str(),
+        "-o".as_ref(),
+        work.as_os_str(),
+        "--store".as_ref(),
+    ]);
+    let project_path = work.join("Synth.spck.1.X64.project");
+    assert!(common::dumped_entries(&project_path, "wem").is_empty());
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    assert!(fs::read(&output).unwrap() == fs::read(&input).unwrap());
+}
+
+#[test]
+fn pck_replace_updates_entry_sizes() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.spck.1.X64");
+    sample_pck(16).write(&input);
+
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    // one smaller and one larger than the original entry
+    fs::write(replace_root.join("500001.wem"), fake_wem(21, 10)).unwrap();
+    fs::write(replace_root.join("500002.wem"), fake_wem(22, 9000)).unwrap();
+    let output = common::repack(&project_path, &dir.path().join("out"));
+
+    let sizes: Vec<(u32, u32)> = mhws_sound_tool::project::list_entries(&output)
+        .unwrap()
+        .into_iter()
+        .filter(|entry| entry.kind == "wem")
+        .map(|entry| (entry.id, entry.size))
+        .collect();
+    assert_eq!(
+        sizes,
+        [
+            (500001, fake_wem(21, 10).len() as u32),
+            (500002, fake_wem(22, 9000).len() as u32),
+            (500003, fake_wem(3, 4096).len() as u32),
+        ]
+    );
+}
+
+#[test]
+fn pck_shares_duplicate_data() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.spck.1.X64");
+    // same voice line in every language
+    PckBuilder::new()
+        .language(1, "english")
+        .language(2, "japanese")
+        .block_size(16)
+        .wem(500001, 1, fake_wem(1, 5000))
+        .wem(500002, 2, fake_wem(1, 5000))
+        .wem(500003, 0, fake_wem(3, 700))
+        .write(&input);
+
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    fs::write(replace_root.join("500001.wem"), fake_wem(40, 3000)).unwrap();
+    fs::write(replace_root.join("500002.wem"), fake_wem(40, 3000)).unwrap();
+    let plain = common::repack(&project_path, &dir.path().join("plain"));
+    fs::write(project_path.join("project.toml"), "share_duplicates = true").unwrap();
+    let shared = common::repack(&project_path, &dir.path().join("shared"));
+    assert_eq!(
+        fs::metadata(&plain).unwrap().len() - fs::metadata(&shared).unwrap().len(),
+        3008
+    );
+
+    let redumped_path = common::dump(&shared, &dir.path().join("redump"));
+    let redumped = common::dumped_entries(&redumped_path, "wem");
+    assert_eq!(redumped[&0], (500001, fake_wem(40, 3000)));
+    assert_eq!(redumped[&1], (500002, fake_wem(40, 3000)));
+    assert_eq!(redumped[&2], (500003, fake_wem(3, 700)));
+}
+
+#[test]
+fn library_dump_repack() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.spck.1.X64");
+    sample_pck(16).write(&input);
+
+    let project = mhws_sound_tool::dump(&input, dir.path().join("work")).unwrap();
+    let stats = mhws_sound_tool::repack(project.project_path(), dir.path().join("out")).unwrap();
+    assert!(fs::read(&stats.output_path).unwrap() == fs::read(&input).unwrap());
+
+    let not_bundle = dir.path().join("readme.txt");
+    fs::write(&not_bundle, "").unwrap();
+    assert!(mhws_sound_tool::dump(&not_bundle, dir.path().join("work")).is_err());
+}
+
+#[test]
+fn meta_only_repack_reads_original_bundle() {
+    for pck in [false, true] {
+        let dir = tempfile::tempdir().unwrap();
+        let input = if pck {
+            let input = dir.path().join("Synth.spck.1.X64");
+            sample_pck(16).write(&input);
+            input
+        } else {
+            let input = dir.path().join("Synth.sbnk.1.X64");
+            sample_bank(16).write(&input);
+            input
+        };
+        // project next to the original bundle
+        common::run_tool([
+            "unpack-bundle".as_ref(),
+            "-i".as_ref(),
+            input.as_os_str(),
+            "--meta-only".as_ref(),
+        ]);
+        let mut project_path = input.clone().into_os_string();
+        project_path.push(".project");
+        let project_path = std::path::PathBuf::from(project_path);
+        assert!(common::dumped_entries(&project_path, "wem").is_empty());
+        assert!(project_path.join("media.json").is_file());
+
+        let output = common::repack(&project_path, &dir.path().join("out"));
+        assert!(fs::read(&output).unwrap() == fs::read(&input).unwrap());
+
+        fs::create_dir_all(project_path.join("replace")).unwrap();
+        fs::write(
+            project_path.join("replace").join("500002.wem"),
+            fake_wem(20, 12345),
+        )
+        .unwrap();
+        fs::remove_file(&output).unwrap();
+        let output = common::repack(&project_path, &dir.path().join("out"));
+        let redumped =
+            common::dumped_entries(&common::dump(&output, &dir.path().join("redump")), "wem");
+        assert_eq!(redumped[&1], (500002, fake_wem(20, 12345)));
+        assert_eq!(redumped[&2].1, fake_wem(3, 4096));
+    }
+}
+
+#[test]
+fn named_dump_is_byte_identical() {
+    for pck in [false, true] {
+        let dir = tempfile::tempdir().unwrap();
+        let input = if pck {
+            let input = dir.path().join("Synth.spck.1.X64");
+            sample_pck(16).write(&input);
+            input
+        } else {
+            let input = dir.path().join("Synth.sbnk.1.X64");
+            sample_bank(16).write(&input);
+            input
+        };
+        let names = dir.path().join("names.txt");
+        fs::write(&names, "500002 Hit\n1001 Synth_Bank\n600001 Play_Hit\n").unwrap();
+        common::run_tool([
+            "unpack-bundle".as_ref(),
+            "-i".as_ref(),
+            input.as_os_str(),
+            "--names".as_ref(),
+            names.as_os_str(),
+        ]);
+        let mut project_path = input.clone().into_os_string();
+        project_path.push(".project");
+        let project_path = std::path::PathBuf::from(project_path);
+        let metadata_file = if pck { "pck.json" } else { "bank.json" };
+        assert!(project_path.join("[001]500002_Hit.wem").is_file());
+        let metadata = fs::read_to_string(project_path.join(metadata_file)).unwrap();
+        assert!(metadata.contains(if pck { "Synth_Bank" } else { "Play_Hit" }));
+
+        let output = common::repack(&project_path, &dir.path().join("out"));
+        assert!(fs::read(&output).unwrap() == fs::read(&input).unwrap());
+    }
+}
+
+#[test]
+fn added_entries_roundtrip() {
+    use mhws_sound_tool::project::ProjectError;
+
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let add_root = project_path.join("add");
+    fs::create_dir_all(&add_root).unwrap();
+    fs::write(add_root.join("500010.wem"), fake_wem(10, 100)).unwrap();
+    fs::write(add_root.join("499999.wem"), fake_wem(11, 200)).unwrap();
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    let redumped =
+        common::dumped_entries(&common::dump(&output, &dir.path().join("redump")), "wem");
+    // IDs stay sorted
+    let ids: Vec<u32> = redumped.values().map(|(id, _)| *id).collect();
+    assert_eq!(ids, [499999, 500001, 500002, 500003, 500010]);
+    assert_eq!(redumped[&0].1, fake_wem(11, 200));
+    assert_eq!(redumped[&4].1, fake_wem(10, 100));
+
+    fs::write(add_root.join("500002.wem"), fake_wem(12, 10)).unwrap();
+    assert!(matches!(
+        mhws_sound_tool::repack(&project_path, dir.path().join("out")),
+        Err(ProjectError::AdditionExists(500002))
+    ));
+
+    let input = dir.path().join("Synth.spck.1.X64");
+    sample_pck(16).write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let add_root = project_path.join("add").join("japanese");
+    fs::create_dir_all(&add_root).unwrap();
+    fs::write(add_root.join("500010.wem"), fake_wem(10, 100)).unwrap();
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    let redumped_path = common::dump(&output, &dir.path().join("redump"));
+    let redumped = common::dumped_entries(&redumped_path, "wem");
+    assert_eq!(redumped[&3], (500010, fake_wem(10, 100)));
+    let metadata = common::metadata(&redumped_path, "pck.json");
+    assert_eq!(metadata["wem_entries"][3]["language_id"], 2);
+
+    fs::rename(&add_root, project_path.join("add").join("klingon")).unwrap();
+    assert!(matches!(
+        mhws_sound_tool::repack(&project_path, dir.path().join("out")),
+        Err(ProjectError::UnknownLanguage(language)) if language == "klingon"
+    ));
+}
+
+#[test]
+fn strip_media_keeps_modified_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+
+    // not next to the bundle, found at the path it was dumped from
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    fs::write(project_path.join("[001]500002.wem"), fake_wem(20, 64)).unwrap();
+    common::run_tool([
+        "strip-media".as_ref(),
+        "-i".as_ref(),
+        project_path.as_os_str(),
+    ]);
+    let loose = common::dumped_entries(&project_path, "wem");
+    assert_eq!(loose.len(), 1);
+    assert_eq!(loose[&1], (500002, fake_wem(20, 64)));
+
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    let redumped =
+        common::dumped_entries(&common::dump(&output, &dir.path().join("redump")), "wem");
+    assert_eq!(redumped[&0], (500001, fake_wem(1, 1000)));
+    assert_eq!(redumped[&1], (500002, fake_wem(20, 64)));
+    assert_eq!(redumped[&2], (500003, fake_wem(3, 4096)));
+}
+
+#[test]
+fn strip_media_skips_bank_without_data() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Events.sbnk.1.X64");
+    BankBuilder::new(1002)
+        .hirc(2, 600001, vec![0; 20])
+        .write(&input);
+
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    common::run_tool([
+        "strip-media".as_ref(),
+        "-i".as_ref(),
+        project_path.as_os_str(),
+    ]);
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    assert_eq!(fs::read(output).unwrap(), fs::read(&input).unwrap());
+}
+
+#[test]
+fn removed_entries_are_dropped() {
+    let dir = tempfile::tempdir().unwrap();
+    let bnk_input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&bnk_input);
+    let pck_input = dir.path().join("Synth.spck.1.X64");
+    sample_pck(16).write(&pck_input);
+
+    for input in [&bnk_input, &pck_input] {
+        let project_path = common::dump(input, &dir.path().join("work"));
+        fs::write(
+            project_path.join("project.toml"),
+            "remove = [500002, \"[2]\"]\n",
+        )
+        .unwrap();
+        let output = common::repack(&project_path, &dir.path().join("out"));
+        let redumped =
+            common::dumped_entries(&common::dump(&output, &dir.path().join("redump")), "wem");
+        assert_eq!(redumped.len(), 1);
+        assert_eq!(redumped[&0], (500001, fake_wem(1, 1000)));
+    }
+}
+
+#[test]
+fn nested_banks_of_pck_roundtrip() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("Synth.spck.1.X64");
+    sample_pck(16).write(&input);
+
+    let dump_nested = |input: &std::path::Path, output_root: &std::path::Path| {
+        common::run_tool([
+            "unpack-bundle".as_ref(),
+            "-i".as_ref(),
+            input.as_os_str(),
+            "-o".as_ref(),
+            output_root.as_os_str(),
+            "--nested".as_ref(),
+        ]);
+        output_root.join("Synth.spck.1.X64.project")
+    };
+    let project_path = dump_nested(&input, &dir.path().join("work"));
+    let nested_path = project_path.join("banks").join("1001.bnk.project");
+    assert!(project_path.join("banks").join("1001.bnk").is_file());
+    assert_eq!(common::dumped_entries(&nested_path, "wem").len(), 3);
+
+    // unchanged nested projects repack to the same bundle
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    assert!(fs::read(&output).unwrap() == fs::read(&input).unwrap());
+
+    let replace_root = nested_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    fs::write(replace_root.join("500002.wem"), fake_wem(40, 50)).unwrap();
+    let output = common::repack(&project_path, &dir.path().join("replaced"));
+    let redumped = dump_nested(&output, &dir.path().join("redump"));
+    let nested = common::dumped_entries(&redumped.join("banks").join("1001.bnk.project"), "wem");
+    assert_eq!(nested[&1], (500002, fake_wem(40, 50)));
+    // streamed wems of the PCK are untouched
+    let streamed = common::dumped_entries(&redumped, "wem");
+    assert_eq!(streamed[&1], (500002, fake_wem(2, 37)));
+
+    // rebased projects keep their nested projects and replace files,
+    // banks are extracted again from the new bundle
+    fs::remove_file(project_path.join("banks").join("1001.bnk")).unwrap();
+    let rebased_root = dir.path().join("rebased");
+    common::run_tool([
+        "rebase".as_ref(),
+        "-i".as_ref(),
+        project_path.as_os_str(),
+        "--new-source".as_ref(),
+        input.as_os_str(),
+        "-o".as_ref(),
+        rebased_root.as_os_str(),
+    ]);
+    let rebased = rebased_root.join("Synth.spck.1.X64.project");
+    let rebased_nested = rebased.join("banks").join("1001.bnk.project");
+    assert!(rebased.join("banks").join("1001.bnk").is_file());
+    assert!(rebased_nested.join("replace").join("500002.wem").is_file());
+    let rebased_output = common::repack(&rebased, &dir.path().join("rebased_out"));
+    assert!(fs::read(&rebased_output).unwrap() == fs::read(&output).unwrap());
+}
+
+#[test]
+fn created_pck_roundtrip() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("NewSounds");
+    fs::create_dir_all(input.join("japanese")).unwrap();
+    fs::write(
+        input.join("pck.toml"),
+        "block_size = 16\n[languages]\nsfx = 0\njapanese = 2\n",
+    )
+    .unwrap();
+    fs::write(input.join("1001.bnk"), sample_bank(16).build()).unwrap();
+    fs::write(input.join("900002.wem"), fake_wem(50, 300)).unwrap();
+    fs::write(input.join("900001.wem"), fake_wem(51, 17)).unwrap();
+    fs::write(input.join("japanese").join("900003.wem"), fake_wem(52, 64)).unwrap();
+    common::run_tool(["create-pck".as_ref(), "-i".as_ref(), input.as_os_str()]);
+
+    let output = dir.path().join("NewSounds.spck.1.X64");
+    let project_path = common::dump(&output, &dir.path().join("work"));
+    let wems = common::dumped_entries(&project_path, "wem");
+    assert_eq!(wems[&0], (900001, fake_wem(51, 17)));
+    assert_eq!(wems[&1], (900002, fake_wem(50, 300)));
+    assert_eq!(wems[&2], (900003, fake_wem(52, 64)));
+    let banks = common::dumped_entries(&project_path, "bnk");
+    assert_eq!(banks[&0], (1001, sample_bank(16).build()));
+    let metadata = common::metadata(&project_path, "pck.json");
+    assert_eq!(metadata["wem_entries"][2]["language_id"], 2);
+
+    // packages written from scratch keep their layout on repack
+    let repacked = common::repack(&project_path, &dir.path().join("out"));
+    assert!(fs::read(&repacked).unwrap() == fs::read(&output).unwrap());
+}
+
+#[test]
+fn lenient_unpack_salvages_truncated_bank() {
+    let dir = tempfile::tempdir().unwrap();
+    let bank = sample_bank(16).build();
+    // cut in the data of the last wem
+    let last = fake_wem(3, 4096);
+    let start = bank
+        .windows(last.len())
+        .position(|window| window == last)
+        .unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    fs::write(&input, &bank[..start + 100]).unwrap();
+
+    let args = |output_root: &std::path::Path| {
+        vec![
+            "unpack-bundle".into(),
+            "-i".into(),
+            input.clone().into_os_string(),
+            "-o".into(),
+            output_root.as_os_str().to_owned(),
+        ]
+    };
+    let output = common::tool_output(dir.path(), args(&dir.path().join("strict")));
+    assert!(!output.status.success());
+
+    let output_root = dir.path().join("lenient");
+    let mut lenient = args(&output_root);
+    lenient.push("--lenient".into());
+    common::run_tool(lenient);
+    let project_path = output_root.join("Synth.sbnk.1.X64.project");
+    let entries = common::dumped_entries(&project_path, "wem");
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[&0], (500001, fake_wem(1, 1000)));
+    assert_eq!(entries[&1], (500002, fake_wem(2, 37)));
+}
+
+#[test]
+fn bnk_without_media_roundtrip() {
+    let dir = tempfile::tempdir().unwrap();
+
+    // a bank without media has no DIDX and DATA, and stays so
+    let input = dir.path().join("Events.bnk");
+    BankBuilder::new(1002)
+        .hirc(4, 600003, vec![2; 12])
+        .write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    assert!(common::dumped_entries(&project_path, "wem").is_empty());
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    assert_eq!(fs::read(&output).unwrap(), fs::read(&input).unwrap());
+
+    // empty sections are dumped, and omitted when packaged
+    let input = dir.path().join("Empty.bnk");
+    BankBuilder::new(1003)
+        .section(b"DIDX", vec![])
+        .section(b"DATA", vec![])
+        .write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    assert!(common::dumped_entries(&project_path, "wem").is_empty());
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    assert_eq!(common::bank_sections(&fs::read(&output).unwrap()), ["BKHD"]);
+
+    // all wem files deleted from a project
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    for entry in fs::read_dir(&project_path).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().unwrap_or_default() == "wem" {
+            fs::remove_file(path).unwrap();
+        }
+    }
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    assert_eq!(
+        common::bank_sections(&fs::read(&output).unwrap()),
+        ["BKHD", "HIRC", "STID"]
+    );
+    let project_path = common::dump(&output, &dir.path().join("redump"));
+    assert!(common::dumped_entries(&project_path, "wem").is_empty());
+}
+
+#[test]
+fn big_endian_bundles_roundtrip() {
+    let dir = tempfile::tempdir().unwrap();
+    let bank = sample_bank(16).big_endian();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    bank.write(&input);
+
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let original = common::dumped_entries(&project_path, "wem");
+    assert_eq!(original[&1], (500002, fake_wem(2, 37)));
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    assert!(fs::read(&output).unwrap() == fs::read(&input).unwrap());
+
+    // replaced entries are written in the same byte order
+    let replace_root = project_path.join("replace");
+    fs::create_dir_all(&replace_root).unwrap();
+    fs::write(replace_root.join("500002.wem"), fake_wem(10, 333)).unwrap();
+    let output = common::repack(&project_path, &dir.path().join("replaced"));
+    assert_eq!(fs::read(&output).unwrap()[8..12], 145u32.to_be_bytes());
+    let redumped =
+        common::dumped_entries(&common::dump(&output, &dir.path().join("redump")), "wem");
+    assert_eq!(redumped[&1], (500002, fake_wem(10, 333)));
+    assert_eq!(redumped[&2], (500003, fake_wem(3, 4096)));
+
+    let input = dir.path().join("Synth.spck.1.X64");
+    PckBuilder::new()
+        .big_endian()
+        .language(1, "english")
+        .block_size(16)
+        .bnk(1001, 0, bank.build())
+        .wem(500001, 0, fake_wem(1, 1000))
+        .wem(500002, 1, fake_wem(2, 37))
+        .write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let original = common::dumped_entries(&project_path, "wem");
+    assert_eq!(original[&1], (500002, fake_wem(2, 37)));
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    assert!(fs::read(&output).unwrap() == fs::read(&input).unwrap());
+}
+
+#[test]
+fn older_bank_versions_roundtrip() {
+    let dir = tempfile::tempdir().unwrap();
+
+    // sound with an effect slot of separate flag bytes and no metadata params
+    let mut sound = vec![];
+    sound.extend_from_slice(&0x0004_0001u32.to_le_bytes());
+    sound.push(0);
+    sound.extend_from_slice(&500001u32.to_le_bytes());
+    sound.extend_from_slice(&37u32.to_le_bytes());
+    sound.push(0);
+    sound.extend_from_slice(&[0, 1, 0, 0]);
+    sound.extend_from_slice(&777u32.to_le_bytes());
+    sound.extend_from_slice(&[1, 1, 0]);
+    sound.extend_from_slice(&0u32.to_le_bytes());
+    sound.extend_from_slice(&600001u32.to_le_bytes());
+    sound.extend_from_slice(&[0, 1, 0]);
+    sound.extend_from_slice(&(-3.0f32).to_bits().to_le_bytes());
+    sound.extend_from_slice(&[0; 6]);
+    // event with a u32 action count
+    let event = [&1u32.to_le_bytes()[..], &700001u32.to_le_bytes()].concat();
+
+    let input = dir.path().join("Old.bnk");
+    BankBuilder::new(1004)
+        .version(120)
+        .wem(500001, fake_wem(1, 37))
+        .hirc(2, 600002, sound)
+        .hirc(4, 600003, event)
+        .write(&input);
+    let project_path = common::dump(&input, &dir.path().join("work"));
+    let metadata = common::metadata(&project_path, "bank.json");
+    let entries = metadata["sections"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|section| section["type"] == "Hirc")
+        .and_then(|section| section["content"]["entries"].as_array())
+        .unwrap();
+    assert_eq!(entries[0]["layout"], "v89");
+    // source data of sounds differs between versions of the layout
+    assert!(entries[0]["object"].is_null());
+    assert!(entries[0]["data"].is_array());
+    assert_eq!(entries[1]["object"]["actions"][0], 700001);
+
+    let output = common::repack(&project_path, &dir.path().join("out"));
+    assert_eq!(fs::read(&output).unwrap(), fs::read(&input).unwrap());
+
+    // no real banks of the layout to check edits against
+    fs::write(
+        project_path.join("project.toml"),
+        "[[props]]\nobject = 600002\nvolume = -3.0\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        mhws_sound_tool::repack(&project_path, dir.path().join("edited")),
+        Err(mhws_sound_tool::project::ProjectError::Props {
+            source: mhws_sound_tool::hirc::HircError::UnverifiedLayout { id: 600002, .. },
+            ..
+        })
+    ));
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use std::fs;
+
+use common::{fake_wem, sample_bank, sample_pck};
+
+#[test]
+fn workspace_packages_with_its_data_packs() {
+    let dir = tempfile::tempdir().unwrap();
+    // rules of the user, names only in the data packs of the workspace
+    let home = dir.path().join("home");
+    fs::create_dir_all(&home).unwrap();
+    fs::write(
+        home.join("rules.toml"),
+        "[[rule]]\nname = \"*_heartbeat*\"\naction = \"remove\"\n",
+    )
+    .unwrap();
+    let workspace = dir.path().join("mod");
+    fs::create_dir_all(workspace.join("packs")).unwrap();
+    fs::write(
+        workspace.join("packs").join("synth.json"),
+        r#"{"name": "synth", "ids": {"500002": "Play_Heartbeat_Loop"}}"#,
+    )
+    .unwrap();
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+    common::dump(&input, &workspace.join("banks"));
+    fs::write(
+        workspace.join("workspace.toml"),
+        "members = [\"banks/*\"]\noutput_root = \"dist\"\ndatapacks = \"packs\"\n",
+    )
+    .unwrap();
+
+    let package = || {
+        common::tool_output(
+            &home,
+            [
+                "package-project".as_ref(),
+                "--workspace".as_ref(),
+                workspace.as_os_str(),
+                "--rules".as_ref(),
+            ],
+        )
+    };
+    assert!(package().status.success());
+    let output = workspace.join("dist/Synth.sbnk.1.X64");
+    let redumped =
+        common::dumped_entries(&common::dump(&output, &dir.path().join("redump")), "wem");
+    assert_eq!(redumped.len(), 2);
+    assert!(redumped.values().all(|(id, _)| *id != 500002));
+
+    // another bundle of the same name would overwrite the output
+    fs::remove_dir_all(workspace.join("dist")).unwrap();
+    common::dump(&input, &workspace.join("more"));
+    fs::write(
+        workspace.join("workspace.toml"),
+        "members = [\"banks/*\", \"more/*\"]\noutput_root = \"dist\"\n",
+    )
+    .unwrap();
+    let output = package();
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Synth.sbnk.1.X64"));
+    assert!(!workspace.join("dist").exists());
+}
+
+#[test]
+fn workspace_commands_cover_all_members() {
+    let dir = tempfile::tempdir().unwrap();
+    let workspace = dir.path().join("mod");
+    let input = dir.path().join("Synth.sbnk.1.X64");
+    sample_bank(16).write(&input);
+    common::dump(&input, &workspace.join("banks"));
+    let input = dir.path().join("Stream.spck.1.X64");
+    sample_pck(16).write(&input);
+    let project_path = common::dump(&input, &workspace.join("streams"));
+    fs::create_dir_all(project_path.join("replace")).unwrap();
+    fs::write(project_path.join("replace/[0].wem"), fake_wem(10, 333)).unwrap();
+    fs::write(
+        workspace.join("workspace.toml"),
+        "members = [\"banks/*\", \"streams/Stream.spck.1.X64.project\"]\noutput_root = \"dist\"\n",
+    )
+    .unwrap();
+    // builds are counted from history
+    fs::write(
+        dir.path().join("config.toml"),
+        "version = 1\n[history]\nkeep = 10\n",
+    )
+    .unwrap();
+
+    common::run_tool_in(
+        dir.path(),
+        [
+            "package-project".as_ref(),
+            "--workspace".as_ref(),
+            workspace.as_os_str(),
+        ],
+    );
+    assert!(workspace.join("dist/Synth.sbnk.1.X64").is_file());
+    assert!(workspace.join("dist/Stream.spck.1.X64").is_file());
+
+    common::run_tool_in(
+        dir.path(),
+        [
+            "validate".as_ref(),
+            "--workspace".as_ref(),
+            workspace.as_os_str(),
+        ],
+    );
+
+    let output = common::tool_output(
+        dir.path(),
+        [
+            "stats".as_ref(),
+            "--workspace".as_ref(),
+            workspace.join("workspace.toml").as_os_str(),
+            "--json".as_ref(),
+        ],
+    );
+    assert!(output.status.success());
+    let stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stats[0]["name"], "banks/Synth.sbnk.1.X64.project");
+    assert_eq!(stats[0]["replacements"], 0);
+    assert_eq!(stats[0]["last_build"], 1);
+    assert_eq!(stats[1]["name"], "streams/Stream.spck.1.X64.project");
+    assert_eq!(stats[1]["replacements"], 1);
+
+    // a missing member is an input failure
+    fs::write(
+        workspace.join("workspace.toml"),
+        "members = [\"missing\"]\n",
+    )
+    .unwrap();
+    let output = common::tool_output(
+        dir.path(),
+        [
+            "validate".as_ref(),
+            "--workspace".as_ref(),
+            workspace.as_os_str(),
+        ],
+    );
+    assert_eq!(output.status.code(), Some(3));
+}